@@ -0,0 +1,37 @@
+#version 140
+
+in  vec2 coords;
+out vec4 color;
+
+// 512 by 2: the spectrum in the bottom row, and the
+// waveform in the top row, both in red
+uniform sampler2D u_texture_0;
+
+#define BARS 64.
+
+float spectrum(float x) {
+    return texture(u_texture_0, vec2(x, 0.25)).r;
+}
+
+void main() {
+    // each bar is as tall as the middle of its band
+    float bar = floor(coords.x * BARS) / BARS;
+    float level = spectrum(bar + 0.5 / BARS);
+    float gap = step(0.15, fract(coords.x * BARS));
+    float lit = step(coords.y, level) * gap;
+
+    // the bass is the first few bins of the spectrum
+    float bass = 0.;
+    for (int i = 0; i < 8; i++) {
+        bass += spectrum((float(i) + 0.5) / 512.);
+    }
+    bass /= 8.;
+
+    // the waveform, as a thin line across the middle
+    float wave = texture(u_texture_0, vec2(coords.x, 0.75)).r;
+    float line = smoothstep(0.01, 0., abs(coords.y - wave));
+
+    vec3 hue = 0.5 + 0.5 * cos(6.28 * bar + vec3(0., 2., 4.));
+    vec3 background = vec3(0.05, 0.02, 0.1) * (1. + 4. * bass * bass);
+    color = vec4(mix(background, hue, lit) + line, 1.);
+}
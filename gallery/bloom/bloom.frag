@@ -0,0 +1,28 @@
+#version 140
+
+in  vec2 coords;
+out vec4 color;
+
+uniform sampler2D u_texture_0;
+
+// every level of the pyramid, largest first
+uniform sampler2D u_level_0;
+uniform sampler2D u_level_1;
+uniform sampler2D u_level_2;
+uniform sampler2D u_level_3;
+uniform sampler2D u_level_4;
+
+#define STRENGTH 0.6
+
+void main() {
+    vec3 scene = texture(u_texture_0, coords).rgb;
+    vec3 glow = texture(u_level_0, coords).rgb
+              + texture(u_level_1, coords).rgb
+              + texture(u_level_2, coords).rgb
+              + texture(u_level_3, coords).rgb
+              + texture(u_level_4, coords).rgb;
+    vec3 lit = scene + glow * STRENGTH;
+
+    // keep the brightest lights from clipping to white
+    color = vec4(lit / (1. + lit), 1.);
+}
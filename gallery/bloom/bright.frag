@@ -0,0 +1,14 @@
+#version 140
+
+in  vec2 coords;
+out vec4 color;
+
+uniform sampler2D u_texture_0;
+
+#define THRESHOLD 0.8
+
+void main() {
+    vec3 scene = texture(u_texture_0, coords).rgb;
+    float brightness = max(scene.r, max(scene.g, scene.b));
+    color = vec4(scene * step(THRESHOLD, brightness), 1.);
+}
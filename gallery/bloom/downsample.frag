@@ -0,0 +1,21 @@
+#version 140
+
+in  vec2 coords;
+out vec4 color;
+
+// the level before, about twice the size of this one
+uniform sampler2D u_texture;
+uniform vec2 u_texture_resolution;
+
+void main() {
+    // four taps between the pixels of the level before,
+    // each the average of four of them thanks to filtering.
+    // its own size is used, which is only exactly twice
+    // this level's when that's even
+    vec2 d = 1.0 / u_texture_resolution;
+    vec3 sum = texture(u_texture, coords + vec2(-d.x, -d.y)).rgb
+             + texture(u_texture, coords + vec2( d.x, -d.y)).rgb
+             + texture(u_texture, coords + vec2(-d.x,  d.y)).rgb
+             + texture(u_texture, coords + vec2( d.x,  d.y)).rgb;
+    color = vec4(sum * 0.25, 1.);
+}
@@ -0,0 +1,24 @@
+#version 140
+
+in  vec2 coords;
+out vec4 color;
+
+uniform float u_time;
+uniform float u_aspect;
+
+void main() {
+    vec2 uv = (coords - 0.5) * vec2(u_aspect, 1.);
+    vec3 scene = vec3(0.02, 0.02, 0.04);
+
+    // a ring of lights, brighter than white where they
+    // overlap, which is what the bloom picks out
+    for (int i = 0; i < 6; i++) {
+        float angle = float(i) * 1.047 + u_time * 0.5;
+        vec2 center = 0.3 * vec2(cos(angle), sin(angle));
+        float inside = smoothstep(0.06, 0.05, length(uv - center));
+        vec3 hue = 0.5 + 0.5 * cos(angle + vec3(0., 2., 4.));
+        scene += 2. * hue * inside;
+    }
+
+    color = vec4(scene, 1.);
+}
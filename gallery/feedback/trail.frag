@@ -0,0 +1,26 @@
+#version 140
+
+in  vec2 coords;
+out vec4 color;
+
+uniform sampler2D u_previous;
+uniform float u_time;
+uniform float u_aspect;
+
+void main() {
+    // where this pixel was last frame: a little further out,
+    // and turned back a little around the middle
+    vec2 centered = coords - 0.5;
+    float angle = 0.01;
+    mat2 turn = mat2(cos(angle), sin(angle), -sin(angle), cos(angle));
+    vec2 last = turn * centered * 0.98 + 0.5;
+    vec3 faded = texture(u_previous, last).rgb * 0.97;
+
+    // the dot, kept round whatever the size of the node
+    vec2 center = 0.3 * vec2(cos(u_time), sin(u_time * 1.3));
+    vec2 offset = (centered - center) * vec2(u_aspect, 1.);
+    float inside = smoothstep(0.03, 0.02, length(offset));
+    vec3 hue = 0.5 + 0.5 * cos(u_time + vec3(0., 2., 4.));
+
+    color = vec4(max(faded, hue * inside), 1.);
+}
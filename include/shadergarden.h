@@ -0,0 +1,131 @@
+/*
+ * The C API of shadergarden, built with `--features ffi`.
+ * The host owns the GL context, which must be current on
+ * the calling thread whenever one of these is called.
+ * Functions that can fail return NULL or -1, and leave a
+ * message to read with shadergarden_last_error.
+ */
+
+#ifndef SHADERGARDEN_H
+#define SHADERGARDEN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct ShaderGarden ShaderGarden;
+
+/* A GL texture, by its name in the host's context. */
+typedef struct ShaderGardenTexture {
+    uint32_t id;
+    uint32_t width;
+    uint32_t height;
+} ShaderGardenTexture;
+
+/* Looks up a GL function by name, in the host's context. */
+typedef const void *(*ShaderGardenGetProcAddress)(
+    const char *symbol,
+    void *user
+);
+
+enum {
+    SHADERGARDEN_RGB8 = 0,
+    SHADERGARDEN_RGBA8 = 1,
+    SHADERGARDEN_R32F = 2,
+    SHADERGARDEN_RGBA32F = 3,
+};
+
+/* The message of the last error on this thread, or NULL. */
+const char *shadergarden_last_error(void);
+
+/* Builds a project and starts watching it. `graph` may be
+ * NULL, for the project's shader.graph. */
+ShaderGarden *shadergarden_create(
+    ShaderGardenGetProcAddress get_proc_address,
+    void *user,
+    const char *project,
+    const char *graph
+);
+
+/* Like shadergarden_create, but in an offscreen context of
+ * its own, on the GPU where there is a display server, or
+ * else rendered in software with OSMesa. */
+ShaderGarden *shadergarden_create_headless(
+    const char *project,
+    const char *graph
+);
+
+void shadergarden_destroy(ShaderGarden *garden);
+
+/* Rebuilds the graph if the project changed, or always if
+ * `force` isn't 0. Returns 1 if rebuilt, 0 if not. */
+int shadergarden_reload(ShaderGarden *garden, int force);
+
+/* Runs the graph once. Returns the number of outputs the
+ * graph has, writing up to `capacity` of them. */
+int shadergarden_forward(
+    ShaderGarden *garden,
+    const ShaderGardenTexture *inputs,
+    size_t input_count,
+    ShaderGardenTexture *outputs,
+    size_t capacity
+);
+
+/* Uploads pixels, row by row from the top, to a named
+ * input, in one of the SHADERGARDEN_* formats. */
+int shadergarden_upload_input(
+    ShaderGarden *garden,
+    const char *name,
+    const uint8_t *pixels,
+    size_t length,
+    uint32_t width,
+    uint32_t height,
+    int format
+);
+
+/* Sets a uniform of a named node. */
+int shadergarden_set_param(
+    ShaderGarden *garden,
+    const char *node,
+    const char *uniform,
+    float value
+);
+
+/* Sets a uniform declared in the graph, from 1 to 4 floats,
+ * for a float up to a vec4. */
+int shadergarden_set_uniform(
+    ShaderGarden *garden,
+    const char *name,
+    const float *values,
+    size_t count
+);
+
+/* Runs the following forward passes at `time` rather than
+ * by the clock, until the graph is rebuilt. */
+int shadergarden_set_time(ShaderGarden *garden, float time, float dt);
+
+/* Rebuilds the graph with the size of every node scaled by
+ * `scale`, which is kept for later rebuilds. */
+int shadergarden_set_render_scale(ShaderGarden *garden, float scale);
+
+/* Reads the nth output of the last forward pass as RGBA
+ * floats, row by row from the top. Returns the number of
+ * floats it has, reading them only if `capacity` is enough.
+ * `width` and `height` may be NULL. */
+int shadergarden_read_output(
+    ShaderGarden *garden,
+    size_t index,
+    float *pixels,
+    size_t capacity,
+    uint32_t *width,
+    uint32_t *height
+);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,227 @@
+"""Python bindings for shadergarden, over its C API.
+
+Build the library with `cargo build --release --features ffi`,
+and point `SHADERGARDEN_LIBRARY` at it if it isn't found.
+
+    import shadergarden
+
+    garden = shadergarden.Garden("demos/life")
+    garden.set_uniform("bpm", 120.0)
+    garden.forward()
+    image = garden.read_output()  # (height, width, 4) float32
+
+For a single frame, e.g. a figure in a notebook, `render` does
+all of this in one call:
+
+    image = shadergarden.render("demos/life", time=2.5, size=(512, 512))
+"""
+
+import ctypes
+import ctypes.util
+import os
+import sys
+
+import numpy as np
+
+__all__ = ["Garden", "ShaderGardenError", "render"]
+
+RGB8, RGBA8, R32F, RGBA32F = range(4)
+
+
+class ShaderGardenError(Exception):
+    pass
+
+
+def _find_library():
+    path = os.environ.get("SHADERGARDEN_LIBRARY")
+    if path:
+        return path
+    path = ctypes.util.find_library("shadergarden")
+    if path:
+        return path
+    # a build in the repository this package came from
+    name = {
+        "darwin": "libshadergarden.dylib",
+        "win32": "shadergarden.dll",
+    }.get(sys.platform, "libshadergarden.so")
+    root = os.path.dirname(os.path.dirname(os.path.dirname(__file__)))
+    for profile in ("release", "debug"):
+        path = os.path.join(root, "target", profile, name)
+        if os.path.exists(path):
+            return path
+    raise ShaderGardenError(
+        "Could not find the shadergarden library, build it with "
+        "`cargo build --release --features ffi` or set SHADERGARDEN_LIBRARY"
+    )
+
+
+_lib = ctypes.CDLL(_find_library())
+_garden = ctypes.c_void_p
+_lib.shadergarden_last_error.restype = ctypes.c_char_p
+_lib.shadergarden_create_headless.argtypes = [ctypes.c_char_p, ctypes.c_char_p]
+_lib.shadergarden_create_headless.restype = _garden
+_lib.shadergarden_destroy.argtypes = [_garden]
+_lib.shadergarden_reload.argtypes = [_garden, ctypes.c_int]
+_lib.shadergarden_forward.argtypes = [
+    _garden, ctypes.c_void_p, ctypes.c_size_t, ctypes.c_void_p, ctypes.c_size_t,
+]
+_lib.shadergarden_upload_input.argtypes = [
+    _garden, ctypes.c_char_p, ctypes.c_void_p, ctypes.c_size_t,
+    ctypes.c_uint32, ctypes.c_uint32, ctypes.c_int,
+]
+_lib.shadergarden_set_param.argtypes = [
+    _garden, ctypes.c_char_p, ctypes.c_char_p, ctypes.c_float,
+]
+_lib.shadergarden_set_uniform.argtypes = [
+    _garden, ctypes.c_char_p, ctypes.POINTER(ctypes.c_float), ctypes.c_size_t,
+]
+_lib.shadergarden_set_time.argtypes = [_garden, ctypes.c_float, ctypes.c_float]
+_lib.shadergarden_set_render_scale.argtypes = [_garden, ctypes.c_float]
+_lib.shadergarden_read_output.argtypes = [
+    _garden, ctypes.c_size_t, ctypes.POINTER(ctypes.c_float), ctypes.c_size_t,
+    ctypes.POINTER(ctypes.c_uint32), ctypes.POINTER(ctypes.c_uint32),
+]
+
+
+def _check(result):
+    if result is None or result == -1:
+        error = _lib.shadergarden_last_error()
+        raise ShaderGardenError(error.decode() if error else "Unknown error")
+    return result
+
+
+def _encode(string):
+    return None if string is None else os.fsencode(string)
+
+
+class Garden:
+    """A project, built in an offscreen context of its own and
+    rebuilt as it changes, on the GPU where there's a display
+    server, or else in software. `graph` defaults to the
+    project's shader.graph."""
+
+    _garden = None
+
+    def __init__(self, project, graph=None):
+        self._garden = _check(
+            _lib.shadergarden_create_headless(_encode(project), _encode(graph))
+        )
+
+    def close(self):
+        if self._garden:
+            _lib.shadergarden_destroy(self._garden)
+            self._garden = None
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *_):
+        self.close()
+
+    def __del__(self):
+        self.close()
+
+    def reload(self, force=False):
+        """Rebuilds the graph if the project changed, or always if
+        `force` is set. Returns whether it was rebuilt."""
+        return _check(_lib.shadergarden_reload(self._garden, int(force))) == 1
+
+    def set_uniform(self, name, value):
+        """Sets a uniform declared in the graph, from a float or up
+        to four of them."""
+        values = np.atleast_1d(np.asarray(value, dtype=np.float32))
+        pointer = values.ctypes.data_as(ctypes.POINTER(ctypes.c_float))
+        _check(_lib.shadergarden_set_uniform(
+            self._garden, name.encode(), pointer, values.size
+        ))
+
+    def set_param(self, node, uniform, value):
+        """Sets a uniform of a named node."""
+        _check(_lib.shadergarden_set_param(
+            self._garden, node.encode(), uniform.encode(), value
+        ))
+
+    def set_time(self, time, dt=1 / 60):
+        """Runs the following passes at `time` rather than by the
+        clock, until the graph is rebuilt."""
+        _check(_lib.shadergarden_set_time(self._garden, time, dt))
+
+    def set_render_scale(self, scale):
+        """Rebuilds the graph with the size of every node scaled, e.g.
+        by 0.5 for half resolution."""
+        _check(_lib.shadergarden_set_render_scale(self._garden, scale))
+
+    def upload_input(self, name, pixels):
+        """Uploads an array to a named input, row by row from the
+        top: uint8 with 3 or 4 channels, or float32 with 1 or 4."""
+        pixels = np.ascontiguousarray(pixels)
+        height, width = pixels.shape[:2]
+        channels = 1 if pixels.ndim == 2 else pixels.shape[2]
+        formats = {
+            (np.dtype(np.uint8), 3): RGB8,
+            (np.dtype(np.uint8), 4): RGBA8,
+            (np.dtype(np.float32), 1): R32F,
+            (np.dtype(np.float32), 4): RGBA32F,
+        }
+        format = formats.get((pixels.dtype, channels))
+        if format is None:
+            raise ShaderGardenError(
+                "Cannot upload {} pixels with {} channels".format(
+                    pixels.dtype, channels
+                )
+            )
+        _check(_lib.shadergarden_upload_input(
+            self._garden, name.encode(), pixels.ctypes.data, pixels.nbytes,
+            width, height, format,
+        ))
+
+    def forward(self):
+        """Runs the graph once, returning how many outputs it has."""
+        return _check(_lib.shadergarden_forward(self._garden, None, 0, None, 0))
+
+    def output_size(self, index=0):
+        """The (width, height) of an output."""
+        width, height = ctypes.c_uint32(), ctypes.c_uint32()
+        _check(_lib.shadergarden_read_output(
+            self._garden, index, None, 0,
+            ctypes.byref(width), ctypes.byref(height),
+        ))
+        return width.value, height.value
+
+    def read_output(self, index=0):
+        """Reads an output of the last forward pass as a float32
+        array of shape (height, width, 4)."""
+        width, height = self.output_size(index)
+        pixels = np.empty((height, width, 4), dtype=np.float32)
+        _check(_lib.shadergarden_read_output(
+            self._garden, index,
+            pixels.ctypes.data_as(ctypes.POINTER(ctypes.c_float)), pixels.size,
+            None, None,
+        ))
+        return pixels
+
+
+def render(project, time=0.0, size=None, graph=None, output=0,
+           uniforms=None, frames=1, dt=1 / 60):
+    """Renders a project at `time` and returns an output as a
+    float32 array of shape (height, width, 4), in an offscreen
+    context that only lasts for the call.
+
+    `size` is a (width, height) to fit the output in, keeping the
+    graph's aspect ratio, by scaling every node as
+    `Garden.set_render_scale` does. `uniforms` maps uniforms
+    declared in the graph to their values. Feedback needs a few
+    frames to get going, so with `frames` above 1, that many are
+    run, `dt` apart, ending at `time`."""
+    with Garden(project, graph) as garden:
+        if size is not None:
+            width, height = garden.output_size(output)
+            scale = min(size[0] / width, size[1] / height)
+            if scale != 1:
+                garden.set_render_scale(scale)
+        for name, value in (uniforms or {}).items():
+            garden.set_uniform(name, value)
+        for frame in range(frames):
+            garden.set_time(time - (frames - 1 - frame) * dt, dt)
+            garden.forward()
+        return garden.read_output(output)
@@ -0,0 +1,136 @@
+"""Controls a running shadergarden over TCP, started with
+`shadergarden run --listen <address>`. Pure Python, so it needs
+neither the shadergarden library nor numpy.
+
+    from shadergarden_client import Client
+
+    with Client("127.0.0.1:7777") as client:
+        client.set("bloom", "intensity", 2.0)
+        client.project("night")
+        print(client.status()["frames"])
+
+Every method raises `ControlError` with shadergarden's message
+when a command is refused.
+"""
+
+import json
+import socket
+
+__all__ = ["Client", "ControlError", "quote"]
+
+
+class ControlError(Exception):
+    pass
+
+
+def quote(word):
+    """Quotes a word of a command if it needs it, e.g. a path with
+    spaces, so that it's read back as it is."""
+    word = str(word)
+    if word and not word.startswith('"') and not any(c.isspace() for c in word):
+        return word
+    return '"{}"'.format(word.replace("\\", "\\\\").replace('"', '\\"'))
+
+
+class Client:
+    """A connection to a running instance. The protocol is one
+    command per line, each answered with a line: `ok`,
+    `error <message>`, or JSON for `status`."""
+
+    def __init__(self, address, timeout=None):
+        host, _, port = address.rpartition(":")
+        self._socket = socket.create_connection((host, int(port)), timeout)
+        self._file = self._socket.makefile("rw", encoding="utf-8", newline="\n")
+
+    def close(self):
+        self._file.close()
+        self._socket.close()
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *_):
+        self.close()
+
+    def reload(self):
+        """Rebuilds the graph, even if nothing has changed."""
+        self._command("reload")
+
+    def pause(self):
+        """Stops or resumes running the graph."""
+        self._command("pause")
+
+    def screenshot(self, path=None):
+        """Saves the output to a png on the machine running
+        shadergarden, relative to its project directory, or at a
+        timestamped path if none is given."""
+        self._command("screenshot" if path is None else "screenshot " + quote(path))
+
+    def set(self, node, uniform, value):
+        """Sets the `u_<uniform>` uniform of a named node."""
+        self._command("set {} {}".format(quote(node + "." + uniform), float(value)))
+
+    def save_state(self, path):
+        """Saves the state of feedback nodes to a file on the machine
+        running shadergarden, relative to its project directory."""
+        self._command("save-state " + quote(path))
+
+    def load_state(self, path):
+        """Restores state saved with `save_state`."""
+        self._command("load-state " + quote(path))
+
+    def palette(self, name=None):
+        """Switches to a palette by name, or to the next one."""
+        self._command("palette" if name is None else "palette " + quote(name))
+
+    def step(self):
+        """Pauses, and runs the graph for a single frame."""
+        self._command("step")
+
+    def seek(self, time):
+        """Moves the graph's clock to a time, in seconds."""
+        self._command("seek {}".format(float(time)))
+
+    def speed(self, speed):
+        """Sets the speed of the graph's clock, where 1 is real
+        time."""
+        self._command("speed {}".format(float(speed)))
+
+    def project(self, name):
+        """Switches scenes, showing one of the sub-projects of a
+        project by name."""
+        self._command("project " + quote(name))
+
+    def next_project(self):
+        """Shows the next sub-project that builds."""
+        self._command("next-project")
+
+    def previous_project(self):
+        """Shows the previous sub-project that builds."""
+        self._command("previous-project")
+
+    def regenerate(self, node=None):
+        """Makes a named random texture, or every one, from a new
+        seed."""
+        self._command("regenerate" if node is None else "regenerate " + quote(node))
+
+    def status(self):
+        """The frame count, late and dropped frames, frame time,
+        clock, and named nodes, as a dict."""
+        reply = self._request("status")
+        if reply.startswith("error "):
+            raise ControlError(reply[len("error "):])
+        return json.loads(reply)
+
+    def _command(self, line):
+        reply = self._request(line)
+        if reply != "ok":
+            raise ControlError(reply[len("error "):] if reply.startswith("error ") else reply)
+
+    def _request(self, line):
+        self._file.write(line + "\n")
+        self._file.flush()
+        reply = self._file.readline()
+        if not reply:
+            raise ControlError("shadergarden closed the connection")
+        return reply.rstrip("\n")
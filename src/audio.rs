@@ -0,0 +1,388 @@
+use std::{
+    collections::VecDeque,
+    convert::TryInto,
+    f32::consts::PI,
+    io::{
+        Read,
+        Write,
+    },
+    process::{
+        Child,
+        ChildStdout,
+        Command,
+        Stdio,
+    },
+    sync::{
+        mpsc,
+        Arc,
+        Mutex,
+    },
+    thread,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use glium::{
+    Rect,
+    Texture2d,
+};
+
+use crate::reconnect::Reconnecting;
+
+/// The rate audio is captured at, in samples per second.
+pub const SAMPLE_RATE: u32 = 44100;
+
+/// The width of an audio texture: the number of spectrum
+/// bins, and of waveform samples, as in Shadertoy.
+pub const WIDTH: usize = 512;
+
+/// Samples per FFT. Only the lowest [`WIDTH`] of the bins
+/// are kept, covering up to about 11kHz.
+const FFT_SIZE: usize = 2048;
+
+/// How much of the last spectrum is kept in each new one,
+/// and the range of decibels mapped to `0..1`. These match
+/// the defaults of the Web Audio analyser Shadertoy uses.
+const SMOOTHING: f32 = 0.8;
+const MIN_DECIBELS: f32 = -100.0;
+const MAX_DECIBELS: f32 = -30.0;
+
+/// The `ffmpeg` input format and device used when no device
+/// is given, for each platform.
+#[cfg(target_os = "linux")]
+const DEFAULT_INPUT: Option<(&str, &str)> = Some(("pulse", "default"));
+#[cfg(target_os = "macos")]
+const DEFAULT_INPUT: Option<(&str, &str)> = Some(("avfoundation", ":0"));
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+const DEFAULT_INPUT: Option<(&str, &str)> = None;
+
+/// The `ffmpeg` output format and device played to when no
+/// device is given. PipeWire and JACK take sound from pulse
+/// clients, through their pulse servers and bridges.
+#[cfg(target_os = "linux")]
+const DEFAULT_OUTPUT: Option<(&str, &str)> = Some(("pulse", "default"));
+#[cfg(target_os = "macos")]
+const DEFAULT_OUTPUT: Option<(&str, &str)> = Some(("audiotoolbox", "0"));
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+const DEFAULT_OUTPUT: Option<(&str, &str)> = None;
+
+/// The longest gap between frames made up with sound, in
+/// seconds. Longer pauses, like a hitch while the graph
+/// rebuilds, are skipped, rather than played late.
+const MAX_GAP: f64 = 0.25;
+
+/// The `ffmpeg` input captured from when no device is
+/// given, written `<format>:<device>`, if the platform has
+/// one.
+pub fn default_input() -> Option<String> {
+    let (format, device) = DEFAULT_INPUT?;
+    Some(format!("{}:{}", format, device))
+}
+
+/// Splits a device written `<format>:<device>`, or picks
+/// the platform's default.
+fn device<'a>(
+    device: Option<&'a str>,
+    default: Option<(&'a str, &'a str)>,
+) -> Result<(&'a str, &'a str), String> {
+    match device {
+        Some(device) => device.split_once(':').ok_or_else(|| {
+            format!(
+                "Audio device `{}` should be written as \
+                 `<format>:<device>`, e.g. `pulse:default`",
+                device
+            )
+        }),
+        None => default.ok_or_else(|| {
+            "There is no default audio device on this platform, pass \
+             one with `:device`"
+                .to_string()
+        }),
+    }
+}
+
+/// Live audio captured by an `ffmpeg` process, which must
+/// be on the path. The most recent samples are kept, mixed
+/// down to mono, and may be held back by a delay. If the
+/// device is unplugged, the last samples are kept until
+/// it's back, see [`Reconnecting`]. Capture stops once
+/// this is dropped.
+pub struct Capture {
+    _process: Reconnecting,
+    samples:  Arc<Mutex<VecDeque<f32>>>,
+    /// How many of the newest samples are held back, not
+    /// yet returned by [`Capture::samples`].
+    delay:    usize,
+}
+
+impl Capture {
+    /// Starts capturing from a device, given as an `ffmpeg`
+    /// input like `pulse:default`, or from the default
+    /// input on Linux and macOS. Samples are held back by
+    /// `delay`, e.g. to line sound up with a slower camera.
+    pub fn start(
+        device: Option<&str>,
+        delay: Duration,
+    ) -> Result<Capture, String> {
+        let (format, input) = self::device(device, DEFAULT_INPUT)?;
+        let delay = (delay.as_secs_f64() * SAMPLE_RATE as f64) as usize;
+        let capacity = FFT_SIZE + delay;
+
+        let (format, input) = (format.to_string(), input.to_string());
+        let command = move || {
+            let mut command = Command::new("ffmpeg");
+            command
+                .args(["-loglevel", "error", "-nostdin"])
+                .args(["-f", &format, "-i", &input])
+                .args(["-ac", "1", "-ar", &SAMPLE_RATE.to_string()])
+                .args(["-f", "f32le", "-"]);
+            command
+        };
+
+        let samples = Arc::new(Mutex::new(VecDeque::with_capacity(capacity)));
+        let shared = samples.clone();
+        let mut bytes = [0; 4 * 256];
+        let read = move |stdout: &mut ChildStdout| {
+            stdout.read_exact(&mut bytes)?;
+            let mut samples = shared.lock().unwrap();
+            for sample in bytes.chunks_exact(4) {
+                if samples.len() == capacity {
+                    samples.pop_front();
+                }
+                // unwrap: chunks are exactly 4 bytes
+                samples
+                    .push_back(f32::from_le_bytes(sample.try_into().unwrap()));
+            }
+            Ok(true)
+        };
+        let name = match device {
+            Some(device) => format!("Audio input `{}`", device),
+            None => "The default audio input".to_string(),
+        };
+        let process = Reconnecting::spawn(name, command, read)?;
+
+        Ok(Capture {
+            _process: process,
+            samples,
+            delay,
+        })
+    }
+
+    /// The most recent samples before the delay, oldest
+    /// first, padded with silence until enough have been
+    /// captured.
+    pub fn samples(&self) -> Vec<f32> {
+        let samples = self.samples.lock().unwrap();
+        let end = samples.len().saturating_sub(self.delay);
+        let start = end.saturating_sub(FFT_SIZE);
+        let mut padded = vec![0.0; FFT_SIZE - (end - start)];
+        padded.extend(samples.range(start..end));
+        padded
+    }
+}
+
+/// Sound made from a texture, played by an `ffmpeg`
+/// process, which must be on the path. Each frame, the
+/// bottom row of the texture is stretched over the time
+/// since the last, as a waveform: red is the left channel
+/// and green the right, with silence at `0.5`, as in the
+/// waveform of an audio texture. Playback stops once this
+/// is dropped.
+pub struct Playback {
+    child:   Child,
+    samples: mpsc::Sender<Vec<f32>>,
+    last:    Option<Instant>,
+    /// Samples owed to the next frame, as frames don't
+    /// last a whole number of samples.
+    owed:    f64,
+}
+
+impl Playback {
+    /// Starts playing to a device, given as an `ffmpeg`
+    /// output like `pulse:default`, or to the default
+    /// output on Linux and macOS.
+    pub fn start(device: Option<&str>) -> Result<Playback, String> {
+        let (format, output) = self::device(device, DEFAULT_OUTPUT)?;
+        let mut child = Command::new("ffmpeg")
+            .args(["-loglevel", "error", "-nostdin"])
+            .args(["-f", "f32le", "-ac", "2"])
+            .args(["-ar", &SAMPLE_RATE.to_string(), "-i", "-"])
+            .args(["-f", format, output])
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Could not start ffmpeg: {}", e))?;
+        let mut stdin = child.stdin.take().unwrap();
+
+        // written on a thread, so the graph isn't held up
+        // while ffmpeg plays what it has
+        let (samples, receive) = mpsc::channel::<Vec<f32>>();
+        thread::spawn(move || {
+            for samples in receive {
+                let bytes = samples
+                    .iter()
+                    .flat_map(|sample| sample.to_le_bytes())
+                    .collect::<Vec<_>>();
+                if stdin.write_all(&bytes).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Playback {
+            child,
+            samples,
+            last: None,
+            owed: 0.0,
+        })
+    }
+
+    /// Plays a waveform, as read by [`waveform`], over the
+    /// time since the last, or not at all for the first.
+    pub fn play(&mut self, waveform: &[[f32; 2]]) {
+        let now = Instant::now();
+        let elapsed = match self.last.replace(now) {
+            Some(last) => (now - last).as_secs_f64().min(MAX_GAP),
+            None => return,
+        };
+        if waveform.is_empty() {
+            return;
+        }
+        self.owed += elapsed * SAMPLE_RATE as f64;
+        let count = self.owed as usize;
+        self.owed -= count as f64;
+
+        // stretched, interpolating between texels
+        let width = waveform.len();
+        let mut samples = Vec::with_capacity(2 * count);
+        for i in 0..count {
+            let x = i as f32 * width as f32 / count as f32;
+            let left = (x as usize).min(width - 1);
+            let right = (left + 1).min(width - 1);
+            let t = x.fract();
+            for (a, b) in waveform[left].iter().zip(waveform[right]) {
+                samples.push((a + (b - a) * t).clamp(-1.0, 1.0));
+            }
+        }
+        let _ = self.samples.send(samples);
+    }
+}
+
+impl Drop for Playback {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Reads the bottom row of a texture back as a stereo
+/// waveform for [`Playback::play`], mapping `0..1` in red
+/// and green to `-1..1`.
+pub fn waveform(texture: &Texture2d) -> Vec<[f32; 2]> {
+    let rect = Rect {
+        left:   0,
+        bottom: 0,
+        width:  texture.width(),
+        height: 1,
+    };
+    let rows: Vec<Vec<(f32, f32, f32, f32)>> = texture
+        .main_level()
+        .first_layer()
+        .into_image(None)
+        .unwrap()
+        .raw_read(&rect);
+    rows.first()
+        .map(|row| {
+            row.iter()
+                .map(|&(r, g, _, _)| [2.0 * r - 1.0, 2.0 * g - 1.0])
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Turns samples into the rows of an audio texture: first
+/// the magnitude spectrum, in decibels mapped to `0..1`,
+/// then the waveform, mapped so that silence is `0.5`.
+/// The spectrum is smoothed over time, so an analyser
+/// should be kept from frame to frame.
+pub struct Analyser {
+    window:   Vec<f32>,
+    smoothed: Vec<f32>,
+}
+
+impl Default for Analyser {
+    fn default() -> Analyser { Analyser::new() }
+}
+
+impl Analyser {
+    pub fn new() -> Analyser {
+        // a Blackman window, to keep loud bins from smearing
+        let window = (0..FFT_SIZE)
+            .map(|i| {
+                let x = 2.0 * PI * i as f32 / FFT_SIZE as f32;
+                0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos()
+            })
+            .collect();
+
+        Analyser {
+            window,
+            smoothed: vec![0.0; WIDTH],
+        }
+    }
+
+    /// Analyses the last [`FFT_SIZE`] samples, as returned
+    /// by [`Capture::samples`], into `2 * WIDTH` values.
+    pub fn analyse(&mut self, samples: &[f32]) -> Vec<f32> {
+        let mut bins = samples
+            .iter()
+            .zip(self.window.iter())
+            .map(|(sample, weight)| [sample * weight, 0.0])
+            .collect::<Vec<_>>();
+        fft(&mut bins);
+
+        let mut values = Vec::with_capacity(2 * WIDTH);
+        for (smoothed, [re, im]) in self.smoothed.iter_mut().zip(bins) {
+            let magnitude = (re * re + im * im).sqrt() / FFT_SIZE as f32;
+            *smoothed = SMOOTHING * *smoothed + (1.0 - SMOOTHING) * magnitude;
+            let decibels = 20.0 * smoothed.max(1e-10).log10();
+            let range = MAX_DECIBELS - MIN_DECIBELS;
+            let level = (decibels - MIN_DECIBELS) / range;
+            values.push(level.clamp(0.0, 1.0));
+        }
+
+        let wave = &samples[samples.len() - WIDTH..];
+        values.extend(wave.iter().map(|s| (0.5 + 0.5 * s).clamp(0.0, 1.0)));
+        values
+    }
+}
+
+/// An in-place radix-2 FFT of complex `[re, im]` values.
+/// The length must be a power of two.
+fn fft(values: &mut [[f32; 2]]) {
+    let n = values.len();
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if i < j {
+            values.swap(i, j);
+        }
+    }
+
+    let mut size = 2;
+    while size <= n {
+        let angle = -2.0 * PI / size as f32;
+        for start in (0..n).step_by(size) {
+            for k in 0..size / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let [re, im] = values[start + k + size / 2];
+                let odd = [re * cos - im * sin, re * sin + im * cos];
+                let even = values[start + k];
+                values[start + k] = [even[0] + odd[0], even[1] + odd[1]];
+                values[start + k + size / 2] =
+                    [even[0] - odd[0], even[1] - odd[1]];
+            }
+        }
+        size *= 2;
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    process::Command,
+    sync::mpsc::{
+        self,
+        Receiver,
+        TrySendError,
+    },
+    time::Duration,
+};
+
+use crate::{
+    reconnect::Reconnecting,
+    upload::{
+        delay_frames,
+        read_frame,
+        Frame,
+    },
+};
+
+/// The `ffmpeg` input for the camera at an index, e.g.
+/// `v4l2:/dev/video0` on Linux.
+pub fn device(index: usize) -> Result<String, String> {
+    if cfg!(target_os = "linux") {
+        Ok(format!("v4l2:/dev/video{}", index))
+    } else if cfg!(target_os = "macos") {
+        Ok(format!("avfoundation:{}", index))
+    } else {
+        Err(
+            "Cameras can't be opened by index on this platform, pass one \
+             with `:device`"
+                .to_string(),
+        )
+    }
+}
+
+/// A webcam streamed by an `ffmpeg` process, which must be
+/// on the path. Frames are scaled to the requested size,
+/// and only the newest is kept when the graph falls behind,
+/// to keep latency low. If the camera is unplugged, the
+/// last frame is kept until it's back, see
+/// [`Reconnecting`]. Capture stops once this is dropped.
+pub struct Camera {
+    _process: Reconnecting,
+}
+
+impl Camera {
+    /// Starts streaming from a device, given as an `ffmpeg`
+    /// input format and device, like `v4l2:/dev/video0`,
+    /// see [`device`]. Returns the camera and its
+    /// frames, each held back by `delay`, e.g. to line
+    /// the camera up with slower inputs.
+    pub fn start(
+        device: &str,
+        width: u32,
+        height: u32,
+        fps: u32,
+        delay: Duration,
+    ) -> Result<(Camera, Receiver<Frame>), String> {
+        let (format, input) = device.split_once(':').ok_or_else(|| {
+            format!(
+                "Camera `{}` should be written as `<format>:<device>`, \
+                 e.g. `v4l2:/dev/video0`",
+                device
+            )
+        })?;
+
+        let (format, input) = (format.to_string(), input.to_string());
+        let command = move || {
+            let size = format!("{}x{}", width, height);
+            let mut command = Command::new("ffmpeg");
+            command
+                .args(["-loglevel", "error", "-nostdin"])
+                .args(["-f", &format, "-framerate", &fps.to_string()])
+                .args(["-video_size", &size, "-i", &input])
+                // in case the camera picked another size, and as
+                // textures start at the bottom row
+                .args(["-vf", &format!("scale={}:{},vflip", width, height)])
+                .args(["-pix_fmt", "rgb24", "-f", "rawvideo", "-"]);
+            command
+        };
+        let (sender, frames) = mpsc::sync_channel(1);
+        let mut bytes = vec![0; (width * height * 3) as usize];
+        let process = Reconnecting::spawn(
+            format!("Camera `{}`", device),
+            command,
+            move |stdout| {
+                let frame = read_frame(stdout, &mut bytes)?;
+                let sent = sender.try_send(frame);
+                Ok(!matches!(sent, Err(TrySendError::Disconnected(_))))
+            },
+        )?;
+
+        let frames = if delay.is_zero() {
+            frames
+        } else {
+            delay_frames(frames, delay)
+        };
+        Ok((Camera { _process: process }, frames))
+    }
+}
@@ -0,0 +1,112 @@
+use std::str::FromStr;
+
+/// The size of a logical canvas, e.g. `7680x2160`, which
+/// can be split into tiles rendered by separate machines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Canvas {
+    pub width:  u32,
+    pub height: u32,
+}
+
+impl FromStr for Canvas {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Canvas, String> {
+        let (width, height) = size(s).ok_or_else(|| {
+            format!("Expected a size like `7680x2160`, found `{}`", s)
+        })?;
+        Ok(Canvas { width, height })
+    }
+}
+
+/// A region of a [`Canvas`], written like an X11 geometry:
+/// `1920x2160+3840+0` is 1920 by 2160 pixels, 3840 pixels
+/// from the left and 0 from the top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub width:  u32,
+    pub height: u32,
+    pub x:      u32,
+    pub y:      u32,
+}
+
+impl FromStr for Tile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Tile, String> {
+        let invalid = || {
+            format!("Expected a tile like `1920x2160+3840+0`, found `{}`", s)
+        };
+        let mut parts = s.split('+');
+        let (width, height) =
+            parts.next().and_then(size).ok_or_else(invalid)?;
+        let mut offset = || -> Result<u32, String> {
+            parts
+                .next()
+                .and_then(|o| o.parse().ok())
+                .ok_or_else(invalid)
+        };
+        let (x, y) = (offset()?, offset()?);
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(Tile {
+            width,
+            height,
+            x,
+            y,
+        })
+    }
+}
+
+impl Tile {
+    /// The whole of a canvas.
+    pub fn whole(canvas: Canvas) -> Tile {
+        Tile {
+            width:  canvas.width,
+            height: canvas.height,
+            x:      0,
+            y:      0,
+        }
+    }
+
+    /// Checks that the tile lies within the canvas.
+    pub fn check(&self, canvas: Canvas) -> Result<(), String> {
+        if self.x + self.width > canvas.width
+            || self.y + self.height > canvas.height
+        {
+            return Err(format!(
+                "The tile {}x{}+{}+{} does not fit in the {}x{} canvas",
+                self.width,
+                self.height,
+                self.x,
+                self.y,
+                canvas.width,
+                canvas.height,
+            ));
+        }
+        Ok(())
+    }
+
+    /// The tile as a fraction of the canvas, as passed to
+    /// `u_tile`: `(x, y, width, height)`, measured from the
+    /// bottom left, like texture coordinates.
+    pub fn uniform(&self, canvas: Canvas) -> [f32; 4] {
+        let (w, h) = (canvas.width as f32, canvas.height as f32);
+        let bottom = canvas.height - self.y - self.height;
+        [
+            self.x as f32 / w,
+            bottom as f32 / h,
+            self.width as f32 / w,
+            self.height as f32 / h,
+        ]
+    }
+}
+
+fn size(s: &str) -> Option<(u32, u32)> {
+    let (width, height) = s.split_once('x')?;
+    match (width.parse().ok()?, height.parse().ok()?) {
+        (0, _) | (_, 0) => None,
+        (width, height) => Some((width, height)),
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    fmt,
+    str::FromStr,
+};
+
+use glium::{
+    backend::Context,
+    program::ComputeShader,
+    texture::UncompressedFloatFormat,
+    Api,
+    CapabilitiesSource,
+};
+
+/// A version of OpenGL or OpenGL ES, e.g. `4.3` or `es3.0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GlVersion {
+    pub es:    bool,
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl FromStr for GlVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<GlVersion, String> {
+        let (es, version) = match s.strip_prefix("es") {
+            Some(version) => (true, version),
+            None => (false, s),
+        };
+        version
+            .split_once('.')
+            .and_then(|(major, minor)| {
+                Some(GlVersion {
+                    es,
+                    major: major.parse().ok()?,
+                    minor: minor.parse().ok()?,
+                })
+            })
+            .ok_or_else(|| {
+                format!(
+                    "Expected a GL version like `4.3` or `es3.0`, found `{}`",
+                    s
+                )
+            })
+    }
+}
+
+impl fmt::Display for GlVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let api = if self.es { "OpenGL ES" } else { "OpenGL" };
+        write!(f, "{} {}.{}", api, self.major, self.minor)
+    }
+}
+
+/// A GLSL version, as written after `#version`, e.g. `430`,
+/// `330 core`, or `300 es`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlslVersion {
+    pub number:  u16,
+    /// `core`, `compatibility`, or `es`, if given.
+    pub profile: Option<String>,
+}
+
+impl GlslVersion {
+    pub fn is_es(&self) -> bool {
+        // GLSL ES 1.00 has no profile
+        self.number == 100 || self.profile.as_deref() == Some("es")
+    }
+}
+
+impl FromStr for GlslVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<GlslVersion, String> {
+        let invalid = || {
+            format!(
+                "Expected a GLSL version like `430` or `300 es`, found `{}`",
+                s
+            )
+        };
+        let mut words = s.split_whitespace();
+        let number = words
+            .next()
+            .and_then(|n| n.parse().ok())
+            .ok_or_else(invalid)?;
+        let profile = match words.next() {
+            Some(p @ ("core" | "compatibility" | "es")) => Some(p.to_string()),
+            Some(_) => return Err(invalid()),
+            None => None,
+        };
+        if words.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(GlslVersion { number, profile })
+    }
+}
+
+impl fmt::Display for GlslVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.number)?;
+        match &self.profile {
+            Some(profile) => write!(f, " {}", profile),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The features and limits of a context that graphs may
+/// depend on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    pub version:           GlVersion,
+    pub renderer:          String,
+    /// The latest GLSL version, as written after
+    /// `#version`, e.g. `330`.
+    pub glsl_version:      u16,
+    /// The largest width or height of a texture.
+    pub max_texture_size:  u32,
+    /// Whether 32-bit float textures can be rendered to.
+    pub float_textures:    bool,
+    /// How many textures a single pass can render to.
+    pub color_attachments: u32,
+    pub compute:           bool,
+}
+
+impl Capabilities {
+    pub fn detect(context: &Context) -> Capabilities {
+        let version = context.get_opengl_version();
+        let caps = context.get_capabilities();
+        let renderer = context.get_opengl_renderer_string().to_string();
+        let glsl = context.get_supported_glsl_version();
+        Capabilities {
+            version: GlVersion {
+                es:    version.0 == Api::GlEs,
+                major: version.1,
+                minor: version.2,
+            },
+            renderer,
+            glsl_version: glsl.1 as u16 * 100 + glsl.2 as u16,
+            max_texture_size: caps.max_texture_size.max(0) as u32,
+            float_textures: UncompressedFloatFormat::F32F32F32F32
+                .is_color_renderable(context),
+            color_attachments: caps.max_draw_buffers.max(0) as u32,
+            compute: ComputeShader::is_supported(context),
+        }
+    }
+
+    /// The names of features that are not available, so
+    /// they can be reported up front.
+    pub fn missing(&self) -> Vec<&'static str> {
+        let mut missing = vec![];
+        if !self.float_textures {
+            missing.push("float textures");
+        }
+        if self.color_attachments < 2 {
+            missing.push("multiple render targets");
+        }
+        if !self.compute {
+            missing.push("compute shaders");
+        }
+        missing
+    }
+
+    /// Whether the context is rendered on the CPU, e.g. by
+    /// llvmpipe or SwiftShader, as on most CI machines.
+    pub fn is_software(&self) -> bool {
+        let renderer = self.renderer.to_lowercase();
+        ["llvmpipe", "softpipe", "swiftshader", "swrast", "software"]
+            .iter()
+            .any(|name| renderer.contains(name))
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "version": self.version.to_string(),
+            "renderer": self.renderer,
+            "glsl_version": self.glsl_version,
+            "max_texture_size": self.max_texture_size,
+            "float_textures": self.float_textures,
+            "color_attachments": self.color_attachments,
+            "compute": self.compute,
+            "software": self.is_software(),
+        })
+    }
+}
+
+/// The minimum a graph needs to render correctly, as
+/// declared with `(require ...)`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Requirements {
+    pub version:           Option<GlVersion>,
+    pub glsl:              Option<GlslVersion>,
+    pub float_textures:    bool,
+    pub color_attachments: Option<u32>,
+    pub compute:           bool,
+}
+
+impl Requirements {
+    /// Errors with every requirement that is not met.
+    pub fn check(&self, caps: &Capabilities) -> Result<(), String> {
+        let mut unmet = vec![];
+        if let Some(version) = self.version {
+            // GL and GL ES versions can't be compared
+            if version.es != caps.version.es || version > caps.version {
+                unmet.push(format!("{}", version));
+            }
+        }
+        if let Some(glsl) = &self.glsl {
+            // nor can GLSL and GLSL ES versions
+            let es = glsl.is_es() != caps.version.es;
+            if es || glsl.number > caps.glsl_version {
+                unmet.push(format!("GLSL {}", glsl));
+            }
+        }
+        if self.float_textures && !caps.float_textures {
+            unmet.push("float textures".to_string());
+        }
+        if let Some(count) = self.color_attachments {
+            if count > caps.color_attachments {
+                unmet.push(format!("{} color attachments", count));
+            }
+        }
+        if self.compute && !caps.compute {
+            unmet.push("compute shaders".to_string());
+        }
+
+        if unmet.is_empty() {
+            return Ok(());
+        }
+        Err(format!(
+            "This graph requires {}, which {} ({}) does not support",
+            unmet.join(", "),
+            caps.version,
+            caps.renderer,
+        ))
+    }
+}
@@ -0,0 +1,301 @@
+//! Checkpoints of a long offline render, so that one that
+//! is interrupted can carry on from the last, rather than
+//! starting over, see [`Checkpoint`].
+
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use serde_json::{
+    json,
+    Value,
+};
+
+use crate::{
+    graph::ShaderGraph,
+    shard::{
+        self,
+        Shard,
+    },
+    sink::SinkSpec,
+};
+
+/// The file progress is saved in, in the directory of a
+/// [`Checkpoint`].
+pub const PROGRESS_FILE: &str = "progress.json";
+
+/// How far a render has got. A checkpointed render is split
+/// into segments, each written as parts of its outputs, see
+/// [`crate::shard::Shard::part`], which are merged once the
+/// last is done.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    /// The hash of the files the graph was built from, see
+    /// [`crate::provenance::Provenance::graph_hash`].
+    pub graph:    String,
+    pub seed:     u32,
+    /// The first frame rendered, and the one after the
+    /// last.
+    pub frames:   (u64, u64),
+    pub segments: u32,
+    /// How many segments are done, from the first.
+    pub done:     u32,
+}
+
+impl Progress {
+    /// The progress of a render of `graph`, with `seed`, of
+    /// the frames from `frames.0` up to `frames.1`, none of
+    /// them done yet. Checkpointed every `every` frames, it
+    /// is split into as many segments, but no shorter than
+    /// `align` frames, see [`Shard::frames`]. Otherwise it
+    /// is a single segment.
+    pub fn new(
+        graph: String,
+        seed: u32,
+        frames: (u64, u64),
+        every: Option<u64>,
+        align: u64,
+    ) -> Progress {
+        let total = frames.1.saturating_sub(frames.0);
+        let segments = match every {
+            Some(every) => {
+                let segments = total.div_ceil(every.max(1));
+                segments.min(total / align.max(1)).max(1) as u32
+            },
+            None => 1,
+        };
+        Progress {
+            graph,
+            seed,
+            frames,
+            segments,
+            done: 0,
+        }
+    }
+
+    /// The segment being rendered, the one after those
+    /// done, as a shard of the whole render.
+    pub fn segment(&self) -> Shard {
+        Shard {
+            index: self.done + 1,
+            count: self.segments,
+        }
+    }
+
+    /// The frames of the segment being rendered.
+    pub fn segment_frames(&self, align: u64) -> (u64, u64) {
+        self.segment().frames(self.frames.0, self.frames.1, align)
+    }
+
+    /// The first frame left to render.
+    pub fn next_frame(&self, align: u64) -> u64 {
+        match self.done < self.segments {
+            true => self.segment_frames(align).0,
+            false => self.frames.1,
+        }
+    }
+
+    /// Whether a render described by `other` can carry on
+    /// from this progress: the same graph, rendering the
+    /// same frames in the same segments, with the same seed
+    /// if `seeded`, or any seed otherwise.
+    pub fn continues(&self, other: &Progress, seeded: bool) -> bool {
+        self.graph == other.graph
+            && self.frames == other.frames
+            && self.segments == other.segments
+            && (self.seed == other.seed || !seeded)
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "graph": self.graph,
+            "seed": self.seed,
+            "start": self.frames.0,
+            "end": self.frames.1,
+            "segments": self.segments,
+            "done": self.done,
+        })
+    }
+
+    pub fn from_json(json: &Value) -> Result<Progress, String> {
+        let malformed = || "Malformed checkpoint progress".to_string();
+        let number = |key: &str| json[key].as_u64().ok_or_else(malformed);
+        Ok(Progress {
+            graph:    json["graph"].as_str().ok_or_else(malformed)?.to_string(),
+            seed:     number("seed")? as u32,
+            frames:   (number("start")?, number("end")?),
+            segments: number("segments")? as u32,
+            done:     number("done")? as u32,
+        })
+    }
+}
+
+/// A directory a render saves its progress in after every
+/// segment, along with the state of the graph's feedback
+/// nodes, see [`ShaderGraph::save_state`].
+pub struct Checkpoint {
+    dir: PathBuf,
+}
+
+impl Checkpoint {
+    pub fn new(dir: &Path) -> Checkpoint {
+        Checkpoint {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    /// Checks that a render of `graph` to `outputs` can be
+    /// carried on from a checkpoint. Anything that can't be
+    /// saved would start over, and the render come out
+    /// different from one in one go.
+    pub fn check(
+        graph: &ShaderGraph,
+        outputs: &[SinkSpec],
+    ) -> Result<(), String> {
+        if !graph.get_inputs().is_empty() {
+            return Err("Renders with inputs can't be checkpointed, as \
+                        inputs can't be resumed"
+                .to_string());
+        }
+        let unsaved = graph.unsaved_state();
+        if !unsaved.is_empty() {
+            return Err(format!(
+                "The render can't be checkpointed, as the state of {} \
+                 can't be saved; name recurrent nodes to save theirs",
+                unsaved.join(", ")
+            ));
+        }
+        // a pipeline would start over after each checkpoint
+        if let Some(output) =
+            outputs.iter().find(|o| o.gst_pipeline().is_some())
+        {
+            return Err(format!(
+                "`{}` can't be checkpointed, as GStreamer pipelines can't \
+                 be resumed",
+                output.path.display()
+            ));
+        }
+        Ok(())
+    }
+
+    /// The progress saved by an earlier run of the render
+    /// `progress` describes, to carry on from, if there is
+    /// one, see [`Progress::continues`].
+    pub fn resume(
+        &self,
+        progress: &Progress,
+        seeded: bool,
+    ) -> Result<Option<Progress>, String> {
+        match self.load()? {
+            Some(saved) if saved.continues(progress, seeded) => Ok(Some(saved)),
+            Some(_) => {
+                eprintln!(
+                    "[warn] The checkpoint in `{}` is of another render, \
+                     starting over",
+                    self.dir.display()
+                );
+                Ok(None)
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// The progress saved last, if any has been.
+    pub fn load(&self) -> Result<Option<Progress>, String> {
+        let path = self.dir.join(PROGRESS_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let json: Value = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+            .map_err(|e| {
+                format!("Could not read `{}`: {}", path.display(), e)
+            })?;
+        Progress::from_json(&json).map(Some)
+    }
+
+    /// Where the state of the graph after `done` segments
+    /// is saved.
+    pub fn state(&self, done: u32) -> PathBuf {
+        self.dir.join(format!("state-{}.sgs", done))
+    }
+
+    /// Saves the state of `graph` and then `progress`, so
+    /// that progress never runs ahead of the state saved,
+    /// even if the render is stopped partway. The state of
+    /// the checkpoint before is removed.
+    pub fn save(
+        &self,
+        progress: &Progress,
+        graph: &ShaderGraph,
+    ) -> Result<(), String> {
+        fs::create_dir_all(&self.dir).map_err(|e| {
+            format!("Could not create `{}`: {}", self.dir.display(), e)
+        })?;
+        graph.save_state(self.state(progress.done))?;
+
+        // renamed into place, so it's never half written
+        let path = self.dir.join(PROGRESS_FILE);
+        let written = self.dir.join(format!("{}.tmp", PROGRESS_FILE));
+        let json = serde_json::to_string_pretty(&progress.to_json()).unwrap();
+        fs::write(&written, json + "\n")
+            .and_then(|_| fs::rename(&written, &path))
+            .map_err(|e| {
+                format!("Could not write `{}`: {}", path.display(), e)
+            })?;
+
+        if let Some(before) = progress.done.checked_sub(1) {
+            let _ = fs::remove_file(self.state(before));
+        }
+        Ok(())
+    }
+
+    /// Puts together the parts every segment wrote of
+    /// `outputs`, removing them, and then the checkpoint,
+    /// once the last segment is done.
+    pub fn finish(&self, outputs: &[SinkSpec]) -> Result<(), String> {
+        for output in outputs.iter().filter(|output| !output.is_stream()) {
+            shard::remove_parts(&shard::merge(&output.path)?);
+        }
+        if let Err(e) = self.remove() {
+            eprintln!("[warn] {}", e);
+        }
+        Ok(())
+    }
+
+    /// Removes the checkpoint, once the render is done.
+    pub fn remove(&self) -> Result<(), String> {
+        fs::remove_dir_all(&self.dir).map_err(|e| {
+            format!("Could not remove `{}`: {}", self.dir.display(), e)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_renders_into_aligned_segments() {
+        let progress = |every, align| {
+            Progress::new("graph".into(), 0, (10, 110), every, align)
+        };
+        assert_eq!(progress(None, 1).segments, 1);
+        assert_eq!(progress(Some(30), 1).segments, 4);
+        // no shorter than the outputs are aligned to
+        assert_eq!(progress(Some(30), 40).segments, 2);
+
+        let mut resumed = progress(Some(30), 5);
+        resumed.done = 2;
+        assert_eq!(resumed.segment(), Shard { index: 3, count: 4 });
+        assert_eq!(resumed.segment_frames(5), (60, 85));
+        assert_eq!(resumed.next_frame(5), 60);
+        resumed.done = 4;
+        assert_eq!(resumed.next_frame(5), 110);
+    }
+}
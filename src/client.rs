@@ -0,0 +1,149 @@
+use std::{
+    io::{
+        BufRead,
+        BufReader,
+        Write,
+    },
+    net::TcpStream,
+    path::Path,
+};
+
+use serde_json::Value;
+
+use crate::control::quote;
+
+/// Controls a running instance over TCP, started with
+/// `shadergarden run --listen <address>`. The protocol is
+/// one [`crate::control::Command`] per line, each answered
+/// with a line, so it is simple enough to speak from any
+/// language, as `python/shadergarden_client.py` does.
+pub struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Client {
+    pub fn connect(address: &str) -> Result<Client, String> {
+        let writer = TcpStream::connect(address).map_err(|e| {
+            format!("Could not connect to `{}`: {}", address, e)
+        })?;
+        let reader = writer
+            .try_clone()
+            .map(BufReader::new)
+            .map_err(|e| format!("{}", e))?;
+        Ok(Client { reader, writer })
+    }
+
+    /// Rebuilds the graph, even if nothing has changed.
+    pub fn reload(&mut self) -> Result<(), String> { self.command("reload") }
+
+    /// Stops or resumes running the graph.
+    pub fn pause(&mut self) -> Result<(), String> { self.command("pause") }
+
+    /// Saves the output to a png on the machine running
+    /// shadergarden, relative to its project directory.
+    pub fn screenshot(&mut self, path: &Path) -> Result<(), String> {
+        let path = path.to_string_lossy();
+        self.command(&format!("screenshot {}", quote(&path)))
+    }
+
+    /// Sets the `u_<uniform>` uniform of a named node.
+    pub fn set(
+        &mut self,
+        node: &str,
+        uniform: &str,
+        value: f32,
+    ) -> Result<(), String> {
+        let target = format!("{}.{}", node, uniform);
+        self.command(&format!("set {} {}", quote(&target), value))
+    }
+
+    /// Saves the state of feedback nodes to a file on the
+    /// machine running shadergarden, relative to its
+    /// project directory.
+    pub fn save_state(&mut self, path: &Path) -> Result<(), String> {
+        let path = path.to_string_lossy();
+        self.command(&format!("save-state {}", quote(&path)))
+    }
+
+    /// Restores state saved with [`Client::save_state`].
+    pub fn load_state(&mut self, path: &Path) -> Result<(), String> {
+        let path = path.to_string_lossy();
+        self.command(&format!("load-state {}", quote(&path)))
+    }
+
+    /// Switches to a palette by name, or to the next one,
+    /// see [`crate::graph::ShaderGraph::set_palette`].
+    pub fn palette(&mut self, name: Option<&str>) -> Result<(), String> {
+        match name {
+            Some(name) => self.command(&format!("palette {}", quote(name))),
+            None => self.command("palette"),
+        }
+    }
+
+    /// Switches scenes, showing one of the sub-projects of
+    /// a project by name, see [`crate::reload::Projects`].
+    pub fn project(&mut self, name: &str) -> Result<(), String> {
+        self.command(&format!("project {}", quote(name)))
+    }
+
+    /// Shows the next sub-project that builds.
+    pub fn next_project(&mut self) -> Result<(), String> {
+        self.command("next-project")
+    }
+
+    /// Shows the previous sub-project that builds.
+    pub fn previous_project(&mut self) -> Result<(), String> {
+        self.command("previous-project")
+    }
+
+    /// Makes a named random texture, or every one, from a
+    /// new seed, see
+    /// [`crate::graph::ShaderGraph::regenerate`].
+    pub fn regenerate(&mut self, node: Option<&str>) -> Result<(), String> {
+        match node {
+            Some(node) => self.command(&format!("regenerate {}", quote(node))),
+            None => self.command("regenerate"),
+        }
+    }
+
+    /// Pauses, and runs the graph for a single frame.
+    pub fn step(&mut self) -> Result<(), String> { self.command("step") }
+
+    /// Moves the graph's clock to a time, in seconds.
+    pub fn seek(&mut self, time: f32) -> Result<(), String> {
+        self.command(&format!("seek {}", time))
+    }
+
+    /// Sets the speed of the graph's clock, where 1 is real
+    /// time.
+    pub fn speed(&mut self, speed: f32) -> Result<(), String> {
+        self.command(&format!("speed {}", speed))
+    }
+
+    /// Asks for the frame count, late and dropped frames,
+    /// frame time, clock, and named nodes, see
+    /// [`crate::control::Status`].
+    pub fn status(&mut self) -> Result<Value, String> {
+        let reply = self.request("status")?;
+        serde_json::from_str(&reply).map_err(|e| format!("{}", e))
+    }
+
+    fn command(&mut self, line: &str) -> Result<(), String> {
+        match self.request(line)?.as_str() {
+            "ok" => Ok(()),
+            reply => Err(reply.strip_prefix("error ").unwrap_or(reply).into()),
+        }
+    }
+
+    /// Sends a line and waits for the reply.
+    fn request(&mut self, line: &str) -> Result<String, String> {
+        writeln!(self.writer, "{}", line).map_err(|e| format!("{}", e))?;
+        let mut reply = String::new();
+        match self.reader.read_line(&mut reply) {
+            Ok(0) => Err("shadergarden closed the connection".to_string()),
+            Ok(_) => Ok(reply.trim_end().to_string()),
+            Err(e) => Err(format!("{}", e)),
+        }
+    }
+}
@@ -0,0 +1,420 @@
+use std::{
+    convert::TryFrom,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use crate::reload::config_dir;
+
+/// Defaults for command line flags, loaded from
+/// `config.toml` in the user's [`config_dir`]. Flags passed
+/// on the command line always win.
+///
+/// ```toml
+/// width = 1280
+/// height = 720
+/// vsync = true
+/// keymap = "~/keys.lisp"
+/// favorites = ["~/gardens", "~/work/shaders"]
+/// ```
+///
+/// It's read as a subset of TOML: top-level `key = value`
+/// pairs, of strings, in double or single quotes, integers,
+/// booleans, and arrays of strings, which can span lines.
+/// Anything else, like tables, is an error.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub width:     Option<u32>,
+    pub height:    Option<u32>,
+    pub vsync:     Option<bool>,
+    pub keymap:    Option<PathBuf>,
+    /// Directories searched for projects that are not
+    /// found in the current directory, in order.
+    pub favorites: Vec<PathBuf>,
+}
+
+/// A value on the right hand side of a `key = value` pair.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    String(String),
+    Integer(i64),
+    Bool(bool),
+    Array(Vec<String>),
+}
+
+impl Config {
+    /// Loads the user's config, or the defaults if there is
+    /// none.
+    pub fn load() -> Result<Config, String> {
+        match config_dir() {
+            Some(dir) if dir.join("config.toml").is_file() => {
+                Config::load_from(&dir.join("config.toml"))
+            },
+            _ => Ok(Config::default()),
+        }
+    }
+
+    /// Loads a config from a file.
+    pub fn load_from(path: &Path) -> Result<Config, String> {
+        let source = fs::read_to_string(path).map_err(|e| {
+            format!("Could not read config `{}`: {}", path.display(), e)
+        })?;
+        Config::parse(&source)
+            .map_err(|e| format!("In config `{}`: {}", path.display(), e))
+    }
+
+    fn parse(source: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+        let chars = source.chars().collect::<Vec<_>>();
+        let mut parser = Parser {
+            chars: &chars,
+            at:    0,
+            line:  1,
+        };
+        let mut seen = vec![];
+
+        while let Some((line, key, value)) = parser.pair()? {
+            let at_line = |e: String| format!("line {}: {}", line, e);
+            if seen.contains(&key) {
+                return Err(at_line(format!("`{}` is set twice", key)));
+            }
+            seen.push(key.clone());
+            let key = key.as_str();
+
+            match (key, value) {
+                ("width", Value::Integer(width)) => {
+                    config.width = Some(positive(key, width).map_err(at_line)?)
+                },
+                ("height", Value::Integer(height)) => {
+                    config.height =
+                        Some(positive(key, height).map_err(at_line)?)
+                },
+                ("vsync", Value::Bool(vsync)) => config.vsync = Some(vsync),
+                ("keymap", Value::String(path)) => {
+                    config.keymap = Some(expand_home(&path))
+                },
+                ("favorites", Value::Array(paths)) => {
+                    config.favorites =
+                        paths.iter().map(|p| expand_home(p)).collect()
+                },
+                ("width", _)
+                | ("height", _)
+                | ("vsync", _)
+                | ("keymap", _)
+                | ("favorites", _) => {
+                    return Err(at_line(format!(
+                        "`{}` has the wrong type of value",
+                        key
+                    )))
+                },
+                (other, _) => {
+                    return Err(at_line(format!("Unknown key `{}`", other)))
+                },
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Finds a project by name: as a path, if it exists,
+    /// and otherwise in each favorite directory in turn.
+    pub fn find_project(&self, project: &Path) -> PathBuf {
+        if project.exists() {
+            return project.to_path_buf();
+        }
+
+        self.favorites
+            .iter()
+            .map(|favorite| favorite.join(project))
+            .find(|path| path.is_dir())
+            .unwrap_or_else(|| project.to_path_buf())
+    }
+}
+
+/// Reads the subset of TOML a config is written in, see
+/// [`Config`], and errors on the rest of TOML, rather than
+/// misreading it.
+struct Parser<'a> {
+    chars: &'a [char],
+    at:    usize,
+    line:  usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> { self.chars.get(self.at).copied() }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        if c == '\n' {
+            self.line += 1;
+        }
+        self.at += 1;
+        Some(c)
+    }
+
+    fn at_line(&self, e: impl AsRef<str>) -> String {
+        format!("line {}: {}", self.line, e.as_ref())
+    }
+
+    /// Skips spaces and tabs, and newlines and comments too
+    /// if `newlines`.
+    fn blank(&mut self, newlines: bool) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' => (),
+                '\n' if newlines => (),
+                '#' => {
+                    while !matches!(self.peek(), None | Some('\n')) {
+                        self.bump();
+                    }
+                    continue;
+                },
+                _ => break,
+            }
+            self.bump();
+        }
+    }
+
+    /// The next `key = value` pair and the line it's on, or
+    /// `None` at the end of the file.
+    fn pair(&mut self) -> Result<Option<(usize, String, Value)>, String> {
+        self.blank(true);
+        let line = self.line;
+        let key = match self.peek() {
+            None => return Ok(None),
+            Some('[') => {
+                return Err(self.at_line(
+                    "Tables aren't supported, only `key = value` pairs",
+                ))
+            },
+            Some('"') | Some('\'') => {
+                return Err(self.at_line("Quoted keys aren't supported"))
+            },
+            Some(_) => self.bare(),
+        };
+        if key.is_empty() {
+            return Err(self.at_line("Expected `key = value`"));
+        }
+        self.blank(false);
+        match self.bump() {
+            Some('=') => (),
+            Some('.') => {
+                return Err(self.at_line("Dotted keys aren't supported"))
+            },
+            _ => return Err(self.at_line("Expected `key = value`")),
+        }
+        self.blank(false);
+        let value = self.value()?;
+        self.blank(false);
+        match self.bump() {
+            None | Some('\n') => Ok(Some((line, key, value))),
+            Some(c) => Err(self.at_line(format!(
+                "Expected the end of the line after the value, found `{}`",
+                c
+            ))),
+        }
+    }
+
+    /// A bare key or value, like `width` or `1_280`.
+    fn bare(&mut self) -> String {
+        let mut bare = String::new();
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_alphanumeric() || "_-+".contains(c)) {
+                break;
+            }
+            bare.push(c);
+            self.bump();
+        }
+        bare
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('[') => self.array(),
+            Some('"') | Some('\'') => self.string().map(Value::String),
+            Some('{') => Err(self.at_line("Inline tables aren't supported")),
+            _ => {
+                let value = self.bare();
+                match value.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    number => number
+                        .replace('_', "")
+                        .parse()
+                        .map(Value::Integer)
+                        .map_err(|_| {
+                            self.at_line(format!(
+                                "Could not read value `{}`",
+                                value
+                            ))
+                        }),
+                }
+            },
+        }
+    }
+
+    /// An array of strings, over as many lines as it takes,
+    /// with an optional trailing comma.
+    fn array(&mut self) -> Result<Value, String> {
+        self.bump();
+        let mut strings = vec![];
+        loop {
+            self.blank(true);
+            if self.peek() == Some(']') {
+                self.bump();
+                return Ok(Value::Array(strings));
+            }
+            match self.value()? {
+                Value::String(string) => strings.push(string),
+                _ => return Err(self.at_line("Expected an array of strings")),
+            }
+            self.blank(true);
+            match self.bump() {
+                Some(',') => (),
+                Some(']') => return Ok(Value::Array(strings)),
+                _ => return Err(self.at_line("Expected `,` or `]`")),
+            }
+        }
+    }
+
+    /// A basic string, in double quotes with escapes, or a
+    /// literal one, in single quotes without.
+    fn string(&mut self) -> Result<String, String> {
+        let quote = self.bump().unwrap();
+        if self.peek() == Some(quote)
+            && self.chars.get(self.at + 1) == Some(&quote)
+        {
+            return Err(self.at_line("Multi-line strings aren't supported"));
+        }
+        let mut string = String::new();
+        loop {
+            match self.bump() {
+                Some(c) if c == quote => return Ok(string),
+                None | Some('\n') => {
+                    return Err(self.at_line("Unclosed string"))
+                },
+                Some('\\') if quote == '"' => string.push(self.escape()?),
+                Some(c) => string.push(c),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, String> {
+        let c = match self.bump() {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some(c @ 'u') | Some(c @ 'U') => {
+                let digits = if c == 'u' { 4 } else { 8 };
+                let hex = (0..digits).filter_map(|_| self.bump());
+                let hex = hex.collect::<String>();
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| {
+                        self.at_line(format!("Invalid escape `\\{}{}`", c, hex))
+                    })?
+            },
+            Some(c) => {
+                return Err(self.at_line(format!("Invalid escape `\\{}`", c)))
+            },
+            None => return Err(self.at_line("Unclosed string")),
+        };
+        Ok(c)
+    }
+}
+
+fn positive(key: &str, value: i64) -> Result<u32, String> {
+    match u32::try_from(value) {
+        Ok(value) if value > 0 => Ok(value),
+        _ => Err(format!("`{}` must be a positive number", key)),
+    }
+}
+
+/// Expands a leading `~` to the home directory.
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> String { Config::parse(source).unwrap_err() }
+
+    #[test]
+    fn parses_pairs() {
+        let config = Config::parse(
+            "# defaults\nwidth = 1_280 # wide\nheight=720\n\nvsync = true\n",
+        )
+        .unwrap();
+        assert_eq!(config.width, Some(1280));
+        assert_eq!(config.height, Some(720));
+        assert_eq!(config.vsync, Some(true));
+    }
+
+    #[test]
+    fn parses_strings() {
+        let keymap = |source| Config::parse(source).unwrap().keymap.unwrap();
+        assert_eq!(
+            keymap("keymap = \"keys # 1.lisp\""),
+            Path::new("keys # 1.lisp")
+        );
+        assert_eq!(
+            keymap("keymap = 'C:\\keys.lisp'"),
+            Path::new("C:\\keys.lisp")
+        );
+        assert_eq!(
+            keymap("keymap = \"say \\\"hi\\\"\\\\\\u00e9.lisp\""),
+            Path::new("say \"hi\"\\é.lisp")
+        );
+        assert!(error("keymap = \"keys.lisp").contains("Unclosed"));
+        assert!(error("keymap = \"\\q\"").contains("Invalid escape"));
+        assert!(error("keymap = \"\"\"keys\"\"\"").contains("Multi-line"));
+    }
+
+    #[test]
+    fn parses_arrays() {
+        let favorites = |source| Config::parse(source).unwrap().favorites;
+        assert_eq!(favorites("favorites = [\"a, b\", 'c]', \"d\\\"\"]"), vec![
+            PathBuf::from("a, b"),
+            PathBuf::from("c]"),
+            PathBuf::from("d\""),
+        ]);
+        assert_eq!(
+            favorites(
+                "favorites = [\n  \"a\", # first\n  \"b\",\n]\nwidth = 1"
+            ),
+            vec![PathBuf::from("a"), PathBuf::from("b")]
+        );
+        assert_eq!(favorites("favorites = []"), Vec::<PathBuf>::new());
+        assert!(error("favorites = [1]").contains("array of strings"));
+        assert!(error("favorites = [\"a\" \"b\"]").contains("`,` or `]`"));
+    }
+
+    #[test]
+    fn refuses_the_rest_of_toml() {
+        assert!(error("[window]\nwidth = 1").contains("line 1: Tables"));
+        assert!(error("width = 1\n[[a]]").contains("line 2: Tables"));
+        assert!(error("window.width = 1").contains("Dotted"));
+        assert!(error("\"width\" = 1").contains("Quoted"));
+        assert!(error("width = { a = 1 }").contains("Inline tables"));
+        assert!(error("width = 1.5").contains("end of the line"));
+        assert!(error("width = 1 2").contains("end of the line"));
+        assert!(error("width = 1\nwidth = 2")
+            .contains("line 2: `width` is set twice"));
+        assert!(error("depth = 1").contains("Unknown key"));
+        assert!(error("width = \"1\"").contains("wrong type"));
+        assert!(error("width = 0").contains("positive"));
+    }
+}
@@ -0,0 +1,683 @@
+use std::{
+    fs::File,
+    io::{
+        self,
+        BufRead,
+        BufReader,
+        Write,
+    },
+    net::{
+        TcpListener,
+        TcpStream,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::{
+        mpsc::{
+            self,
+            Receiver,
+            Sender,
+        },
+        Arc,
+        Mutex,
+    },
+    thread,
+};
+
+use crate::{
+    graph::ShaderGraph,
+    keymap::Action,
+    pacing::PacingStats,
+    reload::shader_dir::{
+        resolve,
+        shader_name,
+    },
+};
+
+/// Something a running instance is told to do from
+/// outside, one per line:
+///
+/// ```text
+/// reload
+/// pause
+/// screenshot "renders/take 2.png"
+/// set bloom.intensity 2.0
+/// ```
+///
+/// Words are separated by whitespace, unless quoted, see
+/// [`quote`].
+///
+/// Over TCP, `status` is also understood, see [`Status`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Rebuilds the graph, even if nothing has changed.
+    Reload,
+    /// Stops or resumes running the graph.
+    Pause,
+    /// Saves the output to a png, at a timestamped path if
+    /// none is given.
+    Screenshot(Option<PathBuf>),
+    /// Sets the `u_<uniform>` uniform of a named node.
+    Set {
+        node:    String,
+        uniform: String,
+        value:   f32,
+    },
+    /// Saves the state of feedback nodes, see
+    /// [`crate::graph::ShaderGraph::save_state`].
+    SaveState(PathBuf),
+    /// Restores state saved with `SaveState`.
+    LoadState(PathBuf),
+    /// Switches to a palette by name, or to the next one.
+    Palette(Option<String>),
+    /// Pauses, and runs the graph for a single frame.
+    Step,
+    /// Moves the graph's clock to a time, in seconds.
+    Seek(f32),
+    /// Sets the speed of the graph's clock, where 1 is real
+    /// time.
+    Speed(f32),
+    /// Shows a sub-project by name, see
+    /// [`crate::reload::Projects`].
+    Project(String),
+    /// Shows the next sub-project that builds.
+    NextProject,
+    /// Shows the previous sub-project that builds.
+    PreviousProject,
+    /// Makes a named random texture, or every one, from a
+    /// new seed, see
+    /// [`crate::graph::ShaderGraph::regenerate`].
+    Regenerate(Option<String>),
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let words = words(line)?;
+        let words = words.iter().map(String::as_str).collect::<Vec<_>>();
+        match words.as_slice() {
+            ["reload"] => Ok(Command::Reload),
+            ["pause"] => Ok(Command::Pause),
+            ["screenshot"] => Ok(Command::Screenshot(None)),
+            ["screenshot", path] => {
+                Ok(Command::Screenshot(Some(PathBuf::from(path))))
+            },
+            ["set", target, value] => {
+                let (node, uniform) =
+                    target.split_once('.').ok_or_else(|| {
+                        format!(
+                            "Expected `<node>.<uniform>`, found `{}`",
+                            target
+                        )
+                    })?;
+                let value = value
+                    .parse()
+                    .map_err(|_| format!("`{}` is not a number", value))?;
+                Ok(Command::Set {
+                    node: node.to_string(),
+                    uniform: uniform.to_string(),
+                    value,
+                })
+            },
+            ["save-state", path] => Ok(Command::SaveState(PathBuf::from(path))),
+            ["load-state", path] => Ok(Command::LoadState(PathBuf::from(path))),
+            ["palette"] => Ok(Command::Palette(None)),
+            ["palette", name] => Ok(Command::Palette(Some(name.to_string()))),
+            ["step"] => Ok(Command::Step),
+            ["seek", time] => time
+                .parse()
+                .map(Command::Seek)
+                .map_err(|_| format!("`{}` is not a number", time)),
+            ["speed", speed] => speed
+                .parse()
+                .map(Command::Speed)
+                .map_err(|_| format!("`{}` is not a number", speed)),
+            ["project", name] => Ok(Command::Project(name.to_string())),
+            ["next-project"] => Ok(Command::NextProject),
+            ["previous-project"] => Ok(Command::PreviousProject),
+            ["regenerate"] => Ok(Command::Regenerate(None)),
+            ["regenerate", node] => {
+                Ok(Command::Regenerate(Some(node.to_string())))
+            },
+            _ => Err(format!("Unknown command `{}`", line)),
+        }
+    }
+
+    /// Resolves the paths of a command against `root`, and
+    /// refuses paths outside it unless `allow_outside`, see
+    /// [`crate::reload::shader_dir::resolve`]. Paths that
+    /// are written to can't be graphs, shaders, or other
+    /// files a project is made of, so that they can't be
+    /// overwritten.
+    pub fn confine(
+        self,
+        root: &Path,
+        allow_outside: bool,
+    ) -> Result<Command, String> {
+        let confine = |path: PathBuf| {
+            resolve(root, &path.to_string_lossy(), allow_outside)
+        };
+        let output = |path: PathBuf| {
+            let path = confine(path)?;
+            let extension = path.extension().and_then(|e| e.to_str());
+            let shader = shader_name(&path).ok().flatten().is_some();
+            match extension {
+                Some(extension)
+                    if shader || PROJECT_FILES.contains(&extension) =>
+                {
+                    Err(format!(
+                        "Won't write to `{}`, as it's named like a file of \
+                         the project",
+                        path.display()
+                    ))
+                },
+                _ => Ok(path),
+            }
+        };
+        Ok(match self {
+            Command::Screenshot(Some(path)) => {
+                Command::Screenshot(Some(output(path)?))
+            },
+            Command::SaveState(path) => Command::SaveState(output(path)?),
+            Command::LoadState(path) => Command::LoadState(confine(path)?),
+            command => command,
+        })
+    }
+}
+
+/// What a command asks of the frame it's taken in: the
+/// action of the key binding it acts like, if any, and what
+/// it asks of the graph, see [`Requests::apply`].
+#[derive(Debug, Default, PartialEq)]
+pub struct Requests {
+    pub action:     Option<Action>,
+    /// Where to save the screenshot, if not at a
+    /// timestamped path.
+    pub screenshot: Option<PathBuf>,
+    /// A node, uniform, and value to set it to.
+    pub set:        Option<(String, String, f32)>,
+    pub save_state: Option<PathBuf>,
+    pub load_state: Option<PathBuf>,
+    pub palette:    Option<String>,
+    pub regenerate: Option<String>,
+    pub seek:       Option<f32>,
+    pub speed:      Option<f32>,
+    /// A sub-project to show, by name, see
+    /// [`crate::reload::Projects::wanted`].
+    pub project:    Option<String>,
+}
+
+impl Requests {
+    pub fn new(command: Option<Command>) -> Requests {
+        let mut requests = Requests::default();
+        match command {
+            Some(Command::Reload) => requests.action = Some(Action::Reload),
+            Some(Command::Pause) => requests.action = Some(Action::Pause),
+            Some(Command::Screenshot(path)) => {
+                requests.action = Some(Action::Screenshot);
+                requests.screenshot = path;
+            },
+            Some(Command::Set {
+                node,
+                uniform,
+                value,
+            }) => requests.set = Some((node, uniform, value)),
+            Some(Command::SaveState(path)) => requests.save_state = Some(path),
+            Some(Command::LoadState(path)) => requests.load_state = Some(path),
+            Some(Command::Palette(None)) => {
+                requests.action = Some(Action::Palette)
+            },
+            Some(Command::Palette(name)) => requests.palette = name,
+            Some(Command::Step) => requests.action = Some(Action::StepFrame),
+            Some(Command::Seek(time)) => requests.seek = Some(time),
+            Some(Command::Speed(speed)) => requests.speed = Some(speed),
+            Some(Command::Project(name)) => requests.project = Some(name),
+            Some(Command::NextProject) => {
+                requests.action = Some(Action::NextProject)
+            },
+            Some(Command::PreviousProject) => {
+                requests.action = Some(Action::PreviousProject)
+            },
+            Some(Command::Regenerate(None)) => {
+                requests.action = Some(Action::Regenerate)
+            },
+            Some(Command::Regenerate(name)) => requests.regenerate = name,
+            None => (),
+        }
+        requests
+    }
+
+    /// Saves the graph's state, switches palettes,
+    /// regenerates a random texture, and moves or speeds up
+    /// the clock, as asked. `palette` is the palette picked
+    /// while running, which is kept across reloads.
+    pub fn apply(
+        &mut self,
+        graph: &mut ShaderGraph,
+        palette: &mut Option<String>,
+    ) {
+        if let Some(path) = self.save_state.take() {
+            match graph.save_state(&path) {
+                Ok(saved) => eprintln!(
+                    "[info] Saved the state of {} node(s) to `{}`",
+                    saved,
+                    path.display()
+                ),
+                Err(e) => eprintln!("[warn] {}", e),
+            }
+        }
+        if let Some(name) = self.palette.take() {
+            match graph.set_palette(&name) {
+                Ok(()) => {
+                    eprintln!("[info] Switched to palette `{}`", name);
+                    *palette = Some(name);
+                },
+                Err(e) => eprintln!("[warn] {}", e),
+            }
+        }
+        if let Some(name) = self.regenerate.take() {
+            match graph.node_id(&name).map(|id| graph.regenerate(Some(id))) {
+                Some(Ok(_)) => eprintln!("[info] Regenerated `{}`", name),
+                Some(Err(_)) => {
+                    eprintln!("[warn] `{}` is not a random texture", name)
+                },
+                None => eprintln!("[warn] There is no node named `{}`", name),
+            }
+        }
+        if let Some(time) = self.seek.take() {
+            graph.seek(time);
+            eprintln!("[info] Moved to {:.2}s", time);
+        }
+        if let Some(speed) = self.speed.take() {
+            graph.set_speed(speed);
+            eprintln!("[info] Running at {}x speed", speed);
+        }
+    }
+}
+
+/// The extensions of files, other than shaders, that a
+/// project is made of, which commands never write to, see
+/// [`Command::confine`].
+const PROJECT_FILES: &[&str] = &["graph", "lisp", "toml", "lock"];
+
+/// Splits a command into words, at whitespace outside of
+/// double quotes. In quotes, `\"` is a quote and `\\` a
+/// backslash; elsewhere, backslashes are kept as they are,
+/// as in Windows paths.
+fn words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = vec![];
+    let mut chars = line.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let mut word = String::new();
+        if c == '"' {
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') if matches!(chars.peek(), Some('"' | '\\')) => {
+                        word.extend(chars.next())
+                    },
+                    Some(c) => word.push(c),
+                    None => {
+                        return Err(format!("Unclosed quote in `{}`", line))
+                    },
+                }
+            }
+        } else {
+            word.push(c);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                word.push(c);
+            }
+        }
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Quotes a word of a command, if it needs it, so that it's
+/// read back as it is, see [`Command::parse`].
+pub fn quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && !word.starts_with('"')
+        && !word.contains(char::is_whitespace);
+    match plain {
+        true => word.to_string(),
+        false => {
+            let escaped = word.replace('\\', "\\\\").replace('"', "\\\"");
+            format!("\"{}\"", escaped)
+        },
+    }
+}
+
+/// What a running instance reports when asked for its
+/// `status` over TCP, as a single line of JSON.
+#[derive(Debug, Clone, Default)]
+pub struct Status {
+    /// Frames presented so far, and how many of them were
+    /// late or dropped.
+    pub pacing:   PacingStats,
+    /// How long the last frame took to render.
+    pub frame_ms: f64,
+    pub paused:   bool,
+    /// The time on the graph's clock, and its speed.
+    pub time:     f32,
+    pub speed:    f32,
+    /// Named nodes, ordered by name.
+    pub nodes:    Vec<String>,
+    pub soloed:   Option<String>,
+}
+
+impl Status {
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "frames": self.pacing.frames,
+            "late": self.pacing.late,
+            "dropped": self.pacing.dropped,
+            "worst_ms": self.pacing.worst.as_secs_f64() * 1000.0,
+            "frame_ms": self.frame_ms,
+            "paused": self.paused,
+            "time": self.time,
+            "speed": self.speed,
+            "nodes": self.nodes,
+            "soloed": self.soloed,
+        })
+    }
+}
+
+/// Reads commands from stdin, a named pipe, or TCP
+/// connections on background threads, so that a running
+/// instance can be scripted from a shell or controlled
+/// remotely, see [`crate::client::Client`].
+pub struct Control {
+    tx:     Sender<Command>,
+    rx:     Receiver<Command>,
+    status: Arc<Mutex<Status>>,
+}
+
+impl Default for Control {
+    fn default() -> Self { Control::new() }
+}
+
+impl Control {
+    /// Creates a control that reads from nothing yet.
+    pub fn new() -> Control {
+        let (tx, rx) = mpsc::channel();
+        Control {
+            tx,
+            rx,
+            status: Arc::new(Mutex::new(Status::default())),
+        }
+    }
+
+    /// Reads commands from `path`, which must be a named
+    /// pipe (see `mkfifo`), or from stdin if `path` is `-`.
+    /// The pipe is reopened whenever a writer closes it.
+    pub fn read_from(&self, path: &Path) -> Result<(), String> {
+        let tx = self.tx.clone();
+
+        if path == Path::new("-") {
+            thread::spawn(move || {
+                let stdin = io::stdin();
+                read_commands(stdin.lock(), &tx);
+            });
+            return Ok(());
+        }
+
+        if !is_fifo(path) {
+            return Err(format!(
+                "`{}` is not a named pipe, create it with `mkfifo`",
+                path.display()
+            ));
+        }
+
+        let path = path.to_path_buf();
+        thread::spawn(move || loop {
+            // blocks until something opens the pipe to write
+            match File::open(&path) {
+                Ok(file) => {
+                    if !read_commands(BufReader::new(file), &tx) {
+                        return;
+                    }
+                },
+                Err(e) => {
+                    let path = path.display();
+                    eprintln!("[warn] Could not open `{}`: {}", path, e);
+                    return;
+                },
+            }
+        });
+        Ok(())
+    }
+
+    /// Accepts TCP connections on `address`, e.g.
+    /// `127.0.0.1:7777`. Every line sent is answered with
+    /// `ok`, `error <message>`, or the status as JSON.
+    /// Anyone who can connect can send commands, so the
+    /// files they name are confined to `root`, see
+    /// [`Command::confine`].
+    pub fn listen(
+        &self,
+        address: &str,
+        root: &Path,
+        allow_outside: bool,
+    ) -> Result<(), String> {
+        let listener = TcpListener::bind(address)
+            .map_err(|e| format!("Could not listen on `{}`: {}", address, e))?;
+
+        let tx = self.tx.clone();
+        let status = self.status.clone();
+        let root = root.to_path_buf();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let tx = tx.clone();
+                let status = status.clone();
+                let root = root.clone();
+                thread::spawn(move || {
+                    let confine = |command: Command| {
+                        command.confine(&root, allow_outside)
+                    };
+                    if let Err(e) = serve(stream, &tx, &status, confine) {
+                        eprintln!("[warn] Control connection error: {}", e);
+                    }
+                });
+            }
+        });
+        Ok(())
+    }
+
+    /// Updates what is reported to `status` requests.
+    pub fn set_status(&self, status: Status) {
+        if let Ok(mut current) = self.status.lock() {
+            *current = status;
+        }
+    }
+
+    /// Takes the next command that has been received, if
+    /// any.
+    pub fn next(&self) -> Option<Command> { self.rx.try_recv().ok() }
+}
+
+/// Sends each command read to `tx`, until the end of the
+/// input. Returns false if nothing is listening anymore.
+fn read_commands<R: BufRead>(reader: R, tx: &Sender<Command>) -> bool {
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("[warn] Could not read command: {}", e);
+                break;
+            },
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match Command::parse(&line) {
+            Ok(command) => {
+                if tx.send(command).is_err() {
+                    return false;
+                }
+            },
+            Err(e) => eprintln!("[warn] {}", e),
+        }
+    }
+    true
+}
+
+/// Answers each line sent over a TCP connection, checking
+/// each command with `confine` first.
+fn serve(
+    stream: TcpStream,
+    tx: &Sender<Command>,
+    status: &Mutex<Status>,
+    confine: impl Fn(Command) -> Result<Command, String>,
+) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        let reply = match line.trim() {
+            "" => continue,
+            "status" => match status.lock() {
+                Ok(status) => status.to_json().to_string(),
+                Err(_) => "error status is unavailable".to_string(),
+            },
+            line => match Command::parse(line)
+                .and_then(&confine)
+                .map(|c| tx.send(c))
+            {
+                Ok(Ok(())) => "ok".to_string(),
+                Ok(Err(_)) => "error shadergarden is shutting down".to_string(),
+                Err(e) => format!("error {}", e),
+            },
+        };
+        writeln!(writer, "{}", reply)?;
+    }
+    Ok(())
+}
+
+#[cfg(target_family = "unix")]
+fn is_fifo(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    path.metadata()
+        .map(|m| m.file_type().is_fifo())
+        .unwrap_or(false)
+}
+
+#[cfg(not(target_family = "unix"))]
+fn is_fifo(_path: &Path) -> bool { false }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn confine(line: &str, allow_outside: bool) -> Result<Command, String> {
+        Command::parse(line)?.confine(Path::new("project"), allow_outside)
+    }
+
+    #[test]
+    fn acts_like_key_bindings() {
+        let requests = |line| Requests::new(Command::parse(line).ok());
+        assert_eq!(requests("palette").action, Some(Action::Palette));
+        assert_eq!(requests("palette warm").action, None);
+        assert_eq!(requests("palette warm").palette.as_deref(), Some("warm"));
+        let screenshot = requests("screenshot a.png");
+        assert_eq!(screenshot.action, Some(Action::Screenshot));
+        assert_eq!(screenshot.screenshot, Some(PathBuf::from("a.png")));
+        assert_eq!(
+            requests("set bloom.intensity 2").set,
+            Some(("bloom".to_string(), "intensity".to_string(), 2.0))
+        );
+        assert_eq!(requests("next-project").action, Some(Action::NextProject));
+        assert_eq!(Requests::new(None), Requests::default());
+    }
+
+    #[test]
+    fn confines_paths_to_the_project() {
+        assert_eq!(
+            confine("save-state states/a.state", false),
+            Ok(Command::SaveState(PathBuf::from("project/states/a.state")))
+        );
+        assert!(confine("screenshot ../out.png", false).is_err());
+        assert!(confine("load-state /etc/passwd", false).is_err());
+        assert!(confine("save-state a/../../b", false).is_err());
+    }
+
+    #[test]
+    fn allows_any_path_when_asked() {
+        assert_eq!(
+            confine("load-state /tmp/a.state", true),
+            Ok(Command::LoadState(PathBuf::from("/tmp/a.state")))
+        );
+    }
+
+    #[test]
+    fn leaves_other_commands_alone() {
+        assert_eq!(confine("screenshot", false), Ok(Command::Screenshot(None)));
+        assert_eq!(confine("pause", false), Ok(Command::Pause));
+    }
+
+    #[test]
+    fn reads_quoted_words() {
+        assert_eq!(
+            Command::parse("screenshot \"takes/take 2.png\""),
+            Ok(Command::Screenshot(Some(PathBuf::from("takes/take 2.png"))))
+        );
+        assert_eq!(
+            Command::parse("project  \"say \\\"hi\\\"\\\\\" "),
+            Ok(Command::Project("say \"hi\"\\".to_string()))
+        );
+        assert_eq!(
+            Command::parse("load-state C:\\states\\a.state"),
+            Ok(Command::LoadState(PathBuf::from("C:\\states\\a.state")))
+        );
+        assert!(Command::parse("screenshot \"out.png").is_err());
+        assert!(Command::parse("screenshot a b").is_err());
+    }
+
+    #[test]
+    fn quotes_words_that_need_it() {
+        for word in ["out.png", "take 2.png", "", "\"", "a\\\" b", "C:\\a b"] {
+            let line = format!("project {}", quote(word));
+            assert_eq!(
+                Command::parse(&line),
+                Ok(Command::Project(word.to_string())),
+                "{}",
+                line
+            );
+        }
+        assert_eq!(quote("out.png"), "out.png");
+    }
+
+    #[test]
+    fn never_writes_project_files() {
+        assert!(confine("screenshot shader.graph", false).is_err());
+        assert!(confine("screenshot blur.frag", false).is_err());
+        assert!(confine("save-state lib.glsl", false).is_err());
+        assert!(confine("save-state shader.lock", false).is_err());
+        assert!(confine("screenshot shots/a.png", false).is_ok());
+        // reading them is harmless
+        assert!(confine("load-state shader.graph", false).is_ok());
+    }
+
+    #[test]
+    fn reports_pacing() {
+        let status = Status {
+            pacing: PacingStats {
+                frames: 100,
+                late: 3,
+                dropped: 2,
+                worst: std::time::Duration::from_millis(50),
+                ..PacingStats::default()
+            },
+            ..Status::default()
+        };
+        let json = status.to_json();
+        assert_eq!(json["frames"], 100);
+        assert_eq!(json["late"], 3);
+        assert_eq!(json["dropped"], 2);
+        assert_eq!(json["worst_ms"], 50.0);
+    }
+}
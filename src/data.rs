@@ -0,0 +1,187 @@
+use std::path::Path;
+
+use serde_json::Value;
+
+/// How records of a data file are laid out in a texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// Each record is a row of the texture, fields run
+    /// along the x axis.
+    Rows,
+    /// Each record is a column of the texture, fields run
+    /// along the y axis.
+    Columns,
+}
+
+impl Layout {
+    pub fn parse(name: &str) -> Result<Layout, String> {
+        match name {
+            "rows" => Ok(Layout::Rows),
+            "columns" => Ok(Layout::Columns),
+            other => Err(format!(
+                "Unknown data layout `{}`, expected `rows` or `columns`",
+                other
+            )),
+        }
+    }
+}
+
+/// A dense table of floats, ready to be uploaded as a
+/// single-channel float texture. `values` is stored row by
+/// row, starting at the bottom of the texture.
+#[derive(Debug, Clone)]
+pub struct DataTable {
+    pub width:  u32,
+    pub height: u32,
+    pub values: Vec<f32>,
+}
+
+impl DataTable {
+    /// Packs a list of records into a table, padding short
+    /// records with zeros.
+    pub fn from_records(records: Vec<Vec<f32>>, layout: Layout) -> DataTable {
+        let fields = records.iter().map(|r| r.len()).max().unwrap_or(0);
+        let (width, height) = match layout {
+            Layout::Rows => (fields, records.len()),
+            Layout::Columns => (records.len(), fields),
+        };
+
+        // textures can't be empty
+        let (width, height) = (width.max(1), height.max(1));
+        let mut values = vec![0.0; width * height];
+        for (record, fields) in records.iter().enumerate() {
+            for (field, value) in fields.iter().enumerate() {
+                let index = match layout {
+                    Layout::Rows => record * width + field,
+                    Layout::Columns => field * width + record,
+                };
+                values[index] = *value;
+            }
+        }
+
+        DataTable {
+            width: width as u32,
+            height: height as u32,
+            values,
+        }
+    }
+}
+
+/// Loads a `.csv` or `.json` file into a [`DataTable`].
+pub fn load_data(path: &Path, layout: Layout) -> Result<DataTable, String> {
+    let text = std::fs::read_to_string(path).map_err(|_| {
+        format!("Could not read data file `{}`", path.display())
+    })?;
+
+    let records = match path.extension().and_then(|e| e.to_str()) {
+        Some("csv") => parse_csv(&text)?,
+        Some("json") => parse_json(&text)?,
+        _ => {
+            return Err(format!(
+                "Unsupported data format `{}`, expected `.csv` or `.json`",
+                path.display()
+            ))
+        },
+    };
+
+    Ok(DataTable::from_records(records, layout))
+}
+
+/// Parses comma-separated numbers, one record per line.
+/// A leading header line that isn't numeric is skipped.
+pub fn parse_csv(text: &str) -> Result<Vec<Vec<f32>>, String> {
+    let mut records = vec![];
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record = line
+            .split(',')
+            .map(|cell| match cell.trim() {
+                "" => Ok(0.0),
+                cell => cell.parse::<f32>(),
+            })
+            .collect::<Result<Vec<f32>, _>>();
+
+        match record {
+            Ok(record) => records.push(record),
+            Err(_) if records.is_empty() && number == 0 => continue,
+            Err(_) => {
+                return Err(format!(
+                    "Invalid number on line {} of CSV: `{}`",
+                    number + 1,
+                    line
+                ))
+            },
+        }
+    }
+
+    Ok(records)
+}
+
+/// Parses a JSON array of records. Each record may be a
+/// number, an array of numbers, or an object whose numeric
+/// values are taken in the sorted order of their keys, not
+/// the order they are written in.
+pub fn parse_json(text: &str) -> Result<Vec<Vec<f32>>, String> {
+    let json: Value = serde_json::from_str(text)
+        .map_err(|e| format!("Invalid JSON data: {}", e))?;
+
+    let items = match json {
+        Value::Array(items) => items,
+        other => vec![other],
+    };
+
+    items.iter().map(json_record).collect()
+}
+
+fn json_record(value: &Value) -> Result<Vec<f32>, String> {
+    match value {
+        Value::Array(fields) => fields.iter().map(json_number).collect(),
+        Value::Object(fields) => fields.values().map(json_number).collect(),
+        other => Ok(vec![json_number(other)?]),
+    }
+}
+
+fn json_number(value: &Value) -> Result<f32, String> {
+    match value {
+        Value::Number(n) => Ok(n.as_f64().unwrap_or(0.0) as f32),
+        Value::Bool(b) => Ok(*b as u8 as f32),
+        Value::Null => Ok(0.0),
+        other => {
+            Err(format!("Expected a number in JSON data, found `{}`", other))
+        },
+    }
+}
+
+/// Selects a value from a JSON document using a small
+/// subset of JSONPath: `$`, `.key`, `['key']`, and
+/// `[index]`, e.g. `$.main.temps[0]`.
+pub fn select<'a>(json: &'a Value, path: &str) -> Option<&'a Value> {
+    let mut current = json;
+    let mut rest = path.trim().strip_prefix('$').unwrap_or(path).trim();
+
+    while !rest.is_empty() {
+        if let Some(tail) = rest.strip_prefix('.') {
+            let end = tail.find(['.', '[']).unwrap_or(tail.len());
+            current = current.get(&tail[..end])?;
+            rest = &tail[end..];
+        } else if let Some(tail) = rest.strip_prefix('[') {
+            let end = tail.find(']')?;
+            let key = tail[..end].trim();
+            current = match key.parse::<usize>() {
+                Ok(index) => current.get(index)?,
+                Err(_) => current.get(key.trim_matches(['\'', '"']))?,
+            };
+            rest = &tail[end + 1..];
+        } else {
+            // a bare key at the start of the path
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            current = current.get(&rest[..end])?;
+            rest = &rest[end..];
+        }
+    }
+
+    Some(current)
+}
@@ -0,0 +1,246 @@
+//! Finds the devices a graph can read from or be shown on,
+//! named as shadergarden takes them, for `shadergarden
+//! devices`. On Linux, devices are read from sysfs and
+//! procfs, and sound servers are asked with `pactl`; on
+//! macOS, `ffmpeg` is asked. Anything that can't be asked
+//! is left out, rather than an error.
+
+use std::{
+    fs,
+    path::Path,
+    process::Command,
+};
+
+use glium::glutin::event_loop::EventLoopWindowTarget;
+
+/// A device, and how to name it to shadergarden.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    /// What names the device, e.g. a camera's index, or an
+    /// `ffmpeg` input.
+    pub id:   String,
+    /// What the device calls itself.
+    pub name: String,
+}
+
+impl Device {
+    fn new(id: impl Into<String>, name: impl Into<String>) -> Device {
+        Device {
+            id:   id.into(),
+            name: name.into(),
+        }
+    }
+}
+
+const V4L2: &str = "/sys/class/video4linux";
+
+/// Reads a small file, like those in sysfs, trimmed.
+fn read(path: impl AsRef<Path>) -> Option<String> {
+    Some(fs::read_to_string(path).ok()?.trim().to_string())
+}
+
+/// The last part of where a link in sysfs points.
+fn link_name(path: impl AsRef<Path>) -> Option<String> {
+    let target = fs::read_link(path).ok()?;
+    Some(target.file_name()?.to_string_lossy().to_string())
+}
+
+/// The numbered entries of a directory starting with
+/// `prefix`, e.g. `video0`, by number.
+fn numbered(dir: &str, prefix: &str) -> Vec<u32> {
+    let mut numbers = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.strip_prefix(prefix)?.parse().ok()
+        })
+        .collect::<Vec<_>>();
+    numbers.sort_unstable();
+    numbers
+}
+
+/// Cameras, by the index `(camera <index>)` takes.
+pub fn cameras() -> Vec<Device> {
+    if cfg!(target_os = "macos") {
+        return avfoundation("video", |index| index.to_string());
+    }
+    numbered(V4L2, "video")
+        .into_iter()
+        // a camera may have other nodes, e.g. for metadata,
+        // and its first is the one that captures
+        .filter(|n| {
+            let index = read(format!("{}/video{}/index", V4L2, n));
+            index.as_deref().unwrap_or("0") == "0"
+        })
+        .map(|n| {
+            let name = read(format!("{}/video{}/name", V4L2, n));
+            Device::new(n.to_string(), name.unwrap_or_default())
+        })
+        .collect()
+}
+
+/// Sound inputs, as the `ffmpeg` inputs `:device` takes,
+/// the default first.
+pub fn audio_inputs() -> Vec<Device> {
+    let mut inputs = crate::audio::default_input()
+        .map(|input| Device::new(input, "The default input"))
+        .into_iter()
+        .collect::<Vec<_>>();
+    if cfg!(target_os = "macos") {
+        let id = |index| format!("avfoundation::{}", index);
+        inputs.extend(avfoundation("audio", id));
+        return inputs;
+    }
+    inputs.extend(pulse_sources());
+    inputs.extend(alsa_captures());
+    inputs
+}
+
+/// Sources of PulseAudio, or of PipeWire through its pulse
+/// server, including the monitor of each output, which
+/// captures what it plays.
+fn pulse_sources() -> Vec<Device> {
+    let output = Command::new("pactl").args(["list", "sources"]).output();
+    let text = match output {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).to_string()
+        },
+        _ => return vec![],
+    };
+    let mut sources = vec![];
+    let mut name = None;
+    for line in text.lines().map(str::trim) {
+        if let Some(value) = line.strip_prefix("Name: ") {
+            name = Some(value.to_string());
+        }
+        if let Some(description) = line.strip_prefix("Description: ") {
+            if let Some(name) = name.take() {
+                let id = format!("pulse:{}", name);
+                sources.push(Device::new(id, description));
+            }
+        }
+    }
+    sources
+}
+
+/// Devices of ALSA sound cards that capture, from lines of
+/// `/proc/asound/pcm` like `00-00: ALC257 Analog : ALC257
+/// Analog : playback 1 : capture 1`.
+fn alsa_captures() -> Vec<Device> {
+    let text = read("/proc/asound/pcm").unwrap_or_default();
+    text.lines()
+        .filter(|line| line.contains("capture"))
+        .filter_map(|line| {
+            let (numbers, rest) = line.split_once(':')?;
+            let (card, device) = numbers.split_once('-')?;
+            let card = card.parse::<u32>().ok()?;
+            let device = device.parse::<u32>().ok()?;
+            let name = rest.split(':').next().unwrap_or_default().trim();
+            let id = format!("alsa:hw:{},{}", card, device);
+            Some(Device::new(id, name))
+        })
+        .collect()
+}
+
+/// Devices listed by `ffmpeg`'s AVFoundation input of a
+/// kind, `video` or `audio`, named from their index.
+fn avfoundation(kind: &str, id: impl Fn(u32) -> String) -> Vec<Device> {
+    let output = Command::new("ffmpeg")
+        .args(["-hide_banner", "-f", "avfoundation"])
+        .args(["-list_devices", "true", "-i", ""])
+        .output();
+    // listed as errors, since there was nothing to open
+    let text = match output {
+        Ok(output) => String::from_utf8_lossy(&output.stderr).to_string(),
+        Err(_) => return vec![],
+    };
+    let heading = format!("AVFoundation {} devices:", kind);
+    let mut devices = vec![];
+    let mut listing = false;
+    for line in text.lines() {
+        // every line starts with `[AVFoundation indev @ 0x..]`
+        let line = line.split_once("] ").map_or(line, |(_, rest)| rest);
+        if line.ends_with("devices:") {
+            listing = line == heading;
+            continue;
+        }
+        let device = line
+            .strip_prefix('[')
+            .and_then(|line| line.split_once("] "))
+            .and_then(|(index, name)| Some((index.parse().ok()?, name)));
+        if let (true, Some((index, name))) = (listing, device) {
+            devices.push(Device::new(id(index), name));
+        }
+    }
+    devices
+}
+
+/// Raw MIDI devices, as the paths `--midi` takes. Only
+/// Linux has these.
+pub fn midi_ports() -> Vec<Device> {
+    let mut ports = fs::read_dir("/dev/snd")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let file = entry.file_name().to_string_lossy().to_string();
+            let (card, _) = file.strip_prefix("midiC")?.split_once('D')?;
+            let name = read(format!("/proc/asound/card{}/id", card));
+            let path = entry.path().to_string_lossy().to_string();
+            Some(Device::new(path, name.unwrap_or_default()))
+        })
+        .collect::<Vec<_>>();
+    ports.sort_by(|a, b| a.id.cmp(&b.id));
+    ports
+}
+
+/// Monitors, by the index `:fullscreen` takes, with their
+/// size and whether they are the primary one.
+pub fn monitors<T>(target: &EventLoopWindowTarget<T>) -> Vec<Device> {
+    let primary = target.primary_monitor();
+    target
+        .available_monitors()
+        .enumerate()
+        .map(|(index, monitor)| {
+            let size = monitor.size();
+            let mut name = format!(
+                "{}, {}x{}",
+                monitor.name().unwrap_or_default(),
+                size.width,
+                size.height
+            );
+            if primary.as_ref() == Some(&monitor) {
+                name.push_str(", primary");
+            }
+            Device::new(index.to_string(), name)
+        })
+        .collect()
+}
+
+/// GPUs, as Mesa's `DRI_PRIME` takes them, to run on one
+/// other than the default. Only found on Linux.
+pub fn gpus() -> Vec<Device> {
+    numbered("/sys/class/drm", "card")
+        .into_iter()
+        .filter_map(|n| {
+            let device = format!("/sys/class/drm/card{}/device", n);
+            // e.g. `0000:01:00.0`, written `pci-0000_01_00_0`
+            let slot = link_name(&device)?;
+            let id = format!("pci-{}", slot.replace([':', '.'], "_"));
+            let vendor = read(format!("{}/vendor", device));
+            let vendor = match vendor.as_deref() {
+                Some("0x10de") => "NVIDIA",
+                Some("0x1002") => "AMD",
+                Some("0x8086") => "Intel",
+                other => other.unwrap_or("Unknown"),
+            };
+            let model = read(format!("{}/device", device)).unwrap_or_default();
+            let driver = link_name(format!("{}/driver", device));
+            let driver = driver.unwrap_or_default();
+            let name = format!("{} {}, {}", vendor, model, driver);
+            Some(Device::new(id, name))
+        })
+        .collect()
+}
@@ -0,0 +1,607 @@
+use std::{
+    fmt,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    str::FromStr,
+};
+
+use crate::{
+    include::{
+        expand,
+        LIBRARY_STRING,
+    },
+    reload::ShaderDir,
+};
+
+/// Where errors in the helper library are reported, see
+/// [`crate::include::LIBRARY`].
+const LIBRARY_FILE: &str = "<shadergarden>/garden.glsl";
+
+/// How build errors are printed, see [`Diagnostic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// The full error, as reported by the compiler.
+    Human,
+    /// One `file:line:column: error: message` line per
+    /// error, which most editors can jump to.
+    Short,
+    /// One JSON object per error, on its own line.
+    Json,
+}
+
+impl FromStr for MessageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(MessageFormat::Human),
+            "short" => Ok(MessageFormat::Short),
+            "json" => Ok(MessageFormat::Json),
+            other => Err(format!(
+                "Unknown message format `{}`, expected `human`, `short`, \
+                 or `json`",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A single build error, located in the file that caused
+/// it where possible.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub file:     PathBuf,
+    pub line:     Option<u32>,
+    pub column:   Option<u32>,
+    /// How many characters from the column are at fault,
+    /// when known.
+    pub length:   Option<u32>,
+    pub severity: Severity,
+    pub message:  String,
+    /// Suggestions on how to fix it, see [`with_hint`].
+    pub help:     Vec<String>,
+}
+
+impl Diagnostic {
+    /// Splits an error from building a graph into
+    /// diagnostics. Shader compiler logs are located in the
+    /// failing shader, in `project`, or in the file it
+    /// included that failed, and everything else in the
+    /// `graph` file.
+    pub fn parse(error: &str, project: &Path, graph: &Path) -> Vec<Diagnostic> {
+        let (_, error) = node_error(error);
+        let (error, help) = split_hints(error);
+        let mut diagnostics = match shader_error(&error) {
+            Some((name, log)) => {
+                let file = project.join(format!("{}.frag", name));
+                // included files are numbered as they were
+                // when the shader was expanded
+                let included = fs::read_to_string(&file)
+                    .ok()
+                    .and_then(|source| expand(&source, project, true).ok())
+                    .map_or(vec![], |expanded| expanded.files);
+                let mut diagnostics = log
+                    .lines()
+                    .filter_map(locate)
+                    .map(|(string, line, column, severity, message)| {
+                        // anything else is blamed on the shader
+                        let file = match string as usize {
+                            LIBRARY_STRING => PathBuf::from(LIBRARY_FILE),
+                            _ => string
+                                .checked_sub(1)
+                                .and_then(|index| included.get(index as usize))
+                                .unwrap_or(&file)
+                                .clone(),
+                        };
+                        Diagnostic {
+                            file,
+                            line: Some(line),
+                            column,
+                            length: None,
+                            severity,
+                            message: message.to_string(),
+                            help: vec![],
+                        }
+                    })
+                    .collect::<Vec<_>>();
+
+                // not every driver's log can be read, so fall
+                // back to the whole log
+                if diagnostics.is_empty() {
+                    diagnostics.push(Diagnostic::unlocated(file, log));
+                }
+                diagnostics
+            },
+            None => vec![graph_error(&error, graph)],
+        };
+        diagnostics[0].help = help;
+        diagnostics
+    }
+
+    fn unlocated(file: PathBuf, message: &str) -> Diagnostic {
+        Diagnostic {
+            file,
+            line: None,
+            column: None,
+            length: None,
+            severity: Severity::Error,
+            message: message.split_whitespace().collect::<Vec<_>>().join(" "),
+            help: vec![],
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "file": self.file,
+            "line": self.line,
+            "column": self.column,
+            "length": self.length,
+            "severity": self.severity.to_string(),
+            "message": self.message,
+            "help": self.help,
+        })
+    }
+}
+
+/// Formats the diagnostic as `file:line:column: error:
+/// message`, leaving out whatever is not known.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.file.display())?;
+        if let Some(line) = self.line {
+            write!(f, "{}:", line)?;
+            if let Some(column) = self.column {
+                write!(f, "{}:", column)?;
+            }
+        }
+        write!(f, " {}: {}", self.severity, self.message)
+    }
+}
+
+/// An error from building a graph, along with what is
+/// known about where it came from, so that frontends can
+/// show it in place rather than as a log. Its `Display`
+/// quotes the offending line, with a caret under the
+/// column the compiler pointed at.
+#[derive(Debug, Clone)]
+pub struct BuildError {
+    /// The error as reported, e.g. the full driver log.
+    pub message:     String,
+    /// The named node that failed to build, if any.
+    pub node:        Option<String>,
+    /// The shader that failed to compile, if any.
+    pub shader:      Option<String>,
+    /// Where the error is, in the failing shader, or
+    /// otherwise in the graph.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The file the diagnostics are in, to quote from.
+    source:          Option<String>,
+}
+
+impl BuildError {
+    /// Locates an error from building `graph`, whose
+    /// shaders are in `project`, quoting from the files on
+    /// disk.
+    pub fn new(message: String, project: &Path, graph: &Path) -> BuildError {
+        let (node, error) = node_error(&message);
+        let shader = shader_error(error).map(|(name, _)| name.to_string());
+        let diagnostics = Diagnostic::parse(&message, project, graph);
+        let source = diagnostics
+            .first()
+            .and_then(|d| fs::read_to_string(&d.file).ok());
+        BuildError {
+            node: node.map(str::to_string),
+            shader,
+            diagnostics,
+            source,
+            message,
+        }
+    }
+
+    /// Quotes from sources in memory instead, for graphs
+    /// that weren't built from the disk. Files included by
+    /// shaders, and shaders that weren't read up front, see
+    /// [`ShaderDir::unread`], are still quoted from the
+    /// disk.
+    pub fn with_sources(mut self, dir: &ShaderDir) -> BuildError {
+        self.source = match &self.shader {
+            Some(name) if self.is_included(name) => return self,
+            Some(name) => dir
+                .shaders
+                .get(name)
+                .cloned()
+                .or_else(|| fs::read_to_string(dir.unread.get(name)?).ok()),
+            None => Some(dir.lisp.clone()),
+        };
+        self
+    }
+
+    /// Accounts for lines added before the graph's own
+    /// source before it was built, so that locations in the
+    /// graph point into the file as written.
+    pub fn with_prelude(mut self, lines: u32) -> BuildError {
+        if self.shader.is_none() {
+            for diagnostic in self.diagnostics.iter_mut() {
+                diagnostic.line = diagnostic
+                    .line
+                    .map(|line| line.saturating_sub(lines).max(1));
+            }
+            if let Some(source) = &self.source {
+                let skipped = source.lines().skip(lines as usize);
+                self.source = Some(skipped.collect::<Vec<_>>().join("\n"));
+            }
+        }
+        self
+    }
+
+    /// Whether the diagnostics are in a file included by a
+    /// shader, rather than the shader itself.
+    fn is_included(&self, shader: &str) -> bool {
+        let file = format!("{}.frag", shader);
+        self.diagnostics
+            .first()
+            .is_some_and(|d| d.file.file_name() != Some(file.as_ref()))
+    }
+
+    /// The quoted line of a diagnostic, if it can be found.
+    fn quote(&self, diagnostic: &Diagnostic) -> Option<&str> {
+        let line = diagnostic.line?.checked_sub(1)?;
+        self.source.as_ref()?.lines().nth(line as usize)
+    }
+}
+
+impl From<String> for BuildError {
+    /// An error that can't be located, e.g. from reading
+    /// the project.
+    fn from(message: String) -> BuildError {
+        BuildError {
+            diagnostics: vec![],
+            node: None,
+            shader: None,
+            source: None,
+            message,
+        }
+    }
+}
+
+/// Formats each diagnostic like:
+///
+/// ```text
+/// error: `foo' undeclared
+///   --> life.frag:12:5
+///    |
+/// 12 |     foo = 1.0;
+///    |     ^
+///    = in node `life`
+/// ```
+///
+/// with a line for each hint, like `= help: did you mean
+/// `shader-rec`?`, and the whole of what's at fault
+/// underlined, when its length is known.
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.diagnostics.is_empty() {
+            return write!(f, "error: {}", self.message);
+        }
+
+        let gutter = self
+            .diagnostics
+            .iter()
+            .filter_map(|d| d.line)
+            .max()
+            .map_or(0, |line| line.to_string().len());
+        let blank = " ".repeat(gutter);
+        for (i, diagnostic) in self.diagnostics.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "{}: {}", diagnostic.severity, diagnostic.message)?;
+            write!(f, "{}--> {}", blank, diagnostic.file.display())?;
+            if let Some(line) = diagnostic.line {
+                write!(f, ":{}", line)?;
+                if let Some(column) = diagnostic.column {
+                    write!(f, ":{}", column)?;
+                }
+            }
+
+            if let (Some(line), Some(quoted)) =
+                (diagnostic.line, self.quote(diagnostic))
+            {
+                write!(f, "\n{} |", blank)?;
+                write!(f, "\n{:>2$} | {}", line, quoted, gutter)?;
+                if let Some(column) = diagnostic.column {
+                    let indent = " ".repeat(column.saturating_sub(1) as usize);
+                    let length = diagnostic.length.unwrap_or(1) as usize;
+                    let carets = "^".repeat(length);
+                    write!(f, "\n{} | {}{}", blank, indent, carets)?;
+                }
+            }
+            for help in diagnostic.help.iter() {
+                write!(f, "\n{} = help: {}", blank, help)?;
+            }
+        }
+        if let Some(node) = &self.node {
+            write!(f, "\n{} = in node `{}`", blank, node)?;
+        }
+        Ok(())
+    }
+}
+
+/// Prints an error in the given format. JSON errors are
+/// printed to stdout, and everything else to stderr.
+pub fn report(format: MessageFormat, error: &BuildError) {
+    match format {
+        MessageFormat::Human => eprintln!("{}", error),
+        MessageFormat::Short => {
+            for diagnostic in error.diagnostics.iter() {
+                eprintln!("{}", diagnostic);
+            }
+        },
+        MessageFormat::Json => {
+            for diagnostic in error.diagnostics.iter() {
+                println!("{}", diagnostic.to_json());
+            }
+        },
+    }
+}
+
+/// Wraps an error from compiling a named shader, so that
+/// it can be traced back to its file.
+pub fn in_shader(name: &str, error: String) -> String {
+    format!("In shader `{}`: {}", name, error)
+}
+
+/// Adds a suggestion on how to fix an error, shown under
+/// it, see [`Diagnostic::help`].
+pub fn with_hint(error: String, hint: &str) -> String {
+    format!("{}\nhelp: {}", error, hint)
+}
+
+/// Suggests the closest of `candidates` to a misspelled
+/// `name`, as a hint, see [`with_hint`]: the one fewest
+/// edits away, if it's close enough to be a typo, or one
+/// that `name` is a whole part of, like `shader-rec` for
+/// `rec`.
+pub fn did_you_mean<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<String> {
+    let most = (name.chars().count() / 3).max(1);
+    let mut best: Option<(usize, &str)> = None;
+    for candidate in candidates {
+        if candidate == name {
+            continue;
+        }
+        let distance = if candidate.split('-').any(|part| part == name) {
+            most
+        } else {
+            edit_distance(name, candidate)
+        };
+        if distance <= most && best.is_none_or(|(d, _)| distance < d) {
+            best = Some((distance, candidate));
+        }
+    }
+    best.map(|(_, candidate)| format!("did you mean `{}`?", candidate))
+}
+
+/// Adds [`did_you_mean`] to an error, as a hint, if any of
+/// `candidates` is close to `name`.
+pub fn suggest<'a>(
+    error: String,
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> String {
+    match did_you_mean(name, candidates) {
+        Some(hint) => with_hint(error, &hint),
+        None => error,
+    }
+}
+
+/// The Levenshtein distance between two strings, in chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substituted = diagonal + (a != *b) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Splits the hints added with [`with_hint`] off an error.
+fn split_hints(error: &str) -> (String, Vec<String>) {
+    let mut message = vec![];
+    let mut help = vec![];
+    for line in error.lines() {
+        match line.strip_prefix("help: ") {
+            Some(hint) => help.push(hint.to_string()),
+            None => message.push(line),
+        }
+    }
+    (message.join("\n"), help)
+}
+
+/// Wraps an error from building a named node.
+pub fn in_node(name: &str, error: String) -> String {
+    format!("In node `{}`: {}", name, error)
+}
+
+/// Splits off the node an error was wrapped in, along
+/// with the post chain, which is reported on its own.
+fn node_error(error: &str) -> (Option<&str>, &str) {
+    let error = error.strip_prefix("In post chain: ").unwrap_or(error);
+    let node = error
+        .strip_prefix("In node `")
+        .and_then(|rest| rest.split_once("`: "));
+    match node {
+        Some((name, rest)) => (Some(name), rest),
+        None => (None, error),
+    }
+}
+
+fn shader_error(error: &str) -> Option<(&str, &str)> {
+    let rest = error.strip_prefix("In shader `")?;
+    let (name, log) = rest.split_once("`: ")?;
+    Some((name, log))
+}
+
+fn graph_error(error: &str, graph: &Path) -> Diagnostic {
+    let mut diagnostic = Diagnostic::unlocated(graph.to_path_buf(), error);
+
+    // parse errors end with `at line <l> column <c>`
+    if let Some((message, location)) = error.rsplit_once(" at line ") {
+        if let Some((line, column)) = location.split_once(" column ") {
+            if let (Ok(line), Ok(column)) =
+                (line.trim().parse::<u32>(), column.trim().parse::<u32>())
+            {
+                // the graph is wrapped in an extra `(` before
+                // it is parsed
+                let column = if line == 1 {
+                    column.saturating_sub(1)
+                } else {
+                    column
+                };
+                diagnostic.line = Some(line);
+                diagnostic.column = Some(column.max(1));
+                diagnostic.message = message.to_string();
+                return diagnostic;
+            }
+        }
+    }
+
+    // other errors aren't located, but most quote what's
+    // wrong, so point at the first place that's written
+    let source = fs::read_to_string(graph).unwrap_or_default();
+    let quoted = error.split('`').skip(1).step_by(2);
+    if let Some((line, column, length)) = quoted
+        .filter_map(|quoted| find_symbol(&source, quoted))
+        .next()
+    {
+        diagnostic.line = Some(line);
+        diagnostic.column = Some(column);
+        diagnostic.length = Some(length);
+    }
+    diagnostic
+}
+
+/// The line, column, and length of the first place a
+/// symbol is written in lisp as a whole, outside comments,
+/// or of a string holding just it.
+fn find_symbol(source: &str, symbol: &str) -> Option<(u32, u32, u32)> {
+    let boundary = |c: Option<char>| {
+        c.is_none_or(|c| c.is_whitespace() || "()[]\"';".contains(c))
+    };
+    if symbol.is_empty() || symbol.chars().any(char::is_whitespace) {
+        return None;
+    }
+    for (number, line) in source.lines().enumerate() {
+        let code = line.split(';').next().unwrap_or(line);
+        for (at, _) in code.match_indices(symbol) {
+            let before = code[..at].chars().next_back();
+            let after = code[at + symbol.len()..].chars().next();
+            if boundary(before) && boundary(after) {
+                let column = code[..at].chars().count() as u32 + 1;
+                let length = symbol.chars().count() as u32;
+                return Some((number as u32 + 1, column, length));
+            }
+        }
+    }
+    None
+}
+
+/// The source string, line, column, severity, and message
+/// of a line of a compiler log.
+type Location<'a> = (u32, u32, Option<u32>, Severity, &'a str);
+
+/// Finds the location in a line of a shader compiler log.
+/// Understands the common driver formats:
+///
+/// ```text
+/// 0:12(3): error: `foo' undeclared          (Mesa)
+/// 0(12) : error C1008: undefined variable   (NVIDIA)
+/// ERROR: 0:12: 'foo' : undeclared identifier (AMD, ANGLE)
+/// ```
+///
+/// The first line of the log is prefixed by glium, so the
+/// location is also looked for after each `: `.
+fn locate(line: &str) -> Option<Location<'_>> {
+    std::iter::once(line)
+        .chain(line.match_indices(": ").map(|(i, _)| &line[i + 2..]))
+        .find_map(location)
+}
+
+fn location(s: &str) -> Option<Location<'_>> {
+    let s = s.trim_start();
+    let (mut severity, s) = if let Some(s) = s.strip_prefix("ERROR: ") {
+        (Some(Severity::Error), s)
+    } else if let Some(s) = s.strip_prefix("WARNING: ") {
+        (Some(Severity::Warning), s)
+    } else {
+        (None, s)
+    };
+
+    // the source string index, 0 unless included
+    let (string, s) = number(s)?;
+    let (line, column, rest) = if let Some(s) = s.strip_prefix(':') {
+        let (line, s) = number(s)?;
+        let (column, s) = match s.strip_prefix('(') {
+            Some(s) => {
+                let (column, s) = number(s)?;
+                (Some(column), s.strip_prefix(')')?)
+            },
+            None => (None, s),
+        };
+        (line, column, s)
+    } else {
+        let (line, s) = number(s.strip_prefix('(')?)?;
+        (line, None, s.strip_prefix(')')?)
+    };
+    let message = rest.trim_start().strip_prefix(':')?.trim();
+
+    let lower = message.to_ascii_lowercase();
+    if severity.is_none() {
+        severity = Some(
+            if lower.starts_with("warning") {
+                Severity::Warning
+            } else {
+                Severity::Error
+            },
+        );
+    }
+    let message = ["error:", "warning:", "error", "warning"]
+        .iter()
+        .find(|prefix| lower.starts_with(*prefix))
+        .map(|prefix| message[prefix.len()..].trim())
+        .unwrap_or(message);
+
+    Some((string, line, column, severity?, message))
+}
+
+fn number(s: &str) -> Option<(u32, &str)> {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let value = s[..end].parse().ok()?;
+    Some((value, &s[end..]))
+}
@@ -0,0 +1,476 @@
+//! Shares textures with other processes as DMA-BUFs,
+//! handles to memory on the GPU, so that GStreamer,
+//! PipeWire, or a compositor can use the output of a graph
+//! without it ever being read back. Exporting needs an EGL
+//! context, and a driver with
+//! `EGL_MESA_image_dma_buf_export`, as Mesa's have; EGL is
+//! loaded when the first exporter is created.
+//! See [`DmabufExporter`] for how frames are handed over.
+
+use std::{
+    ffi::CString,
+    io,
+    os::{
+        raw::{
+            c_char,
+            c_int,
+            c_uint,
+            c_void,
+        },
+        unix::{
+            io::AsRawFd,
+            net::{
+                UnixListener,
+                UnixStream,
+            },
+        },
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    rc::Rc,
+};
+
+use glium::{
+    backend::{
+        Context,
+        Facade,
+    },
+    texture::{
+        MipmapsOption,
+        UncompressedFloatFormat,
+    },
+    uniforms::MagnifySamplerFilter,
+    BlitTarget,
+    GlObject,
+    Surface,
+    Texture2d,
+};
+
+use crate::sink::OutputSink;
+
+/// Buffers frames are copied into in turn, so that a frame
+/// isn't overwritten while a client is still using it.
+pub const BUFFERS: u32 = 2;
+
+/// Bytes in every message sent to clients.
+pub const MESSAGE: usize = 40;
+
+/// A message describing a buffer, sent with its DMA-BUF.
+const KIND_BUFFER: u32 = 1;
+
+/// A message saying which buffer holds a new frame.
+const KIND_FRAME: u32 = 2;
+
+const EGL_GL_TEXTURE_2D: c_uint = 0x30B1;
+const EGL_GL_TEXTURE_LEVEL: c_int = 0x30BC;
+const EGL_NONE: c_int = 0x3038;
+
+type GetCurrent = unsafe extern "C" fn() -> *mut c_void;
+type GetProcAddress = unsafe extern "C" fn(*const c_char) -> *mut c_void;
+type CreateImage = unsafe extern "C" fn(
+    *mut c_void,
+    *mut c_void,
+    c_uint,
+    *mut c_void,
+    *const c_int,
+) -> *mut c_void;
+type DestroyImage = unsafe extern "C" fn(*mut c_void, *mut c_void) -> c_uint;
+type ExportQuery = unsafe extern "C" fn(
+    *mut c_void,
+    *mut c_void,
+    *mut c_int,
+    *mut c_int,
+    *mut u64,
+) -> c_uint;
+type Export = unsafe extern "C" fn(
+    *mut c_void,
+    *mut c_void,
+    *mut c_int,
+    *mut c_int,
+    *mut c_int,
+) -> c_uint;
+
+/// The functions used from EGL.
+#[derive(Clone, Copy)]
+struct Library {
+    current_display: GetCurrent,
+    current_context: GetCurrent,
+    create_image:    CreateImage,
+    destroy_image:   DestroyImage,
+    export_query:    ExportQuery,
+    export:          Export,
+}
+
+impl Library {
+    fn load() -> Result<Library, String> {
+        // safety: the library is EGL, whose functions have the
+        // signatures from egl.h and eglext.h
+        unsafe {
+            let library = ["libEGL.so.1", "libEGL.so"]
+                .iter()
+                .filter_map(|name| CString::new(*name).ok())
+                .map(|name| libc::dlopen(name.as_ptr(), libc::RTLD_NOW))
+                .find(|library| !library.is_null())
+                .ok_or_else(|| "Could not find libEGL".to_string())?;
+
+            let symbol = |name: &[u8]| {
+                let symbol = libc::dlsym(library, name.as_ptr() as *const _);
+                if symbol.is_null() {
+                    Err("libEGL is missing functions, update it".to_string())
+                } else {
+                    Ok(symbol)
+                }
+            };
+            let get_proc_address: GetProcAddress =
+                std::mem::transmute(symbol(b"eglGetProcAddress\0")?);
+            let extension = |name: &[u8]| {
+                let function = get_proc_address(name.as_ptr() as *const _);
+                if function.is_null() {
+                    Err("The EGL driver can't export DMA-BUFs, it needs \
+                         EGL_MESA_image_dma_buf_export"
+                        .to_string())
+                } else {
+                    Ok(function)
+                }
+            };
+            let current_display: GetCurrent =
+                std::mem::transmute(symbol(b"eglGetCurrentDisplay\0")?);
+            let current_context: GetCurrent =
+                std::mem::transmute(symbol(b"eglGetCurrentContext\0")?);
+            let create_image: CreateImage =
+                std::mem::transmute(extension(b"eglCreateImageKHR\0")?);
+            let destroy_image: DestroyImage =
+                std::mem::transmute(extension(b"eglDestroyImageKHR\0")?);
+            let export_query: ExportQuery = std::mem::transmute(extension(
+                b"eglExportDMABUFImageQueryMESA\0",
+            )?);
+            let export: Export =
+                std::mem::transmute(extension(b"eglExportDMABUFImageMESA\0")?);
+            Ok(Library {
+                current_display,
+                current_context,
+                create_image,
+                destroy_image,
+                export_query,
+                export,
+            })
+        }
+    }
+}
+
+/// A texture exported as a DMA-BUF, with one plane.
+struct Buffer {
+    texture:  Texture2d,
+    context:  Rc<Context>,
+    library:  Library,
+    display:  usize,
+    image:    usize,
+    fd:       c_int,
+    fourcc:   u32,
+    modifier: u64,
+    stride:   u32,
+    offset:   u32,
+}
+
+impl Buffer {
+    fn new(
+        context: &Rc<Context>,
+        library: Library,
+        size: (u32, u32),
+    ) -> Result<Buffer, String> {
+        let texture = Texture2d::empty_with_format(
+            context,
+            UncompressedFloatFormat::U8U8U8U8,
+            MipmapsOption::NoMipmap,
+            size.0,
+            size.1,
+        )
+        .map_err(|e| e.to_string())?;
+        let id = texture.get_id() as usize;
+
+        // safety: the functions are called with the context
+        // current, which the texture belongs to, and write at
+        // most one plane, as queried first
+        let exported = unsafe {
+            context.exec_in_context(move || {
+                let display = (library.current_display)();
+                let egl = (library.current_context)();
+                if display.is_null() || egl.is_null() {
+                    return Err("DMA-BUFs can only be exported from an EGL \
+                                context, e.g. under Wayland"
+                        .to_string());
+                }
+                let attributes = [EGL_GL_TEXTURE_LEVEL, 0, EGL_NONE];
+                let image = (library.create_image)(
+                    display,
+                    egl,
+                    EGL_GL_TEXTURE_2D,
+                    id as *mut c_void,
+                    attributes.as_ptr(),
+                );
+                if image.is_null() {
+                    return Err(
+                        "Could not make an EGL image of the output".to_string()
+                    );
+                }
+                let (mut fourcc, mut planes, mut modifier) = (0, 0, 0);
+                let (mut fd, mut stride, mut offset) = (-1, 0, 0);
+                let exported = (library.export_query)(
+                    display,
+                    image,
+                    &mut fourcc,
+                    &mut planes,
+                    &mut modifier,
+                ) != 0
+                    && planes == 1
+                    && (library.export)(
+                        display,
+                        image,
+                        &mut fd,
+                        &mut stride,
+                        &mut offset,
+                    ) != 0;
+                if !exported {
+                    (library.destroy_image)(display, image);
+                    return Err(
+                        "Could not export the output as a DMA-BUF".to_string()
+                    );
+                }
+                Ok((
+                    display as usize,
+                    image as usize,
+                    fd,
+                    fourcc,
+                    modifier,
+                    stride,
+                    offset,
+                ))
+            })?
+        };
+        let (display, image, fd, fourcc, modifier, stride, offset) = exported;
+        Ok(Buffer {
+            texture,
+            context: context.clone(),
+            library,
+            display,
+            image,
+            fd,
+            fourcc: fourcc as u32,
+            modifier,
+            stride: stride as u32,
+            offset: offset as u32,
+        })
+    }
+
+    /// The message describing this buffer, as `index`.
+    fn message(&self, index: u32) -> [u8; MESSAGE] {
+        let mut message = [0; MESSAGE];
+        let (width, height) = self.texture.dimensions();
+        let fields = [
+            KIND_BUFFER,
+            index,
+            width,
+            height,
+            self.fourcc,
+            self.stride,
+            self.offset,
+        ];
+        for (i, field) in fields.iter().enumerate() {
+            message[i * 4..][..4].copy_from_slice(&field.to_le_bytes());
+        }
+        message[32..].copy_from_slice(&self.modifier.to_le_bytes());
+        message
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        let library = self.library;
+        let (display, image) = (self.display, self.image);
+        // safety: the image was made with this display, and
+        // the fd was exported for it
+        unsafe {
+            self.context.exec_in_context(move || {
+                (library.destroy_image)(
+                    display as *mut c_void,
+                    image as *mut c_void,
+                );
+            });
+            libc::close(self.fd);
+        }
+    }
+}
+
+/// Shares every frame pushed to it with clients of a Unix
+/// socket, as DMA-BUFs. Each frame is copied on the GPU
+/// into one of [`BUFFERS`] textures, flipped so the top row
+/// comes first, which are exported once. Every message to a
+/// client is [`MESSAGE`] bytes of little-endian numbers,
+/// starting with a `u32` kind:
+///
+/// ```text
+/// kind 1, a buffer, sent with its DMA-BUF fd attached
+///   u32 kind, u32 buffer, u32 width, u32 height,
+///   u32 DRM fourcc, u32 stride, u32 offset, u32 reserved,
+///   u64 DRM format modifier
+///
+/// kind 2, a frame, once the GPU has finished copying it
+///   u32 kind, u32 buffer, u64 frame, 24 bytes reserved
+/// ```
+///
+/// Clients are sent every buffer when they connect, and
+/// again when the output changes size, and then every
+/// frame. A frame stays in its buffer until [`BUFFERS`]
+/// more have been sent. Clients that fall so far behind
+/// that the socket fills up are disconnected.
+pub struct DmabufExporter {
+    path:     PathBuf,
+    context:  Rc<Context>,
+    library:  Library,
+    listener: UnixListener,
+    clients:  Vec<UnixStream>,
+    buffers:  Vec<Buffer>,
+    frame:    u64,
+}
+
+impl DmabufExporter {
+    /// Listens for clients on a socket at `path`, replacing
+    /// any file there, to share textures of `facade`.
+    pub fn new<F: Facade + ?Sized>(
+        facade: &F,
+        path: &Path,
+    ) -> Result<DmabufExporter, String> {
+        let library = Library::load()?;
+        let _ = std::fs::remove_file(path);
+        let listener = UnixListener::bind(path)
+            .and_then(|listener| {
+                listener.set_nonblocking(true)?;
+                Ok(listener)
+            })
+            .map_err(|e| {
+                format!("Could not listen on `{}`: {}", path.display(), e)
+            })?;
+        Ok(DmabufExporter {
+            path: path.to_path_buf(),
+            context: facade.get_context().clone(),
+            library,
+            listener,
+            clients: vec![],
+            buffers: vec![],
+            frame: 0,
+        })
+    }
+
+    /// Sends a client every buffer.
+    fn announce(&self, client: &UnixStream) -> io::Result<()> {
+        for (index, buffer) in self.buffers.iter().enumerate() {
+            send(client, &buffer.message(index as u32), Some(buffer.fd))?;
+        }
+        Ok(())
+    }
+}
+
+impl OutputSink for DmabufExporter {
+    fn name(&self) -> String { format!("DMA-BUF {}", self.path.display()) }
+
+    fn push(&mut self, texture: &Texture2d) -> Result<(), String> {
+        let size = texture.dimensions();
+        let resized = self
+            .buffers
+            .first()
+            .is_none_or(|buffer| buffer.texture.dimensions() != size);
+        if resized {
+            self.buffers = (0..BUFFERS)
+                .map(|_| Buffer::new(&self.context, self.library, size))
+                .collect::<Result<_, _>>()?;
+            let clients = std::mem::take(&mut self.clients);
+            self.clients = clients
+                .into_iter()
+                .filter(|client| self.announce(client).is_ok())
+                .collect();
+        }
+        while let Ok((client, _)) = self.listener.accept() {
+            if client.set_nonblocking(true).is_ok()
+                && self.announce(&client).is_ok()
+            {
+                self.clients.push(client);
+            }
+        }
+
+        let index = (self.frame % BUFFERS as u64) as u32;
+        let buffer = &self.buffers[index as usize];
+        let target = BlitTarget {
+            left:   0,
+            bottom: size.1,
+            width:  size.0 as i32,
+            height: -(size.1 as i32),
+        };
+        texture.as_surface().blit_whole_color_to(
+            &buffer.texture.as_surface(),
+            &target,
+            MagnifySamplerFilter::Nearest,
+        );
+        // clients never see a frame half copied
+        self.context.finish();
+
+        let mut message = [0; MESSAGE];
+        message[..4].copy_from_slice(&KIND_FRAME.to_le_bytes());
+        message[4..8].copy_from_slice(&index.to_le_bytes());
+        message[8..16].copy_from_slice(&self.frame.to_le_bytes());
+        self.clients
+            .retain(|client| send(client, &message, None).is_ok());
+        self.frame += 1;
+        Ok(())
+    }
+}
+
+impl Drop for DmabufExporter {
+    fn drop(&mut self) { let _ = std::fs::remove_file(&self.path); }
+}
+
+/// Sends a whole message without blocking, with an fd
+/// attached if given.
+fn send(
+    client: &UnixStream,
+    message: &[u8],
+    fd: Option<c_int>,
+) -> io::Result<()> {
+    // aligned for the control message header
+    let mut control = [0u64; 8];
+    // safety: the header points at the message and control
+    // buffer, which outlive the call, and the control buffer
+    // fits one fd
+    let sent = unsafe {
+        let mut iov = libc::iovec {
+            iov_base: message.as_ptr() as *mut c_void,
+            iov_len:  message.len(),
+        };
+        let mut header: libc::msghdr = std::mem::zeroed();
+        header.msg_iov = &mut iov;
+        header.msg_iovlen = 1;
+        if let Some(fd) = fd {
+            let length = std::mem::size_of::<c_int>() as u32;
+            header.msg_control = control.as_mut_ptr() as *mut c_void;
+            header.msg_controllen = libc::CMSG_SPACE(length) as _;
+            let cmsg = libc::CMSG_FIRSTHDR(&header);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(length) as _;
+            std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut c_int, fd);
+        }
+        libc::sendmsg(
+            client.as_raw_fd(),
+            &header,
+            libc::MSG_DONTWAIT | libc::MSG_NOSIGNAL,
+        )
+    };
+    if sent < 0 {
+        Err(io::Error::last_os_error())
+    } else if sent as usize != message.len() {
+        Err(io::Error::new(io::ErrorKind::WriteZero, "partial message"))
+    } else {
+        Ok(())
+    }
+}
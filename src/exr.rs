@@ -0,0 +1,216 @@
+//! Writes OpenEXR images with several layers, so that every
+//! pass of a graph can be handed to a compositor like Nuke
+//! or Fusion in one file per frame. Only what's needed for
+//! that is supported: 32-bit float RGBA layers of the same
+//! size, in scanlines, compressed with zlib.
+
+use std::{
+    fs,
+    path::Path,
+};
+
+use image::{
+    imageops::{
+        self,
+        FilterType,
+    },
+    ImageBuffer,
+    Rgba,
+};
+
+/// An image of 32-bit float RGBA pixels, top row first, as
+/// read with [`crate::graph::ShaderGraph::read_output_f32`].
+pub type FloatImage = ImageBuffer<Rgba<f32>, Vec<f32>>;
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+/// Single part, scanlines, short names.
+const VERSION: [u8; 4] = [2, 0, 0, 0];
+const FLOAT: i32 = 2;
+const ZIP_COMPRESSION: u8 = 3;
+/// The scanlines compressed together with
+/// `ZIP_COMPRESSION`.
+const LINES_PER_BLOCK: u32 = 16;
+const CHANNELS: [&str; 4] = ["R", "G", "B", "A"];
+
+/// A named image to write as a layer, whose channels are
+/// called `<name>.R` and so on. A layer with an empty name
+/// is the image's main RGBA, which viewers show by default.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub name:  String,
+    pub image: FloatImage,
+}
+
+impl Layer {
+    pub fn new(name: impl Into<String>, image: FloatImage) -> Layer {
+        Layer {
+            name: name.into(),
+            image,
+        }
+    }
+
+    /// Scales the layer to a size, if it isn't already, as
+    /// every layer of an image must be the same size.
+    pub fn fit(self, (width, height): (u32, u32)) -> Layer {
+        if self.image.dimensions() == (width, height) {
+            return self;
+        }
+        let image =
+            imageops::resize(&self.image, width, height, FilterType::Triangle);
+        Layer { image, ..self }
+    }
+
+    fn channel(&self, index: usize) -> String {
+        match self.name.as_str() {
+            "" => CHANNELS[index].to_string(),
+            name => format!("{}.{}", name, CHANNELS[index]),
+        }
+    }
+}
+
+/// Encodes layers of the same size as one image, with the
+/// size of the first. Layer names must be distinct.
+pub fn encode(layers: &[Layer]) -> Result<Vec<u8>, String> {
+    let (width, height) = match layers.first() {
+        Some(layer) => layer.image.dimensions(),
+        None => return Err("An EXR needs at least one layer".into()),
+    };
+    if let Some(layer) = layers
+        .iter()
+        .find(|l| l.image.dimensions() != (width, height))
+    {
+        return Err(format!(
+            "Layer `{}` is {}x{}, not {}x{} as the first",
+            layer.name,
+            layer.image.width(),
+            layer.image.height(),
+            width,
+            height
+        ));
+    }
+
+    // readers expect channels sorted by name
+    let mut channels = layers
+        .iter()
+        .enumerate()
+        .flat_map(|(layer, l)| {
+            (0..CHANNELS.len()).map(move |i| (l.channel(i), layer, i))
+        })
+        .collect::<Vec<_>>();
+    channels.sort();
+    if let Some(pair) = channels.windows(2).find(|pair| pair[0].0 == pair[1].0)
+    {
+        return Err(format!("Two layers have the channel `{}`", pair[0].0));
+    }
+
+    let mut bytes = vec![];
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&VERSION);
+
+    let mut list = vec![];
+    for (name, _, _) in channels.iter() {
+        list.extend_from_slice(name.as_bytes());
+        list.push(0);
+        list.extend_from_slice(&FLOAT.to_le_bytes());
+        // not perceptually linear, and three reserved bytes
+        list.extend_from_slice(&[0; 4]);
+        // no subsampling
+        list.extend_from_slice(&1i32.to_le_bytes());
+        list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    list.push(0);
+    let window = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|i| i.to_le_bytes())
+        .collect::<Vec<_>>();
+    attribute(&mut bytes, "channels", "chlist", &list);
+    attribute(&mut bytes, "compression", "compression", &[ZIP_COMPRESSION]);
+    attribute(&mut bytes, "dataWindow", "box2i", &window);
+    attribute(&mut bytes, "displayWindow", "box2i", &window);
+    // from the top row down
+    attribute(&mut bytes, "lineOrder", "lineOrder", &[0]);
+    attribute(&mut bytes, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute(&mut bytes, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(
+        &mut bytes,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    bytes.push(0);
+
+    // the offset of every block comes before the blocks
+    let blocks = height.div_ceil(LINES_PER_BLOCK);
+    let table = bytes.len();
+    bytes.resize(table + blocks as usize * 8, 0);
+    for block in 0..blocks {
+        let offset = bytes.len() as u64;
+        let at = table + block as usize * 8;
+        bytes[at..at + 8].copy_from_slice(&offset.to_le_bytes());
+
+        let first = block * LINES_PER_BLOCK;
+        let last = (first + LINES_PER_BLOCK).min(height);
+        let mut raw = vec![];
+        for y in first..last {
+            for (_, layer, index) in channels.iter() {
+                let image = &layers[*layer].image;
+                for x in 0..width {
+                    let value = image.get_pixel(x, y).0[*index];
+                    raw.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+        }
+        let data = compress(&raw);
+        bytes.extend_from_slice(&(first as i32).to_le_bytes());
+        bytes.extend_from_slice(&(data.len() as i32).to_le_bytes());
+        bytes.extend_from_slice(&data);
+    }
+    Ok(bytes)
+}
+
+/// Encodes layers as one image, and writes it to a file.
+pub fn write(path: &Path, layers: &[Layer]) -> Result<(), String> {
+    let bytes = encode(layers)?;
+    fs::write(path, bytes)
+        .map_err(|e| format!("Could not write `{}`: {}", path.display(), e))
+}
+
+fn attribute(bytes: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    bytes.extend_from_slice(name.as_bytes());
+    bytes.push(0);
+    bytes.extend_from_slice(kind.as_bytes());
+    bytes.push(0);
+    bytes.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    bytes.extend_from_slice(value);
+}
+
+/// Compresses a block as OpenEXR's zip compression does:
+/// bytes at even and odd offsets are split apart, each is
+/// stored as the difference from the one before, and the
+/// result is deflated. Blocks that don't get smaller are
+/// kept as they are, which readers know by their size.
+fn compress(raw: &[u8]) -> Vec<u8> {
+    let half = raw.len().div_ceil(2);
+    let mut split = vec![0; raw.len()];
+    for (index, byte) in raw.iter().enumerate() {
+        let at = if index % 2 == 0 {
+            index / 2
+        } else {
+            half + index / 2
+        };
+        split[at] = *byte;
+    }
+    let mut previous = split.first().copied().unwrap_or(0);
+    for byte in split.iter_mut().skip(1) {
+        let current = *byte;
+        *byte = current.wrapping_sub(previous).wrapping_add(128);
+        previous = current;
+    }
+
+    let compressed = deflate::deflate_bytes_zlib(&split);
+    if compressed.len() < raw.len() {
+        compressed
+    } else {
+        raw.to_vec()
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    sync::mpsc::{
+        self,
+        Receiver,
+        Sender,
+    },
+    thread,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use serde_json::Value;
+
+use crate::data::select;
+
+/// Where a feed gets its data from.
+#[derive(Debug, Clone)]
+pub enum Source {
+    /// Fetch a URL over HTTP every `interval`.
+    Poll {
+        url:      String,
+        interval: Duration,
+    },
+    /// Subscribe to a websocket, handling every message.
+    Socket { url: String },
+}
+
+impl Source {
+    /// Picks a source based on the URL scheme.
+    pub fn from_url(url: &str, interval: Duration) -> Source {
+        if url.starts_with("ws://") || url.starts_with("wss://") {
+            Source::Socket {
+                url: url.to_string(),
+            }
+        } else {
+            Source::Poll {
+                url: url.to_string(),
+                interval,
+            }
+        }
+    }
+}
+
+/// What to pull out of each JSON document received.
+#[derive(Debug, Clone, Default)]
+pub struct Mapping {
+    /// Uniform names and the JSON path of the scalar that
+    /// sets them.
+    pub fields: Vec<(String, String)>,
+    /// JSON paths of arrays, each becoming a row of the
+    /// feed's data texture.
+    pub rows:   Vec<String>,
+}
+
+/// A single update produced by a feed.
+#[derive(Debug, Clone, Default)]
+pub struct FeedUpdate {
+    /// One value per field, in the order of
+    /// [`Mapping::fields`].
+    pub fields: Vec<f32>,
+    /// One array per row path, in the order of
+    /// [`Mapping::rows`].
+    pub rows:   Vec<Vec<f32>>,
+}
+
+impl FeedUpdate {
+    /// Extracts all mapped values from a JSON document.
+    /// Missing or non-numeric values become zero.
+    pub fn extract(mapping: &Mapping, json: &Value) -> FeedUpdate {
+        let number =
+            |v: Option<&Value>| v.and_then(Value::as_f64).unwrap_or(0.0) as f32;
+
+        let fields = mapping
+            .fields
+            .iter()
+            .map(|(_, path)| number(select(json, path)))
+            .collect();
+
+        let rows = mapping
+            .rows
+            .iter()
+            .map(|path| match select(json, path) {
+                Some(Value::Array(items)) => {
+                    items.iter().map(|v| number(Some(v))).collect()
+                },
+                other => vec![number(other)],
+            })
+            .collect();
+
+        FeedUpdate { fields, rows }
+    }
+}
+
+/// A live data feed running on a background thread.
+/// Updates are throttled so that at most one is sent every
+/// `throttle`. The thread stops once the feed is dropped.
+pub struct Feed {
+    receiver: Receiver<FeedUpdate>,
+    latest:   Option<FeedUpdate>,
+}
+
+impl Feed {
+    pub fn spawn(source: Source, mapping: Mapping, throttle: Duration) -> Feed {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut throttled = Throttled {
+                sender,
+                mapping,
+                throttle,
+                last: None,
+            };
+            match source {
+                Source::Poll { url, interval } => {
+                    poll(&url, interval, &mut throttled)
+                },
+                Source::Socket { url } => subscribe(&url, &mut throttled),
+            }
+        });
+
+        Feed {
+            receiver,
+            latest: None,
+        }
+    }
+
+    /// A feed that never connects, so its fields stay at
+    /// zero, see [`crate::graph::Plan::set_safe`].
+    pub fn idle() -> Feed {
+        Feed {
+            receiver: mpsc::channel().1,
+            latest:   None,
+        }
+    }
+
+    /// Returns the newest update if one arrived since the
+    /// last call.
+    pub fn update(&mut self) -> Option<&FeedUpdate> {
+        let newest = self.receiver.try_iter().last()?;
+        self.latest = Some(newest);
+        self.latest.as_ref()
+    }
+
+    /// Returns the most recent update received, if any.
+    pub fn latest(&self) -> Option<&FeedUpdate> { self.latest.as_ref() }
+}
+
+struct Throttled {
+    sender:   Sender<FeedUpdate>,
+    mapping:  Mapping,
+    throttle: Duration,
+    last:     Option<Instant>,
+}
+
+impl Throttled {
+    /// Parses and sends a document, unless one was sent too
+    /// recently. Returns `false` once the feed is dropped.
+    fn send(&mut self, text: &str) -> bool {
+        if let Some(last) = self.last {
+            if last.elapsed() < self.throttle {
+                return true;
+            }
+        }
+
+        let json = match serde_json::from_str(text) {
+            Ok(json) => json,
+            Err(e) => {
+                eprintln!("[warn] Feed sent invalid JSON: {}", e);
+                return true;
+            },
+        };
+
+        self.last = Some(Instant::now());
+        let update = FeedUpdate::extract(&self.mapping, &json);
+        self.sender.send(update).is_ok()
+    }
+}
+
+fn poll(url: &str, interval: Duration, throttled: &mut Throttled) {
+    loop {
+        match ureq::get(url).call().map(|r| r.into_string()) {
+            Ok(Ok(text)) => {
+                if !throttled.send(&text) {
+                    return;
+                }
+            },
+            Ok(Err(e)) => eprintln!("[warn] Could not read `{}`: {}", url, e),
+            Err(e) => eprintln!("[warn] Could not fetch `{}`: {}", url, e),
+        }
+        thread::sleep(interval);
+    }
+}
+
+fn subscribe(url: &str, throttled: &mut Throttled) {
+    loop {
+        let mut socket = match tungstenite::connect(url) {
+            Ok((socket, _)) => socket,
+            Err(e) => {
+                eprintln!("[warn] Could not connect to `{}`: {}", url, e);
+                thread::sleep(Duration::from_secs(1));
+                continue;
+            },
+        };
+
+        loop {
+            match socket.read() {
+                Ok(tungstenite::Message::Text(text)) => {
+                    if !throttled.send(&text) {
+                        return;
+                    }
+                },
+                Ok(_) => (),
+                Err(e) => {
+                    eprintln!("[warn] Lost connection to `{}`: {}", url, e);
+                    break;
+                },
+            }
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,574 @@
+//! A C ABI for embedding shadergarden in hosts that aren't
+//! written in rust, like C++ apps, Python through `ctypes`,
+//! or game engine plugins, declared in
+//! `include/shadergarden.h`. The host owns the GL context,
+//! which must be current on the calling thread whenever one
+//! of these functions is called. Functions that can fail
+//! return null or `-1`, and leave a message to be read with
+//! [`shadergarden_last_error`].
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    ffi::{
+        CStr,
+        CString,
+    },
+    os::raw::{
+        c_char,
+        c_int,
+        c_void,
+    },
+    panic::{
+        self,
+        AssertUnwindSafe,
+    },
+    path::PathBuf,
+    ptr,
+    rc::Rc,
+    slice,
+};
+
+use glium::{
+    backend::{
+        Backend,
+        Context,
+        Facade,
+    },
+    debug::DebugCallbackBehavior,
+    texture::{
+        Dimensions,
+        MipmapsOption,
+        UncompressedFloatFormat,
+    },
+    GlObject,
+    SwapBuffersError,
+    Texture2d,
+};
+
+use crate::{
+    diagnostic::BuildError,
+    graph::{
+        PixelFormat,
+        Uniform,
+    },
+    lisp::BuildOptions,
+    reload::{
+        ShaderGraphWatcher,
+        WatchResult,
+    },
+    util::{
+        self,
+        read_texture,
+        Headless,
+    },
+};
+
+/// Looks up a GL function by name, in the host's context.
+pub type GetProcAddress =
+    unsafe extern "C" fn(*const c_char, *mut c_void) -> *const c_void;
+
+/// A GL texture, by its name in the host's context.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ShaderGardenTexture {
+    pub id:     u32,
+    pub width:  u32,
+    pub height: u32,
+}
+
+/// A project being run for a host, and rebuilt as it
+/// changes.
+pub struct ShaderGarden {
+    context:   Rc<Context>,
+    watcher:   ShaderGraphWatcher,
+    /// The context of a garden made with
+    /// [`shadergarden_create_headless`], dropped after the
+    /// graph.
+    _headless: Option<Headless>,
+}
+
+/// The host's context, which shadergarden never makes
+/// current or presents, as the host does.
+struct HostBackend {
+    get_proc_address: GetProcAddress,
+    user:             *mut c_void,
+}
+
+unsafe impl Backend for HostBackend {
+    fn swap_buffers(&self) -> Result<(), SwapBuffersError> { Ok(()) }
+
+    unsafe fn get_proc_address(&self, symbol: &str) -> *const c_void {
+        match CString::new(symbol) {
+            Ok(symbol) => (self.get_proc_address)(symbol.as_ptr(), self.user),
+            Err(_) => ptr::null(),
+        }
+    }
+
+    // the default framebuffer is never drawn to
+    fn get_framebuffer_dimensions(&self) -> (u32, u32) { (1, 1) }
+
+    fn is_current(&self) -> bool { true }
+
+    unsafe fn make_current(&self) {}
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(error: String) {
+    // messages can't hold nul bytes, but just in case
+    let error = error.replace('\0', " ");
+    let error = CString::new(error).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(error));
+}
+
+/// Runs the body of an exported function, storing its
+/// error, if any, and keeping panics from unwinding into
+/// the host.
+fn guard<T>(failed: T, body: impl FnOnce() -> Result<T, String>) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(error)) => {
+            set_error(error);
+            failed
+        },
+        Err(_) => {
+            set_error("shadergarden panicked, see stderr".to_string());
+            failed
+        },
+    }
+}
+
+/// Reads a string argument, which must be UTF-8.
+unsafe fn string<'a>(
+    pointer: *const c_char,
+    name: &str,
+) -> Result<&'a str, String> {
+    if pointer.is_null() {
+        return Err(format!("`{}` is null", name));
+    }
+    CStr::from_ptr(pointer)
+        .to_str()
+        .map_err(|_| format!("`{}` is not UTF-8", name))
+}
+
+unsafe fn borrow<'a>(
+    garden: *mut ShaderGarden,
+) -> Result<&'a mut ShaderGarden, String> {
+    garden
+        .as_mut()
+        .ok_or_else(|| "The garden is null".to_string())
+}
+
+/// The message of the last error on this thread, or null.
+/// It is valid until the next call that fails.
+#[no_mangle]
+pub extern "C" fn shadergarden_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| match &*last.borrow() {
+        Some(error) => error.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// Builds a project in the host's current context, and
+/// starts watching it for changes. `graph` may be null, for
+/// the project's `shader.graph`. Returns null on failure.
+///
+/// # Safety
+///
+/// Strings must be nul-terminated, and `get_proc_address`
+/// must look up functions in the current context.
+#[no_mangle]
+pub unsafe extern "C" fn shadergarden_create(
+    get_proc_address: Option<GetProcAddress>,
+    user: *mut c_void,
+    project: *const c_char,
+    graph: *const c_char,
+) -> *mut ShaderGarden {
+    guard(ptr::null_mut(), || {
+        let get_proc_address = get_proc_address
+            .ok_or_else(|| "`get_proc_address` is null".to_string())?;
+        let backend = HostBackend {
+            get_proc_address,
+            user,
+        };
+        let context =
+            Context::new(backend, false, DebugCallbackBehavior::default())
+                .map_err(|e| format!("Could not use the context: {}", e))?;
+        let garden = create(context, None, project, graph)?;
+        Ok(Box::into_raw(Box::new(garden)))
+    })
+}
+
+/// Like [`shadergarden_create`], but in an offscreen
+/// context of its own, on the GPU where there's a display
+/// server, or else rendered in software with OSMesa, see
+/// [`util::create_headless`], for hosts without a GL
+/// context, like scripts. Outputs are read back with
+/// [`shadergarden_read_output`].
+///
+/// # Safety
+///
+/// Strings must be nul-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn shadergarden_create_headless(
+    project: *const c_char,
+    graph: *const c_char,
+) -> *mut ShaderGarden {
+    guard(ptr::null_mut(), || {
+        let headless = util::create_headless(1, 1)?;
+        let context = headless.renderer.get_context().clone();
+        let garden = create(context, Some(headless), project, graph)?;
+        Ok(Box::into_raw(Box::new(garden)))
+    })
+}
+
+/// Builds a project in a context, and starts watching it.
+unsafe fn create(
+    context: Rc<Context>,
+    headless: Option<Headless>,
+    project: *const c_char,
+    graph: *const c_char,
+) -> Result<ShaderGarden, String> {
+    let project = PathBuf::from(string(project, "project")?);
+    let config = match graph.is_null() {
+        true => project.join("shader.graph"),
+        false => PathBuf::from(string(graph, "graph")?),
+    };
+    let watcher = ShaderGraphWatcher::new_watch_dir(
+        &context,
+        &project,
+        &config,
+        BuildOptions::default(),
+    )
+    .map_err(|e| BuildError::new(e, &project, &config).to_string())?;
+
+    Ok(ShaderGarden {
+        context,
+        watcher,
+        _headless: headless,
+    })
+}
+
+/// Frees a garden, and stops watching its project.
+///
+/// # Safety
+///
+/// `garden` must come from [`shadergarden_create`], and not
+/// be used again.
+#[no_mangle]
+pub unsafe extern "C" fn shadergarden_destroy(garden: *mut ShaderGarden) {
+    if !garden.is_null() {
+        guard((), || {
+            drop(Box::from_raw(garden));
+            Ok(())
+        })
+    }
+}
+
+/// Rebuilds the graph if the project changed since the
+/// last call, or whether it did or not if `force` isn't 0.
+/// Call this before uploading inputs for the next forward
+/// pass, as a rebuilt graph has nothing uploaded yet.
+/// Returns 1 if the graph was rebuilt, and 0 if not. If it
+/// fails to build, the last graph that built keeps running.
+///
+/// # Safety
+///
+/// `garden` must come from [`shadergarden_create`].
+#[no_mangle]
+pub unsafe extern "C" fn shadergarden_reload(
+    garden: *mut ShaderGarden,
+    force: c_int,
+) -> c_int {
+    guard(-1, || {
+        let garden = borrow(garden)?;
+        let (_, result) = match force {
+            0 => garden.watcher.graph(),
+            _ => garden.watcher.graph_force_reload(),
+        };
+        match result {
+            WatchResult::NoChange => Ok(0),
+            WatchResult::Rebuilt => Ok(1),
+            WatchResult::Err(e) => Err(e.to_string()),
+        }
+    })
+}
+
+/// Runs the graph once, see [`shadergarden_reload`] to
+/// pick up changes to the project. `inputs` are passed to
+/// the graph's inputs in order; inputs not given must have
+/// pixels uploaded with [`shadergarden_upload_input`].
+/// Writes up to `capacity` outputs, which stay valid until
+/// the next call, and returns how many the graph has.
+///
+/// # Safety
+///
+/// `garden` must come from [`shadergarden_create`], and
+/// `inputs` and `outputs` must hold as many textures as
+/// their counts say.
+#[no_mangle]
+pub unsafe extern "C" fn shadergarden_forward(
+    garden: *mut ShaderGarden,
+    inputs: *const ShaderGardenTexture,
+    input_count: usize,
+    outputs: *mut ShaderGardenTexture,
+    capacity: usize,
+) -> c_int {
+    guard(-1, || {
+        let garden = borrow(garden)?;
+        let inputs = match input_count {
+            0 => &[],
+            _ => slice::from_raw_parts(inputs, input_count),
+        };
+
+        let context = garden.context.clone();
+        let graph = garden.watcher.graph_no_reload();
+        if inputs.len() > graph.get_inputs().len() {
+            return Err(format!(
+                "Passed {} inputs to a graph with {}",
+                inputs.len(),
+                graph.get_inputs().len()
+            ));
+        }
+
+        // the host keeps ownership of its textures
+        let wrapped = inputs
+            .iter()
+            .map(|texture| {
+                Texture2d::from_id(
+                    &context,
+                    UncompressedFloatFormat::U8U8U8U8,
+                    texture.id,
+                    false,
+                    MipmapsOption::NoMipmap,
+                    Dimensions::Texture2d {
+                        width:  texture.width,
+                        height: texture.height,
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        let input_map = graph
+            .get_inputs()
+            .iter()
+            .copied()
+            .zip(wrapped.iter())
+            .collect::<BTreeMap<_, _>>();
+
+        let order = graph.get_outputs().clone();
+        let output_map = graph.forward(input_map);
+        for (index, id) in order.iter().take(capacity).enumerate() {
+            let texture = output_map[id];
+            *outputs.add(index) = ShaderGardenTexture {
+                id:     texture.get_id(),
+                width:  texture.width(),
+                height: texture.height(),
+            };
+        }
+        Ok(order.len() as c_int)
+    })
+}
+
+/// Uploads pixels from the host's memory to a named input,
+/// see [`crate::graph::ShaderGraph::upload_input`].
+/// `format` is 0 for RGB8, 1 for RGBA8, 2 for R32F, and 3
+/// for RGBA32F.
+///
+/// # Safety
+///
+/// `garden` must come from [`shadergarden_create`], and
+/// `pixels` must hold `length` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn shadergarden_upload_input(
+    garden: *mut ShaderGarden,
+    name: *const c_char,
+    pixels: *const u8,
+    length: usize,
+    width: u32,
+    height: u32,
+    format: c_int,
+) -> c_int {
+    guard(-1, || {
+        let garden = borrow(garden)?;
+        let name = string(name, "name")?;
+        let format = match format {
+            0 => PixelFormat::Rgb8,
+            1 => PixelFormat::Rgba8,
+            2 => PixelFormat::R32f,
+            3 => PixelFormat::Rgba32f,
+            other => return Err(format!("Unknown pixel format {}", other)),
+        };
+        let pixels = match length {
+            0 => &[],
+            _ => slice::from_raw_parts(pixels, length),
+        };
+        let graph = garden.watcher.graph_no_reload();
+        graph.upload_input(name, pixels, width, height, format)?;
+        Ok(0)
+    })
+}
+
+/// Sets a uniform of a named node, see
+/// [`crate::graph::ShaderGraph::set_param`].
+///
+/// # Safety
+///
+/// `garden` must come from [`shadergarden_create`].
+#[no_mangle]
+pub unsafe extern "C" fn shadergarden_set_param(
+    garden: *mut ShaderGarden,
+    node: *const c_char,
+    uniform: *const c_char,
+    value: f32,
+) -> c_int {
+    guard(-1, || {
+        let garden = borrow(garden)?;
+        let node = string(node, "node")?;
+        let uniform = string(uniform, "uniform")?;
+        let graph = garden.watcher.graph_no_reload();
+        graph.set_param(node, uniform, value)?;
+        Ok(0)
+    })
+}
+
+/// Sets a uniform declared in the graph, see
+/// [`crate::graph::ShaderGraph::set_uniform`], from 1 to 4
+/// floats, for a `float` up to a `vec4`.
+///
+/// # Safety
+///
+/// `garden` must come from [`shadergarden_create`], and
+/// `values` must hold `count` floats.
+#[no_mangle]
+pub unsafe extern "C" fn shadergarden_set_uniform(
+    garden: *mut ShaderGarden,
+    name: *const c_char,
+    values: *const f32,
+    count: usize,
+) -> c_int {
+    guard(-1, || {
+        let garden = borrow(garden)?;
+        let name = string(name, "name")?;
+        if values.is_null() {
+            return Err("`values` is null".to_string());
+        }
+        let value = match *slice::from_raw_parts(values, count) {
+            [x] => Uniform::Float(x),
+            [x, y] => Uniform::Vec2([x, y]),
+            [x, y, z] => Uniform::Vec3([x, y, z]),
+            [x, y, z, w] => Uniform::Vec4([x, y, z, w]),
+            _ => {
+                return Err(format!("Cannot set a uniform to {} floats", count))
+            },
+        };
+        let graph = garden.watcher.graph_no_reload();
+        graph.set_uniform(name, value)?;
+        Ok(0)
+    })
+}
+
+/// Runs the following forward passes at `time`, with
+/// `u_dt` set to `dt`, rather than by the clock, see
+/// [`crate::graph::ShaderGraph::set_fixed_time`]. A rebuilt
+/// graph runs by the clock until this is called again.
+///
+/// # Safety
+///
+/// `garden` must come from [`shadergarden_create`].
+#[no_mangle]
+pub unsafe extern "C" fn shadergarden_set_time(
+    garden: *mut ShaderGarden,
+    time: f32,
+    dt: f32,
+) -> c_int {
+    guard(-1, || {
+        let garden = borrow(garden)?;
+        garden.watcher.graph_no_reload().set_fixed_time(time, dt);
+        Ok(0)
+    })
+}
+
+/// Rebuilds the graph with the size of every node scaled,
+/// e.g. by 0.5 to render at half resolution, see
+/// [`crate::lisp::BuildOptions::render_scale`]. The scale
+/// is kept when the project changes and the graph is
+/// rebuilt.
+///
+/// # Safety
+///
+/// `garden` must come from [`shadergarden_create`].
+#[no_mangle]
+pub unsafe extern "C" fn shadergarden_set_render_scale(
+    garden: *mut ShaderGarden,
+    scale: f32,
+) -> c_int {
+    guard(-1, || {
+        let garden = borrow(garden)?;
+        if !(scale > 0.0 && scale.is_finite()) {
+            return Err(format!("Cannot render at a scale of {}", scale));
+        }
+        let options = BuildOptions {
+            render_scale: scale,
+            ..garden.watcher.options().clone()
+        };
+        match garden.watcher.set_options(options) {
+            (_, WatchResult::Err(e)) => Err(e.to_string()),
+            _ => Ok(0),
+        }
+    })
+}
+
+/// Reads the nth output of the last forward pass back as
+/// RGBA floats, row by row from the top, writing its size
+/// to `width` and `height`. Returns the number of floats
+/// the output has, and only reads it if `capacity` is
+/// enough, so it can be called once with no buffer to find
+/// its size.
+///
+/// # Safety
+///
+/// `garden` must come from [`shadergarden_create`],
+/// `pixels` must hold `capacity` floats, and `width` and
+/// `height` must be valid or null.
+#[no_mangle]
+pub unsafe extern "C" fn shadergarden_read_output(
+    garden: *mut ShaderGarden,
+    index: usize,
+    pixels: *mut f32,
+    capacity: usize,
+    width: *mut u32,
+    height: *mut u32,
+) -> c_int {
+    guard(-1, || {
+        let garden = borrow(garden)?;
+        let graph = garden.watcher.graph_no_reload();
+        let id = *graph
+            .get_outputs()
+            .get(index)
+            .ok_or_else(|| format!("The graph has no output {}", index))?;
+        let texture = graph
+            .texture(id)
+            .ok_or_else(|| format!("Output {} has no texture", index))?;
+
+        let (w, h) = (texture.width(), texture.height());
+        if let Some(width) = width.as_mut() {
+            *width = w;
+        }
+        if let Some(height) = height.as_mut() {
+            *height = h;
+        }
+        let length = w as usize * h as usize * 4;
+        if capacity >= length && !pixels.is_null() {
+            let out = slice::from_raw_parts_mut(pixels, length);
+            let rows = read_texture(texture);
+            let rows = rows.chunks_exact(w.max(1) as usize).rev();
+            for (pixel, value) in rows.flatten().zip(out.chunks_exact_mut(4)) {
+                value.copy_from_slice(pixel);
+            }
+        }
+        Ok(length as c_int)
+    })
+}
@@ -0,0 +1,143 @@
+use std::{
+    io::{
+        BufRead,
+        BufReader,
+        Write,
+    },
+    net::{
+        TcpListener,
+        TcpStream,
+    },
+    time::Duration,
+};
+
+/// How long to wait on another machine before giving up on
+/// the lock.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A connection to one other machine in the lock.
+struct Peer {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Peer {
+    fn new(stream: TcpStream) -> Result<Peer, String> {
+        let error = |e: std::io::Error| format!("Frame lock error: {}", e);
+        stream.set_nodelay(true).map_err(error)?;
+        stream.set_read_timeout(Some(TIMEOUT)).map_err(error)?;
+        let writer = stream.try_clone().map_err(error)?;
+        Ok(Peer {
+            reader: BufReader::new(stream),
+            writer,
+        })
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.writer, "{}", line)
+            .map_err(|e| format!("Frame lock error: {}", e))
+    }
+
+    fn receive(&mut self) -> Result<String, String> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => Err("A machine left the frame lock".to_string()),
+            Ok(_) => Ok(line.trim_end().to_string()),
+            Err(e) => Err(format!("Frame lock error: {}", e)),
+        }
+    }
+}
+
+/// Keeps several machines rendering the same frame at the
+/// same time, e.g. the tiles of a video wall. Every frame,
+/// each machine waits at a barrier until all of them are
+/// ready, and they agree on a frame number, which is used
+/// as a shared clock instead of each machine's own.
+pub struct FrameLock {
+    role: Role,
+}
+
+enum Role {
+    /// Waits for every follower, then releases them all.
+    Leader {
+        followers: Vec<Peer>,
+        frame:     u64,
+    },
+    Follower(Peer),
+}
+
+impl FrameLock {
+    /// Accepts `followers` machines on `address`, waiting
+    /// until all of them have joined.
+    pub fn lead(address: &str, followers: usize) -> Result<FrameLock, String> {
+        let listener = TcpListener::bind(address)
+            .map_err(|e| format!("Could not listen on `{}`: {}", address, e))?;
+
+        let mut peers = vec![];
+        while peers.len() < followers {
+            let (stream, peer) = listener
+                .accept()
+                .map_err(|e| format!("Frame lock error: {}", e))?;
+            eprintln!(
+                "[info] {} joined the frame lock ({} of {})",
+                peer,
+                peers.len() + 1,
+                followers,
+            );
+            peers.push(Peer::new(stream)?);
+        }
+
+        Ok(FrameLock {
+            role: Role::Leader {
+                followers: peers,
+                frame:     0,
+            },
+        })
+    }
+
+    /// Joins the lock led from `address`.
+    pub fn follow(address: &str) -> Result<FrameLock, String> {
+        let stream = TcpStream::connect(address).map_err(|e| {
+            format!("Could not connect to `{}`: {}", address, e)
+        })?;
+        Ok(FrameLock {
+            role: Role::Follower(Peer::new(stream)?),
+        })
+    }
+
+    /// Waits until every machine is ready to render, and
+    /// returns the number of the frame to render.
+    pub fn barrier(&mut self) -> Result<u64, String> {
+        match &mut self.role {
+            Role::Leader { followers, frame } => {
+                for follower in followers.iter_mut() {
+                    match follower.receive()?.as_str() {
+                        "ready" => (),
+                        other => {
+                            return Err(format!(
+                                "Unexpected frame lock message `{}`",
+                                other
+                            ))
+                        },
+                    }
+                }
+
+                let current = *frame;
+                for follower in followers.iter_mut() {
+                    follower.send(&format!("go {}", current))?;
+                }
+                *frame += 1;
+                Ok(current)
+            },
+            Role::Follower(leader) => {
+                leader.send("ready")?;
+                let line = leader.receive()?;
+                line.strip_prefix("go ")
+                    .and_then(|frame| frame.parse().ok())
+                    .ok_or_else(|| {
+                        format!("Unexpected frame lock message `{}`", line)
+                    })
+            },
+        }
+    }
+}
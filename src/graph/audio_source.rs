@@ -0,0 +1,55 @@
+use std::borrow::Cow;
+
+use glium::{
+    texture::{
+        ClientFormat,
+        RawImage2d,
+    },
+    Rect,
+    Texture2d,
+};
+
+use crate::{
+    audio::{
+        Analyser,
+        Capture,
+        WIDTH,
+    },
+    graph::InputSource,
+};
+
+/// A source that captures live audio, laid out like a
+/// Shadertoy audio channel: a `512x2` texture whose bottom
+/// row is the spectrum and whose top row is the waveform,
+/// both in the red channel and updated every frame.
+pub struct AudioSource {
+    pub capture:  Capture,
+    pub analyser: Analyser,
+    pub texture:  Texture2d,
+}
+
+impl std::fmt::Debug for AudioSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AudioSource").finish()
+    }
+}
+
+impl InputSource for AudioSource {
+    fn update(&mut self) {
+        let values = self.analyser.analyse(&self.capture.samples());
+        let rect = Rect {
+            left:   0,
+            bottom: 0,
+            width:  WIDTH as u32,
+            height: 2,
+        };
+        self.texture.write(rect, RawImage2d {
+            data:   Cow::Owned(values),
+            width:  WIDTH as u32,
+            height: 2,
+            format: ClientFormat::F32,
+        });
+    }
+
+    fn texture(&self) -> &Texture2d { &self.texture }
+}
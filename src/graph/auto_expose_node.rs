@@ -0,0 +1,153 @@
+use std::{
+    rc::Rc,
+    time::Instant,
+};
+
+use glium::{
+    backend::Context,
+    uniform,
+    uniforms::{
+        AsUniformValue,
+        UniformValue,
+    },
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::{
+    graph::{
+        node::Node,
+        stats_node::StatsNode,
+        NodeId,
+        UniformMap,
+    },
+    util::{
+        compile_shader,
+        default_buffer,
+        RectStrip,
+    },
+};
+
+/// Tonemaps its input with an exposure that slowly adapts
+/// to the input's mean luminance, like an eye adjusting to
+/// the dark. The adapted exposure is passed as
+/// `u_exposure` to every node that takes this one as an
+/// input.
+pub struct AutoExposeNode {
+    pub context:  Rc<Context>,
+    pub stats:    StatsNode,
+    pub tonemap:  Program,
+    pub texture:  Texture2d,
+    /// How quickly the exposure adapts, per second.
+    pub speed:    f32,
+    /// The mean luminance the exposure aims for.
+    pub key:      f32,
+    pub exposure: f32,
+    last:         Option<Instant>,
+}
+
+impl std::fmt::Debug for AutoExposeNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AutoExposeNode")
+            .field("input", &self.stats.input)
+            .field("speed", &self.speed)
+            .field("key", &self.key)
+            .field("exposure", &self.exposure)
+            .finish()
+    }
+}
+
+impl AutoExposeNode {
+    pub fn new(
+        context: &Rc<Context>,
+        input: NodeId,
+        speed: f32,
+        key: f32,
+    ) -> Result<AutoExposeNode, String> {
+        Ok(AutoExposeNode {
+            context: context.clone(),
+            stats: StatsNode::new(context, input, 1)?,
+            tonemap: compile_shader(
+                context,
+                include_str!("./shaders/tonemap.frag"),
+            )?,
+            texture: default_buffer(context, 1, 1),
+            speed,
+            key,
+            exposure: 1.0,
+            last: None,
+        })
+    }
+
+    /// Moves the exposure towards the one that would bring
+    /// the mean luminance to `key`, frame-rate
+    /// independently.
+    fn adapt(&mut self) {
+        let now = Instant::now();
+        let delta = match self.last.replace(now) {
+            Some(last) => (now - last).as_secs_f32(),
+            None => {
+                // jump straight to the target on the first frame
+                self.exposure = self.target();
+                return;
+            },
+        };
+
+        let blend = 1.0 - (-delta * self.speed).exp();
+        self.exposure += (self.target() - self.exposure) * blend;
+    }
+
+    fn target(&self) -> f32 { self.key / self.stats.stats.mean.max(1e-4) }
+}
+
+impl Node for AutoExposeNode {
+    fn inputs(&self) -> Vec<NodeId> { vec![self.stats.input] }
+
+    fn outputs(&self) -> (&str, UniformValue<'_>) {
+        ("texture", self.texture.as_uniform_value())
+    }
+
+    fn uniforms(&self) -> Vec<(String, UniformValue<'_>)> {
+        vec![("exposure".to_string(), UniformValue::Float(self.exposure))]
+    }
+
+    fn texture(&self) -> Option<&Texture2d> { Some(&self.texture) }
+
+    // the exposure eases towards the target over time
+    fn remembers(&self) -> bool { true }
+
+    fn stats(&self) -> Option<&crate::graph::Stats> { Some(&self.stats.stats) }
+
+    fn forward(&mut self, rect_strip: &RectStrip, uniforms: UniformMap) {
+        let input = match uniforms.get("texture", 0) {
+            Some(UniformValue::Texture2d(texture, _)) => *texture,
+            _ => {
+                eprintln!("[warn] Auto-expose node expects a texture input");
+                return;
+            },
+        };
+
+        if self.texture.dimensions() != input.dimensions() {
+            self.texture =
+                default_buffer(&self.context, input.width(), input.height());
+        }
+
+        self.stats.reduce(rect_strip, input);
+        self.adapt();
+
+        self.texture
+            .as_surface()
+            .draw(
+                &rect_strip.buffer,
+                rect_strip.indices,
+                &self.tonemap,
+                &uniform! {
+                    u_input: input,
+                    u_exposure: self.exposure,
+                },
+                &Default::default(),
+            )
+            .unwrap();
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use serde_json::{
+    json,
+    Value,
+};
+
+use crate::graph::NodeId;
+
+const DIR: &str = "baked";
+const MANIFEST: &str = "bake.json";
+
+/// A node rendered ahead of time into an image, see
+/// [`crate::graph::Plan::bake_node`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Baked {
+    pub node:  NodeId,
+    /// The node's name, if it has one, for people reading
+    /// the manifest.
+    pub name:  Option<String>,
+    /// What the node rendered, see
+    /// [`crate::graph::Plan::cache_key`].
+    pub key:   String,
+    pub image: PathBuf,
+}
+
+impl Baked {
+    /// Where `shadergarden bake` puts its images in a
+    /// project, along with a manifest of what each
+    /// replaces.
+    pub fn dir(project: &Path) -> PathBuf { project.join(DIR) }
+
+    /// Reads the manifest in a directory of baked images,
+    /// with every image in the directory. A directory
+    /// without one has nothing baked.
+    pub fn read_all(dir: &Path) -> Result<Vec<Baked>, String> {
+        let path = dir.join(MANIFEST);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(vec![])
+            },
+            Err(e) => {
+                return Err(format!(
+                    "Could not read `{}`: {}",
+                    path.display(),
+                    e
+                ))
+            },
+        };
+        let malformed =
+            || format!("`{}` is not a valid manifest", path.display());
+        let json: Value =
+            serde_json::from_str(&text).map_err(|_| malformed())?;
+        let nodes = json["nodes"].as_array().ok_or_else(malformed)?;
+        nodes
+            .iter()
+            .map(|node| {
+                let id = node["node"].as_u64().ok_or_else(malformed)?;
+                let key = node["key"].as_str().ok_or_else(malformed)?;
+                let image = node["image"].as_str().ok_or_else(malformed)?;
+                Ok(Baked {
+                    node:  NodeId(id as usize),
+                    name:  node["name"].as_str().map(str::to_string),
+                    key:   key.to_string(),
+                    image: dir.join(image),
+                })
+            })
+            .collect()
+    }
+
+    /// Writes the manifest of a directory of baked images,
+    /// which must all be in the directory.
+    pub fn write_all(dir: &Path, baked: &[Baked]) -> Result<(), String> {
+        let nodes = baked
+            .iter()
+            .map(|baked| {
+                let image =
+                    baked.image.strip_prefix(dir).unwrap_or(&baked.image);
+                json!({
+                    "node": baked.node.0,
+                    "name": baked.name,
+                    "key": baked.key,
+                    "image": image,
+                })
+            })
+            .collect::<Vec<_>>();
+        let text = serde_json::to_string_pretty(&json!({ "nodes": nodes }))
+            .map_err(|e| e.to_string())?;
+        let path = dir.join(MANIFEST);
+        fs::write(&path, text + "\n")
+            .map_err(|e| format!("Could not write `{}`: {}", path.display(), e))
+    }
+}
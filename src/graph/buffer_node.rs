@@ -0,0 +1,69 @@
+use std::rc::Rc;
+
+use glium::{
+    backend::Context,
+    uniforms::UniformValue,
+    Surface,
+    Texture2d,
+};
+
+use crate::{
+    graph::{
+        node::Node,
+        NodeId,
+        TextureOptions,
+        UniformMap,
+    },
+    util::RectStrip,
+};
+
+/// A texture that any node can read, and that one node
+/// writes, or the graph's output, for state consulted by
+/// several unrelated passes, and for echoes and global
+/// feedback without the graph being built around a
+/// recurrent shader. It starts out black, and is written by
+/// the graph after every frame, so every node reading it
+/// sees the same frame, the one before. See
+/// [`crate::graph::ShaderGraph::add_buffer`] and
+/// [`crate::graph::ShaderGraph::add_previous_output`].
+pub struct BufferNode {
+    pub texture: Texture2d,
+    pub options: TextureOptions,
+}
+
+impl BufferNode {
+    /// Creates a buffer cleared to transparent black.
+    pub fn new(
+        context: &Rc<Context>,
+        (width, height): (u32, u32),
+        options: TextureOptions,
+    ) -> Result<BufferNode, String> {
+        let texture = options.buffer(context, width, height)?;
+        texture.as_surface().clear_color(0.0, 0.0, 0.0, 0.0);
+        Ok(BufferNode { texture, options })
+    }
+}
+
+impl std::fmt::Debug for BufferNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BufferNode")
+            .field("width", &self.texture.get_width())
+            .field("height", &self.texture.get_height())
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+impl Node for BufferNode {
+    fn inputs(&self) -> Vec<NodeId> { vec![] }
+
+    fn outputs(&self) -> (&str, UniformValue<'_>) {
+        ("texture", self.options.sampled(&self.texture))
+    }
+
+    fn texture(&self) -> Option<&Texture2d> { Some(&self.texture) }
+
+    fn state(&self) -> Vec<&Texture2d> { vec![&self.texture] }
+
+    fn forward(&mut self, _rect_strip: &RectStrip, _uniforms: UniformMap) {}
+}
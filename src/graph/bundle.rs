@@ -0,0 +1,972 @@
+use std::{
+    collections::BTreeMap,
+    convert::TryInto,
+    fs,
+    io::{
+        BufWriter,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    rc::Rc,
+    str::FromStr,
+};
+
+use glium::backend::Context;
+use serde_json::{
+    json,
+    Value,
+};
+
+use crate::{
+    capabilities::Requirements,
+    data::DataTable,
+    graph::{
+        snapshot::Reader,
+        Baked,
+        Condition,
+        External,
+        Init,
+        NodeId,
+        NodeSpec,
+        OutputWindow,
+        Plan,
+        Profile,
+        RandomTexture,
+        ShaderGraph,
+        TextureOptions,
+    },
+    knobs::{
+        Knob,
+        Smoothing,
+        Source,
+    },
+    meta::Meta,
+    palette::Palette,
+    points::Point,
+    util::binary::{
+        self,
+        ProgramBinary,
+    },
+    video::Playback,
+};
+
+/// Written at the start of every bundle, with a version.
+const MAGIC: &[u8; 8] = b"SGBUNDL2";
+
+/// A graph frozen for release: its plan, with every shader
+/// as it is compiled, and the program binaries the driver
+/// linked them into. Realizing a bundle reads no lisp and
+/// watches no files, and links programs from their binaries
+/// where the driver accepts them, compiling them otherwise.
+/// Files the graph reads as it runs, like videos, are not
+/// bundled, and are opened where they were when it was
+/// frozen.
+#[derive(Debug, Clone)]
+pub struct Bundle {
+    plan:     Plan,
+    binaries: Vec<ProgramBinary>,
+}
+
+impl Bundle {
+    /// Freezes a plan by realizing it once, which also
+    /// checks that it builds. Returns the bundle along with
+    /// the graph realized.
+    pub fn freeze(
+        context: &Rc<Context>,
+        plan: &Plan,
+        external: &External,
+    ) -> Result<(Bundle, ShaderGraph), String> {
+        let plan = plan.frozen();
+        for (spec, _) in plan.nodes.iter() {
+            to_json(spec)?;
+        }
+        for fallback in plan.fallbacks.values() {
+            to_json(fallback)?;
+        }
+
+        let mut graph = ShaderGraph::new(context);
+        binary::record_binaries(true);
+        let realized = plan.realize(&mut graph, external);
+        binary::record_binaries(false);
+        let binaries = binary::take_binaries();
+        realized?;
+        Ok((Bundle { plan, binaries }, graph))
+    }
+
+    /// The plan the bundle realizes.
+    pub fn plan(&self) -> &Plan { &self.plan }
+
+    /// Creates the bundled graph, see [`Plan::realize`].
+    pub fn realize(
+        &self,
+        graph: &mut ShaderGraph,
+        external: &External,
+    ) -> Result<(), String> {
+        binary::load_binaries(self.binaries.clone());
+        let realized = self.plan.realize(graph, external);
+        binary::take_binaries();
+        realized
+    }
+
+    /// Writes the bundle as little-endian binary: the magic
+    /// bytes, the plan as JSON, then each program binary,
+    /// each prefixed with a `u32` length.
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let plan = plan_to_json(&self.plan)?.to_string();
+        let error = |e: std::io::Error| {
+            format!("Could not write bundle `{}`: {}", path.display(), e)
+        };
+        let file = fs::File::create(path).map_err(error)?;
+        let mut out = BufWriter::new(file);
+        let mut put = |bytes: &[u8]| out.write_all(bytes).map_err(error);
+
+        put(MAGIC)?;
+        put(&(plan.len() as u32).to_le_bytes())?;
+        put(plan.as_bytes())?;
+        put(&(self.binaries.len() as u32).to_le_bytes())?;
+        for binary in self.binaries.iter() {
+            put(&binary.key)?;
+            put(&binary.format.to_le_bytes())?;
+            put(&(binary.content.len() as u32).to_le_bytes())?;
+            put(&binary.content)?;
+        }
+        out.flush().map_err(error)
+    }
+
+    /// Reads a bundle written by [`Bundle::write`].
+    pub fn read(path: &Path) -> Result<Bundle, String> {
+        let bytes = fs::read(path).map_err(|e| {
+            format!("Could not read bundle `{}`: {}", path.display(), e)
+        })?;
+        let invalid = || format!("`{}` is not a valid bundle", path.display());
+
+        let mut reader = Reader { bytes: &bytes };
+        if reader.take(MAGIC.len()).ok_or_else(invalid)? != MAGIC {
+            return Err(invalid());
+        }
+        let length = reader.u32().ok_or_else(invalid)?;
+        let plan = reader.take(length as usize).ok_or_else(invalid)?;
+        let plan = serde_json::from_slice(plan)
+            .map_err(|e| e.to_string())
+            .and_then(|json| plan_from_json(&json))
+            .map_err(|e| format!("{}: {}", invalid(), e))?;
+
+        let mut binaries = vec![];
+        for _ in 0..reader.u32().ok_or_else(invalid)? {
+            let key = reader.take(20).and_then(|key| key.try_into().ok());
+            let key = key.ok_or_else(invalid)?;
+            let format = reader.u32().ok_or_else(invalid)?;
+            let length = reader.u32().ok_or_else(invalid)?;
+            let content = reader.take(length as usize).ok_or_else(invalid)?;
+            binaries.push(ProgramBinary {
+                key,
+                format,
+                content: content.to_vec(),
+            });
+        }
+        Ok(Bundle { plan, binaries })
+    }
+}
+
+/// Reads a field of a JSON object, with a typed getter.
+fn get<'a, T>(
+    json: &'a Value,
+    key: &str,
+    getter: impl Fn(&'a Value) -> Option<T>,
+) -> Result<T, String> {
+    json.get(key)
+        .and_then(getter)
+        .ok_or_else(|| format!("Expected `{}` in {}", key, json))
+}
+
+fn get_u32(json: &Value, key: &str) -> Result<u32, String> {
+    get(json, key, |v| v.as_u64().map(|n| n as u32))
+}
+
+fn get_f32(json: &Value, key: &str) -> Result<f32, String> {
+    get(json, key, |v| v.as_f64().map(|n| n as f32))
+}
+
+fn get_str<'a>(json: &'a Value, key: &str) -> Result<&'a str, String> {
+    get(json, key, Value::as_str)
+}
+
+fn get_parsed<T: FromStr<Err = String>>(
+    json: &Value,
+    key: &str,
+) -> Result<T, String> {
+    get_str(json, key)?.parse()
+}
+
+/// Parses a field that may be `null`.
+fn get_optional<T: FromStr<Err = String>>(
+    json: &Value,
+    key: &str,
+) -> Result<Option<T>, String> {
+    match json.get(key) {
+        Some(Value::Null) | None => Ok(None),
+        Some(_) => get_parsed(json, key).map(Some),
+    }
+}
+
+fn get_id(json: &Value, key: &str) -> Result<NodeId, String> {
+    get(json, key, |v| v.as_u64().map(|n| NodeId(n as usize)))
+}
+
+fn get_ids(json: &Value, key: &str) -> Result<Vec<NodeId>, String> {
+    get(json, key, Value::as_array)?
+        .iter()
+        .map(|id| id.as_u64().map(|n| NodeId(n as usize)))
+        .collect::<Option<_>>()
+        .ok_or_else(|| format!("Expected node ids in `{}`", key))
+}
+
+fn get_floats(json: &Value, key: &str) -> Result<Vec<f32>, String> {
+    get(json, key, Value::as_array)?
+        .iter()
+        .map(|n| n.as_f64().map(|n| n as f32))
+        .collect::<Option<_>>()
+        .ok_or_else(|| format!("Expected numbers in `{}`", key))
+}
+
+fn ids(ids: &[NodeId]) -> Vec<usize> { ids.iter().map(|id| id.0).collect() }
+
+fn texture_to_json(texture: &TextureOptions) -> Value {
+    json!({
+        "format": texture.format.to_string(),
+        "filter": texture.filter.to_string(),
+        "wrap": texture.wrap.to_string(),
+    })
+}
+
+fn texture_from_json(json: &Value) -> Result<TextureOptions, String> {
+    Ok(TextureOptions {
+        format: get_parsed(json, "format")?,
+        filter: get_parsed(json, "filter")?,
+        wrap:   get_parsed(json, "wrap")?,
+    })
+}
+
+fn init_from_json(json: &Value) -> Result<Init, String> {
+    Ok(match json.get("image") {
+        Some(_) => Init::Image(PathBuf::from(get_str(json, "image")?)),
+        None => Init::Shader(get_str(json, "shader")?.to_string()),
+    })
+}
+
+/// Writes a planned node as JSON, tagged with its kind.
+fn to_json(spec: &NodeSpec) -> Result<Value, String> {
+    Ok(match spec {
+        NodeSpec::Input => json!({ "kind": "input" }),
+        NodeSpec::Shader {
+            source,
+            inputs,
+            width,
+            height,
+            recurrent,
+            init,
+            texture,
+        } => json!({
+            "kind": "shader",
+            "source": source,
+            "inputs": ids(inputs),
+            "width": width,
+            "height": height,
+            "recurrent": recurrent,
+            "init": match init {
+                Some(Init::Image(path)) => json!({ "image": path }),
+                Some(Init::Shader(source)) => json!({ "shader": source }),
+                None => Value::Null,
+            },
+            "texture": texture_to_json(texture),
+        }),
+        NodeSpec::Repeat {
+            source,
+            inputs,
+            feedback,
+            iterations,
+            width,
+            height,
+            texture,
+        } => json!({
+            "kind": "repeat",
+            "source": source,
+            "inputs": ids(inputs),
+            "feedback": feedback,
+            "iterations": iterations,
+            "width": width,
+            "height": height,
+            "texture": texture_to_json(texture),
+        }),
+        NodeSpec::Mrt {
+            source,
+            inputs,
+            width,
+            height,
+            count,
+        } => json!({
+            "kind": "mrt",
+            "source": source,
+            "inputs": ids(inputs),
+            "width": width,
+            "height": height,
+            "count": count,
+        }),
+        NodeSpec::Attachment { node, index } => json!({
+            "kind": "attachment",
+            "node": node.0,
+            "index": index,
+        }),
+        NodeSpec::Pyramid {
+            source,
+            input,
+            levels,
+        } => json!({
+            "kind": "pyramid",
+            "source": source,
+            "input": input.0,
+            "levels": levels,
+        }),
+        NodeSpec::Points {
+            source,
+            points,
+            inputs,
+            width,
+            height,
+        } => json!({
+            "kind": "points",
+            "source": source,
+            "points": points
+                .iter()
+                .flat_map(|point| point.position.iter().chain(&point.color))
+                .collect::<Vec<_>>(),
+            "inputs": ids(inputs),
+            "width": width,
+            "height": height,
+        }),
+        NodeSpec::Data(table) => json!({
+            "kind": "data",
+            "width": table.width,
+            "height": table.height,
+            "values": table.values,
+        }),
+        NodeSpec::Random(random) => json!({
+            "kind": "random",
+            "width": random.width,
+            "height": random.height,
+            "distribution": random.distribution.to_string(),
+            "seed": random.seed,
+            "regen_on_reload": random.regen_on_reload,
+        }),
+        NodeSpec::Image(path) => json!({
+            "kind": "image",
+            "path": path,
+        }),
+        NodeSpec::Compute {
+            source,
+            inputs,
+            width,
+            height,
+            groups,
+            count,
+        } => json!({
+            "kind": "compute",
+            "source": source,
+            "inputs": ids(inputs),
+            "width": width,
+            "height": height,
+            "groups": groups,
+            "count": count,
+        }),
+        NodeSpec::Audio { device, delay } => json!({
+            "kind": "audio",
+            "device": device,
+            "delay": delay,
+        }),
+        NodeSpec::Buffer {
+            width,
+            height,
+            texture,
+        } => json!({
+            "kind": "buffer",
+            "width": width,
+            "height": height,
+            "texture": texture_to_json(texture),
+        }),
+        NodeSpec::PreviousOutput => json!({ "kind": "previous-output" }),
+        NodeSpec::Camera {
+            device,
+            width,
+            height,
+            fps,
+            delay,
+        } => json!({
+            "kind": "camera",
+            "device": device,
+            "width": width,
+            "height": height,
+            "fps": fps,
+            "delay": delay,
+        }),
+        NodeSpec::Video {
+            path,
+            size,
+            playback,
+        } => json!({
+            "kind": "video",
+            "path": path,
+            "size": size.map(|(width, height)| [width, height]),
+            "looping": playback.looping,
+            "rate": playback.rate,
+            "start": playback.start,
+            "fps": playback.fps,
+        }),
+        #[cfg(feature = "feed")]
+        NodeSpec::Feed { url, .. } => {
+            return Err(format!("The feed from `{}` can't be bundled", url))
+        },
+        NodeSpec::Stats { input, bins } => json!({
+            "kind": "stats",
+            "input": input.0,
+            "bins": bins,
+        }),
+        NodeSpec::AutoExpose { input, speed, key } => json!({
+            "kind": "auto-expose",
+            "input": input.0,
+            "speed": speed,
+            "key": key,
+        }),
+        NodeSpec::Flow { input, window } => json!({
+            "kind": "flow",
+            "input": input.0,
+            "window": window,
+        }),
+        NodeSpec::Reproject {
+            input,
+            velocity,
+            blend,
+            clamp,
+        } => json!({
+            "kind": "reproject",
+            "input": input.0,
+            "velocity": velocity.0,
+            "blend": blend,
+            "clamp": clamp,
+        }),
+        NodeSpec::Fluid {
+            inputs,
+            width,
+            height,
+            iterations,
+            dissipation,
+        } => json!({
+            "kind": "fluid",
+            "inputs": ids(inputs),
+            "width": width,
+            "height": height,
+            "iterations": iterations,
+            "dissipation": dissipation,
+        }),
+        NodeSpec::Extern { name, inputs } => json!({
+            "kind": "extern",
+            "name": name,
+            "inputs": ids(inputs),
+        }),
+    })
+}
+
+/// Reads a planned node written by [`to_json`].
+fn from_json(json: &Value) -> Result<NodeSpec, String> {
+    let source = || get_str(json, "source").map(str::to_string);
+    let inputs = || get_ids(json, "inputs");
+    let width = || get_u32(json, "width");
+    let height = || get_u32(json, "height");
+    // bundles from before `:delay` have none
+    let delay = || json["delay"].as_f64().unwrap_or(0.0) as f32;
+    Ok(match get_str(json, "kind")? {
+        "input" => NodeSpec::Input,
+        "shader" => NodeSpec::Shader {
+            source:    source()?,
+            inputs:    inputs()?,
+            width:     width()?,
+            height:    height()?,
+            recurrent: get(json, "recurrent", Value::as_bool)?,
+            init:      match json.get("init") {
+                Some(Value::Null) | None => None,
+                Some(init) => Some(init_from_json(init)?),
+            },
+            texture:   texture_from_json(get(json, "texture", Some)?)?,
+        },
+        "repeat" => NodeSpec::Repeat {
+            source:     source()?,
+            inputs:     inputs()?,
+            feedback:   get_u32(json, "feedback")? as usize,
+            iterations: get_u32(json, "iterations")?,
+            width:      width()?,
+            height:     height()?,
+            texture:    texture_from_json(get(json, "texture", Some)?)?,
+        },
+        "mrt" => NodeSpec::Mrt {
+            source: source()?,
+            inputs: inputs()?,
+            width:  width()?,
+            height: height()?,
+            count:  get_u32(json, "count")? as usize,
+        },
+        "attachment" => NodeSpec::Attachment {
+            node:  get_id(json, "node")?,
+            index: get_u32(json, "index")? as usize,
+        },
+        "pyramid" => NodeSpec::Pyramid {
+            source: source()?,
+            input:  get_id(json, "input")?,
+            levels: get_u32(json, "levels")?,
+        },
+        "points" => NodeSpec::Points {
+            source: source()?,
+            points: get_floats(json, "points")?
+                .chunks_exact(6)
+                .map(|point| Point {
+                    position: [point[0], point[1], point[2]],
+                    color:    [point[3], point[4], point[5]],
+                })
+                .collect(),
+            inputs: inputs()?,
+            width:  width()?,
+            height: height()?,
+        },
+        "data" => NodeSpec::Data(DataTable {
+            width:  width()?,
+            height: height()?,
+            values: get_floats(json, "values")?,
+        }),
+        "random" => NodeSpec::Random(RandomTexture {
+            width:           width()?,
+            height:          height()?,
+            distribution:    get_parsed(json, "distribution")?,
+            seed:            get_u32(json, "seed")?,
+            regen_on_reload: get(json, "regen_on_reload", Value::as_bool)?,
+        }),
+        "image" => NodeSpec::Image(PathBuf::from(get_str(json, "path")?)),
+        "compute" => NodeSpec::Compute {
+            source: source()?,
+            inputs: inputs()?,
+            width:  width()?,
+            height: height()?,
+            groups: get(json, "groups", |groups| {
+                let groups = groups.as_array()?;
+                let group = |i: usize| Some(groups.get(i)?.as_u64()? as u32);
+                Some([group(0)?, group(1)?, group(2)?])
+            })?,
+            count:  json["count"].as_u64().map(|count| count as usize),
+        },
+        "audio" => NodeSpec::Audio {
+            device: json["device"].as_str().map(str::to_string),
+            delay:  delay(),
+        },
+        "buffer" => NodeSpec::Buffer {
+            width:   width()?,
+            height:  height()?,
+            texture: texture_from_json(get(json, "texture", Some)?)?,
+        },
+        "previous-output" => NodeSpec::PreviousOutput,
+        "camera" => NodeSpec::Camera {
+            device: get_str(json, "device")?.to_string(),
+            width:  width()?,
+            height: height()?,
+            fps:    get_u32(json, "fps")?,
+            delay:  delay(),
+        },
+        "video" => NodeSpec::Video {
+            path:     PathBuf::from(get_str(json, "path")?),
+            size:     match json.get("size") {
+                Some(Value::Null) | None => None,
+                Some(_) => Some((
+                    get(json, "size", |size| size.get(0)?.as_u64())? as u32,
+                    get(json, "size", |size| size.get(1)?.as_u64())? as u32,
+                )),
+            },
+            playback: Playback {
+                looping: get(json, "looping", Value::as_bool)?,
+                rate:    get(json, "rate", Value::as_f64)?,
+                start:   get(json, "start", Value::as_f64)?,
+                fps:     get_u32(json, "fps")?,
+            },
+        },
+        "stats" => NodeSpec::Stats {
+            input: get_id(json, "input")?,
+            bins:  get_u32(json, "bins")?,
+        },
+        "auto-expose" => NodeSpec::AutoExpose {
+            input: get_id(json, "input")?,
+            speed: get_f32(json, "speed")?,
+            key:   get_f32(json, "key")?,
+        },
+        "flow" => NodeSpec::Flow {
+            input:  get_id(json, "input")?,
+            window: get_u32(json, "window")?,
+        },
+        "reproject" => NodeSpec::Reproject {
+            input:    get_id(json, "input")?,
+            velocity: get_id(json, "velocity")?,
+            blend:    get_f32(json, "blend")?,
+            clamp:    get(json, "clamp", Value::as_bool)?,
+        },
+        "fluid" => NodeSpec::Fluid {
+            inputs:      inputs()?,
+            width:       width()?,
+            height:      height()?,
+            iterations:  get_u32(json, "iterations")?,
+            dissipation: get_f32(json, "dissipation")?,
+        },
+        "extern" => NodeSpec::Extern {
+            name:   get_str(json, "name")?.to_string(),
+            inputs: inputs()?,
+        },
+        other => return Err(format!("Unknown node kind `{}`", other)),
+    })
+}
+
+/// Writes a frozen plan as JSON. Nodes are listed in the
+/// order they were planned, and referred to by index.
+fn plan_to_json(plan: &Plan) -> Result<Value, String> {
+    let nodes = plan
+        .nodes
+        .iter()
+        .map(|(spec, shader)| {
+            let mut json = to_json(spec)?;
+            json["shader"] = json!(shader);
+            Ok(json)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let topology = &plan.topology;
+    let by_id = |map: &BTreeMap<NodeId, u32>| {
+        map.iter()
+            .map(|(id, n)| json!([id.0, n]))
+            .collect::<Vec<_>>()
+    };
+
+    Ok(json!({
+        "nodes": nodes,
+        "outputs": ids(topology.get_outputs()),
+        "names": topology
+            .node_names()
+            .map(|(name, id)| json!([name, id.0]))
+            .collect::<Vec<_>>(),
+        "labels": topology
+            .node_labels()
+            .map(|(id, label)| json!([id.0, label]))
+            .collect::<Vec<_>>(),
+        "hints": topology
+            .hints()
+            .map(|(first, second)| json!([first.0, second.0]))
+            .collect::<Vec<_>>(),
+        "writes": plan
+            .writes
+            .iter()
+            .map(|(buffer, writer)| json!([buffer.0, writer.0]))
+            .collect::<Vec<_>>(),
+        "requirements": plan
+            .requirements
+            .iter()
+            .map(|requirements| json!({
+                "version": requirements.version.map(|v| v.to_string()),
+                "glsl": requirements.glsl.as_ref().map(|v| v.to_string()),
+                "float_textures": requirements.float_textures,
+                "color_attachments": requirements.color_attachments,
+                "compute": requirements.compute,
+            }))
+            .collect::<Vec<_>>(),
+        "uniforms": plan
+            .uniforms
+            .iter()
+            .map(|(name, kind)| json!([name, kind.to_string()]))
+            .collect::<Vec<_>>(),
+        "palettes": plan
+            .palettes
+            .iter()
+            .map(|palette| json!({
+                "name": palette.name,
+                "colors": palette.colors,
+            }))
+            .collect::<Vec<_>>(),
+        "knobs": plan
+            .knobs
+            .iter()
+            .map(|knob| {
+                let source = match &knob.source {
+                    Source::MidiCc { cc, channel } => {
+                        json!({ "midi_cc": cc, "channel": channel })
+                    },
+                    Source::Osc(address) => json!({ "osc": address }),
+                };
+                json!({
+                    "uniform": knob.uniform,
+                    "source": source,
+                    "range": [knob.range.0, knob.range.1],
+                    "input": [knob.input.0, knob.input.1],
+                    "default": knob.default,
+                    "smooth": knob.smoothing.time,
+                    "slew": knob.smoothing.slew,
+                })
+            })
+            .collect::<Vec<_>>(),
+        "windows": plan
+            .windows
+            .iter()
+            .map(|window| json!({
+                "node": window.node.0,
+                "title": window.title,
+                "size": window.size.map(|(w, h)| [w, h]),
+                "fullscreen": window.fullscreen,
+            }))
+            .collect::<Vec<_>>(),
+        "warmup": plan.warmup,
+        "meta": plan.meta.to_json(),
+        "profile": plan.profile.as_ref().map(Profile::to_json),
+        "warmups": by_id(&plan.warmups),
+        "substeps": by_id(&plan.substeps),
+        "params": plan
+            .params
+            .iter()
+            .map(|((id, uniform), value)| json!([id.0, uniform, value]))
+            .collect::<Vec<_>>(),
+        "conditions": plan
+            .conditions
+            .iter()
+            .map(|(id, condition)| json!([id.0, condition.to_json()]))
+            .collect::<Vec<_>>(),
+        "versions": plan
+            .versions
+            .iter()
+            .map(|(id, version)| json!([id.0, version.to_string()]))
+            .collect::<Vec<_>>(),
+        "fallbacks": plan
+            .fallbacks
+            .iter()
+            .map(|(id, fallback)| Ok(json!([id.0, to_json(fallback)?])))
+            .collect::<Result<Vec<_>, String>>()?,
+        "cached": plan.cached.iter().map(|id| id.0).collect::<Vec<_>>(),
+        "baked": plan
+            .baked
+            .iter()
+            .map(|baked| json!({
+                "node": baked.node.0,
+                "name": baked.name,
+                "key": baked.key,
+                "image": baked.image,
+            }))
+            .collect::<Vec<_>>(),
+    }))
+}
+
+/// Reads a plan written by [`plan_to_json`].
+fn plan_from_json(json: &Value) -> Result<Plan, String> {
+    let list = |key| get(json, key, Value::as_array);
+    let id = |json: &Value| json.as_u64().map(|n| NodeId(n as usize));
+    let pair =
+        |json: &Value| Some((id(json.get(0)?)?, json.get(1)?.as_u64()? as u32));
+    let malformed = |key: &str| format!("Malformed `{}` in the plan", key);
+
+    let mut plan = Plan::new();
+    for node in list("nodes")? {
+        let spec = from_json(node)?;
+        if spec
+            .inputs()
+            .iter()
+            .any(|input| input.0 >= plan.nodes.len())
+        {
+            return Err("A node takes an input planned after it".to_string());
+        }
+        plan.add(spec, node["shader"].as_str());
+    }
+    let count = plan.nodes.len();
+    let checked = |id: Option<NodeId>, key: &str| match id {
+        Some(id) if id.0 < count => Ok(id),
+        _ => Err(malformed(key)),
+    };
+
+    for output in get_ids(json, "outputs")? {
+        plan.mark_output(checked(Some(output), "outputs")?);
+    }
+    for name in list("names")? {
+        let id = checked(name.get(1).and_then(id), "names")?;
+        let name = name.get(0).and_then(Value::as_str);
+        plan.name_node(id, name.ok_or_else(|| malformed("names"))?);
+    }
+    // bundles made before labels have none
+    for label in json["labels"].as_array().into_iter().flatten() {
+        let id = checked(label.get(0).and_then(id), "labels")?;
+        let label = label.get(1).and_then(Value::as_str);
+        plan.label_node(id, label.ok_or_else(|| malformed("labels"))?);
+    }
+    for hint in list("hints")? {
+        let first = checked(hint.get(0).and_then(id), "hints")?;
+        let second = checked(hint.get(1).and_then(id), "hints")?;
+        plan.order_before(first, second)?;
+    }
+    // bundles made before buffers have none
+    for write in json["writes"].as_array().into_iter().flatten() {
+        let buffer = checked(write.get(0).and_then(id), "writes")?;
+        let writer = checked(write.get(1).and_then(id), "writes")?;
+        plan.write_buffer(buffer, writer)?;
+    }
+    for requirements in list("requirements")? {
+        let flag = |key| get(requirements, key, Value::as_bool);
+        plan.require(Requirements {
+            version:           get_optional(requirements, "version")?,
+            glsl:              get_optional(requirements, "glsl")?,
+            float_textures:    flag("float_textures")?,
+            color_attachments: requirements["color_attachments"]
+                .as_u64()
+                .map(|count| count as u32),
+            compute:           flag("compute")?,
+        });
+    }
+    for uniform in list("uniforms")? {
+        let name = uniform.get(0).and_then(Value::as_str);
+        let kind = uniform.get(1).and_then(Value::as_str);
+        match (name, kind) {
+            (Some(name), Some(kind)) => {
+                plan.declare_uniform(name, kind.parse()?)?
+            },
+            _ => return Err(malformed("uniforms")),
+        }
+    }
+    for palette in list("palettes")? {
+        let colors = get(palette, "colors", Value::as_array)?
+            .iter()
+            .map(|color| {
+                let channel = |i: usize| Some(color.get(i)?.as_f64()? as f32);
+                Some([channel(0)?, channel(1)?, channel(2)?, channel(3)?])
+            })
+            .collect::<Option<_>>()
+            .ok_or_else(|| malformed("palettes"))?;
+        plan.add_palette(Palette {
+            name: get_str(palette, "name")?.to_string(),
+            colors,
+        });
+    }
+    for knob in list("knobs")? {
+        let source = &knob["source"];
+        let cc = source["midi_cc"].as_u64();
+        let source = match (cc, source["osc"].as_str()) {
+            (Some(cc), None) => Source::MidiCc {
+                cc:      cc as u8,
+                channel: source["channel"].as_u64().map(|c| c as u8),
+            },
+            (None, Some(address)) => Source::Osc(address.to_string()),
+            _ => return Err(malformed("knobs")),
+        };
+        let range = get_floats(knob, "range")?;
+        let range = match range.as_slice() {
+            [low, high] => (*low, *high),
+            _ => return Err(malformed("knobs")),
+        };
+        // bundles from before smoothing use all of the knob,
+        // and follow it at once
+        let input = match knob.get("input") {
+            Some(_) => match get_floats(knob, "input")?.as_slice() {
+                [low, high] => (*low, *high),
+                _ => return Err(malformed("knobs")),
+            },
+            None => (0.0, 1.0),
+        };
+        let smoothing = Smoothing {
+            time: knob["smooth"].as_f64().unwrap_or(0.0) as f32,
+            slew: knob["slew"].as_f64().map(|slew| slew as f32),
+        };
+        plan.add_knob(Knob {
+            uniform: get_str(knob, "uniform")?.to_string(),
+            source,
+            range,
+            input,
+            default: get_f32(knob, "default")?,
+            smoothing,
+        });
+    }
+    for window in list("windows")? {
+        let size = match &window["size"] {
+            Value::Null => None,
+            _ => match get_floats(window, "size")?.as_slice() {
+                [w, h] => Some((*w as u32, *h as u32)),
+                _ => return Err(malformed("windows")),
+            },
+        };
+        let fullscreen = match &window["fullscreen"] {
+            Value::Null => None,
+            index => {
+                let index = index.as_u64().ok_or_else(|| malformed("windows"));
+                Some(index? as usize)
+            },
+        };
+        plan.add_window(OutputWindow {
+            node: checked(Some(get_id(window, "node")?), "windows")?,
+            title: get_str(window, "title")?.to_string(),
+            size,
+            fullscreen,
+        })?;
+    }
+    plan.warm_up(get_u32(json, "warmup")?);
+    // bundles frozen before graphs had a `meta` have none
+    if !json["profile"].is_null() {
+        plan.add_profile(Profile::from_json(&json["profile"])?, true)?;
+    }
+    if !json["meta"].is_null() {
+        plan.set_meta(Meta::from_json(&json["meta"])?)?;
+    }
+    for warmup in list("warmups")? {
+        let (id, frames) = pair(warmup).ok_or_else(|| malformed("warmups"))?;
+        plan.warm_up_node(checked(Some(id), "warmups")?, frames);
+    }
+    for substeps in list("substeps")? {
+        let (id, count) =
+            pair(substeps).ok_or_else(|| malformed("substeps"))?;
+        plan.set_substeps(checked(Some(id), "substeps")?, count);
+    }
+    for param in list("params")? {
+        let id = checked(param.get(0).and_then(id), "params")?;
+        let uniform = param.get(1).and_then(Value::as_str);
+        let value = param.get(2).and_then(Value::as_f64);
+        match (uniform, value) {
+            (Some(uniform), Some(value)) => {
+                plan.set_param(id, uniform, value as f32)
+            },
+            _ => return Err(malformed("params")),
+        }
+    }
+    // bundles made before conditions have none
+    for condition in json["conditions"].as_array().into_iter().flatten() {
+        let id = checked(condition.get(0).and_then(id), "conditions")?;
+        let condition = condition.get(1).ok_or_else(|| malformed("conditions"));
+        plan.set_condition(id, Condition::from_json(condition?)?);
+    }
+    for version in list("versions")? {
+        let id = checked(version.get(0).and_then(id), "versions")?;
+        let version = version.get(1).and_then(Value::as_str);
+        let version = version.ok_or_else(|| malformed("versions"))?;
+        plan.set_node_glsl(id, version.parse()?);
+    }
+    for fallback in list("fallbacks")? {
+        let id = checked(fallback.get(0).and_then(id), "fallbacks")?;
+        let spec = fallback.get(1).ok_or_else(|| malformed("fallbacks"))?;
+        plan.set_fallback(id, from_json(spec)?)?;
+    }
+    // bundles made before caching have none
+    for cached in json["cached"].as_array().into_iter().flatten() {
+        plan.cache_node(checked(id(cached), "cached")?);
+    }
+    // nor anything baked
+    for baked in json["baked"].as_array().into_iter().flatten() {
+        plan.bake_node(Baked {
+            node:  checked(baked.get("node").and_then(id), "baked")?,
+            name:  baked["name"].as_str().map(str::to_string),
+            key:   get_str(baked, "key")?.to_string(),
+            image: PathBuf::from(get_str(baked, "image")?),
+        });
+    }
+    Ok(plan)
+}
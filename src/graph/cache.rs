@@ -0,0 +1,160 @@
+use std::{
+    convert::TryInto,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    rc::Rc,
+};
+
+use glium::{
+    backend::Context,
+    texture::{
+        ClientFormat,
+        MipmapsOption,
+        RawImage2d,
+        UncompressedFloatFormat,
+    },
+    Texture2d,
+};
+use image::{
+    ImageBuffer,
+    Rgba,
+};
+
+/// Starts every file of the cache, with the version of its
+/// layout.
+const MAGIC: &[u8; 8] = b"SGNODE01";
+
+/// The output of a node as kept on disk between runs, see
+/// [`crate::graph::ShaderGraph::cache_node`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedOutput {
+    pub width:  u32,
+    pub height: u32,
+    /// Red, green, blue, then alpha, from the bottom row
+    /// up.
+    pub pixels: Vec<f32>,
+}
+
+impl CachedOutput {
+    /// Uploads the output as a float texture.
+    pub fn texture(&self, context: &Rc<Context>) -> Result<Texture2d, String> {
+        let raw = RawImage2d {
+            data:   std::borrow::Cow::Borrowed(&self.pixels[..]),
+            width:  self.width,
+            height: self.height,
+            format: ClientFormat::F32F32F32F32,
+        };
+        Texture2d::with_format(
+            context,
+            raw,
+            UncompressedFloatFormat::F32F32F32F32,
+            MipmapsOption::NoMipmap,
+        )
+        .map_err(|e| format!("Could not upload cached output: {}", e))
+    }
+
+    /// Writes the output as a 16-bit PNG. Errors if it
+    /// holds values outside of 0 to 1, which a PNG
+    /// can't.
+    pub fn save_png(&self, path: &Path) -> Result<(), String> {
+        if self.pixels.iter().any(|value| !(0.0..=1.0).contains(value)) {
+            return Err(format!(
+                "Could not write `{}`, as it holds values outside of 0 to 1",
+                path.display()
+            ));
+        }
+        let row = self.width as usize * 4;
+        let pixels = self
+            .pixels
+            .chunks_exact(row.max(1))
+            .rev()
+            .flatten()
+            .map(|value| (value * u16::MAX as f32).round() as u16)
+            .collect();
+        // unwrap: as many values as the output has
+        let image: ImageBuffer<Rgba<u16>, Vec<u16>> =
+            ImageBuffer::from_raw(self.width, self.height, pixels).unwrap();
+        image
+            .save(path)
+            .map_err(|e| format!("Could not write `{}`: {}", path.display(), e))
+    }
+
+    /// Reads an image, e.g. one written by
+    /// [`CachedOutput::save_png`], at 16 bits per channel.
+    pub fn load_png(path: &Path) -> Result<CachedOutput, String> {
+        let image = image::open(path)
+            .map_err(|e| format!("Could not load `{}`: {}", path.display(), e))?
+            .to_rgba16();
+        let row = image.width() as usize * 4;
+        let pixels = image
+            .as_raw()
+            .chunks_exact(row.max(1))
+            .rev()
+            .flatten()
+            .map(|value| *value as f32 / u16::MAX as f32)
+            .collect();
+        Ok(CachedOutput {
+            width: image.width(),
+            height: image.height(),
+            pixels,
+        })
+    }
+}
+
+/// `node-cache` in the user's cache directory, see
+/// [`crate::reload::cache_dir`].
+pub fn default_dir() -> Option<PathBuf> {
+    Some(crate::reload::cache_dir()?.join("node-cache"))
+}
+
+fn path(dir: &Path, key: &str) -> PathBuf { dir.join(format!("{}.bin", key)) }
+
+/// Reads an output written by [`write`], if there is one.
+pub fn read(dir: &Path, key: &str) -> Option<CachedOutput> {
+    let bytes = fs::read(path(dir, key)).ok()?;
+    let rest = bytes.strip_prefix(&MAGIC[..])?;
+    let number = |at: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(rest.get(at..at + 4)?.try_into().ok()?))
+    };
+    let (width, height) = (number(0)?, number(4)?);
+    let pixels = rest[8..]
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect::<Vec<_>>();
+    if pixels.len() != width as usize * height as usize * 4 {
+        return None;
+    }
+    Some(CachedOutput {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Writes an output under a key. As with program binaries,
+/// the file is written next to where it goes and then moved
+/// there, so that runs at the same time never read half of
+/// one.
+pub fn write(
+    dir: &Path,
+    key: &str,
+    output: &CachedOutput,
+) -> Result<(), String> {
+    let path = path(dir, key);
+    let error = |e: std::io::Error| {
+        format!("Could not cache `{}`: {}", path.display(), e)
+    };
+    fs::create_dir_all(dir).map_err(error)?;
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&output.width.to_le_bytes());
+    bytes.extend_from_slice(&output.height.to_le_bytes());
+    for value in output.pixels.iter() {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    let partial = path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(&partial, bytes).map_err(error)?;
+    fs::rename(&partial, &path).map_err(error)
+}
@@ -0,0 +1,31 @@
+use glium::Texture2d;
+
+use crate::{
+    camera::Camera,
+    graph::InputSource,
+    upload::StreamingTexture,
+};
+
+/// A source that streams frames from a webcam. The texture
+/// is black until the first frame arrives, and then holds
+/// the newest frame.
+pub struct CameraSource {
+    pub camera:  Camera,
+    pub texture: StreamingTexture,
+}
+
+impl std::fmt::Debug for CameraSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let texture = self.texture.texture();
+        f.debug_struct("CameraSource")
+            .field("width", &texture.get_width())
+            .field("height", &texture.get_height())
+            .finish()
+    }
+}
+
+impl InputSource for CameraSource {
+    fn update(&mut self) { self.texture.update(); }
+
+    fn texture(&self) -> &Texture2d { self.texture.texture() }
+}
@@ -0,0 +1,70 @@
+use std::time::Instant;
+
+/// The transport a graph's `u_time` is read from: a clock
+/// that can be paused, moved to any time, and run faster or
+/// slower than real time. See
+/// [`super::ShaderGraph::pause`].
+#[derive(Debug, Clone, Copy)]
+pub struct Clock {
+    /// The time when `since` was taken, in seconds.
+    base:  f64,
+    /// When the clock last started or was moved, or `None`
+    /// while it is paused.
+    since: Option<Instant>,
+    speed: f64,
+}
+
+impl Default for Clock {
+    fn default() -> Clock { Clock::new() }
+}
+
+impl Clock {
+    /// A running clock, starting from zero at normal speed.
+    pub fn new() -> Clock {
+        Clock {
+            base:  0.0,
+            since: Some(Instant::now()),
+            speed: 1.0,
+        }
+    }
+
+    /// The time on the clock, in seconds.
+    pub fn time(&self) -> f64 {
+        let elapsed = self
+            .since
+            .map_or(0.0, |since| since.elapsed().as_secs_f64() * self.speed);
+        self.base + elapsed
+    }
+
+    pub fn pause(&mut self) {
+        self.base = self.time();
+        self.since = None;
+    }
+
+    pub fn resume(&mut self) {
+        if self.since.is_none() {
+            self.since = Some(Instant::now());
+        }
+    }
+
+    pub fn is_paused(&self) -> bool { self.since.is_none() }
+
+    /// Moves the clock to a time, keeping it paused or
+    /// running as it was.
+    pub fn seek(&mut self, time: f64) {
+        self.base = time;
+        if self.since.is_some() {
+            self.since = Some(Instant::now());
+        }
+    }
+
+    /// How fast the clock runs, where 1 is real time.
+    pub fn speed(&self) -> f64 { self.speed }
+
+    /// Sets how fast the clock runs from now on. Negative
+    /// speeds run it backwards.
+    pub fn set_speed(&mut self, speed: f64) {
+        self.seek(self.time());
+        self.speed = speed;
+    }
+}
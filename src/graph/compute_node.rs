@@ -36,7 +36,7 @@ impl<T: AsUniformValue> std::fmt::Debug for ComputeNode<T> {
 impl<T: AsUniformValue> Node for ComputeNode<T> {
     fn inputs(&self) -> Vec<NodeId> { vec![self.input] }
 
-    fn outputs(&self) -> (&str, UniformValue) {
+    fn outputs(&self) -> (&str, UniformValue<'_>) {
         ("compute", self.output.as_uniform_value())
     }
 
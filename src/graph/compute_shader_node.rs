@@ -0,0 +1,154 @@
+use std::rc::Rc;
+
+use glium::{
+    backend::Context,
+    program::{
+        ComputeShader,
+        UniformBlock as Block,
+    },
+    uniforms::{
+        AsUniformValue,
+        LayoutMismatchError,
+        UniformBlock,
+        UniformBuffer,
+        UniformValue,
+    },
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::{
+    graph::{
+        node::Node,
+        NodeId,
+        UniformMap,
+    },
+    util::{
+        compile_shader,
+        default_buffer,
+        RectStrip,
+    },
+};
+
+/// A shader storage buffer of `vec4`s.
+type Storage = UniformBuffer<[[f32; 4]]>;
+
+/// Dispatches a compute shader every frame. glium can't
+/// bind images, so the shader writes its output as `vec4`s
+/// to the `u_pixels` storage buffer, a row at a time from
+/// the bottom, which is then copied into the node's
+/// texture. It can also keep any data it likes, like
+/// particles, in the `u_buffer` storage buffer. Both
+/// buffers start zeroed, and carry over from one frame to
+/// the next.
+pub struct ComputeShaderNode {
+    pub shader:  ComputeShader,
+    pub inputs:  Vec<NodeId>,
+    /// Workgroups dispatched along each axis.
+    pub groups:  [u32; 3],
+    pub pixels:  Storage,
+    pub buffer:  Option<Storage>,
+    pub unpack:  Program,
+    pub texture: Texture2d,
+}
+
+impl std::fmt::Debug for ComputeShaderNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ComputeShaderNode")
+            .field("inputs", &self.inputs)
+            .field("groups", &self.groups)
+            .finish()
+    }
+}
+
+impl ComputeShaderNode {
+    /// Compiles a compute shader with an output of `width`
+    /// by `height`, and a buffer of `count` `vec4`s if any.
+    pub fn new(
+        context: &Rc<Context>,
+        source: &str,
+        inputs: Vec<NodeId>,
+        (width, height): (u32, u32),
+        groups: [u32; 3],
+        count: Option<usize>,
+    ) -> Result<ComputeShaderNode, String> {
+        let storage = |count: usize| {
+            let buffer = Storage::empty_unsized(context, count * 16)
+                .map_err(|e| format!("Could not create buffer: {:?}", e))?;
+            buffer.write(&vec![[0.0; 4]; count]);
+            Ok::<_, String>(buffer)
+        };
+
+        Ok(ComputeShaderNode {
+            shader: ComputeShader::from_source(context, source)
+                .map_err(|e| format!("{}", e))?,
+            inputs,
+            groups,
+            pixels: storage((width * height) as usize)?,
+            buffer: count.map(storage).transpose()?,
+            unpack: compile_shader(
+                context,
+                include_str!("./shaders/unpack.frag"),
+            )?,
+            texture: default_buffer(context, width, height),
+        })
+    }
+}
+
+/// Binds a buffer to a storage block of one array of
+/// `vec4`s.
+fn block(storage: &Storage) -> UniformValue<'_> {
+    fn matches(block: &Block) -> Result<(), LayoutMismatchError> {
+        <[[f32; 4]]>::matches(&block.layout, 0)
+    }
+    UniformValue::Block(storage.as_slice_any(), matches)
+}
+
+impl Node for ComputeShaderNode {
+    fn inputs(&self) -> Vec<NodeId> { self.inputs.to_owned() }
+
+    fn outputs(&self) -> (&str, UniformValue<'_>) {
+        ("texture", self.texture.as_uniform_value())
+    }
+
+    fn texture(&self) -> Option<&Texture2d> { Some(&self.texture) }
+
+    fn patch(
+        &mut self,
+        context: &Rc<Context>,
+        source: &str,
+    ) -> Result<(), String> {
+        self.shader = ComputeShader::from_source(context, source)
+            .map_err(|e| format!("{}", e))?;
+        Ok(())
+    }
+
+    fn forward(&mut self, rect_strip: &RectStrip, uniforms: UniformMap) {
+        let (width, height) = self.texture.dimensions();
+
+        let mut uniforms = uniforms;
+        let resolution = [width as f32, height as f32];
+        uniforms.add_resolution(resolution);
+        uniforms.add("pixels", block(&self.pixels));
+        if let Some(buffer) = &self.buffer {
+            uniforms.add("buffer", block(buffer));
+        }
+        let [x, y, z] = self.groups;
+        self.shader.execute(uniforms, x, y, z);
+
+        let mut unpack = UniformMap::new();
+        unpack.add("pixels", block(&self.pixels));
+        unpack.add("width", UniformValue::SignedInt(width as i32));
+        self.texture
+            .as_surface()
+            .draw(
+                &rect_strip.buffer,
+                rect_strip.indices,
+                &self.unpack,
+                &unpack,
+                &Default::default(),
+            )
+            .unwrap();
+    }
+}
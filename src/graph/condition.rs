@@ -0,0 +1,174 @@
+use std::fmt;
+
+use serde_json::{
+    json,
+    Value,
+};
+
+/// A number in a [`Condition`]: a constant, or a uniform,
+/// by name without the `u_` prefix.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Number(f32),
+    Uniform(String),
+}
+
+/// How two operands are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compare {
+    Greater,
+    Less,
+    GreaterEqual,
+    LessEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Compare {
+    /// The comparison written as in the lisp, e.g. `>=`.
+    pub fn parse(op: &str) -> Option<Compare> {
+        Some(match op {
+            ">" => Compare::Greater,
+            "<" => Compare::Less,
+            ">=" => Compare::GreaterEqual,
+            "<=" => Compare::LessEqual,
+            "=" => Compare::Equal,
+            "!=" => Compare::NotEqual,
+            _ => return None,
+        })
+    }
+
+    fn apply(self, a: f32, b: f32) -> bool {
+        match self {
+            Compare::Greater => a > b,
+            Compare::Less => a < b,
+            Compare::GreaterEqual => a >= b,
+            Compare::LessEqual => a <= b,
+            Compare::Equal => a == b,
+            Compare::NotEqual => a != b,
+        }
+    }
+}
+
+impl fmt::Display for Compare {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self {
+            Compare::Greater => ">",
+            Compare::Less => "<",
+            Compare::GreaterEqual => ">=",
+            Compare::LessEqual => "<=",
+            Compare::Equal => "=",
+            Compare::NotEqual => "!=",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+/// When a node runs, tested on the CPU before every forward
+/// pass, see [`crate::graph::ShaderGraph::set_condition`].
+/// Written in the lisp as `:when (> u_energy 0.5)`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Compare(Compare, Operand, Operand),
+    /// True when the operand isn't zero.
+    Truthy(Operand),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    /// Every uniform the condition reads.
+    pub fn uniforms(&self) -> Vec<&str> {
+        let mut names = vec![];
+        self.visit(&mut |operand| {
+            if let Operand::Uniform(name) = operand {
+                names.push(name.as_str());
+            }
+        });
+        names
+    }
+
+    fn visit<'a>(&'a self, f: &mut impl FnMut(&'a Operand)) {
+        match self {
+            Condition::Compare(_, a, b) => {
+                f(a);
+                f(b);
+            },
+            Condition::Truthy(a) => f(a),
+            Condition::And(a, b) | Condition::Or(a, b) => {
+                a.visit(f);
+                b.visit(f);
+            },
+            Condition::Not(a) => a.visit(f),
+        }
+    }
+
+    /// Tests the condition, with `lookup` giving the value
+    /// of each uniform. Uniforms it can't find are
+    /// zero.
+    pub fn eval(&self, lookup: &impl Fn(&str) -> Option<f32>) -> bool {
+        let value = |operand: &Operand| match operand {
+            Operand::Number(x) => *x,
+            Operand::Uniform(name) => lookup(name).unwrap_or(0.0),
+        };
+        match self {
+            Condition::Compare(op, a, b) => op.apply(value(a), value(b)),
+            Condition::Truthy(a) => value(a) != 0.0,
+            Condition::And(a, b) => a.eval(lookup) && b.eval(lookup),
+            Condition::Or(a, b) => a.eval(lookup) || b.eval(lookup),
+            Condition::Not(a) => !a.eval(lookup),
+        }
+    }
+
+    /// Written as nested lists, as in the lisp, with
+    /// uniforms as strings, e.g. `[">", "u_energy", 0.5]`.
+    pub fn to_json(&self) -> Value {
+        let operand = |operand: &Operand| match operand {
+            Operand::Number(x) => json!(x),
+            Operand::Uniform(name) => json!(format!("u_{}", name)),
+        };
+        match self {
+            Condition::Compare(op, a, b) => {
+                json!([op.to_string(), operand(a), operand(b)])
+            },
+            Condition::Truthy(a) => operand(a),
+            Condition::And(a, b) => json!(["and", a.to_json(), b.to_json()]),
+            Condition::Or(a, b) => json!(["or", a.to_json(), b.to_json()]),
+            Condition::Not(a) => json!(["not", a.to_json()]),
+        }
+    }
+
+    /// Reads what [`Condition::to_json`] wrote.
+    pub fn from_json(json: &Value) -> Result<Condition, String> {
+        let malformed = || "Malformed `conditions` in the plan".to_string();
+        let operand = |json: &Value| match json {
+            Value::Number(x) => {
+                Ok(Operand::Number(x.as_f64().ok_or_else(malformed)? as f32))
+            },
+            Value::String(name) => match name.strip_prefix("u_") {
+                Some(name) => Ok(Operand::Uniform(name.to_string())),
+                None => Err(malformed()),
+            },
+            _ => Err(malformed()),
+        };
+        let list = match json.as_array() {
+            Some(list) => list,
+            None => return Ok(Condition::Truthy(operand(json)?)),
+        };
+        let boxed = |json: &Value| Condition::from_json(json).map(Box::new);
+        match list.as_slice() {
+            [op, a] if op == "not" => Ok(Condition::Not(boxed(a)?)),
+            [op, a, b] if op == "and" => {
+                Ok(Condition::And(boxed(a)?, boxed(b)?))
+            },
+            [op, a, b] if op == "or" => Ok(Condition::Or(boxed(a)?, boxed(b)?)),
+            [op, a, b] => {
+                let op = op.as_str().and_then(Compare::parse);
+                let op = op.ok_or_else(malformed)?;
+                Ok(Condition::Compare(op, operand(a)?, operand(b)?))
+            },
+            _ => Err(malformed()),
+        }
+    }
+}
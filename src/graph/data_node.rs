@@ -0,0 +1,44 @@
+use glium::{
+    uniforms::{
+        AsUniformValue,
+        UniformValue,
+    },
+    Texture2d,
+};
+
+use crate::{
+    graph::{
+        node::Node,
+        NodeId,
+        UniformMap,
+    },
+    util::RectStrip,
+};
+
+/// A node that holds a static texture uploaded from the
+/// CPU, e.g. a table of values from a data file, or an
+/// image.
+pub struct DataNode {
+    pub texture: Texture2d,
+}
+
+impl std::fmt::Debug for DataNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DataNode")
+            .field("width", &self.texture.get_width())
+            .field("height", &self.texture.get_height())
+            .finish()
+    }
+}
+
+impl Node for DataNode {
+    fn inputs(&self) -> Vec<NodeId> { vec![] }
+
+    fn outputs(&self) -> (&str, UniformValue<'_>) {
+        ("texture", self.texture.as_uniform_value())
+    }
+
+    fn texture(&self) -> Option<&Texture2d> { Some(&self.texture) }
+
+    fn forward(&mut self, _rect_strip: &RectStrip, _uniforms: UniformMap) {}
+}
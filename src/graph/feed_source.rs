@@ -0,0 +1,77 @@
+use std::rc::Rc;
+
+use glium::{
+    backend::Context,
+    uniforms::UniformValue,
+    Texture2d,
+};
+
+use crate::{
+    data::{
+        DataTable,
+        Layout,
+    },
+    feed::{
+        Feed,
+        Mapping,
+    },
+    graph::InputSource,
+    util::float_texture,
+};
+
+/// A source fed by a live data feed. Each mapped field is
+/// exposed as a uniform to the nodes that take this one as
+/// an input, and the data texture holds all fields in its
+/// first row, followed by one row per mapped array.
+pub struct FeedSource {
+    pub context: Rc<Context>,
+    pub feed:    Feed,
+    pub mapping: Mapping,
+    pub texture: Texture2d,
+}
+
+impl std::fmt::Debug for FeedSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FeedSource")
+            .field("mapping", &self.mapping)
+            .finish()
+    }
+}
+
+impl InputSource for FeedSource {
+    fn update(&mut self) {
+        let update = match self.feed.update() {
+            Some(update) => update,
+            None => return,
+        };
+
+        let mut records = vec![update.fields.clone()];
+        records.extend(update.rows.iter().cloned());
+        let table = DataTable::from_records(records, Layout::Rows);
+
+        match float_texture(
+            &self.context,
+            table.width,
+            table.height,
+            &table.values,
+        ) {
+            Ok(texture) => self.texture = texture,
+            Err(e) => eprintln!("[warn] Could not update feed: {}", e),
+        }
+    }
+
+    fn texture(&self) -> &Texture2d { &self.texture }
+
+    fn uniforms(&self) -> Vec<(String, UniformValue<'_>)> {
+        let values = self.feed.latest().map(|u| u.fields.as_slice());
+        self.mapping
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| {
+                let value = values.and_then(|v| v.get(i)).unwrap_or(&0.0);
+                (name.to_string(), UniformValue::Float(*value))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,139 @@
+use std::rc::Rc;
+
+use glium::{
+    backend::Context,
+    uniform,
+    uniforms::{
+        AsUniformValue,
+        UniformValue,
+    },
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::{
+    graph::{
+        node::Node,
+        NodeId,
+        UniformMap,
+    },
+    util::{
+        compile_shader,
+        float_buffer,
+        RectStrip,
+    },
+};
+
+/// Estimates the optical flow between the current and
+/// previous frame of its input. The output texture holds
+/// the motion of each pixel in `rg`, in pixels per frame,
+/// and how reliable the estimate is in `b`.
+pub struct FlowNode {
+    pub context:  Rc<Context>,
+    pub input:    NodeId,
+    pub luma:     Program,
+    pub flow:     Program,
+    /// Radius of the window each estimate is made over,
+    /// larger windows track faster motion but blur edges.
+    pub window:   u32,
+    pub current:  Texture2d,
+    pub previous: Texture2d,
+    pub texture:  Texture2d,
+}
+
+impl std::fmt::Debug for FlowNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FlowNode")
+            .field("input", &self.input)
+            .field("window", &self.window)
+            .finish()
+    }
+}
+
+impl FlowNode {
+    pub fn new(
+        context: &Rc<Context>,
+        input: NodeId,
+        window: u32,
+    ) -> Result<FlowNode, String> {
+        Ok(FlowNode {
+            context: context.clone(),
+            input,
+            luma: compile_shader(context, include_str!("./shaders/luma.frag"))?,
+            flow: compile_shader(context, include_str!("./shaders/flow.frag"))?,
+            window,
+            current: float_buffer(context, 1, 1)?,
+            previous: float_buffer(context, 1, 1)?,
+            texture: float_buffer(context, 1, 1)?,
+        })
+    }
+
+    /// Rebuilds all buffers when the input changes size.
+    /// The first frame after a resize has no motion.
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        if self.texture.dimensions() == (width, height) {
+            return Ok(());
+        }
+
+        self.current = float_buffer(&self.context, width, height)?;
+        self.previous = float_buffer(&self.context, width, height)?;
+        self.texture = float_buffer(&self.context, width, height)?;
+        Ok(())
+    }
+}
+
+impl Node for FlowNode {
+    fn inputs(&self) -> Vec<NodeId> { vec![self.input] }
+
+    fn outputs(&self) -> (&str, UniformValue<'_>) {
+        ("texture", self.texture.as_uniform_value())
+    }
+
+    fn texture(&self) -> Option<&Texture2d> { Some(&self.texture) }
+
+    // it compares each frame with the one before
+    fn remembers(&self) -> bool { true }
+
+    fn forward(&mut self, rect_strip: &RectStrip, uniforms: UniformMap) {
+        let input = match uniforms.get("texture", 0) {
+            Some(UniformValue::Texture2d(texture, _)) => *texture,
+            _ => {
+                eprintln!("[warn] Flow node expects a texture input");
+                return;
+            },
+        };
+
+        if let Err(e) = self.resize(input.width(), input.height()) {
+            eprintln!("[warn] {}", e);
+            return;
+        }
+
+        std::mem::swap(&mut self.current, &mut self.previous);
+        self.current
+            .as_surface()
+            .draw(
+                &rect_strip.buffer,
+                rect_strip.indices,
+                &self.luma,
+                &uniform! { u_input: input },
+                &Default::default(),
+            )
+            .unwrap();
+
+        self.texture
+            .as_surface()
+            .draw(
+                &rect_strip.buffer,
+                rect_strip.indices,
+                &self.flow,
+                &uniform! {
+                    u_current: &self.current,
+                    u_previous: &self.previous,
+                    u_window: self.window as i32,
+                },
+                &Default::default(),
+            )
+            .unwrap();
+    }
+}
@@ -0,0 +1,202 @@
+use std::rc::Rc;
+
+use glium::{
+    backend::Context,
+    uniform,
+    uniforms::{
+        AsUniformValue,
+        UniformValue,
+        Uniforms,
+    },
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::{
+    graph::{
+        node::Node,
+        Buffer,
+        NodeId,
+        UniformMap,
+    },
+    util::{
+        compile_shader,
+        float_buffer,
+        RectStrip,
+    },
+};
+
+/// A stable fluids simulation. Each frame, velocity and dye
+/// are advected, then the velocity is made divergence free
+/// by solving for pressure. The first input adds force from
+/// its `rg` channels, in pixels per frame, and the second
+/// adds dye. The dye is this node's output, and the
+/// velocity is passed as `u_velocity` to every node that
+/// takes this one as an input.
+pub struct FluidNode {
+    pub inputs:      Vec<NodeId>,
+    pub advect:      Program,
+    pub divergence:  Program,
+    pub jacobi:      Program,
+    pub project:     Program,
+    pub velocity:    Buffer,
+    pub dye:         Buffer,
+    pub pressure:    Buffer,
+    pub divergent:   Texture2d,
+    /// Bound in place of missing inputs.
+    pub blank:       Texture2d,
+    /// Jacobi iterations of the pressure solve per frame.
+    pub iterations:  u32,
+    /// How much velocity and dye are kept each frame.
+    pub dissipation: f32,
+}
+
+impl std::fmt::Debug for FluidNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FluidNode")
+            .field("inputs", &self.inputs)
+            .field("iterations", &self.iterations)
+            .field("dissipation", &self.dissipation)
+            .finish()
+    }
+}
+
+impl FluidNode {
+    pub fn new(
+        context: &Rc<Context>,
+        inputs: Vec<NodeId>,
+        width: u32,
+        height: u32,
+        iterations: u32,
+        dissipation: f32,
+    ) -> Result<FluidNode, String> {
+        if inputs.len() > 2 {
+            return Err(format!(
+                "Fluid takes at most a force and a dye input, found {} inputs",
+                inputs.len()
+            ));
+        }
+
+        let field = || float_buffer(context, width, height);
+        let program = |source| compile_shader(context, source);
+        Ok(FluidNode {
+            inputs,
+            advect: program(include_str!("./shaders/fluid_advect.frag"))?,
+            divergence: program(include_str!(
+                "./shaders/fluid_divergence.frag"
+            ))?,
+            jacobi: program(include_str!("./shaders/fluid_jacobi.frag"))?,
+            project: program(include_str!("./shaders/fluid_project.frag"))?,
+            velocity: Buffer::Double(field()?, field()?),
+            dye: Buffer::Double(field()?, field()?),
+            pressure: Buffer::Double(field()?, field()?),
+            divergent: field()?,
+            blank: float_buffer(context, 1, 1)?,
+            iterations,
+            dissipation,
+        })
+    }
+}
+
+/// Renders a full-screen pass into a texture.
+fn pass<U: Uniforms>(
+    rect_strip: &RectStrip,
+    target: &Texture2d,
+    program: &Program,
+    uniforms: &U,
+) {
+    target
+        .as_surface()
+        .draw(
+            &rect_strip.buffer,
+            rect_strip.indices,
+            program,
+            uniforms,
+            &Default::default(),
+        )
+        .unwrap();
+}
+
+impl Node for FluidNode {
+    fn inputs(&self) -> Vec<NodeId> { self.inputs.to_owned() }
+
+    fn outputs(&self) -> (&str, UniformValue<'_>) {
+        ("texture", self.dye.front().as_uniform_value())
+    }
+
+    fn uniforms(&self) -> Vec<(String, UniformValue<'_>)> {
+        vec![(
+            "velocity".to_string(),
+            self.velocity.front().as_uniform_value(),
+        )]
+    }
+
+    fn texture(&self) -> Option<&Texture2d> { Some(self.dye.front()) }
+
+    fn state(&self) -> Vec<&Texture2d> {
+        let mut state = self.velocity.state();
+        state.extend(self.dye.state());
+        state.extend(self.pressure.state());
+        state
+    }
+
+    fn forward(&mut self, rect_strip: &RectStrip, uniforms: UniformMap) {
+        let blank = &self.blank;
+        let input = |index| match uniforms.get("texture", index) {
+            Some(UniformValue::Texture2d(texture, _)) => *texture,
+            _ => blank,
+        };
+        let (force, source) = (input(0), input(1));
+        // the share of the frame this is, when substepped
+        let step = match uniforms.get("step", 0) {
+            Some(UniformValue::Float(step)) => *step,
+            _ => 1.0,
+        };
+
+        // advect velocity along itself, then dye along the velocity
+        self.velocity.swap();
+        let previous = self.velocity.back().unwrap();
+        pass(rect_strip, self.velocity.front(), &self.advect, &uniform! {
+            u_field: previous,
+            u_velocity: previous,
+            u_source: force,
+            u_dissipation: self.dissipation,
+            u_step: step,
+        });
+
+        self.dye.swap();
+        pass(rect_strip, self.dye.front(), &self.advect, &uniform! {
+            u_field: self.dye.back().unwrap(),
+            u_velocity: self.velocity.front(),
+            u_source: source,
+            u_dissipation: self.dissipation,
+            u_step: step,
+        });
+
+        // solve for the pressure that removes divergence,
+        // starting from last frame's solution
+        pass(rect_strip, &self.divergent, &self.divergence, &uniform! {
+            u_velocity: self.velocity.front(),
+        });
+
+        for _ in 0..self.iterations {
+            self.pressure.swap();
+            pass(rect_strip, self.pressure.front(), &self.jacobi, &uniform! {
+                u_pressure: self.pressure.back().unwrap(),
+                u_divergence: &self.divergent,
+            });
+        }
+
+        self.velocity.swap();
+        pass(
+            rect_strip,
+            self.velocity.front(),
+            &self.project,
+            &uniform! {
+                u_velocity: self.velocity.back().unwrap(),
+                u_pressure: self.pressure.front(),
+            },
+        );
+    }
+}
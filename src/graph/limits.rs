@@ -0,0 +1,268 @@
+use crate::graph::{
+    Format,
+    NodeId,
+    NodeSpec,
+    Plan,
+};
+
+/// Bytes in a megabyte, as memory is reported in.
+const MB: u64 = 1024 * 1024;
+
+/// Caps on what a graph can ask of the machine, checked
+/// before anything is created, so that a graph submitted by
+/// a stranger, e.g. to a gallery kiosk, can't run it out of
+/// memory or hang it building. Every cap is off unless set.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// The most nodes a graph can have.
+    pub nodes:      Option<usize>,
+    /// The most bytes of textures the nodes can create, all
+    /// together, see [`Limits::check`].
+    pub memory:     Option<u64>,
+    /// The largest width or height of any node.
+    pub resolution: Option<u32>,
+    /// The most iterations of anything repeated: `repeat`
+    /// in the lisp, repeated shaders, fluid solvers,
+    /// substeps, and warm-up frames.
+    pub iterations: Option<u32>,
+}
+
+impl Limits {
+    /// No limits at all, the default.
+    pub fn none() -> Limits { Limits::default() }
+
+    pub fn is_none(&self) -> bool { *self == Limits::none() }
+
+    pub fn check_nodes(&self, count: usize) -> Result<(), String> {
+        match self.nodes {
+            Some(max) if count > max => Err(format!(
+                "The graph has more than {} nodes, the most allowed",
+                max
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks how many times something is repeated, named
+    /// by `what` in the error.
+    pub fn check_iterations(
+        &self,
+        what: &str,
+        count: u64,
+    ) -> Result<(), String> {
+        match self.iterations {
+            Some(max) if count > max as u64 => Err(format!(
+                "{} {} times is more than the {} allowed",
+                what, count, max
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_resolution(
+        &self,
+        (width, height): (u32, u32),
+    ) -> Result<(), String> {
+        match self.resolution {
+            Some(max) if width > max || height > max => Err(format!(
+                "A {}x{} node is larger than the {}x{} allowed",
+                width, height, max, max
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks a plan against every limit, with the size of
+    /// its nodes scaled by `render_scale`, and by its
+    /// profile, see [`Plan::render_scale`]. The memory used
+    /// is estimated from the size and format of each
+    /// texture the nodes create. The size of inputs,
+    /// and of videos not scaled to one, isn't known
+    /// until they play, so they aren't counted.
+    pub fn check(&self, plan: &Plan, render_scale: f32) -> Result<(), String> {
+        if self.is_none() {
+            return Ok(());
+        }
+        let render_scale = render_scale * plan.render_scale();
+        self.check_nodes(plan.nodes.len())?;
+        self.check_iterations("Warming up", plan.warmup as u64)?;
+        for frames in plan.warmups.values() {
+            self.check_iterations("Warming up", *frames as u64)?;
+        }
+        for substeps in plan.substeps.values() {
+            self.check_iterations("Stepping", *substeps as u64)?;
+        }
+
+        let mut sizes: Vec<Option<(u32, u32)>> = vec![];
+        let mut memory = 0u64;
+        for (id, spec) in plan.nodes() {
+            let locate = |e| plan.locate(id, &plan.nodes[id.0].1, e);
+            let (size, bytes) = footprint(spec, &sizes, render_scale);
+            if let Some(size) = size {
+                self.check_resolution(size).map_err(locate)?;
+                memory += size.0 as u64 * size.1 as u64 * bytes;
+            }
+            match spec {
+                NodeSpec::Repeat { iterations, .. } => self
+                    .check_iterations("Repeating a shader", *iterations as u64)
+                    .map_err(locate)?,
+                NodeSpec::Fluid { iterations, .. } => self
+                    .check_iterations("Solving a fluid", *iterations as u64)
+                    .map_err(locate)?,
+                _ => (),
+            }
+            sizes.push(size);
+        }
+
+        match self.memory {
+            Some(max) if memory > max => Err(format!(
+                "The graph needs about {} MB of textures, more than the {} \
+                 MB allowed",
+                memory / MB,
+                max / MB
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The size of a node, if known before it's created, and
+/// the bytes per pixel of every texture it creates at that
+/// size, from the sizes of the nodes before it.
+fn footprint(
+    spec: &NodeSpec,
+    sizes: &[Option<(u32, u32)>],
+    render_scale: f32,
+) -> (Option<(u32, u32)>, u64) {
+    // as in `ShaderGraph::scaled`
+    let scaled = |width: u32, height: u32| {
+        let scale =
+            |size: u32| ((size as f32 * render_scale).round() as u32).max(1);
+        Some((scale(width), scale(height)))
+    };
+    let of = |input: &NodeId| sizes.get(input.0).copied().flatten();
+    let bytes = |format: Format| format.bytes_per_pixel() as u64;
+
+    match spec {
+        NodeSpec::Shader {
+            width,
+            height,
+            recurrent,
+            texture,
+            ..
+        } => {
+            let buffers = if *recurrent { 2 } else { 1 };
+            (scaled(*width, *height), buffers * bytes(texture.format))
+        },
+        NodeSpec::Repeat {
+            width,
+            height,
+            texture,
+            ..
+        } => (scaled(*width, *height), 2 * bytes(texture.format)),
+        NodeSpec::Buffer {
+            width,
+            height,
+            texture,
+        } => (scaled(*width, *height), bytes(texture.format)),
+        NodeSpec::Mrt {
+            width,
+            height,
+            count,
+            ..
+        } => (scaled(*width, *height), *count as u64 * 8),
+        NodeSpec::Points { width, height, .. }
+        | NodeSpec::Compute { width, height, .. } => {
+            (scaled(*width, *height), 8)
+        },
+        // velocity, dye, and pressure, each double
+        // buffered, and the divergence, all floats
+        NodeSpec::Fluid { width, height, .. } => {
+            (scaled(*width, *height), 7 * 16)
+        },
+        NodeSpec::Image(path) => (image::image_dimensions(path).ok(), 4),
+        NodeSpec::Random(random) => (Some((random.width, random.height)), 16),
+        NodeSpec::Video {
+            size: Some(size), ..
+        } => (Some(*size), 4),
+        NodeSpec::Camera { width, height, .. } => (Some((*width, *height)), 4),
+        NodeSpec::Attachment { node, .. } => (of(node), 0),
+        // every level together is under twice the first
+        NodeSpec::Pyramid { input, .. } => (of(input), 2 * 8),
+        NodeSpec::Flow { input, .. } => (of(input), 3 * 16),
+        NodeSpec::Reproject { input, .. } => (of(input), 2 * 8),
+        NodeSpec::AutoExpose { input, .. } => (of(input), 8),
+        _ => (None, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lisp::tests::plan;
+
+    /// A 1024x512 shader feeding a recurrent one, and a
+    /// repeated one, of the same size, with 20 MB of 16-bit
+    /// textures in all.
+    fn graph() -> Plan {
+        plan(
+            "(let a (shader \"a\" 1024 512))
+             (let b (shader-rec \"a\" 1024 512 a))
+             (let b (repeat 4 b (shader \"a\" 1024 512 a b)))
+             (output b)",
+            &[("a", "void main() {}")],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn allows_anything_without_limits() {
+        assert_eq!(Limits::none().check(&graph(), 100.0), Ok(()));
+    }
+
+    #[test]
+    fn checks_nodes() {
+        let limits = |nodes| Limits {
+            nodes: Some(nodes),
+            ..Limits::none()
+        };
+        assert!(limits(3).check(&graph(), 1.0).is_ok());
+        assert!(limits(2).check(&graph(), 1.0).is_err());
+    }
+
+    #[test]
+    fn checks_scaled_resolution() {
+        let limits = Limits {
+            resolution: Some(1024),
+            ..Limits::none()
+        };
+        assert!(limits.check(&graph(), 1.0).is_ok());
+        let error = limits.check(&graph(), 2.0).unwrap_err();
+        assert!(error.contains("2048x1024"), "{}", error);
+    }
+
+    #[test]
+    fn checks_memory() {
+        let limits = |mb| Limits {
+            memory: Some(mb * MB),
+            ..Limits::none()
+        };
+        // 4 MB for a, and twice that for the others, which
+        // are double buffered
+        assert!(limits(20).check(&graph(), 1.0).is_ok());
+        assert!(limits(19).check(&graph(), 1.0).is_err());
+        assert!(limits(20).check(&graph(), 2.0).is_err());
+    }
+
+    #[test]
+    fn checks_iterations() {
+        let limits = |iterations| Limits {
+            iterations: Some(iterations),
+            ..Limits::none()
+        };
+        assert!(limits(4).check(&graph(), 1.0).is_ok());
+        let error = limits(3).check(&graph(), 1.0).unwrap_err();
+        assert!(error.contains("Repeating a shader 4 times"), "{}", error);
+        assert!(limits(3).check_iterations("Warming up", 3).is_ok());
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    collections::BTreeSet,
+    fmt::Write,
+};
+
+use glium::{
+    texture::{
+        InternalFormat,
+        InternalFormatType,
+    },
+    Texture2d,
+};
+
+use crate::graph::{
+    NodeId,
+    ShaderGraph,
+    Topology,
+};
+
+/// Something suspicious about how a graph is wired, found
+/// by [`ShaderGraph::validate`]. None of these stop a graph
+/// from running, but they are the usual reasons a pass
+/// renders black.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    /// Nodes that depend on each other, so can't be run.
+    Cycle(Vec<NodeId>),
+    /// A node that no output depends on, so is never seen.
+    Unused(NodeId),
+    /// A texture uniform the node's shader reads from, like
+    /// `u_texture_2`, with no input bound to it, so it
+    /// samples black.
+    DanglingInput { node: NodeId, uniform: String },
+    /// An input of a different size than the node reading
+    /// it, which is stretched to fit.
+    ResolutionMismatch {
+        node:       NodeId,
+        input:      NodeId,
+        size:       (u32, u32),
+        input_size: (u32, u32),
+    },
+}
+
+impl Issue {
+    /// The node the issue is about.
+    pub fn node(&self) -> Option<NodeId> {
+        match self {
+            Issue::Cycle(nodes) => nodes.first().copied(),
+            Issue::Unused(node)
+            | Issue::DanglingInput { node, .. }
+            | Issue::ResolutionMismatch { node, .. } => Some(*node),
+        }
+    }
+
+    /// Describes the issue, naming nodes as the graph does.
+    pub fn describe(&self, topology: &Topology) -> String {
+        let name = |id: &NodeId| label(topology, *id);
+        match self {
+            Issue::Cycle(nodes) => format!(
+                "Nodes {} depend on each other",
+                nodes.iter().map(name).collect::<Vec<_>>().join(", ")
+            ),
+            Issue::Unused(node) => {
+                format!("`{}` is not used by any output", name(node))
+            },
+            Issue::DanglingInput { node, uniform } => format!(
+                "`{}` reads `{}`, but has no input bound to it",
+                name(node),
+                uniform
+            ),
+            Issue::ResolutionMismatch {
+                node,
+                input,
+                size,
+                input_size,
+            } => format!(
+                "`{}` is {}x{}, but reads `{}` at {}x{}",
+                name(node),
+                size.0,
+                size.1,
+                name(input),
+                input_size.0,
+                input_size.1
+            ),
+        }
+    }
+
+    pub fn to_json(&self, topology: &Topology) -> serde_json::Value {
+        let kind = match self {
+            Issue::Cycle(_) => "cycle",
+            Issue::Unused(_) => "unused",
+            Issue::DanglingInput { .. } => "dangling-input",
+            Issue::ResolutionMismatch { .. } => "resolution-mismatch",
+        };
+        serde_json::json!({
+            "kind": kind,
+            "node": self.node().map(|id| label(topology, id)),
+            "message": self.describe(topology),
+        })
+    }
+}
+
+/// A node's name, or its id if it has none.
+fn label(topology: &Topology, id: NodeId) -> String {
+    match topology.name_of(id) {
+        Some(name) => name.to_string(),
+        None => format!("#{}", id.0),
+    }
+}
+
+/// Every node some output depends on, through its inputs,
+/// through being ordered before one that is used, or
+/// through writing a buffer that is used.
+fn used(graph: &ShaderGraph) -> BTreeSet<NodeId> {
+    let topology = &graph.topology;
+    let mut used = BTreeSet::new();
+    let mut stack = topology.get_outputs().clone();
+    while let Some(id) = stack.pop() {
+        if !used.insert(id) {
+            continue;
+        }
+        stack.extend(topology.node_inputs(id));
+        let before = topology.hints().filter(|(_, after)| *after == id);
+        stack.extend(before.map(|(before, _)| before));
+        stack.extend(graph.buffers.get(&id).copied().flatten());
+    }
+    used
+}
+
+/// How many textures are bound to a node with these
+/// inputs, as in `ShaderGraph::build_inputs`.
+fn bound_textures(graph: &ShaderGraph, inputs: &[NodeId]) -> usize {
+    inputs
+        .iter()
+        .map(|input| graph.topology.resolve(&graph.bypassed, *input))
+        .filter(|input| match &graph.nodes[input.0] {
+            Some(node) => node.outputs().0 == "texture",
+            None => true,
+        })
+        .count()
+}
+
+/// The index of a uniform named like an input texture,
+/// `u_texture` being the first.
+fn texture_index(uniform: &str) -> Option<usize> {
+    match uniform.strip_prefix("u_texture")? {
+        "" => Some(0),
+        index => index.strip_prefix('_')?.parse().ok(),
+    }
+}
+
+pub(super) fn validate(graph: &ShaderGraph) -> Vec<Issue> {
+    let topology = &graph.topology;
+    let mut issues = vec![];
+    if let Some(nodes) = topology.cycle() {
+        issues.push(Issue::Cycle(nodes));
+    }
+
+    let used = used(graph);
+    let ids = (0..topology.len()).map(NodeId);
+    issues.extend(ids.filter(|id| !used.contains(id)).map(Issue::Unused));
+
+    for id in (0..topology.len()).map(NodeId) {
+        let node = match &graph.nodes[id.0] {
+            Some(node) => node,
+            None => continue,
+        };
+        let inputs = node.inputs();
+
+        if let Some(program) = node.program() {
+            let bound = bound_textures(graph, &inputs);
+            let mut dangling = program
+                .uniforms()
+                .map(|(uniform, _)| uniform)
+                .filter(|uniform| {
+                    texture_index(uniform).is_some_and(|index| index >= bound)
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            dangling.sort();
+            issues.extend(
+                dangling
+                    .into_iter()
+                    .map(|uniform| Issue::DanglingInput { node: id, uniform }),
+            );
+        }
+
+        let size = match node.texture() {
+            Some(texture) => texture.dimensions(),
+            None => continue,
+        };
+        for input in inputs {
+            let resolved = topology.resolve(&graph.bypassed, input);
+            // sized like the output once the graph runs
+            if graph.previous == Some(resolved) {
+                continue;
+            }
+            let input_size = match graph.texture(resolved) {
+                Some(texture) => texture.dimensions(),
+                None => continue,
+            };
+            if input_size != size {
+                issues.push(Issue::ResolutionMismatch {
+                    node: id,
+                    input,
+                    size,
+                    input_size,
+                });
+            }
+        }
+    }
+    issues
+}
+
+/// A short name for a texture's format, like `rgba16f`.
+fn format_name(texture: &Texture2d) -> Option<String> {
+    let (channels, ty, bits) = match texture.get_internal_format().ok()? {
+        InternalFormat::OneComponent { ty1, bits1 } => ("r", ty1, bits1),
+        InternalFormat::TwoComponents { ty1, bits1, .. } => ("rg", ty1, bits1),
+        InternalFormat::ThreeComponents { ty1, bits1, .. } => {
+            ("rgb", ty1, bits1)
+        },
+        InternalFormat::FourComponents { ty1, bits1, .. } => {
+            ("rgba", ty1, bits1)
+        },
+    };
+    let suffix = match ty {
+        InternalFormatType::Float => "f",
+        InternalFormatType::Int => "i",
+        InternalFormatType::UnsignedInt => "ui",
+        InternalFormatType::SignedNormalized => "_snorm",
+        InternalFormatType::UnsignedNormalized => "",
+    };
+    Some(format!("{}{}{}", channels, bits, suffix))
+}
+
+pub(super) fn to_dot(graph: &ShaderGraph) -> String {
+    let topology = &graph.topology;
+    let inputs = topology.get_inputs();
+    let outputs = topology.get_outputs();
+    let mut dot = String::new();
+    // unwrap: writing to a string can't fail
+    writeln!(dot, "digraph shadergarden {{").unwrap();
+    writeln!(dot, "    rankdir=LR;").unwrap();
+    writeln!(dot, "    node [shape=box, fontname=monospace];").unwrap();
+
+    for id in topology.evaluation_order() {
+        let mut text = label(topology, *id);
+        if let Some(note) = topology.label_of(*id) {
+            write!(text, "\n{}", note).unwrap();
+        }
+        let texture = graph
+            .texture(*id)
+            .or_else(|| graph.uploads.get(id).map(|(_, texture)| texture));
+        if let Some(texture) = texture {
+            let (width, height) = texture.dimensions();
+            write!(text, "\n{}x{}", width, height).unwrap();
+        }
+        let mut attributes = vec![format!("label={:?}", text)];
+        if inputs.contains(id) {
+            attributes.push("shape=invhouse".to_string());
+        }
+        if outputs.contains(id) {
+            attributes.push("peripheries=2".to_string());
+        }
+        if graph.bypassed.contains(id) {
+            attributes.push("style=dashed".to_string());
+        }
+        writeln!(dot, "    n{} [{}];", id.0, attributes.join(", ")).unwrap();
+    }
+
+    for id in topology.evaluation_order() {
+        for input in topology.node_inputs(*id) {
+            let format = graph
+                .texture(topology.resolve(&graph.bypassed, *input))
+                .and_then(format_name);
+            match format {
+                Some(format) => writeln!(
+                    dot,
+                    "    n{} -> n{} [label={:?}];",
+                    input.0, id.0, format
+                ),
+                None => writeln!(dot, "    n{} -> n{};", input.0, id.0),
+            }
+            .unwrap();
+        }
+    }
+    for (before, after) in topology.hints() {
+        writeln!(dot, "    n{} -> n{} [style=dotted];", before.0, after.0)
+            .unwrap();
+    }
+    // written after every frame, so they don't rank the nodes
+    let writes = graph
+        .buffers
+        .iter()
+        .filter_map(|(buffer, writer)| Some((writer.as_ref()?, buffer)));
+    for (writer, buffer) in writes {
+        writeln!(
+            dot,
+            "    n{} -> n{} [style=dashed, constraint=false];",
+            writer.0, buffer.0
+        )
+        .unwrap();
+    }
+    writeln!(dot, "}}").unwrap();
+    dot
+}
@@ -1,36 +1,228 @@
 use std::{
-    collections::BTreeMap,
+    borrow::Cow,
+    collections::{
+        hash_map::RandomState,
+        BTreeMap,
+        BTreeSet,
+    },
+    hash::BuildHasher,
+    path::{
+        Path,
+        PathBuf,
+    },
     rc::Rc,
-    time::Instant,
+    time::{
+        Duration,
+        Instant,
+        SystemTime,
+    },
 };
 
 use glium::{
     backend::Context,
-    uniforms::AsUniformValue,
+    texture::{
+        ClientFormat,
+        RawImage2d,
+    },
+    uniform,
+    uniforms::{
+        AsUniformValue,
+        MagnifySamplerFilter,
+        MinifySamplerFilter,
+        SamplerBehavior,
+        UniformValue,
+    },
+    Rect,
+    Surface,
     Texture2d,
 };
+use image::{
+    ImageBuffer,
+    Rgba,
+    RgbaImage,
+};
 
-use crate::util::{
-    compile_shader,
-    default_buffer,
-    RectStrip,
+use crate::{
+    audio::{
+        self,
+        Analyser,
+        Capture,
+    },
+    camera::Camera,
+    canvas::{
+        Canvas,
+        Tile,
+    },
+    capabilities::{
+        Capabilities,
+        Requirements,
+    },
+    data::DataTable,
+    graph::snapshot::{
+        Image,
+        NodeState,
+    },
+    keyboard::{
+        Keyboard,
+        KEYS,
+    },
+    knobs::Knob,
+    meta::Meta,
+    palette::Palette,
+    points::Point,
+    upload::StreamingTexture,
+    util::{
+        compile_point_shader,
+        compile_shader,
+        debug,
+        default_buffer,
+        float_texture,
+        font,
+        read_texture,
+        RectStrip,
+    },
+    video::{
+        Playback,
+        Video,
+    },
 };
 
+mod audio_source;
+mod auto_expose_node;
+mod bake;
+mod buffer_node;
+mod bundle;
+mod cache;
+mod camera_source;
+mod clock;
 mod compute_node;
+mod compute_shader_node;
+mod condition;
+mod data_node;
+#[cfg(feature = "feed")]
+mod feed_source;
+mod flow_node;
+mod fluid_node;
+mod limits;
+mod lint;
+mod mrt_node;
 mod node;
+mod plan;
+mod point_node;
+mod profile;
+mod profiler;
+mod pyramid_node;
+mod random;
+mod repeat_node;
+mod reproject_node;
 mod shader_node;
+mod snapshot;
+mod source;
+mod source_node;
+mod sources;
+mod stats_node;
+mod texture;
+mod topology;
 mod uniform;
+mod validator;
+mod video_source;
+mod window;
 
+pub use audio_source::AudioSource;
+pub use auto_expose_node::AutoExposeNode;
+pub use bake::Baked;
+pub use buffer_node::BufferNode;
+pub use bundle::Bundle;
+pub use cache::CachedOutput;
+pub use camera_source::CameraSource;
+pub use clock::Clock;
 pub use compute_node::{
     ComputeNode,
     ComputeNodeFn,
 };
+pub use compute_shader_node::ComputeShaderNode;
+pub use condition::{
+    Compare,
+    Condition,
+    Operand,
+};
+pub use data_node::DataNode;
+#[cfg(feature = "feed")]
+pub use feed_source::FeedSource;
+pub use flow_node::FlowNode;
+pub use fluid_node::FluidNode;
+pub use limits::Limits;
+pub use lint::Issue;
+pub use mrt_node::{
+    AttachmentNode,
+    MrtNode,
+};
 pub use node::Node;
+pub use plan::{
+    External,
+    ExternalFn,
+    Init,
+    NodeSpec,
+    Plan,
+    Realization,
+};
+pub use point_node::PointNode;
+pub use profile::Profile;
+pub use profiler::{
+    Profiler,
+    Timings,
+};
+pub use pyramid_node::PyramidNode;
+pub use random::{
+    Distribution,
+    RandomTexture,
+};
+pub use repeat_node::RepeatNode;
+pub use reproject_node::ReprojectNode;
 pub use shader_node::{
     Buffer,
     ShaderNode,
 };
+pub use source::InputSource;
+pub use source_node::SourceNode;
+pub use sources::Sources;
+pub use stats_node::{
+    Stats,
+    StatsNode,
+};
+pub use texture::{
+    Filter,
+    Format,
+    PixelFormat,
+    TextureOptions,
+    Wrap,
+};
+pub use topology::Topology;
 use uniform::UniformMap;
+pub use uniform::{
+    Uniform,
+    UniformType,
+};
+pub use validator::Validator;
+pub use video_source::VideoSource;
+pub use window::OutputWindow;
+
+pub use crate::diagnostic::BuildError;
+
+/// The size of [`ShaderGraph::error_screen`], which is
+/// scaled to fit the window like any other output.
+const ERROR_SCREEN: (u32, u32) = (1280, 720);
+
+/// `u_dt` on the first frame, and the most it can be, so
+/// that simulations don't blow up after a pause or a hitch.
+const FIRST_DT: f32 = 1.0 / 60.0;
+const MAX_DT: f32 = 0.1;
+
+/// A seed that differs from build to build, see
+/// [`ShaderGraph::set_build_seed`].
+fn random_seed() -> u32 {
+    RandomState::new().hash_one(SystemTime::now()) as u32
+}
 
 // TODO: remove the distinction between uniforms and
 // textures as inputs
@@ -40,28 +232,147 @@ use uniform::UniformMap;
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct NodeId(usize);
 
+impl NodeId {
+    /// The order the node was added to the graph in, from
+    /// 0, e.g. to tell unnamed nodes apart in reports.
+    pub fn index(self) -> usize { self.0 }
+}
+
 /// Represents a Directed Acyclic Graph of shaders.
 /// Each shader is run sequentially, and can be the input to
 /// shaders later down the line.
 pub struct ShaderGraph {
-    context:     Rc<Context>,
-    rect_strip:  RectStrip,
-    pub created: std::time::Instant,
+    context:      Rc<Context>,
+    capabilities: Capabilities,
+    rect_strip:   RectStrip,
+    /// The transport `u_time` is read from, see
+    /// [`ShaderGraph::pause`].
+    clock:        Clock,
+    render_scale: f32,
+    /// See [`ShaderGraph::supersample`].
+    supersample:  u32,
+    quality:      f32,
+    /// The canvas size and the tile of it being rendered,
+    /// see [`ShaderGraph::set_canvas`].
+    canvas:       [f32; 2],
+    tile:         [f32; 4],
+    /// The size of the first output, passed to every node
+    /// as `u_output_resolution`, kept from the last frame
+    /// for outputs that aren't sized until they run.
+    output_size:  [f32; 2],
+    /// See [`ShaderGraph::add_previous_output`].
+    previous:     Option<NodeId>,
+    /// Every buffer, and the node that writes it, if any,
+    /// see [`ShaderGraph::add_buffer`].
+    buffers:      BTreeMap<NodeId, Option<NodeId>>,
 
     /// None is an input node.
-    nodes: Vec<Option<Box<dyn Node>>>,
+    nodes:    Vec<Option<Box<dyn Node>>>,
+    topology: Topology,
 
-    // TODO: use sets?
-    inputs:  Vec<NodeId>,
-    outputs: Vec<NodeId>,
+    /// Nodes that pass their first input through instead
+    /// of running.
+    bypassed:   BTreeSet<NodeId>,
+    /// A node shown in place of every output, if any.
+    solo:       Option<NodeId>,
+    /// Checks each node for NaN and infinite pixels.
+    validator:  Option<Validator>,
+    /// Times each node on the GPU, see
+    /// [`ShaderGraph::set_profiling`].
+    profiler:   Option<Profiler>,
+    /// The most GPU time a frame can take, see
+    /// [`ShaderGraph::set_gpu_timeout`].
+    timeout:    Option<Duration>,
+    /// Nodes stopped for taking too long, and the frame the
+    /// last one was stopped at.
+    stalled:    BTreeSet<NodeId>,
+    stall:      Option<i32>,
+    /// Uniforms set from outside the graph, see
+    /// [`ShaderGraph::set_param`].
+    params:     BTreeMap<(NodeId, String), f32>,
+    /// Uniforms the application passes to every node, see
+    /// [`ShaderGraph::set_uniform`].
+    uniforms:   BTreeMap<String, Uniform>,
+    /// Color sets, and which one is passed to every node,
+    /// see [`ShaderGraph::set_palette`].
+    palettes:   Vec<Palette>,
+    palette:    usize,
+    /// The names of the colors of palettes, `palette[0]`,
+    /// `palette[1]`, and so on, up to the length of the
+    /// longest, so they aren't formatted every frame.
+    swatches:   Vec<String>,
+    /// Uniforms set from MIDI and OSC, see
+    /// [`ShaderGraph::add_knob`].
+    knobs:      Vec<Knob>,
+    /// Nodes shown in windows of their own, see
+    /// [`ShaderGraph::add_window`].
+    windows:    Vec<OutputWindow>,
+    /// Who made the graph, see [`ShaderGraph::set_meta`].
+    meta:       Meta,
+    /// How seeded nodes were seeded, so they can be seeded
+    /// again, see [`ShaderGraph::reset_feedback`].
+    inits:      BTreeMap<NodeId, Init>,
+    /// Random textures, and the seed each was last made
+    /// from, see [`ShaderGraph::add_random`].
+    randoms:    BTreeMap<NodeId, (RandomTexture, u32)>,
+    /// Nodes to cache once they've run, by their key, and
+    /// where, see [`ShaderGraph::cache_node`].
+    caching:    BTreeMap<NodeId, String>,
+    cache_dir:  Option<PathBuf>,
+    /// Nodes whose output was set, and the nodes that are
+    /// only read by them, which aren't run, see
+    /// [`ShaderGraph::fix_node`].
+    fixed:      BTreeSet<NodeId>,
+    settled:    BTreeSet<NodeId>,
+    /// The text of every shader running, and the extension
+    /// of its kind, see [`ShaderGraph::sources`].
+    sources:    BTreeMap<NodeId, (&'static str, String)>,
+    /// Extra passes to run before the next forward pass,
+    /// see [`ShaderGraph::warm_up`].
+    warmup:     u32,
+    warmups:    BTreeMap<NodeId, u32>,
+    /// Nodes run several times a frame, see
+    /// [`ShaderGraph::set_substeps`].
+    substeps:   BTreeMap<NodeId, u32>,
+    /// Nodes only run when a condition holds, see
+    /// [`ShaderGraph::set_condition`].
+    conditions: BTreeMap<NodeId, Condition>,
+    /// Buffers a forward pass fills and empties again, kept
+    /// so it doesn't allocate them every frame: the nodes
+    /// whose conditions don't hold, and the nodes cached
+    /// that ran.
+    skipped:    Vec<NodeId>,
+    ran_cached: Vec<NodeId>,
+    /// When the last forward pass started, and how long
+    /// before that the one before it did, in seconds.
+    last_frame: Option<Instant>,
+    dt:         f32,
+    /// The time and `u_dt` used in place of the clock, see
+    /// [`ShaderGraph::set_fixed_time`].
+    fixed_time: Option<(f32, f32)>,
+    /// The `u_dt` of the next forward pass, after a step
+    /// with [`ShaderGraph::step_frame`].
+    step:       Option<f32>,
+    /// See [`ShaderGraph::set_build_seed`].
+    build_seed: u32,
+    /// How many times every node has run since the graph
+    /// was built or reset, passed as `u_frame`.
+    frame:      i32,
+    /// See [`ShaderGraph::set_mouse`].
+    mouse:      [f32; 4],
+    /// See [`ShaderGraph::set_keyboard`], uploaded once
+    /// it's first set.
+    keyboard:   Option<(Keyboard, Texture2d)>,
+    /// Inputs uploaded from the CPU, see
+    /// [`ShaderGraph::upload_input`].
+    uploads:    BTreeMap<NodeId, (PixelFormat, Texture2d)>,
 }
 
 impl std::fmt::Debug for ShaderGraph {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ShaderGraph")
             .field("nodes", &self.nodes)
-            .field("inputs", &self.inputs)
-            .field("outputs", &self.outputs)
+            .field("topology", &self.topology)
             .finish()
     }
 }
@@ -71,45 +382,307 @@ impl ShaderGraph {
     /// context.
     pub fn new(context: &Rc<Context>) -> ShaderGraph {
         ShaderGraph {
-            context:    context.clone(),
-            rect_strip: RectStrip::new(context),
-            nodes:      vec![],
-            inputs:     vec![],
-            outputs:    vec![],
-            created:    Instant::now(),
+            context:      context.clone(),
+            capabilities: Capabilities::detect(context),
+            rect_strip:   RectStrip::new(context),
+            nodes:        vec![],
+            topology:     Topology::new(),
+            clock:        Clock::new(),
+            render_scale: 1.0,
+            supersample:  1,
+            quality:      1.0,
+            canvas:       [0.0, 0.0],
+            tile:         [0.0, 0.0, 1.0, 1.0],
+            output_size:  [0.0, 0.0],
+            previous:     None,
+            buffers:      BTreeMap::new(),
+            bypassed:     BTreeSet::new(),
+            solo:         None,
+            validator:    None,
+            profiler:     None,
+            timeout:      None,
+            stalled:      BTreeSet::new(),
+            stall:        None,
+            params:       BTreeMap::new(),
+            uniforms:     BTreeMap::new(),
+            palettes:     vec![],
+            palette:      0,
+            swatches:     vec![],
+            knobs:        vec![],
+            windows:      vec![],
+            meta:         Meta::default(),
+            inits:        BTreeMap::new(),
+            randoms:      BTreeMap::new(),
+            caching:      BTreeMap::new(),
+            cache_dir:    cache::default_dir(),
+            fixed:        BTreeSet::new(),
+            settled:      BTreeSet::new(),
+            sources:      BTreeMap::new(),
+            warmup:       0,
+            warmups:      BTreeMap::new(),
+            substeps:     BTreeMap::new(),
+            conditions:   BTreeMap::new(),
+            skipped:      vec![],
+            ran_cached:   vec![],
+            last_frame:   None,
+            dt:           FIRST_DT,
+            fixed_time:   None,
+            step:         None,
+            build_seed:   random_seed(),
+            frame:        0,
+            mouse:        [0.0; 4],
+            keyboard:     None,
+            uploads:      BTreeMap::new(),
         }
     }
 
-    pub fn get_inputs(&self) -> &Vec<NodeId> { &self.inputs }
+    /// Creates a graph that shows an error as text, to
+    /// stand in for a graph that failed to build. It
+    /// has as many inputs as the graph it stands in
+    /// for, which are ignored, and a single output.
+    pub fn error_screen(
+        context: &Rc<Context>,
+        error: &str,
+        inputs: usize,
+    ) -> Result<ShaderGraph, String> {
+        let (width, height) = ERROR_SCREEN;
+        let pixels = font::draw_text(error, width, height, 2);
+        let raw = RawImage2d::from_raw_rgba_reversed(&pixels, (width, height));
+        let texture = Texture2d::new(context, raw)
+            .map_err(|e| format!("Could not draw error screen: {}", e))?;
+
+        let mut graph = ShaderGraph::new(context);
+        for _ in 0..inputs {
+            graph.add_input();
+        }
+        let screen = graph.add_node(Some(Box::new(DataNode { texture })));
+        graph.mark_output(screen);
+        Ok(graph)
+    }
+
+    /// Scales the size of every node added afterwards, so
+    /// that heavy graphs can be rendered at a lower
+    /// resolution and upscaled for display.
+    pub fn set_render_scale(&mut self, scale: f32) {
+        self.render_scale = scale;
+    }
+
+    /// Marks the graph as rendered this many times larger
+    /// than its size in each direction, e.g. by a profile,
+    /// see [`Profile::supersample`]. The graph's nodes are
+    /// scaled as set by [`ShaderGraph::set_render_scale`];
+    /// this tells hosts to average each block of that many
+    /// pixels squared of the output into one, as `render`
+    /// does.
+    pub fn set_supersample(&mut self, factor: u32) {
+        self.supersample = factor.max(1);
+    }
+
+    /// See [`ShaderGraph::set_supersample`], 1 unless set.
+    pub fn supersample(&self) -> u32 { self.supersample }
+
+    /// Sets the `u_quality` uniform passed to every node,
+    /// between `0` and `1`. See
+    /// [`crate::quality::QualityController`].
+    pub fn set_quality(&mut self, quality: f32) { self.quality = quality; }
+
+    /// Errors if the context this graph is built in does
+    /// not meet the requirements, so that the graph
+    /// fails to build instead of rendering garbage.
+    pub fn require(&self, requirements: &Requirements) -> Result<(), String> {
+        requirements.check(&self.capabilities)
+    }
+
+    /// The features and limits of the context this graph is
+    /// built in, e.g. to warn before running a graph on
+    /// hardware it won't fit.
+    pub fn capabilities(&self) -> &Capabilities { &self.capabilities }
+
+    /// Renders one tile of a larger canvas, e.g. one screen
+    /// of a video wall. Every node is passed `u_canvas`,
+    /// the size of the canvas in pixels, and `u_tile`,
+    /// the region of it being rendered as a fraction
+    /// from the bottom left, so that
+    /// `u_tile.xy + gl_FragCoord.xy / u_resolution *
+    /// u_tile.zw` is the same position on every
+    /// machine.
+    pub fn set_canvas(&mut self, canvas: Canvas, tile: Tile) {
+        self.canvas = [canvas.width as f32, canvas.height as f32];
+        self.tile = tile.uniform(canvas);
+    }
+
+    /// Sets the seed passed to every node as
+    /// `u_build_seed`, a float from 0 to 1. Each graph
+    /// is created with a random seed, which is rolled
+    /// again when its feedback is reset, so that
+    /// generative pieces vary from build to build while
+    /// staying the same as they run. Setting
+    /// it pins down a variation, e.g. one logged earlier.
+    pub fn set_build_seed(&mut self, seed: u32) { self.build_seed = seed; }
 
-    pub fn get_outputs(&self) -> &Vec<NodeId> { &self.outputs }
+    /// The seed set with [`ShaderGraph::set_build_seed`].
+    pub fn build_seed(&self) -> u32 { self.build_seed }
+
+    /// Sets the `u_mouse` uniform passed to every node, as
+    /// in Shadertoy's `iMouse` but measured in fractions of
+    /// the output from the bottom left: `xy` is where the
+    /// left button was last held, and `zw` where it was
+    /// pressed. `z` is negative once the button is
+    /// released, and `w` is only positive the frame
+    /// it's pressed.
+    pub fn set_mouse(&mut self, mouse: [f32; 4]) { self.mouse = mouse; }
+
+    /// Sets the `u_keyboard` texture passed to every node,
+    /// as Shadertoy's keyboard input, see
+    /// [`Keyboard::texels`]. It's only uploaded again
+    /// when a key changes.
+    pub fn set_keyboard(&mut self, keyboard: &Keyboard) -> Result<(), String> {
+        match &mut self.keyboard {
+            Some((last, _)) if last == keyboard => (),
+            Some((last, texture)) => {
+                let texels = keyboard.texels();
+                let raw = RawImage2d {
+                    data:   Cow::Borrowed(&texels[..]),
+                    width:  KEYS as u32,
+                    height: 3,
+                    format: ClientFormat::F32,
+                };
+                texture.write(
+                    Rect {
+                        left:   0,
+                        bottom: 0,
+                        width:  KEYS as u32,
+                        height: 3,
+                    },
+                    raw,
+                );
+                *last = keyboard.clone();
+            },
+            None => {
+                let texels = keyboard.texels();
+                let texture =
+                    float_texture(&self.context, KEYS as u32, 3, &texels)?;
+                self.keyboard = Some((keyboard.clone(), texture));
+            },
+        }
+        Ok(())
+    }
+
+    /// Runs the following forward passes at `time`, with
+    /// `u_dt` set to `dt`, rather than by the clock, so
+    /// that offline renders come out the same every
+    /// time.
+    pub fn set_fixed_time(&mut self, time: f32, dt: f32) {
+        self.fixed_time = Some((time, dt));
+    }
+
+    /// Stops `u_time`, until [`ShaderGraph::resume`] is
+    /// called. Forward passes still run, with a `u_dt` of
+    /// zero, so paused feedback loops hold still.
+    pub fn pause(&mut self) { self.clock.pause(); }
+
+    pub fn resume(&mut self) { self.clock.resume(); }
+
+    pub fn is_paused(&self) -> bool { self.clock.is_paused() }
+
+    /// Moves `u_time` ahead by one frame of `dt` seconds,
+    /// which is also the `u_dt` of the next forward pass.
+    /// Meant for stepping through a paused graph.
+    pub fn step_frame(&mut self, dt: f32) {
+        self.clock.seek(self.clock.time() + dt as f64);
+        self.step = Some(dt);
+    }
+
+    /// Moves `u_time` to a time in seconds, keeping the
+    /// graph paused or running as it was.
+    pub fn seek(&mut self, time: f32) { self.clock.seek(time as f64); }
+
+    /// Sets how fast `u_time` runs, where 1 is real time,
+    /// scaling `u_dt` along with it.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.clock.set_speed(speed as f64);
+    }
+
+    pub fn speed(&self) -> f32 { self.clock.speed() as f32 }
+
+    /// The `u_time` of the next forward pass, in seconds.
+    pub fn time(&self) -> f32 {
+        match self.fixed_time {
+            Some((time, _)) => time,
+            None => self.clock.time() as f32,
+        }
+    }
+
+    /// The number of forward passes run since the graph was
+    /// built or its feedback was reset, passed as
+    /// `u_frame`.
+    pub fn frame(&self) -> i32 { self.frame }
+
+    /// Takes the clock and frame count of an older graph,
+    /// so that `u_time` and `u_frame` carry on after a
+    /// rebuild rather than starting over. Otherwise, a
+    /// new graph starts from zero, running at normal
+    /// speed.
+    pub fn carry_clock(&mut self, old: &ShaderGraph) {
+        self.clock = old.clock;
+        self.frame = old.frame;
+        self.last_frame = old.last_frame;
+        self.fixed_time = old.fixed_time;
+    }
+
+    /// Applies the render scale to the size of a node, and
+    /// checks that it fits in a texture.
+    fn scaled(&self, width: u32, height: u32) -> Result<(u32, u32), String> {
+        let scale = |size: u32| {
+            ((size as f32 * self.render_scale).round() as u32).max(1)
+        };
+        let (width, height) = (scale(width), scale(height));
+        let max = self.capabilities.max_texture_size;
+        if width > max || height > max {
+            return Err(format!(
+                "A {}x{} node is larger than the largest texture supported, \
+                 {}x{}",
+                width, height, max, max
+            ));
+        }
+        Ok((width, height))
+    }
+
+    pub fn get_inputs(&self) -> &Vec<NodeId> { self.topology.get_inputs() }
+
+    pub fn get_outputs(&self) -> &Vec<NodeId> { self.topology.get_outputs() }
+
+    /// The shape of the graph, without its nodes.
+    pub fn topology(&self) -> &Topology { &self.topology }
 
     /// Adds anything that implements the `Node` trait to
     /// the graph Will panic if the `Node` does not
     /// preserve DAG structure.
     pub fn add_node(&mut self, node: Option<Box<dyn Node>>) -> NodeId {
-        if let Some(ref node) = node {
-            self.assert_dag(&node.inputs())
-        }
-
+        let inputs = node.as_ref().map(|n| n.inputs()).unwrap_or_default();
+        let id = self.topology.add_node(inputs);
         self.nodes.push(node);
-        NodeId(self.nodes.len() - 1)
+        id
     }
 
-    fn assert_dag(&self, nodes: &[NodeId]) {
-        // to preserve acyclic structure, can only ref backwards
-        for NodeId(input) in nodes {
-            assert!(input < &self.nodes.len());
-        }
+    /// Adds a node run by a shader, keeping its source.
+    fn add_shader_node(
+        &mut self,
+        node: Box<dyn Node>,
+        source: &str,
+        extension: &'static str,
+    ) -> NodeId {
+        let id = self.add_node(Some(node));
+        self.sources.insert(id, (extension, source.to_string()));
+        id
     }
 
     /// Adds an input to the shader graph.
     /// Use the returned `NodeId` to mark it as a
     /// texture input to other shaders.
     pub fn add_input(&mut self) -> NodeId {
-        let id = self.add_node(None);
-        self.inputs.push(id);
-        id
+        self.nodes.push(None);
+        self.topology.add_input()
     }
 
     // TODO: custom uniforms!
@@ -124,11 +697,8 @@ impl ShaderGraph {
         width: u32,
         height: u32,
     ) -> Result<NodeId, String> {
-        self._add_shader(
-            source,
-            inputs,
-            Buffer::Single(default_buffer(&self.context, width, height)),
-        )
+        let options = TextureOptions::default();
+        self.add_shader_with(source, inputs, width, height, false, options)
     }
 
     /// Add a recurrent shader to the graph.
@@ -139,28 +709,863 @@ impl ShaderGraph {
         width: u32,
         height: u32,
     ) -> Result<NodeId, String> {
-        // set up the shader and its buffers
-        self._add_shader(
-            source,
+        let options = TextureOptions::default();
+        self.add_shader_with(source, inputs, width, height, true, options)
+    }
+
+    /// Adds a shader whose output is stored and sampled as
+    /// `options` says, e.g. in floats for a simulation, or
+    /// repeating for a tiling texture. A `recurrent` shader
+    /// can read its own last frame, as with
+    /// [`ShaderGraph::add_rec_shader`].
+    pub fn add_shader_with(
+        &mut self,
+        source: &str,
+        inputs: Vec<NodeId>,
+        width: u32,
+        height: u32,
+        recurrent: bool,
+        options: TextureOptions,
+    ) -> Result<NodeId, String> {
+        let (width, height) = self.scaled(width, height)?;
+        self.check_format(options.format)?;
+        let buffer = || options.buffer(&self.context, width, height);
+        let buffer = if recurrent {
+            Buffer::Double(buffer()?, buffer()?)
+        } else {
+            Buffer::Single(buffer()?)
+        };
+
+        let shader_node = ShaderNode {
+            shader: compile_shader(&self.context, source)?,
             inputs,
-            Buffer::new_double(|| default_buffer(&self.context, width, height)),
-        )
+            buffer,
+            options,
+        };
+        Ok(self.add_shader_node(Box::new(shader_node), source, "frag"))
+    }
+
+    /// Checks that the driver can render to a format.
+    fn check_format(&self, format: Format) -> Result<(), String> {
+        if format.is_float() && !self.capabilities.float_textures {
+            return Err(format!(
+                "{} textures are not supported by this driver",
+                format
+            ));
+        }
+        Ok(())
+    }
+
+    /// Fills the state of a feedback node, like the last
+    /// frame of a recurrent shader, with an image or the
+    /// output of a shader rather than black, e.g. to seed a
+    /// cellular automaton. Init shaders are passed the size
+    /// of the node as `resolution`, and its width over its
+    /// height as `aspect`.
+    pub fn init_node(&mut self, id: NodeId, init: &Init) -> Result<(), String> {
+        let state = match self.nodes.get(id.0) {
+            Some(Some(node)) => node.state(),
+            _ => vec![],
+        };
+        if state.is_empty() {
+            return Err(format!("Node {} has no state to initialize", id.0));
+        }
+
+        match init {
+            Init::Image(path) => {
+                let seed = self.load_image(path)?;
+                // nearest, to keep seed pixels crisp
+                let filter = MagnifySamplerFilter::Nearest;
+                for texture in state {
+                    seed.as_surface().fill(&texture.as_surface(), filter);
+                }
+            },
+            Init::Shader(source) => {
+                let program = compile_shader(&self.context, source)?;
+                for texture in state {
+                    let (width, height) = texture.dimensions();
+                    texture
+                        .as_surface()
+                        .draw(
+                            &self.rect_strip.buffer,
+                            self.rect_strip.indices,
+                            &program,
+                            &uniform! {
+                                resolution: [width as f32, height as f32],
+                                aspect: width as f32 / height.max(1) as f32,
+                            },
+                            &Default::default(),
+                        )
+                        .map_err(|e| format!("Could not run seed: {}", e))?;
+                }
+            },
+        }
+        self.inits.insert(id, init.clone());
+        Ok(())
     }
 
-    fn _add_shader(
+    /// Starts every feedback loop over, without rebuilding
+    /// the graph: the state of each node is cleared to
+    /// black, or seeded again if it was seeded with
+    /// [`ShaderGraph::init_node`]. Compiled shaders are
+    /// kept, so this is quick enough to restart a
+    /// simulation while tuning it. Returns the number
+    /// of nodes reset.
+    pub fn reset_feedback(&mut self) -> Result<usize, String> {
+        self.build_seed = random_seed();
+        self.frame = 0;
+        let mut reset = 0;
+        for node in self.nodes.iter().flatten() {
+            let state = node.state();
+            if state.is_empty() {
+                continue;
+            }
+            for texture in state {
+                texture.as_surface().clear_color(0.0, 0.0, 0.0, 0.0);
+            }
+            reset += 1;
+        }
+
+        for (id, init) in self.inits.clone() {
+            self.init_node(id, &init)?;
+        }
+        Ok(reset)
+    }
+
+    /// Whether any frame depends on the frames before it:
+    /// through feedback, nodes like optical flow that
+    /// compare frames, sources like videos that play on,
+    /// or conditions, which keep a skipped node's last
+    /// output. Graphs without any can render their frames
+    /// in any order, see [`ShaderGraph::render_at`].
+    pub fn has_feedback(&self) -> bool {
+        !self.conditions.is_empty()
+            || self.nodes.iter().flatten().any(|node| node.remembers())
+    }
+
+    /// Add a shader that is run `iterations` times per
+    /// frame, ping-ponging between two buffers. After the
+    /// first iteration, the input at index `feedback` is
+    /// replaced by the previous iteration's output.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_repeat_shader(
         &mut self,
         source: &str,
         inputs: Vec<NodeId>,
-        buffer: Buffer,
+        feedback: usize,
+        iterations: u32,
+        width: u32,
+        height: u32,
+        options: TextureOptions,
+    ) -> Result<NodeId, String> {
+        let (width, height) = self.scaled(width, height)?;
+        if feedback >= inputs.len() {
+            return Err("Fed back input is not an input of the shader".into());
+        }
+        self.check_format(options.format)?;
+
+        let repeat_node = RepeatNode {
+            shader: compile_shader(&self.context, source)?,
+            inputs,
+            feedback,
+            iterations: iterations.max(1),
+            buffer: Buffer::Double(
+                options.buffer(&self.context, width, height)?,
+                options.buffer(&self.context, width, height)?,
+            ),
+            options,
+        };
+        Ok(self.add_shader_node(Box::new(repeat_node), source, "frag"))
+    }
+
+    /// Add a chain of `depth` downsampling passes, each
+    /// running the same shader at half the size of the
+    /// last.
+    pub fn add_pyramid(
+        &mut self,
+        source: &str,
+        input: NodeId,
+        depth: u32,
     ) -> Result<NodeId, String> {
         let shader = compile_shader(&self.context, source)?;
+        let pyramid_node =
+            PyramidNode::new(&self.context, shader, input, depth);
+        Ok(self.add_shader_node(Box::new(pyramid_node), source, "frag"))
+    }
 
-        let shader_node = ShaderNode {
+    /// Adds a shader that renders to `count` textures at
+    /// once, see [`MrtNode`]. The first is the node's
+    /// output, and the others can be added as nodes of
+    /// their own with [`ShaderGraph::add_attachment`].
+    pub fn add_mrt_shader(
+        &mut self,
+        source: &str,
+        inputs: Vec<NodeId>,
+        width: u32,
+        height: u32,
+        count: usize,
+    ) -> Result<NodeId, String> {
+        if count == 0 {
+            return Err("A shader must render to at least one texture".into());
+        }
+        if count > self.capabilities.color_attachments as usize {
+            return Err(format!(
+                "A shader can render to at most {} textures with this \
+                 driver, not {}",
+                self.capabilities.color_attachments, count
+            ));
+        }
+        let size = self.scaled(width, height)?;
+        let node = MrtNode::new(&self.context, source, inputs, size, count)?;
+        Ok(self.add_shader_node(Box::new(node), source, "frag"))
+    }
+
+    /// Adds a node whose output is one of the textures
+    /// another node renders to, counting from 0, e.g. the
+    /// second output of a shader added with
+    /// [`ShaderGraph::add_mrt_shader`].
+    pub fn add_attachment(
+        &mut self,
+        id: NodeId,
+        index: usize,
+    ) -> Result<NodeId, String> {
+        let texture = match self.nodes.get(id.0) {
+            Some(Some(node)) => node.attachment(index),
+            _ => None,
+        }
+        .ok_or_else(|| format!("Node {} has no output {}", id.0, index))?;
+        let node = AttachmentNode { input: id, texture };
+        Ok(self.add_node(Some(Box::new(node))))
+    }
+
+    /// Adds a node that dispatches a compute shader
+    /// `groups` workgroups at a time, with an output of
+    /// `width` by `height` and a buffer of `count`
+    /// `vec4`s, see [`ComputeShaderNode`]. Needs OpenGL
+    /// 4.3.
+    pub fn add_compute_shader(
+        &mut self,
+        source: &str,
+        inputs: Vec<NodeId>,
+        width: u32,
+        height: u32,
+        groups: [u32; 3],
+        count: Option<usize>,
+    ) -> Result<NodeId, String> {
+        if !self.capabilities.compute {
+            return Err(
+                "Compute shaders are not supported by this driver".to_string()
+            );
+        }
+        let size = self.scaled(width, height)?;
+        let node = ComputeShaderNode::new(
+            &self.context,
+            source,
+            inputs,
+            size,
+            groups,
+            count,
+        )?;
+        Ok(self.add_shader_node(Box::new(node), source, "comp"))
+    }
+
+    /// Adds a node that renders a point cloud with a
+    /// fragment shader. The points are uploaded once, when
+    /// the node is created. See [`crate::points`] for
+    /// loading point clouds from disk.
+    pub fn add_points(
+        &mut self,
+        source: &str,
+        points: &[Point],
+        inputs: Vec<NodeId>,
+        width: u32,
+        height: u32,
+    ) -> Result<NodeId, String> {
+        let (width, height) = self.scaled(width, height)?;
+        let shader = compile_point_shader(&self.context, source)?;
+        let points = glium::VertexBuffer::new(&self.context, points)
+            .map_err(|e| format!("Could not upload point cloud: {}", e))?;
+
+        let point_node = PointNode {
             shader,
             inputs,
-            buffer,
+            points,
+            texture: default_buffer(&self.context, width, height),
+            point_size: 1.0,
+        };
+        Ok(self.add_shader_node(Box::new(point_node), source, "frag"))
+    }
+
+    /// Adds a node holding a table of values as a
+    /// single-channel float texture. See [`crate::data`]
+    /// for loading tables from disk.
+    pub fn add_data(&mut self, table: &DataTable) -> Result<NodeId, String> {
+        let texture = float_texture(
+            &self.context,
+            table.width,
+            table.height,
+            &table.values,
+        )?;
+        Ok(self.add_node(Some(Box::new(DataNode { texture }))))
+    }
+
+    /// Adds a node holding a static image, at its own size,
+    /// e.g. to stand in for a node the driver can't run,
+    /// see [`Plan::set_fallback`].
+    pub fn add_image(&mut self, path: &Path) -> Result<NodeId, String> {
+        let texture = self.load_image(path)?;
+        Ok(self.add_node(Some(Box::new(DataNode { texture }))))
+    }
+
+    /// Adds a node holding a texture of random floats. It's
+    /// made from its seed, so it's the same every time the
+    /// graph is built, unless `regen_on_reload`, when every
+    /// build gets a new seed. Either way, it only changes
+    /// while running when asked to, see
+    /// [`ShaderGraph::regenerate`].
+    pub fn add_random(
+        &mut self,
+        random: &RandomTexture,
+    ) -> Result<NodeId, String> {
+        self.check_format(Format::Rgba32f)?;
+        let seed = if random.regen_on_reload {
+            random_seed()
+        } else {
+            random.seed
+        };
+        let texture = random.texture(&self.context, seed)?;
+        let id = self.add_node(Some(Box::new(DataNode { texture })));
+        self.randoms.insert(id, (*random, seed));
+        Ok(id)
+    }
+
+    /// Makes a random texture again, from a seed, or a new
+    /// one if none is given.
+    fn seed_random(
+        &mut self,
+        id: NodeId,
+        seed: Option<u32>,
+    ) -> Result<(), String> {
+        let (random, last) = self
+            .randoms
+            .get_mut(&id)
+            .ok_or_else(|| format!("Node {} is not a random texture", id.0))?;
+        let seed = seed.unwrap_or_else(random_seed);
+        let texture = random.texture(&self.context, seed)?;
+        *last = seed;
+        self.nodes[id.0] = Some(Box::new(DataNode { texture }));
+        Ok(())
+    }
+
+    /// Makes a random texture from a new seed, or every
+    /// random texture if `id` is `None`, and keeps them
+    /// that way when the graph is rebuilt, see
+    /// [`ShaderGraph::carry_random`]. Returns the number of
+    /// textures made.
+    pub fn regenerate(&mut self, id: Option<NodeId>) -> Result<usize, String> {
+        let ids = match id {
+            Some(id) => vec![id],
+            None => self.randoms.keys().copied().collect(),
+        };
+        for id in ids.iter() {
+            self.seed_random(*id, None)?;
+        }
+        Ok(ids.len())
+    }
+
+    /// Makes every random texture that's `regen_on_reload`
+    /// from a new seed, for reloads that keep the graph,
+    /// like recompiling a shader. Returns the number made.
+    pub fn regenerate_on_reload(&mut self) -> Result<usize, String> {
+        let ids = self
+            .randoms
+            .iter()
+            .filter(|(_, (random, _))| random.regen_on_reload)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in ids.iter() {
+            self.seed_random(*id, None)?;
+        }
+        Ok(ids.len())
+    }
+
+    /// Keeps the random textures of an older graph, so that
+    /// regenerating one isn't undone by the next rebuild.
+    /// Nodes are matched by name, and only kept if they're
+    /// made the same way, and not `regen_on_reload`.
+    /// Returns the number of nodes kept.
+    pub fn carry_random(&mut self, old: &ShaderGraph) -> usize {
+        let mut carried = vec![];
+        for (name, id) in self.topology.node_names() {
+            let old_id = match old.node_id(name) {
+                Some(old_id) => old_id,
+                None => continue,
+            };
+            match (self.randoms.get(&id), old.randoms.get(&old_id)) {
+                (Some((random, seed)), Some((old_random, old_seed)))
+                    if random == old_random
+                        && !random.regen_on_reload
+                        && seed != old_seed =>
+                {
+                    carried.push((id, *old_seed))
+                },
+                _ => (),
+            }
+        }
+        carried
+            .into_iter()
+            .filter(|(id, seed)| self.seed_random(*id, Some(*seed)).is_ok())
+            .count()
+    }
+
+    /// Keeps the output of a node on disk between runs,
+    /// under a key that changes whenever anything the node
+    /// renders does, see [`Plan::cache_node`]. If an output
+    /// of the same size is cached, the node is fixed to it
+    /// now, and never runs. Otherwise it runs once, in the
+    /// next forward pass, and its output is cached, and
+    /// fixed from then on, see [`ShaderGraph::fix_node`].
+    /// Returns whether the output was cached already.
+    pub fn cache_node(
+        &mut self,
+        id: NodeId,
+        key: String,
+    ) -> Result<bool, String> {
+        let size = self
+            .texture(id)
+            .map(Texture2d::dimensions)
+            .ok_or_else(|| format!("Node {} has no output to cache", id.0))?;
+        let cached = match &self.cache_dir {
+            Some(dir) => cache::read(dir, &key),
+            None => return Ok(false),
+        };
+        match cached {
+            Some(output) if (output.width, output.height) == size => {
+                self.fix_node(id, &output)?;
+                Ok(true)
+            },
+            _ => {
+                self.caching.insert(id, key);
+                Ok(false)
+            },
+        }
+    }
+
+    /// Sets where [`ShaderGraph::cache_node`] keeps
+    /// outputs, by default `node-cache` in the user's
+    /// cache directory. With `None`, cached nodes run
+    /// once every time the graph is built, and nothing
+    /// is written.
+    pub fn set_cache_dir(&mut self, dir: Option<PathBuf>) {
+        self.cache_dir = dir;
+    }
+
+    /// Caches the output of nodes that have just run for
+    /// the first time, see [`ShaderGraph::cache_node`].
+    fn store_cached(&mut self, ran: &[NodeId]) {
+        for id in ran.iter() {
+            let key = match self.caching.remove(id) {
+                Some(key) => key,
+                None => continue,
+            };
+            let output = match self.node_output(*id) {
+                Some(output) => output,
+                None => continue,
+            };
+            if let Some(dir) = &self.cache_dir {
+                if let Err(e) = cache::write(dir, &key, &output) {
+                    eprintln!("[warn] {}", e);
+                }
+            }
+            self.fixed.insert(*id);
+        }
+        if !ran.is_empty() {
+            self.settle();
+        }
+    }
+
+    /// The output of a node as it is now, as floats, e.g.
+    /// to cache or bake it. `None` if it has no output.
+    pub fn node_output(&self, id: NodeId) -> Option<CachedOutput> {
+        let texture = self.texture(id)?;
+        Some(CachedOutput {
+            width:  texture.width(),
+            height: texture.height(),
+            pixels: read_texture(texture).into_iter().flatten().collect(),
+        })
+    }
+
+    /// Writes an output into a node, of the same size, and
+    /// stops running it, so that it keeps that output. The
+    /// node keeps its format and how it's sampled. Nodes
+    /// only read by fixed nodes stop running too, unless
+    /// they are outputs, shown in windows, or write to
+    /// buffers.
+    pub fn fix_node(
+        &mut self,
+        id: NodeId,
+        output: &CachedOutput,
+    ) -> Result<(), String> {
+        let texture = self
+            .texture(id)
+            .ok_or_else(|| format!("Node {} has no output to fix", id.0))?;
+        if texture.dimensions() != (output.width, output.height) {
+            return Err(format!(
+                "A {}x{} output doesn't fit node {}, which is {}x{}",
+                output.width,
+                output.height,
+                id.0,
+                texture.width(),
+                texture.height()
+            ));
+        }
+        let rect = Rect {
+            left:   0,
+            bottom: 0,
+            width:  output.width,
+            height: output.height,
+        };
+        texture.write(rect, RawImage2d {
+            data:   std::borrow::Cow::Borrowed(&output.pixels[..]),
+            width:  output.width,
+            height: output.height,
+            format: ClientFormat::F32F32F32F32,
+        });
+        self.fixed.insert(id);
+        self.settle();
+        Ok(())
+    }
+
+    /// Finds the nodes that needn't run, see
+    /// [`ShaderGraph::fix_node`]. Inputs always come before
+    /// the nodes reading them, so going backwards, every
+    /// reader of a node is settled before the node is.
+    fn settle(&mut self) {
+        let mut readers = vec![vec![]; self.topology.len()];
+        for id in 0..self.topology.len() {
+            for input in self.topology.node_inputs(NodeId(id)) {
+                readers[input.0].push(NodeId(id));
+            }
+        }
+        let mut kept = self.topology.get_outputs().clone();
+        kept.extend(self.windows.iter().map(|window| window.node));
+        kept.extend(self.buffers.values().flatten());
+
+        let mut settled = self.fixed.clone();
+        for id in self.topology.evaluation_order().iter().rev() {
+            let readers = &readers[id.0];
+            if !kept.contains(id)
+                && !readers.is_empty()
+                && readers.iter().all(|reader| settled.contains(reader))
+            {
+                settled.insert(*id);
+            }
+        }
+        self.settled = settled;
+    }
+
+    /// Uploads an image file as a texture.
+    fn load_image(&self, path: &Path) -> Result<Texture2d, String> {
+        let image = image::open(path)
+            .map_err(|e| format!("Could not load `{}`: {}", path.display(), e))?
+            .to_rgba8();
+        let size = image.dimensions();
+        let raw = RawImage2d::from_raw_rgba_reversed(&image.into_raw(), size);
+        Texture2d::new(&self.context, raw).map_err(|e| {
+            format!("Could not upload `{}`: {}", path.display(), e)
+        })
+    }
+
+    /// Adds a buffer, a texture that any node can take as
+    /// an input and that one node writes, see
+    /// [`ShaderGraph::write_buffer`]. It starts out black,
+    /// and is scaled like any node, see
+    /// [`ShaderGraph::set_render_scale`].
+    pub fn add_buffer(
+        &mut self,
+        width: u32,
+        height: u32,
+        options: TextureOptions,
+    ) -> Result<NodeId, String> {
+        let size = self.scaled(width, height)?;
+        self.check_format(options.format)?;
+        let node = BufferNode::new(&self.context, size, options)?;
+        let id = self.add_node(Some(Box::new(node)));
+        self.buffers.insert(id, None);
+        Ok(id)
+    }
+
+    /// Copies the output of `writer` into a buffer after
+    /// every frame, stretched to fit, so that every node
+    /// reading the buffer, the writer included, sees what
+    /// was written the frame before, whatever order they
+    /// run in. A buffer has at most one writer.
+    pub fn write_buffer(
+        &mut self,
+        buffer: NodeId,
+        writer: NodeId,
+    ) -> Result<(), String> {
+        let describe = |id| self.topology.describe(id);
+        match self.buffers.get(&buffer) {
+            None => Err(format!("`{}` is not a buffer", describe(buffer))),
+            Some(Some(_)) => Err(format!(
+                "`{}` is already written by another node",
+                describe(buffer)
+            )),
+            Some(None) => {
+                self.buffers.insert(buffer, Some(writer));
+                Ok(())
+            },
+        }
+    }
+
+    /// Adds a buffer holding the graph's first output from
+    /// the frame before, black until the first frame has
+    /// run, for echoes and global feedback from anywhere in
+    /// the graph. It's written like any buffer, see
+    /// [`ShaderGraph::write_buffer`], but at the output's
+    /// size. A graph has at most one, which is returned if
+    /// it's added again.
+    pub fn add_previous_output(&mut self) -> Result<NodeId, String> {
+        if let Some(id) = self.previous {
+            return Ok(id);
+        }
+        let options = self.previous_options();
+        let node = BufferNode::new(&self.context, (1, 1), options)?;
+        let id = self.add_node(Some(Box::new(node)));
+        self.previous = Some(id);
+        Ok(id)
+    }
+
+    /// How the previous output is stored: in floats where
+    /// supported, so that outputs outside of 0 to 1 are
+    /// kept as they are.
+    fn previous_options(&self) -> TextureOptions {
+        let format = match self.capabilities.float_textures {
+            true => Format::Rgba16f,
+            false => Format::Rgba16,
         };
-        Ok(self.add_node(Some(Box::new(shader_node))))
+        TextureOptions {
+            format,
+            ..TextureOptions::default()
+        }
+    }
+
+    /// Writes every buffer, see
+    /// [`ShaderGraph::write_buffer`], and the previous
+    /// output, resizing it to match the output first.
+    fn write_buffers(
+        &mut self,
+        input_map: &BTreeMap<NodeId, &Texture2d>,
+    ) -> Result<(), String> {
+        let mut writes = self
+            .buffers
+            .iter()
+            .filter_map(|(buffer, writer)| Some((*buffer, (*writer)?)))
+            .collect::<Vec<_>>();
+        let output = self.topology.get_outputs().first().copied();
+        if let (Some(previous), Some(output)) = (self.previous, output) {
+            writes.push((previous, output));
+        }
+
+        for (buffer, writer) in writes {
+            if buffer == writer {
+                continue;
+            }
+            // writers can be inputs passed straight through
+            let passed = input_map.get(&writer).copied();
+            let size = match self.texture(writer).or(passed) {
+                Some(texture) => texture.dimensions(),
+                None => continue,
+            };
+            let current = self.texture(buffer).map(Texture2d::dimensions);
+            if Some(buffer) == self.previous && current != Some(size) {
+                let options = self.previous_options();
+                let node = BufferNode::new(&self.context, size, options)?;
+                self.nodes[buffer.0] = Some(Box::new(node));
+            }
+            // unwrap: both were found above
+            self.texture(writer).or(passed).unwrap().as_surface().fill(
+                &self.texture(buffer).unwrap().as_surface(),
+                MagnifySamplerFilter::Linear,
+            );
+        }
+        Ok(())
+    }
+
+    /// Adds a node reading from a live input, updated every
+    /// forward pass. Sources of your own can be used from
+    /// the lisp by adding them in an [`ExternalFn`].
+    pub fn add_source(&mut self, source: Box<dyn InputSource>) -> NodeId {
+        self.add_node(Some(Box::new(SourceNode { source })))
+    }
+
+    /// Adds a node driven by a live data feed. The node's
+    /// texture starts out as a single black texel, and is
+    /// replaced whenever the feed sends an update.
+    #[cfg(feature = "feed")]
+    pub fn add_feed(
+        &mut self,
+        feed: crate::feed::Feed,
+        mapping: crate::feed::Mapping,
+    ) -> Result<NodeId, String> {
+        let texture = float_texture(&self.context, 1, 1, &[0.0])?;
+        Ok(self.add_source(Box::new(FeedSource {
+            context: self.context.clone(),
+            feed,
+            mapping,
+            texture,
+        })))
+    }
+
+    /// Adds a node that captures live audio from a device,
+    /// see [`crate::audio::Capture::start`], as a texture
+    /// laid out like a Shadertoy audio channel, held back
+    /// by `delay`.
+    pub fn add_audio(
+        &mut self,
+        device: Option<&str>,
+        delay: Duration,
+    ) -> Result<NodeId, String> {
+        let capture = Capture::start(device, delay)?;
+        let zeros = [0.0; 2 * audio::WIDTH];
+        let texture =
+            float_texture(&self.context, audio::WIDTH as u32, 2, &zeros)?;
+        Ok(self.add_source(Box::new(AudioSource {
+            capture,
+            analyser: Analyser::new(),
+            texture,
+        })))
+    }
+
+    /// Adds a node that streams a webcam, see
+    /// [`crate::camera::Camera::start`]. Each frame is
+    /// uploaded in full as soon as it arrives, or once it's
+    /// `delay` old.
+    pub fn add_camera(
+        &mut self,
+        device: &str,
+        width: u32,
+        height: u32,
+        fps: u32,
+        delay: Duration,
+    ) -> Result<NodeId, String> {
+        let (camera, frames) =
+            Camera::start(device, width, height, fps, delay)?;
+        let budget = (width * height * 3) as usize;
+        let texture = StreamingTexture::new(
+            &self.context,
+            width,
+            height,
+            frames,
+            budget,
+        )?;
+        Ok(self.add_source(Box::new(CameraSource { camera, texture })))
+    }
+
+    /// Adds a node that plays a video file, see
+    /// [`crate::video::Video::open`], scaled to `size` or
+    /// at its own size.
+    pub fn add_video(
+        &mut self,
+        path: &Path,
+        size: Option<(u32, u32)>,
+        playback: &Playback,
+    ) -> Result<NodeId, String> {
+        let (width, height) = match size {
+            Some(size) => size,
+            None => Video::probe(path)?,
+        };
+        let (video, frames) = Video::open(path, width, height, playback)?;
+        let budget = (width * height * 3) as usize;
+        let texture = StreamingTexture::new(
+            &self.context,
+            width,
+            height,
+            frames,
+            budget,
+        )?;
+        Ok(self.add_source(Box::new(VideoSource { video, texture })))
+    }
+
+    /// Adds a node that computes luminance statistics of
+    /// its input every frame, with a histogram of
+    /// `bins` bins as its output texture. The results
+    /// can be read back with [`ShaderGraph::stats`].
+    pub fn add_stats(
+        &mut self,
+        input: NodeId,
+        bins: u32,
+    ) -> Result<NodeId, String> {
+        let stats_node = StatsNode::new(&self.context, input, bins)?;
+        Ok(self.add_node(Some(Box::new(stats_node))))
+    }
+
+    /// Adds a node that tonemaps its input, adapting the
+    /// exposure over time so that the mean luminance
+    /// approaches `key`. `speed` controls how quickly it
+    /// adapts, per second.
+    pub fn add_auto_expose(
+        &mut self,
+        input: NodeId,
+        speed: f32,
+        key: f32,
+    ) -> Result<NodeId, String> {
+        let node = AutoExposeNode::new(&self.context, input, speed, key)?;
+        Ok(self.add_node(Some(Box::new(node))))
+    }
+
+    /// Adds a node that estimates the optical flow between
+    /// consecutive frames of its input, over a window of
+    /// the given radius.
+    pub fn add_flow(
+        &mut self,
+        input: NodeId,
+        window: u32,
+    ) -> Result<NodeId, String> {
+        let flow_node = FlowNode::new(&self.context, input, window)?;
+        Ok(self.add_node(Some(Box::new(flow_node))))
+    }
+
+    /// Adds a node that blends `input` with its own
+    /// history, reprojected along the per-pixel motion
+    /// in `velocity`, given in pixels per frame. If
+    /// `clamp` is set, history is limited to the colors
+    /// around each pixel to avoid ghosting.
+    pub fn add_reproject(
+        &mut self,
+        input: NodeId,
+        velocity: NodeId,
+        blend: f32,
+        clamp: bool,
+    ) -> Result<NodeId, String> {
+        let node =
+            ReprojectNode::new(&self.context, input, velocity, blend, clamp)?;
+        Ok(self.add_node(Some(Box::new(node))))
+    }
+
+    /// Adds a stable fluids simulation of the given size.
+    /// The first input, if any, adds force to the fluid,
+    /// and the second adds dye. `iterations` sets the
+    /// number of pressure solver passes per frame.
+    pub fn add_fluid(
+        &mut self,
+        inputs: Vec<NodeId>,
+        width: u32,
+        height: u32,
+        iterations: u32,
+        dissipation: f32,
+    ) -> Result<NodeId, String> {
+        let (width, height) = self.scaled(width, height)?;
+        let fluid_node = FluidNode::new(
+            &self.context,
+            inputs,
+            width,
+            height,
+            iterations,
+            dissipation,
+        )?;
+        Ok(self.add_node(Some(Box::new(fluid_node))))
     }
 
     /// Adds a compute node, which produces
@@ -183,20 +1588,747 @@ impl ShaderGraph {
             node.texture()?;
         }
 
-        if !self.outputs.contains(&id) {
-            self.outputs.push(id)
+        self.topology.mark_output(id);
+        Some(id)
+    }
+
+    /// Gives a node a name, so it can be looked up later,
+    /// e.g. to bypass it. Naming another node the same
+    /// moves the name to that node.
+    pub fn name_node(&mut self, id: NodeId, name: &str) {
+        self.topology.name_node(id, name);
+    }
+
+    /// Looks up a node by name.
+    pub fn node_id(&self, name: &str) -> Option<NodeId> {
+        self.topology.node_id(name)
+    }
+
+    /// All named nodes, ordered by name.
+    pub fn node_names(&self) -> impl Iterator<Item = (&str, NodeId)> {
+        self.topology.node_names()
+    }
+
+    /// Labels a node, e.g. `gaussian H`, to tell it apart
+    /// in the DOT export, debug groups, and profiles, where
+    /// nodes that weren't named are otherwise only shown by
+    /// id. Unlike names, labels can't be looked up, and
+    /// many nodes can share one.
+    pub fn label_node(&mut self, id: NodeId, label: &str) {
+        self.topology.label_node(id, label);
+    }
+
+    /// The label of a node, if it has one.
+    pub fn label_of(&self, id: NodeId) -> Option<&str> {
+        self.topology.label_of(id)
+    }
+
+    /// Enables or bypasses a named node. A bypassed node is
+    /// not run, and nodes that take it as an input receive
+    /// its first input instead. Only nodes with at least
+    /// one input can be bypassed.
+    pub fn set_node_enabled(
+        &mut self,
+        name: &str,
+        enabled: bool,
+    ) -> Result<(), String> {
+        let id = self
+            .node_id(name)
+            .ok_or_else(|| format!("No node is named `{}`", name))?;
+
+        if enabled {
+            self.bypassed.remove(&id);
+            self.stalled.remove(&id);
+            return Ok(());
+        }
+
+        match &self.nodes[id.0] {
+            Some(node) if !node.inputs().is_empty() => {
+                self.bypassed.insert(id);
+                Ok(())
+            },
+            _ => Err(format!("Node `{}` has no input to pass through", name)),
         }
+    }
 
-        Some(id)
+    /// Whether a named node is enabled, if it exists.
+    pub fn is_node_enabled(&self, name: &str) -> Option<bool> {
+        self.node_id(name).map(|id| !self.bypassed.contains(&id))
+    }
+
+    /// Routes a named node straight to every output, or
+    /// goes back to the regular outputs if `name` is
+    /// `None`. While a node is soloed, the nodes after it
+    /// are not run.
+    pub fn set_solo(&mut self, name: Option<&str>) -> Result<(), String> {
+        let name = match name {
+            Some(name) => name,
+            None => {
+                self.solo = None;
+                return Ok(());
+            },
+        };
+
+        let id = self
+            .node_id(name)
+            .ok_or_else(|| format!("No node is named `{}`", name))?;
+        if let Some(node) = &self.nodes[id.0] {
+            node.texture().ok_or_else(|| {
+                format!("Node `{}` has no texture to show", name)
+            })?;
+        }
+
+        self.solo = Some(id);
+        Ok(())
+    }
+
+    /// The node currently soloed, if any.
+    pub fn solo(&self) -> Option<NodeId> { self.solo }
+
+    /// Recompiles a single named node from an in-memory
+    /// fragment shader, without rebuilding the graph or
+    /// touching disk. The node keeps its inputs and
+    /// textures, so feedback and simulations carry on. If
+    /// the source does not compile, the old shader is kept.
+    pub fn patch_node_source(
+        &mut self,
+        name: &str,
+        source: &str,
+    ) -> Result<(), String> {
+        let id = self
+            .node_id(name)
+            .ok_or_else(|| format!("No node is named `{}`", name))?;
+        self.patch_node(id, source)
+    }
+
+    /// Like [`ShaderGraph::patch_node_source`], for a node
+    /// that may not be named.
+    pub fn patch_node(
+        &mut self,
+        id: NodeId,
+        source: &str,
+    ) -> Result<(), String> {
+        match self.nodes.get_mut(id.0) {
+            Some(Some(node)) => {
+                node.patch(&self.context, source)?;
+                // the new shader gets another chance
+                self.stalled.remove(&id);
+                if let Some((_, kept)) = self.sources.get_mut(&id) {
+                    *kept = source.to_string();
+                }
+                Ok(())
+            },
+            Some(None) => Err(format!("Node {} is an input", id.0)),
+            None => Err(format!("Node {} is not in the graph", id.0)),
+        }
+    }
+
+    /// The text of the shader a node is running, as it was
+    /// compiled, if it runs one.
+    pub fn source_of(&self, id: NodeId) -> Option<&str> {
+        self.sources.get(&id).map(|(_, source)| source.as_str())
+    }
+
+    /// A copy of the text of every shader running, for
+    /// recovering exactly what was running, e.g. after a
+    /// crash, once the files on disk have moved on. Files
+    /// are named after their node, or `node-<id>` if it has
+    /// no name, and init shaders get `.init` before their
+    /// extension.
+    pub fn sources(&self) -> Sources {
+        let file = |id: NodeId| match self.topology.name_of(id) {
+            Some(name) => name
+                .chars()
+                .map(|c| {
+                    if c.is_alphanumeric() || c == '-' {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect(),
+            None => format!("node-{}", id.0),
+        };
+        let mut files = vec![];
+        for (id, (extension, source)) in self.sources.iter() {
+            let name = format!("{}.{}", file(*id), extension);
+            files.push((name, source.clone()));
+        }
+        for (id, init) in self.inits.iter() {
+            if let Init::Shader(source) = init {
+                let name = format!("{}.init.frag", file(*id));
+                files.push((name, source.clone()));
+            }
+        }
+        Sources { files }
+    }
+
+    /// Writes the text of every shader running into a
+    /// directory, see [`ShaderGraph::sources`]. Returns the
+    /// number of files written.
+    pub fn dump_sources(&self, dir: impl AsRef<Path>) -> Result<usize, String> {
+        self.sources().write(dir)
+    }
+
+    /// The state textures of a named node, see
+    /// [`Node::state`]. Empty if it has none.
+    fn state_of(&self, name: &str) -> Vec<&Texture2d> {
+        match self.node_id(name).and_then(|id| self.nodes[id.0].as_ref()) {
+            Some(node) => node.state(),
+            None => vec![],
+        }
+    }
+
+    /// Copies the state of feedback nodes, like the last
+    /// frame of a recurrent shader, from an older graph, so
+    /// that simulations carry on after a rebuild. Nodes are
+    /// matched by name, and only copied if their textures
+    /// are the same size as before. Returns the number of
+    /// nodes copied.
+    pub fn carry_state(&mut self, old: &ShaderGraph) -> usize {
+        let mut carried = 0;
+        for (name, _) in self.topology.node_names() {
+            let (state, old_state) = (self.state_of(name), old.state_of(name));
+            let compatible = !state.is_empty()
+                && state.len() == old_state.len()
+                && state
+                    .iter()
+                    .zip(old_state.iter())
+                    .all(|(new, old)| new.dimensions() == old.dimensions());
+            if !compatible {
+                continue;
+            }
+            for (new, old) in state.iter().zip(old_state.iter()) {
+                old.as_surface()
+                    .fill(&new.as_surface(), MagnifySamplerFilter::Nearest);
+            }
+            carried += 1;
+        }
+        carried
+    }
+
+    /// Saves the state of every named feedback node to a
+    /// file, see [`ShaderGraph::carry_state`], so that a
+    /// long running simulation can be checkpointed and
+    /// resumed with [`ShaderGraph::load_state`].
+    /// Returns the number of nodes saved.
+    pub fn save_state(&self, path: impl AsRef<Path>) -> Result<usize, String> {
+        let nodes = self
+            .topology
+            .node_names()
+            .map(|(name, _)| NodeState {
+                name:     name.to_string(),
+                textures: self
+                    .state_of(name)
+                    .into_iter()
+                    .map(Image::read)
+                    .collect(),
+            })
+            .filter(|node| !node.textures.is_empty())
+            .collect::<Vec<_>>();
+        snapshot::write(path.as_ref(), &nodes)?;
+        Ok(nodes.len())
+    }
+
+    /// The nodes whose frames depend on frames before, see
+    /// [`ShaderGraph::has_feedback`], in a way that
+    /// [`ShaderGraph::save_state`] can't save, described
+    /// for messages: nodes without a name, nodes that
+    /// remember without state textures, like optical
+    /// flow or videos, and nodes with conditions, which
+    /// keep their last output. A graph without any is
+    /// the same after [`ShaderGraph::load_state`] as
+    /// when it was saved.
+    pub fn unsaved_state(&self) -> Vec<String> {
+        let mut unsaved = vec![];
+        for (index, node) in self.nodes.iter().enumerate() {
+            let id = NodeId(index);
+            let node = match node {
+                Some(node) if node.remembers() => node,
+                _ if self.conditions.contains_key(&id) => {
+                    unsaved.push(self.topology.describe(id));
+                    continue;
+                },
+                _ => continue,
+            };
+            let named = self.topology.name_of(id).is_some();
+            if !named
+                || node.state().is_empty()
+                || self.conditions.contains_key(&id)
+            {
+                unsaved.push(self.topology.describe(id));
+            }
+        }
+        unsaved
+    }
+
+    /// Restores state saved with
+    /// [`ShaderGraph::save_state`]. As when carrying
+    /// state over, nodes are matched by
+    /// name, and skipped if their textures have changed
+    /// size. Returns the number of nodes restored.
+    pub fn load_state(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<usize, String> {
+        let mut loaded = 0;
+        for node in snapshot::read(path.as_ref())? {
+            let state = self.state_of(&node.name);
+            let compatible = !state.is_empty()
+                && state.len() == node.textures.len()
+                && state.iter().zip(node.textures.iter()).all(|(t, image)| {
+                    t.dimensions() == (image.width, image.height)
+                });
+            if !compatible {
+                continue;
+            }
+            for (texture, image) in state.iter().zip(node.textures.iter()) {
+                image.write(texture);
+            }
+            loaded += 1;
+        }
+        Ok(loaded)
+    }
+
+    /// Passes a `u_<uniform>` float to a named node on
+    /// every forward pass, e.g. to tweak a shader while
+    /// it runs.
+    pub fn set_param(
+        &mut self,
+        node: &str,
+        uniform: &str,
+        value: f32,
+    ) -> Result<(), String> {
+        let id = self
+            .node_id(node)
+            .ok_or_else(|| format!("No node is named `{}`", node))?;
+        self.set_node_param(id, uniform, value);
+        Ok(())
+    }
+
+    /// As [`ShaderGraph::set_param`], for a node by id.
+    pub fn set_node_param(&mut self, id: NodeId, uniform: &str, value: f32) {
+        self.params.insert((id, uniform.to_string()), value);
+    }
+
+    /// Declares a `u_<name>` uniform that is passed to
+    /// every node, for the application running the
+    /// graph to set with [`ShaderGraph::set_uniform`],
+    /// e.g. to pass in the mouse position or game
+    /// state. It is zero until it is first set. Arrays
+    /// are passed by element and structs by field, so
+    /// GLSL can declare them as e.g. `uniform float
+    /// u_weights[16];`.
+    pub fn declare_uniform(
+        &mut self,
+        name: &str,
+        kind: UniformType,
+    ) -> Result<(), String> {
+        if uniform::BUILT_IN.contains(&name) {
+            return Err(format!(
+                "Cannot declare uniform `{}`, it is built in",
+                name
+            ));
+        }
+        kind.validate()
+            .map_err(|e| format!("Cannot declare uniform `{}`: {}", name, e))?;
+        match self.uniforms.get(name) {
+            Some(value) if value.matches(&kind) => (),
+            _ => {
+                self.uniforms.insert(name.to_string(), Uniform::zero(&kind));
+            },
+        }
+        Ok(())
+    }
+
+    /// Sets a uniform declared with
+    /// [`ShaderGraph::declare_uniform`], or with `uniform`
+    /// in the lisp. The value is kept until it is set
+    /// again, so uniforms that change every frame
+    /// should be set every frame.
+    pub fn set_uniform(
+        &mut self,
+        name: &str,
+        value: Uniform,
+    ) -> Result<(), String> {
+        let slot = self.uniforms.get_mut(name).ok_or_else(|| {
+            format!("No uniform named `{}` is declared", name)
+        })?;
+        if !value.matches(&slot.kind()) {
+            return Err(format!(
+                "Uniform `{}` is a {}, not a {}",
+                name,
+                slot.kind(),
+                value.kind()
+            ));
+        }
+        *slot = value;
+        Ok(())
+    }
+
+    /// Every declared uniform, with its current value,
+    /// ordered by name.
+    pub fn uniforms(&self) -> impl Iterator<Item = (&str, &Uniform)> {
+        self.uniforms
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
     }
 
-    fn time(created: Instant) -> f32 {
-        ((Instant::now() - created).as_millis() as f64 / 1000.0) as f32
+    /// Adds a palette, see [`ShaderGraph::set_palette`].
+    /// The first palette added is active until another
+    /// is set. Adding a palette with the same name
+    /// replaces it.
+    pub fn add_palette(&mut self, palette: Palette) {
+        match self.palettes.iter_mut().find(|p| p.name == palette.name) {
+            Some(old) => *old = palette,
+            None => self.palettes.push(palette),
+        }
+        let length = self.palettes.iter().map(|p| p.colors.len()).max();
+        for index in self.swatches.len()..length.unwrap_or(0) {
+            self.swatches.push(format!("palette[{}]", index));
+        }
+    }
+
+    /// Makes a palette the one passed to every node, as
+    /// `u_palette[0], .., u_palette[n]`, along with the
+    /// number of colors in it as `u_palette_size`.
+    /// Every palette is padded with its last color to
+    /// the length of the longest, so shaders can
+    /// declare `u_palette` with that length whichever
+    /// palette is active.
+    pub fn set_palette(&mut self, name: &str) -> Result<(), String> {
+        self.palette = self
+            .palettes
+            .iter()
+            .position(|palette| palette.name == name)
+            .ok_or_else(|| format!("No palette is named `{}`", name))?;
+        Ok(())
+    }
+
+    /// Switches to the palette after the active one,
+    /// wrapping around, and returns its name.
+    pub fn next_palette(&mut self) -> Option<&str> {
+        if self.palettes.is_empty() {
+            return None;
+        }
+        self.palette = (self.palette + 1) % self.palettes.len();
+        self.palette()
+    }
+
+    /// The name of the active palette, if the graph has
+    /// any.
+    pub fn palette(&self) -> Option<&str> {
+        self.palettes.get(self.palette).map(|p| p.name.as_str())
+    }
+
+    /// Adds a knob, a float uniform declared with
+    /// [`ShaderGraph::declare_uniform`] that is set from a
+    /// MIDI or OSC controller, see [`crate::knobs::Knobs`].
+    /// The uniform starts at the knob's default value.
+    pub fn add_knob(&mut self, knob: Knob) -> Result<(), String> {
+        self.set_uniform(&knob.uniform, Uniform::Float(knob.default))
+            .map_err(|e| format!("Cannot add knob: {}", e))?;
+        self.knobs.push(knob);
+        Ok(())
+    }
+
+    /// Every knob added, see [`ShaderGraph::add_knob`].
+    pub fn knobs(&self) -> &[Knob] { &self.knobs }
+
+    /// Asks for a node to be shown in a window of its own,
+    /// alongside the output. The graph only keeps track of
+    /// the windows; the application running it opens them,
+    /// and draws [`ShaderGraph::texture`] of each node.
+    pub fn add_window(&mut self, window: OutputWindow) -> Result<(), String> {
+        if window.node.0 >= self.nodes.len() {
+            return Err("Cannot show a node not in the graph".to_string());
+        }
+        self.windows.push(window);
+        Ok(())
+    }
+
+    /// Every window asked for, see
+    /// [`ShaderGraph::add_window`].
+    pub fn windows(&self) -> &[OutputWindow] { &self.windows }
+
+    /// Credits the graph, e.g. to tag what's exported from
+    /// it, see [`Meta`].
+    pub fn set_meta(&mut self, meta: Meta) { self.meta = meta; }
+
+    pub fn meta(&self) -> &Meta { &self.meta }
+
+    /// Runs the whole graph `frames` extra times before the
+    /// next forward pass, so that simulations show up
+    /// already evolved rather than starting from nothing.
+    pub fn warm_up(&mut self, frames: u32) { self.warmup = frames; }
+
+    /// Like [`ShaderGraph::warm_up`], but runs just one
+    /// node extra times, e.g. a slow feedback loop in a
+    /// graph that is otherwise quick to settle.
+    pub fn warm_up_node(&mut self, id: NodeId, frames: u32) {
+        self.warmups.insert(id, frames);
+    }
+
+    /// Runs a node `substeps` times per frame rather than
+    /// once, to keep stiff simulations stable without
+    /// raising the frame rate. Each step receives its share
+    /// of the frame as `u_dt`, in seconds, and as `u_step`,
+    /// as a fraction of the frame.
+    pub fn set_substeps(&mut self, id: NodeId, substeps: u32) {
+        self.substeps.insert(id, substeps.max(1));
+    }
+
+    /// Only runs a node while `condition` holds, to save
+    /// the GPU time of effects that are inactive, e.g.
+    /// one that reacts to loud audio. While it doesn't
+    /// hold, the node keeps its last output. Conditions
+    /// are tested once per forward pass, before any
+    /// node runs, so uniforms from nodes, like
+    /// `u_stats_mean`, are as the last pass left
+    /// them. Errors if a uniform the condition reads isn't
+    /// declared, built in, or passed on by any node.
+    pub fn set_condition(
+        &mut self,
+        id: NodeId,
+        condition: Condition,
+    ) -> Result<(), String> {
+        for name in condition.uniforms() {
+            if self.float_uniform(name).is_none() {
+                return Err(format!(
+                    "The condition of `{}` reads `u_{}`, which is not a \
+                     float uniform of the graph",
+                    self.topology.describe(id),
+                    name
+                ));
+            }
+        }
+        self.conditions.insert(id, condition);
+        Ok(())
+    }
+
+    /// The value of a float or int uniform, by name without
+    /// `u_`: a declared one, one that is built in, or one a
+    /// node passes on, as conditions read them.
+    pub fn float_uniform(&self, name: &str) -> Option<f32> {
+        let value = |value: &UniformValue| match value {
+            UniformValue::Float(x) => Some(*x),
+            UniformValue::SignedInt(n) => Some(*n as f32),
+            _ => None,
+        };
+        match name {
+            "time" => return Some(self.time()),
+            "dt" => return Some(self.dt),
+            "frame" => return Some(self.frame as f32),
+            "quality" => return Some(self.quality),
+            _ => (),
+        }
+        match self.uniforms.get(name) {
+            Some(Uniform::Float(x)) => return Some(*x),
+            Some(Uniform::Int(n)) => return Some(*n as f32),
+            Some(_) => return None,
+            None => (),
+        }
+        self.nodes.iter().flatten().find_map(|node| {
+            let uniforms = node.uniforms();
+            uniforms
+                .iter()
+                .find(|(n, _)| n == name)
+                .and_then(|(_, v)| value(v))
+        })
+    }
+
+    /// Adds the nodes whose conditions don't hold this pass
+    /// to `skipped`, in order.
+    fn skip(&self, skipped: &mut Vec<NodeId>) {
+        let lookup = |name: &str| self.float_uniform(name);
+        let skip = self
+            .conditions
+            .iter()
+            .filter(|(_, condition)| !condition.eval(&lookup))
+            .map(|(id, _)| *id);
+        skipped.extend(skip);
+    }
+
+    /// The order nodes are run in during a forward pass.
+    /// Nodes are run in the order they were added, except
+    /// where [`ShaderGraph::order_before`] moves them, so
+    /// the order is the same from frame to frame and from
+    /// machine to machine.
+    pub fn evaluation_order(&self) -> &[NodeId] {
+        self.topology.evaluation_order()
+    }
+
+    /// Makes sure `first` is run before `second`, for nodes
+    /// whose order matters even though neither is an input
+    /// of the other, like nodes with side effects or
+    /// feedback. Errors if this would form a cycle.
+    pub fn order_before(
+        &mut self,
+        first: NodeId,
+        second: NodeId,
+    ) -> Result<(), String> {
+        self.topology.order_before(first, second)
+    }
+
+    /// Checks the output of every node for NaN and infinite
+    /// pixels after each forward pass, logging the first
+    /// one found in each node. This is slow, and meant for
+    /// debugging.
+    pub fn set_validation(&mut self, enabled: bool) -> Result<(), String> {
+        self.validator = if enabled {
+            Some(Validator::new(&self.context)?)
+        } else {
+            None
+        };
+        Ok(())
+    }
+
+    /// Times how long the GPU spends on every node, with
+    /// timer queries, see [`ShaderGraph::timings`]. This
+    /// needs the timer functions to be loaded first, with
+    /// [`crate::util::timer::load_with`]. Enabling it again
+    /// keeps the timings so far.
+    pub fn set_profiling(&mut self, enabled: bool) -> Result<(), String> {
+        match (enabled, &self.profiler) {
+            (true, None) => self.profiler = Some(Profiler::new(&self.context)?),
+            (false, Some(_)) => self.profiler = None,
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// How long the GPU spent on each node in the latest
+    /// forward pass measured, if profiling. Results arrive
+    /// a few passes late, so as not to wait on the GPU.
+    pub fn timings(&self) -> Option<&Timings> {
+        self.profiler.as_ref()?.timings()
+    }
+
+    /// Stops the slowest node whenever a frame takes more
+    /// than `timeout` of GPU time, e.g. a runaway
+    /// raymarching loop, before it freezes the machine
+    /// or the driver resets the GPU. A stopped node
+    /// keeps its last output until the graph is
+    /// rebuilt, its shader is patched, or it's enabled
+    /// again with [`ShaderGraph::set_node_enabled`].
+    /// Frames are timed
+    /// with [`ShaderGraph::set_profiling`], which this
+    /// turns on, so they are caught a few frames late.
+    pub fn set_gpu_timeout(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        if timeout.is_some() {
+            self.set_profiling(true)?;
+        }
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    /// Nodes stopped for taking too long, see
+    /// [`ShaderGraph::set_gpu_timeout`].
+    pub fn stalled(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.stalled.iter().copied()
+    }
+
+    /// Stops the slowest node of the latest frame measured,
+    /// if the frame went over the timeout.
+    fn check_timeout(&mut self) {
+        let (timeout, timings) = match (self.timeout, self.timings()) {
+            (Some(timeout), Some(timings)) => (timeout, timings),
+            _ => return,
+        };
+        let total = timings.total();
+        // frames from before the last node was stopped
+        // still include it
+        let measured = self.stall.is_none_or(|at| timings.frame > at);
+        if !measured || total <= timeout {
+            return;
+        }
+        let slowest = timings
+            .nodes
+            .iter()
+            .filter(|(id, _)| !self.stalled.contains(id))
+            .max_by_key(|(_, time)| **time);
+        let (id, time) = match slowest {
+            Some((id, time)) => (*id, *time),
+            None => return,
+        };
+
+        let node = match self.topology.name_of(id) {
+            Some(name) => format!("`{}`", name),
+            None => format!("node {}", id.0),
+        };
+        eprintln!(
+            "[warn] Frame {} took {:.1} ms on the GPU, over the limit of \
+             {:.1} ms",
+            timings.frame,
+            total.as_secs_f64() * 1000.0,
+            timeout.as_secs_f64() * 1000.0
+        );
+        eprintln!(
+            "[warn] Stopped {}, which took {:.1} ms",
+            node,
+            time.as_secs_f64() * 1000.0
+        );
+        self.stalled.insert(id);
+        self.stall = Some(self.frame);
+    }
+
+    /// Looks for mistakes in how the graph is wired, like
+    /// nodes no output uses, shaders reading inputs they
+    /// weren't given, and inputs of a different size than
+    /// the node reading them. Sizes are read from the
+    /// nodes' textures, so this works on built graphs.
+    pub fn validate(&self) -> Vec<Issue> { lint::validate(self) }
+
+    /// Describes the graph in GraphViz's DOT language, with
+    /// each node's name and size, and the format of the
+    /// texture passed along each edge. Graph inputs are
+    /// drawn as trapezoids, outputs with a double border,
+    /// and ordering hints as dotted edges. Render it with
+    /// e.g. `dot -Tsvg graph.dot -o graph.svg`.
+    pub fn to_dot(&self) -> String { lint::to_dot(self) }
+
+    /// Returns the statistics a node computed during the
+    /// last forward pass, if it computes any.
+    pub fn stats(&self, id: NodeId) -> Option<&Stats> {
+        self.nodes.get(id.0)?.as_ref()?.stats()
+    }
+
+    /// Returns the texture a node rendered in the last
+    /// forward pass, if it renders one. Inputs have none.
+    pub fn texture(&self, id: NodeId) -> Option<&Texture2d> {
+        self.nodes.get(id.0)?.as_ref()?.texture()
+    }
+
+    /// See [`ShaderGraph::set_palette`].
+    /// Adds the colors of a palette, padded to as many as
+    /// there are `swatches`, see
+    /// [`ShaderGraph::set_palette`].
+    fn add_palette_uniforms(
+        uniforms: &mut UniformMap,
+        palette: &Palette,
+        swatches: &[String],
+    ) {
+        for (index, name) in swatches.iter().enumerate() {
+            // unwrap: palettes always have a color
+            let color = palette
+                .colors
+                .get(index)
+                .or_else(|| palette.colors.last())
+                .unwrap();
+            uniforms.add(name, UniformValue::Vec4(*color));
+        }
+        let size = palette.colors.len() as i32;
+        uniforms.add("palette_size", UniformValue::SignedInt(size));
     }
 
     fn build_inputs<'a>(
         mut uniforms: UniformMap<'a>,
         previous: &'a [Option<Box<dyn Node>>],
+        topology: &Topology,
+        bypassed: &BTreeSet<NodeId>,
         inputs: &'a [NodeId],
         input_map: &'a BTreeMap<NodeId, &'a Texture2d>,
     ) -> UniformMap<'a> {
@@ -204,10 +2336,14 @@ impl ShaderGraph {
         // let mut uniforms = UniformMap::new();
 
         for input in inputs.iter() {
+            let input = &topology.resolve(bypassed, *input);
             match &previous[input.0] {
                 Some(node) => {
                     let (kind, uniform_value) = node.outputs();
                     uniforms.add(kind, uniform_value);
+                    for (name, uniform_value) in node.uniforms() {
+                        uniforms.add(&name, uniform_value);
+                    }
                 },
                 None => {
                     uniforms
@@ -219,7 +2355,9 @@ impl ShaderGraph {
         uniforms
     }
 
-    fn pull_outputs<'a>(
+    /// Returns the output textures from the last forward
+    /// pass, without running the graph again.
+    pub fn pull_outputs<'a>(
         &'a self,
         input_map: BTreeMap<NodeId, &'a Texture2d>,
     ) -> BTreeMap<NodeId, &'a Texture2d> {
@@ -227,11 +2365,13 @@ impl ShaderGraph {
         // note that an input is fair game to be pulled as an output
         // texture
         let mut output_map = BTreeMap::new();
-        for id in self.outputs.iter() {
-            let texture = match &self.nodes[id.0] {
+        for id in self.topology.get_outputs().iter() {
+            let shown = self.solo.unwrap_or(*id);
+            let resolved = self.topology.resolve(&self.bypassed, shown);
+            let texture = match &self.nodes[resolved.0] {
                 // unwrap: checked before insertion
                 Some(node) => node.texture().unwrap(),
-                None => input_map[id],
+                None => input_map[&resolved],
             };
             output_map.insert(*id, texture);
         }
@@ -256,31 +2396,328 @@ impl ShaderGraph {
         // ensure all input textures have been passed in
         // use btreemap because `inputs` is small
         // and node ids are cheap to compare
-        for input in self.inputs.iter() {
-            assert!(input_map.contains_key(input));
+        for input in self.topology.get_inputs().iter() {
+            assert!(
+                input_map.contains_key(input)
+                    || self.uploads.contains_key(input)
+            );
+        }
+
+        let now = Instant::now();
+        let last = self.last_frame.replace(now);
+        self.dt = match (self.fixed_time, self.step.take(), last) {
+            (Some((_, dt)), ..) => dt,
+            (None, Some(dt), _) => dt,
+            _ if self.clock.is_paused() => 0.0,
+            (None, None, Some(last)) => {
+                let dt = (now - last).as_secs_f32().min(MAX_DT);
+                dt * self.clock.speed() as f32
+            },
+            (None, None, None) => FIRST_DT * self.clock.speed() as f32,
+        };
+
+        // uploaded inputs stand in for any not passed
+        let uploads = std::mem::take(&mut self.uploads);
+        let mut inputs = input_map.clone();
+        for (id, (_, texture)) in uploads.iter() {
+            inputs.entry(*id).or_insert(texture);
+        }
+
+        // see [`ShaderGraph::warm_up`]
+        for _ in 0..std::mem::take(&mut self.warmup) {
+            self.run(&inputs);
+        }
+        self.run(&inputs);
+        drop(inputs);
+        self.uploads = uploads;
+
+        // pulls and returns all the output textures
+        let graph: &'a ShaderGraph = self;
+        let mut input_map = input_map;
+        for (id, (_, texture)) in graph.uploads.iter() {
+            input_map.entry(*id).or_insert(texture);
+        }
+        graph.pull_outputs(input_map)
+    }
+
+    /// Does one forward pass as the frame numbered `frame`
+    /// of an offline render, at `time` seconds, so that a
+    /// render farm can split a render between workers and
+    /// get the same frames it would in one go: graphs
+    /// without feedback frame by frame, and graphs with it
+    /// in ranges, each starting from frame 0. `u_time` is
+    /// `time`, `u_frame` is `frame`, and `u_dt` is `time`
+    /// over `frame`, the length of a frame when frames are
+    /// evenly spaced from zero, or a 60th of a second on
+    /// frame 0.
+    /// Feedback advances exactly once, warm-ups included
+    /// only on frame 0, where they would run in one go.
+    /// Later passes stay at `time`, as with
+    /// [`ShaderGraph::set_fixed_time`].
+    pub fn render_at<'a>(
+        &'a mut self,
+        time: f64,
+        frame: u64,
+        input_map: BTreeMap<NodeId, &'a Texture2d>,
+    ) -> BTreeMap<NodeId, &'a Texture2d> {
+        let dt = match frame {
+            0 => FIRST_DT,
+            _ => (time / frame as f64) as f32,
+        };
+        if frame > 0 {
+            self.warmup = 0;
+            self.warmups.clear();
+        }
+        self.fixed_time = Some((time as f32, dt));
+        // wrapping, as it does counting up
+        self.frame = frame as i32;
+        self.forward(input_map)
+    }
+
+    /// Uploads pixels from the CPU to a named input, which
+    /// is then used by every forward pass it isn't passed
+    /// to, so that hosts don't need to make textures
+    /// themselves. Pixels are given row by row from the
+    /// top, as image libraries store them. Uploading the
+    /// same size and format again reuses the texture.
+    pub fn upload_input(
+        &mut self,
+        name: &str,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+        format: PixelFormat,
+    ) -> Result<(), String> {
+        let id = self
+            .node_id(name)
+            .filter(|id| self.topology.get_inputs().contains(id))
+            .ok_or_else(|| format!("No input is named `{}`", name))?;
+        let old = self.uploads.remove(&id);
+        let upload = texture::upload_pixels(
+            &self.context,
+            old,
+            pixels,
+            width,
+            height,
+            format,
+        )?;
+        self.uploads.insert(id, upload);
+        Ok(())
+    }
+
+    /// The texture of a named node from the last forward
+    /// pass, or what was uploaded to a named input.
+    fn named_texture(&self, name: &str) -> Result<&Texture2d, String> {
+        let id = self
+            .node_id(name)
+            .ok_or_else(|| format!("No node is named `{}`", name))?;
+        match &self.nodes[id.0] {
+            Some(node) => node.texture(),
+            None => self.uploads.get(&id).map(|(_, texture)| texture),
+        }
+        .ok_or_else(|| format!("`{}` has no texture to read", name))
+    }
+
+    /// Reads a named node back to the CPU as 8-bit RGBA,
+    /// as of the last forward pass, row by row from the
+    /// top. Reading waits for the GPU to finish, so is best
+    /// kept out of the render loop.
+    pub fn read_output(&self, name: &str) -> Result<RgbaImage, String> {
+        let texture = self.named_texture(name)?;
+        let rows: Vec<Vec<(u8, u8, u8, u8)>> = texture.read();
+        let pixels = rows
+            .iter()
+            .rev()
+            .flatten()
+            .flat_map(|&(r, g, b, a)| [r, g, b, a])
+            .collect();
+        // unwrap: read as many pixels as the texture has
+        Ok(
+            ImageBuffer::from_raw(texture.width(), texture.height(), pixels)
+                .unwrap(),
+        )
+    }
+
+    /// Like [`ShaderGraph::read_output`], but as 32-bit
+    /// floats, for nodes storing values outside of 0 to 1.
+    pub fn read_output_f32(
+        &self,
+        name: &str,
+    ) -> Result<ImageBuffer<Rgba<f32>, Vec<f32>>, String> {
+        let texture = self.named_texture(name)?;
+        let pixels = read_texture(texture);
+        let width = texture.width() as usize;
+        let pixels = pixels
+            .chunks_exact(width.max(1))
+            .rev()
+            .flatten()
+            .flatten()
+            .copied()
+            .collect();
+        // unwrap: read as many pixels as the texture has
+        Ok(
+            ImageBuffer::from_raw(texture.width(), texture.height(), pixels)
+                .unwrap(),
+        )
+    }
+
+    /// Runs every node once, see [`ShaderGraph::forward`].
+    fn run(&mut self, input_map: &BTreeMap<NodeId, &Texture2d>) {
+        // see [`ShaderGraph::set_condition`]
+        let mut skipped = std::mem::take(&mut self.skipped);
+        self.skip(&mut skipped);
+        // see [`ShaderGraph::cache_node`]
+        let mut ran_cached = std::mem::take(&mut self.ran_cached);
+
+        // uniforms that are the same for every node are only
+        // gathered once, which matters in large graphs
+        let mut shared = UniformMap::new();
+        shared.add("time", UniformValue::Float(self.time()));
+        shared.add("quality", self.quality.as_uniform_value());
+        shared.add("canvas", UniformValue::Vec2(self.canvas));
+        shared.add("tile", UniformValue::Vec4(self.tile));
+        let output = self.topology.get_outputs().first();
+        if let Some(texture) = output.and_then(|id| self.texture(*id)) {
+            let (width, height) = texture.dimensions();
+            self.output_size = [width as f32, height as f32];
+        }
+        shared.add("output_resolution", UniformValue::Vec2(self.output_size));
+        // the top 24 bits, as many as a float holds
+        let seed = (self.build_seed >> 8) as f32 / (1 << 24) as f32;
+        shared.add("build_seed", UniformValue::Float(seed));
+        shared.add("frame", UniformValue::SignedInt(self.frame));
+        shared.add("mouse", UniformValue::Vec4(self.mouse));
+        if let Some((_, texture)) = &self.keyboard {
+            let sampler = SamplerBehavior {
+                minify_filter: MinifySamplerFilter::Nearest,
+                magnify_filter: MagnifySamplerFilter::Nearest,
+                ..Default::default()
+            };
+            let texture = UniformValue::Texture2d(texture, Some(sampler));
+            shared.add("keyboard", texture);
+        }
+        for (name, value) in self.uniforms.iter() {
+            value.visit(name, &mut |name, value| {
+                shared.add(name, value);
+            });
         }
+        if let Some(palette) = self.palettes.get(self.palette) {
+            Self::add_palette_uniforms(&mut shared, palette, &self.swatches);
+        }
+
+        let mut solo_reached = false;
+        for position in 0..self.topology.len() {
+            // nothing after a soloed node can affect it
+            if solo_reached {
+                break;
+            }
+
+            let NodeId(split_index) =
+                self.topology.evaluation_order()[position];
+            solo_reached = self.solo == Some(NodeId(split_index));
 
-        for split_index in 0..self.nodes.len() {
             // this is a DAG, so we can only ever reference
             // previous nodes from the current one
             // we split here so we can have multiple mutible borrows.
             let (previous, current) = self.nodes.split_at_mut(split_index);
 
+            if self.bypassed.contains(&NodeId(split_index))
+                || self.stalled.contains(&NodeId(split_index))
+                || skipped.binary_search(&NodeId(split_index)).is_ok()
+                || self.settled.contains(&NodeId(split_index))
+            {
+                continue;
+            }
+            // see [`ShaderGraph::warm_up_node`], kept for
+            // nodes that don't run until they do
+            let extra = self.warmups.remove(&NodeId(split_index));
+
             if let Some(ref mut node) = current[0] {
-                let mut uniforms = UniformMap::new();
-                let time = Self::time(self.created);
-                uniforms.add("time", time.as_uniform_value());
+                let id = NodeId(split_index);
+                let name = self.topology.name_of(id);
+                // see [`ShaderGraph::set_substeps`]
+                let substeps = self.substeps.get(&id).copied().unwrap_or(1);
+                let step = 1.0 / substeps as f32;
+                for _ in 0..(1 + extra.unwrap_or(0)) * substeps {
+                    let mut uniforms = UniformMap::over(&shared);
+                    uniforms.add("dt", UniformValue::Float(self.dt * step));
+                    uniforms.add("step", UniformValue::Float(step));
+                    // params are ordered by node, so only this
+                    // node's are looked at
+                    let params = self.params.range((id, String::new())..);
+                    for ((_, uniform), value) in
+                        params.take_while(|((node, _), _)| *node == id)
+                    {
+                        uniforms.add(uniform, value.as_uniform_value());
+                    }
 
-                let inputs = node.inputs();
-                let uniforms = Self::build_inputs(
-                    uniforms, &*previous, &inputs, &input_map,
-                );
+                    let uniforms = Self::build_inputs(
+                        uniforms,
+                        &*previous,
+                        &self.topology,
+                        &self.bypassed,
+                        self.topology.node_inputs(id),
+                        input_map,
+                    );
+
+                    if let Some(profiler) = &mut self.profiler {
+                        profiler.begin(id);
+                    }
+                    if !debug::is_loaded() {
+                        node.forward(&self.rect_strip, uniforms);
+                    } else {
+                        let label = match name {
+                            Some(name) => {
+                                format!("{} (node {})", name, split_index)
+                            },
+                            None => format!("node {}", split_index),
+                        };
+                        let label = match self.topology.label_of(id) {
+                            Some(text) => format!("{}: {}", label, text),
+                            None => label,
+                        };
+                        debug::push_group(&self.context, &label);
+                        node.forward(&self.rect_strip, uniforms);
+                        debug::pop_group(&self.context);
 
-                node.forward(&self.rect_strip, uniforms);
+                        // textures can be swapped or rebuilt at any
+                        // time, so they are labeled after every pass
+                        let context = &self.context;
+                        if let Some(texture) = node.texture() {
+                            debug::label_texture(context, texture, &label);
+                        }
+                        if let Some(program) = node.program() {
+                            debug::label_program(context, program, &label);
+                        }
+                    }
+                    if let Some(profiler) = &mut self.profiler {
+                        profiler.end();
+                    }
+                }
+
+                if let (Some(validator), Some(texture)) =
+                    (&mut self.validator, node.texture())
+                {
+                    validator.check(id, name, texture);
+                }
+                if self.caching.contains_key(&id) {
+                    ran_cached.push(id);
+                }
             }
         }
-
-        // pulls and returns all the output textures
-        self.pull_outputs(input_map)
+        drop(shared);
+        self.store_cached(&ran_cached);
+        ran_cached.clear();
+        self.ran_cached = ran_cached;
+        skipped.clear();
+        self.skipped = skipped;
+        if let Some(profiler) = &mut self.profiler {
+            profiler.end_frame(self.frame);
+        }
+        if let Err(e) = self.write_buffers(input_map) {
+            eprintln!("[warn] {}", e);
+        }
+        self.check_timeout();
+        self.frame = self.frame.wrapping_add(1);
     }
 }
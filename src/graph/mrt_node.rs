@@ -0,0 +1,173 @@
+use std::rc::Rc;
+
+use glium::{
+    backend::Context,
+    framebuffer::MultiOutputFrameBuffer,
+    uniforms::{
+        AsUniformValue,
+        UniformValue,
+    },
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::{
+    graph::{
+        node::Node,
+        NodeId,
+        UniformMap,
+    },
+    util::{
+        compile_shader,
+        default_buffer,
+        RectStrip,
+    },
+};
+
+/// The name of the fragment shader output written to each
+/// attachment, `color_0, .., color_n`.
+fn output_name(index: usize) -> String { format!("color_{}", index) }
+
+/// Compiles a shader, checking that it writes every one of
+/// `count` outputs, as glium panics when drawing to an
+/// output the shader doesn't declare.
+fn compile(
+    context: &Rc<Context>,
+    source: &str,
+    count: usize,
+) -> Result<Program, String> {
+    let shader = compile_shader(context, source)?;
+    for index in 0..count {
+        if shader.get_frag_data_location(&output_name(index)).is_none() {
+            return Err(format!(
+                "The shader renders to {} textures, but doesn't declare \
+                 `out vec4 {};`",
+                count,
+                output_name(index)
+            ));
+        }
+    }
+    Ok(shader)
+}
+
+/// Runs a fragment shader once per frame, rendering to
+/// several textures at once, for when a pass computes more
+/// than fits in one. The shader writes `color_0`,
+/// `color_1`, and so on. The first attachment is this
+/// node's output, and the rest are picked out by
+/// [`AttachmentNode`]s.
+pub struct MrtNode {
+    pub context:     Rc<Context>,
+    pub shader:      Program,
+    pub inputs:      Vec<NodeId>,
+    /// Shared with the attachment nodes reading them.
+    pub attachments: Vec<Rc<Texture2d>>,
+}
+
+impl std::fmt::Debug for MrtNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MrtNode")
+            .field("inputs", &self.inputs)
+            .field("attachments", &self.attachments.len())
+            .finish()
+    }
+}
+
+impl MrtNode {
+    pub fn new(
+        context: &Rc<Context>,
+        source: &str,
+        inputs: Vec<NodeId>,
+        (width, height): (u32, u32),
+        count: usize,
+    ) -> Result<MrtNode, String> {
+        Ok(MrtNode {
+            context: context.clone(),
+            shader: compile(context, source, count)?,
+            inputs,
+            attachments: (0..count)
+                .map(|_| Rc::new(default_buffer(context, width, height)))
+                .collect(),
+        })
+    }
+}
+
+impl Node for MrtNode {
+    fn inputs(&self) -> Vec<NodeId> { self.inputs.to_owned() }
+
+    fn outputs(&self) -> (&str, UniformValue<'_>) {
+        ("texture", self.attachments[0].as_uniform_value())
+    }
+
+    fn texture(&self) -> Option<&Texture2d> { Some(&self.attachments[0]) }
+
+    fn program(&self) -> Option<&Program> { Some(&self.shader) }
+
+    fn attachment(&self, index: usize) -> Option<Rc<Texture2d>> {
+        self.attachments.get(index).cloned()
+    }
+
+    fn patch(
+        &mut self,
+        context: &Rc<Context>,
+        source: &str,
+    ) -> Result<(), String> {
+        self.shader = compile(context, source, self.attachments.len())?;
+        Ok(())
+    }
+
+    fn forward(&mut self, rect_strip: &RectStrip, uniforms: UniformMap) {
+        let (width, height) = self.attachments[0].dimensions();
+        let mut uniforms = uniforms;
+        uniforms.add_resolution([width as f32, height as f32]);
+
+        let names = (0..self.attachments.len())
+            .map(output_name)
+            .collect::<Vec<_>>();
+        let outputs = names
+            .iter()
+            .map(String::as_str)
+            .zip(self.attachments.iter().map(|texture| &**texture));
+        let mut framebuffer =
+            MultiOutputFrameBuffer::new(&self.context, outputs).unwrap();
+        framebuffer
+            .draw(
+                &rect_strip.buffer,
+                rect_strip.indices,
+                &self.shader,
+                &uniforms,
+                &Default::default(),
+            )
+            .unwrap();
+    }
+}
+
+/// One of the textures an [`MrtNode`] renders to, so that
+/// it can be used like any other node. It takes the
+/// [`MrtNode`] as its only input, so that it is always
+/// evaluated after it.
+pub struct AttachmentNode {
+    pub input:   NodeId,
+    pub texture: Rc<Texture2d>,
+}
+
+impl std::fmt::Debug for AttachmentNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AttachmentNode")
+            .field("input", &self.input)
+            .finish()
+    }
+}
+
+impl Node for AttachmentNode {
+    fn inputs(&self) -> Vec<NodeId> { vec![self.input] }
+
+    fn outputs(&self) -> (&str, UniformValue<'_>) {
+        ("texture", self.texture.as_uniform_value())
+    }
+
+    fn texture(&self) -> Option<&Texture2d> { Some(&self.texture) }
+
+    fn forward(&mut self, _rect_strip: &RectStrip, _uniforms: UniformMap) {}
+}
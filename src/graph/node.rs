@@ -1,11 +1,16 @@
+use std::rc::Rc;
+
 use glium::{
+    backend::Context,
     uniforms::UniformValue,
+    Program,
     Texture2d,
 };
 
 use crate::{
     graph::{
         NodeId,
+        Stats,
         UniformMap,
     },
     util::RectStrip,
@@ -19,7 +24,11 @@ pub trait Node: std::fmt::Debug {
     fn inputs(&self) -> Vec<NodeId>;
 
     /// Returns (kind, uniforms) tuple.
-    fn outputs(&self) -> (&str, UniformValue);
+    fn outputs(&self) -> (&str, UniformValue<'_>);
+
+    /// Extra named uniforms passed to every node that takes
+    /// this node as an input, e.g. values from a data feed.
+    fn uniforms(&self) -> Vec<(String, UniformValue<'_>)> { vec![] }
 
     // TODO: remove texture in favor of `outputs`?
     /// Denotes whether the node produces an output texture.
@@ -28,6 +37,42 @@ pub trait Node: std::fmt::Debug {
     /// `Some`.
     fn texture(&self) -> Option<&Texture2d>;
 
+    /// The main program this node runs, if it has one, so
+    /// that it can be labeled for debugging tools.
+    fn program(&self) -> Option<&Program> { None }
+
+    /// Recompiles the main program from a new fragment
+    /// shader source, keeping the node's textures. See
+    /// [`crate::graph::ShaderGraph::patch_node_source`].
+    fn patch(
+        &mut self,
+        _context: &Rc<Context>,
+        _source: &str,
+    ) -> Result<(), String> {
+        Err("Only shader nodes can be patched".to_string())
+    }
+
+    /// Textures that carry over from one frame to the next,
+    /// like the last frame of a feedback shader, so they
+    /// can be kept when the graph is rebuilt. See
+    /// [`crate::graph::ShaderGraph::carry_state`].
+    fn state(&self) -> Vec<&Texture2d> { vec![] }
+
+    /// Whether the node's output depends on frames before
+    /// the one it's running, as it does for every node with
+    /// [`Node::state`]. See
+    /// [`crate::graph::ShaderGraph::has_feedback`].
+    fn remembers(&self) -> bool { !self.state().is_empty() }
+
+    /// Statistics computed by this node during the last
+    /// forward pass, if it computes any.
+    fn stats(&self) -> Option<&Stats> { None }
+
+    /// One of the extra textures this node renders to, for
+    /// nodes with several outputs. See
+    /// [`crate::graph::ShaderGraph::add_attachment`].
+    fn attachment(&self, _index: usize) -> Option<Rc<Texture2d>> { None }
+
     // TODO: should I pass a rect strip or a context?
     // I can build a rect strip from a context, but that takes
     // time. Is the performance hit worth the generalized
@@ -0,0 +1,1591 @@
+use std::{
+    borrow::Cow,
+    collections::{
+        BTreeMap,
+        BTreeSet,
+    },
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use sha1::{
+    Digest,
+    Sha1,
+};
+
+use crate::{
+    capabilities::{
+        GlslVersion,
+        Requirements,
+    },
+    data::DataTable,
+    diagnostic::{
+        in_node,
+        in_shader,
+    },
+    graph::{
+        Baked,
+        CachedOutput,
+        Condition,
+        NodeId,
+        OutputWindow,
+        Profile,
+        RandomTexture,
+        ShaderGraph,
+        TextureOptions,
+        Topology,
+        UniformType,
+    },
+    include::line_directive,
+    knobs::Knob,
+    meta::Meta,
+    palette::Palette,
+    points::Point,
+    video::Playback,
+};
+
+/// Uniforms that change as a graph runs, so that a node
+/// reading them isn't static, see [`Plan::statics`].
+const VARYING: [&str; 9] = [
+    "u_time",
+    "u_dt",
+    "u_frame",
+    "u_mouse",
+    "u_keyboard",
+    "u_quality",
+    "u_canvas",
+    "u_tile",
+    "u_palette",
+];
+
+pub type ExternalFn =
+    Box<dyn Fn(&mut ShaderGraph, &[NodeId]) -> Result<NodeId, String>>;
+pub type External = BTreeMap<String, ExternalFn>;
+
+/// What a feedback node starts from, instead of black.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Init {
+    /// An image, scaled to the size of the node.
+    Image(PathBuf),
+    /// The source of a fragment shader, run once.
+    Shader(String),
+}
+
+/// A node to be created when a [`Plan`] is realized.
+#[derive(Debug, Clone)]
+pub enum NodeSpec {
+    Input,
+    Shader {
+        source:    String,
+        inputs:    Vec<NodeId>,
+        width:     u32,
+        height:    u32,
+        /// Whether the shader can read its own last frame.
+        recurrent: bool,
+        /// What a recurrent shader's last frame starts as.
+        init:      Option<Init>,
+        texture:   TextureOptions,
+    },
+    Repeat {
+        source:     String,
+        inputs:     Vec<NodeId>,
+        feedback:   usize,
+        iterations: u32,
+        width:      u32,
+        height:     u32,
+        texture:    TextureOptions,
+    },
+    /// A shader rendering to several textures at once.
+    Mrt {
+        source: String,
+        inputs: Vec<NodeId>,
+        width:  u32,
+        height: u32,
+        count:  usize,
+    },
+    /// One of the textures another node renders to.
+    Attachment {
+        node:  NodeId,
+        index: usize,
+    },
+    Pyramid {
+        source: String,
+        input:  NodeId,
+        levels: u32,
+    },
+    Points {
+        source: String,
+        points: Vec<Point>,
+        inputs: Vec<NodeId>,
+        width:  u32,
+        height: u32,
+    },
+    Data(DataTable),
+    Random(RandomTexture),
+    /// A static image, at its own size.
+    Image(PathBuf),
+    Compute {
+        source: String,
+        inputs: Vec<NodeId>,
+        width:  u32,
+        height: u32,
+        groups: [u32; 3],
+        /// The size of `u_buffer`, in `vec4`s, if any.
+        count:  Option<usize>,
+    },
+    Audio {
+        /// The `ffmpeg` input to capture from, see
+        /// [`crate::audio::Capture::start`].
+        device: Option<String>,
+        /// In seconds, see [`Plan::input_delay`].
+        delay:  f32,
+    },
+    Camera {
+        /// The `ffmpeg` input to stream, see
+        /// [`crate::camera::device`].
+        device: String,
+        width:  u32,
+        height: u32,
+        fps:    u32,
+        /// In seconds, see [`Plan::input_delay`].
+        delay:  f32,
+    },
+    Video {
+        path:     PathBuf,
+        /// The size to scale to, or the video's own size.
+        size:     Option<(u32, u32)>,
+        playback: Playback,
+    },
+    #[cfg(feature = "feed")]
+    Feed {
+        url:      String,
+        every:    Duration,
+        throttle: Duration,
+        mapping:  crate::feed::Mapping,
+    },
+    Stats {
+        input: NodeId,
+        bins:  u32,
+    },
+    AutoExpose {
+        input: NodeId,
+        speed: f32,
+        key:   f32,
+    },
+    Flow {
+        input:  NodeId,
+        window: u32,
+    },
+    Reproject {
+        input:    NodeId,
+        velocity: NodeId,
+        blend:    f32,
+        clamp:    bool,
+    },
+    Fluid {
+        inputs:      Vec<NodeId>,
+        width:       u32,
+        height:      u32,
+        iterations:  u32,
+        dissipation: f32,
+    },
+    /// A texture any node can read, and one node writes,
+    /// see [`ShaderGraph::add_buffer`].
+    Buffer {
+        width:   u32,
+        height:  u32,
+        texture: TextureOptions,
+    },
+    /// The graph's output from the frame before, see
+    /// [`ShaderGraph::add_previous_output`].
+    PreviousOutput,
+    /// A subgraph built by a rust function, see
+    /// [`External`].
+    Extern {
+        name:   String,
+        inputs: Vec<NodeId>,
+    },
+}
+
+impl NodeSpec {
+    /// The fragment or compute shader the node runs, if
+    /// any.
+    pub fn source(&self) -> Option<&str> {
+        match self {
+            NodeSpec::Shader { source, .. }
+            | NodeSpec::Mrt { source, .. }
+            | NodeSpec::Compute { source, .. }
+            | NodeSpec::Repeat { source, .. }
+            | NodeSpec::Pyramid { source, .. }
+            | NodeSpec::Points { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+
+    /// Everything about the node but its source, which a
+    /// node can be patched with, see [`Plan::patches`].
+    fn shape(&self) -> String {
+        let mut spec = self.clone();
+        match &mut spec {
+            NodeSpec::Shader { source, .. }
+            | NodeSpec::Mrt { source, .. }
+            | NodeSpec::Compute { source, .. }
+            | NodeSpec::Repeat { source, .. }
+            | NodeSpec::Pyramid { source, .. }
+            | NodeSpec::Points { source, .. } => source.clear(),
+            _ => (),
+        }
+        format!("{:?}", spec)
+    }
+
+    /// What the node's state starts as, if not black.
+    pub fn init(&self) -> Option<&Init> {
+        match self {
+            NodeSpec::Shader { init, .. } => init.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Adds a preamble to the node's fragment shaders, see
+    /// [`Plan::set_preamble`].
+    fn prepend(&mut self, preamble: &str) {
+        match self {
+            NodeSpec::Shader { source, init, .. } => {
+                *source = with_preamble(source, preamble);
+                if let Some(Init::Shader(init)) = init {
+                    *init = with_preamble(init, preamble);
+                }
+            },
+            NodeSpec::Mrt { source, .. }
+            | NodeSpec::Repeat { source, .. }
+            | NodeSpec::Pyramid { source, .. }
+            | NodeSpec::Points { source, .. } => {
+                *source = with_preamble(source, preamble)
+            },
+            _ => (),
+        }
+    }
+
+    /// Sets the GLSL version of the node's shaders, see
+    /// [`with_version`].
+    fn set_version(&mut self, version: &GlslVersion, replace: bool) {
+        match self {
+            NodeSpec::Shader { source, init, .. } => {
+                *source = with_version(source, version, replace);
+                if let Some(Init::Shader(init)) = init {
+                    *init = with_version(init, version, replace);
+                }
+            },
+            NodeSpec::Mrt { source, .. }
+            | NodeSpec::Compute { source, .. }
+            | NodeSpec::Repeat { source, .. }
+            | NodeSpec::Pyramid { source, .. }
+            | NodeSpec::Points { source, .. } => {
+                *source = with_version(source, version, replace)
+            },
+            _ => (),
+        }
+    }
+
+    pub fn inputs(&self) -> Vec<NodeId> {
+        match self {
+            NodeSpec::Input
+            | NodeSpec::Data(_)
+            | NodeSpec::Random(_)
+            | NodeSpec::Image(_)
+            | NodeSpec::Audio { .. }
+            | NodeSpec::Camera { .. }
+            | NodeSpec::Video { .. }
+            | NodeSpec::Buffer { .. }
+            | NodeSpec::PreviousOutput => vec![],
+            #[cfg(feature = "feed")]
+            NodeSpec::Feed { .. } => vec![],
+            NodeSpec::Shader { inputs, .. }
+            | NodeSpec::Repeat { inputs, .. }
+            | NodeSpec::Mrt { inputs, .. }
+            | NodeSpec::Points { inputs, .. }
+            | NodeSpec::Compute { inputs, .. }
+            | NodeSpec::Fluid { inputs, .. }
+            | NodeSpec::Extern { inputs, .. } => inputs.clone(),
+            NodeSpec::Attachment { node: input, .. }
+            | NodeSpec::Pyramid { input, .. }
+            | NodeSpec::Stats { input, .. }
+            | NodeSpec::AutoExpose { input, .. }
+            | NodeSpec::Flow { input, .. } => vec![*input],
+            NodeSpec::Reproject {
+                input, velocity, ..
+            } => vec![*input, *velocity],
+        }
+    }
+}
+
+/// Sets the `#version` line of a shader. An existing line
+/// is only replaced if `replace`, and a new one is followed
+/// by a `#line`, so errors still point into the shader as
+/// written.
+fn with_version(source: &str, version: &GlslVersion, replace: bool) -> String {
+    let line = format!("#version {}", version);
+    let mut lines = source.lines().collect::<Vec<_>>();
+    let existing = lines
+        .iter()
+        .position(|line| line.trim_start().starts_with("#version"));
+    match existing {
+        Some(index) if replace => {
+            lines[index] = &line;
+            lines.join("\n")
+        },
+        Some(_) => source.to_string(),
+        None => {
+            let number = version.number as u32;
+            format!("{}\n{}\n{}", line, line_directive(number, 1, 0), source)
+        },
+    }
+}
+
+/// Inserts a preamble after the `#version` line of a
+/// shader, if any, then resets the line number so that
+/// errors still point into the shader as written.
+fn with_preamble(source: &str, preamble: &str) -> String {
+    let lines = source.lines().collect::<Vec<_>>();
+    let version = lines
+        .iter()
+        .position(|line| line.trim_start().starts_with("#version"));
+    let (head, tail) = lines.split_at(version.map_or(0, |line| line + 1));
+    let number = version
+        .and_then(|line| lines[line].split_whitespace().nth(1))
+        .and_then(|number| number.parse::<u32>().ok())
+        .unwrap_or(110);
+    let next = head.len() as u32 + 1;
+
+    let mut out = head.join("\n");
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(preamble.trim_end());
+    out.push('\n');
+    out.push_str(&line_directive(number, next, 0));
+    out.push('\n');
+    out.push_str(&tail.join("\n"));
+    out
+}
+
+/// Adds a black node, standing in for a live input in safe
+/// mode, see [`Plan::set_safe`].
+fn black(
+    graph: &mut ShaderGraph,
+    width: u32,
+    height: u32,
+) -> Result<NodeId, String> {
+    graph.add_data(&DataTable {
+        width,
+        height,
+        values: vec![0.0; width as usize * height as usize],
+    })
+}
+
+/// A description of a shader graph that has not been
+/// created on the GPU yet. Plans are built without a GL
+/// context, e.g. by evaluating the lisp for a graph, and
+/// then realized into a [`ShaderGraph`], which compiles the
+/// shaders and allocates the textures.
+#[derive(Debug, Clone, Default)]
+pub struct Plan {
+    pub(super) topology:     Topology,
+    /// Each node, along with the name of the shader it was
+    /// loaded from, to point errors at.
+    pub(super) nodes:        Vec<(NodeSpec, Option<String>)>,
+    pub(super) requirements: Vec<Requirements>,
+    pub(super) uniforms:     Vec<(String, UniformType)>,
+    pub(super) palettes:     Vec<Palette>,
+    pub(super) knobs:        Vec<Knob>,
+    pub(super) windows:      Vec<OutputWindow>,
+    /// Warm-up frames for the graph, and for single nodes.
+    pub(super) warmup:       u32,
+    pub(super) warmups:      BTreeMap<NodeId, u32>,
+    pub(super) substeps:     BTreeMap<NodeId, u32>,
+    pub(super) params:       BTreeMap<(NodeId, String), f32>,
+    pub(super) conditions:   BTreeMap<NodeId, Condition>,
+    /// The node writing each buffer, see
+    /// [`Plan::write_buffer`].
+    pub(super) writes:       BTreeMap<NodeId, NodeId>,
+    /// GLSL prepended to every fragment shader.
+    preamble:                Option<String>,
+    /// The GLSL version of shaders that don't give one, and
+    /// the versions single nodes asked for.
+    glsl:                    Option<GlslVersion>,
+    pub(super) versions:     BTreeMap<NodeId, GlslVersion>,
+    /// What nodes are replaced with if they can't be
+    /// created, see [`Plan::set_fallback`].
+    pub(super) fallbacks:    BTreeMap<NodeId, NodeSpec>,
+    /// Nodes whose output is kept on disk between runs,
+    /// see [`Plan::cache_node`].
+    pub(super) cached:       BTreeSet<NodeId>,
+    /// Nodes replaced by images, see [`Plan::bake_node`].
+    pub(super) baked:        Vec<Baked>,
+    /// Whether live inputs are left closed, see
+    /// [`Plan::set_safe`].
+    pub(super) safe:         bool,
+    /// Lisp files included by the graph, to watch.
+    includes:                Vec<PathBuf>,
+    /// Files included by its shaders, to watch.
+    included:                Vec<PathBuf>,
+    /// The names of the shaders it was built from.
+    shaders:                 Vec<String>,
+    pub(super) meta:         Meta,
+    /// The names of every profile declared, and the one the
+    /// graph is built with, see [`Plan::add_profile`].
+    profiles:                Vec<String>,
+    pub(super) profile:      Option<Profile>,
+}
+
+impl Plan {
+    pub fn new() -> Plan { Plan::default() }
+
+    /// The shape of the planned graph.
+    pub fn topology(&self) -> &Topology { &self.topology }
+
+    /// Every planned node, in the order it was added.
+    pub fn nodes(&self) -> impl Iterator<Item = (NodeId, &NodeSpec)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(id, (spec, _))| (NodeId(id), spec))
+    }
+
+    /// Plans a node. `shader` is the name of the shader its
+    /// source was loaded from, if any.
+    pub fn add(&mut self, spec: NodeSpec, shader: Option<&str>) -> NodeId {
+        let id = match spec {
+            NodeSpec::Input => self.topology.add_input(),
+            _ => self.topology.add_node(spec.inputs()),
+        };
+        self.nodes.push((spec, shader.map(str::to_string)));
+        id
+    }
+
+    pub fn add_input(&mut self) -> NodeId { self.add(NodeSpec::Input, None) }
+
+    /// See [`ShaderGraph::mark_output`].
+    pub fn mark_output(&mut self, id: NodeId) { self.topology.mark_output(id) }
+
+    /// See [`ShaderGraph::name_node`].
+    pub fn name_node(&mut self, id: NodeId, name: &str) {
+        self.topology.name_node(id, name)
+    }
+
+    /// See [`ShaderGraph::write_buffer`].
+    pub fn write_buffer(
+        &mut self,
+        buffer: NodeId,
+        writer: NodeId,
+    ) -> Result<(), String> {
+        let describe = |id| self.topology.describe(id);
+        let spec = self.nodes.get(buffer.0).map(|(spec, _)| spec);
+        if !matches!(spec, Some(NodeSpec::Buffer { .. })) {
+            return Err(format!("`{}` is not a buffer", describe(buffer)));
+        }
+        if self.writes.contains_key(&buffer) {
+            return Err(format!(
+                "`{}` is already written by another node",
+                describe(buffer)
+            ));
+        }
+        self.writes.insert(buffer, writer);
+        Ok(())
+    }
+
+    /// See [`ShaderGraph::label_node`].
+    pub fn label_node(&mut self, id: NodeId, label: &str) {
+        self.topology.label_node(id, label)
+    }
+
+    /// See [`ShaderGraph::order_before`].
+    pub fn order_before(
+        &mut self,
+        first: NodeId,
+        second: NodeId,
+    ) -> Result<(), String> {
+        self.topology.order_before(first, second)
+    }
+
+    /// Adds requirements that are checked against the
+    /// context before anything is created in it.
+    pub fn require(&mut self, requirements: Requirements) {
+        self.requirements.push(requirements);
+    }
+
+    /// Records a file the graph was built from, besides its
+    /// lisp and shaders, so it can be watched for changes.
+    pub fn add_include(&mut self, path: &Path) {
+        if !self.includes.iter().any(|p| p == path) {
+            self.includes.push(path.to_path_buf());
+        }
+    }
+
+    /// Every file recorded with [`Plan::add_include`].
+    pub fn includes(&self) -> &[PathBuf] { &self.includes }
+
+    /// Records a file included by a shader, see
+    /// [`crate::include`]. Unlike other files, a change to
+    /// one only needs the shaders including it recompiled.
+    pub fn add_shader_include(&mut self, path: &Path) {
+        if !self.included.iter().any(|p| p == path) {
+            self.included.push(path.to_path_buf());
+        }
+    }
+
+    /// Every file recorded with
+    /// [`Plan::add_shader_include`].
+    pub fn shader_includes(&self) -> &[PathBuf] { &self.included }
+
+    /// Records a shader the graph was built from, by name.
+    pub fn add_shader(&mut self, name: &str) {
+        if !self.shaders.iter().any(|shader| shader == name) {
+            self.shaders.push(name.to_string());
+        }
+    }
+
+    /// Every shader recorded with [`Plan::add_shader`].
+    pub fn shaders(&self) -> &[String] { &self.shaders }
+
+    /// Every file the graph reads besides its lisp and
+    /// shaders: includes, the images and videos of its
+    /// nodes, and its preview, see [`Meta`].
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files = self.includes.clone();
+        files.extend(self.included.iter().cloned());
+        files.extend(self.meta.preview.iter().cloned());
+        files.extend(self.baked.iter().map(|baked| baked.image.clone()));
+        let specs = self
+            .nodes
+            .iter()
+            .map(|(spec, _)| spec)
+            .chain(self.fallbacks.values());
+        for spec in specs {
+            let file = match spec {
+                NodeSpec::Image(path) => path,
+                NodeSpec::Video { path, .. } => path,
+                NodeSpec::Shader {
+                    init: Some(Init::Image(path)),
+                    ..
+                } => path,
+                _ => continue,
+            };
+            if !files.contains(file) {
+                files.push(file.clone());
+            }
+        }
+        files
+    }
+
+    /// See [`ShaderGraph::declare_uniform`]. Uniforms that
+    /// are built in are an error here already, rather than
+    /// once the plan is realized.
+    pub fn declare_uniform(
+        &mut self,
+        name: &str,
+        kind: UniformType,
+    ) -> Result<(), String> {
+        if super::uniform::BUILT_IN.contains(&name) {
+            return Err(format!(
+                "Cannot declare uniform `{}`, it is built in",
+                name
+            ));
+        }
+        self.uniforms.push((name.to_string(), kind));
+        Ok(())
+    }
+
+    /// Sets GLSL, like shared uniforms, helper functions,
+    /// and `#define`s, to insert after the `#version`
+    /// line of every fragment shader in the graph,
+    /// whether planned before or after. Compute shaders
+    /// are left as is. Errors if a preamble was already
+    /// set.
+    pub fn set_preamble(&mut self, preamble: &str) -> Result<(), String> {
+        if self.preamble.is_some() {
+            return Err("The graph's preamble is already set".to_string());
+        }
+        self.preamble = Some(preamble.to_string());
+        Ok(())
+    }
+
+    /// Sets the GLSL version of every shader in the graph
+    /// without a `#version` line, whether planned before or
+    /// after. Errors if a version was already set.
+    pub fn set_glsl(&mut self, version: GlslVersion) -> Result<(), String> {
+        if self.glsl.is_some() {
+            return Err("The graph's GLSL version is already set".to_string());
+        }
+        self.glsl = Some(version);
+        Ok(())
+    }
+
+    /// Sets the GLSL version of a node's shaders, replacing
+    /// their `#version` lines, if any.
+    pub fn set_node_glsl(&mut self, id: NodeId, version: GlslVersion) {
+        self.versions.insert(id, version);
+    }
+
+    /// Sets what a node is replaced with if it can't be
+    /// created, e.g. because the driver lacks a feature it
+    /// needs, so that the graph still builds on weaker
+    /// hardware. The fallback may only take inputs of the
+    /// node. Attachments of a node that falls back show its
+    /// fallback instead.
+    pub fn set_fallback(
+        &mut self,
+        id: NodeId,
+        fallback: NodeSpec,
+    ) -> Result<(), String> {
+        let inputs = self.nodes[id.0].0.inputs();
+        if fallback
+            .inputs()
+            .iter()
+            .any(|input| !inputs.contains(input))
+        {
+            return Err(
+                "A fallback can only take the inputs of its node".to_string()
+            );
+        }
+        self.fallbacks.insert(id, fallback);
+        Ok(())
+    }
+
+    /// See [`ShaderGraph::cache_node`]. Only static nodes
+    /// can be cached, see [`Plan::statics`], which is
+    /// checked when the plan is realized, by
+    /// [`Plan::cache_key`].
+    pub fn cache_node(&mut self, id: NodeId) { self.cached.insert(id); }
+
+    /// Identifies what a cached node renders: its shader as
+    /// it is compiled, its size and params, the files of
+    /// its images, and the keys of its inputs, as the hex
+    /// SHA-1 of them all, since the cache is shared by
+    /// every project. Errors if the node isn't static,
+    /// see [`Plan::statics`], as it would be frozen at
+    /// its first frame.
+    pub fn cache_key(&self, id: NodeId) -> Result<String, String> {
+        if !self.statics().contains(&id) {
+            return Err(format!(
+                "{} can't be cached, as it may change from frame to \
+                 frame, e.g. by reading `u_time` or a control",
+                self.topology.describe(id)
+            ));
+        }
+        let mut hasher = Sha1::new();
+        self.hash_node(id, &mut hasher)?;
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect())
+    }
+
+    /// Feeds what a static node renders to a hasher, see
+    /// [`Plan::cache_key`], after its inputs.
+    fn hash_node(&self, id: NodeId, hasher: &mut Sha1) -> Result<(), String> {
+        let spec = self.spec(id);
+        for input in spec.inputs() {
+            self.hash_node(input, hasher)?;
+        }
+        if let NodeSpec::Image(path) = &*spec {
+            hasher.update(fs::read(path).map_err(|e| {
+                format!("Could not read `{}`: {}", path.display(), e)
+            })?);
+        }
+        hasher.update(format!("{:?}", spec).as_bytes());
+        for ((node, uniform), value) in self.params.range((id, String::new())..)
+        {
+            if *node != id {
+                break;
+            }
+            hasher.update(format!("{}={}", uniform, value).as_bytes());
+        }
+        // so that no two nodes' bytes run into each other
+        hasher.update(b"\0");
+        Ok(())
+    }
+
+    /// The nodes that render the same thing whenever they
+    /// run, like cached nodes, but also without reading any
+    /// uniform that changes as the graph runs, which their
+    /// shaders are searched for. Their inputs are static
+    /// too.
+    pub fn statics(&self) -> BTreeSet<NodeId> {
+        // controls and knobs are declared uniforms too
+        let varying = self
+            .uniforms
+            .iter()
+            .map(|(name, _)| format!("u_{}", name))
+            .chain(VARYING.iter().map(|name| name.to_string()))
+            .collect::<Vec<_>>();
+        let mut statics = BTreeSet::new();
+        // inputs are always planned before their nodes
+        for id in (0..self.nodes.len()).map(NodeId) {
+            let spec = self.spec(id);
+            let constant = match &*spec {
+                NodeSpec::Shader {
+                    source,
+                    recurrent: false,
+                    ..
+                } => !varying.iter().any(|name| source.contains(name)),
+                NodeSpec::Random(random) => !random.regen_on_reload,
+                NodeSpec::Data(_) | NodeSpec::Image(_) => true,
+                _ => false,
+            };
+            if constant
+                && spec.inputs().iter().all(|input| statics.contains(input))
+            {
+                statics.insert(id);
+            }
+        }
+        statics
+    }
+
+    /// The static shaders worth baking into images, see
+    /// [`Plan::bake_node`]: those whose output is read by a
+    /// node that isn't static, or is shown.
+    pub fn bakeable(&self) -> Vec<NodeId> {
+        let shown = self
+            .topology
+            .get_outputs()
+            .iter()
+            .copied()
+            .chain(self.windows.iter().map(|window| window.node))
+            .collect::<BTreeSet<_>>();
+        let statics = self.statics();
+        statics
+            .iter()
+            .copied()
+            .filter(|id| matches!(self.nodes[id.0].0, NodeSpec::Shader { .. }))
+            .filter(|id| {
+                shown.contains(id)
+                    || (0..self.nodes.len()).map(NodeId).any(|reader| {
+                        !statics.contains(&reader)
+                            && self.nodes[reader.0].0.inputs().contains(id)
+                    })
+            })
+            .collect()
+    }
+
+    /// Replaces a node's output with an image rendered from
+    /// it ahead of time, as long as the node still renders
+    /// what it did then, see [`Plan::cache_key`]. The node
+    /// is then fixed to the image when the plan is
+    /// realized, see [`ShaderGraph::fix_node`].
+    pub fn bake_node(&mut self, baked: Baked) { self.baked.push(baked); }
+
+    /// Bakes every node listed in a directory made by
+    /// `shadergarden bake`, see [`Baked::dir`], if there
+    /// is one.
+    pub fn read_baked(&mut self, dir: &Path) -> Result<(), String> {
+        for baked in Baked::read_all(dir)? {
+            self.bake_node(baked);
+        }
+        Ok(())
+    }
+
+    /// Builds the graph without opening any live input or
+    /// calling any external function, so that it can still
+    /// be previewed on a machine without the devices it
+    /// uses, or without permission to use them. Cameras
+    /// and external functions are black, audio is silent,
+    /// and feeds keep their fields at zero.
+    pub fn set_safe(&mut self, safe: bool) { self.safe = safe; }
+
+    /// Fixes a node to the image it was baked into, if it's
+    /// still what the node renders.
+    fn realize_baked(
+        &self,
+        graph: &mut ShaderGraph,
+        ids: &[NodeId],
+        baked: &Baked,
+    ) -> Result<(), String> {
+        let id = baked.node;
+        let stale = format!(
+            "`{}` is out of date, run `shadergarden bake` again",
+            baked.image.display()
+        );
+        if self.cache_key(id).ok().as_ref() != Some(&baked.key) {
+            return Err(stale);
+        }
+        let output = CachedOutput::load_png(&baked.image)?;
+        graph
+            .fix_node(ids[id.0], &output)
+            .map_err(|e| self.locate(id, &self.nodes[id.0].1, e))
+    }
+
+    /// A planned node as it is created: with its GLSL
+    /// version set, then the preamble inserted after it.
+    fn spec(&self, id: NodeId) -> Cow<'_, NodeSpec> {
+        self.prepared(&self.nodes[id.0].0, self.versions.get(&id))
+    }
+
+    /// A node's fallback as it is created, like
+    /// [`Plan::spec`], but without the GLSL version the
+    /// node asked for, which may be why it couldn't be
+    /// created.
+    fn fallback(&self, id: NodeId) -> Option<Cow<'_, NodeSpec>> {
+        let fallback = self.fallbacks.get(&id)?;
+        Some(self.prepared(fallback, None))
+    }
+
+    /// A node with a GLSL version set, or the graph's, then
+    /// the profile's `#define`s and the preamble inserted
+    /// after it.
+    fn prepared<'a>(
+        &'a self,
+        spec: &'a NodeSpec,
+        version: Option<&GlslVersion>,
+    ) -> Cow<'a, NodeSpec> {
+        let defines = self.profile.as_ref().map(Profile::preamble);
+        let defines = defines.filter(|defines| !defines.is_empty());
+        let unchanged = version.is_none()
+            && self.glsl.is_none()
+            && self.preamble.is_none()
+            && defines.is_none();
+        if unchanged || spec.source().is_none() {
+            return Cow::Borrowed(spec);
+        }
+
+        let mut spec = spec.clone();
+        match (version, &self.glsl) {
+            (Some(version), _) => spec.set_version(version, true),
+            (None, Some(version)) => spec.set_version(version, false),
+            (None, None) => (),
+        }
+        match (defines, &self.preamble) {
+            (Some(defines), Some(preamble)) => {
+                spec.prepend(&(defines + preamble))
+            },
+            (Some(defines), None) => spec.prepend(&defines),
+            (None, Some(preamble)) => spec.prepend(preamble),
+            (None, None) => (),
+        }
+        Cow::Owned(spec)
+    }
+
+    /// The plan with every shader as it will be compiled,
+    /// and no files left to watch, see [`super::Bundle`].
+    pub(super) fn frozen(&self) -> Plan {
+        let mut plan = self.clone();
+        for (id, (spec, _)) in plan.nodes.iter_mut().enumerate() {
+            *spec = self.spec(NodeId(id)).into_owned();
+        }
+        for (id, fallback) in plan.fallbacks.iter_mut() {
+            if let Some(frozen) = self.fallback(*id) {
+                *fallback = frozen.into_owned();
+            }
+        }
+        // still checked, though already set
+        if let Some(version) = plan.glsl.take() {
+            plan.requirements.push(Requirements {
+                glsl: Some(version),
+                ..Requirements::default()
+            });
+        }
+        plan.preamble = None;
+        if let Some(profile) = &mut plan.profile {
+            profile.defines.clear();
+        }
+        plan.includes.clear();
+        plan.included.clear();
+        plan
+    }
+
+    /// Checks a GLSL version against the graph's context.
+    fn require_glsl(
+        graph: &ShaderGraph,
+        version: &GlslVersion,
+    ) -> Result<(), String> {
+        graph.require(&Requirements {
+            glsl: Some(version.clone()),
+            ..Requirements::default()
+        })
+    }
+
+    /// See [`ShaderGraph::add_palette`].
+    pub fn add_palette(&mut self, palette: Palette) {
+        self.palettes.push(palette);
+    }
+
+    /// See [`ShaderGraph::add_knob`].
+    pub fn add_knob(&mut self, knob: Knob) { self.knobs.push(knob); }
+
+    /// Every knob added, see [`Plan::add_knob`].
+    pub fn knobs(&self) -> &[Knob] { &self.knobs }
+
+    /// See [`ShaderGraph::add_window`].
+    pub fn add_window(&mut self, window: OutputWindow) -> Result<(), String> {
+        if window.node.0 >= self.nodes.len() {
+            return Err("Cannot show a node not in the graph".to_string());
+        }
+        self.windows.push(window);
+        Ok(())
+    }
+
+    /// Every window asked for, see [`Plan::add_window`].
+    pub fn windows(&self) -> &[OutputWindow] { &self.windows }
+
+    /// See [`ShaderGraph::set_meta`]. A graph is only
+    /// credited once.
+    pub fn set_meta(&mut self, meta: Meta) -> Result<(), String> {
+        if !self.meta.is_empty() {
+            return Err("The graph already has a `meta`".to_string());
+        }
+        self.meta = meta;
+        Ok(())
+    }
+
+    pub fn meta(&self) -> &Meta { &self.meta }
+
+    /// Declares a profile, and builds the graph with it if
+    /// `selected`. Errors if a profile of the same name was
+    /// already declared.
+    pub fn add_profile(
+        &mut self,
+        profile: Profile,
+        selected: bool,
+    ) -> Result<(), String> {
+        if self.profiles.contains(&profile.name) {
+            return Err(format!(
+                "Profile `{}` is declared twice",
+                profile.name
+            ));
+        }
+        self.profiles.push(profile.name.clone());
+        if selected {
+            self.profile = Some(profile);
+        }
+        Ok(())
+    }
+
+    /// The names of the profiles declared, in order.
+    pub fn profiles(&self) -> &[String] { &self.profiles }
+
+    /// The profile the graph is built with, if any.
+    pub fn profile(&self) -> Option<&Profile> { self.profile.as_ref() }
+
+    /// How much larger the profile makes every node, see
+    /// [`Profile::render_scale`].
+    pub fn render_scale(&self) -> f32 {
+        self.profile.as_ref().map_or(1.0, Profile::render_scale)
+    }
+
+    /// See [`ShaderGraph::warm_up`].
+    pub fn warm_up(&mut self, frames: u32) { self.warmup = frames; }
+
+    /// See [`ShaderGraph::warm_up_node`].
+    pub fn warm_up_node(&mut self, id: NodeId, frames: u32) {
+        self.warmups.insert(id, frames);
+    }
+
+    /// See [`ShaderGraph::set_substeps`].
+    pub fn set_substeps(&mut self, id: NodeId, substeps: u32) {
+        self.substeps.insert(id, substeps);
+    }
+
+    /// See [`ShaderGraph::set_condition`].
+    pub fn set_condition(&mut self, id: NodeId, condition: Condition) {
+        self.conditions.insert(id, condition);
+    }
+
+    /// See [`ShaderGraph::set_node_param`].
+    pub fn set_param(&mut self, id: NodeId, uniform: &str, value: f32) {
+        self.params.insert((id, uniform.to_string()), value);
+    }
+
+    /// Creates every planned node in a graph. `external`
+    /// holds the rust functions that `Extern` nodes call.
+    /// See [`Realization`] to spread this over several
+    /// frames.
+    pub fn realize(
+        &self,
+        graph: &mut ShaderGraph,
+        external: &External,
+    ) -> Result<(), String> {
+        self.realize_setup(graph)?;
+        let mut ids: Vec<NodeId> = Vec::with_capacity(self.nodes.len());
+        for id in 0..self.nodes.len() {
+            let id = self.realize_nth(graph, external, &ids, NodeId(id))?;
+            ids.push(id);
+        }
+        self.realize_links(graph, &ids)
+    }
+
+    /// Sets up everything in a graph but its nodes.
+    fn realize_setup(&self, graph: &mut ShaderGraph) -> Result<(), String> {
+        for requirements in self.requirements.iter() {
+            graph.require(requirements)?;
+        }
+        if let Some(version) = &self.glsl {
+            Self::require_glsl(graph, version)?;
+        }
+        for (name, kind) in self.uniforms.iter() {
+            graph.declare_uniform(name, kind.clone())?;
+        }
+        for palette in self.palettes.iter() {
+            graph.add_palette(palette.clone());
+        }
+        for knob in self.knobs.iter() {
+            graph.add_knob(knob.clone())?;
+        }
+        graph.set_meta(self.meta.clone());
+        if let Some(profile) = &self.profile {
+            graph.render_scale *= profile.render_scale();
+            graph.set_supersample(profile.supersample);
+        }
+        Ok(())
+    }
+
+    /// Creates a planned node, given the ids in the graph
+    /// of the nodes before it. External functions may
+    /// add several nodes, so ids in the plan and the
+    /// graph can differ.
+    fn realize_nth(
+        &self,
+        graph: &mut ShaderGraph,
+        external: &External,
+        ids: &[NodeId],
+        id: NodeId,
+    ) -> Result<NodeId, String> {
+        let (spec, shader) = &self.nodes[id.0];
+        let created = self
+            .versions
+            .get(&id)
+            .map_or(Ok(()), |version| Self::require_glsl(graph, version))
+            .and_then(|()| {
+                self.realize_node(graph, external, ids, &self.spec(id))
+            })
+            .map_err(|e| self.locate(id, shader, e));
+        let error = match created {
+            Ok(id) => return Ok(id),
+            Err(error) => error,
+        };
+
+        if let NodeSpec::Attachment { node, .. } = spec {
+            if self.fallbacks.contains_key(node) {
+                return Ok(ids[node.0]);
+            }
+        }
+        let fallback = match self.fallback(id) {
+            Some(fallback) => fallback,
+            None => return Err(error),
+        };
+        eprintln!("[warn] {}", error);
+        eprintln!("[warn] Using the fallback of node {} instead", id.0);
+        self.realize_node(graph, external, ids, &fallback)
+            .map_err(|e| self.locate(id, &None, format!("In fallback: {}", e)))
+    }
+
+    /// Names, orders, and sets up the nodes once they are
+    /// all created.
+    fn realize_links(
+        &self,
+        graph: &mut ShaderGraph,
+        ids: &[NodeId],
+    ) -> Result<(), String> {
+        for (name, id) in self.topology.node_names() {
+            graph.name_node(ids[id.0], name);
+        }
+        for (id, label) in self.topology.node_labels() {
+            graph.label_node(ids[id.0], label);
+        }
+        for id in self.topology.get_outputs() {
+            graph.mark_output(ids[id.0]);
+        }
+        for (first, second) in self.topology.hints() {
+            graph.order_before(ids[first.0], ids[second.0])?;
+        }
+        graph.warm_up(self.warmup);
+        for (id, frames) in self.warmups.iter() {
+            graph.warm_up_node(ids[id.0], *frames);
+        }
+        for (id, substeps) in self.substeps.iter() {
+            graph.set_substeps(ids[id.0], *substeps);
+        }
+        for ((id, uniform), value) in self.params.iter() {
+            graph.set_node_param(ids[id.0], uniform, *value);
+        }
+        for (id, condition) in self.conditions.iter() {
+            graph.set_condition(ids[id.0], condition.clone())?;
+        }
+        for (buffer, writer) in self.writes.iter() {
+            graph.write_buffer(ids[buffer.0], ids[writer.0])?;
+        }
+        for window in self.windows.iter() {
+            graph.add_window(OutputWindow {
+                node: ids[window.node.0],
+                ..window.clone()
+            })?;
+        }
+        for id in self.cached.iter() {
+            let shader = &self.nodes[id.0].1;
+            self.cache_key(*id)
+                .and_then(|key| graph.cache_node(ids[id.0], key))
+                .map_err(|e| self.locate(*id, shader, e))?;
+        }
+        for baked in self.baked.iter() {
+            if let Err(e) = self.realize_baked(graph, ids, baked) {
+                eprintln!("[warn] {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Brings a graph realized from this plan up to date
+    /// with a newer plan built from the same lisp, by
+    /// recompiling only the nodes whose shaders changed,
+    /// see [`Plan::patches`]. Every other node, and the
+    /// textures of the patched ones, are kept. Returns
+    /// the number of nodes patched, or `None` if the
+    /// graph must be rebuilt.
+    pub fn patch(
+        &self,
+        graph: &mut ShaderGraph,
+        new: &Plan,
+    ) -> Option<Result<usize, String>> {
+        let changed = self.patches(new)?;
+        graph.set_meta(new.meta.clone());
+        let patched = changed.iter().try_for_each(|(id, source)| {
+            graph
+                .patch_node(*id, source)
+                .map_err(|e| self.locate(*id, &new.nodes[id.0].1, e))
+        });
+        Some(patched.map(|()| changed.len()))
+    }
+
+    /// The nodes whose shaders changed between this plan
+    /// and a newer one built from the same lisp, with
+    /// their new sources, or `None` if the plans differ
+    /// in more than shader sources, or have fallbacks,
+    /// which nodes may be running instead, or a cached
+    /// node renders something else, in which case a
+    /// graph realized from this plan must be rebuilt,
+    /// rather than patched.
+    pub fn patches(&self, new: &Plan) -> Option<Vec<(NodeId, String)>> {
+        if self.nodes.len() != new.nodes.len()
+            || self.uniforms != new.uniforms
+            || self.palettes != new.palettes
+            || self.knobs != new.knobs
+            || self.windows != new.windows
+            || !self.topology.node_labels().eq(new.topology.node_labels())
+            || self.params != new.params
+            || self.conditions != new.conditions
+            || self.writes != new.writes
+            || self.profile != new.profile
+            || self.glsl != new.glsl
+            || self.versions != new.versions
+            || !self.fallbacks.is_empty()
+            || !new.fallbacks.is_empty()
+            || self.cached != new.cached
+            || self.baked != new.baked
+            || self.delays() != new.delays()
+            || self.safe != new.safe
+        {
+            return None;
+        }
+
+        let mut changed = vec![];
+        for id in (0..new.nodes.len()).map(NodeId) {
+            let (old, spec) = (self.spec(id), new.spec(id));
+            // nodes are only sized, and initialized, when they
+            // are created
+            let same_shape = old.shape() == spec.shape();
+            // external functions may add any number of nodes,
+            // so ids in the graph can't be trusted
+            if !same_shape || matches!(*spec, NodeSpec::Extern { .. }) {
+                return None;
+            }
+            // a cached node doesn't run, so it can't be patched
+            if self.cached.contains(&id)
+                && self.cache_key(id).ok() != new.cache_key(id).ok()
+            {
+                return None;
+            }
+            if old.source() != spec.source() {
+                changed.push((id, spec.source()?.to_string()));
+            }
+        }
+        Some(changed)
+    }
+
+    /// The delay of each live input, in seconds, as asked
+    /// for with `:delay`, in order.
+    fn delays(&self) -> Vec<f32> {
+        self.nodes
+            .iter()
+            .filter_map(|(spec, _)| match spec {
+                NodeSpec::Audio { delay, .. }
+                | NodeSpec::Camera { delay, .. } => Some(*delay),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// How long a live input asking for a delay of `delay`
+    /// seconds is held back. A live input can't be moved
+    /// ahead of time, so one asking for a negative delay is
+    /// ahead of the others by holding all of them back
+    /// instead, by the most any asks to be ahead.
+    pub fn input_delay(&self, delay: f32) -> Duration {
+        let lead = self.delays().into_iter().fold(0.0, f32::min);
+        Duration::from_secs_f32((delay - lead).max(0.0))
+    }
+
+    /// Wraps an error from creating a node with the shader
+    /// and node it came from, see
+    /// [`crate::diagnostic::BuildError`].
+    pub(super) fn locate(
+        &self,
+        id: NodeId,
+        shader: &Option<String>,
+        error: String,
+    ) -> String {
+        let error = match shader {
+            Some(name) => in_shader(name, error),
+            None => error,
+        };
+        match self.topology.name_of(id) {
+            Some(node) => in_node(node, error),
+            None => error,
+        }
+    }
+
+    fn realize_node(
+        &self,
+        graph: &mut ShaderGraph,
+        external: &External,
+        ids: &[NodeId],
+        spec: &NodeSpec,
+    ) -> Result<NodeId, String> {
+        let map = |inputs: &[NodeId]| -> Vec<NodeId> {
+            inputs.iter().map(|input| ids[input.0]).collect()
+        };
+
+        match spec {
+            NodeSpec::Input => Ok(graph.add_input()),
+            NodeSpec::Shader {
+                source,
+                inputs,
+                width,
+                height,
+                recurrent,
+                init,
+                texture,
+            } => {
+                let id = graph.add_shader_with(
+                    source,
+                    map(inputs),
+                    *width,
+                    *height,
+                    *recurrent,
+                    *texture,
+                )?;
+                if let Some(init) = init {
+                    graph.init_node(id, init)?;
+                }
+                Ok(id)
+            },
+            NodeSpec::Repeat {
+                source,
+                inputs,
+                feedback,
+                iterations,
+                width,
+                height,
+                texture,
+            } => graph.add_repeat_shader(
+                source,
+                map(inputs),
+                *feedback,
+                *iterations,
+                *width,
+                *height,
+                *texture,
+            ),
+            NodeSpec::Mrt {
+                source,
+                inputs,
+                width,
+                height,
+                count,
+            } => graph.add_mrt_shader(
+                source,
+                map(inputs),
+                *width,
+                *height,
+                *count,
+            ),
+            NodeSpec::Attachment { node, index } => {
+                graph.add_attachment(ids[node.0], *index)
+            },
+            NodeSpec::Pyramid {
+                source,
+                input,
+                levels,
+            } => graph.add_pyramid(source, ids[input.0], *levels),
+            NodeSpec::Points {
+                source,
+                points,
+                inputs,
+                width,
+                height,
+            } => graph.add_points(source, points, map(inputs), *width, *height),
+            NodeSpec::Data(table) => graph.add_data(table),
+            NodeSpec::Random(random) => graph.add_random(random),
+            NodeSpec::Image(path) => graph.add_image(path),
+            NodeSpec::Compute {
+                source,
+                inputs,
+                width,
+                height,
+                groups,
+                count,
+            } => graph.add_compute_shader(
+                source,
+                map(inputs),
+                *width,
+                *height,
+                *groups,
+                *count,
+            ),
+            NodeSpec::Audio { .. } if self.safe => {
+                // a silent waveform sits at the middle
+                let width = crate::audio::WIDTH;
+                let mut values = vec![0.0; width];
+                values.resize(2 * width, 0.5);
+                graph.add_data(&DataTable {
+                    width: width as u32,
+                    height: 2,
+                    values,
+                })
+            },
+            NodeSpec::Audio { device, delay } => {
+                graph.add_audio(device.as_deref(), self.input_delay(*delay))
+            },
+            NodeSpec::Buffer {
+                width,
+                height,
+                texture,
+            } => graph.add_buffer(*width, *height, *texture),
+            NodeSpec::PreviousOutput => graph.add_previous_output(),
+            NodeSpec::Camera { width, height, .. } if self.safe => {
+                black(graph, *width, *height)
+            },
+            NodeSpec::Camera {
+                device,
+                width,
+                height,
+                fps,
+                delay,
+            } => graph.add_camera(
+                device,
+                *width,
+                *height,
+                *fps,
+                self.input_delay(*delay),
+            ),
+            NodeSpec::Video {
+                path,
+                size,
+                playback,
+            } => graph.add_video(path, *size, playback),
+            #[cfg(feature = "feed")]
+            NodeSpec::Feed {
+                url,
+                every,
+                throttle,
+                mapping,
+            } => {
+                use crate::feed::{
+                    Feed,
+                    Source,
+                };
+                let feed = match self.safe {
+                    true => Feed::idle(),
+                    false => {
+                        let source = Source::from_url(url, *every);
+                        Feed::spawn(source, mapping.clone(), *throttle)
+                    },
+                };
+                graph.add_feed(feed, mapping.clone())
+            },
+            NodeSpec::Stats { input, bins } => {
+                graph.add_stats(ids[input.0], *bins)
+            },
+            NodeSpec::AutoExpose { input, speed, key } => {
+                graph.add_auto_expose(ids[input.0], *speed, *key)
+            },
+            NodeSpec::Flow { input, window } => {
+                graph.add_flow(ids[input.0], *window)
+            },
+            NodeSpec::Reproject {
+                input,
+                velocity,
+                blend,
+                clamp,
+            } => graph.add_reproject(
+                ids[input.0],
+                ids[velocity.0],
+                *blend,
+                *clamp,
+            ),
+            NodeSpec::Fluid {
+                inputs,
+                width,
+                height,
+                iterations,
+                dissipation,
+            } => graph.add_fluid(
+                map(inputs),
+                *width,
+                *height,
+                *iterations,
+                *dissipation,
+            ),
+            NodeSpec::Extern { .. } if self.safe => black(graph, 1, 1),
+            NodeSpec::Extern { name, inputs } => {
+                let adder = external.get(name).ok_or_else(|| {
+                    format!(
+                        "Could not load external function `{}`, it is not \
+                         defined",
+                        name
+                    )
+                })?;
+                adder(graph, &map(inputs)).map_err(|e| {
+                    format!("While adding external function `{}`: {}", name, e)
+                })
+            },
+        }
+    }
+}
+
+/// A plan being realized a few nodes at a time, so that a
+/// large graph can be built between frames while another
+/// keeps running. Nodes are still created, and compiled, in
+/// the context of the thread stepping it, so this spreads
+/// the work out rather than taking it off that thread. See
+/// [`Plan::realize`] to realize a plan all at once.
+pub struct Realization {
+    plan:  Plan,
+    graph: ShaderGraph,
+    /// The ids in the graph of the nodes created so far.
+    ids:   Vec<NodeId>,
+}
+
+impl Realization {
+    /// Starts realizing a plan in a new graph, setting up
+    /// everything but its nodes.
+    pub fn new(
+        plan: Plan,
+        mut graph: ShaderGraph,
+    ) -> Result<Realization, String> {
+        plan.realize_setup(&mut graph)?;
+        let ids = Vec::with_capacity(plan.nodes.len());
+        Ok(Realization { plan, graph, ids })
+    }
+
+    /// Creates nodes until `budget` has passed, or until
+    /// every node is created. At least one node is created
+    /// each call. Returns whether the graph is finished.
+    pub fn step(
+        &mut self,
+        external: &External,
+        budget: Duration,
+    ) -> Result<bool, String> {
+        let started = Instant::now();
+        while self.ids.len() < self.plan.nodes.len() {
+            let id = NodeId(self.ids.len());
+            let graph = &mut self.graph;
+            let id = self.plan.realize_nth(graph, external, &self.ids, id)?;
+            self.ids.push(id);
+            if started.elapsed() >= budget {
+                break;
+            }
+        }
+
+        if self.ids.len() < self.plan.nodes.len() {
+            return Ok(false);
+        }
+        self.plan.realize_links(&mut self.graph, &self.ids)?;
+        Ok(true)
+    }
+
+    /// The plan, and the graph realized from it so far.
+    pub fn into_parts(self) -> (Plan, ShaderGraph) { (self.plan, self.graph) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lisp::tests::plan;
+
+    const GRAPH: &str = "(let a (shader \"a\" 8 8))
+                         (let b (shader \"b\" 8 8 a))
+                         (output b)";
+
+    fn sources(a: &str, b: &str) -> Plan {
+        plan(GRAPH, &[("a", a), ("b", b)]).unwrap()
+    }
+
+    #[test]
+    fn patches_changed_shaders_only() {
+        let old = sources("void main() {}", "void main() {}");
+        let new = sources("void main() {}", "void main() { }");
+        assert_eq!(
+            old.patches(&new),
+            Some(vec![(NodeId(1), "void main() { }".to_string())])
+        );
+        assert_eq!(old.patches(&old), Some(vec![]));
+    }
+
+    #[test]
+    fn rebuilds_on_anything_else() {
+        let old = plan(GRAPH, &[("a", ""), ("b", "")]).unwrap();
+        let rebuilt = |lisp: &str| {
+            let new = plan(lisp, &[("a", ""), ("b", "")]).unwrap();
+            old.patches(&new)
+        };
+        // resized, rewired, made recurrent, grown, and labeled
+        let resized = GRAPH.replace("\"b\" 8 8", "\"b\" 16 8");
+        assert_eq!(rebuilt(&resized), None);
+        let rewired = GRAPH.replace("8 8 a", "8 8");
+        assert_eq!(rebuilt(&rewired), None);
+        let recurrent = GRAPH.replace("(shader \"b\"", "(shader-rec \"b\"");
+        assert_eq!(rebuilt(&recurrent), None);
+        let grown = format!("{} (let c (shader \"a\" 8 8))", GRAPH);
+        assert_eq!(rebuilt(&grown), None);
+        let labeled = GRAPH.replace("8 8 a", "8 8 a :label \"b\"");
+        assert_eq!(rebuilt(&labeled), None);
+    }
+
+    #[test]
+    fn finds_static_nodes() {
+        let plan = plan(
+            "(let lut (shader \"still\" 8 8))
+             (let moving (shader \"moving\" 8 8 lut))
+             (let after (shader \"still\" 8 8 moving))
+             (let trail (shader-rec \"still\" 8 8 lut))
+             (output after)",
+            &[
+                ("still", "void main() {}"),
+                ("moving", "uniform float u_time;"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(plan.statics(), [NodeId(0)].iter().copied().collect());
+        assert!(plan.cache_key(NodeId(0)).is_ok());
+        assert!(plan.cache_key(NodeId(1)).is_err());
+        assert!(plan.cache_key(NodeId(2)).is_err());
+        assert!(plan.cache_key(NodeId(3)).is_err());
+    }
+}
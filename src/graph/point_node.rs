@@ -0,0 +1,108 @@
+use std::rc::Rc;
+
+use glium::{
+    backend::Context,
+    index::{
+        NoIndices,
+        PrimitiveType,
+    },
+    uniforms::{
+        AsUniformValue,
+        UniformValue,
+    },
+    Blend,
+    DrawParameters,
+    Program,
+    Surface,
+    Texture2d,
+    VertexBuffer,
+};
+
+use crate::{
+    graph::{
+        node::Node,
+        NodeId,
+        UniformMap,
+    },
+    points::Point,
+    util::{
+        compile_point_shader,
+        RectStrip,
+    },
+};
+
+/// Renders a point cloud into a texture.
+/// Each point is drawn with the built-in point vertex
+/// shader and the node's fragment shader, blended
+/// additively so dense regions glow.
+pub struct PointNode {
+    pub shader:     Program,
+    pub inputs:     Vec<NodeId>,
+    pub points:     VertexBuffer<Point>,
+    pub texture:    Texture2d,
+    pub point_size: f32,
+}
+
+impl std::fmt::Debug for PointNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PointNode")
+            .field("inputs", &self.inputs)
+            .field("points", &self.points.len())
+            .finish()
+    }
+}
+
+impl Node for PointNode {
+    fn inputs(&self) -> Vec<NodeId> { self.inputs.to_owned() }
+
+    fn outputs(&self) -> (&str, UniformValue<'_>) {
+        ("texture", self.texture.as_uniform_value())
+    }
+
+    fn texture(&self) -> Option<&Texture2d> { Some(&self.texture) }
+
+    fn program(&self) -> Option<&Program> { Some(&self.shader) }
+
+    fn patch(
+        &mut self,
+        context: &Rc<Context>,
+        source: &str,
+    ) -> Result<(), String> {
+        self.shader = compile_point_shader(context, source)?;
+        Ok(())
+    }
+
+    fn forward(&mut self, _rect_strip: &RectStrip, uniforms: UniformMap) {
+        let resolution = [
+            self.texture.get_width() as f32,
+            self.texture.get_height().unwrap() as f32,
+        ];
+
+        let mut uniforms = uniforms;
+        uniforms.add_resolution(resolution);
+
+        let params = DrawParameters {
+            blend: Blend {
+                color: glium::BlendingFunction::Addition {
+                    source:      glium::LinearBlendingFactor::One,
+                    destination: glium::LinearBlendingFactor::One,
+                },
+                ..Default::default()
+            },
+            point_size: Some(self.point_size),
+            ..Default::default()
+        };
+
+        let mut surface = self.texture.as_surface();
+        surface.clear_color(0.0, 0.0, 0.0, 0.0);
+        surface
+            .draw(
+                &self.points,
+                NoIndices(PrimitiveType::Points),
+                &self.shader,
+                &uniforms,
+                &params,
+            )
+            .unwrap();
+    }
+}
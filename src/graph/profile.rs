@@ -0,0 +1,74 @@
+use serde_json::{
+    json,
+    Value,
+};
+
+/// Settings for one way of running a graph, e.g. quickly
+/// while iterating on it, or at full quality for an export,
+/// declared in the lisp with `(profile 'dev :scale 0.5)`.
+/// A graph can declare several, and is built with the one
+/// selected, if any, see [`crate::lisp::BuildOptions`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub name:        String,
+    /// Scales the size of every node, on top of the render
+    /// scale the graph is built with.
+    pub scale:       f32,
+    /// Renders every node this many times larger in each
+    /// direction, for the output to be averaged back down,
+    /// see [`crate::graph::ShaderGraph::supersample`].
+    pub supersample: u32,
+    /// Names `#define`d as 1 in every fragment shader.
+    pub defines:     Vec<String>,
+}
+
+impl Profile {
+    /// A profile that changes nothing.
+    pub fn new(name: &str) -> Profile {
+        Profile {
+            name:        name.to_string(),
+            scale:       1.0,
+            supersample: 1,
+            defines:     vec![],
+        }
+    }
+
+    /// How much larger every node is, all told.
+    pub fn render_scale(&self) -> f32 { self.scale * self.supersample as f32 }
+
+    /// The `#define`s of the profile, to insert after the
+    /// `#version` line of every fragment shader.
+    pub fn preamble(&self) -> String {
+        self.defines
+            .iter()
+            .map(|name| format!("#define {} 1\n", name))
+            .collect()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "scale": self.scale,
+            "supersample": self.supersample,
+            "defines": self.defines,
+        })
+    }
+
+    /// Reads what [`Profile::to_json`] wrote.
+    pub fn from_json(json: &Value) -> Result<Profile, String> {
+        let malformed = || "Malformed `profile` in the plan".to_string();
+        let defines = json["defines"]
+            .as_array()
+            .ok_or_else(malformed)?
+            .iter()
+            .map(|name| name.as_str().map(str::to_string).ok_or_else(malformed))
+            .collect::<Result<_, _>>()?;
+        Ok(Profile {
+            name: json["name"].as_str().ok_or_else(malformed)?.to_string(),
+            scale: json["scale"].as_f64().ok_or_else(malformed)? as f32,
+            supersample: json["supersample"].as_u64().ok_or_else(malformed)?
+                as u32,
+            defines,
+        })
+    }
+}
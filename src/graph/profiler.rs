@@ -0,0 +1,133 @@
+use std::{
+    collections::{
+        BTreeMap,
+        VecDeque,
+    },
+    rc::Rc,
+    time::Duration,
+};
+
+use glium::backend::Context;
+
+use crate::{
+    graph::NodeId,
+    util::timer::{
+        self,
+        Query,
+    },
+};
+
+/// Frames whose queries are left running before waiting on
+/// the oldest, as results arrive a few frames late.
+const IN_FLIGHT: usize = 4;
+
+/// How long the GPU spent on each node in one forward pass,
+/// see [`crate::graph::ShaderGraph::timings`].
+#[derive(Debug, Clone, Default)]
+pub struct Timings {
+    /// The forward pass measured, as `u_frame`.
+    pub frame: i32,
+    /// Every node that ran, including all of its substeps.
+    pub nodes: BTreeMap<NodeId, Duration>,
+}
+
+impl Timings {
+    /// The time of every node together.
+    pub fn total(&self) -> Duration { self.nodes.values().sum() }
+}
+
+/// Times every node with GPU timer queries. Results are
+/// read back a few frames later, so that measuring doesn't
+/// wait on the GPU.
+pub struct Profiler {
+    context: Rc<Context>,
+    /// The queries of the pass being run, by node.
+    current: Vec<(NodeId, Query)>,
+    pending: VecDeque<(i32, Vec<(NodeId, Query)>)>,
+    free:    Vec<Query>,
+    last:    Option<Timings>,
+}
+
+impl Profiler {
+    /// Fails if timer queries aren't loaded, see
+    /// [`timer::load_with`].
+    pub fn new(context: &Rc<Context>) -> Result<Profiler, String> {
+        if !timer::is_loaded() {
+            return Err("GPU timer queries are not supported".into());
+        }
+        Ok(Profiler {
+            context: context.clone(),
+            current: vec![],
+            pending: VecDeque::new(),
+            free:    vec![],
+            last:    None,
+        })
+    }
+
+    /// Starts timing a node, up to [`Profiler::end`].
+    pub fn begin(&mut self, id: NodeId) {
+        let query = match self.free.pop() {
+            Some(query) => query,
+            // unwrap: checked when created
+            None => Query::new(&self.context).unwrap(),
+        };
+        query.begin(&self.context);
+        self.current.push((id, query));
+    }
+
+    pub fn end(&mut self) {
+        if let Some((_, query)) = self.current.last() {
+            query.end(&self.context);
+        }
+    }
+
+    /// Finishes the pass, and reads back the results of
+    /// earlier passes the GPU is done with.
+    pub fn end_frame(&mut self, frame: i32) {
+        let current = std::mem::take(&mut self.current);
+        self.pending.push_back((frame, current));
+        while let Some((_, queries)) = self.pending.front() {
+            let wait = self.pending.len() > IN_FLIGHT;
+            // queries finish in order, so the last is enough
+            let ready = match queries.last() {
+                Some((_, query)) => {
+                    wait || query.result(&self.context, false).is_some()
+                },
+                None => true,
+            };
+            if !ready {
+                break;
+            }
+
+            // unwrap: there is a front
+            let (frame, queries) = self.pending.pop_front().unwrap();
+            let mut timings = Timings {
+                frame,
+                nodes: BTreeMap::new(),
+            };
+            for (id, query) in queries {
+                // unwrap: waits for the result
+                let time = query.result(&self.context, true).unwrap();
+                *timings.nodes.entry(id).or_default() += time;
+                self.free.push(query);
+            }
+            self.last = Some(timings);
+        }
+    }
+
+    /// The latest pass with results.
+    pub fn timings(&self) -> Option<&Timings> { self.last.as_ref() }
+}
+
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        let pending = self.pending.drain(..).flat_map(|(_, queries)| queries);
+        let queries = self.current.drain(..).chain(pending);
+        for (_, query) in queries {
+            query.delete(&self.context);
+        }
+        for query in self.free.drain(..) {
+            query.delete(&self.context);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::rc::Rc;
+
+use glium::{
+    backend::Context,
+    uniforms::{
+        AsUniformValue,
+        UniformValue,
+    },
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::{
+    graph::{
+        node::Node,
+        NodeId,
+        UniformMap,
+    },
+    util::{
+        compile_shader,
+        default_buffer,
+        RectStrip,
+    },
+};
+
+/// Repeatedly downsamples its input with a shader, each
+/// level half the size of the one before, rounded up, see
+/// [`PyramidNode::sizes`]. The smallest
+/// level is this node's output, and every level is passed
+/// as `u_level_0, .., u_level_n` to the nodes that take
+/// this one as an input, largest first.
+pub struct PyramidNode {
+    pub context: Rc<Context>,
+    pub shader:  Program,
+    pub input:   NodeId,
+    pub depth:   u32,
+    /// Rebuilt whenever the input changes size.
+    pub levels:  Vec<Texture2d>,
+}
+
+impl std::fmt::Debug for PyramidNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PyramidNode")
+            .field("input", &self.input)
+            .field("depth", &self.depth)
+            .finish()
+    }
+}
+
+impl PyramidNode {
+    pub fn new(
+        context: &Rc<Context>,
+        shader: Program,
+        input: NodeId,
+        depth: u32,
+    ) -> PyramidNode {
+        let depth = depth.clamp(1, 16);
+        PyramidNode {
+            context: context.clone(),
+            shader,
+            input,
+            depth,
+            levels: (0..depth).map(|_| default_buffer(context, 1, 1)).collect(),
+        }
+    }
+
+    /// The size of each level for an input of a given size,
+    /// largest first. Odd sizes are rounded up, as
+    /// [`crate::graph::StatsNode`] does, so that the last
+    /// row and column of every level are still covered by
+    /// the next, e.g. 1919 pixels become 960, then 480.
+    pub fn sizes(width: u32, height: u32, depth: u32) -> Vec<(u32, u32)> {
+        let mut size = (width.max(1), height.max(1));
+        (0..depth)
+            .map(|_| {
+                size = (size.0.div_ceil(2), size.1.div_ceil(2));
+                size
+            })
+            .collect()
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        let sizes = PyramidNode::sizes(width, height, self.depth);
+        if self.levels[0].dimensions() == sizes[0] {
+            return;
+        }
+
+        self.levels = sizes
+            .into_iter()
+            .map(|(width, height)| default_buffer(&self.context, width, height))
+            .collect();
+    }
+}
+
+impl Node for PyramidNode {
+    fn inputs(&self) -> Vec<NodeId> { vec![self.input] }
+
+    fn outputs(&self) -> (&str, UniformValue<'_>) {
+        ("texture", self.levels.last().unwrap().as_uniform_value())
+    }
+
+    fn uniforms(&self) -> Vec<(String, UniformValue<'_>)> {
+        self.levels
+            .iter()
+            .map(|level| ("level".to_string(), level.as_uniform_value()))
+            .collect()
+    }
+
+    fn texture(&self) -> Option<&Texture2d> { self.levels.last() }
+
+    fn program(&self) -> Option<&Program> { Some(&self.shader) }
+
+    fn patch(
+        &mut self,
+        context: &Rc<Context>,
+        source: &str,
+    ) -> Result<(), String> {
+        self.shader = compile_shader(context, source)?;
+        Ok(())
+    }
+
+    fn forward(&mut self, rect_strip: &RectStrip, uniforms: UniformMap) {
+        let input = match uniforms.get("texture", 0) {
+            Some(UniformValue::Texture2d(texture, _)) => *texture,
+            _ => {
+                eprintln!("[warn] Pyramid node expects a texture input");
+                return;
+            },
+        };
+        self.resize(input.width(), input.height());
+
+        for (index, level) in self.levels.iter().enumerate() {
+            let source = match index {
+                0 => input,
+                _ => &self.levels[index - 1],
+            };
+            let resolution = [level.width() as f32, level.height() as f32];
+            // not quite twice the level's own for odd sizes
+            let source_resolution =
+                [source.width() as f32, source.height() as f32];
+
+            let mut uniforms = uniforms.clone();
+            uniforms.set("texture", 0, source.as_uniform_value());
+            uniforms.add_resolution(resolution);
+            uniforms.add(
+                "texture_resolution",
+                UniformValue::Vec2(source_resolution),
+            );
+            uniforms.add("level", UniformValue::SignedInt(index as i32));
+
+            level
+                .as_surface()
+                .draw(
+                    &rect_strip.buffer,
+                    rect_strip.indices,
+                    &self.shader,
+                    &uniforms,
+                    &Default::default(),
+                )
+                .unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounds_odd_levels_up() {
+        assert_eq!(PyramidNode::sizes(1080, 1919, 4), vec![
+            (540, 960),
+            (270, 480),
+            (135, 240),
+            (68, 120),
+        ]);
+    }
+
+    #[test]
+    fn every_level_covers_the_one_before() {
+        for &(width, height) in &[(1080, 1919), (1, 1), (3, 1001)] {
+            let mut before = (width, height);
+            for size in PyramidNode::sizes(width, height, 12) {
+                assert!(size.0 >= 1 && size.1 >= 1);
+                assert!(size.0 * 2 >= before.0 && size.1 * 2 >= before.1);
+                before = size;
+            }
+        }
+    }
+}
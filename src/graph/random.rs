@@ -0,0 +1,130 @@
+use std::{
+    fmt,
+    rc::Rc,
+    str::FromStr,
+};
+
+use glium::{
+    backend::Context,
+    texture::{
+        ClientFormat,
+        MipmapsOption,
+        RawImage2d,
+        UncompressedFloatFormat,
+    },
+    Texture2d,
+};
+
+/// How the values of a [`RandomTexture`] are spread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Distribution {
+    /// Evenly from 0 to 1, the default.
+    Uniform,
+    /// Normally, with a mean of 0 and a deviation of 1.
+    Gaussian,
+}
+
+impl FromStr for Distribution {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Distribution, String> {
+        match name {
+            "uniform" => Ok(Distribution::Uniform),
+            "gaussian" | "normal" => Ok(Distribution::Gaussian),
+            other => Err(format!(
+                "Unknown distribution `{}`, expected `uniform` or `gaussian`",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Distribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Distribution::Uniform => "uniform",
+            Distribution::Gaussian => "gaussian",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A texture of random floats, four independent ones per
+/// pixel, made from a seed, so it's the same every time the
+/// graph is built, unless it's asked to change, see
+/// [`crate::graph::ShaderGraph::add_random`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RandomTexture {
+    pub width:           u32,
+    pub height:          u32,
+    pub distribution:    Distribution,
+    pub seed:            u32,
+    /// Whether it's made from a new seed every time the
+    /// graph is rebuilt, rather than kept.
+    pub regen_on_reload: bool,
+}
+
+impl RandomTexture {
+    /// The values of every pixel from a seed, red, green,
+    /// blue, then alpha, from the bottom row up.
+    pub fn values(&self, seed: u32) -> Vec<f32> {
+        let count = self.width as usize * self.height as usize * 4;
+        let mut rng = SplitMix(seed as u64);
+        match self.distribution {
+            Distribution::Uniform => (0..count).map(|_| rng.unit()).collect(),
+            Distribution::Gaussian => {
+                // Box-Muller makes two at a time
+                let mut values = Vec::with_capacity(count + 1);
+                while values.len() < count {
+                    let (u, v) = (1.0 - rng.unit(), rng.unit());
+                    let radius = (-2.0 * u.ln()).sqrt();
+                    let angle = std::f32::consts::TAU * v;
+                    values.push(radius * angle.cos());
+                    values.push(radius * angle.sin());
+                }
+                values.truncate(count);
+                values
+            },
+        }
+    }
+
+    /// Uploads the values from a seed as a float texture.
+    pub fn texture(
+        &self,
+        context: &Rc<Context>,
+        seed: u32,
+    ) -> Result<Texture2d, String> {
+        let values = self.values(seed);
+        let raw = RawImage2d {
+            data:   std::borrow::Cow::Borrowed(&values[..]),
+            width:  self.width,
+            height: self.height,
+            format: ClientFormat::F32F32F32F32,
+        };
+        Texture2d::with_format(
+            context,
+            raw,
+            UncompressedFloatFormat::F32F32F32F32,
+            MipmapsOption::NoMipmap,
+        )
+        .map_err(|e| format!("Could not upload random texture: {}", e))
+    }
+}
+
+/// A small, fast generator, good enough for noise.
+struct SplitMix(u64);
+
+impl SplitMix {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// From 0 up to, but not including, 1.
+    fn unit(&mut self) -> f32 {
+        (self.next() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
@@ -0,0 +1,104 @@
+use std::rc::Rc;
+
+use glium::{
+    backend::Context,
+    uniforms::UniformValue,
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::{
+    graph::{
+        node::Node,
+        Buffer,
+        NodeId,
+        TextureOptions,
+        UniformMap,
+    },
+    util::{
+        compile_shader,
+        RectStrip,
+    },
+};
+
+/// Runs a single shader several times per frame,
+/// ping-ponging between two textures. On every iteration
+/// after the first, the input at `feedback` is replaced by
+/// the output of the previous iteration. The current
+/// iteration is passed as `u_iteration`.
+pub struct RepeatNode {
+    pub shader:     Program,
+    pub inputs:     Vec<NodeId>,
+    /// Index of the input fed back into the shader.
+    pub feedback:   usize,
+    pub iterations: u32,
+    pub buffer:     Buffer,
+    /// How the buffer is stored and sampled.
+    pub options:    TextureOptions,
+}
+
+impl std::fmt::Debug for RepeatNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RepeatNode")
+            .field("inputs", &self.inputs)
+            .field("feedback", &self.feedback)
+            .field("iterations", &self.iterations)
+            .finish()
+    }
+}
+
+impl Node for RepeatNode {
+    fn inputs(&self) -> Vec<NodeId> { self.inputs.to_owned() }
+
+    fn outputs(&self) -> (&str, UniformValue<'_>) {
+        ("texture", self.options.sampled(self.buffer.front()))
+    }
+
+    fn texture(&self) -> Option<&Texture2d> { Some(self.buffer.front()) }
+
+    fn program(&self) -> Option<&Program> { Some(&self.shader) }
+
+    fn patch(
+        &mut self,
+        context: &Rc<Context>,
+        source: &str,
+    ) -> Result<(), String> {
+        self.shader = compile_shader(context, source)?;
+        Ok(())
+    }
+
+    fn forward(&mut self, rect_strip: &RectStrip, uniforms: UniformMap) {
+        let front = self.buffer.front();
+        let resolution =
+            [front.get_width() as f32, front.get_height().unwrap() as f32];
+
+        for iteration in 0..self.iterations {
+            let mut uniforms = uniforms.clone();
+            uniforms.add_resolution(resolution);
+            uniforms
+                .add("iteration", UniformValue::SignedInt(iteration as i32));
+
+            // the first iteration reads the actual input
+            if iteration > 0 {
+                self.buffer.swap();
+            }
+            let front = self.buffer.front();
+            if iteration > 0 {
+                let back = self.options.sampled(self.buffer.back().unwrap());
+                uniforms.set("texture", self.feedback, back);
+            }
+
+            front
+                .as_surface()
+                .draw(
+                    &rect_strip.buffer,
+                    rect_strip.indices,
+                    &self.shader,
+                    &uniforms,
+                    &Default::default(),
+                )
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::rc::Rc;
+
+use glium::{
+    backend::Context,
+    uniform,
+    uniforms::{
+        AsUniformValue,
+        UniformValue,
+    },
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::{
+    graph::{
+        node::Node,
+        Buffer,
+        NodeId,
+        UniformMap,
+    },
+    util::{
+        compile_shader,
+        default_buffer,
+        RectStrip,
+    },
+};
+
+/// Blends its input with the previous output, moved along a
+/// velocity texture such as the output of a flow node. High
+/// blend values smooth noise over time, or leave motion
+/// trails when history isn't clamped.
+pub struct ReprojectNode {
+    pub context: Rc<Context>,
+    pub input:   NodeId,
+    pub motion:  NodeId,
+    pub program: Program,
+    pub buffer:  Buffer,
+    /// How much of the history to keep each frame.
+    pub blend:   f32,
+    /// Whether history is limited to the colors around each
+    /// pixel in the input.
+    pub clamp:   bool,
+    /// Whether the history holds a previous frame yet.
+    primed:      bool,
+}
+
+impl std::fmt::Debug for ReprojectNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReprojectNode")
+            .field("input", &self.input)
+            .field("motion", &self.motion)
+            .field("blend", &self.blend)
+            .field("clamp", &self.clamp)
+            .finish()
+    }
+}
+
+impl ReprojectNode {
+    pub fn new(
+        context: &Rc<Context>,
+        input: NodeId,
+        motion: NodeId,
+        blend: f32,
+        clamp: bool,
+    ) -> Result<ReprojectNode, String> {
+        Ok(ReprojectNode {
+            context: context.clone(),
+            input,
+            motion,
+            program: compile_shader(
+                context,
+                include_str!("./shaders/reproject.frag"),
+            )?,
+            buffer: Buffer::new_double(|| default_buffer(context, 1, 1)),
+            blend,
+            clamp,
+            primed: false,
+        })
+    }
+}
+
+impl Node for ReprojectNode {
+    fn inputs(&self) -> Vec<NodeId> { vec![self.input, self.motion] }
+
+    fn outputs(&self) -> (&str, UniformValue<'_>) {
+        ("texture", self.buffer.front().as_uniform_value())
+    }
+
+    fn texture(&self) -> Option<&Texture2d> { Some(self.buffer.front()) }
+
+    fn state(&self) -> Vec<&Texture2d> { self.buffer.state() }
+
+    fn forward(&mut self, rect_strip: &RectStrip, uniforms: UniformMap) {
+        let (input, velocity) =
+            match (uniforms.get("texture", 0), uniforms.get("texture", 1)) {
+                (
+                    Some(UniformValue::Texture2d(input, _)),
+                    Some(UniformValue::Texture2d(velocity, _)),
+                ) => (*input, *velocity),
+                _ => {
+                    eprintln!(
+                        "[warn] Reproject node expects an input and a \
+                         velocity texture"
+                    );
+                    return;
+                },
+            };
+
+        // history is dropped if the input changes size
+        if self.buffer.front().dimensions() != input.dimensions() {
+            let (width, height) = input.dimensions();
+            let context = &self.context;
+            self.buffer =
+                Buffer::new_double(|| default_buffer(context, width, height));
+            self.primed = false;
+        }
+
+        self.buffer.swap();
+        let front = self.buffer.front();
+        let history = self.buffer.back().unwrap();
+        front
+            .as_surface()
+            .draw(
+                &rect_strip.buffer,
+                rect_strip.indices,
+                &self.program,
+                &uniform! {
+                    u_input: input,
+                    u_velocity: velocity,
+                    u_history: history,
+                    u_blend: if self.primed { self.blend } else { 0.0 },
+                    u_clamp: self.clamp,
+                },
+                &Default::default(),
+            )
+            .unwrap();
+        self.primed = true;
+    }
+}
@@ -1,8 +1,8 @@
+use std::rc::Rc;
+
 use glium::{
-    uniforms::{
-        AsUniformValue,
-        UniformValue,
-    },
+    backend::Context,
+    uniforms::UniformValue,
     Program,
     Surface,
     Texture2d,
@@ -12,9 +12,13 @@ use crate::{
     graph::{
         node::Node,
         NodeId,
+        TextureOptions,
         UniformMap,
     },
-    util::RectStrip,
+    util::{
+        compile_shader,
+        RectStrip,
+    },
 };
 
 pub enum Buffer {
@@ -40,6 +44,15 @@ impl Buffer {
         }
     }
 
+    /// Both textures of a double buffer, none of a single
+    /// one, as only double buffers hold on to past frames.
+    pub fn state(&self) -> Vec<&Texture2d> {
+        match self {
+            Buffer::Single(_) => vec![],
+            Buffer::Double(front, back) => vec![front, back],
+        }
+    }
+
     pub fn swap(&mut self) {
         // Using `Box`, the swap is cheap.
         if let Buffer::Double(ref mut front, ref mut back) = self {
@@ -54,9 +67,11 @@ impl Buffer {
 /// and the texture it owns that is updated in each forward
 /// pass.
 pub struct ShaderNode {
-    pub shader: Program,
-    pub inputs: Vec<NodeId>,
-    pub buffer: Buffer,
+    pub shader:  Program,
+    pub inputs:  Vec<NodeId>,
+    pub buffer:  Buffer,
+    /// How the buffer is stored and sampled.
+    pub options: TextureOptions,
 }
 
 impl std::fmt::Debug for ShaderNode {
@@ -70,19 +85,25 @@ impl std::fmt::Debug for ShaderNode {
 impl Node for ShaderNode {
     fn inputs(&self) -> Vec<NodeId> { self.inputs.to_owned() }
 
-    fn outputs(&self) -> (&str, UniformValue) {
-        match self.buffer {
-            Buffer::Single(ref texture) => {
-                ("texture", texture.as_uniform_value())
-            },
-            Buffer::Double(ref texture, _) => {
-                ("texture", texture.as_uniform_value())
-            },
-        }
+    fn outputs(&self) -> (&str, UniformValue<'_>) {
+        ("texture", self.options.sampled(self.buffer.front()))
     }
 
     fn texture(&self) -> Option<&Texture2d> { Some(self.buffer.front()) }
 
+    fn program(&self) -> Option<&Program> { Some(&self.shader) }
+
+    fn state(&self) -> Vec<&Texture2d> { self.buffer.state() }
+
+    fn patch(
+        &mut self,
+        context: &Rc<Context>,
+        source: &str,
+    ) -> Result<(), String> {
+        self.shader = compile_shader(context, source)?;
+        Ok(())
+    }
+
     fn forward(&mut self, rect_strip: &RectStrip, uniforms: UniformMap) {
         self.buffer.swap();
 
@@ -91,9 +112,9 @@ impl Node for ShaderNode {
             [front.get_width() as f32, front.get_height().unwrap() as f32];
 
         let mut uniforms = uniforms;
-        uniforms.add("resolution", UniformValue::Vec2(resolution));
+        uniforms.add_resolution(resolution);
         if let Some(back) = self.buffer.back() {
-            uniforms.add("previous", back.as_uniform_value());
+            uniforms.add("previous", self.options.sampled(back));
         }
 
         // taking the previous inputs,
@@ -103,7 +124,7 @@ impl Node for ShaderNode {
             .as_surface()
             .draw(
                 &rect_strip.buffer,
-                &rect_strip.indices,
+                rect_strip.indices,
                 &self.shader,
                 &uniforms,
                 &Default::default(),
@@ -0,0 +1,56 @@
+#version 140
+
+// Lucas-Kanade optical flow between two luminance frames.
+// Solves for the motion that best explains the brightness
+// change over a window around each pixel. Flow is written
+// to rg in pixels per frame, and its confidence to b.
+
+uniform sampler2D u_current;
+uniform sampler2D u_previous;
+uniform int u_window;
+
+out vec4 color;
+
+float luma(sampler2D tex, ivec2 p, ivec2 size) {
+    return texelFetch(tex, clamp(p, ivec2(0), size - 1), 0).r;
+}
+
+void main() {
+    ivec2 size = textureSize(u_current, 0);
+    ivec2 center = ivec2(gl_FragCoord.xy);
+
+    // structure tensor and mismatch vector
+    vec3 tensor = vec3(0.);
+    vec2 mismatch = vec2(0.);
+
+    for (int y = -u_window; y <= u_window; y++) {
+        for (int x = -u_window; x <= u_window; x++) {
+            ivec2 p = center + ivec2(x, y);
+            float dx = luma(u_current, p + ivec2(1, 0), size)
+                - luma(u_current, p - ivec2(1, 0), size);
+            float dy = luma(u_current, p + ivec2(0, 1), size)
+                - luma(u_current, p - ivec2(0, 1), size);
+            float dt = luma(u_current, p, size) - luma(u_previous, p, size);
+            vec2 grad = vec2(dx, dy) * 0.5;
+
+            tensor += vec3(grad.x * grad.x, grad.x * grad.y, grad.y * grad.y);
+            mismatch -= grad * dt;
+        }
+    }
+
+    float det = tensor.x * tensor.z - tensor.y * tensor.y;
+    if (abs(det) < 1e-7) {
+        color = vec4(0.);
+        return;
+    }
+
+    vec2 flow = vec2(
+        tensor.z * mismatch.x - tensor.y * mismatch.y,
+        tensor.x * mismatch.y - tensor.y * mismatch.x) / det;
+
+    // the smaller eigenvalue measures how well constrained the
+    // solution is, i.e. whether the window contains a corner
+    float trace = tensor.x + tensor.z;
+    float lambda = 0.5 * (trace - sqrt(max(trace * trace - 4. * det, 0.)));
+    color = vec4(flow, lambda, 1.);
+}
@@ -0,0 +1,22 @@
+#version 140
+
+// Moves a field along the velocity, fading it by the
+// dissipation and adding the source on top, scaled to the
+// share of the frame being stepped.
+
+uniform sampler2D u_field;
+uniform sampler2D u_velocity;
+uniform sampler2D u_source;
+uniform float u_dissipation;
+uniform float u_step;
+
+in vec2 coords;
+out vec4 color;
+
+void main() {
+    vec2 size = vec2(textureSize(u_field, 0));
+    vec2 velocity = texture(u_velocity, coords).rg;
+    vec4 moved = texture(u_field, coords - velocity * u_step / size);
+    vec4 source = texture(u_source, coords) * u_step;
+    color = moved * pow(u_dissipation, u_step) + source;
+}
@@ -0,0 +1,23 @@
+#version 140
+
+uniform sampler2D u_velocity;
+
+out vec4 color;
+
+vec2 velocity(ivec2 p, ivec2 size) {
+    // no flow through the walls
+    if (p.x < 0 || p.y < 0 || p.x >= size.x || p.y >= size.y) {
+        return vec2(0.);
+    }
+    return texelFetch(u_velocity, p, 0).rg;
+}
+
+void main() {
+    ivec2 size = textureSize(u_velocity, 0);
+    ivec2 p = ivec2(gl_FragCoord.xy);
+    float dx = velocity(p + ivec2(1, 0), size).x
+        - velocity(p - ivec2(1, 0), size).x;
+    float dy = velocity(p + ivec2(0, 1), size).y
+        - velocity(p - ivec2(0, 1), size).y;
+    color = vec4(0.5 * (dx + dy), 0., 0., 1.);
+}
@@ -0,0 +1,24 @@
+#version 140
+
+// A single Jacobi iteration of the pressure Poisson
+// equation. More iterations give a less compressible fluid.
+
+uniform sampler2D u_pressure;
+uniform sampler2D u_divergence;
+
+out vec4 color;
+
+float pressure(ivec2 p, ivec2 size) {
+    return texelFetch(u_pressure, clamp(p, ivec2(0), size - 1), 0).r;
+}
+
+void main() {
+    ivec2 size = textureSize(u_pressure, 0);
+    ivec2 p = ivec2(gl_FragCoord.xy);
+    float around = pressure(p + ivec2(1, 0), size)
+        + pressure(p - ivec2(1, 0), size)
+        + pressure(p + ivec2(0, 1), size)
+        + pressure(p - ivec2(0, 1), size);
+    float divergence = texelFetch(u_divergence, p, 0).r;
+    color = vec4((around - divergence) * 0.25, 0., 0., 1.);
+}
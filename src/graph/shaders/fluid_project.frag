@@ -0,0 +1,23 @@
+#version 140
+
+// Subtracts the pressure gradient from the velocity,
+// leaving it divergence free.
+
+uniform sampler2D u_velocity;
+uniform sampler2D u_pressure;
+
+out vec4 color;
+
+float pressure(ivec2 p, ivec2 size) {
+    return texelFetch(u_pressure, clamp(p, ivec2(0), size - 1), 0).r;
+}
+
+void main() {
+    ivec2 size = textureSize(u_velocity, 0);
+    ivec2 p = ivec2(gl_FragCoord.xy);
+    vec2 gradient = 0.5 * vec2(
+        pressure(p + ivec2(1, 0), size) - pressure(p - ivec2(1, 0), size),
+        pressure(p + ivec2(0, 1), size) - pressure(p - ivec2(0, 1), size));
+    vec4 velocity = texelFetch(u_velocity, p, 0);
+    color = vec4(velocity.rg - gradient, 0., 1.);
+}
@@ -0,0 +1,147 @@
+// The shadergarden helper library, inserted into shaders
+// that turn a part of it on, like `#define SG_AA 1`. Kept
+// to GLSL 1.10, so any shader can use it. Every name starts
+// with `sg_`, and each part is only inserted once.
+
+#ifdef SG_AA
+#ifndef SG_AA_INCLUDED
+#define SG_AA_INCLUDED
+
+// A step from 0 to 1 at `edge`, smoothed over a pixel.
+float sg_aastep(float edge, float x) {
+    float w = max(fwidth(x), 1e-6) * 0.5;
+    return smoothstep(edge - w, edge + w, x);
+}
+
+// How much of a pixel is inside a shape, from its signed
+// distance, negative inside.
+float sg_fill(float d) {
+    return 1.0 - sg_aastep(0.0, d);
+}
+
+// How much of a pixel is on the outline of a shape `width`
+// wide, in the units of its signed distance.
+float sg_stroke(float d, float width) {
+    return 1.0 - sg_aastep(width * 0.5, abs(d));
+}
+
+// Lines every `spacing` units of `p`, a pixel wide.
+float sg_grid(vec2 p, float spacing) {
+    vec2 cell = abs(fract(p / spacing - 0.5) - 0.5) / fwidth(p / spacing);
+    return 1.0 - clamp(min(cell.x, cell.y), 0.0, 1.0);
+}
+
+#endif
+#endif
+
+#ifdef SG_HASH
+#ifndef SG_HASH_INCLUDED
+#define SG_HASH_INCLUDED
+
+// Hashes from 0 to 1 without `sin`, so they're the same on
+// every GPU, after Dave Hoskins' "Hash without Sine" (MIT).
+
+float sg_hash11(float p) {
+    p = fract(p * 0.1031);
+    p *= p + 33.33;
+    p *= p + p;
+    return fract(p);
+}
+
+float sg_hash12(vec2 p) {
+    vec3 p3 = fract(vec3(p.xyx) * 0.1031);
+    p3 += dot(p3, p3.yzx + 33.33);
+    return fract((p3.x + p3.y) * p3.z);
+}
+
+float sg_hash13(vec3 p3) {
+    p3 = fract(p3 * 0.1031);
+    p3 += dot(p3, p3.zyx + 31.32);
+    return fract((p3.x + p3.y) * p3.z);
+}
+
+vec2 sg_hash22(vec2 p) {
+    vec3 p3 = fract(vec3(p.xyx) * vec3(0.1031, 0.1030, 0.0973));
+    p3 += dot(p3, p3.yzx + 33.33);
+    return fract((p3.xx + p3.yz) * p3.zy);
+}
+
+vec3 sg_hash33(vec3 p3) {
+    p3 = fract(p3 * vec3(0.1031, 0.1030, 0.0973));
+    p3 += dot(p3, p3.yxz + 33.33);
+    return fract((p3.xxy + p3.yxx) * p3.zyx);
+}
+
+#endif
+#endif
+
+#ifdef SG_COLOR
+#ifndef SG_COLOR_INCLUDED
+#define SG_COLOR_INCLUDED
+
+vec3 sg_srgb_to_linear(vec3 c) {
+    c = max(c, 0.0);
+    vec3 lo = c / 12.92;
+    vec3 hi = pow((c + 0.055) / 1.055, vec3(2.4));
+    return mix(lo, hi, step(0.04045, c));
+}
+
+vec3 sg_linear_to_srgb(vec3 c) {
+    c = max(c, 0.0);
+    vec3 lo = c * 12.92;
+    vec3 hi = 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055;
+    return mix(lo, hi, step(0.0031308, c));
+}
+
+// Rec. 709 luma of a linear color.
+float sg_luma(vec3 c) {
+    return dot(c, vec3(0.2126, 0.7152, 0.0722));
+}
+
+// Hue, saturation, and value, all from 0 to 1.
+vec3 sg_rgb_to_hsv(vec3 c) {
+    vec4 k = vec4(0.0, -1.0 / 3.0, 2.0 / 3.0, -1.0);
+    vec4 p = mix(vec4(c.bg, k.wz), vec4(c.gb, k.xy), step(c.b, c.g));
+    vec4 q = mix(vec4(p.xyw, c.r), vec4(c.r, p.yzx), step(p.x, c.r));
+    float d = q.x - min(q.w, q.y);
+    float e = 1.0e-10;
+    return vec3(abs(q.z + (q.w - q.y) / (6.0 * d + e)), d / (q.x + e), q.x);
+}
+
+vec3 sg_hsv_to_rgb(vec3 c) {
+    vec3 p = abs(fract(c.xxx + vec3(1.0, 2.0 / 3.0, 1.0 / 3.0)) * 6.0 - 3.0);
+    return c.z * mix(vec3(1.0), clamp(p - 1.0, 0.0, 1.0), c.y);
+}
+
+// Oklab, from and to linear sRGB, for mixing colors
+// evenly, after Bjorn Ottosson.
+vec3 sg_linear_to_oklab(vec3 c) {
+    vec3 lms = vec3(
+        dot(c, vec3(0.4122214708, 0.5363325363, 0.0514459929)),
+        dot(c, vec3(0.2119034982, 0.6806995451, 0.1073969566)),
+        dot(c, vec3(0.0883024619, 0.2817188376, 0.6299787005))
+    );
+    lms = sign(lms) * pow(abs(lms), vec3(1.0 / 3.0));
+    return vec3(
+        dot(lms, vec3(0.2104542553, 0.7936177850, -0.0040720468)),
+        dot(lms, vec3(1.9779984951, -2.4285922050, 0.4505937099)),
+        dot(lms, vec3(0.0259040371, 0.7827717662, -0.8086757660))
+    );
+}
+
+vec3 sg_oklab_to_linear(vec3 c) {
+    vec3 lms = vec3(
+        dot(c, vec3(1.0, 0.3963377774, 0.2158037573)),
+        dot(c, vec3(1.0, -0.1055613458, -0.0638541728)),
+        dot(c, vec3(1.0, -0.0894841775, -1.2914855480))
+    );
+    lms = lms * lms * lms;
+    return vec3(
+        dot(lms, vec3(4.0767416621, -3.3077115913, 0.2309699292)),
+        dot(lms, vec3(-1.2684380046, 2.6097574011, -0.3413193965)),
+        dot(lms, vec3(-0.0041960863, -0.7034186147, 1.7076147010))
+    );
+}
+
+#endif
+#endif
@@ -0,0 +1,9 @@
+#version 140
+
+uniform float u_weight;
+
+out vec4 color;
+
+void main() {
+    color = vec4(u_weight);
+}
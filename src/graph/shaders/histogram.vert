@@ -0,0 +1,18 @@
+#version 140
+
+// Scatters one point per sample of the input into the bin
+// of its luminance. There is no vertex buffer, samples are
+// placed on a grid using `gl_VertexID`.
+
+uniform sampler2D u_input;
+uniform int u_grid;
+uniform int u_bins;
+
+void main() {
+    ivec2 cell = ivec2(gl_VertexID % u_grid, gl_VertexID / u_grid);
+    vec2 uv = (vec2(cell) + 0.5) / float(u_grid);
+    vec3 rgb = texture(u_input, uv).rgb;
+    float luma = clamp(dot(rgb, vec3(0.2126, 0.7152, 0.0722)), 0., 1.);
+    float bin = min(floor(luma * float(u_bins)), float(u_bins - 1));
+    gl_Position = vec4((bin + 0.5) / float(u_bins) * 2. - 1., 0., 0., 1.);
+}
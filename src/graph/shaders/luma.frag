@@ -0,0 +1,11 @@
+#version 140
+
+uniform sampler2D u_input;
+
+in vec2 coords;
+out vec4 color;
+
+void main() {
+    vec3 rgb = texture(u_input, coords).rgb;
+    color = vec4(dot(rgb, vec3(0.2126, 0.7152, 0.0722)));
+}
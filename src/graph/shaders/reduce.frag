@@ -0,0 +1,38 @@
+#version 140
+
+// Reduces 4x4 blocks of the input into a single texel of
+// (min, max, sum, count). On the first level, the input is
+// an arbitrary texture and luminance is computed from rgb.
+
+uniform sampler2D u_input;
+uniform bool u_first;
+
+out vec4 color;
+
+void main() {
+    ivec2 size = textureSize(u_input, 0);
+    ivec2 base = ivec2(gl_FragCoord.xy) * 4;
+    vec4 acc = vec4(1e30, -1e30, 0., 0.);
+
+    for (int y = 0; y < 4; y++) {
+        for (int x = 0; x < 4; x++) {
+            ivec2 p = base + ivec2(x, y);
+            if (p.x >= size.x || p.y >= size.y) {
+                continue;
+            }
+
+            vec4 texel = texelFetch(u_input, p, 0);
+            if (u_first) {
+                float luma = dot(texel.rgb, vec3(0.2126, 0.7152, 0.0722));
+                texel = vec4(luma, luma, luma, 1.);
+            }
+            acc = vec4(
+                min(acc.r, texel.r),
+                max(acc.g, texel.g),
+                acc.b + texel.b,
+                acc.a + texel.a);
+        }
+    }
+
+    color = acc;
+}
@@ -0,0 +1,45 @@
+#version 140
+
+// Blends the input with its own history, fetched from where
+// each pixel was last frame according to the velocity.
+
+uniform sampler2D u_input;
+uniform sampler2D u_velocity;
+uniform sampler2D u_history;
+uniform float u_blend;
+uniform bool u_clamp;
+
+in vec2 coords;
+out vec4 color;
+
+void main() {
+    vec2 size = vec2(textureSize(u_input, 0));
+    vec4 current = texture(u_input, coords);
+    vec2 velocity = texture(u_velocity, coords).rg;
+    vec2 origin = coords - velocity / size;
+
+    // history from off screen can't be trusted
+    if (any(lessThan(origin, vec2(0.))) || any(greaterThan(origin, vec2(1.)))) {
+        color = current;
+        return;
+    }
+
+    vec4 history = texture(u_history, origin);
+
+    // clamping history to the colors around the pixel rejects
+    // stale history, which removes ghosting at the cost of trails
+    if (u_clamp) {
+        vec4 lo = current;
+        vec4 hi = current;
+        for (int y = -1; y <= 1; y++) {
+            for (int x = -1; x <= 1; x++) {
+                vec4 near = texture(u_input, coords + vec2(x, y) / size);
+                lo = min(lo, near);
+                hi = max(hi, near);
+            }
+        }
+        history = clamp(history, lo, hi);
+    }
+
+    color = mix(current, history, u_blend);
+}
@@ -0,0 +1,16 @@
+#version 140
+
+// Scales the input by the adapted exposure, then maps it
+// into displayable range with the Reinhard operator.
+
+uniform sampler2D u_input;
+uniform float u_exposure;
+
+in vec2 coords;
+out vec4 color;
+
+void main() {
+    vec4 hdr = texture(u_input, coords);
+    vec3 exposed = hdr.rgb * u_exposure;
+    color = vec4(exposed / (1. + exposed), hdr.a);
+}
@@ -0,0 +1,16 @@
+#version 430
+
+// Copies the pixels a compute shader wrote to a buffer into
+// the node's texture, a row at a time from the bottom.
+
+layout(std430) buffer u_pixels {
+    vec4 pixels[];
+};
+uniform int u_width;
+
+out vec4 color;
+
+void main() {
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+    color = pixels[pixel.y * u_width + pixel.x];
+}
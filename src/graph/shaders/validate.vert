@@ -0,0 +1,15 @@
+#version 140
+
+// Emits a point for every pixel of the input that is NaN or
+// infinite, and moves every other pixel off screen. There is
+// no vertex buffer, pixels are found using `gl_VertexID`.
+
+uniform sampler2D u_input;
+
+void main() {
+    ivec2 size = textureSize(u_input, 0);
+    ivec2 pixel = ivec2(gl_VertexID % size.x, gl_VertexID / size.x);
+    vec4 value = texelFetch(u_input, pixel, 0);
+    bool invalid = any(isnan(value)) || any(isinf(value));
+    gl_Position = invalid ? vec4(0., 0., 0., 1.) : vec4(2., 2., 0., 1.);
+}
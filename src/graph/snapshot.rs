@@ -0,0 +1,164 @@
+use std::{
+    borrow::Cow,
+    convert::TryInto,
+    fs,
+    io::{
+        BufWriter,
+        Write,
+    },
+    path::Path,
+};
+
+use glium::{
+    texture::{
+        ClientFormat,
+        RawImage2d,
+    },
+    Rect,
+    Texture2d,
+};
+
+use crate::util::read_texture;
+
+/// Written at the start of every snapshot, with a version.
+const MAGIC: &[u8; 8] = b"SGSTATE1";
+
+/// The contents of a node's state textures, see
+/// [`crate::graph::ShaderGraph::save_state`].
+pub struct NodeState {
+    pub name:     String,
+    pub textures: Vec<Image>,
+}
+
+/// A texture read back to the CPU, row by row, bottom to
+/// top.
+pub struct Image {
+    pub width:  u32,
+    pub height: u32,
+    pub pixels: Vec<[f32; 4]>,
+}
+
+impl Image {
+    pub fn read(texture: &Texture2d) -> Image {
+        Image {
+            width:  texture.width(),
+            height: texture.height(),
+            pixels: read_texture(texture),
+        }
+    }
+
+    /// Uploads the image into a texture of the same size.
+    pub fn write(&self, texture: &Texture2d) {
+        let data = self.pixels.iter().flatten().copied().collect::<Vec<_>>();
+        let rect = Rect {
+            left:   0,
+            bottom: 0,
+            width:  self.width,
+            height: self.height,
+        };
+        texture.write(rect, RawImage2d {
+            data:   Cow::Owned(data),
+            width:  self.width,
+            height: self.height,
+            format: ClientFormat::F32F32F32F32,
+        });
+    }
+}
+
+/// Writes snapshots as little-endian binary: the magic
+/// bytes, then for each node its name and textures, each
+/// prefixed with a `u32` length.
+pub fn write(path: &Path, nodes: &[NodeState]) -> Result<(), String> {
+    let error = |e: std::io::Error| {
+        format!("Could not write snapshot `{}`: {}", path.display(), e)
+    };
+    let file = fs::File::create(path).map_err(error)?;
+    let mut out = BufWriter::new(file);
+    let mut put = |bytes: &[u8]| out.write_all(bytes).map_err(error);
+
+    put(MAGIC)?;
+    put(&(nodes.len() as u32).to_le_bytes())?;
+    for node in nodes {
+        put(&(node.name.len() as u32).to_le_bytes())?;
+        put(node.name.as_bytes())?;
+        put(&(node.textures.len() as u32).to_le_bytes())?;
+        for image in node.textures.iter() {
+            put(&image.width.to_le_bytes())?;
+            put(&image.height.to_le_bytes())?;
+            for channel in image.pixels.iter().flatten() {
+                put(&channel.to_le_bytes())?;
+            }
+        }
+    }
+    out.flush().map_err(error)
+}
+
+/// Reads a snapshot written by [`write`].
+pub fn read(path: &Path) -> Result<Vec<NodeState>, String> {
+    let bytes = fs::read(path).map_err(|e| {
+        format!("Could not read snapshot `{}`: {}", path.display(), e)
+    })?;
+    let invalid = || format!("`{}` is not a valid snapshot", path.display());
+
+    let mut reader = Reader { bytes: &bytes };
+    if reader.take(MAGIC.len()).ok_or_else(invalid)? != MAGIC {
+        return Err(invalid());
+    }
+
+    let mut nodes = vec![];
+    for _ in 0..reader.u32().ok_or_else(invalid)? {
+        let length = reader.u32().ok_or_else(invalid)?;
+        let name = reader.take(length as usize).ok_or_else(invalid)?;
+        let name = String::from_utf8(name.to_vec()).map_err(|_| invalid())?;
+
+        let mut textures = vec![];
+        for _ in 0..reader.u32().ok_or_else(invalid)? {
+            let width = reader.u32().ok_or_else(invalid)?;
+            let height = reader.u32().ok_or_else(invalid)?;
+            let count = width as usize * height as usize;
+            // don't trust the size before allocating for it
+            if count.saturating_mul(16) > reader.bytes.len() {
+                return Err(invalid());
+            }
+            let mut pixels = Vec::with_capacity(count);
+            for _ in 0..count {
+                let mut pixel = [0.0; 4];
+                for channel in pixel.iter_mut() {
+                    *channel = reader.f32().ok_or_else(invalid)?;
+                }
+                pixels.push(pixel);
+            }
+            textures.push(Image {
+                width,
+                height,
+                pixels,
+            });
+        }
+        nodes.push(NodeState { name, textures });
+    }
+    Ok(nodes)
+}
+
+/// Reads little-endian binary, see [`read`].
+pub(super) struct Reader<'a> {
+    pub(super) bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(super) fn take(&mut self, count: usize) -> Option<&'a [u8]> {
+        if count > self.bytes.len() {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    pub(super) fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        Some(f32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+}
@@ -0,0 +1,22 @@
+use glium::{
+    uniforms::UniformValue,
+    Texture2d,
+};
+
+/// A live input, like a camera, an audio capture, or a data
+/// feed, read by a graph as a node without inputs.
+/// Implement this trait to add sources of your own, see
+/// [`crate::graph::ShaderGraph::add_source`].
+pub trait InputSource: std::fmt::Debug {
+    /// Brings the source up to date, once per forward pass,
+    /// e.g. by uploading the newest frame.
+    fn update(&mut self);
+
+    /// The texture holding the latest input.
+    fn texture(&self) -> &Texture2d;
+
+    /// Extra named uniforms passed to every node that takes
+    /// this source as an input, e.g. values from a data
+    /// feed.
+    fn uniforms(&self) -> Vec<(String, UniformValue<'_>)> { vec![] }
+}
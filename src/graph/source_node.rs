@@ -0,0 +1,45 @@
+use glium::{
+    uniforms::{
+        AsUniformValue,
+        UniformValue,
+    },
+    Texture2d,
+};
+
+use crate::{
+    graph::{
+        node::Node,
+        InputSource,
+        NodeId,
+        UniformMap,
+    },
+    util::RectStrip,
+};
+
+/// A node that reads from an [`InputSource`], updating it
+/// every forward pass.
+#[derive(Debug)]
+pub struct SourceNode {
+    pub source: Box<dyn InputSource>,
+}
+
+impl Node for SourceNode {
+    fn inputs(&self) -> Vec<NodeId> { vec![] }
+
+    fn outputs(&self) -> (&str, UniformValue<'_>) {
+        ("texture", self.source.texture().as_uniform_value())
+    }
+
+    fn uniforms(&self) -> Vec<(String, UniformValue<'_>)> {
+        self.source.uniforms()
+    }
+
+    fn texture(&self) -> Option<&Texture2d> { Some(self.source.texture()) }
+
+    // videos play on from one frame to the next
+    fn remembers(&self) -> bool { true }
+
+    fn forward(&mut self, _rect_strip: &RectStrip, _uniforms: UniformMap) {
+        self.source.update();
+    }
+}
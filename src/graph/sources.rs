@@ -0,0 +1,33 @@
+use std::{
+    fs,
+    path::Path,
+};
+
+/// The exact text of every shader a graph is running, taken
+/// with [`crate::graph::ShaderGraph::sources`]. It outlives
+/// the graph, so that what was running can be recovered
+/// after a crash, even if the files have been edited since.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sources {
+    /// File names, e.g. `blur.frag` for a node named
+    /// `blur`, and the text in each.
+    pub files: Vec<(String, String)>,
+}
+
+impl Sources {
+    /// Writes each source into a directory, creating it if
+    /// need be. Returns the number of files written.
+    pub fn write(&self, dir: impl AsRef<Path>) -> Result<usize, String> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir).map_err(|e| {
+            format!("Could not create `{}`: {}", dir.display(), e)
+        })?;
+        for (name, text) in self.files.iter() {
+            let path = dir.join(name);
+            fs::write(&path, text).map_err(|e| {
+                format!("Could not write `{}`: {}", path.display(), e)
+            })?;
+        }
+        Ok(self.files.len())
+    }
+}
@@ -0,0 +1,278 @@
+use std::rc::Rc;
+
+use glium::{
+    backend::Context,
+    index::{
+        NoIndices,
+        PrimitiveType,
+    },
+    uniform,
+    uniforms::{
+        AsUniformValue,
+        UniformValue,
+    },
+    vertex::EmptyVertexAttributes,
+    Blend,
+    DrawParameters,
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::{
+    graph::{
+        node::Node,
+        NodeId,
+        UniformMap,
+    },
+    util::{
+        compile_shader,
+        float_buffer,
+        read_texture,
+        RectStrip,
+    },
+};
+
+/// Luminance samples along each axis of the input used to
+/// build the histogram.
+const HISTOGRAM_GRID: u32 = 256;
+
+/// Statistics about the luminance of a texture, computed
+/// every frame by a [`StatsNode`].
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub min:       f32,
+    pub max:       f32,
+    pub mean:      f32,
+    /// Fraction of samples falling in each bin, for
+    /// luminance in `[0, 1]`. Sums to one.
+    pub histogram: Vec<f32>,
+}
+
+/// Computes luminance statistics of its input on the GPU by
+/// repeatedly reducing it, then reads the small result back
+/// to the CPU. The histogram texture is this node's output,
+/// and `u_stats_min`, `u_stats_max`, and `u_stats_mean` are
+/// passed to every node that takes this one as an input.
+pub struct StatsNode {
+    pub context:   Rc<Context>,
+    pub input:     NodeId,
+    pub reduce:    Program,
+    pub scatter:   Program,
+    /// Each level is a quarter of the size of the previous
+    /// one, rebuilt whenever the input changes size.
+    pub levels:    Vec<Texture2d>,
+    pub histogram: Texture2d,
+    pub stats:     Stats,
+}
+
+impl std::fmt::Debug for StatsNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StatsNode")
+            .field("input", &self.input)
+            .field("stats", &self.stats)
+            .finish()
+    }
+}
+
+impl StatsNode {
+    pub fn new(
+        context: &Rc<Context>,
+        input: NodeId,
+        bins: u32,
+    ) -> Result<StatsNode, String> {
+        let scatter = Program::from_source(
+            context,
+            include_str!("./shaders/histogram.vert"),
+            include_str!("./shaders/histogram.frag"),
+            None,
+        )
+        .map_err(|e| format!("{}", e))?;
+
+        Ok(StatsNode {
+            context: context.clone(),
+            input,
+            reduce: compile_shader(
+                context,
+                include_str!("./shaders/reduce.frag"),
+            )?,
+            scatter,
+            levels: vec![],
+            histogram: float_buffer(context, bins.max(1), 1)?,
+            stats: Stats::default(),
+        })
+    }
+
+    /// The size of each level of the reduction chain for an
+    /// input of a given size, stopping once a level is
+    /// small enough to read back cheaply. Odd sizes are
+    /// rounded up, and `reduce.frag` skips texels past
+    /// the edge.
+    fn sizes(width: u32, height: u32) -> Vec<(u32, u32)> {
+        let mut sizes = vec![];
+        let (mut width, mut height) = (width, height);
+        loop {
+            width = width.div_ceil(4).max(1);
+            height = height.div_ceil(4).max(1);
+            sizes.push((width, height));
+            if width <= 8 && height <= 8 {
+                return sizes;
+            }
+        }
+    }
+
+    /// Rebuilds the reduction chain for an input of a given
+    /// size.
+    fn resize(&mut self, width: u32, height: u32) {
+        let sizes = StatsNode::sizes(width, height);
+        if let Some(first) = self.levels.first() {
+            if first.dimensions() == sizes[0] {
+                return;
+            }
+        }
+
+        self.levels = sizes
+            .into_iter()
+            .map(|(width, height)| {
+                float_buffer(&self.context, width, height).unwrap()
+            })
+            .collect();
+    }
+
+    /// Updates the minimum, maximum, and mean luminance of
+    /// the input, leaving the histogram untouched.
+    pub fn reduce(&mut self, rect_strip: &RectStrip, input: &Texture2d) {
+        self.resize(input.width(), input.height());
+
+        for index in 0..self.levels.len() {
+            let (previous, current) = self.levels.split_at(index);
+            let source = previous.last().unwrap_or(input);
+            current[0]
+                .as_surface()
+                .draw(
+                    &rect_strip.buffer,
+                    rect_strip.indices,
+                    &self.reduce,
+                    &uniform! {
+                        u_input: source,
+                        u_first: index == 0,
+                    },
+                    &Default::default(),
+                )
+                .unwrap();
+        }
+
+        let (mut min, mut max, mut sum, mut count) =
+            (f32::INFINITY, f32::NEG_INFINITY, 0.0, 0.0);
+        for [lo, hi, total, n] in read_texture(self.levels.last().unwrap()) {
+            min = min.min(lo);
+            max = max.max(hi);
+            sum += total;
+            count += n;
+        }
+
+        self.stats.min = min;
+        self.stats.max = max;
+        self.stats.mean = if count > 0.0 { sum / count } else { 0.0 };
+    }
+
+    fn scatter(&mut self, input: &Texture2d) {
+        let samples = HISTOGRAM_GRID * HISTOGRAM_GRID;
+        let params = DrawParameters {
+            blend: Blend {
+                color: glium::BlendingFunction::Addition {
+                    source:      glium::LinearBlendingFactor::One,
+                    destination: glium::LinearBlendingFactor::One,
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut surface = self.histogram.as_surface();
+        surface.clear_color(0.0, 0.0, 0.0, 0.0);
+        surface
+            .draw(
+                EmptyVertexAttributes {
+                    len: samples as usize,
+                },
+                NoIndices(PrimitiveType::Points),
+                &self.scatter,
+                &uniform! {
+                    u_input: input,
+                    u_grid: HISTOGRAM_GRID as i32,
+                    u_bins: self.histogram.width() as i32,
+                    u_weight: 1.0 / samples as f32,
+                },
+                &params,
+            )
+            .unwrap();
+
+        self.stats.histogram =
+            read_texture(&self.histogram).iter().map(|p| p[0]).collect();
+    }
+}
+
+impl Node for StatsNode {
+    fn inputs(&self) -> Vec<NodeId> { vec![self.input] }
+
+    fn outputs(&self) -> (&str, UniformValue<'_>) {
+        ("texture", self.histogram.as_uniform_value())
+    }
+
+    fn uniforms(&self) -> Vec<(String, UniformValue<'_>)> {
+        vec![
+            ("stats_min".to_string(), UniformValue::Float(self.stats.min)),
+            ("stats_max".to_string(), UniformValue::Float(self.stats.max)),
+            (
+                "stats_mean".to_string(),
+                UniformValue::Float(self.stats.mean),
+            ),
+        ]
+    }
+
+    fn texture(&self) -> Option<&Texture2d> { Some(&self.histogram) }
+
+    fn stats(&self) -> Option<&Stats> { Some(&self.stats) }
+
+    fn forward(&mut self, rect_strip: &RectStrip, uniforms: UniformMap) {
+        let input = match uniforms.get("texture", 0) {
+            Some(UniformValue::Texture2d(texture, _)) => *texture,
+            _ => {
+                eprintln!("[warn] Stats node expects a texture input");
+                return;
+            },
+        };
+
+        self.reduce(rect_strip, input);
+        self.scatter(input);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reduces_odd_sizes_to_a_few_texels() {
+        assert_eq!(StatsNode::sizes(1080, 1919), vec![
+            (270, 480),
+            (68, 120),
+            (17, 30),
+            (5, 8),
+        ]);
+        assert_eq!(StatsNode::sizes(1, 1), vec![(1, 1)]);
+    }
+
+    #[test]
+    fn every_level_covers_the_one_before() {
+        for &(width, height) in &[(1080, 1919), (4097, 3), (9, 9)] {
+            let mut before = (width, height);
+            for size in StatsNode::sizes(width, height) {
+                assert!(size.0 * 4 >= before.0 && size.1 * 4 >= before.1);
+                before = size;
+            }
+            assert!(before.0 <= 8 && before.1 <= 8);
+        }
+    }
+}
@@ -0,0 +1,363 @@
+use std::{
+    borrow::Cow,
+    fmt,
+    rc::Rc,
+    str::FromStr,
+};
+
+use glium::{
+    backend::Context,
+    texture::{
+        ClientFormat,
+        MipmapsOption,
+        PixelValue,
+        RawImage2d,
+        UncompressedFloatFormat,
+    },
+    uniforms::{
+        AsUniformValue,
+        MagnifySamplerFilter,
+        MinifySamplerFilter,
+        SamplerBehavior,
+        SamplerWrapFunction,
+        UniformValue,
+    },
+    Rect,
+    Texture2d,
+};
+
+/// How a node's output is stored. Simulations that
+/// accumulate small changes want float precision, and
+/// masks can get away with a single channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Rgba8,
+    /// 16 bits per channel, from 0 to 1, the default.
+    Rgba16,
+    Rgba16f,
+    Rgba32f,
+    R16f,
+    R32f,
+}
+
+impl Format {
+    /// Whether the format holds floats, which older or
+    /// embedded GPUs may not render to.
+    pub fn is_float(self) -> bool {
+        !matches!(self, Format::Rgba8 | Format::Rgba16)
+    }
+
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            Format::R16f => 2,
+            Format::Rgba8 | Format::R32f => 4,
+            Format::Rgba16 | Format::Rgba16f => 8,
+            Format::Rgba32f => 16,
+        }
+    }
+
+    fn gl(self) -> UncompressedFloatFormat {
+        match self {
+            Format::Rgba8 => UncompressedFloatFormat::U8U8U8U8,
+            Format::Rgba16 => UncompressedFloatFormat::U16U16U16U16,
+            Format::Rgba16f => UncompressedFloatFormat::F16F16F16F16,
+            Format::Rgba32f => UncompressedFloatFormat::F32F32F32F32,
+            Format::R16f => UncompressedFloatFormat::F16,
+            Format::R32f => UncompressedFloatFormat::F32,
+        }
+    }
+}
+
+/// How pixels uploaded from the CPU are laid out, see
+/// [`crate::graph::ShaderGraph::upload_input`]. Floats are
+/// in native byte order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    Rgb8,
+    Rgba8,
+    R32f,
+    Rgba32f,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::Rgba8 | PixelFormat::R32f => 4,
+            PixelFormat::Rgba32f => 16,
+        }
+    }
+
+    /// How the pixels are stored once uploaded.
+    fn storage(self) -> Format {
+        match self {
+            PixelFormat::Rgb8 | PixelFormat::Rgba8 => Format::Rgba8,
+            PixelFormat::R32f => Format::R32f,
+            PixelFormat::Rgba32f => Format::Rgba32f,
+        }
+    }
+}
+
+/// Uploads pixels, given row by row from the top, into a
+/// texture. The texture given is written to if it is the
+/// same size and format, otherwise a new one is made.
+pub(crate) fn upload_pixels(
+    context: &Rc<Context>,
+    texture: Option<(PixelFormat, Texture2d)>,
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    format: PixelFormat,
+) -> Result<(PixelFormat, Texture2d), String> {
+    let expected = (width * height) as usize * format.bytes_per_pixel();
+    if pixels.len() != expected {
+        return Err(format!(
+            "Expected {} bytes for a {}x{} {:?} image, found {}",
+            expected,
+            width,
+            height,
+            format,
+            pixels.len()
+        ));
+    }
+
+    // textures start at the bottom row
+    let row = width as usize * format.bytes_per_pixel();
+    let flipped = pixels.chunks_exact(row.max(1)).rev().flatten();
+    let reuse = texture.filter(|(old, texture)| {
+        *old == format && texture.dimensions() == (width, height)
+    });
+    let texture = match format {
+        PixelFormat::Rgb8 | PixelFormat::Rgba8 => {
+            let client = match format {
+                PixelFormat::Rgb8 => ClientFormat::U8U8U8,
+                _ => ClientFormat::U8U8U8U8,
+            };
+            let data = flipped.copied().collect();
+            write_raw(context, reuse, data, width, height, client, format)
+        },
+        PixelFormat::R32f | PixelFormat::Rgba32f => {
+            let client = match format {
+                PixelFormat::R32f => ClientFormat::F32,
+                _ => ClientFormat::F32F32F32F32,
+            };
+            let bytes = flipped.copied().collect::<Vec<u8>>();
+            let data = bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+            write_raw(context, reuse, data, width, height, client, format)
+        },
+    }?;
+    Ok((format, texture))
+}
+
+fn write_raw<T: PixelValue + Clone>(
+    context: &Rc<Context>,
+    reuse: Option<(PixelFormat, Texture2d)>,
+    data: Vec<T>,
+    width: u32,
+    height: u32,
+    client: ClientFormat,
+    format: PixelFormat,
+) -> Result<Texture2d, String> {
+    let raw = RawImage2d {
+        data: Cow::Owned(data),
+        width,
+        height,
+        format: client,
+    };
+    if let Some((_, texture)) = reuse {
+        let rect = Rect {
+            left: 0,
+            bottom: 0,
+            width,
+            height,
+        };
+        texture.write(rect, raw);
+        return Ok(texture);
+    }
+    Texture2d::with_format(
+        context,
+        raw,
+        format.storage().gl(),
+        MipmapsOption::NoMipmap,
+    )
+    .map_err(|e| format!("Could not upload {:?} pixels: {}", format, e))
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Format, String> {
+        match name {
+            "rgba8" => Ok(Format::Rgba8),
+            "rgba16" => Ok(Format::Rgba16),
+            "rgba16f" => Ok(Format::Rgba16f),
+            "rgba32f" => Ok(Format::Rgba32f),
+            "r16f" => Ok(Format::R16f),
+            "r32f" => Ok(Format::R32f),
+            other => Err(format!(
+                "Unknown texture format `{}`, expected `rgba8`, `rgba16`, \
+                 `rgba16f`, `rgba32f`, `r16f`, or `r32f`",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Format::Rgba8 => "rgba8",
+            Format::Rgba16 => "rgba16",
+            Format::Rgba16f => "rgba16f",
+            Format::Rgba32f => "rgba32f",
+            Format::R16f => "r16f",
+            Format::R32f => "r32f",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How a node's output is sampled between pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Linear,
+    Nearest,
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Filter, String> {
+        match name {
+            "linear" => Ok(Filter::Linear),
+            "nearest" => Ok(Filter::Nearest),
+            other => Err(format!(
+                "Unknown filter `{}`, expected `linear` or `nearest`",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Filter::Linear => "linear",
+            Filter::Nearest => "nearest",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How a node's output is sampled outside of `0..1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    /// Reflects back and forth, the default.
+    Mirror,
+    Repeat,
+    /// Repeats the edge pixels.
+    Clamp,
+}
+
+impl FromStr for Wrap {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Wrap, String> {
+        match name {
+            "mirror" => Ok(Wrap::Mirror),
+            "repeat" => Ok(Wrap::Repeat),
+            "clamp" => Ok(Wrap::Clamp),
+            other => Err(format!(
+                "Unknown wrap `{}`, expected `mirror`, `repeat`, or `clamp`",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Wrap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Wrap::Mirror => "mirror",
+            Wrap::Repeat => "repeat",
+            Wrap::Clamp => "clamp",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How a shader node stores its output, and how nodes
+/// that take it as an input sample it. The default matches
+/// every other node: 16 bits per channel, linear filtering,
+/// and mirrored wrapping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureOptions {
+    pub format: Format,
+    pub filter: Filter,
+    pub wrap:   Wrap,
+}
+
+impl Default for TextureOptions {
+    fn default() -> TextureOptions {
+        TextureOptions {
+            format: Format::Rgba16,
+            filter: Filter::Linear,
+            wrap:   Wrap::Mirror,
+        }
+    }
+}
+
+impl TextureOptions {
+    /// Creates an empty texture in the format.
+    pub fn buffer(
+        &self,
+        context: &Rc<Context>,
+        width: u32,
+        height: u32,
+    ) -> Result<Texture2d, String> {
+        Texture2d::empty_with_format(
+            context,
+            self.format.gl(),
+            MipmapsOption::NoMipmap,
+            width,
+            height,
+        )
+        .map_err(|e| {
+            format!("Could not create a {} texture: {}", self.format, e)
+        })
+    }
+
+    pub fn sampler(&self) -> SamplerBehavior {
+        let (minify, magnify) = match self.filter {
+            Filter::Linear => {
+                (MinifySamplerFilter::Linear, MagnifySamplerFilter::Linear)
+            },
+            Filter::Nearest => {
+                (MinifySamplerFilter::Nearest, MagnifySamplerFilter::Nearest)
+            },
+        };
+        let wrap = match self.wrap {
+            Wrap::Mirror => SamplerWrapFunction::Mirror,
+            Wrap::Repeat => SamplerWrapFunction::Repeat,
+            Wrap::Clamp => SamplerWrapFunction::Clamp,
+        };
+        SamplerBehavior {
+            wrap_function: (wrap, wrap, wrap),
+            minify_filter: minify,
+            magnify_filter: magnify,
+            ..Default::default()
+        }
+    }
+
+    /// A texture as a uniform, sampled as configured.
+    pub fn sampled<'a>(&self, texture: &'a Texture2d) -> UniformValue<'a> {
+        // as every other node's output is sampled
+        if *self == TextureOptions::default() {
+            return texture.as_uniform_value();
+        }
+        UniformValue::Texture2d(texture, Some(self.sampler()))
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::{
+    BTreeMap,
+    BTreeSet,
+};
+
+use crate::graph::NodeId;
+
+/// The shape of a graph: which nodes feed into which, which
+/// are inputs and outputs, what they are named and labeled,
+/// and the order they run in. This is kept apart from the
+/// nodes themselves, so that it can be built and checked
+/// without a GL context.
+#[derive(Debug, Clone, Default)]
+pub struct Topology {
+    /// The inputs of each node, empty for graph inputs.
+    edges:   Vec<Vec<NodeId>>,
+    inputs:  Vec<NodeId>,
+    outputs: Vec<NodeId>,
+    names:   BTreeMap<String, NodeId>,
+    /// Notes on what nodes are for, not unique, unlike
+    /// names.
+    labels:  BTreeMap<NodeId, String>,
+
+    /// The order nodes are run in, see
+    /// [`Topology::evaluation_order`].
+    order: Vec<NodeId>,
+    /// Extra `(before, after)` constraints on the order.
+    hints: BTreeSet<(NodeId, NodeId)>,
+}
+
+impl Topology {
+    pub fn new() -> Topology { Topology::default() }
+
+    /// The number of nodes, including inputs.
+    pub fn len(&self) -> usize { self.edges.len() }
+
+    pub fn is_empty(&self) -> bool { self.edges.is_empty() }
+
+    /// Adds a node that takes the given inputs. Will panic
+    /// if an input is not already in the graph, as that
+    /// could form a cycle.
+    pub fn add_node(&mut self, inputs: Vec<NodeId>) -> NodeId {
+        // to preserve acyclic structure, can only ref backwards
+        for NodeId(input) in inputs.iter() {
+            assert!(*input < self.edges.len());
+        }
+
+        self.edges.push(inputs);
+        // nothing can depend on the newest node yet, so it is
+        // always run last
+        let id = NodeId(self.edges.len() - 1);
+        self.order.push(id);
+        id
+    }
+
+    /// Adds an input to the graph.
+    pub fn add_input(&mut self) -> NodeId {
+        let id = self.add_node(vec![]);
+        self.inputs.push(id);
+        id
+    }
+
+    /// The inputs of a node.
+    pub fn node_inputs(&self, id: NodeId) -> &[NodeId] { &self.edges[id.0] }
+
+    pub fn get_inputs(&self) -> &Vec<NodeId> { &self.inputs }
+
+    pub fn get_outputs(&self) -> &Vec<NodeId> { &self.outputs }
+
+    /// Marks a node as an output, once.
+    pub fn mark_output(&mut self, id: NodeId) {
+        if !self.outputs.contains(&id) {
+            self.outputs.push(id)
+        }
+    }
+
+    /// Gives a node a name. Naming another node the same
+    /// moves the name to that node.
+    pub fn name_node(&mut self, id: NodeId, name: &str) {
+        self.names.insert(name.to_string(), id);
+    }
+
+    /// Looks up a node by name.
+    pub fn node_id(&self, name: &str) -> Option<NodeId> {
+        self.names.get(name).copied()
+    }
+
+    /// The name of a node, if it has one.
+    pub fn name_of(&self, id: NodeId) -> Option<&str> {
+        self.node_names()
+            .find(|(_, named)| *named == id)
+            .map(|(n, _)| n)
+    }
+
+    /// All named nodes, ordered by name.
+    pub fn node_names(&self) -> impl Iterator<Item = (&str, NodeId)> {
+        self.names.iter().map(|(name, id)| (name.as_str(), *id))
+    }
+
+    /// Labels a node, replacing any label it had.
+    pub fn label_node(&mut self, id: NodeId, label: &str) {
+        self.labels.insert(id, label.to_string());
+    }
+
+    /// The label of a node, if it has one.
+    pub fn label_of(&self, id: NodeId) -> Option<&str> {
+        self.labels.get(&id).map(String::as_str)
+    }
+
+    /// All labeled nodes, ordered by id.
+    pub fn node_labels(&self) -> impl Iterator<Item = (NodeId, &str)> {
+        self.labels.iter().map(|(id, label)| (*id, label.as_str()))
+    }
+
+    /// How a node is referred to in messages and tools: by
+    /// name, or by id if it has none, followed by its
+    /// label.
+    pub fn describe(&self, id: NodeId) -> String {
+        let node = match self.name_of(id) {
+            Some(name) => name.to_string(),
+            None => format!("node {}", id.0),
+        };
+        match self.label_of(id) {
+            Some(label) => format!("{} \"{}\"", node, label),
+            None => node,
+        }
+    }
+
+    /// Follows bypassed nodes back to the node whose output
+    /// should be used in their place.
+    pub fn resolve(
+        &self,
+        bypassed: &BTreeSet<NodeId>,
+        mut id: NodeId,
+    ) -> NodeId {
+        while bypassed.contains(&id) {
+            // only nodes with inputs can be bypassed
+            id = self.edges[id.0][0];
+        }
+        id
+    }
+
+    /// The order nodes are run in during a forward pass.
+    /// Nodes are run in the order they were added, except
+    /// where [`Topology::order_before`] moves them, so the
+    /// order is the same from frame to frame and from
+    /// machine to machine.
+    pub fn evaluation_order(&self) -> &[NodeId] { &self.order }
+
+    /// Every `(before, after)` pair passed to
+    /// [`Topology::order_before`].
+    pub fn hints(&self) -> impl Iterator<Item = (NodeId, NodeId)> + '_ {
+        self.hints.iter().copied()
+    }
+
+    /// Makes sure `first` is run before `second`, for nodes
+    /// whose order matters even though neither is an input
+    /// of the other, like nodes with side effects or
+    /// feedback. Errors if this would form a cycle.
+    pub fn order_before(
+        &mut self,
+        first: NodeId,
+        second: NodeId,
+    ) -> Result<(), String> {
+        if first.0 >= self.len() || second.0 >= self.len() {
+            return Err("Cannot order a node that is not in the graph".into());
+        }
+        if first == second {
+            return Err("Cannot order a node before itself".into());
+        }
+
+        self.hints.insert((first, second));
+        match self.sort() {
+            Some(order) => {
+                self.order = order;
+                Ok(())
+            },
+            None => {
+                self.hints.remove(&(first, second));
+                Err(format!(
+                    "Running node {} before node {} would form a cycle",
+                    first.0, second.0
+                ))
+            },
+        }
+    }
+
+    /// The nodes left unsorted because they depend on each
+    /// other, or on nodes that do, if any. Topologies
+    /// refuse to form cycles as they are built, so this
+    /// only checks that that held.
+    pub fn cycle(&self) -> Option<Vec<NodeId>> {
+        let sorted = self.sorted().into_iter().collect::<BTreeSet<_>>();
+        let unsorted = (0..self.len())
+            .map(NodeId)
+            .filter(|id| !sorted.contains(id))
+            .collect::<Vec<_>>();
+        Some(unsorted).filter(|unsorted| !unsorted.is_empty())
+    }
+
+    /// Topologically sorts the nodes, see
+    /// [`Topology::sorted`]. Returns `None` on a cycle.
+    fn sort(&self) -> Option<Vec<NodeId>> {
+        let order = self.sorted();
+        if order.len() == self.len() {
+            Some(order)
+        } else {
+            None
+        }
+    }
+
+    /// Sorts the nodes, following both their inputs and the
+    /// ordering hints. Of the nodes ready to run, the
+    /// earliest added is always picked first, to keep the
+    /// order stable. Nodes on or after a cycle are left
+    /// out.
+    fn sorted(&self) -> Vec<NodeId> {
+        let mut edges = vec![vec![]; self.len()];
+        let mut waiting = vec![0; self.len()];
+        let inputs = self.edges.iter().enumerate().flat_map(|(id, inputs)| {
+            inputs.iter().map(move |input| (*input, NodeId(id)))
+        });
+        for (before, after) in inputs.chain(self.hints.iter().copied()) {
+            edges[before.0].push(after);
+            waiting[after.0] += 1;
+        }
+
+        let mut ready: BTreeSet<NodeId> = (0..self.len())
+            .filter(|id| waiting[*id] == 0)
+            .map(NodeId)
+            .collect();
+        let mut order = Vec::with_capacity(self.len());
+        while let Some(id) = ready.iter().next().copied() {
+            ready.remove(&id);
+            order.push(id);
+            for after in edges[id.0].iter() {
+                waiting[after.0] -= 1;
+                if waiting[after.0] == 0 {
+                    ready.insert(*after);
+                }
+            }
+        }
+
+        order
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A chain of three nodes, fed by an input.
+    fn chain() -> Topology {
+        let mut topology = Topology::new();
+        let input = topology.add_input();
+        let first = topology.add_node(vec![input]);
+        let second = topology.add_node(vec![first]);
+        topology.add_node(vec![second]);
+        topology
+    }
+
+    #[test]
+    fn runs_in_the_order_added() {
+        let topology = chain();
+        assert_eq!(topology.len(), 4);
+        assert_eq!(topology.get_inputs(), &vec![NodeId(0)]);
+        assert_eq!(topology.evaluation_order(), &[
+            NodeId(0),
+            NodeId(1),
+            NodeId(2),
+            NodeId(3)
+        ]);
+        assert_eq!(topology.cycle(), None);
+    }
+
+    #[test]
+    fn orders_unrelated_nodes() {
+        let mut topology = Topology::new();
+        let trail = topology.add_node(vec![]);
+        let sparks = topology.add_node(vec![]);
+        let shown = topology.add_node(vec![trail, sparks]);
+        topology.order_before(sparks, trail).unwrap();
+        assert_eq!(topology.evaluation_order(), &[sparks, trail, shown]);
+        assert_eq!(topology.hints().collect::<Vec<_>>(), [(sparks, trail)]);
+    }
+
+    #[test]
+    fn refuses_cycles() {
+        let mut topology = chain();
+        let order = topology.evaluation_order().to_vec();
+        assert!(topology.order_before(NodeId(3), NodeId(1)).is_err());
+        assert!(topology.order_before(NodeId(1), NodeId(1)).is_err());
+        assert!(topology.order_before(NodeId(0), NodeId(4)).is_err());
+        // a refused hint leaves the order as it was
+        assert_eq!(topology.evaluation_order(), &order[..]);
+        assert_eq!(topology.hints().count(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn only_takes_earlier_inputs() {
+        let mut topology = Topology::new();
+        topology.add_node(vec![NodeId(0)]);
+    }
+
+    #[test]
+    fn names_and_labels() {
+        let mut topology = chain();
+        topology.name_node(NodeId(1), "blur");
+        topology.label_node(NodeId(1), "gaussian H");
+        topology.label_node(NodeId(2), "gaussian V");
+        assert_eq!(topology.node_id("blur"), Some(NodeId(1)));
+        assert_eq!(topology.node_id("sharpen"), None);
+        assert_eq!(topology.name_of(NodeId(1)), Some("blur"));
+        assert_eq!(topology.describe(NodeId(1)), "blur \"gaussian H\"");
+        assert_eq!(topology.describe(NodeId(2)), "node 2 \"gaussian V\"");
+        assert_eq!(topology.describe(NodeId(3)), "node 3");
+
+        // a name moves to the last node given it
+        topology.name_node(NodeId(3), "blur");
+        assert_eq!(topology.node_id("blur"), Some(NodeId(3)));
+        assert_eq!(topology.name_of(NodeId(1)), None);
+    }
+
+    #[test]
+    fn marks_outputs_once() {
+        let mut topology = chain();
+        topology.mark_output(NodeId(3));
+        topology.mark_output(NodeId(1));
+        topology.mark_output(NodeId(3));
+        assert_eq!(topology.get_outputs(), &vec![NodeId(3), NodeId(1)]);
+    }
+
+    #[test]
+    fn resolves_bypassed_nodes() {
+        let topology = chain();
+        let bypassed = [NodeId(2), NodeId(3)].iter().copied().collect();
+        assert_eq!(topology.resolve(&bypassed, NodeId(3)), NodeId(1));
+        assert_eq!(topology.resolve(&bypassed, NodeId(1)), NodeId(1));
+    }
+}
@@ -1,39 +1,448 @@
-use std::collections::BTreeMap;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    rc::Rc,
+    str::FromStr,
+};
 
 use glium::uniforms::{
     UniformValue,
     Uniforms,
 };
 
+/// Uniforms every node already receives, which can't be
+/// declared by the host.
+pub const BUILT_IN: &[&str] = &[
+    "time",
+    "dt",
+    "step",
+    "quality",
+    "canvas",
+    "tile",
+    "output_resolution",
+    "aspect",
+    "texture",
+    "palette",
+    "palette_size",
+    "build_seed",
+    "frame",
+    "mouse",
+    "keyboard",
+];
+
+/// The type of a uniform set by the application running a
+/// graph, see [`crate::graph::ShaderGraph::set_uniform`].
+/// Besides single values, uniforms can be fixed-size
+/// arrays, like `float u_weights[16]`, and structs, for
+/// kernels, palettes, and lists of lights.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UniformType {
+    Float,
+    Vec2,
+    Vec3,
+    Vec4,
+    Int,
+    /// A number of elements of one type, which can't be
+    /// another array.
+    Array(Box<UniformType>, usize),
+    /// Named fields, in the order the struct declares them.
+    Struct(Vec<(String, UniformType)>),
+}
+
+impl UniformType {
+    /// Checks that arrays have elements, aren't nested,
+    /// and that structs have fields, with distinct names.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            UniformType::Array(_, 0) => {
+                Err("Uniform arrays must have at least one element".into())
+            },
+            UniformType::Array(element, _) => match **element {
+                UniformType::Array(..) => {
+                    Err("Uniform arrays can't hold other arrays".into())
+                },
+                ref element => element.validate(),
+            },
+            UniformType::Struct(fields) if fields.is_empty() => {
+                Err("Uniform structs must have at least one field".into())
+            },
+            UniformType::Struct(fields) => {
+                for (index, (name, kind)) in fields.iter().enumerate() {
+                    if fields[..index].iter().any(|(other, _)| other == name) {
+                        return Err(format!(
+                            "Uniform struct has two fields named `{}`",
+                            name
+                        ));
+                    }
+                    kind.validate()?;
+                }
+                Ok(())
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+impl FromStr for UniformType {
+    type Err = String;
+
+    /// Parses a single value's type, or an array of them,
+    /// written like `float[16]`.
+    fn from_str(name: &str) -> Result<UniformType, String> {
+        if let Some((element, count)) = name.split_once('[') {
+            let count = count
+                .strip_suffix(']')
+                .and_then(|count| count.parse().ok())
+                .ok_or_else(|| {
+                    format!(
+                        "Expected an array type like `float[16]`, got `{}`",
+                        name
+                    )
+                })?;
+            return Ok(UniformType::Array(Box::new(element.parse()?), count));
+        }
+
+        match name {
+            "float" => Ok(UniformType::Float),
+            "vec2" => Ok(UniformType::Vec2),
+            "vec3" => Ok(UniformType::Vec3),
+            "vec4" => Ok(UniformType::Vec4),
+            "int" => Ok(UniformType::Int),
+            other => Err(format!(
+                "Unknown uniform type `{}`, expected `float`, `vec2`, \
+                 `vec3`, `vec4`, or `int`",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for UniformType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            UniformType::Float => "float",
+            UniformType::Vec2 => "vec2",
+            UniformType::Vec3 => "vec3",
+            UniformType::Vec4 => "vec4",
+            UniformType::Int => "int",
+            UniformType::Array(element, count) => {
+                return write!(f, "{}[{}]", element, count);
+            },
+            UniformType::Struct(fields) => {
+                write!(f, "struct {{")?;
+                for (name, kind) in fields.iter() {
+                    write!(f, " {} {};", kind, name)?;
+                }
+                return write!(f, " }}");
+            },
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The value of a uniform set by the application running a
+/// graph.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Uniform {
+    Float(f32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+    Int(i32),
+    /// Elements of the same type.
+    Array(Vec<Uniform>),
+    /// Fields by name, in the order the struct declares
+    /// them.
+    Struct(Vec<(String, Uniform)>),
+}
+
+impl Uniform {
+    /// The value a uniform has until it is first set.
+    pub fn zero(kind: &UniformType) -> Uniform {
+        match kind {
+            UniformType::Float => Uniform::Float(0.0),
+            UniformType::Vec2 => Uniform::Vec2([0.0; 2]),
+            UniformType::Vec3 => Uniform::Vec3([0.0; 3]),
+            UniformType::Vec4 => Uniform::Vec4([0.0; 4]),
+            UniformType::Int => Uniform::Int(0),
+            UniformType::Array(element, count) => {
+                Uniform::Array(vec![Uniform::zero(element); *count])
+            },
+            UniformType::Struct(fields) => Uniform::Struct(
+                fields
+                    .iter()
+                    .map(|(name, kind)| (name.clone(), Uniform::zero(kind)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// The type of the value. Arrays take the type of their
+    /// first element, see [`Uniform::matches`].
+    pub fn kind(&self) -> UniformType {
+        match self {
+            Uniform::Float(_) => UniformType::Float,
+            Uniform::Vec2(_) => UniformType::Vec2,
+            Uniform::Vec3(_) => UniformType::Vec3,
+            Uniform::Vec4(_) => UniformType::Vec4,
+            Uniform::Int(_) => UniformType::Int,
+            Uniform::Array(elements) => UniformType::Array(
+                Box::new(match elements.first() {
+                    Some(element) => element.kind(),
+                    None => UniformType::Float,
+                }),
+                elements.len(),
+            ),
+            Uniform::Struct(fields) => UniformType::Struct(
+                fields
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.kind()))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Whether the value is of a type, down to every
+    /// element of an array.
+    pub fn matches(&self, kind: &UniformType) -> bool {
+        match (self, kind) {
+            (Uniform::Array(elements), UniformType::Array(element, count)) => {
+                elements.len() == *count
+                    && elements.iter().all(|value| value.matches(element))
+            },
+            (Uniform::Struct(values), UniformType::Struct(fields)) => {
+                values.len() == fields.len()
+                    && values.iter().zip(fields.iter()).all(
+                        |((name, value), (field, kind))| {
+                            name == field && value.matches(kind)
+                        },
+                    )
+            },
+            (value, kind) => value.kind() == *kind,
+        }
+    }
+
+    /// Passes each single value the uniform is made of to
+    /// `output`, named as GLSL names them: arrays by
+    /// element, like `weights[3]`, and structs by
+    /// field, like `light.color`.
+    pub fn visit<F: FnMut(&str, UniformValue<'static>)>(
+        &self,
+        name: &str,
+        output: &mut F,
+    ) {
+        match self {
+            Uniform::Float(x) => output(name, UniformValue::Float(*x)),
+            Uniform::Vec2(v) => output(name, UniformValue::Vec2(*v)),
+            Uniform::Vec3(v) => output(name, UniformValue::Vec3(*v)),
+            Uniform::Vec4(v) => output(name, UniformValue::Vec4(*v)),
+            Uniform::Int(n) => output(name, UniformValue::SignedInt(*n)),
+            Uniform::Array(elements) => {
+                for (index, element) in elements.iter().enumerate() {
+                    element.visit(&format!("{}[{}]", name, index), output);
+                }
+            },
+            Uniform::Struct(fields) => {
+                for (field, value) in fields.iter() {
+                    value.visit(&format!("{}.{}", name, field), output);
+                }
+            },
+        }
+    }
+}
+
+/// The GLSL names of a kind of uniform, `u_<kind>` and
+/// `u_<kind>_<index>`. Every uniform of every draw is
+/// named, so each kind is only formatted once, and kept
+/// along with the kind itself for every map to share.
+struct Names {
+    plain:   String,
+    indexed: Vec<String>,
+}
+
+thread_local! {
+    static NAMES: RefCell<HashMap<Rc<str>, Names>> =
+        RefCell::new(HashMap::new());
+}
+
+/// The shared copy of a kind, see [`Names`].
+fn intern(kind: &str) -> Rc<str> {
+    NAMES.with(|names| {
+        let mut names = names.borrow_mut();
+        if let Some((kind, _)) = names.get_key_value(kind) {
+            return kind.clone();
+        }
+        let kind = Rc::<str>::from(kind);
+        let plain = format!("u_{}", kind);
+        names.insert(kind.clone(), Names {
+            plain,
+            indexed: vec![],
+        });
+        kind
+    })
+}
+
+/// The storage of maps that were dropped, kept for the
+/// next maps made to reuse, so that once a graph has run a
+/// frame, running it doesn't allocate for uniforms. Maps
+/// only hold a few kinds of their own, see
+/// [`UniformMap::over`], so only so many are kept.
 #[derive(Default)]
-pub struct UniformMap<'a>(BTreeMap<String, Vec<UniformValue<'a>>>);
+struct Pool {
+    kinds:  Vec<Vec<(Rc<str>, Vec<UniformValue<'static>>)>>,
+    values: Vec<Vec<UniformValue<'static>>>,
+}
+
+impl Pool {
+    const MOST: usize = 256;
+}
+
+thread_local! {
+    static POOL: RefCell<Pool> = RefCell::new(Pool::default());
+}
+
+/// Reuses the allocation of an empty vector for a vector of
+/// another type of the same size, e.g. uniforms borrowing
+/// for another lifetime. Collecting a vector's own iterator
+/// reuses its allocation where the types are the same size,
+/// and an empty vector has nothing to convert.
+fn recycle<T, U>(empty: Vec<T>) -> Vec<U> {
+    debug_assert!(empty.is_empty());
+    empty.into_iter().filter_map(|_| None).collect()
+}
+
+/// A vector from the [`Pool`], or a new one if it's empty.
+fn pooled<T, U>(take: fn(&mut Pool) -> Option<Vec<T>>) -> Vec<U> {
+    POOL.try_with(|pool| take(&mut pool.borrow_mut()))
+        .ok()
+        .flatten()
+        .map(recycle)
+        .unwrap_or_default()
+}
+
+/// Uniforms by kind, numbered in the order they were added.
+/// A map can be laid over a base map, see
+/// [`UniformMap::over`], rather than copying it. Dropped
+/// maps leave their storage for new ones, see [`Pool`].
+#[derive(Clone)]
+pub struct UniformMap<'a> {
+    base: Option<&'a UniformMap<'a>>,
+    own:  Vec<(Rc<str>, Vec<UniformValue<'a>>)>,
+}
+
+impl Default for UniformMap<'_> {
+    fn default() -> Self {
+        UniformMap {
+            base: None,
+            own:  pooled(|pool| pool.kinds.pop()),
+        }
+    }
+}
+
+impl Drop for UniformMap<'_> {
+    fn drop(&mut self) {
+        let mut own = std::mem::take(&mut self.own);
+        let _ = POOL.try_with(|pool| {
+            let mut pool = pool.borrow_mut();
+            for (_, mut values) in own.drain(..) {
+                if pool.values.len() < Pool::MOST {
+                    values.clear();
+                    pool.values.push(recycle(values));
+                }
+            }
+            if pool.kinds.len() < Pool::MOST {
+                pool.kinds.push(recycle(own));
+            }
+        });
+    }
+}
 
 impl<'a> UniformMap<'a> {
     /// Create a new empty `UniformMap`.
-    pub fn new() -> UniformMap<'a> { UniformMap(BTreeMap::new()) }
+    pub fn new() -> UniformMap<'a> { UniformMap::default() }
+
+    /// Creates a map holding every uniform of `base`, which
+    /// is only copied kind by kind, as kinds are added to
+    /// or set in the new map. Every node of a graph
+    /// shares the uniforms of the frame, so this keeps
+    /// large graphs from copying them once per node.
+    pub fn over(base: &'a UniformMap<'a>) -> UniformMap<'a> {
+        UniformMap {
+            base: Some(base),
+            own:  pooled(|pool| pool.kinds.pop()),
+        }
+    }
+
+    /// The index in `own` of a kind.
+    fn find(&self, kind: &str) -> Option<usize> {
+        self.own.iter().position(|(own, _)| &**own == kind)
+    }
+
+    /// The uniforms of a kind that this map has to itself,
+    /// copied from its base if need be.
+    fn own_kind(&mut self, kind: &str) -> Option<&mut Vec<UniformValue<'a>>> {
+        let index = match self.find(kind) {
+            Some(index) => index,
+            None => {
+                let base = self.base?.get_kind_all(kind)?;
+                let mut values = pooled(|pool| pool.values.pop());
+                values.extend_from_slice(base);
+                self.own.push((intern(kind), values));
+                self.own.len() - 1
+            },
+        };
+        Some(&mut self.own[index].1)
+    }
 
     /// Inserts a kind of uniform into the uniform map.
     /// Will automatically number the item `u_{kind}_N`,
     /// starting at `N = 0`.
     /// Returns the index of the item, i.e. `N`.
     pub fn add(&mut self, kind: &str, uniform: UniformValue<'a>) -> usize {
-        if !self.0.contains_key(kind) {
-            self.0.insert(kind.to_string(), vec![]);
+        if let Some(uniforms) = self.own_kind(kind) {
+            uniforms.push(uniform);
+            return uniforms.len() - 1;
         }
+        let mut values = pooled(|pool| pool.values.pop());
+        values.push(uniform);
+        self.own.push((intern(kind), values));
+        0
+    }
 
-        let uniforms = self.0.get_mut(kind).unwrap();
-        uniforms.push(uniform);
-        uniforms.len() - 1
+    /// Adds the size of the texture a pass renders to, in
+    /// pixels, as `resolution`, and its width over its
+    /// height as `aspect`.
+    pub fn add_resolution(&mut self, resolution: [f32; 2]) {
+        self.add("resolution", UniformValue::Vec2(resolution));
+        let aspect = resolution[0] / resolution[1].max(1.0);
+        self.add("aspect", UniformValue::Float(aspect));
     }
 
     /// Get a specific kind of uniform at a given index.
     pub fn get(&self, kind: &str, index: usize) -> Option<&UniformValue<'a>> {
-        self.0.get(kind)?.get(index)
+        self.get_kind_all(kind)?.get(index)
+    }
+
+    /// Replace a specific kind of uniform at a given index.
+    /// Returns the uniform that was replaced, if there was
+    /// one.
+    pub fn set(
+        &mut self,
+        kind: &str,
+        index: usize,
+        uniform: UniformValue<'a>,
+    ) -> Option<UniformValue<'a>> {
+        let slot = self.own_kind(kind)?.get_mut(index)?;
+        Some(std::mem::replace(slot, uniform))
     }
 
     /// Get all uniforms of a given kind.
     pub fn get_kind_all(&self, kind: &str) -> Option<&Vec<UniformValue<'a>>> {
-        self.0.get(kind)
+        match self.find(kind) {
+            Some(index) => Some(&self.own[index].1),
+            None => self.base?.get_kind_all(kind),
+        }
     }
 
     /// Join two maps by appending one to the other.
@@ -41,10 +450,28 @@ impl<'a> UniformMap<'a> {
     /// together, With `self` being first, and `other`
     /// being second.
     pub fn append(&mut self, other: Self) {
-        for (key, values) in other.0.into_iter() {
+        other.each_kind(|kind, values| {
             for value in values {
-                self.add(&key, value);
+                self.add(kind, *value);
+            }
+        });
+    }
+
+    /// Passes every kind in the map to `f` once, along with
+    /// its uniforms, whether they are the map's own or its
+    /// base's.
+    fn each_kind<F: FnMut(&Rc<str>, &Vec<UniformValue<'a>>)>(&self, mut f: F) {
+        let mut layer = Some(self);
+        while let Some(map) = layer {
+            for (kind, uniforms) in map.own.iter() {
+                // a kind shows the uniforms of the top layer
+                // that has it
+                let shown = self.get_kind_all(kind);
+                if shown.is_some_and(|shown| std::ptr::eq(shown, uniforms)) {
+                    f(kind, uniforms);
+                }
             }
+            layer = map.base;
         }
     }
 }
@@ -54,19 +481,95 @@ impl<'a> Uniforms for UniformMap<'a> {
         &'b self,
         mut output: F,
     ) {
-        for (kind, uniforms) in self.0.iter() {
-            // if there is only one uniform of a kind,
-            // no subscript is required.
-            // this lets us have `u_time` instead of `u_time_0`,
-            // but if an input adds another `time` uniform,
-            // the user *must* disambiguiate which one is intended.
-            if uniforms.len() == 1 {
-                output(&format!("u_{}", kind), uniforms[0]);
-            }
+        NAMES.with(|names| {
+            let mut names = names.borrow_mut();
+            self.each_kind(|kind, uniforms| {
+                // unwrap: every kind in a map is interned
+                let names = names.get_mut(kind).unwrap();
+                while names.indexed.len() < uniforms.len() {
+                    let index = names.indexed.len();
+                    names.indexed.push(format!("u_{}_{}", kind, index));
+                }
 
-            for (index, uniform) in uniforms.iter().enumerate() {
-                output(&format!("u_{}_{}", kind, index), *uniform)
-            }
+                // if there is only one uniform of a kind,
+                // no subscript is required.
+                // this lets us have `u_time` instead of `u_time_0`,
+                // but if an input adds another `time` uniform,
+                // the user *must* disambiguiate which one is intended.
+                if uniforms.len() == 1 {
+                    output(&names.plain, uniforms[0]);
+                }
+
+                for (name, uniform) in names.indexed.iter().zip(uniforms) {
+                    output(name, *uniform)
+                }
+            });
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(map: &UniformMap) -> Vec<String> {
+        let mut names = vec![];
+        map.visit_values(|name, _| names.push(name.to_string()));
+        names.sort();
+        names
+    }
+
+    fn float(value: Option<&UniformValue>) -> Option<f32> {
+        match value {
+            Some(UniformValue::Float(x)) => Some(*x),
+            _ => None,
         }
     }
+
+    #[test]
+    fn lays_maps_over_a_base() {
+        let mut base = UniformMap::new();
+        base.add("time", UniformValue::Float(1.0));
+        base.add("texture", UniformValue::Float(2.0));
+
+        let mut map = UniformMap::over(&base);
+        assert_eq!(map.add("texture", UniformValue::Float(3.0)), 1);
+        map.add("dt", UniformValue::Float(4.0));
+        assert_eq!(names(&map), [
+            "u_dt",
+            "u_dt_0",
+            "u_texture_0",
+            "u_texture_1",
+            "u_time",
+            "u_time_0",
+        ]);
+        let replaced = map.set("time", 0, UniformValue::Float(5.0));
+        assert_eq!(float(replaced.as_ref()), Some(1.0));
+        assert_eq!(float(map.get("time", 0)), Some(5.0));
+
+        // the base is left as it was
+        assert_eq!(float(base.get("time", 0)), Some(1.0));
+        assert_eq!(names(&base), [
+            "u_texture",
+            "u_texture_0",
+            "u_time",
+            "u_time_0",
+        ]);
+    }
+
+    #[test]
+    fn reuses_the_storage_of_dropped_maps() {
+        let base = UniformMap::new();
+        let mut map = UniformMap::over(&base);
+        map.add("texture", UniformValue::Float(1.0));
+        map.add("dt", UniformValue::Float(2.0));
+        drop(map);
+
+        let mut map = UniformMap::over(&base);
+        assert!(map.own.capacity() > 0);
+        assert_eq!(names(&map), Vec::<String>::new());
+        map.add("time", UniformValue::Float(3.0));
+        assert!(map.own[0].1.capacity() > 1);
+        assert_eq!(names(&map), ["u_time", "u_time_0"]);
+    }
 }
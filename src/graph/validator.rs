@@ -0,0 +1,123 @@
+use std::{
+    collections::BTreeSet,
+    rc::Rc,
+};
+
+use glium::{
+    backend::Context,
+    index::{
+        NoIndices,
+        PrimitiveType,
+    },
+    uniform,
+    vertex::EmptyVertexAttributes,
+    Blend,
+    DrawParameters,
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::{
+    graph::NodeId,
+    util::{
+        float_buffer,
+        read_pixel,
+        read_texture,
+    },
+};
+
+/// Checks the output of each node for NaN or infinite
+/// pixels, which spread through feedback loops and are
+/// hard to trace back to where they started. Invalid
+/// pixels are counted on the GPU, and only the first
+/// occurrence in each node is located and reported.
+pub struct Validator {
+    program:  Program,
+    count:    Texture2d,
+    reported: BTreeSet<NodeId>,
+}
+
+impl Validator {
+    pub fn new(context: &Rc<Context>) -> Result<Validator, String> {
+        let program = Program::from_source(
+            context,
+            include_str!("./shaders/validate.vert"),
+            include_str!("./shaders/histogram.frag"),
+            None,
+        )
+        .map_err(|e| format!("{}", e))?;
+
+        Ok(Validator {
+            program,
+            count: float_buffer(context, 1, 1)?,
+            reported: BTreeSet::new(),
+        })
+    }
+
+    /// Checks a node's texture, logging the first invalid
+    /// pixel if this node hasn't had one before.
+    pub fn check(
+        &mut self,
+        id: NodeId,
+        name: Option<&str>,
+        texture: &Texture2d,
+    ) {
+        if self.reported.contains(&id) {
+            return;
+        }
+
+        let params = DrawParameters {
+            blend: Blend {
+                color: glium::BlendingFunction::Addition {
+                    source:      glium::LinearBlendingFactor::One,
+                    destination: glium::LinearBlendingFactor::One,
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let (width, height) = texture.dimensions();
+        let mut surface = self.count.as_surface();
+        surface.clear_color(0.0, 0.0, 0.0, 0.0);
+        surface
+            .draw(
+                EmptyVertexAttributes {
+                    len: (width * height) as usize,
+                },
+                NoIndices(PrimitiveType::Points),
+                &self.program,
+                &uniform! {
+                    u_input: texture,
+                    u_weight: 1.0f32,
+                },
+                &params,
+            )
+            .unwrap();
+
+        let invalid = read_pixel(&self.count, 0, 0)[0];
+        if invalid == 0.0 {
+            return;
+        }
+
+        // only now is it worth reading back the whole texture
+        let first = read_texture(texture)
+            .iter()
+            .position(|p| p.iter().any(|c| !c.is_finite()))
+            .unwrap_or(0) as u32;
+        let name = match name {
+            Some(name) => format!("`{}`", name),
+            None => format!("{:?}", id),
+        };
+        eprintln!(
+            "[warn] Node {} produced {} NaN or infinite pixels, the first at \
+             ({}, {})",
+            name,
+            invalid,
+            first % width,
+            first / width,
+        );
+        self.reported.insert(id);
+    }
+}
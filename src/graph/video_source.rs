@@ -0,0 +1,31 @@
+use glium::Texture2d;
+
+use crate::{
+    graph::InputSource,
+    upload::StreamingTexture,
+    video::Video,
+};
+
+/// A source that plays a video file. The texture is black
+/// until the first frame arrives, and holds the last frame
+/// once a video that doesn't loop ends.
+pub struct VideoSource {
+    pub video:   Video,
+    pub texture: StreamingTexture,
+}
+
+impl std::fmt::Debug for VideoSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let texture = self.texture.texture();
+        f.debug_struct("VideoSource")
+            .field("width", &texture.get_width())
+            .field("height", &texture.get_height())
+            .finish()
+    }
+}
+
+impl InputSource for VideoSource {
+    fn update(&mut self) { self.texture.update(); }
+
+    fn texture(&self) -> &Texture2d { self.texture.texture() }
+}
@@ -0,0 +1,16 @@
+use crate::graph::NodeId;
+
+/// A window a node is shown in, besides the main output,
+/// e.g. to watch intermediate buffers on a laptop while the
+/// output is on a projector, see
+/// [`crate::graph::ShaderGraph::add_window`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputWindow {
+    pub node:       NodeId,
+    pub title:      String,
+    /// The size of the window in pixels, or the size of the
+    /// node if not given.
+    pub size:       Option<(u32, u32)>,
+    /// The monitor the window covers, by index, if any.
+    pub fullscreen: Option<usize>,
+}
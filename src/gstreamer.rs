@@ -0,0 +1,448 @@
+//! Feeds frames into a GStreamer pipeline through an
+//! `appsrc`, so that the output of a graph can be encoded,
+//! streamed, or recorded by anything GStreamer can build,
+//! written as for `gst-launch-1.0`. GStreamer is loaded
+//! when the first pipeline is started, so shadergarden
+//! builds without it.
+
+use std::{
+    ffi::{
+        CStr,
+        CString,
+    },
+    os::raw::{
+        c_char,
+        c_int,
+        c_void,
+    },
+};
+
+use glium::Texture2d;
+
+use crate::sink::{
+    read_frame,
+    OutputSink,
+};
+
+/// The name of the `appsrc` put before the pipeline.
+pub const APPSRC: &str = "shadergarden";
+
+const GST_FORMAT_TIME: c_int = 3;
+const GST_STATE_NULL: c_int = 1;
+const GST_STATE_PLAYING: c_int = 4;
+const GST_STATE_CHANGE_FAILURE: c_int = 0;
+const GST_MESSAGE_EOS: c_int = 1 << 0;
+const GST_MESSAGE_ERROR: c_int = 1 << 1;
+const GST_CLOCK_TIME_NONE: u64 = u64::MAX;
+
+/// Frames a live pipeline can fall behind by before frames
+/// are dropped.
+const QUEUED: u64 = 2;
+
+/// The start of `GstBuffer`, after its `GstMiniObject`,
+/// which is the same size on every 64-bit platform.
+#[repr(C)]
+struct GstBuffer {
+    mini_object: [u64; 8],
+    pool:        *mut c_void,
+    pts:         u64,
+    dts:         u64,
+    duration:    u64,
+}
+
+/// The start of `GstMessage`.
+#[repr(C)]
+struct GstMessage {
+    mini_object: [u64; 8],
+    kind:        c_int,
+}
+
+/// The start of `GError`.
+#[repr(C)]
+struct GError {
+    domain:  u32,
+    code:    c_int,
+    message: *const c_char,
+}
+
+type Init = unsafe extern "C" fn(*mut c_int, *mut *mut *mut c_char);
+type ParseLaunch =
+    unsafe extern "C" fn(*const c_char, *mut *mut GError) -> *mut c_void;
+type GetByName =
+    unsafe extern "C" fn(*mut c_void, *const c_char) -> *mut c_void;
+type CapsFromString = unsafe extern "C" fn(*const c_char) -> *mut c_void;
+type Unref = unsafe extern "C" fn(*mut c_void);
+type BufferNew =
+    unsafe extern "C" fn(*mut c_void, usize, *mut c_void) -> *mut GstBuffer;
+type BufferFill =
+    unsafe extern "C" fn(*mut GstBuffer, usize, *const c_void, usize) -> usize;
+type SetState = unsafe extern "C" fn(*mut c_void, c_int) -> c_int;
+type GetBus = unsafe extern "C" fn(*mut c_void) -> *mut c_void;
+type TimedPop =
+    unsafe extern "C" fn(*mut c_void, u64, c_int) -> *mut GstMessage;
+type ParseError =
+    unsafe extern "C" fn(*mut GstMessage, *mut *mut GError, *mut *mut c_char);
+type ObjectSet = unsafe extern "C" fn(*mut c_void, *const c_char, ...);
+type ErrorFree = unsafe extern "C" fn(*mut GError);
+type SetCaps = unsafe extern "C" fn(*mut c_void, *mut c_void);
+type PushBuffer = unsafe extern "C" fn(*mut c_void, *mut GstBuffer) -> c_int;
+type EndOfStream = unsafe extern "C" fn(*mut c_void) -> c_int;
+type LevelBytes = unsafe extern "C" fn(*mut c_void) -> u64;
+
+/// The functions used from GStreamer, and its app library.
+struct Library {
+    parse_launch:      ParseLaunch,
+    get_by_name:       GetByName,
+    caps_from_string:  CapsFromString,
+    mini_object_unref: Unref,
+    object_unref:      Unref,
+    buffer_new:        BufferNew,
+    buffer_fill:       BufferFill,
+    set_state:         SetState,
+    get_bus:           GetBus,
+    timed_pop:         TimedPop,
+    parse_error:       ParseError,
+    object_set:        ObjectSet,
+    error_free:        ErrorFree,
+    set_caps:          SetCaps,
+    push_buffer:       PushBuffer,
+    end_of_stream:     EndOfStream,
+    level_bytes:       LevelBytes,
+}
+
+impl Library {
+    #[cfg(unix)]
+    fn load() -> Result<Library, String> {
+        #[cfg(target_os = "macos")]
+        const NAMES: [&[&str]; 2] =
+            [&["libgstreamer-1.0.0.dylib"], &["libgstapp-1.0.0.dylib"]];
+        #[cfg(not(target_os = "macos"))]
+        const NAMES: [&[&str]; 2] = [
+            &["libgstreamer-1.0.so.0", "libgstreamer-1.0.so"],
+            &["libgstapp-1.0.so.0", "libgstapp-1.0.so"],
+        ];
+
+        // safety: the libraries are GStreamer's, whose
+        // functions have the signatures from gst/gst.h and
+        // gst/app/app.h
+        unsafe {
+            let open = |names: &[&str]| {
+                names
+                    .iter()
+                    .filter_map(|name| CString::new(*name).ok())
+                    .map(|name| libc::dlopen(name.as_ptr(), libc::RTLD_NOW))
+                    .find(|library| !library.is_null())
+                    .ok_or_else(|| {
+                        "Could not find GStreamer, install it with its base \
+                         plugins"
+                            .to_string()
+                    })
+            };
+            let gst = open(NAMES[0])?;
+            let app = open(NAMES[1])?;
+
+            let symbol = |library, name: &[u8]| {
+                let symbol = libc::dlsym(library, name.as_ptr() as *const _);
+                if symbol.is_null() {
+                    Err("GStreamer is missing functions, update it".to_string())
+                } else {
+                    Ok(symbol)
+                }
+            };
+            // glib is found through GStreamer, which links it
+            let init: Init = std::mem::transmute(symbol(gst, b"gst_init\0")?);
+            let parse_launch: ParseLaunch =
+                std::mem::transmute(symbol(gst, b"gst_parse_launch\0")?);
+            let get_by_name: GetByName =
+                std::mem::transmute(symbol(gst, b"gst_bin_get_by_name\0")?);
+            let caps_from_string: CapsFromString =
+                std::mem::transmute(symbol(gst, b"gst_caps_from_string\0")?);
+            let mini_object_unref: Unref =
+                std::mem::transmute(symbol(gst, b"gst_mini_object_unref\0")?);
+            let object_unref: Unref =
+                std::mem::transmute(symbol(gst, b"gst_object_unref\0")?);
+            let buffer_new: BufferNew =
+                std::mem::transmute(symbol(gst, b"gst_buffer_new_allocate\0")?);
+            let buffer_fill: BufferFill =
+                std::mem::transmute(symbol(gst, b"gst_buffer_fill\0")?);
+            let set_state: SetState =
+                std::mem::transmute(symbol(gst, b"gst_element_set_state\0")?);
+            let get_bus: GetBus =
+                std::mem::transmute(symbol(gst, b"gst_element_get_bus\0")?);
+            let timed_pop: TimedPop = std::mem::transmute(symbol(
+                gst,
+                b"gst_bus_timed_pop_filtered\0",
+            )?);
+            let parse_error: ParseError =
+                std::mem::transmute(symbol(gst, b"gst_message_parse_error\0")?);
+            let object_set: ObjectSet =
+                std::mem::transmute(symbol(gst, b"g_object_set\0")?);
+            let error_free: ErrorFree =
+                std::mem::transmute(symbol(gst, b"g_error_free\0")?);
+            let set_caps: SetCaps =
+                std::mem::transmute(symbol(app, b"gst_app_src_set_caps\0")?);
+            let push_buffer: PushBuffer =
+                std::mem::transmute(symbol(app, b"gst_app_src_push_buffer\0")?);
+            let end_of_stream: EndOfStream = std::mem::transmute(symbol(
+                app,
+                b"gst_app_src_end_of_stream\0",
+            )?);
+            let level_bytes: LevelBytes = std::mem::transmute(symbol(
+                app,
+                b"gst_app_src_get_current_level_bytes\0",
+            )?);
+
+            init(std::ptr::null_mut(), std::ptr::null_mut());
+            Ok(Library {
+                parse_launch,
+                get_by_name,
+                caps_from_string,
+                mini_object_unref,
+                object_unref,
+                buffer_new,
+                buffer_fill,
+                set_state,
+                get_bus,
+                timed_pop,
+                parse_error,
+                object_set,
+                error_free,
+                set_caps,
+                push_buffer,
+                end_of_stream,
+                level_bytes,
+            })
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn load() -> Result<Library, String> {
+        Err("GStreamer pipelines are only supported on Unix".to_string())
+    }
+
+    /// The message of an error, which is freed.
+    ///
+    /// # Safety
+    ///
+    /// The error must be null, or one GStreamer made.
+    unsafe fn take_error(&self, error: *mut GError) -> String {
+        if error.is_null() {
+            return "unknown error".to_string();
+        }
+        let message = CStr::from_ptr((*error).message)
+            .to_string_lossy()
+            .into_owned();
+        (self.error_free)(error);
+        message
+    }
+}
+
+/// A GStreamer pipeline, fed every frame pushed to it as
+/// RGBA, top row first, timestamped by frame. Each frame is
+/// read back from the GPU once. Offline renders wait for
+/// the pipeline to take each frame, while live pipelines
+/// drop frames when they fall behind.
+pub struct GstreamerSink {
+    description: String,
+    library:     Library,
+    pipeline:    *mut c_void,
+    appsrc:      *mut c_void,
+    bus:         *mut c_void,
+    size:        (u32, u32),
+    fps:         f64,
+    live:        bool,
+    frame:       u64,
+}
+
+impl GstreamerSink {
+    /// Starts a pipeline, written as for `gst-launch-1.0`,
+    /// e.g. `videoconvert ! x264enc ! mp4mux ! filesink
+    /// location=out.mp4`, after an `appsrc` for frames of
+    /// `size` pushed at `fps`.
+    pub fn new(
+        description: &str,
+        size: (u32, u32),
+        fps: f64,
+        live: bool,
+    ) -> Result<GstreamerSink, String> {
+        let library = Library::load()?;
+        let launch = format!("appsrc name={} ! {}", APPSRC, description);
+        let launch = CString::new(launch)
+            .map_err(|_| "A GStreamer pipeline can't hold a nul".to_string())?;
+        let caps = CString::new(format!(
+            "video/x-raw,format=RGBA,width={},height={},framerate={}/1000",
+            size.0,
+            size.1,
+            (fps * 1000.0).round() as u64
+        ))
+        .unwrap();
+        let name = CString::new(APPSRC).unwrap();
+        let format = CString::new("format").unwrap();
+        let block = CString::new("block").unwrap();
+
+        // safety: the strings outlive the calls, and every
+        // object is checked before it's used
+        unsafe {
+            let mut error = std::ptr::null_mut();
+            let pipeline = (library.parse_launch)(launch.as_ptr(), &mut error);
+            if pipeline.is_null() {
+                return Err(format!(
+                    "Invalid GStreamer pipeline: {}",
+                    library.take_error(error)
+                ));
+            }
+            // a pipeline that only partly parsed still runs
+            if !error.is_null() {
+                let message = library.take_error(error);
+                (library.object_unref)(pipeline);
+                return Err(format!("Invalid GStreamer pipeline: {}", message));
+            }
+            let appsrc = (library.get_by_name)(pipeline, name.as_ptr());
+            let caps = (library.caps_from_string)(caps.as_ptr());
+            (library.set_caps)(appsrc, caps);
+            (library.mini_object_unref)(caps);
+            (library.object_set)(
+                appsrc,
+                format.as_ptr(),
+                GST_FORMAT_TIME,
+                block.as_ptr(),
+                !live as c_int,
+                std::ptr::null::<c_char>(),
+            );
+            let bus = (library.get_bus)(pipeline);
+
+            let sink = GstreamerSink {
+                description: description.to_string(),
+                library,
+                pipeline,
+                appsrc,
+                bus,
+                size,
+                fps,
+                live,
+                frame: 0,
+            };
+            if (sink.library.set_state)(pipeline, GST_STATE_PLAYING)
+                == GST_STATE_CHANGE_FAILURE
+            {
+                return Err(sink.failure().unwrap_or_else(|| {
+                    "Could not start the GStreamer pipeline".to_string()
+                }));
+            }
+            Ok(sink)
+        }
+    }
+
+    /// The error the pipeline stopped with, if it has, or
+    /// the end of the stream, if `eos` and it's been
+    /// reached, waiting up to `timeout` nanoseconds for
+    /// either.
+    fn poll(&self, timeout: u64, eos: bool) -> Option<Result<(), String>> {
+        let kinds = if eos {
+            GST_MESSAGE_ERROR | GST_MESSAGE_EOS
+        } else {
+            GST_MESSAGE_ERROR
+        };
+        // safety: the bus is the pipeline's, and the message
+        // is unreferenced once it's been read
+        unsafe {
+            let message = (self.library.timed_pop)(self.bus, timeout, kinds);
+            if message.is_null() {
+                return None;
+            }
+            let result = if (*message).kind == GST_MESSAGE_ERROR {
+                let mut error = std::ptr::null_mut();
+                (self.library.parse_error)(
+                    message,
+                    &mut error,
+                    std::ptr::null_mut(),
+                );
+                Err(format!("GStreamer: {}", self.library.take_error(error)))
+            } else {
+                Ok(())
+            };
+            (self.library.mini_object_unref)(message as *mut c_void);
+            Some(result)
+        }
+    }
+
+    /// The error the pipeline stopped with, if it has.
+    fn failure(&self) -> Option<String> { self.poll(0, false)?.err() }
+}
+
+impl OutputSink for GstreamerSink {
+    fn name(&self) -> String { format!("GStreamer `{}`", self.description) }
+
+    fn push(&mut self, texture: &Texture2d) -> Result<(), String> {
+        if let Some(e) = self.failure() {
+            return Err(e);
+        }
+        if texture.dimensions() != self.size {
+            return Err(format!(
+                "The output changed size, from {}x{} to {}x{}",
+                self.size.0,
+                self.size.1,
+                texture.width(),
+                texture.height()
+            ));
+        }
+        let frame = self.frame;
+        self.frame += 1;
+        let pixels = read_frame(texture).into_raw();
+
+        let library = &self.library;
+        let nanos = |frame: u64| (frame as f64 * 1e9 / self.fps) as u64;
+        // safety: the buffer is allocated at the frame's size,
+        // and handed to the appsrc, which takes it
+        unsafe {
+            let queued = (library.level_bytes)(self.appsrc);
+            if self.live && queued >= QUEUED * pixels.len() as u64 {
+                return Ok(());
+            }
+            let buffer = (library.buffer_new)(
+                std::ptr::null_mut(),
+                pixels.len(),
+                std::ptr::null_mut(),
+            );
+            if buffer.is_null() {
+                return Err("Could not allocate a GStreamer buffer".to_string());
+            }
+            (library.buffer_fill)(
+                buffer,
+                0,
+                pixels.as_ptr() as *const c_void,
+                pixels.len(),
+            );
+            (*buffer).pts = nanos(frame);
+            (*buffer).duration = nanos(frame + 1) - nanos(frame);
+            if (library.push_buffer)(self.appsrc, buffer) != 0 {
+                return Err(self.failure().unwrap_or_else(|| {
+                    "The GStreamer pipeline stopped taking frames".to_string()
+                }));
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), String> {
+        // safety: the appsrc is the pipeline's
+        unsafe { (self.library.end_of_stream)(self.appsrc) };
+        match self.poll(GST_CLOCK_TIME_NONE, true) {
+            Some(Err(e)) => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for GstreamerSink {
+    fn drop(&mut self) {
+        // safety: each object is unreferenced once, after the
+        // pipeline stops
+        unsafe {
+            (self.library.set_state)(self.pipeline, GST_STATE_NULL);
+            (self.library.object_unref)(self.bus);
+            (self.library.object_unref)(self.appsrc);
+            (self.library.object_unref)(self.pipeline);
+        }
+    }
+}
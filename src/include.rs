@@ -0,0 +1,213 @@
+//! GLSL `#include`s, which shaders can use to share code
+//! kept in other files, like `#include "lib/noise.glsl"`.
+//! They are expanded before a shader is compiled, and each
+//! included file is numbered as its own source string, so
+//! that compiler errors can be traced back to it.
+
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use crate::reload::shader_dir::resolve;
+
+/// The GLSL version of a shader, from its `#version` line,
+/// or 110, the version of shaders without one.
+pub fn glsl_version(source: &str) -> u32 {
+    source
+        .lines()
+        .find_map(|line| line.trim_start().strip_prefix("#version"))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|number| number.parse().ok())
+        .unwrap_or(110)
+}
+
+/// A `#line` directive numbering the line after it `line`
+/// of source string `string`, in a shader of a GLSL
+/// version. Before GLSL 3.30, `#line` numbered the line
+/// after it from one past the number given.
+pub fn line_directive(version: u32, line: u32, string: usize) -> String {
+    let line = if version >= 330 { line } else { line - 1 };
+    format!("#line {} {}", line, string)
+}
+
+/// The helper library shaders can turn parts of on, with a
+/// line like `#define SG_AA 1`, see [`LIBRARY_FLAGS`]. It
+/// is inserted after the last such line, and changes with
+/// the crate, whose version it defines as `SG_VERSION`,
+/// e.g. 200 for 0.2.0.
+pub const LIBRARY: &str = include_str!("./graph/shaders/garden.glsl");
+
+/// The parts of [`LIBRARY`]: antialiasing with `fwidth`,
+/// hashes, and color space conversions.
+pub const LIBRARY_FLAGS: [&str; 3] = ["SG_AA", "SG_HASH", "SG_COLOR"];
+
+/// The source string [`LIBRARY`] is numbered as, past any
+/// number of included files.
+pub const LIBRARY_STRING: usize = 1000;
+
+/// Whether a line turns on a part of [`LIBRARY`].
+fn library_flag(line: &str) -> bool {
+    let rest = line.trim_start().strip_prefix('#');
+    let rest = rest.and_then(|r| r.trim_start().strip_prefix("define"));
+    let name = rest.and_then(|r| r.split_whitespace().next());
+    name.is_some_and(|name| LIBRARY_FLAGS.contains(&name))
+}
+
+/// The crate's version as a number, see [`LIBRARY`].
+fn library_version() -> u32 {
+    let part = |part: &str| part.parse::<u32>().unwrap_or(0);
+    part(env!("CARGO_PKG_VERSION_MAJOR")) * 10000
+        + part(env!("CARGO_PKG_VERSION_MINOR")) * 100
+        + part(env!("CARGO_PKG_VERSION_PATCH"))
+}
+
+/// The file named by an `#include` line, if it is one.
+fn included(line: &str) -> Option<Result<&str, ()>> {
+    let rest = line.trim_start().strip_prefix('#')?;
+    let rest = rest.trim_start().strip_prefix("include")?.trim();
+    let name = rest
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .or_else(|| rest.strip_prefix('<')?.strip_suffix('>'));
+    Some(name.ok_or(()))
+}
+
+/// A shader with its `#include`s expanded.
+#[derive(Debug, Clone)]
+pub struct Expanded {
+    pub source: String,
+    /// Every file included, directly or not. The file at
+    /// index `i` is numbered as source string `i + 1`.
+    pub files:  Vec<PathBuf>,
+}
+
+/// Expands the `#include` lines of a shader, with files
+/// found relative to `root`, and only inside it unless
+/// `allow_outside`, see [`resolve`]. A file is only
+/// included once, however many times it is named, and a
+/// file including itself is an error. Errors are written as
+/// compiler logs, so they are located like any other, see
+/// [`crate::diagnostic`]. The parts of the [`LIBRARY`] the
+/// shader turns on are inserted too.
+pub fn expand(
+    source: &str,
+    root: &Path,
+    allow_outside: bool,
+) -> Result<Expanded, String> {
+    let mut expanded = Expanded {
+        source: String::with_capacity(source.len()),
+        files:  vec![],
+    };
+    let library = source
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| library_flag(line))
+        .map(|(number, _)| number as u32 + 1)
+        .last();
+    // shaders without includes are left exactly as written
+    let includes = source.lines().any(|line| included(line).is_some());
+    if !includes && library.is_none() {
+        expanded.source = source.to_string();
+        return Ok(expanded);
+    }
+
+    let version = glsl_version(source);
+    let mut stack = vec![];
+    let files = Files {
+        root,
+        allow_outside,
+        version,
+        library,
+    };
+    expand_into(&mut expanded, &mut stack, source, 0, &files)?;
+    Ok(expanded)
+}
+
+/// Where included files are found, and the version of the
+/// shader including them.
+struct Files<'a> {
+    root:          &'a Path,
+    allow_outside: bool,
+    version:       u32,
+    /// The line of the shader the [`LIBRARY`] is inserted
+    /// after, if it turns any of it on.
+    library:       Option<u32>,
+}
+
+fn expand_into(
+    expanded: &mut Expanded,
+    stack: &mut Vec<PathBuf>,
+    source: &str,
+    string: usize,
+    files: &Files,
+) -> Result<(), String> {
+    let version = files.version;
+    for (number, line) in source.lines().enumerate() {
+        let number = number as u32 + 1;
+        let error = |message: String| {
+            format!("{}:{}: error: {}", string, number, message)
+        };
+        let name = match included(line) {
+            Some(Ok(name)) => name,
+            Some(Err(())) => {
+                return Err(error("Expected `#include \"<file>\"`".to_string()))
+            },
+            None => {
+                expanded.source.push_str(line);
+                expanded.source.push('\n');
+                if string == 0 && files.library == Some(number) {
+                    push_library(expanded, version, number);
+                }
+                continue;
+            },
+        };
+
+        let path = resolve(files.root, name, files.allow_outside)
+            .map_err(|e| error(format!("Could not include {}", e)))?
+            .canonicalize()
+            .map_err(|e| {
+                error(format!("Could not include `{}`: {}", name, e))
+            })?;
+        if stack.contains(&path) {
+            return Err(error(format!("`{}` includes itself", name)));
+        }
+        // already included, the line is left blank so that
+        // the lines after it keep their numbers
+        if expanded.files.contains(&path) {
+            expanded.source.push('\n');
+            continue;
+        }
+        let contents = fs::read_to_string(&path).map_err(|e| {
+            error(format!("Could not include `{}`: {}", name, e))
+        })?;
+
+        expanded.files.push(path.clone());
+        let index = expanded.files.len();
+        expanded.source.push_str(&line_directive(version, 1, index));
+        expanded.source.push('\n');
+        stack.push(path);
+        expand_into(expanded, stack, &contents, index, files)?;
+        stack.pop();
+        let resume = line_directive(version, number + 1, string);
+        expanded.source.push_str(&resume);
+        expanded.source.push('\n');
+    }
+    Ok(())
+}
+
+/// Inserts the [`LIBRARY`] after a line of a shader.
+fn push_library(expanded: &mut Expanded, version: u32, number: u32) {
+    let source = &mut expanded.source;
+    source.push_str("#ifndef SG_VERSION\n");
+    source.push_str(&format!("#define SG_VERSION {}\n", library_version()));
+    source.push_str("#endif\n");
+    source.push_str(&line_directive(version, 1, LIBRARY_STRING));
+    source.push('\n');
+    source.push_str(LIBRARY);
+    source.push_str(&line_directive(version, number + 1, 0));
+    source.push('\n');
+}
@@ -1,4 +1,11 @@
-use std::path::Path;
+use std::{
+    path::Path,
+    sync::mpsc::{
+        self,
+        SyncSender,
+    },
+    thread,
+};
 
 use ffmpeg_next::{
     format::{
@@ -14,13 +21,20 @@ use ffmpeg_next::{
 };
 use glium::{
     backend::Facade,
-    texture::RawImage2d,
     Texture2d,
 };
 
+use crate::upload::{
+    Frame,
+    StreamingTexture,
+};
+
+/// A looping video, decoded on a background thread. At
+/// most `budget` bytes of each frame are uploaded per call
+/// to [`FrameStream::next_frame`], see
+/// [`StreamingTexture`].
 pub struct FrameStream {
-    frames: Vec<Texture2d>,
-    index:  usize,
+    texture: StreamingTexture,
 }
 
 impl FrameStream {
@@ -28,51 +42,108 @@ impl FrameStream {
         filename: &Path,
         width: u32,
         height: u32,
+        budget: usize,
         facade: &F,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         ffmpeg_next::init().unwrap();
-        let mut ictx = input(&filename)?;
-
-        let input = ictx.streams().best(Type::Video).expect("no video stream");
-        let video_stream_index = input.index();
-        let mut decoder = input.codec().decoder().video()?;
-
-        let mut scaler = Context::get(
-            decoder.format(),
-            decoder.width(),
-            decoder.height(),
-            Pixel::RGB24,
-            width,
-            height,
-            Flags::BILINEAR,
-        )?;
-
-        let mut frames = vec![];
-        for (stream, packet) in ictx.packets() {
-            if stream.index() == video_stream_index {
-                decoder.send_packet(&packet)?;
-                let mut decoded = Video::empty();
-                while decoder.receive_frame(&mut decoded).is_ok() {
-                    let mut rgb_frame = Video::empty();
-                    scaler
-                        .run(&decoded, &mut rgb_frame)
-                        .expect("scaler failed");
-                    let data = rgb_frame.data(0);
-                    let raw_image = RawImage2d::from_raw_rgb_reversed(
-                        data,
-                        (width, height),
+
+        // fail early if the file can't be decoded at all
+        let ictx = input(&filename)?;
+        ictx.streams().best(Type::Video).ok_or("no video stream")?;
+
+        // a small queue keeps the decoder just ahead of playback
+        let (sender, receiver) = mpsc::sync_channel(2);
+        let filename = filename.to_path_buf();
+        thread::spawn(move || loop {
+            match decode(&filename, width, height, &sender) {
+                Ok(true) => continue,
+                Ok(false) => return,
+                Err(e) => {
+                    eprintln!(
+                        "[warn] Could not decode `{}`: {}",
+                        filename.display(),
+                        e
                     );
-                    frames.push(Texture2d::new(facade, raw_image).unwrap());
-                }
+                    return;
+                },
             }
-        }
-        decoder.send_eof()?;
-        Ok(Self { frames, index: 0 })
+        });
+
+        let texture =
+            StreamingTexture::new(facade, width, height, receiver, budget)?;
+        Ok(Self { texture })
     }
 
-    pub fn next_frame(&mut self) -> &Texture2d {
-        let frame = &self.frames[self.index % self.frames.len()];
-        self.index += 1;
-        frame
+    pub fn next_frame(&mut self) -> &Texture2d { self.texture.update() }
+
+    /// The next frame of the video, uploaded whole, waiting
+    /// for it to be decoded, so that offline renders see
+    /// every frame once, in order.
+    pub fn next_whole_frame(&mut self) -> &Texture2d {
+        self.texture.next_whole()
     }
+
+    /// The most recent complete frame, without uploading
+    /// any more of the next one.
+    pub fn current_frame(&self) -> &Texture2d { self.texture.texture() }
+}
+
+/// Decodes every frame of a video once, sending each one.
+/// Returns `false` once the stream has been dropped.
+fn decode(
+    filename: &Path,
+    width: u32,
+    height: u32,
+    sender: &SyncSender<Frame>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut ictx = input(filename)?;
+
+    let input = ictx.streams().best(Type::Video).ok_or("no video stream")?;
+    let video_stream_index = input.index();
+    let mut decoder = input.codec().decoder().video()?;
+
+    let mut scaler = Context::get(
+        decoder.format(),
+        decoder.width(),
+        decoder.height(),
+        Pixel::RGB24,
+        width,
+        height,
+        Flags::BILINEAR,
+    )?;
+
+    for (stream, packet) in ictx.packets() {
+        if stream.index() != video_stream_index {
+            continue;
+        }
+
+        decoder.send_packet(&packet)?;
+        let mut decoded = Video::empty();
+        while decoder.receive_frame(&mut decoded).is_ok() {
+            let mut rgb_frame = Video::empty();
+            scaler.run(&decoded, &mut rgb_frame)?;
+            if sender.send(to_frame(&rgb_frame, width, height)).is_err() {
+                return Ok(false);
+            }
+        }
+    }
+    decoder.send_eof()?;
+    Ok(true)
+}
+
+/// Flips a decoded frame so that it starts at the bottom
+/// row, dropping any padding at the end of each row.
+fn to_frame(rgb_frame: &Video, width: u32, height: u32) -> Frame {
+    let data = rgb_frame.data(0);
+    let stride = rgb_frame.stride(0);
+    let row_bytes = width as usize * 3;
+
+    (0..height as usize)
+        .rev()
+        .flat_map(|row| {
+            data[row * stride..row * stride + row_bytes]
+                .chunks_exact(3)
+                .map(|p| (p[0], p[1], p[2]))
+        })
+        .collect()
 }
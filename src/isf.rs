@@ -0,0 +1,446 @@
+//! Shaders in the Interactive Shader Format, as used by VJ
+//! software. An ISF shader is GLSL with a JSON header
+//! declaring its inputs and passes. Here, its images are
+//! the inputs of a node, and its other inputs are params of
+//! that node, see [`crate::graph::ShaderGraph::set_param`].
+
+use std::fmt::Write;
+
+use serde_json::Value;
+
+/// The type of an input, with its default value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputKind {
+    /// An image, or an audio waveform or spectrum, which is
+    /// read from an input of the node.
+    Image,
+    Float(f32),
+    Bool(bool),
+    /// An integer, often picked from a list of values.
+    Long(i32),
+    /// A button, which is only true while it's pressed.
+    Event,
+    Point2D([f32; 2]),
+    Color([f32; 4]),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Input {
+    pub name: String,
+    pub kind: InputKind,
+}
+
+/// A pass, which renders to a named target that later
+/// passes can read, except for the last.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pass {
+    pub target:     Option<String>,
+    /// Whether the target is kept from frame to frame, so
+    /// the pass can read its own last frame.
+    pub persistent: bool,
+    /// Whether the target is stored as floats.
+    pub float:      bool,
+    /// The size of the target, as expressions of `$WIDTH`
+    /// and `$HEIGHT`, see [`Isf::size`].
+    pub width:      Option<String>,
+    pub height:     Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Isf {
+    pub description: Option<String>,
+    pub inputs:      Vec<Input>,
+    pub passes:      Vec<Pass>,
+    /// The GLSL after the header.
+    pub code:        String,
+}
+
+impl Isf {
+    /// Splits a shader into its JSON header, which must
+    /// come first, in a `/* */` comment, and its code.
+    pub fn parse(text: &str) -> Result<Isf, String> {
+        let (header, code) = text
+            .trim_start()
+            .strip_prefix("/*")
+            .and_then(|rest| rest.split_once("*/"))
+            .ok_or("Expected an ISF shader, starting with a JSON comment")?;
+        let json: Value = serde_json::from_str(header)
+            .map_err(|e| format!("The ISF header is not valid JSON: {}", e))?;
+
+        let mut inputs = vec![];
+        for input in json["INPUTS"].as_array().into_iter().flatten() {
+            let name = input["NAME"].as_str().ok_or("An input has no NAME")?;
+            let default = &input["DEFAULT"];
+            let number =
+                |fallback: f32| default.as_f64().map_or(fallback, |n| n as f32);
+            let kind = match input["TYPE"].as_str().unwrap_or_default() {
+                "image" | "audio" | "audioFFT" => InputKind::Image,
+                "float" => InputKind::Float(number(0.0)),
+                "bool" => InputKind::Bool(
+                    default.as_bool().unwrap_or_else(|| number(0.0) != 0.0),
+                ),
+                "long" => InputKind::Long(number(0.0) as i32),
+                "event" => InputKind::Event,
+                "point2D" => {
+                    let [x, y] = components(default, [0.0; 2]);
+                    InputKind::Point2D([x, y])
+                },
+                "color" => InputKind::Color(components(default, [0.0; 4])),
+                other => {
+                    return Err(format!(
+                        "Input `{}` has type `{}`, which is not supported",
+                        name, other
+                    ))
+                },
+            };
+            inputs.push(Input {
+                name: name.to_string(),
+                kind,
+            });
+        }
+        // images the shader ships with are passed in too
+        if let Some(imported) = json["IMPORTED"].as_object() {
+            for name in imported.keys() {
+                inputs.push(Input {
+                    name: name.to_string(),
+                    kind: InputKind::Image,
+                });
+            }
+        }
+
+        let size = |pass: &Value, key| match &pass[key] {
+            Value::String(size) => Some(size.clone()),
+            Value::Number(size) => Some(size.to_string()),
+            _ => None,
+        };
+        let mut passes = json["PASSES"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|pass| Pass {
+                target:     pass["TARGET"].as_str().map(str::to_string),
+                persistent: truthy(&pass["PERSISTENT"]),
+                float:      truthy(&pass["FLOAT"]),
+                width:      size(pass, "WIDTH"),
+                height:     size(pass, "HEIGHT"),
+            })
+            .collect::<Vec<_>>();
+        if passes.is_empty() {
+            passes.push(Pass {
+                target:     None,
+                persistent: false,
+                float:      false,
+                width:      None,
+                height:     None,
+            });
+        }
+
+        Ok(Isf {
+            description: json["DESCRIPTION"].as_str().map(str::to_string),
+            inputs,
+            passes,
+            code: code.to_string(),
+        })
+    }
+
+    /// The inputs read from images, in order.
+    pub fn images(&self) -> impl Iterator<Item = &Input> {
+        self.inputs.iter().filter(|i| i.kind == InputKind::Image)
+    }
+
+    /// The params each other input is passed as, with their
+    /// defaults. Points and colors are split into one param
+    /// per component, like `center_x` and `tint_r`.
+    pub fn params(&self) -> Vec<(String, f32)> {
+        let mut params = vec![];
+        for input in self.inputs.iter() {
+            let name = &input.name;
+            match &input.kind {
+                InputKind::Image => (),
+                InputKind::Float(value) => params.push((name.clone(), *value)),
+                InputKind::Bool(value) => {
+                    params.push((name.clone(), *value as u8 as f32))
+                },
+                InputKind::Long(value) => {
+                    params.push((name.clone(), *value as f32))
+                },
+                InputKind::Event => params.push((name.clone(), 0.0)),
+                InputKind::Point2D(point) => {
+                    for (suffix, value) in ["x", "y"].iter().zip(point) {
+                        params.push((format!("{}_{}", name, suffix), *value));
+                    }
+                },
+                InputKind::Color(color) => {
+                    for (suffix, value) in
+                        ["r", "g", "b", "a"].iter().zip(color)
+                    {
+                        params.push((format!("{}_{}", name, suffix), *value));
+                    }
+                },
+            }
+        }
+        params
+    }
+
+    /// The size of a pass, given the size of the node. ISF
+    /// sizes can be expressions like `floor($WIDTH / 2)`,
+    /// written with numbers, `$WIDTH`, `$HEIGHT`, `+`, `-`,
+    /// `*`, `/`, parentheses, and the functions `floor`,
+    /// `ceil`, `round`, `abs`, `sqrt`, `min`, `max`, and
+    /// `pow`.
+    pub fn size(
+        &self,
+        pass: usize,
+        width: u32,
+        height: u32,
+    ) -> Result<(u32, u32), String> {
+        let pass = &self.passes[pass];
+        let eval = |size: &Option<String>, default: u32| match size {
+            Some(size) => {
+                let size = size
+                    .replace("$WIDTH", &width.to_string())
+                    .replace("$HEIGHT", &height.to_string());
+                let chars = size.chars().filter(|c| !c.is_whitespace());
+                let mut tokens = Tokens {
+                    chars: chars.collect(),
+                    at:    0,
+                };
+                let value = tokens.sum();
+                match value {
+                    Some(value) if tokens.at == tokens.chars.len() => {
+                        Ok((value.floor() as u32).max(1))
+                    },
+                    _ => Err(format!("Could not evaluate size `{}`", size)),
+                }
+            },
+            None => Ok(default),
+        };
+        Ok((eval(&pass.width, width)?, eval(&pass.height, height)?))
+    }
+
+    /// The GLSL for a pass, with ISF's built-ins defined in
+    /// terms of shadergarden's. `samplers` maps each image
+    /// and target the pass can read to the uniform it's
+    /// bound to; targets not listed are left unbound, as
+    /// passes can only read the targets of earlier passes,
+    /// or their own last frame.
+    pub fn source(&self, pass: usize, samplers: &[(&str, String)]) -> String {
+        let mut source = String::from(
+            "#version 330
+
+in  vec2 coords;
+out vec4 color;
+
+uniform vec2  u_resolution;
+uniform float u_time;
+uniform float u_dt;
+uniform int   u_frame;
+",
+        );
+
+        // every image and target, bound or not
+        let mut declared = vec![];
+        let targets = self.passes.iter().filter_map(|p| p.target.as_deref());
+        for name in self.images().map(|i| i.name.as_str()).chain(targets) {
+            match samplers.iter().find(|(image, _)| *image == name) {
+                Some((_, uniform)) => {
+                    if !declared.contains(uniform) {
+                        writeln!(source, "uniform sampler2D {};", uniform)
+                            .unwrap();
+                        declared.push(uniform.clone());
+                    }
+                    writeln!(source, "#define {} {}", name, uniform).unwrap();
+                },
+                None => {
+                    writeln!(source, "uniform sampler2D {};", name).unwrap()
+                },
+            }
+        }
+
+        for input in self.inputs.iter() {
+            let name = &input.name;
+            let components = |suffixes: &[&str]| {
+                suffixes
+                    .iter()
+                    .map(|suffix| format!("u_{}_{}", name, suffix))
+                    .collect::<Vec<_>>()
+            };
+            let (uniforms, value) = match &input.kind {
+                InputKind::Image => continue,
+                InputKind::Float(_) => {
+                    (vec![format!("u_{}", name)], format!("u_{}", name))
+                },
+                InputKind::Bool(_) | InputKind::Event => {
+                    (vec![format!("u_{}", name)], format!("(u_{} > 0.5)", name))
+                },
+                InputKind::Long(_) => (
+                    vec![format!("u_{}", name)],
+                    format!("int(round(u_{}))", name),
+                ),
+                InputKind::Point2D(_) => {
+                    let uniforms = components(&["x", "y"]);
+                    let value = format!("vec2({})", uniforms.join(", "));
+                    (uniforms, value)
+                },
+                InputKind::Color(_) => {
+                    let uniforms = components(&["r", "g", "b", "a"]);
+                    let value = format!("vec4({})", uniforms.join(", "));
+                    (uniforms, value)
+                },
+            };
+            for uniform in uniforms {
+                writeln!(source, "uniform float {};", uniform).unwrap();
+            }
+            writeln!(source, "#define {} {}", name, value).unwrap();
+        }
+
+        write!(
+            source,
+            "
+// ISF's built-ins, in terms of shadergarden's
+#define PASSINDEX {}
+#define TIME u_time
+#define TIMEDELTA u_dt
+#define RENDERSIZE u_resolution
+#define FRAMEINDEX u_frame
+#define DATE vec4(0., 0., 0., u_time)
+#define isf_FragNormCoord coords
+#define vv_FragNormCoord coords
+#define texture2D texture
+#define IMG_SIZE(image) vec2(textureSize(image, 0))
+#define IMG_NORM_PIXEL(image, coord) texture(image, coord)
+#define IMG_PIXEL(image, coord) texture(image, (coord) / IMG_SIZE(image))
+#define IMG_THIS_NORM_PIXEL(image) texture(image, coords)
+#define IMG_THIS_PIXEL(image) texture(image, coords)
+",
+            pass
+        )
+        .unwrap();
+
+        // `gl_FragColor` is gone from newer GLSL, and names
+        // starting with `gl_` can't be defined
+        source.push_str(&self.code.replace("gl_FragColor", "color"));
+        source
+    }
+}
+
+/// JSON booleans, or numbers where anything but zero is
+/// true, as ISF allows both.
+fn truthy(value: &Value) -> bool {
+    value
+        .as_bool()
+        .unwrap_or_else(|| value.as_f64().is_some_and(|n| n != 0.0))
+}
+
+/// The numbers in a JSON array, padded with `fallback`.
+fn components<const N: usize>(value: &Value, fallback: [f32; N]) -> [f32; N] {
+    let mut out = fallback;
+    let values = value.as_array().into_iter().flatten();
+    for (out, value) in out.iter_mut().zip(values) {
+        if let Some(value) = value.as_f64() {
+            *out = value as f32;
+        }
+    }
+    out
+}
+
+/// A tiny parser for the arithmetic in ISF sizes.
+struct Tokens {
+    chars: Vec<char>,
+    at:    usize,
+}
+
+impl Tokens {
+    fn peek(&self) -> Option<char> { self.chars.get(self.at).copied() }
+
+    /// The arguments of a function, in parentheses.
+    fn args(&mut self) -> Option<Vec<f64>> {
+        if self.peek()? != '(' {
+            return None;
+        }
+        let mut args = vec![];
+        loop {
+            self.at += 1;
+            args.push(self.sum()?);
+            match self.peek()? {
+                ',' => continue,
+                ')' => break,
+                _ => return None,
+            }
+        }
+        self.at += 1;
+        Some(args)
+    }
+
+    fn sum(&mut self) -> Option<f64> {
+        let mut value = self.product()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.at += 1;
+            let rhs = self.product()?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Some(value)
+    }
+
+    fn product(&mut self) -> Option<f64> {
+        let mut value = self.atom()?;
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.at += 1;
+            let rhs = self.atom()?;
+            value = if op == '*' { value * rhs } else { value / rhs };
+        }
+        Some(value)
+    }
+
+    fn atom(&mut self) -> Option<f64> {
+        match self.peek()? {
+            '(' => {
+                self.at += 1;
+                let value = self.sum()?;
+                if self.peek()? != ')' {
+                    return None;
+                }
+                self.at += 1;
+                Some(value)
+            },
+            '-' => {
+                self.at += 1;
+                Some(-self.atom()?)
+            },
+            c if c.is_ascii_alphabetic() => {
+                let start = self.at;
+                while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+                    self.at += 1;
+                }
+                let name = self.chars[start..self.at].iter();
+                let name = name.collect::<String>();
+                let args = self.args()?;
+                match (name.as_str(), args.as_slice()) {
+                    ("floor", [x]) => Some(x.floor()),
+                    ("ceil", [x]) => Some(x.ceil()),
+                    ("round", [x]) => Some(x.round()),
+                    ("abs", [x]) => Some(x.abs()),
+                    ("sqrt", [x]) => Some(x.sqrt()),
+                    ("min", [x, y]) => Some(x.min(*y)),
+                    ("max", [x, y]) => Some(x.max(*y)),
+                    ("pow", [x, y]) => Some(x.powf(*y)),
+                    _ => None,
+                }
+            },
+            _ => {
+                let start = self.at;
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_digit() || c == '.')
+                {
+                    self.at += 1;
+                }
+                self.chars[start..self.at]
+                    .iter()
+                    .collect::<String>()
+                    .parse()
+                    .ok()
+            },
+        }
+    }
+}
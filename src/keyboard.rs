@@ -0,0 +1,126 @@
+//! The state of the keyboard, passed to every node as
+//! `u_keyboard`, see
+//! [`crate::graph::ShaderGraph::set_keyboard`].
+
+use glium::glutin::event::VirtualKeyCode;
+
+/// How many keys are tracked, one per JavaScript key code.
+pub const KEYS: usize = 256;
+
+/// Which keys are held, were pressed since the last frame,
+/// and are toggled on, by JavaScript key code, as in
+/// Shadertoy's keyboard texture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keyboard {
+    held:    [bool; KEYS],
+    pressed: [bool; KEYS],
+    /// Flipped each time a key is pressed.
+    toggled: [bool; KEYS],
+}
+
+impl Default for Keyboard {
+    fn default() -> Self { Self::new() }
+}
+
+impl Keyboard {
+    pub fn new() -> Keyboard {
+        Keyboard {
+            held:    [false; KEYS],
+            pressed: [false; KEYS],
+            toggled: [false; KEYS],
+        }
+    }
+
+    /// Presses or releases a key, by JavaScript key code,
+    /// see [`key_code`]. Keys held down are only pressed
+    /// once, however often the system repeats them.
+    pub fn set_key(&mut self, code: u8, down: bool) {
+        let code = code as usize;
+        if down && !self.held[code] {
+            self.pressed[code] = true;
+            self.toggled[code] = !self.toggled[code];
+        }
+        self.held[code] = down;
+    }
+
+    /// Whether a key is held down.
+    pub fn is_held(&self, code: u8) -> bool { self.held[code as usize] }
+
+    /// Forgets which keys were pressed, once a frame has
+    /// seen them. Call this after every forward pass.
+    pub fn end_frame(&mut self) { self.pressed = [false; KEYS]; }
+
+    /// The keys as a `256x3` single-channel texture, from
+    /// the bottom row: held, pressed, then toggled, each
+    /// `0` or `1`.
+    pub fn texels(&self) -> Vec<f32> {
+        [&self.held, &self.pressed, &self.toggled]
+            .iter()
+            .flat_map(|row| row.iter())
+            .map(|&on| if on { 1.0 } else { 0.0 })
+            .collect()
+    }
+}
+
+/// The JavaScript key code of a key, as Shadertoy uses, if
+/// it has one, e.g. `65` for `A` and `37` for `Left`.
+pub fn key_code(key: VirtualKeyCode) -> Option<u8> {
+    use VirtualKeyCode::*;
+    let letters = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X,
+        Y, Z,
+    ];
+    let digits = [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+    let numpad = [
+        Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7,
+        Numpad8, Numpad9,
+    ];
+    let functions = [F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12];
+    let offset = |keys: &[VirtualKeyCode], first: u8| {
+        let index = keys.iter().position(|k| *k == key)?;
+        Some(first + index as u8)
+    };
+    offset(&letters, 65)
+        .or_else(|| offset(&digits, 48))
+        .or_else(|| offset(&numpad, 96))
+        .or_else(|| offset(&functions, 112))
+        .or(Some(match key {
+            Back => 8,
+            Tab => 9,
+            Return | NumpadEnter => 13,
+            LShift | RShift => 16,
+            LControl | RControl => 17,
+            LAlt | RAlt => 18,
+            Pause => 19,
+            Capital => 20,
+            Escape => 27,
+            Space => 32,
+            PageUp => 33,
+            PageDown => 34,
+            End => 35,
+            Home => 36,
+            Left => 37,
+            Up => 38,
+            Right => 39,
+            Down => 40,
+            Insert => 45,
+            Delete => 46,
+            NumpadMultiply => 106,
+            NumpadAdd => 107,
+            NumpadSubtract => 109,
+            NumpadDecimal => 110,
+            NumpadDivide => 111,
+            Semicolon => 186,
+            Equals => 187,
+            Comma => 188,
+            Minus => 189,
+            Period => 190,
+            Slash => 191,
+            Grave => 192,
+            LBracket => 219,
+            Backslash => 220,
+            RBracket => 221,
+            Apostrophe => 222,
+            _ => return None,
+        }))
+}
@@ -0,0 +1,378 @@
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::{
+        Duration,
+        Instant,
+        SystemTime,
+    },
+};
+
+use glium::glutin::event::{
+    ModifiersState,
+    VirtualKeyCode,
+};
+use lexpr::Value;
+
+/// Something the runner does when a key is pressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Saves the output to a png.
+    Screenshot,
+    /// Stops or resumes running the graph.
+    Pause,
+    Fullscreen,
+    /// Rebuilds the graph, even if nothing has changed.
+    Reload,
+    /// Bypasses or re-enables the nth named node,
+    /// counting from 1.
+    Bypass(usize),
+    /// Solos the nth named node, counting from 1.
+    Solo(usize),
+    Unsolo,
+    /// Steps through simulations of color blindness.
+    ColorBlindness,
+    /// Steps through the video scopes.
+    Scopes,
+    /// Shows or hides the plots of uniforms, see `--plot`.
+    Plots,
+    /// Shows the value of the pixel under the cursor.
+    Picker,
+    /// Captures a frame with RenderDoc.
+    Capture,
+    /// Steps through the crop and safe area guides.
+    Guides,
+    /// Starts or stops recording the output.
+    Record,
+    /// Pauses and shows past frames from the history, or
+    /// goes back to running.
+    Freeze,
+    /// Shows the frame before the one shown while frozen.
+    StepBack,
+    /// Shows the frame after the one shown while frozen.
+    StepForward,
+    /// Starts every feedback loop over, without rebuilding
+    /// the graph.
+    ResetFeedback,
+    /// Makes every random texture from a new seed, see
+    /// [`crate::graph::ShaderGraph::regenerate`].
+    Regenerate,
+    /// Zooms the view back out, see
+    /// [`crate::present::View`].
+    ResetView,
+    /// Steps through the ways of comparing the output with
+    /// the reference image.
+    Compare,
+    /// Switches to the graph's next palette.
+    Palette,
+    /// Pauses, and runs the graph for a single frame.
+    StepFrame,
+    /// Halves the speed of the graph's clock.
+    Slower,
+    /// Doubles the speed of the graph's clock.
+    Faster,
+    NormalSpeed,
+    /// Moves the graph's clock back a second.
+    SeekBack,
+    /// Moves the graph's clock ahead a second.
+    SeekForward,
+    /// Moves the graph's clock back to zero.
+    Rewind,
+    /// Shows the next sub-project, see
+    /// [`crate::reload::Projects`].
+    NextProject,
+    PreviousProject,
+    /// Shows the nth sub-project, counting from 1.
+    Project(usize),
+}
+
+impl Action {
+    fn parse(name: &str, index: Option<&Value>) -> Result<Action, String> {
+        let index = || match index.and_then(Value::as_u64) {
+            Some(index) if index > 0 => Ok(index as usize),
+            _ => Err(format!("`{}` expects a number, from 1", name)),
+        };
+
+        match name {
+            "screenshot" => Ok(Action::Screenshot),
+            "pause" => Ok(Action::Pause),
+            "fullscreen" => Ok(Action::Fullscreen),
+            "reload" => Ok(Action::Reload),
+            "bypass" => Ok(Action::Bypass(index()?)),
+            "solo" => Ok(Action::Solo(index()?)),
+            "unsolo" => Ok(Action::Unsolo),
+            "color-blindness" => Ok(Action::ColorBlindness),
+            "scopes" => Ok(Action::Scopes),
+            "plots" => Ok(Action::Plots),
+            "picker" => Ok(Action::Picker),
+            "capture" => Ok(Action::Capture),
+            "guides" => Ok(Action::Guides),
+            "record" => Ok(Action::Record),
+            "freeze" => Ok(Action::Freeze),
+            "step-back" => Ok(Action::StepBack),
+            "step-forward" => Ok(Action::StepForward),
+            "reset-feedback" => Ok(Action::ResetFeedback),
+            "regenerate" => Ok(Action::Regenerate),
+            "reset-view" => Ok(Action::ResetView),
+            "compare" => Ok(Action::Compare),
+            "palette" => Ok(Action::Palette),
+            "step-frame" => Ok(Action::StepFrame),
+            "slower" => Ok(Action::Slower),
+            "faster" => Ok(Action::Faster),
+            "normal-speed" => Ok(Action::NormalSpeed),
+            "seek-back" => Ok(Action::SeekBack),
+            "seek-forward" => Ok(Action::SeekForward),
+            "rewind" => Ok(Action::Rewind),
+            "next-project" => Ok(Action::NextProject),
+            "previous-project" => Ok(Action::PreviousProject),
+            "project" => Ok(Action::Project(index()?)),
+            other => Err(format!("Unknown action `{}`", other)),
+        }
+    }
+}
+
+/// A key, along with the modifiers that must be held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chord {
+    pub modifiers: ModifiersState,
+    pub key:       VirtualKeyCode,
+}
+
+impl Chord {
+    /// Parses a chord like `"Ctrl+Shift+S"`. Keys are named
+    /// after [`VirtualKeyCode`], except that digits can be
+    /// written on their own.
+    pub fn parse(chord: &str) -> Result<Chord, String> {
+        let mut parts: Vec<&str> = chord.split('+').collect();
+        // unwrap: split always yields at least one part
+        let key = parts.pop().unwrap();
+
+        let mut modifiers = ModifiersState::empty();
+        for part in parts {
+            modifiers |= match part {
+                "Shift" => ModifiersState::SHIFT,
+                "Ctrl" => ModifiersState::CTRL,
+                "Alt" => ModifiersState::ALT,
+                "Logo" => ModifiersState::LOGO,
+                other => {
+                    return Err(format!(
+                        "Unknown modifier `{}` in `{}`",
+                        other, chord
+                    ))
+                },
+            };
+        }
+
+        let name = match key {
+            digit
+                if digit.len() == 1 && digit.as_bytes()[0].is_ascii_digit() =>
+            {
+                format!("Key{}", digit)
+            },
+            other => other.to_string(),
+        };
+        let key = KEYS
+            .iter()
+            .find(|k| format!("{:?}", k) == name)
+            .ok_or_else(|| format!("Unknown key `{}` in `{}`", key, chord))?;
+
+        Ok(Chord {
+            modifiers,
+            key: *key,
+        })
+    }
+}
+
+type Bindings = Vec<(Chord, Action)>;
+
+/// Keys that can be bound in a keymap.
+const KEYS: &[VirtualKeyCode] = {
+    use VirtualKeyCode::*;
+    &[
+        Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, A, B, C, D,
+        E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z, F1,
+        F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, Escape, Space, Return,
+        Tab, Back, Delete, Insert, Home, End, PageUp, PageDown, Left, Right,
+        Up, Down, Minus, Equals, Comma, Period, Slash, Backslash, Semicolon,
+        Apostrophe, Grave, LBracket, RBracket,
+    ]
+};
+
+/// The bindings used when no keymap file is given.
+const DEFAULT: &str = r#"
+(bind "F12" screenshot)
+(bind "Space" pause)
+(bind "F11" fullscreen)
+(bind "Ctrl+R" reload)
+(bind "1" bypass 1) (bind "Shift+1" solo 1)
+(bind "2" bypass 2) (bind "Shift+2" solo 2)
+(bind "3" bypass 3) (bind "Shift+3" solo 3)
+(bind "4" bypass 4) (bind "Shift+4" solo 4)
+(bind "5" bypass 5) (bind "Shift+5" solo 5)
+(bind "6" bypass 6) (bind "Shift+6" solo 6)
+(bind "7" bypass 7) (bind "Shift+7" solo 7)
+(bind "8" bypass 8) (bind "Shift+8" solo 8)
+(bind "9" bypass 9) (bind "Shift+9" solo 9)
+(bind "Shift+0" unsolo)
+(bind "F8" color-blindness)
+(bind "F7" scopes)
+(bind "Shift+F7" plots)
+(bind "F6" picker)
+(bind "F10" capture)
+(bind "F9" guides)
+(bind "F4" record)
+(bind "F3" freeze)
+(bind "Left" step-back)
+(bind "Right" step-forward)
+(bind "F5" reset-feedback)
+(bind "Shift+F5" regenerate)
+(bind "Home" reset-view)
+(bind "F2" compare)
+(bind "P" palette)
+(bind "Period" step-frame)
+(bind "LBracket" slower)
+(bind "RBracket" faster)
+(bind "Backslash" normal-speed)
+(bind "Ctrl+Left" seek-back)
+(bind "Ctrl+Right" seek-forward)
+(bind "Back" rewind)
+(bind "PageDown" next-project)
+(bind "PageUp" previous-project)
+"#;
+
+/// Maps keys to runner actions. A keymap is a lisp file of
+/// `(bind "<chord>" <action> [node])` forms, see
+/// [`Keymap::default`] for the bindings used when there is
+/// none. Keymaps loaded from a file are reloaded when the
+/// file changes.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Bindings,
+    path:     Option<PathBuf>,
+    modified: Option<SystemTime>,
+    checked:  Instant,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap {
+            // unwrap: the default keymap is known to parse
+            bindings: Keymap::parse(DEFAULT).unwrap(),
+            path:     None,
+            modified: None,
+            checked:  Instant::now(),
+        }
+    }
+}
+
+impl Keymap {
+    /// How often a keymap file is checked for changes.
+    const POLL: Duration = Duration::from_millis(500);
+
+    /// Loads a keymap from a file.
+    pub fn load(path: &Path) -> Result<Keymap, String> {
+        let (bindings, modified) = Keymap::read(path)?;
+        Ok(Keymap {
+            bindings,
+            path: Some(path.to_path_buf()),
+            modified,
+            checked: Instant::now(),
+        })
+    }
+
+    fn read(path: &Path) -> Result<(Bindings, Option<SystemTime>), String> {
+        let source = fs::read_to_string(path).map_err(|e| {
+            format!("Could not read keymap `{}`: {}", path.display(), e)
+        })?;
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        let bindings = Keymap::parse(&source)
+            .map_err(|e| format!("In keymap `{}`: {}", path.display(), e))?;
+        Ok((bindings, modified))
+    }
+
+    fn parse(source: &str) -> Result<Bindings, String> {
+        let forms = lexpr::from_str(&format!("({})", source))
+            .map_err(|e| format!("{}", e))?;
+        let forms = forms
+            .list_iter()
+            .ok_or_else(|| "Expected a list of bindings".to_string())?;
+
+        let mut bindings = vec![];
+        for form in forms {
+            let parts: Vec<&Value> = form
+                .list_iter()
+                .ok_or_else(|| format!("Expected a binding, found `{}`", form))?
+                .collect();
+
+            match parts.as_slice() {
+                [bind, chord, action, rest @ ..]
+                    if bind.as_symbol() == Some("bind") && rest.len() <= 1 =>
+                {
+                    let chord = chord.as_str().ok_or_else(|| {
+                        format!("Expected a key as a string, found `{}`", chord)
+                    })?;
+                    let action = action.as_symbol().ok_or_else(|| {
+                        format!("Expected an action, found `{}`", action)
+                    })?;
+                    bindings.push((
+                        Chord::parse(chord)?,
+                        Action::parse(action, rest.first().copied())?,
+                    ));
+                },
+                _ => {
+                    return Err(format!(
+                        "Expected `(bind \"<key>\" <action>)`, found `{}`",
+                        form
+                    ))
+                },
+            }
+        }
+
+        Ok(bindings)
+    }
+
+    /// Reloads the keymap file if it has changed since it
+    /// was last read. If the new keymap is invalid, the
+    /// old bindings are kept.
+    pub fn reload(&mut self) {
+        let path = match &self.path {
+            Some(path) if self.checked.elapsed() >= Self::POLL => path,
+            _ => return,
+        };
+        self.checked = Instant::now();
+
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        if modified == self.modified {
+            return;
+        }
+
+        match Keymap::read(path) {
+            Ok((bindings, modified)) => {
+                self.bindings = bindings;
+                self.modified = modified;
+                eprintln!("[info] Keymap reloaded");
+            },
+            Err(e) => {
+                self.modified = modified;
+                eprintln!("[warn] {}", e);
+            },
+        }
+    }
+
+    /// Returns the action bound to a key, if any. The held
+    /// modifiers must match the binding exactly.
+    pub fn action(
+        &self,
+        modifiers: ModifiersState,
+        key: VirtualKeyCode,
+    ) -> Option<Action> {
+        let chord = Chord { modifiers, key };
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == chord)
+            .map(|(_, action)| *action)
+    }
+}
@@ -0,0 +1,417 @@
+//! Hardware knobs and faders driving uniforms, declared in
+//! the lisp with `control`, e.g.
+//!
+//! ```text
+//! (control "cutoff" :midi-cc 21 :range 0 1)
+//! (control "warp" :osc "/1/fader2" :range -2 2 :smooth 0.2)
+//! ```
+//!
+//! Values can be eased towards where a knob is turned,
+//! see [`Smoothing`], so jittery controllers don't make
+//! visuals strobe.
+//!
+//! Listening for them is behind the `knobs` feature, see
+//! [`Knobs`]. MIDI is read raw from a device file, like
+//! `/dev/snd/midiC1D0` on Linux, and OSC is received over
+//! UDP.
+
+#[cfg(feature = "knobs")]
+use std::{
+    collections::BTreeMap,
+    convert::TryInto,
+    fs::File,
+    io::Read,
+    net::UdpSocket,
+    path::Path,
+    sync::mpsc::{
+        self,
+        Receiver,
+        Sender,
+    },
+    thread,
+    time::Instant,
+};
+
+#[cfg(feature = "knobs")]
+use crate::graph::{
+    ShaderGraph,
+    Uniform,
+};
+
+/// Where a knob's value comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    /// A MIDI control change, on one channel from 1 to 16,
+    /// or on any.
+    MidiCc { cc: u8, channel: Option<u8> },
+    /// The first number sent to an OSC address.
+    Osc(String),
+}
+
+/// The longest time between frames a knob is smoothed
+/// over, in seconds, so it doesn't jump after a hitch.
+#[cfg(feature = "knobs")]
+const MAX_STEP: f32 = 0.1;
+
+/// How a knob's uniform follows the knob, rather than
+/// jumping to each value it's turned to.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Smoothing {
+    /// The time constant of a one-pole filter, in seconds:
+    /// the uniform covers about two thirds of the way to a
+    /// new value in this long. 0 follows at once.
+    pub time: f32,
+    /// The most the uniform may change per second, in the
+    /// units of its range, if it's limited.
+    pub slew: Option<f32>,
+}
+
+impl Smoothing {
+    /// Moves a value towards a target over `dt` seconds.
+    pub fn step(&self, value: f32, target: f32, dt: f32) -> f32 {
+        let mut next = if self.time > 0.0 {
+            value + (target - value) * (1.0 - (-dt / self.time).exp())
+        } else {
+            target
+        };
+        if let Some(slew) = self.slew {
+            let most = slew * dt;
+            next = value + (next - value).clamp(-most, most);
+        }
+        next
+    }
+}
+
+/// A float uniform set from a [`Source`]. Values from the
+/// source, from 0 to 127 for MIDI and from 0 to 1 for OSC,
+/// are taken as 0 to 1, then the part of that in `input`
+/// is mapped onto `range`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Knob {
+    pub uniform:   String,
+    pub source:    Source,
+    pub range:     (f32, f32),
+    /// The part of the knob's travel used, from 0 to 1,
+    /// e.g. for a fader that never quite reaches its ends.
+    /// Values beyond it are clamped.
+    pub input:     (f32, f32),
+    /// The value until the knob is turned.
+    pub default:   f32,
+    pub smoothing: Smoothing,
+}
+
+/// A message received from a controller.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    MidiCc {
+        channel: u8,
+        cc:      u8,
+        value:   u8,
+    },
+    Osc {
+        address: String,
+        value:   f32,
+    },
+}
+
+impl Knob {
+    /// The value a message sets the knob to, if it is for
+    /// this knob.
+    pub fn value(&self, message: &Message) -> Option<f32> {
+        let t = match (&self.source, message) {
+            (
+                Source::MidiCc { cc, channel },
+                Message::MidiCc {
+                    channel: sent,
+                    cc: changed,
+                    value,
+                },
+            ) if cc == changed && channel.is_none_or(|c| c == *sent) => {
+                *value as f32 / 127.0
+            },
+            (
+                Source::Osc(address),
+                Message::Osc {
+                    address: sent,
+                    value,
+                },
+            ) if address == sent => *value,
+            _ => return None,
+        };
+        let (from, to) = self.input;
+        let t = ((t - from) / (to - from)).clamp(0.0, 1.0);
+        let (low, high) = self.range;
+        Some(low + (high - low) * t)
+    }
+}
+
+/// Listens for controllers on background threads, and sets
+/// the uniforms of the knobs they turn.
+#[cfg(feature = "knobs")]
+pub struct Knobs {
+    sender:   Sender<Message>,
+    receiver: Receiver<Message>,
+    /// The last value of each knob turned, by uniform, so
+    /// it outlasts rebuilds of the graph.
+    values:   BTreeMap<String, f32>,
+    /// Where each uniform has been smoothed to so far.
+    smoothed: BTreeMap<String, f32>,
+    /// When the uniforms were last set.
+    last:     Option<Instant>,
+}
+
+#[cfg(feature = "knobs")]
+impl Default for Knobs {
+    fn default() -> Self { Self::new() }
+}
+
+#[cfg(feature = "knobs")]
+impl Knobs {
+    pub fn new() -> Knobs {
+        let (sender, receiver) = mpsc::channel();
+        Knobs {
+            sender,
+            receiver,
+            values: BTreeMap::new(),
+            smoothed: BTreeMap::new(),
+            last: None,
+        }
+    }
+
+    /// Receives OSC packets on a UDP port, e.g. 9000.
+    pub fn listen_osc(&self, port: u16) -> Result<(), String> {
+        let socket = UdpSocket::bind(("0.0.0.0", port)).map_err(|e| {
+            format!("Could not listen for OSC on port {}: {}", port, e)
+        })?;
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            let mut buffer = vec![0; 65536];
+            loop {
+                let length = match socket.recv(&mut buffer) {
+                    Ok(length) => length,
+                    Err(e) => {
+                        eprintln!("[warn] Stopped receiving OSC: {}", e);
+                        return;
+                    },
+                };
+                let mut messages = vec![];
+                parse_osc(&buffer[..length], &mut messages);
+                for message in messages {
+                    if sender.send(message).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Reads raw MIDI from a device, e.g.
+    /// `/dev/snd/midiC1D0`. If the device is unplugged,
+    /// knobs stay where they were, and it's opened again
+    /// once it's back, see [`crate::reconnect::RETRY`].
+    pub fn listen_midi(&self, device: &Path) -> Result<(), String> {
+        let mut file = File::open(device).map_err(|e| {
+            let device = device.display();
+            format!("Could not open MIDI device `{}`: {}", device, e)
+        })?;
+        let (device, sender) = (device.to_path_buf(), self.sender.clone());
+        thread::spawn(move || {
+            let mut parser = MidiParser::default();
+            let mut buffer = [0; 256];
+            loop {
+                let length = match file.read(&mut buffer) {
+                    Ok(length) if length > 0 => length,
+                    stopped => {
+                        let why = match stopped {
+                            Err(e) => e.to_string(),
+                            _ => "it was closed".to_string(),
+                        };
+                        eprintln!(
+                            "[warn] Stopped reading MIDI from `{}`, as {}, \
+                             keeping the knobs where they are until it's back",
+                            device.display(),
+                            why
+                        );
+                        file = reopen(&device);
+                        parser = MidiParser::default();
+                        eprintln!(
+                            "[info] MIDI device `{}` is back",
+                            device.display()
+                        );
+                        continue;
+                    },
+                };
+                for byte in buffer[..length].iter() {
+                    if let Some(message) = parser.push(*byte) {
+                        if sender.send(message).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Sets the uniforms of the graph's knobs, from every
+    /// message received since the last call, or from
+    /// earlier ones if their knobs were turned before the
+    /// graph was rebuilt. Uniforms are smoothed towards
+    /// their knobs' values, see [`Smoothing`]. Call this
+    /// every frame.
+    pub fn turn(&mut self, graph: &mut ShaderGraph) {
+        for message in self.receiver.try_iter() {
+            for knob in graph.knobs() {
+                if let Some(value) = knob.value(&message) {
+                    self.values.insert(knob.uniform.clone(), value);
+                }
+            }
+        }
+
+        let now = Instant::now();
+        let dt = match self.last.replace(now) {
+            Some(last) => (now - last).as_secs_f32().min(MAX_STEP),
+            None => 0.0,
+        };
+        let mut turned = BTreeMap::new();
+        for knob in graph.knobs() {
+            let target = match self.values.get(&knob.uniform) {
+                Some(target) => *target,
+                None => continue,
+            };
+            // knobs of the same uniform move it once a frame
+            if turned.contains_key(&knob.uniform) {
+                continue;
+            }
+            // from the default, until the knob is first turned
+            let value = self.smoothed.get(&knob.uniform).copied();
+            let value = value.unwrap_or(knob.default);
+            let value = knob.smoothing.step(value, target, dt);
+            self.smoothed.insert(knob.uniform.clone(), value);
+            turned.insert(knob.uniform.clone(), value);
+        }
+        for (uniform, value) in turned {
+            // knobs are checked to be floats when added
+            let _ = graph.set_uniform(&uniform, Uniform::Float(value));
+        }
+    }
+}
+
+/// Reads control changes from a stream of MIDI bytes,
+/// keeping track of running status.
+#[cfg(feature = "knobs")]
+#[derive(Debug, Default)]
+struct MidiParser {
+    status: u8,
+    data:   [u8; 2],
+    count:  usize,
+}
+
+#[cfg(feature = "knobs")]
+impl MidiParser {
+    fn push(&mut self, byte: u8) -> Option<Message> {
+        match byte {
+            // real-time messages can come between any bytes
+            0xf8..=0xff => return None,
+            0x80..=0xf7 => {
+                self.status = byte;
+                self.count = 0;
+                return None;
+            },
+            _ => (),
+        }
+        if self.status & 0xf0 != 0xb0 {
+            return None;
+        }
+
+        self.data[self.count] = byte;
+        self.count += 1;
+        if self.count < 2 {
+            return None;
+        }
+        self.count = 0;
+        Some(Message::MidiCc {
+            channel: (self.status & 0x0f) + 1,
+            cc:      self.data[0],
+            value:   self.data[1],
+        })
+    }
+}
+
+/// Opens a MIDI device once it can be, trying every
+/// [`crate::reconnect::RETRY`].
+#[cfg(feature = "knobs")]
+fn reopen(device: &Path) -> File {
+    loop {
+        thread::sleep(crate::reconnect::RETRY);
+        if let Ok(file) = File::open(device) {
+            return file;
+        }
+    }
+}
+
+/// Reads an OSC string, padded with nuls to a multiple of
+/// four bytes, returning it and the rest of the packet.
+#[cfg(feature = "knobs")]
+fn osc_string(packet: &[u8]) -> Option<(&str, &[u8])> {
+    let end = packet.iter().position(|byte| *byte == 0)?;
+    let string = std::str::from_utf8(&packet[..end]).ok()?;
+    let padded = (end + 4) & !3;
+    Some((string, packet.get(padded..)?))
+}
+
+/// Reads the messages in an OSC packet, taking the first
+/// argument of each that is a number. Bundles are read
+/// in order, ignoring their time tags.
+#[cfg(feature = "knobs")]
+fn parse_osc(packet: &[u8], messages: &mut Vec<Message>) -> Option<()> {
+    let (address, rest) = osc_string(packet)?;
+    if address == "#bundle" {
+        // skip the time tag
+        let mut rest = rest.get(8..)?;
+        while rest.len() >= 4 {
+            let size = u32::from_be_bytes(bytes(rest)?) as usize;
+            let element = rest.get(4..4 + size)?;
+            parse_osc(element, messages);
+            rest = &rest[4 + size..];
+        }
+        return Some(());
+    }
+
+    let (tags, mut rest) = osc_string(rest)?;
+    for tag in tags.strip_prefix(',')?.chars() {
+        let value = match tag {
+            'f' => f32::from_be_bytes(bytes(rest)?),
+            'i' => i32::from_be_bytes(bytes(rest)?) as f32,
+            'd' => f64::from_be_bytes(bytes(rest)?) as f32,
+            'h' => i64::from_be_bytes(bytes(rest)?) as f32,
+            'T' => 1.0,
+            'F' => 0.0,
+            // strings and blobs can come before the number
+            's' | 'S' => {
+                rest = osc_string(rest)?.1;
+                continue;
+            },
+            'b' => {
+                let size = u32::from_be_bytes(bytes(rest)?) as usize;
+                rest = rest.get(4 + ((size + 3) & !3)..)?;
+                continue;
+            },
+            'N' | 'I' => continue,
+            _ => return None,
+        };
+        messages.push(Message::Osc {
+            address: address.to_string(),
+            value,
+        });
+        return Some(());
+    }
+    None
+}
+
+/// The first `N` bytes of an OSC argument.
+#[cfg(feature = "knobs")]
+fn bytes<const N: usize>(data: &[u8]) -> Option<[u8; N]> {
+    data.get(..N)?.try_into().ok()
+}
@@ -0,0 +1,201 @@
+//! Measures how late live inputs arrive, to line them up
+//! with `:delay`. A camera is pointed at a window that
+//! flashes, and each flash is timed until a frame sees it.
+//! Sound is timed from playing a click until it's captured,
+//! by a microphone by the speakers, or with the output
+//! looped back to the input.
+
+use std::{
+    thread,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use crate::{
+    audio::{
+        Capture,
+        Playback,
+    },
+    upload::Frame,
+};
+
+/// How often a window flashes, lit for the first half.
+pub const FLASH_PERIOD: Duration = Duration::from_secs(1);
+
+/// The least difference in brightness, from 0 to 1, a
+/// camera must see between a lit and a dark window.
+const MIN_CONTRAST: f32 = 0.05;
+
+/// How often capture is checked for a click. Samples come
+/// in blocks of 256, about 6ms, which limits how exactly
+/// sound is timed anyway.
+const TICK: Duration = Duration::from_millis(2);
+const CLICK_LENGTH: Duration = Duration::from_millis(10);
+/// How long a click is waited for before it's given up on,
+/// and how long the silence around each is.
+const CLICK_TIMEOUT: Duration = Duration::from_secs(1);
+const QUIET: Duration = Duration::from_millis(500);
+/// The newest samples looked at for a click.
+const CLICK_WINDOW: usize = 256;
+/// The quietest a click may be captured, from 0 to 1.
+const MIN_LEVEL: f32 = 0.02;
+
+/// The middle of several times, or none if there are none.
+pub fn median(times: &[Duration]) -> Option<Duration> {
+    let mut times = times.to_vec();
+    times.sort();
+    times.get(times.len() / 2).copied()
+}
+
+/// The mean brightness of a frame, from 0 to 1.
+pub fn brightness(frame: &Frame) -> f32 {
+    let sum = frame
+        .iter()
+        .map(|&(r, g, b)| r as u32 + g as u32 + b as u32)
+        .sum::<u32>();
+    sum as f32 / (frame.len().max(1) * 3 * 255) as f32
+}
+
+/// Times how long a window's flashes take to be seen by a
+/// camera. The host shows the window lit whenever
+/// [`Flashes::lit`] says so, calls [`Flashes::shown`] once
+/// each frame is on screen, and [`Flashes::frame`] with
+/// each frame from the camera. The first flash only finds
+/// how bright a lit window looks, so isn't timed.
+#[derive(Debug)]
+pub struct Flashes {
+    started:   Instant,
+    /// Whether the window on screen is lit.
+    lit:       bool,
+    /// How many flashes have been shown.
+    count:     usize,
+    /// The brightness of the camera's last frame.
+    latest:    f32,
+    /// The brightest frame of the flash being shown.
+    peak:      f32,
+    /// The brightest frame of the flash before.
+    bright:    Option<f32>,
+    /// When the flash being shown went up, and how bright a
+    /// frame must be to have seen it, until one has.
+    waiting:   Option<(Instant, f32)>,
+    /// How long each flash timed took to be seen.
+    pub times: Vec<Duration>,
+}
+
+impl Flashes {
+    pub fn new() -> Flashes {
+        Flashes {
+            started: Instant::now(),
+            lit:     false,
+            count:   0,
+            latest:  0.0,
+            peak:    0.0,
+            bright:  None,
+            waiting: None,
+            times:   vec![],
+        }
+    }
+
+    /// Whether the window should be lit at a time.
+    pub fn lit(&self, now: Instant) -> bool {
+        let elapsed = (now - self.started).as_secs_f64();
+        (elapsed / FLASH_PERIOD.as_secs_f64()).fract() < 0.5
+    }
+
+    /// How many flashes have been shown so far.
+    pub fn count(&self) -> usize { self.count }
+
+    /// Notes that the window was shown lit or dark at a
+    /// time, once it's on screen.
+    pub fn shown(&mut self, lit: bool, now: Instant) {
+        if lit && !self.lit {
+            self.count += 1;
+            // frames are as dark as they get by now, half a
+            // period after the last flash went down
+            self.waiting = self
+                .bright
+                .filter(|bright| bright - self.latest > MIN_CONTRAST)
+                .map(|bright| (now, (self.latest + bright) / 2.0));
+            self.peak = self.latest;
+        }
+        if !lit && self.lit {
+            self.waiting = None;
+            self.bright = Some(self.peak);
+        }
+        self.lit = lit;
+    }
+
+    /// Notes the brightness of a frame from the camera, see
+    /// [`brightness`], as it arrives.
+    pub fn frame(&mut self, brightness: f32, now: Instant) {
+        self.latest = brightness;
+        if self.lit {
+            self.peak = self.peak.max(brightness);
+        }
+        if let Some((shown, threshold)) = self.waiting {
+            if brightness > threshold {
+                self.times.push(now - shown);
+                self.waiting = None;
+            }
+        }
+    }
+}
+
+impl Default for Flashes {
+    fn default() -> Flashes { Flashes::new() }
+}
+
+/// Plays clicks to an output, and times how long each
+/// takes to be captured from an input, both given as
+/// `ffmpeg` devices, see [`Capture::start`]. Clicks that
+/// aren't heard within a second aren't timed.
+pub fn clicks(
+    input: Option<&str>,
+    output: Option<&str>,
+    trials: usize,
+) -> Result<Vec<Duration>, String> {
+    let capture = Capture::start(input, Duration::ZERO)?;
+    let mut playback = Playback::start(output)?;
+    let level = || {
+        let samples = capture.samples();
+        samples[samples.len() - CLICK_WINDOW..]
+            .iter()
+            .fold(0.0, |peak: f32, sample| peak.max(sample.abs()))
+    };
+    let quiet = |playback: &mut Playback| {
+        let start = Instant::now();
+        let mut noise = 0.0f32;
+        while start.elapsed() < QUIET {
+            playback.play(&[[0.0; 2]]);
+            noise = noise.max(level());
+            thread::sleep(TICK);
+        }
+        noise
+    };
+
+    // a burst of a high tone, a few samples per texel
+    let click = (0..16)
+        .map(|i| if i % 2 == 0 { [0.9; 2] } else { [-0.9; 2] })
+        .collect::<Vec<_>>();
+    let threshold = (quiet(&mut playback) * 4.0).max(MIN_LEVEL);
+    let mut times = vec![];
+    for _ in 0..trials {
+        let clicked = Instant::now();
+        while clicked.elapsed() < CLICK_TIMEOUT {
+            if clicked.elapsed() < CLICK_LENGTH {
+                playback.play(&click);
+            } else {
+                playback.play(&[[0.0; 2]]);
+            }
+            if level() > threshold {
+                times.push(clicked.elapsed());
+                break;
+            }
+            thread::sleep(TICK);
+        }
+        quiet(&mut playback);
+    }
+    Ok(times)
+}
@@ -2,11 +2,62 @@ pub use glium;
 pub use include_dir;
 pub use notify;
 
+pub mod audio;
+pub mod camera;
+pub mod canvas;
+pub mod capabilities;
+pub mod checkpoint;
+pub mod client;
+pub mod config;
+pub mod control;
+pub mod data;
+pub mod devices;
+pub mod diagnostic;
+#[cfg(target_os = "linux")]
+pub mod dmabuf;
+pub mod exr;
+#[cfg(feature = "feed")]
+pub mod feed;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod framelock;
 pub mod graph;
+pub mod gstreamer;
+pub mod include;
 #[cfg(feature = "ffmpeg")]
 pub mod input;
+pub mod isf;
+pub mod keyboard;
+pub mod keymap;
+pub mod knobs;
+pub mod latency;
 pub mod lisp;
 pub mod map;
+pub mod meta;
+#[cfg(feature = "ndi")]
+pub mod ndi;
+pub mod pacing;
+pub mod palette;
+#[cfg(target_os = "linux")]
+pub mod pipewire;
+pub mod present;
+pub mod provenance;
+pub mod quality;
+pub mod reconnect;
+pub mod record;
 pub mod reload;
+#[cfg(feature = "renderdoc")]
+pub mod renderdoc;
+pub mod schedule;
+pub mod shard;
+pub mod shm;
+pub mod sink;
+pub mod ssim;
+pub mod sync;
+pub mod thumbnail;
+pub mod timing;
+pub mod upload;
 pub mod util;
+pub mod video;
 pub mod png;
+pub mod points;
@@ -1,13 +1,29 @@
-use std::collections::BTreeMap;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
 
 use lexpr::Value;
 
 use crate::{
-    graph::{
-        NodeId,
-        ShaderGraph,
+    diagnostic::{
+        in_shader,
+        suggest,
     },
+    graph::Limits,
+    include::expand,
     lisp::Val,
+    reload::shader_dir::{
+        capture_device,
+        resolve,
+        shader_name,
+        shader_path,
+        Stage,
+    },
 };
 
 pub type FnDef = (Vec<String>, Vec<Value>);
@@ -31,7 +47,15 @@ impl<T> Scope<T> {
             }
         }
 
-        Err(format!("Item `{}` is not defined", name))
+        let error = format!("Item `{}` is not defined", name);
+        Err(suggest(error, name, self.names()))
+    }
+
+    /// Every name defined, in any scope.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.items
+            .iter()
+            .flat_map(|item| item.keys().map(String::as_str))
     }
 
     pub fn set(&mut self, name: String, item: T) {
@@ -45,10 +69,6 @@ impl<T> Scope<T> {
     }
 }
 
-pub type ExternalFn =
-    Box<dyn Fn(&mut ShaderGraph, &[NodeId]) -> Result<NodeId, String>>;
-pub type External = BTreeMap<String, ExternalFn>;
-
 // Note that functions are not first class.
 // We're trying to describe a graph,
 // not make a turing complete language ;)
@@ -59,32 +79,97 @@ pub struct Env {
     functions: Scope<FnDef>,
     /// Maps shader name to shader source.
     shaders:   BTreeMap<String, String>,
-    /// Maps names to rust functions that construct
-    /// subgraphs.
-    external:  External,
+    /// Shaders on disk not read yet, see
+    /// [`Env::with_unread`].
+    unread:    BTreeMap<String, PathBuf>,
+    /// Directory that file paths in the config are relative
+    /// to.
+    root:      PathBuf,
+    /// Files being included, innermost last.
+    including: Vec<PathBuf>,
+    /// Files included by the shaders used so far.
+    included:  Vec<PathBuf>,
+    /// The names of the shaders used so far.
+    used:      Vec<String>,
+    /// Whether files outside `root` can be read.
+    outside:   bool,
+    /// What the graph is allowed to ask for.
+    limits:    Limits,
+    /// The profile the graph is built with, if any.
+    profile:   Option<String>,
+    /// Iterations of every `repeat` evaluated so far,
+    /// nested or not, checked against
+    /// [`Limits::iterations`].
+    repeated:  u64,
+    /// Functions being called, innermost last.
+    calling:   Vec<String>,
 }
 
+/// How deeply functions can call each other, or themselves,
+/// before the graph is refused. Each call takes a lot of
+/// stack in debug builds, so this is well before the 2 MB
+/// of a spawned thread would overflow.
+const MAX_CALL_DEPTH: usize = 16;
+
 impl std::fmt::Debug for Env {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Env")
             .field("vars", &self.vars)
             .field("functions", &self.functions)
             .field("shaders", &self.shaders.keys().collect::<Vec<&String>>())
-            .field("external", &self.external.keys().collect::<Vec<&String>>())
+            .field("unread", &self.unread.keys().collect::<Vec<&String>>())
+            .field("root", &self.root)
+            .field("including", &self.including)
+            .field("included", &self.included)
+            .field("used", &self.used)
+            .field("outside", &self.outside)
+            .field("limits", &self.limits)
+            .field("profile", &self.profile)
             .finish()
     }
 }
 
 impl Env {
-    pub fn new(shaders: BTreeMap<String, String>, external: External) -> Env {
+    /// An environment for a graph whose files are found in
+    /// `root`, and only there unless `outside`, built
+    /// within `limits`, with the named `profile` if
+    /// any.
+    pub fn new(
+        shaders: BTreeMap<String, String>,
+        root: &Path,
+        outside: bool,
+        limits: Limits,
+        profile: Option<String>,
+    ) -> Env {
         Env {
             vars: Scope::new(),
             functions: Scope::new(),
             shaders,
-            external,
+            unread: BTreeMap::new(),
+            root: root.to_path_buf(),
+            including: vec![],
+            included: vec![],
+            used: vec![],
+            outside,
+            limits,
+            profile,
+            repeated: 0,
+            calling: vec![],
         }
     }
 
+    /// Adds shaders that are read from disk only if the
+    /// graph asks for them, see
+    /// [`crate::reload::ShaderDir::unread`].
+    pub fn with_unread(mut self, unread: BTreeMap<String, PathBuf>) -> Env {
+        self.unread = unread;
+        self
+    }
+
+    pub fn limits(&self) -> &Limits { &self.limits }
+
+    pub fn profile(&self) -> Option<&str> { self.profile.as_deref() }
+
     pub fn get(&self, name: &str) -> Result<&Val, String> {
         self.vars.get(name)
     }
@@ -95,6 +180,11 @@ impl Env {
         self.functions.get(name)
     }
 
+    /// The names of every function defined with `define`.
+    pub fn fn_names(&self) -> impl Iterator<Item = &str> {
+        self.functions.names()
+    }
+
     pub fn set_fn(&mut self, name: String, item: FnDef) {
         self.functions.set(name, item)
     }
@@ -109,17 +199,140 @@ impl Env {
         self.functions.exit_scope();
     }
 
-    pub fn shader(&self, name: &str) -> Result<&String, String> {
-        self.shaders.get(name).ok_or(format!(
-            "Could not load shader `{}`, it is not defined",
-            name
-        ))
+    /// The source of a fragment shader, with its
+    /// `#include`s expanded, see [`crate::include`].
+    pub fn shader(&mut self, name: &str) -> Result<String, String> {
+        self.shader_as(name, Stage::Fragment)
     }
 
-    pub fn external(&self, name: &str) -> Result<&ExternalFn, String> {
-        self.external.get(name).ok_or(format!(
-            "Could not load external function `{}`, it is not defined",
-            name
-        ))
+    /// Whether there is a shader by a name, read or not.
+    fn has_shader(&self, name: &str) -> bool {
+        self.shaders.contains_key(name) || self.unread.contains_key(name)
     }
+
+    /// Like [`Env::shader`], for a shader of any stage. The
+    /// name may end in the extension of a stage, or not, in
+    /// which case it's that of `stage`. Naming a shader of
+    /// another stage is an error.
+    pub fn shader_as(
+        &mut self,
+        name: &str,
+        stage: Stage,
+    ) -> Result<String, String> {
+        let file = |name: &str| shader_path(Path::new(""), name);
+        let name = match shader_name(Path::new(name)) {
+            Ok(Some(named)) => named,
+            // shared code was only ever in fragment shaders
+            _ if stage == Stage::Library && self.has_shader(name) => {
+                name.to_string()
+            },
+            _ => stage.name(name),
+        };
+        let (stem, found) = Stage::split(&name);
+        if !stage.accepts(found) && self.has_shader(&name) {
+            return Err(format!(
+                "`{}` is {}, but {} is needed here",
+                file(&name).display(),
+                found,
+                stage
+            ));
+        }
+        if !self.has_shader(&name) {
+            let other = Stage::ALL
+                .iter()
+                .map(|other| other.name(stem))
+                .find(|other| self.has_shader(other));
+            if let Some(other) = other {
+                return Err(format!(
+                    "There is no `{}`, only `{}`, which is {}",
+                    file(&name).display(),
+                    file(&other).display(),
+                    Stage::split(&other).1
+                ));
+            }
+        }
+        let name = name.as_str();
+
+        if let Some(path) = self.unread.remove(name) {
+            let source = fs::read_to_string(&path).map_err(|e| {
+                format!("Could not read `{}`: {}", path.display(), e)
+            })?;
+            self.shaders.insert(name.to_string(), source);
+        }
+        let source = self.shaders.get(name).ok_or_else(|| {
+            let error =
+                format!("Could not load shader `{}`, it is not defined", name);
+            let names = self.shaders.keys().chain(self.unread.keys());
+            suggest(error, name, names.map(String::as_str))
+        })?;
+        let expanded = expand(source, &self.root, self.outside)
+            .map_err(|e| in_shader(name, e))?;
+        for file in expanded.files {
+            if !self.included.contains(&file) {
+                self.included.push(file);
+            }
+        }
+        if !self.used.iter().any(|used| used == name) {
+            self.used.push(name.to_string());
+        }
+        Ok(expanded.source)
+    }
+
+    /// Every file included by the shaders used so far.
+    pub fn shader_includes(&self) -> &[PathBuf] { &self.included }
+
+    /// The names of the shaders used so far.
+    pub fn shaders_used(&self) -> &[String] { &self.used }
+
+    /// Resolves a path in the config relative to the
+    /// project directory, see [`resolve`].
+    pub fn path(&self, file: &str) -> Result<PathBuf, String> {
+        resolve(&self.root, file, self.outside)
+    }
+
+    /// Checks a capture device named in the config, see
+    /// [`capture_device`].
+    pub fn device(&self, device: String) -> Result<String, String> {
+        capture_device(&device, self.outside)?;
+        Ok(device)
+    }
+
+    /// Marks a file as being included, erroring if it is
+    /// already, as it would include itself forever.
+    pub fn enter_include(&mut self, path: &Path) -> Result<(), String> {
+        let path = &path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if self.including.iter().any(|p| p == path) {
+            return Err(format!("`{}` includes itself", path.display()));
+        }
+        self.including.push(path.to_path_buf());
+        Ok(())
+    }
+
+    pub fn exit_include(&mut self) { self.including.pop(); }
+
+    /// Counts one more iteration of a `repeat`, erroring
+    /// once there have been more in all than the limits
+    /// allow, as nested repeats multiply.
+    pub fn repeat_once(&mut self) -> Result<(), String> {
+        self.repeated += 1;
+        self.limits
+            .check_iterations("Repeating, in all,", self.repeated)
+    }
+
+    /// Marks a function as being called, erroring if calls
+    /// are nested too deeply, as with a function that calls
+    /// itself forever.
+    pub fn enter_call(&mut self, name: &str) -> Result<(), String> {
+        if self.calling.len() >= MAX_CALL_DEPTH {
+            return Err(format!(
+                "`{}` calls functions more than {} deep, does it call \
+                 itself?",
+                name, MAX_CALL_DEPTH
+            ));
+        }
+        self.calling.push(name.to_string());
+        Ok(())
+    }
+
+    pub fn exit_call(&mut self) { self.calling.pop(); }
 }
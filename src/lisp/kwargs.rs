@@ -0,0 +1,64 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    diagnostic::{
+        did_you_mean,
+        with_hint,
+    },
+    lisp::Val,
+};
+
+/// Keyword arguments passed to a form, e.g. the
+/// `:layout rows` in `(data "values.csv" :layout rows)`.
+/// Each keyword should be taken at most once; call
+/// [`Kwargs::finish`] afterwards to reject unknown
+/// keywords.
+#[derive(Debug, Default)]
+pub struct Kwargs {
+    form:  String,
+    items: BTreeMap<String, Val>,
+    /// Every keyword taken, passed or not, to suggest in
+    /// place of unknown ones.
+    known: Vec<String>,
+}
+
+impl Kwargs {
+    pub fn new(form: &str) -> Kwargs {
+        Kwargs {
+            form:  form.to_string(),
+            items: BTreeMap::new(),
+            known: vec![],
+        }
+    }
+
+    pub fn insert(&mut self, name: &str, val: Val) -> Result<(), String> {
+        if self.items.insert(name.to_string(), val).is_some() {
+            return Err(format!(
+                "Keyword `:{}` passed more than once to `{}`",
+                name, self.form
+            ));
+        }
+        Ok(())
+    }
+
+    /// Removes a keyword argument, if it was passed.
+    pub fn take(&mut self, name: &str) -> Option<Val> {
+        self.known.push(name.to_string());
+        self.items.remove(name)
+    }
+
+    /// Errors if any keyword arguments have not been taken.
+    pub fn finish(self) -> Result<(), String> {
+        let name = match self.items.keys().next() {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        let error =
+            format!("Unexpected keyword `:{}` passed to `{}`", name, self.form);
+        let known = self.known.iter().map(String::as_str);
+        match did_you_mean(name, known) {
+            Some(hint) => Err(with_hint(error, &hint.replacen('`', "`:", 1))),
+            None => Err(error),
+        }
+    }
+}
@@ -0,0 +1,642 @@
+//! Rewrites graphs written for older releases to the syntax
+//! of this one, see [`migrate`]. Graphs are rewritten as
+//! text, so that comments, blank lines, and indentation are
+//! kept, and only the forms that changed are touched.
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use crate::{
+    lisp::SYNTAX_VERSION,
+    reload::shader_dir::resolve,
+};
+
+/// A change to the syntax that older graphs need rewriting
+/// for, see [`MIGRATIONS`].
+pub struct Migration {
+    /// The version of the syntax it brings graphs up to,
+    /// see [`SYNTAX_VERSION`].
+    pub version:     u32,
+    /// What changed, for reports.
+    pub description: &'static str,
+    pub apply:       fn(&mut Items, &mut Migrated),
+}
+
+/// Every migration, in the order the syntax changed, each
+/// run on the output of the one before.
+pub const MIGRATIONS: &[Migration] = &[Migration {
+    version:     2,
+    description: "Uniforms that are built in can't be declared",
+    apply:       built_in_uniforms,
+}];
+
+/// Uniforms that became built in after graphs could
+/// declare their own, and the type each is built in as.
+/// `None` is a texture, which graphs can't declare.
+const NOW_BUILT_IN: &[(&str, Option<&str>)] = &[
+    ("build_seed", Some("float")),
+    ("frame", Some("int")),
+    ("mouse", Some("vec4")),
+    ("keyboard", None),
+    ("output_resolution", Some("vec2")),
+    ("aspect", Some("float")),
+];
+
+/// A form of a graph as written, see [`Items`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Syntax {
+    /// A symbol, number, keyword, or string, with its
+    /// quotes.
+    Atom(String),
+    /// A list, in parentheses or brackets.
+    List { open: char, body: Items },
+}
+
+/// A form, and the whitespace and comments before it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub before: String,
+    pub syntax: Syntax,
+    /// The line it starts on, from 1.
+    pub line:   usize,
+}
+
+/// A sequence of forms, as in a file or a list, and the
+/// whitespace and comments after the last one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Items {
+    pub items: Vec<Item>,
+    pub after: String,
+}
+
+/// A graph, migrated, see [`migrate`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Migrated {
+    pub source:   String,
+    /// What was rewritten, by line.
+    pub changes:  Vec<String>,
+    /// What needs rewriting, but can't be rewritten
+    /// without changing what the graph does, by line.
+    pub warnings: Vec<String>,
+    /// Every form the version it's written for allowed but
+    /// the current one doesn't, by line, saying what to
+    /// write instead, for errors, see [`outdated`].
+    pub removed:  Vec<String>,
+}
+
+impl Syntax {
+    pub fn as_atom(&self) -> Option<&str> {
+        match self {
+            Syntax::Atom(atom) => Some(atom),
+            Syntax::List { .. } => None,
+        }
+    }
+
+    /// The atom, without quotes if it's a string.
+    fn as_name(&self) -> Option<&str> {
+        let atom = self.as_atom()?;
+        Some(match atom.strip_prefix('"') {
+            Some(string) => string.strip_suffix('"').unwrap_or(string),
+            None => atom,
+        })
+    }
+
+    /// The forms of a list whose first form is `head`.
+    fn as_form(&self, head: &str) -> Option<&[Item]> {
+        match self {
+            Syntax::List { body, .. } => match body.items.first() {
+                Some(first) if first.syntax.as_atom() == Some(head) => {
+                    Some(&body.items)
+                },
+                _ => None,
+            },
+            Syntax::Atom(_) => None,
+        }
+    }
+}
+
+impl Items {
+    /// The version of the syntax declared with `(version
+    /// <n>)` among these forms, and where.
+    fn version(&self) -> Option<(usize, u32)> {
+        self.items.iter().enumerate().find_map(|(index, item)| {
+            let form = item.syntax.as_form("version")?;
+            let version = form.get(1)?.syntax.as_atom()?.parse().ok()?;
+            Some((index, version)).filter(|(_, version)| *version > 0)
+        })
+    }
+
+    /// Parses a graph as written.
+    pub fn parse(source: &str) -> Result<Items, String> {
+        let chars = source.chars().collect::<Vec<_>>();
+        let mut parser = Parser {
+            chars: &chars,
+            at:    0,
+            line:  1,
+        };
+        parser.items(None)
+    }
+
+    /// Writes the forms back out, exactly as they were
+    /// parsed, but for what was changed.
+    pub fn print(&self) -> String {
+        let mut out = String::new();
+        self.print_into(&mut out);
+        out
+    }
+
+    fn print_into(&self, out: &mut String) {
+        for item in self.items.iter() {
+            out.push_str(&item.before);
+            match &item.syntax {
+                Syntax::Atom(atom) => out.push_str(atom),
+                Syntax::List { open, body } => {
+                    out.push(*open);
+                    body.print_into(out);
+                    out.push(if *open == '[' { ']' } else { ')' });
+                },
+            }
+        }
+        out.push_str(&self.after);
+    }
+
+    /// Calls `f` on these forms, and on those of every list
+    /// in them, however deep.
+    pub fn visit(&mut self, f: &mut impl FnMut(&mut Items)) {
+        f(self);
+        for item in self.items.iter_mut() {
+            if let Syntax::List { body, .. } = &mut item.syntax {
+                body.visit(f);
+            }
+        }
+    }
+
+    /// Adds `(version <n>)` for the current syntax as the
+    /// first form, after the comments that open the file,
+    /// returning the line it's on.
+    fn declare_version(&mut self) -> usize {
+        let next = match self.items.first_mut() {
+            Some(next) => &mut next.before,
+            None => &mut self.after,
+        };
+        let before = std::mem::replace(next, "\n".to_string());
+        let line = before.matches('\n').count() + 1;
+        let version = Items {
+            items: vec![
+                Item {
+                    before: String::new(),
+                    syntax: Syntax::Atom("version".to_string()),
+                    line,
+                },
+                Item {
+                    before: " ".to_string(),
+                    syntax: Syntax::Atom(SYNTAX_VERSION.to_string()),
+                    line,
+                },
+            ],
+            after: String::new(),
+        };
+        self.items.insert(0, Item {
+            before,
+            syntax: Syntax::List {
+                open: '(',
+                body: version,
+            },
+            line,
+        });
+        line
+    }
+
+    /// Removes a form along with the line it was on, but
+    /// keeps the comments before it.
+    pub fn remove(&mut self, index: usize) {
+        let removed = self.items.remove(index);
+        let next = match self.items.get_mut(index) {
+            Some(next) => &mut next.before,
+            None => &mut self.after,
+        };
+        let rest = next.trim_start_matches([' ', '\t']);
+        let rest = rest.strip_prefix('\n').unwrap_or(rest);
+        let before = removed.before.trim_end_matches([' ', '\t']);
+        *next = format!("{}{}", before, rest);
+    }
+}
+
+struct Parser<'a> {
+    chars: &'a [char],
+    at:    usize,
+    line:  usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> { self.chars.get(self.at).copied() }
+
+    fn bump(&mut self, out: &mut String) {
+        let c = self.chars[self.at];
+        if c == '\n' {
+            self.line += 1;
+        }
+        out.push(c);
+        self.at += 1;
+    }
+
+    /// Whitespace and comments.
+    fn trivia(&mut self) -> String {
+        let mut trivia = String::new();
+        while let Some(c) = self.peek() {
+            if c == ';' {
+                while !matches!(self.peek(), None | Some('\n')) {
+                    self.bump(&mut trivia);
+                }
+            } else if c.is_whitespace() {
+                self.bump(&mut trivia);
+            } else {
+                break;
+            }
+        }
+        trivia
+    }
+
+    fn items(&mut self, close: Option<char>) -> Result<Items, String> {
+        let mut items = vec![];
+        loop {
+            let before = self.trivia();
+            let line = self.line;
+            let c = match self.peek() {
+                Some(c) => c,
+                None if close.is_none() => {
+                    return Ok(Items {
+                        items,
+                        after: before,
+                    })
+                },
+                None => {
+                    return Err(format!(
+                        "Expected `{}` before the end of the file",
+                        close.unwrap()
+                    ))
+                },
+            };
+            let syntax = match c {
+                ')' | ']' if Some(c) == close => {
+                    self.at += 1;
+                    return Ok(Items {
+                        items,
+                        after: before,
+                    });
+                },
+                ')' | ']' => {
+                    return Err(format!("Unexpected `{}` on line {}", c, line))
+                },
+                '(' | '[' => {
+                    self.at += 1;
+                    let close = if c == '[' { ']' } else { ')' };
+                    Syntax::List {
+                        open: c,
+                        body: self.items(Some(close))?,
+                    }
+                },
+                '"' => Syntax::Atom(self.string()?),
+                _ => Syntax::Atom(self.atom()),
+            };
+            items.push(Item {
+                before,
+                syntax,
+                line,
+            });
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let line = self.line;
+        let mut string = String::new();
+        self.bump(&mut string);
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.bump(&mut string);
+                    return Ok(string);
+                },
+                Some('\\') if self.at + 1 < self.chars.len() => {
+                    self.bump(&mut string);
+                    self.bump(&mut string);
+                },
+                Some(_) => self.bump(&mut string),
+                None => {
+                    return Err(format!("Unclosed string on line {}", line))
+                },
+            }
+        }
+    }
+
+    fn atom(&mut self) -> String {
+        let mut atom = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || "()[]\";".contains(c) {
+                break;
+            }
+            self.bump(&mut atom);
+        }
+        atom
+    }
+}
+
+/// The version of the syntax a graph declares with
+/// `(version <n>)`, if any, read without evaluating it.
+pub fn declared_version(source: &str) -> Option<u32> {
+    Items::parse(source)
+        .ok()?
+        .version()
+        .map(|(_, version)| version)
+}
+
+/// Rewrites a graph to the current syntax, running each of
+/// [`MIGRATIONS`] newer than the version it declares, or
+/// every one if it declares none, and updating the version
+/// it declares, or declaring it on a line of its own before
+/// the first form. A graph that is already current comes
+/// back unchanged.
+pub fn migrate(source: &str) -> Result<Migrated, String> {
+    let mut items = Items::parse(source)?;
+    let declared = items.version();
+    let mut migrated = Migrated::default();
+    let from = match declared {
+        Some((_, version)) if version > SYNTAX_VERSION => {
+            return Err(format!(
+                "The graph is written for version {} of the syntax, \
+                 newer than the {} this release reads",
+                version, SYNTAX_VERSION
+            ))
+        },
+        Some((_, version)) => version,
+        None => 0,
+    };
+    for migration in MIGRATIONS.iter().filter(|m| m.version > from) {
+        (migration.apply)(&mut items, &mut migrated);
+    }
+    let outdated = declared.filter(|(_, version)| *version < SYNTAX_VERSION);
+    if declared.is_none() {
+        let line = items.declare_version();
+        migrated.changes.push(format!(
+            "line {}: Declared version {} of the syntax",
+            line, SYNTAX_VERSION
+        ));
+    } else if let Some((index, version)) = outdated {
+        let item = &mut items.items[index];
+        if let Syntax::List { body, .. } = &mut item.syntax {
+            body.items[1].syntax = Syntax::Atom(SYNTAX_VERSION.to_string());
+        }
+        migrated.changes.push(format!(
+            "line {}: Declared version {} of the syntax, up from {}",
+            item.line, SYNTAX_VERSION, version
+        ));
+    }
+    migrated.source = items.print();
+    // a rewrite should never break the syntax, but check
+    super::parse(&migrated.source)
+        .map_err(|e| format!("Migrating broke the graph: {}", e))?;
+    Ok(migrated)
+}
+
+/// The forms of a graph written for an older version of
+/// the syntax that the current one doesn't allow, see
+/// [`Migrated::removed`], found without evaluating it. A
+/// graph that declares no version is read as current, and
+/// one that can't be read has none.
+pub fn outdated(source: &str) -> Vec<String> {
+    match declared_version(source) {
+        Some(version) if version < SYNTAX_VERSION => {
+            migrate(source).map(|m| m.removed).unwrap_or_default()
+        },
+        _ => vec![],
+    }
+}
+
+/// Migrates a graph in a project, see [`migrate`], and
+/// every file it includes with a literal path, returning
+/// each file read and how it was migrated. Nothing is
+/// written. Includes outside the project aren't followed.
+pub fn migrate_graph(
+    project: &Path,
+    graph: &Path,
+) -> Result<Vec<(PathBuf, Migrated)>, String> {
+    let mut files = vec![];
+    let mut seen = BTreeSet::new();
+    let mut queue = vec![graph.to_path_buf()];
+    while let Some(path) = queue.pop() {
+        let key = path.canonicalize().unwrap_or_else(|_| path.clone());
+        if !seen.insert(key) {
+            continue;
+        }
+        let source = fs::read_to_string(&path).map_err(|e| {
+            format!("Could not read `{}`: {}", path.display(), e)
+        })?;
+        let mut migrated = migrate(&source)
+            .map_err(|e| format!("In `{}`: {}", path.display(), e))?;
+
+        let mut items = Items::parse(&migrated.source)?;
+        items.visit(&mut |items| {
+            for item in items.items.iter() {
+                let form = match item.syntax.as_form("include") {
+                    Some(form) => form,
+                    None => continue,
+                };
+                let file = form.get(1).and_then(|i| i.syntax.as_atom());
+                match file.filter(|file| file.starts_with('"')) {
+                    Some(_) => {
+                        let file = form[1].syntax.as_name().unwrap();
+                        match resolve(project, file, false) {
+                            Ok(path) => queue.push(path),
+                            Err(e) => migrated
+                                .warnings
+                                .push(format!("line {}: {}", item.line, e)),
+                        }
+                    },
+                    None => migrated.warnings.push(format!(
+                        "line {}: Only includes of a literal path are \
+                         migrated",
+                        item.line
+                    )),
+                }
+            }
+        });
+        files.push((path, migrated));
+    }
+    Ok(files)
+}
+
+/// Removes declarations of uniforms that are built in now,
+/// when they are declared with the type they are built in
+/// as, as every shader receives them anyway.
+fn built_in_uniforms(items: &mut Items, migrated: &mut Migrated) {
+    items.visit(&mut |items| {
+        let mut index = 0;
+        while index < items.items.len() {
+            let item = &items.items[index];
+            let line = item.line;
+            let (form, kind) = match (
+                item.syntax.as_form("uniform"),
+                item.syntax.as_form("control"),
+            ) {
+                (Some(form), _) => (form, form.get(2)),
+                (_, Some(form)) => (form, None),
+                _ => {
+                    index += 1;
+                    continue;
+                },
+            };
+            let name = form.get(1).and_then(|name| name.syntax.as_name());
+            let built_in = NOW_BUILT_IN
+                .iter()
+                .find(|(built_in, _)| Some(*built_in) == name);
+            let (name, built_in) = match built_in {
+                Some((name, built_in)) => (name, built_in),
+                None => {
+                    index += 1;
+                    continue;
+                },
+            };
+            migrated.removed.push(format!(
+                "line {}: Declaring `{}` was removed in version 2, as \
+                 `u_{}` is built in{}; {}",
+                line,
+                name,
+                name,
+                built_in.map_or(String::new(), |t| format!(" as a {}", t)),
+                if form[0].syntax.as_atom() == Some("control") {
+                    "rename the control, and the shaders that read it"
+                } else {
+                    "remove the declaration, or rename it if it's another \
+                     type"
+                }
+            ));
+            let kind = kind.and_then(|kind| kind.syntax.as_atom());
+            if kind.is_some() && kind == *built_in && form.len() == 3 {
+                migrated.changes.push(format!(
+                    "line {}: Removed the declaration of `u_{}`, which \
+                     is built in now",
+                    line, name
+                ));
+                items.remove(index);
+            } else {
+                migrated.warnings.push(format!(
+                    "line {}: `u_{}` is built in now{}, so rename the \
+                     uniform declared here, and the shaders that read it",
+                    line,
+                    name,
+                    built_in.map_or(String::new(), |t| format!(" as a {}", t))
+                ));
+                index += 1;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn migrated(source: &str) -> Migrated { migrate(source).unwrap() }
+
+    #[test]
+    fn declares_the_version() {
+        assert_eq!(
+            migrated("(output \"a\")\n").source,
+            "(version 2)\n(output \"a\")\n"
+        );
+        assert_eq!(
+            migrated(";; a graph\n\n(output \"a\")\n").source,
+            ";; a graph\n\n(version 2)\n(output \"a\")\n"
+        );
+        assert_eq!(migrated(";; empty\n").source, ";; empty\n(version 2)\n");
+        let bumped = migrated(";; old\n(version 1)\n(output \"a\")\n");
+        assert_eq!(bumped.source, ";; old\n(version 2)\n(output \"a\")\n");
+        assert_eq!(bumped.changes.len(), 1);
+    }
+
+    #[test]
+    fn leaves_current_graphs_alone() {
+        let source = "; keep this\n(version 2)\n(uniform \"frame\" int)\n";
+        assert_eq!(migrated(source), Migrated {
+            source: source.to_string(),
+            ..Migrated::default()
+        });
+    }
+
+    #[test]
+    fn refuses_newer_graphs() {
+        assert!(migrate("(version 3)\n").is_err());
+    }
+
+    #[test]
+    fn removes_built_in_uniforms() {
+        for (name, kind) in NOW_BUILT_IN {
+            let kind = match kind {
+                Some(kind) => kind,
+                None => continue,
+            };
+            let before = format!(
+                "(version 1)\n; the {}\n(uniform \"{}\" {})\n(output \"a\")\n",
+                name, name, kind
+            );
+            let after = migrated(&before);
+            assert_eq!(
+                after.source,
+                format!("(version 2)\n; the {}\n(output \"a\")\n", name),
+            );
+            assert_eq!(after.changes.len(), 2, "{:?}", after.changes);
+            assert!(after.warnings.is_empty());
+            assert_eq!(outdated(&before), after.removed);
+            assert!(after.removed[0].starts_with(&format!(
+                "line 3: Declaring `{}` was removed in version 2",
+                name
+            )));
+        }
+    }
+
+    #[test]
+    fn removes_built_in_uniforms_in_lists() {
+        let before = "(version 1)\n(defnode \"n\" ()\n  (uniform \
+                      \"aspect\" float)\n  (output \"a\"))\n";
+        assert_eq!(
+            migrated(before).source,
+            "(version 2)\n(defnode \"n\" ()\n  (output \"a\"))\n"
+        );
+    }
+
+    #[test]
+    fn warns_about_clashing_uniforms() {
+        for before in [
+            "(version 1)\n(uniform \"frame\" float)\n",
+            "(version 1)\n(uniform \"mouse\" vec4 4)\n",
+            "(version 1)\n(uniform \"keyboard\" float)\n",
+            "(version 1)\n(control \"aspect\" :midi-cc 1)\n",
+        ] {
+            let after = migrated(before);
+            assert_eq!(after.source, before.replace("version 1", "version 2"));
+            assert_eq!(after.warnings.len(), 1, "{}", before);
+            assert_eq!(after.removed.len(), 1, "{}", before);
+        }
+    }
+
+    #[test]
+    fn finds_removed_forms_only_in_older_graphs() {
+        let source = "(uniform \"frame\" int)\n";
+        assert_eq!(outdated(source), Vec::<String>::new());
+        assert_eq!(
+            outdated(&format!("(version 2)\n{}", source)),
+            Vec::<String>::new()
+        );
+        assert_eq!(outdated(&format!("(version 1)\n{}", source)).len(), 1);
+        assert_eq!(
+            outdated("(version 1)\n(uniform \"speed\" float)\n").len(),
+            0
+        );
+    }
+}
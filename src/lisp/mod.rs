@@ -1,27 +1,198 @@
-use std::rc::Rc;
+use std::{
+    borrow::Cow,
+    fs,
+    rc::Rc,
+};
 
 use glium::backend::Context;
 use lexpr::Value;
 
 use crate::{
+    camera,
+    capabilities::{
+        GlslVersion,
+        Requirements,
+    },
+    data::{
+        load_data,
+        Layout,
+    },
+    diagnostic::{
+        suggest,
+        with_hint,
+    },
     graph::{
+        Baked,
+        Compare,
+        Condition,
+        Distribution,
+        External,
+        Format,
+        Init,
+        Limits,
         NodeId,
+        NodeSpec,
+        Operand,
+        OutputWindow,
+        Plan,
+        Profile,
+        RandomTexture,
+        Realization,
         ShaderGraph,
+        TextureOptions,
+        UniformType,
+    },
+    isf::{
+        InputKind,
+        Isf,
+    },
+    knobs::{
+        Knob,
+        Smoothing,
+        Source,
     },
-    reload::ShaderDir,
+    meta::Meta,
+    palette::{
+        parse_color,
+        Palette,
+    },
+    points::load_points,
+    reload::{
+        ShaderDir,
+        Stage,
+    },
+    video::Playback,
 };
 
 mod env;
+mod kwargs;
 mod load;
+pub mod migrate;
 mod val;
 
-pub use env::{
-    Env,
-    External,
-};
+pub use env::Env;
+pub use kwargs::Kwargs;
 pub use load::load_shaders;
 pub use val::Val;
 
+/// The version of the syntax of graphs this release reads.
+/// It goes up whenever graphs written for older releases
+/// need rewriting, see [`migrate::MIGRATIONS`]. Graphs can
+/// declare the version they are written for with
+/// `(version <n>)`, so that errors say when they are
+/// written for another one, see [`evaluate`].
+pub const SYNTAX_VERSION: u32 = 2;
+
+/// The keywords of statements, see [`declare`], to suggest
+/// in place of misspelled ones.
+const STATEMENTS: &[&str] = &[
+    "input", "output", "define", "defnode", "include", "let", "require",
+    "version", "warmup", "glsl", "preamble", "palette", "uniform", "control",
+    "profile", "order", "buffer", "write", "meta", "window", "repeat",
+];
+
+/// The built-in functions that make nodes, see [`node`],
+/// to suggest in place of misspelled ones.
+const FORMS: &[&str] = &[
+    "shader",
+    "shader-inline",
+    "shader-param",
+    "shader-rec",
+    "shader-rec-inline",
+    "repeat",
+    "shader-mrt",
+    "attachment",
+    "pyramid",
+    "points",
+    "data",
+    "random-texture",
+    "compute",
+    "isf",
+    "audio",
+    "prev-output",
+    "camera",
+    "video",
+    "feed",
+    "stats",
+    "auto-expose",
+    "flow",
+    "reproject",
+    "fluid",
+    "extern",
+];
+
+/// Settings applied to every graph built from lisp.
+#[derive(Debug, Clone)]
+pub struct BuildOptions {
+    /// Scales the size of every node, see
+    /// [`ShaderGraph::set_render_scale`].
+    pub render_scale:  f32,
+    /// Checks every node for NaN and infinite pixels, see
+    /// [`ShaderGraph::set_validation`].
+    pub validate:      bool,
+    /// Keeps the state of feedback nodes when the graph is
+    /// rebuilt, see [`ShaderGraph::carry_state`].
+    pub keep_state:    bool,
+    /// Keeps `u_time` and `u_frame` running when the graph
+    /// is rebuilt, see [`ShaderGraph::carry_clock`].
+    /// Otherwise, they start over.
+    pub keep_clock:    bool,
+    /// Shows build errors in place of the graph's output
+    /// until it builds again, see
+    /// [`ShaderGraph::error_screen`].
+    pub error_screen:  bool,
+    /// Rebuilds changed graphs in the background, with the
+    /// lisp evaluated on another thread and the nodes
+    /// created a few at a time between frames, so that the
+    /// old graph keeps running until the new one is swapped
+    /// in. See [`crate::reload::ShaderGraphWatcher::graph`].
+    ///
+    /// Only the lisp leaves the render thread: shaders are
+    /// still compiled on it, as a glium context can't be
+    /// shared with another thread, so this time-slices the
+    /// rebuild rather than moving it off the thread. A
+    /// single slow shader, or a slow driver, still holds up
+    /// the frame it's compiled in.
+    pub background:    bool,
+    /// Lets graphs read files outside their project
+    /// directory, see
+    /// [`crate::reload::shader_dir::resolve`]. Only for
+    /// trusted projects.
+    pub allow_outside: bool,
+    /// What graphs are allowed to ask for, checked before
+    /// anything is created, see [`Limits::check`].
+    pub limits:        Limits,
+    /// The profile graphs are built with, one of those they
+    /// declare with `(profile ...)`, see [`Profile`].
+    pub profile:       Option<String>,
+    /// Fixes nodes to the images `shadergarden bake`
+    /// rendered them into, in the project's `baked`
+    /// directory, see [`Plan::read_baked`].
+    pub baked:         bool,
+    /// Builds graphs without opening cameras, audio, or
+    /// feeds, or calling external functions, see
+    /// [`Plan::set_safe`].
+    pub safe:          bool,
+}
+
+impl Default for BuildOptions {
+    fn default() -> BuildOptions {
+        BuildOptions {
+            render_scale:  1.0,
+            validate:      false,
+            keep_state:    false,
+            keep_clock:    false,
+            error_screen:  false,
+            background:    false,
+            allow_outside: false,
+            limits:        Limits::none(),
+            profile:       None,
+            baked:         true,
+            safe:          false,
+        }
+    }
+}
+
 /// Takes a source string of lisp that represents the shader
 /// graph to be constructed, And constructs that shader
 /// graph within a certain context, Provided a map of all
@@ -32,18 +203,188 @@ pub fn graph_from_sexp(
     shader_dir: ShaderDir,
     external: External,
 ) -> Result<ShaderGraph, String> {
-    let mut graph = ShaderGraph::new(context);
-    let mut env = Env::new(shader_dir.shaders, external);
+    graph_from_sexp_with_options(
+        context,
+        shader_dir,
+        external,
+        &BuildOptions::default(),
+    )
+}
 
-    // little hack to get a list of expressions
-    let sexp = lexpr::from_str(&format!("({})", shader_dir.lisp))
-        .map_err(|e| format!("{}", e))?;
+/// Like [`graph_from_sexp`], but with non-default
+/// [`BuildOptions`].
+pub fn graph_from_sexp_with_options(
+    context: &Rc<Context>,
+    shader_dir: ShaderDir,
+    external: External,
+    options: &BuildOptions,
+) -> Result<ShaderGraph, String> {
+    let plan = plan_from_sexp_with_options(shader_dir, options)?;
+    graph_from_plan(context, &plan, external, options)
+}
 
-    begin(&mut graph, &mut env, &sexp)?;
+/// Realizes a [`Plan`] into a new graph, see
+/// [`plan_from_sexp`], once it's checked against the
+/// [`BuildOptions::limits`].
+pub fn graph_from_plan(
+    context: &Rc<Context>,
+    plan: &Plan,
+    external: External,
+    options: &BuildOptions,
+) -> Result<ShaderGraph, String> {
+    options.limits.check(plan, options.render_scale)?;
+    let mut graph = new_graph(context, options)?;
+    plan.realize(&mut graph, &external)?;
+    Ok(graph)
+}
+
+/// Like [`graph_from_plan`], but returns a [`Realization`]
+/// to create the graph's nodes a few at a time.
+pub fn realization_from_plan(
+    context: &Rc<Context>,
+    plan: Plan,
+    options: &BuildOptions,
+) -> Result<Realization, String> {
+    options.limits.check(&plan, options.render_scale)?;
+    Realization::new(plan, new_graph(context, options)?)
+}
 
+/// An empty graph, built with `options`.
+fn new_graph(
+    context: &Rc<Context>,
+    options: &BuildOptions,
+) -> Result<ShaderGraph, String> {
+    let mut graph = ShaderGraph::new(context);
+    graph.set_render_scale(options.render_scale);
+    graph.set_validation(options.validate)?;
     Ok(graph)
 }
 
+/// Evaluates the lisp for a shader graph into a [`Plan`],
+/// without a GL context. Nothing is compiled or allocated
+/// until the plan is realized, so this is enough to check
+/// the structure of a graph, or to build it with another
+/// backend. Files the graph refers to, like point clouds,
+/// are still read.
+pub fn plan_from_sexp(shader_dir: ShaderDir) -> Result<Plan, String> {
+    plan_from_sexp_with_limits(shader_dir, &Limits::none())
+}
+
+/// Like [`plan_from_sexp`], but stops evaluating the lisp
+/// as soon as it makes more nodes, or repeats anything more
+/// times, than `limits` allow. The rest of the limits are
+/// checked when the plan is realized.
+pub fn plan_from_sexp_with_limits(
+    shader_dir: ShaderDir,
+    limits: &Limits,
+) -> Result<Plan, String> {
+    let options = BuildOptions {
+        limits: limits.clone(),
+        ..BuildOptions::default()
+    };
+    plan_from_sexp_with_options(shader_dir, &options)
+}
+
+/// Like [`plan_from_sexp_with_limits`], but also with the
+/// [`BuildOptions::profile`] selected. Errors if the graph
+/// doesn't declare that profile.
+pub fn plan_from_sexp_with_options(
+    shader_dir: ShaderDir,
+    options: &BuildOptions,
+) -> Result<Plan, String> {
+    let mut plan = Plan::new();
+    let mut env = Env::new(
+        shader_dir.shaders,
+        &shader_dir.root,
+        shader_dir.allow_outside,
+        options.limits.clone(),
+        options.profile.clone(),
+    )
+    .with_unread(shader_dir.unread);
+    evaluate(&mut plan, &mut env, &shader_dir.lisp)?;
+    if let Some(name) = &options.profile {
+        if !plan.profiles().contains(name) {
+            return Err(match plan.profiles() {
+                [] => format!(
+                    "The graph declares no profiles, so has no `{}`",
+                    name
+                ),
+                profiles => format!(
+                    "The graph has no profile `{}`, only `{}`",
+                    name,
+                    profiles.join("`, `")
+                ),
+            });
+        }
+    }
+    for file in env.shader_includes() {
+        plan.add_shader_include(file);
+    }
+    for name in env.shaders_used() {
+        plan.add_shader(name);
+    }
+    if options.baked {
+        plan.read_baked(&Baked::dir(&shader_dir.root))?;
+    }
+    plan.set_safe(options.safe);
+
+    Ok(plan)
+}
+
+/// Evaluates a graph, or a file it includes, against the
+/// version of the syntax it declares. Graphs written for an
+/// older version than [`SYNTAX_VERSION`] are checked for
+/// forms that have since been removed first, which are
+/// errors, each with what to write instead, see
+/// [`migrate::outdated`]. Otherwise, graphs written for an
+/// older or newer version still build if they can, with a
+/// warning, and their errors say what to do about it.
+fn evaluate(plan: &mut Plan, env: &mut Env, lisp: &str) -> Result<(), String> {
+    let removed = migrate::outdated(lisp);
+    if !removed.is_empty() {
+        return Err(with_hint(
+            removed.join("\n"),
+            "run `shadergarden migrate` to rewrite what it can",
+        ));
+    }
+    let result = parse(lisp).and_then(|sexp| begin(plan, env, &sexp));
+    let hint = match migrate::declared_version(lisp) {
+        Some(version) if version < SYNTAX_VERSION => format!(
+            "The graph is written for version {} of the syntax, and this \
+             release reads version {}; run `shadergarden migrate` to update it",
+            version, SYNTAX_VERSION
+        ),
+        Some(version) if version > SYNTAX_VERSION => format!(
+            "The graph is written for version {} of the syntax, and this \
+             release only reads up to version {}; it may need a newer release",
+            version, SYNTAX_VERSION
+        ),
+        _ => return result,
+    };
+    match result {
+        Ok(()) => {
+            eprintln!("[warn] {}", hint);
+            Ok(())
+        },
+        Err(e) => Err(with_hint(e, &hint)),
+    }
+}
+
+/// Parses lisp into a list of its top-level forms.
+fn parse(lisp: &str) -> Result<Value, String> {
+    // little hack to get a list of expressions
+    let options = lexpr::parse::Options::default()
+        .with_keyword_syntax(lexpr::parse::KeywordSyntax::ColonPrefix);
+    lexpr::from_str_custom(&format!("({})", lisp), options).map_err(|e| {
+        let error = format!("{}", e);
+        if error.starts_with("EOF while parsing a list") {
+            with_hint(error, "a `(` above it is never closed")
+        } else {
+            error
+        }
+    })
+}
+
 fn into_iter(sexp: &Value) -> Result<lexpr::cons::ListIter<'_>, String> {
     sexp.list_iter()
         .ok_or_else(|| "Expected a form".to_string())
@@ -59,9 +400,309 @@ fn next_item<'a>(
 fn next_symbol<'a>(
     iter: &mut lexpr::cons::ListIter<'a>,
 ) -> Result<&'a str, String> {
-    return next_item(iter)?
+    next_item(iter)?
         .as_symbol()
-        .ok_or_else(|| "Expected a symbol".to_string());
+        .ok_or_else(|| "Expected a symbol".to_string())
+}
+
+/// Splits the rest of a form into positional arguments and
+/// `:keyword value` pairs. Keyword values are evaluated,
+/// but unbound symbols are taken literally, so
+/// `:layout rows` works without quoting.
+fn keyword_args<'a>(
+    plan: &mut Plan,
+    env: &mut Env,
+    form: &str,
+    mut iter: lexpr::cons::ListIter<'a>,
+) -> Result<(Vec<&'a Value>, Kwargs), String> {
+    let mut positional = vec![];
+    let mut kwargs = Kwargs::new(form);
+
+    while let Some(item) = iter.next() {
+        if let Some(keyword) = item.as_keyword() {
+            let value = iter.next().ok_or_else(|| {
+                format!("Keyword `:{}` is missing a value", keyword)
+            })?;
+            let val = match value.as_symbol() {
+                Some(symbol) if env.get(symbol).is_err() => {
+                    Val::String(symbol.to_string())
+                },
+                _ => expr(plan, env, value)?,
+            };
+            kwargs.insert(keyword, val)?;
+        } else {
+            positional.push(item);
+        }
+    }
+
+    Ok((positional, kwargs))
+}
+
+/// Parses `(control <name> :midi-cc <cc> :channel <n>
+/// :osc <address> :range <low> <high> :input-range <low>
+/// <high> :default <value> :smooth <seconds> :slew
+/// <rate>)`, declaring a float uniform set from a MIDI or
+/// OSC controller, or both. `:range` and `:input-range`
+/// take two values, unlike other keywords, so they are read
+/// here.
+fn control(
+    plan: &mut Plan,
+    env: &mut Env,
+    mut iter: lexpr::cons::ListIter<'_>,
+) -> Result<(), String> {
+    let name = match next_item(&mut iter)? {
+        Value::String(name) => name.to_string(),
+        other => other
+            .as_symbol()
+            .ok_or_else(|| {
+                "Expected `(control \"name\" <sources...>)`".to_string()
+            })?
+            .to_string(),
+    };
+
+    let mut kwargs = Kwargs::new("control");
+    let mut range = None;
+    let mut input = None;
+    while let Some(item) = iter.next() {
+        let keyword = item.as_keyword().ok_or_else(|| {
+            format!("Expected a keyword in `control`, found `{}`", item)
+        })?;
+        let mut value = || -> Result<f64, String> {
+            let value = iter.next().ok_or_else(|| {
+                format!("Keyword `:{}` is missing a value", keyword)
+            })?;
+            expr(plan, env, value)?.to_float()
+        };
+        match keyword {
+            "range" if range.is_some() => {
+                return Err("Keyword `:range` passed more than once".to_string())
+            },
+            "range" => range = Some((value()? as f32, value()? as f32)),
+            "input-range" if input.is_some() => {
+                return Err(
+                    "Keyword `:input-range` passed more than once".to_string()
+                )
+            },
+            "input-range" => input = Some((value()? as f32, value()? as f32)),
+            "osc" => {
+                let value = iter.next().ok_or_else(|| {
+                    "Keyword `:osc` is missing a value".to_string()
+                })?;
+                // unquoted, as with other keywords
+                let address = match value.as_symbol() {
+                    Some(symbol) if env.get(symbol).is_err() => {
+                        Val::String(symbol.to_string())
+                    },
+                    _ => expr(plan, env, value)?,
+                };
+                kwargs.insert(keyword, address)?;
+            },
+            _ => kwargs.insert(keyword, Val::Number(value()?))?,
+        }
+    }
+
+    let (low, high) = range.unwrap_or((0.0, 1.0));
+    let default = match kwargs.take("default") {
+        Some(default) => default.to_float()? as f32,
+        None => low,
+    };
+    let input = input.unwrap_or((0.0, 1.0));
+    if !(0.0..=1.0).contains(&input.0)
+        || !(0.0..=1.0).contains(&input.1)
+        || input.0 == input.1
+    {
+        return Err(
+            "`:input-range` takes two different values from 0 to 1".to_string()
+        );
+    }
+    let time = match kwargs.take("smooth") {
+        Some(time) => time.to_float()? as f32,
+        None => 0.0,
+    };
+    if time < 0.0 {
+        return Err("`:smooth` can't be negative".to_string());
+    }
+    let slew = kwargs
+        .take("slew")
+        .map(|slew| slew.to_float())
+        .transpose()?;
+    if slew.is_some_and(|slew| slew <= 0.0) {
+        return Err("`:slew` must be more than 0".to_string());
+    }
+    let smoothing = Smoothing {
+        time,
+        slew: slew.map(|slew| slew as f32),
+    };
+    let channel = match kwargs.take("channel") {
+        Some(channel) => match channel.to_nat()? {
+            channel @ 1..=16 => Some(channel as u8),
+            _ => return Err("MIDI channels go from 1 to 16".to_string()),
+        },
+        None => None,
+    };
+    let mut sources = vec![];
+    if let Some(cc) = kwargs.take("midi-cc") {
+        match cc.to_nat()? {
+            cc @ 0..=127 => sources.push(Source::MidiCc {
+                cc: cc as u8,
+                channel,
+            }),
+            _ => return Err("MIDI CC numbers go from 0 to 127".to_string()),
+        }
+    } else if channel.is_some() {
+        return Err("`:channel` is only used with `:midi-cc`".to_string());
+    }
+    if let Some(address) = kwargs.take("osc") {
+        sources.push(Source::Osc(address.to_string()?));
+    }
+    kwargs.finish()?;
+    if sources.is_empty() {
+        return Err(format!(
+            "Control `{}` needs a `:midi-cc` or an `:osc` address",
+            name
+        ));
+    }
+
+    plan.declare_uniform(&name, UniformType::Float)?;
+    for source in sources {
+        plan.add_knob(Knob {
+            uniform: name.clone(),
+            source,
+            range: (low, high),
+            input,
+            default,
+            smoothing,
+        });
+    }
+    Ok(())
+}
+
+/// Parses `(profile <name> :scale <scale> :supersample <n>
+/// :define <name>)`, declaring a [`Profile`], which only
+/// applies if it's the one selected. `:define` can be given
+/// more than once, unlike other keywords, so it's read
+/// here.
+fn profile(
+    plan: &mut Plan,
+    env: &mut Env,
+    mut iter: lexpr::cons::ListIter<'_>,
+) -> Result<(), String> {
+    let name = match next_item(&mut iter)? {
+        Value::String(name) => name.to_string(),
+        other => quoted_symbol(other)
+            .ok_or_else(|| "Expected `(profile '<name> ...)`".to_string())?
+            .to_string(),
+    };
+
+    let mut profile = Profile::new(&name);
+    let mut kwargs = Kwargs::new("profile");
+    while let Some(item) = iter.next() {
+        let keyword = item.as_keyword().ok_or_else(|| {
+            format!("Expected a keyword in `profile`, found `{}`", item)
+        })?;
+        let value = iter.next().ok_or_else(|| {
+            format!("Keyword `:{}` is missing a value", keyword)
+        })?;
+        let value = match value.as_symbol() {
+            // unquoted, as with other keywords
+            Some(symbol) if env.get(symbol).is_err() => {
+                Val::String(symbol.to_string())
+            },
+            _ => expr(plan, env, value)?,
+        };
+        match keyword {
+            "define" => {
+                let define = value.to_string()?;
+                let word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+                let valid = define.chars().all(word)
+                    && define.starts_with(|c: char| !c.is_ascii_digit());
+                if !valid {
+                    return Err(format!(
+                        "Expected a name to `#define`, found `{}`",
+                        define
+                    ));
+                }
+                profile.defines.push(define);
+            },
+            _ => kwargs.insert(keyword, value)?,
+        }
+    }
+    if let Some(scale) = kwargs.take("scale") {
+        profile.scale = scale.to_float()? as f32;
+        if profile.scale.is_nan() || profile.scale <= 0.0 {
+            return Err("A profile's `:scale` must be positive".to_string());
+        }
+    }
+    if let Some(factor) = kwargs.take("supersample") {
+        profile.supersample = match factor.to_nat()? {
+            0 => return Err("Can't supersample 0 times".to_string()),
+            factor => factor as u32,
+        };
+    }
+    kwargs.finish()?;
+
+    let selected = env.profile() == Some(name.as_str());
+    plan.add_profile(profile, selected)
+}
+
+/// Parses a count of workgroups, given as a number, or as
+/// up to three numbers like `"64x64"`.
+fn workgroups(groups: &Val) -> Result<[u32; 3], String> {
+    let invalid = || {
+        format!(
+            "Expected workgroups like `64` or `\"64x64\"`, found `{:?}`",
+            groups
+        )
+    };
+    if let Val::Number(_) = groups {
+        return Ok([groups.to_nat()? as u32, 1, 1]);
+    }
+
+    let mut counts = [1; 3];
+    let text = groups.to_string().map_err(|_| invalid())?;
+    let parts = text.split('x').collect::<Vec<_>>();
+    if parts.len() > 3 {
+        return Err(invalid());
+    }
+    for (count, part) in counts.iter_mut().zip(parts) {
+        *count = part.parse().map_err(|_| invalid())?;
+    }
+    Ok(counts)
+}
+
+/// A symbol, written plainly or quoted, like `'sunset`.
+fn quoted_symbol(value: &Value) -> Option<&str> {
+    if let Some(symbol) = value.as_symbol() {
+        return Some(symbol);
+    }
+    let mut iter = value.list_iter()?;
+    match (iter.next()?.as_symbol()?, iter.next()?, iter.next()) {
+        ("quote", symbol, None) => symbol.as_symbol(),
+        _ => None,
+    }
+}
+
+/// Parses the type of a `uniform`: a symbol like `vec3`, or
+/// `(struct (<field> <type>) ...)`.
+fn uniform_type(value: &Value) -> Result<UniformType, String> {
+    if let Some(symbol) = value.as_symbol() {
+        return symbol.parse();
+    }
+
+    let mut iter = into_iter(value)?;
+    if next_symbol(&mut iter)? != "struct" {
+        return Err("Expected a uniform type, or `(struct ...)`".to_string());
+    }
+    let mut fields = vec![];
+    for field in iter {
+        let mut field_iter = into_iter(field)?;
+        let name = next_symbol(&mut field_iter)
+            .map_err(|_| "Expected `(<field> <type>)` in struct".to_string())?;
+        let kind = uniform_type(next_item(&mut field_iter)?)?;
+        iter_finish(field_iter)?;
+        fields.push((name.to_string(), kind));
+    }
+    Ok(UniformType::Struct(fields))
 }
 
 fn iter_finish(iter: lexpr::cons::ListIter<'_>) -> Result<(), String> {
@@ -72,95 +713,357 @@ fn iter_finish(iter: lexpr::cons::ListIter<'_>) -> Result<(), String> {
     }
 }
 
-fn begin(
-    mut graph: &mut ShaderGraph,
-    env: &mut Env,
-    sexp: &Value,
-) -> Result<(), String> {
+fn begin(plan: &mut Plan, env: &mut Env, sexp: &Value) -> Result<(), String> {
     for declaration in into_iter(sexp)? {
-        declare(&mut graph, env, declaration)?;
+        declare(plan, env, declaration)?;
     }
 
     Ok(())
 }
 
-fn declare(
-    graph: &mut ShaderGraph,
+/// Defines a function from the symbols it binds and the
+/// forms of its body, the last of which is its output.
+fn define_fn(
     env: &mut Env,
-    sexp: &Value,
+    name: &str,
+    params: lexpr::cons::ListIter<'_>,
+    body: lexpr::cons::ListIter<'_>,
 ) -> Result<(), String> {
+    // extract all the arguments
+    let mut args = vec![];
+    for arg in params {
+        args.push(
+            arg.as_symbol()
+                .ok_or_else(|| "Expected symbol in signature".to_string())?
+                .to_string(),
+        );
+    }
+
+    let forms: Vec<Value> = body.map(|f| f.to_owned()).collect();
+    if forms.is_empty() {
+        return Err(format!(
+            "Definition `{}` must have at least one expression in body",
+            name
+        ));
+    }
+    env.set_fn(name.to_string(), (args, forms));
+    Ok(())
+}
+
+fn declare(plan: &mut Plan, env: &mut Env, sexp: &Value) -> Result<(), String> {
     let mut iter = into_iter(sexp)?;
     let keyword = next_symbol(&mut iter)?;
 
     match keyword {
         "input" => {
             let var = next_symbol(&mut iter)?;
-            let input = graph.add_input();
+            let input = plan.add_input();
+            // named so hosts can upload to it, see
+            // [`ShaderGraph::upload_input`]
+            plan.name_node(input, var);
             env.set(var.to_string(), Val::Node(input));
         },
         "output" => {
             let id = next_symbol(&mut iter)?;
-            graph.mark_output(env.get(id)?.to_node()?);
+            plan.mark_output(env.get(id)?.to_node()?);
         },
         "define" => {
-            // get the form defining the signature
+            // a constant, like `(define pi 3.14159)`
             let list = next_item(&mut iter)?;
+            if let Some(name) = list.as_symbol() {
+                let val = expr(plan, env, next_item(&mut iter)?)?;
+                iter_finish(iter)?;
+                env.set(name.to_string(), val);
+                return Ok(());
+            }
+
+            // get the form defining the signature
             let mut signature = into_iter(list)?;
             let name = next_symbol(&mut signature)?;
+            define_fn(env, name, signature, iter)?;
+            return Ok(());
+        },
+        "defnode" => {
+            let name = next_symbol(&mut iter)?;
+            let params = into_iter(next_item(&mut iter)?)?;
+            define_fn(env, name, params, iter)?;
+            return Ok(());
+        },
+        "include" => {
+            let file = expr(plan, env, next_item(&mut iter)?)?.to_string()?;
+            iter_finish(iter)?;
+            let path = env.path(&file)?;
+            // watched even if it fails to build, so that
+            // fixing it rebuilds the graph
+            plan.add_include(&path);
+            let lisp = fs::read_to_string(&path)
+                .map_err(|e| format!("Could not read `{}`: {}", file, e))?;
 
-            // extract all the arguments
-            let mut args = vec![];
-            for arg in signature {
-                args.push(
-                    arg.as_symbol()
-                        .ok_or_else(|| {
-                            "Expected symbol in signature".to_string()
-                        })?
-                        .to_string(),
-                );
-            }
-
-            let forms: Vec<Value> = iter.map(|f| f.to_owned()).collect();
-            if forms.is_empty() {
-                return Err(format!(
-                    "Definition `{}` must have at least one expression in body",
-                    name
-                ));
-            }
-            env.set_fn(name.to_string(), (args, forms));
+            env.enter_include(&path)?;
+            let included = evaluate(plan, env, &lisp);
+            env.exit_include();
+            included
+                .map_err(|e| format!("While including `{}`: {}", file, e))?;
             return Ok(());
         },
         "let" => {
             let var = next_symbol(&mut iter)?;
-            let val = expr(graph, env, next_item(&mut iter)?)?;
+            let val = expr(plan, env, next_item(&mut iter)?)?;
+            if let Val::Node(id) = val {
+                plan.name_node(id, var);
+            }
             env.set(var.to_string(), val);
         },
+        "require" => {
+            let (args, mut kwargs) = keyword_args(plan, env, "require", iter)?;
+            if !args.is_empty() {
+                return Err("Expected `(require :feature value ...)`".into());
+            }
+            let version = match kwargs.take("gl") {
+                // `4.0` would read back as `4`
+                Some(Val::Number(n)) => Some(format!("{:.1}", n).parse()?),
+                Some(other) => Some(other.to_string()?.parse()?),
+                None => None,
+            };
+            let flag =
+                |val: Option<Val>| val.map_or(Ok(false), |v| v.to_bool());
+            let requirements = Requirements {
+                version,
+                glsl: glsl_version(&mut kwargs)?,
+                float_textures: flag(kwargs.take("float-textures"))?,
+                color_attachments: kwargs
+                    .take("color-attachments")
+                    .map(|v| v.to_nat().map(|n| n as u32))
+                    .transpose()?,
+                compute: flag(kwargs.take("compute"))?,
+            };
+            kwargs.finish()?;
+            plan.require(requirements);
+            return Ok(());
+        },
+        // read before the graph is evaluated, see [`evaluate`]
+        "version" => {
+            let version = expr(plan, env, next_item(&mut iter)?)?.to_nat()?;
+            if version == 0 {
+                return Err("Expected `(version <n>)`, from 1".to_string());
+            }
+        },
+        "warmup" => {
+            let frames = expr(plan, env, next_item(&mut iter)?)?.to_nat()?;
+            plan.warm_up(frames as u32);
+        },
+        "glsl" => {
+            let version = expr(plan, env, next_item(&mut iter)?)?;
+            iter_finish(iter)?;
+            plan.set_glsl(version.to_string()?.parse()?)?;
+            return Ok(());
+        },
+        "preamble" => {
+            let mut preamble = String::new();
+            for name in iter {
+                let name = expr(plan, env, name)?.to_string()?;
+                // the shaders it's added to have their own
+                let source = env.shader_as(&name, Stage::Library)?;
+                let lines = source.lines();
+                for line in lines.filter(|l| !l.starts_with("#version")) {
+                    preamble.push_str(line);
+                    preamble.push('\n');
+                }
+            }
+            plan.set_preamble(&preamble)?;
+            return Ok(());
+        },
+        "palette" => {
+            let name = match next_item(&mut iter)? {
+                Value::String(name) => name.to_string(),
+                other => quoted_symbol(other)
+                    .ok_or_else(|| {
+                        "Expected `(palette <name> <colors...>)`".to_string()
+                    })?
+                    .to_string(),
+            };
+            let colors = iter
+                .map(|color| expr(plan, env, color)?.to_string())
+                .collect::<Result<Vec<_>, _>>()?;
+            let colors = colors.iter().map(String::as_str).collect::<Vec<_>>();
+            plan.add_palette(Palette::new(&name, &colors)?);
+            return Ok(());
+        },
+        "uniform" => {
+            let name = match next_item(&mut iter)? {
+                Value::String(name) => name.to_string(),
+                other => other
+                    .as_symbol()
+                    .ok_or_else(|| {
+                        "Expected `(uniform \"name\" <type>)`".to_string()
+                    })?
+                    .to_string(),
+            };
+            let mut kind = uniform_type(next_item(&mut iter)?)?;
+            if let Some(count) = iter.next() {
+                let count = expr(plan, env, count)?.to_nat()?;
+                kind = UniformType::Array(Box::new(kind), count);
+            }
+            kind.validate()?;
+            plan.declare_uniform(&name, kind)?;
+        },
+        "control" => return control(plan, env, iter),
+        "profile" => return profile(plan, env, iter),
+        "order" => {
+            let (args, mut kwargs) = keyword_args(plan, env, "order", iter)?;
+            let id = match args.as_slice() {
+                [id] => expr(plan, env, id)?.to_node()?,
+                _ => return Err("Expected `(order <node> ...)`".to_string()),
+            };
+            if let Some(before) = kwargs.take("before") {
+                plan.order_before(id, before.to_node()?)?;
+            }
+            if let Some(after) = kwargs.take("after") {
+                plan.order_before(after.to_node()?, id)?;
+            }
+            return kwargs.finish();
+        },
+        "buffer" => {
+            let name = match next_item(&mut iter)? {
+                Value::String(name) => name.to_string(),
+                other => quoted_symbol(other)
+                    .ok_or_else(|| {
+                        "Expected `(buffer '<name> <width> <height> ...)`"
+                            .to_string()
+                    })?
+                    .to_string(),
+            };
+            let (args, mut kwargs) = keyword_args(plan, env, "buffer", iter)?;
+            let (width, height) = match args.as_slice() {
+                [width, height] => (
+                    expr(plan, env, width)?.to_nat()? as u32,
+                    expr(plan, env, height)?.to_nat()? as u32,
+                ),
+                _ => {
+                    return Err(format!(
+                        "Expected `(buffer '{} <width> <height> ...)`",
+                        name
+                    ))
+                },
+            };
+            let texture = texture_options(&mut kwargs)?;
+            kwargs.finish()?;
+            // declared once, to be read and written by name
+            if env.get(&name).is_ok() {
+                return Err(format!("`{}` is already defined", name));
+            }
+            let spec = NodeSpec::Buffer {
+                width,
+                height,
+                texture,
+            };
+            let id = plan.add(spec, None);
+            plan.name_node(id, &name);
+            env.set(name, Val::Node(id));
+            return Ok(());
+        },
+        "write" => {
+            // the buffer by name, quoted or not
+            let buffer = next_item(&mut iter)?;
+            let buffer = match quoted_symbol(buffer) {
+                Some(name) => env.get(name)?.clone(),
+                None => expr(plan, env, buffer)?,
+            };
+            let writer = expr(plan, env, next_item(&mut iter)?)?;
+            plan.write_buffer(buffer.to_node()?, writer.to_node()?)?;
+        },
+        "meta" => {
+            let (args, mut kwargs) = keyword_args(plan, env, "meta", iter)?;
+            if !args.is_empty() {
+                return Err(
+                    "Expected `(meta :title \"...\" :author \"...\" ...)`"
+                        .to_string(),
+                );
+            }
+            let mut text =
+                |key| kwargs.take(key).map(|v| v.to_string()).transpose();
+            let title = text("title")?;
+            let author = text("author")?;
+            let license = text("license")?;
+            let preview = text("preview")?;
+            kwargs.finish()?;
+            plan.set_meta(Meta {
+                title,
+                author,
+                license,
+                preview: preview.map(|p| env.path(&p)).transpose()?,
+            })?;
+            return Ok(());
+        },
+        "window" => {
+            let (args, mut kwargs) = keyword_args(plan, env, "window", iter)?;
+            let node = match args.as_slice() {
+                [node] => expr(plan, env, node)?.to_node()?,
+                _ => return Err("Expected `(window <node> ...)`".to_string()),
+            };
+            // named after the node, and its label
+            let title = match kwargs.take("title") {
+                Some(title) => title.to_string()?,
+                None => plan.topology().describe(node),
+            };
+            let width = kwargs.take("width").map(|w| w.to_nat()).transpose()?;
+            let height =
+                kwargs.take("height").map(|h| h.to_nat()).transpose()?;
+            let size = match (width, height) {
+                (Some(w), Some(h)) => Some((w as u32, h as u32)),
+                (None, None) => None,
+                _ => {
+                    return Err(
+                        "A window needs both a `:width` and a `:height`"
+                            .to_string(),
+                    )
+                },
+            };
+            let fullscreen =
+                kwargs.take("fullscreen").map(|m| m.to_nat()).transpose()?;
+            kwargs.finish()?;
+            plan.add_window(OutputWindow {
+                node,
+                title,
+                size,
+                fullscreen,
+            })?;
+            return Ok(());
+        },
         "repeat" => {
-            let times = expr(graph, env, next_item(&mut iter)?)?.to_nat()?;
+            let times = expr(plan, env, next_item(&mut iter)?)?.to_nat()?;
+            env.limits().check_iterations("Repeating", times as u64)?;
             let forms: Vec<Value> = iter.map(|f| f.to_owned()).collect();
             for _ in 0..times {
+                env.repeat_once()?;
                 for form in forms.iter() {
-                    declare(graph, env, form)?;
+                    declare(plan, env, form)?;
                 }
             }
             return Ok(());
         },
         other => {
-            return Err(format!(
-                "Expected a statement keyword, found `{}`",
-                other
-            ))
+            let error =
+                format!("Expected a statement keyword, found `{}`", other);
+            // a common mistake is a node on its own, unnamed
+            if FORMS.contains(&other) || env.get_fn(other).is_ok() {
+                return Err(with_hint(
+                    error,
+                    &format!(
+                        "`{}` makes a node, so name it, like `(let <name> \
+                         ({} ...))`",
+                        other, other
+                    ),
+                ));
+            }
+            return Err(suggest(error, other, STATEMENTS.iter().copied()));
         },
     }
 
     iter_finish(iter)
 }
 
-fn expr(
-    graph: &mut ShaderGraph,
-    env: &mut Env,
-    value: &Value,
-) -> Result<Val, String> {
+fn expr(plan: &mut Plan, env: &mut Env, value: &Value) -> Result<Val, String> {
     let val = match value {
         Value::Number(n) => {
             if let Some(float) = n.as_f64() {
@@ -174,7 +1077,28 @@ fn expr(
         Value::String(_) => Val::String(value.as_str().unwrap().to_string()),
         Value::Symbol(_) => env.get(value.as_symbol().unwrap())?.clone(),
 
-        x if x.is_list() => node(graph, env, x)?,
+        x if x.is_list() => {
+            let (form, label) = take_keyword(Cow::Borrowed(x), "label")?;
+            let (form, when) = take_keyword(form, "when")?;
+            let label = label.map(|label| expr(plan, env, &label)?.to_string());
+            let label = label.transpose()?;
+            let when = when.map(|when| condition(env, &when)).transpose()?;
+            let val = node(plan, env, &form)?;
+            env.limits().check_nodes(plan.nodes().count())?;
+            if let Some(label) = label {
+                let id = val.to_node().map_err(|_| {
+                    format!("Only nodes can take a `:label`, found `{}`", x)
+                })?;
+                plan.label_node(id, &label);
+            }
+            if let Some(when) = when {
+                let id = val.to_node().map_err(|_| {
+                    format!("Only nodes can take a `:when`, found `{}`", x)
+                })?;
+                plan.set_condition(id, when);
+            }
+            val
+        },
 
         other => return Err(format!("unexpected value `{}`", other)),
     };
@@ -182,119 +1106,956 @@ fn expr(
     Ok(val)
 }
 
+/// Takes a keyword and its value, unevaluated, out of a
+/// form, for keywords any form that makes a node can take:
+/// `:label "<text>"`, see [`ShaderGraph::label_node`], and
+/// `:when <condition>`, see [`condition`].
+fn take_keyword<'a>(
+    form: Cow<'a, Value>,
+    keyword: &str,
+) -> Result<(Cow<'a, Value>, Option<Value>), String> {
+    let at = into_iter(&form)?.position(|i| i.as_keyword() == Some(keyword));
+    let at = match at {
+        Some(at) => at,
+        None => return Ok((form, None)),
+    };
+    let items = into_iter(&form)?.collect::<Vec<_>>();
+    let value = items
+        .get(at + 1)
+        .ok_or_else(|| format!("Keyword `:{}` is missing a value", keyword))?;
+    let value = (*value).clone();
+    let rest = items[..at].iter().chain(&items[at + 2..]);
+    let rest = rest.map(|item| (*item).clone()).collect::<Vec<_>>();
+    Ok((Cow::Owned(Value::list(rest)), Some(value)))
+}
+
+/// Parses the condition of a node, tested on the CPU every
+/// frame, see [`ShaderGraph::set_condition`]: comparisons
+/// like `(> u_energy 0.5)`, combined with `and`, `or`, and
+/// `not`. Symbols starting with `u_` are uniforms, other
+/// symbols are looked up when the graph is built, and a
+/// uniform alone holds when it isn't zero.
+fn condition(env: &Env, value: &Value) -> Result<Condition, String> {
+    let operand = |value: &Value| -> Result<Operand, String> {
+        match value.as_symbol() {
+            Some(name) if name.starts_with("u_") => {
+                Ok(Operand::Uniform(name["u_".len()..].to_string()))
+            },
+            Some(name) => {
+                Ok(Operand::Number(env.get(name)?.to_float()? as f32))
+            },
+            None => match value.as_f64() {
+                Some(x) => Ok(Operand::Number(x as f32)),
+                None => Err(format!(
+                    "Expected a number or uniform, found `{}`",
+                    value
+                )),
+            },
+        }
+    };
+    if !value.is_list() {
+        return Ok(Condition::Truthy(operand(value)?));
+    }
+
+    let items = into_iter(value)?.collect::<Vec<_>>();
+    let (head, args) = match items.split_first() {
+        Some((head, args)) => (head.as_symbol().unwrap_or(""), args),
+        None => return Err("Expected a condition, found `()`".to_string()),
+    };
+    let conditions = || {
+        args.iter()
+            .map(|arg| condition(env, arg))
+            .collect::<Result<Vec<_>, _>>()
+    };
+    let combine = |join: fn(Box<Condition>, Box<Condition>) -> Condition| {
+        let mut conditions = conditions()?.into_iter();
+        let first = conditions
+            .next()
+            .ok_or_else(|| format!("Expected `({} <condition> ...)`", head))?;
+        Ok(conditions.fold(first, |a, b| join(Box::new(a), Box::new(b))))
+    };
+    match (head, args) {
+        ("and", _) => combine(Condition::And),
+        ("or", _) => combine(Condition::Or),
+        ("not", [arg]) => Ok(Condition::Not(Box::new(condition(env, arg)?))),
+        ("not", _) => Err("Expected `(not <condition>)`".to_string()),
+        (op, [a, b]) => match Compare::parse(op) {
+            Some(op) => Ok(Condition::Compare(op, operand(a)?, operand(b)?)),
+            None => Err(format!("Unknown condition `{}`", value)),
+        },
+        _ => Err(format!("Unknown condition `{}`", value)),
+    }
+}
+
+/// Parses `<name> <width> <height> <inputs...>`, followed
+/// by any keyword arguments, which the caller must take.
 fn shader(
-    graph: &mut ShaderGraph,
+    plan: &mut Plan,
     env: &mut Env,
-    mut iter: lexpr::cons::ListIter<'_>,
-) -> Result<(String, u32, u32, Vec<NodeId>), String> {
-    let name = expr(graph, env, next_item(&mut iter)?)?.to_string()?;
-    let width = expr(graph, env, next_item(&mut iter)?)?.to_nat()?;
-    let height = expr(graph, env, next_item(&mut iter)?)?.to_nat()?;
-    let mut inputs = vec![];
-    for remaining in iter {
-        let node_id = expr(graph, env, remaining)?.to_node()?;
-        inputs.push(node_id);
+    form: &str,
+    iter: lexpr::cons::ListIter<'_>,
+) -> Result<(String, u32, u32, Vec<NodeId>, Kwargs), String> {
+    let (args, kwargs) = keyword_args(plan, env, form, iter)?;
+    let (name, width, height, inputs) = match args.as_slice() {
+        [name, width, height, inputs @ ..] => (name, width, height, inputs),
+        _ => {
+            return Err(format!(
+                "Expected `({} <shader> <width> <height> ...)`",
+                form
+            ))
+        },
+    };
+    let name = expr(plan, env, name)?.to_string()?;
+    let width = expr(plan, env, width)?.to_nat()?;
+    let height = expr(plan, env, height)?.to_nat()?;
+    let inputs = inputs
+        .iter()
+        .map(|input| expr(plan, env, input)?.to_node())
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((name, width as u32, height as u32, inputs, kwargs))
+}
+
+/// Takes `:format`, `:filter`, and `:wrap`, see
+/// [`TextureOptions`].
+fn texture_options(kwargs: &mut Kwargs) -> Result<TextureOptions, String> {
+    let mut options = TextureOptions::default();
+    if let Some(format) = kwargs.take("format") {
+        options.format = format.to_string()?.parse()?;
+    }
+    if let Some(filter) = kwargs.take("filter") {
+        options.filter = filter.to_string()?.parse()?;
+    }
+    if let Some(wrap) = kwargs.take("wrap") {
+        options.wrap = wrap.to_string()?.parse()?;
+    }
+    Ok(options)
+}
+
+/// Takes `:glsl`, a GLSL version like `430` or `"300 es"`.
+fn glsl_version(kwargs: &mut Kwargs) -> Result<Option<GlslVersion>, String> {
+    kwargs
+        .take("glsl")
+        .map(|version| version.to_string()?.parse())
+        .transpose()
+}
+
+/// Takes `:cache`, whether to keep the node's output on
+/// disk between runs, see [`Plan::cache_node`].
+fn cache(kwargs: &mut Kwargs) -> Result<bool, String> {
+    kwargs
+        .take("cache")
+        .map_or(Ok(false), |cache| cache.to_bool())
+}
+
+/// The longest `:delay`, in seconds, as a delayed input
+/// keeps everything it captures for that long.
+const MAX_DELAY: f64 = 5.0;
+
+/// Takes `:delay`, how many seconds a live input is held
+/// back, or, if negative, ahead of the others, see
+/// [`Plan::input_delay`].
+fn delay(kwargs: &mut Kwargs) -> Result<f32, String> {
+    let delay = match kwargs.take("delay") {
+        Some(delay) => delay.to_float()?,
+        None => return Ok(0.0),
+    };
+    if delay.abs() > MAX_DELAY {
+        return Err(format!(
+            "A delay of {}s is too long, inputs can be delayed by up to {}s",
+            delay, MAX_DELAY
+        ));
+    }
+    Ok(delay as f32)
+}
+
+/// Takes `:fallback`, an image, or `:fallback-shader`, a
+/// shader run on the same inputs at the same size, to stand
+/// in for a node that can't be created, see
+/// [`Plan::set_fallback`].
+fn fallback(
+    env: &mut Env,
+    kwargs: &mut Kwargs,
+    inputs: &[NodeId],
+    width: u32,
+    height: u32,
+) -> Result<Option<NodeSpec>, String> {
+    match (kwargs.take("fallback"), kwargs.take("fallback-shader")) {
+        (Some(_), Some(_)) => {
+            Err("Pass either `:fallback` or `:fallback-shader`, not both"
+                .into())
+        },
+        (Some(image), None) => {
+            Ok(Some(NodeSpec::Image(env.path(&image.to_string()?)?)))
+        },
+        (None, Some(shader)) => {
+            let shader = shader.to_string()?;
+            let name = shader.strip_suffix(".frag").unwrap_or(&shader);
+            Ok(Some(NodeSpec::Shader {
+                source: env.shader(name)?,
+                inputs: inputs.to_vec(),
+                width,
+                height,
+                recurrent: false,
+                init: None,
+                texture: TextureOptions::default(),
+            }))
+        },
+        (None, None) => Ok(None),
     }
-    Ok((name, width as u32, height as u32, inputs))
+}
+
+/// Sets the fallback of a planned node, if any.
+fn with_fallback(
+    plan: &mut Plan,
+    id: NodeId,
+    fallback: Option<NodeSpec>,
+) -> Result<Val, String> {
+    if let Some(fallback) = fallback {
+        plan.set_fallback(id, fallback)?;
+    }
+    Ok(Val::Node(id))
+}
+
+/// Plans a node, with the GLSL version it asked for, if
+/// any.
+fn add_node(
+    plan: &mut Plan,
+    spec: NodeSpec,
+    shader: Option<&str>,
+    glsl: Option<GlslVersion>,
+) -> NodeId {
+    let id = plan.add(spec, shader);
+    if let Some(version) = glsl {
+        plan.set_node_glsl(id, version);
+    }
+    id
 }
 
 fn external(
-    graph: &mut ShaderGraph,
+    plan: &mut Plan,
     env: &mut Env,
     mut iter: lexpr::cons::ListIter<'_>,
 ) -> Result<(String, Vec<NodeId>), String> {
-    let name = expr(graph, env, next_item(&mut iter)?)?.to_string()?;
+    let name = expr(plan, env, next_item(&mut iter)?)?.to_string()?;
     let mut inputs = vec![];
     for remaining in iter {
-        let node_id = expr(graph, env, remaining)?.to_node()?;
+        let node_id = expr(plan, env, remaining)?.to_node()?;
         inputs.push(node_id);
     }
     Ok((name, inputs))
 }
 
-fn node(
-    graph: &mut ShaderGraph,
-    env: &mut Env,
-    sexp: &Value,
-) -> Result<Val, String> {
+fn node(plan: &mut Plan, env: &mut Env, sexp: &Value) -> Result<Val, String> {
     let mut iter = into_iter(sexp)?;
     let function = next_symbol(&mut iter)?;
 
     match function {
         "shader" => {
-            let (name, width, height, inputs) = shader(graph, env, iter)?;
-            let node_id = graph.add_shader(
-                env.shader(&name)?,
+            let (name, width, height, inputs, mut kwargs) =
+                shader(plan, env, function, iter)?;
+            let texture = texture_options(&mut kwargs)?;
+            let glsl = glsl_version(&mut kwargs)?;
+            let fallback = fallback(env, &mut kwargs, &inputs, width, height)?;
+            let cached = cache(&mut kwargs)?;
+            kwargs.finish()?;
+            let spec = NodeSpec::Shader {
+                source: env.shader(&name)?.to_string(),
                 inputs,
-                width as u32,
-                height as u32,
-            )?;
-            Ok(Val::Node(node_id))
+                width,
+                height,
+                recurrent: false,
+                init: None,
+                texture,
+            };
+            let id = add_node(plan, spec, Some(&name), glsl);
+            if cached {
+                plan.cache_node(id);
+            }
+            with_fallback(plan, id, fallback)
         },
         "shader-inline" => {
-            let (src, width, height, inputs) = shader(graph, env, iter)?;
-            let node_id = graph.add_shader(
-                &src,
+            let (source, width, height, inputs, mut kwargs) =
+                shader(plan, env, function, iter)?;
+            let texture = texture_options(&mut kwargs)?;
+            let glsl = glsl_version(&mut kwargs)?;
+            let fallback = fallback(env, &mut kwargs, &inputs, width, height)?;
+            let cached = cache(&mut kwargs)?;
+            kwargs.finish()?;
+            let spec = NodeSpec::Shader {
+                source,
                 inputs,
-                width as u32,
-                height as u32,
-            )?;
-            Ok(Val::Node(node_id))
+                width,
+                height,
+                recurrent: false,
+                init: None,
+                texture,
+            };
+            let id = add_node(plan, spec, None, glsl);
+            if cached {
+                plan.cache_node(id);
+            }
+            with_fallback(plan, id, fallback)
         },
         "shader-param" => {
             // get the shader we'll be running the transformations
             // against
             let decl = into_iter(next_item(&mut iter)?)?;
-            let (name, width, height, inputs) = shader(graph, env, decl)?;
+            let (name, width, height, inputs, mut kwargs) =
+                shader(plan, env, "shader", decl)?;
+            let texture = texture_options(&mut kwargs)?;
+            let glsl = glsl_version(&mut kwargs)?;
+            let fallback = fallback(env, &mut kwargs, &inputs, width, height)?;
+            let cached = cache(&mut kwargs)?;
+            kwargs.finish()?;
             let mut source = env.shader(&name)?.to_string();
 
             // parse the substitutions to be applied
             // let mut subst = vec![];
             for form in iter {
-                source = subst(graph, env, form, source)?;
+                source = subst(plan, env, form, source)?;
             }
 
-            let node_id = graph.add_shader(
-                &source,
+            let spec = NodeSpec::Shader {
+                source,
                 inputs,
-                width as u32,
-                height as u32,
-            )?;
-            Ok(Val::Node(node_id))
-        },
-        "shader-rec" => {
-            let (name, width, height, inputs) = shader(graph, env, iter)?;
-            let node_id = graph.add_rec_shader(
-                env.shader(&name)?,
+                width,
+                height,
+                recurrent: false,
+                init: None,
+                texture,
+            };
+            let id = add_node(plan, spec, Some(&name), glsl);
+            if cached {
+                plan.cache_node(id);
+            }
+            with_fallback(plan, id, fallback)
+        },
+        "shader-rec" | "shader-rec-inline" => {
+            let (args, mut kwargs) = keyword_args(plan, env, function, iter)?;
+            let (source, width, height, inputs) = match args.as_slice() {
+                [source, width, height, inputs @ ..] => (
+                    expr(plan, env, source)?.to_string()?,
+                    expr(plan, env, width)?.to_nat()? as u32,
+                    expr(plan, env, height)?.to_nat()? as u32,
+                    inputs,
+                ),
+                _ => {
+                    return Err(format!(
+                        "Expected `({} <shader> <width> <height> ...)`",
+                        function
+                    ))
+                },
+            };
+            let inputs = inputs
+                .iter()
+                .map(|input| expr(plan, env, input)?.to_node())
+                .collect::<Result<Vec<_>, _>>()?;
+            let init = match (kwargs.take("init"), kwargs.take("init-shader")) {
+                (Some(_), Some(_)) => {
+                    return Err(
+                        "Pass either `:init` or `:init-shader`, not both"
+                            .into(),
+                    )
+                },
+                (Some(image), None) => {
+                    Some(Init::Image(env.path(&image.to_string()?)?))
+                },
+                (None, Some(shader)) => {
+                    let shader = shader.to_string()?;
+                    let name = shader.strip_suffix(".frag").unwrap_or(&shader);
+                    Some(Init::Shader(env.shader(name)?.to_string()))
+                },
+                (None, None) => None,
+            };
+            let warmup = kwargs.take("warmup").map(|w| w.to_nat());
+            let substeps = kwargs.take("substeps").map(|s| s.to_nat());
+            let texture = texture_options(&mut kwargs)?;
+            let glsl = glsl_version(&mut kwargs)?;
+            let fallback = fallback(env, &mut kwargs, &inputs, width, height)?;
+            kwargs.finish()?;
+
+            // `source` is the name of the shader, unless inline
+            let (name, source) = match function {
+                "shader-rec" => {
+                    (Some(source.clone()), env.shader(&source)?.to_string())
+                },
+                _ => (None, source),
+            };
+            let spec = NodeSpec::Shader {
+                source,
+                inputs,
+                width,
+                height,
+                recurrent: true,
+                init,
+                texture,
+            };
+            let id = add_node(plan, spec, name.as_deref(), glsl);
+            if let Some(frames) = warmup.transpose()? {
+                plan.warm_up_node(id, frames as u32);
+            }
+            if let Some(substeps) = substeps.transpose()? {
+                plan.set_substeps(id, substeps as u32);
+            }
+            with_fallback(plan, id, fallback)
+        },
+        "repeat" => {
+            let iterations =
+                expr(plan, env, next_item(&mut iter)?)?.to_nat()?;
+            let name = next_symbol(&mut iter)?;
+            let looped = env.get(name)?.to_node()?;
+            let mut body = into_iter(next_item(&mut iter)?)?;
+            iter_finish(iter)?;
+
+            let kind = next_symbol(&mut body)?;
+            let (source, width, height, inputs, mut kwargs) =
+                shader(plan, env, kind, body)?;
+            let texture = texture_options(&mut kwargs)?;
+            let glsl = glsl_version(&mut kwargs)?;
+            let fallback = fallback(env, &mut kwargs, &inputs, width, height)?;
+            kwargs.finish()?;
+            let (shader_name, source) = match kind {
+                "shader" => {
+                    (Some(source.clone()), env.shader(&source)?.to_string())
+                },
+                "shader-inline" => (None, source),
+                _ => {
+                    return Err(
+                        "Expected `(repeat <n> <name> (shader ...))`".into()
+                    )
+                },
+            };
+            let feedback =
+                inputs.iter().position(|i| *i == looped).ok_or_else(|| {
+                    format!("`{}` is not an input of the repeated shader", name)
+                })?;
+
+            let spec = NodeSpec::Repeat {
+                source,
+                inputs,
+                feedback,
+                iterations: iterations as u32,
+                width,
+                height,
+                texture,
+            };
+            let id = add_node(plan, spec, shader_name.as_deref(), glsl);
+            with_fallback(plan, id, fallback)
+        },
+        "shader-mrt" => {
+            let (args, mut kwargs) = keyword_args(plan, env, function, iter)?;
+            let (name, count, width, height, inputs) = match args.as_slice() {
+                [name, count, width, height, inputs @ ..] => (
+                    expr(plan, env, name)?.to_string()?,
+                    expr(plan, env, count)?.to_nat()?,
+                    expr(plan, env, width)?.to_nat()?,
+                    expr(plan, env, height)?.to_nat()?,
+                    inputs,
+                ),
+                _ => {
+                    return Err("Expected `(shader-mrt <name> <count> <width> \
+                                <height> ...)`"
+                        .to_string())
+                },
+            };
+            let inputs = inputs
+                .iter()
+                .map(|input| expr(plan, env, input)?.to_node())
+                .collect::<Result<Vec<_>, _>>()?;
+            let (width, height) = (width as u32, height as u32);
+            let glsl = glsl_version(&mut kwargs)?;
+            let fallback = fallback(env, &mut kwargs, &inputs, width, height)?;
+            kwargs.finish()?;
+            let spec = NodeSpec::Mrt {
+                source: env.shader(&name)?.to_string(),
                 inputs,
-                width as u32,
-                height as u32,
-            )?;
-            Ok(Val::Node(node_id))
-        },
-        "shader-rec-inline" => {
-            let (src, width, height, inputs) = shader(graph, env, iter)?;
-            let node_id = graph.add_rec_shader(
-                &src,
+                width,
+                height,
+                count,
+            };
+            let id = add_node(plan, spec, Some(&name), glsl);
+            with_fallback(plan, id, fallback)
+        },
+        "attachment" => {
+            let node = expr(plan, env, next_item(&mut iter)?)?.to_node()?;
+            let index = expr(plan, env, next_item(&mut iter)?)?.to_nat()?;
+            iter_finish(iter)?;
+            let spec = NodeSpec::Attachment { node, index };
+            Ok(Val::Node(plan.add(spec, None)))
+        },
+        "pyramid" => {
+            let (args, mut kwargs) = keyword_args(plan, env, "pyramid", iter)?;
+            let (input, name) = match args.as_slice() {
+                [input, name] => (
+                    expr(plan, env, input)?.to_node()?,
+                    expr(plan, env, name)?.to_string()?,
+                ),
+                _ => {
+                    return Err(
+                        "Expected `(pyramid <input> <shader> ...)`".to_string()
+                    )
+                },
+            };
+            let levels = match kwargs.take("levels") {
+                Some(levels) => levels.to_nat()?,
+                None => 6,
+            };
+            let glsl = glsl_version(&mut kwargs)?;
+            kwargs.finish()?;
+
+            let spec = NodeSpec::Pyramid {
+                source: env.shader(&name)?.to_string(),
+                input,
+                levels: levels as u32,
+            };
+            Ok(Val::Node(add_node(plan, spec, Some(&name), glsl)))
+        },
+        "points" => {
+            let file = expr(plan, env, next_item(&mut iter)?)?.to_string()?;
+            let (name, width, height, inputs, mut kwargs) =
+                shader(plan, env, function, iter)?;
+            let glsl = glsl_version(&mut kwargs)?;
+            kwargs.finish()?;
+            let path = env.path(&file)?;
+            plan.add_include(&path);
+            let points = load_points(&path)?;
+            let spec = NodeSpec::Points {
+                source: env.shader(&name)?.to_string(),
+                points,
                 inputs,
-                width as u32,
-                height as u32,
-            )?;
-            Ok(Val::Node(node_id))
+                width,
+                height,
+            };
+            Ok(Val::Node(add_node(plan, spec, Some(&name), glsl)))
+        },
+        "data" => {
+            let (args, mut kwargs) = keyword_args(plan, env, "data", iter)?;
+            let file = match args.as_slice() {
+                [file] => expr(plan, env, file)?.to_string()?,
+                _ => return Err("Expected `(data <file> ...)`".to_string()),
+            };
+            let layout = match kwargs.take("layout") {
+                Some(layout) => Layout::parse(&layout.to_string()?)?,
+                None => Layout::Rows,
+            };
+            kwargs.finish()?;
+
+            let path = env.path(&file)?;
+            plan.add_include(&path);
+            let table = load_data(&path, layout)?;
+            Ok(Val::Node(plan.add(NodeSpec::Data(table), None)))
+        },
+        "random-texture" => {
+            let (args, mut kwargs) =
+                keyword_args(plan, env, "random-texture", iter)?;
+            let (width, height) = match args.as_slice() {
+                [width, height] => (
+                    expr(plan, env, width)?.to_nat()? as u32,
+                    expr(plan, env, height)?.to_nat()? as u32,
+                ),
+                _ => {
+                    return Err("Expected `(random-texture <width> <height> \
+                                ...)`"
+                        .to_string())
+                },
+            };
+            let distribution = match kwargs.take("distribution") {
+                Some(distribution) => distribution.to_string()?.parse()?,
+                None => Distribution::Uniform,
+            };
+            // each random texture differs unless seeded alike
+            let earlier = plan
+                .nodes()
+                .filter(|(_, spec)| matches!(spec, NodeSpec::Random(_)))
+                .count();
+            let seed = match kwargs.take("seed") {
+                Some(seed) => seed.to_nat()? as u32,
+                None => earlier as u32,
+            };
+            let regen_on_reload = match kwargs.take("regen-on-reload") {
+                Some(regen) => regen.to_bool()?,
+                None => false,
+            };
+            kwargs.finish()?;
+
+            let random = RandomTexture {
+                width,
+                height,
+                distribution,
+                seed,
+                regen_on_reload,
+            };
+            Ok(Val::Node(plan.add(NodeSpec::Random(random), None)))
+        },
+        "compute" => {
+            let (args, mut kwargs) = keyword_args(plan, env, "compute", iter)?;
+            let (name, width, height, inputs) = match args.as_slice() {
+                [name, width, height, inputs @ ..] => (
+                    expr(plan, env, name)?.to_string()?,
+                    expr(plan, env, width)?.to_nat()? as u32,
+                    expr(plan, env, height)?.to_nat()? as u32,
+                    inputs,
+                ),
+                _ => {
+                    return Err("Expected `(compute <name> <width> <height> \
+                                ...)`"
+                        .to_string())
+                },
+            };
+            let inputs = inputs
+                .iter()
+                .map(|input| expr(plan, env, input)?.to_node())
+                .collect::<Result<Vec<_>, _>>()?;
+            let name = name.strip_suffix(".comp").unwrap_or(&name).to_string();
+            // by default, one 8 by 8 workgroup per 8 by 8 pixels
+            let groups = match kwargs.take("groups") {
+                Some(groups) => workgroups(&groups)?,
+                None => [width.div_ceil(8), height.div_ceil(8), 1],
+            };
+            let count =
+                kwargs.take("buffer").map(|c| c.to_nat()).transpose()?;
+            let glsl = glsl_version(&mut kwargs)?;
+            let fallback = fallback(env, &mut kwargs, &inputs, width, height)?;
+            kwargs.finish()?;
+
+            let spec = NodeSpec::Compute {
+                source: env.shader_as(&name, Stage::Compute)?,
+                inputs,
+                width,
+                height,
+                groups,
+                count,
+            };
+            let id = add_node(plan, spec, Some(&name), glsl);
+            with_fallback(plan, id, fallback)
+        },
+        "isf" => {
+            let (name, width, height, images, mut kwargs) =
+                shader(plan, env, function, iter)?;
+            let name = name.strip_suffix(".fs").unwrap_or(&name);
+            let file = format!("{}.fs", name);
+            let isf = Isf::parse(&env.shader_as(&file, Stage::Isf)?)?;
+            let expected = isf.images().count();
+            if images.len() != expected {
+                return Err(format!(
+                    "`{}` takes {} image(s), but was passed {}",
+                    file,
+                    expected,
+                    images.len()
+                ));
+            }
+
+            let glsl = glsl_version(&mut kwargs)?;
+
+            // params start as the shader's defaults, unless set
+            // by keyword, and colors can be set as hex
+            let mut params = isf.params();
+            for (param, value) in params.iter_mut() {
+                *value = match kwargs.take(param) {
+                    Some(Val::Bool(set)) => set as u8 as f32,
+                    Some(set) => set.to_float()? as f32,
+                    None => continue,
+                };
+            }
+            for input in isf.inputs.iter() {
+                let color = match (&input.kind, kwargs.take(&input.name)) {
+                    (InputKind::Color(_), Some(color)) => color.to_string()?,
+                    (_, Some(_)) => {
+                        return Err(format!(
+                            "Set `{}` by its components, e.g. `:{}_x`",
+                            input.name, input.name
+                        ))
+                    },
+                    _ => continue,
+                };
+                let color = parse_color(&color)?;
+                for (suffix, value) in ["r", "g", "b", "a"].iter().zip(color) {
+                    let param = format!("{}_{}", input.name, suffix);
+                    for (name, set) in params.iter_mut() {
+                        if *name == param {
+                            *set = value;
+                        }
+                    }
+                }
+            }
+            kwargs.finish()?;
+
+            // each pass is a node, reading the images, and the
+            // targets of the passes before it
+            let mut inputs = images;
+            let mut samplers = isf
+                .images()
+                .enumerate()
+                .map(|(i, image)| {
+                    (image.name.as_str(), format!("u_texture_{}", i))
+                })
+                .collect::<Vec<_>>();
+            let mut id = None;
+            for (index, pass) in isf.passes.iter().enumerate() {
+                let (width, height) = isf.size(index, width, height)?;
+                let mut bound = samplers.clone();
+                if let (true, Some(target)) = (pass.persistent, &pass.target) {
+                    bound.push((target, "u_previous".to_string()));
+                }
+                let mut texture = TextureOptions::default();
+                if pass.float {
+                    texture.format = Format::Rgba32f;
+                }
+                let spec = NodeSpec::Shader {
+                    source: isf.source(index, &bound),
+                    inputs: inputs.clone(),
+                    width,
+                    height,
+                    recurrent: pass.persistent,
+                    init: None,
+                    texture,
+                };
+                let node = add_node(plan, spec, Some(&file), glsl.clone());
+                for (param, value) in params.iter() {
+                    plan.set_param(node, param, *value);
+                }
+                if let Some(target) = &pass.target {
+                    let uniform = format!("u_texture_{}", inputs.len());
+                    samplers.push((target, uniform));
+                    inputs.push(node);
+                }
+                id = Some(node);
+            }
+            // unwrap: every shader has at least one pass
+            Ok(Val::Node(id.unwrap()))
+        },
+        "audio" => {
+            let (args, mut kwargs) = keyword_args(plan, env, "audio", iter)?;
+            if !args.is_empty() {
+                return Err("Expected `(audio :device <device>)`".into());
+            }
+            let device = match kwargs.take("device") {
+                Some(device) => Some(env.device(device.to_string()?)?),
+                None => None,
+            };
+            let delay = delay(&mut kwargs)?;
+            kwargs.finish()?;
+            let spec = NodeSpec::Audio { device, delay };
+            Ok(Val::Node(plan.add(spec, None)))
+        },
+        "prev-output" => {
+            if iter.next().is_some() {
+                return Err("Expected `(prev-output)`".into());
+            }
+            // there is only one, however often it's used
+            let existing = plan
+                .nodes()
+                .find(|(_, spec)| matches!(spec, NodeSpec::PreviousOutput));
+            Ok(Val::Node(match existing {
+                Some((id, _)) => id,
+                None => plan.add(NodeSpec::PreviousOutput, None),
+            }))
+        },
+        "camera" => {
+            let (args, mut kwargs) = keyword_args(plan, env, "camera", iter)?;
+            let index = match args.as_slice() {
+                [] => 0,
+                [index] => expr(plan, env, index)?.to_nat()?,
+                _ => return Err("Expected `(camera <index> ...)`".into()),
+            };
+            let device = match kwargs.take("device") {
+                Some(device) => env.device(device.to_string()?)?,
+                None => camera::device(index)?,
+            };
+            let mut size = |key, default| match kwargs.take(key) {
+                Some(value) => value.to_nat().map(|n| n as u32),
+                None => Ok(default),
+            };
+            let spec = NodeSpec::Camera {
+                device,
+                width: size("width", 640)?,
+                height: size("height", 480)?,
+                fps: size("fps", 30)?,
+                delay: delay(&mut kwargs)?,
+            };
+            kwargs.finish()?;
+            Ok(Val::Node(plan.add(spec, None)))
+        },
+        "video" => {
+            let (args, mut kwargs) = keyword_args(plan, env, "video", iter)?;
+            let (file, size) = match args.as_slice() {
+                [file] => (file, None),
+                [file, width, height] => {
+                    let width = expr(plan, env, width)?.to_nat()? as u32;
+                    let height = expr(plan, env, height)?.to_nat()? as u32;
+                    (file, Some((width, height)))
+                },
+                _ => return Err("Expected `(video <file> ...)`".to_string()),
+            };
+            let file = expr(plan, env, file)?.to_string()?;
+            let path = env.path(&file)?;
+            let mut playback = Playback::default();
+            if let Some(looping) = kwargs.take("loop") {
+                playback.looping = looping.to_bool()?;
+            }
+            if let Some(rate) = kwargs.take("rate") {
+                playback.rate = rate.to_float()?;
+            }
+            if let Some(start) = kwargs.take("start") {
+                playback.start = start.to_float()?;
+            }
+            if let Some(fps) = kwargs.take("fps") {
+                playback.fps = fps.to_nat()? as u32;
+            }
+            kwargs.finish()?;
+
+            let spec = NodeSpec::Video {
+                path,
+                size,
+                playback,
+            };
+            Ok(Val::Node(plan.add(spec, None)))
+        },
+        "feed" => feed_node(plan, env, iter),
+        "stats" => {
+            let (args, mut kwargs) = keyword_args(plan, env, "stats", iter)?;
+            let input = match args.as_slice() {
+                [input] => expr(plan, env, input)?.to_node()?,
+                _ => return Err("Expected `(stats <input> ...)`".to_string()),
+            };
+            let bins = match kwargs.take("bins") {
+                Some(bins) => bins.to_nat()?,
+                None => 64,
+            };
+            kwargs.finish()?;
+
+            let spec = NodeSpec::Stats {
+                input,
+                bins: bins as u32,
+            };
+            Ok(Val::Node(plan.add(spec, None)))
+        },
+        "auto-expose" => {
+            let (args, mut kwargs) =
+                keyword_args(plan, env, "auto-expose", iter)?;
+            let input = match args.as_slice() {
+                [input] => expr(plan, env, input)?.to_node()?,
+                _ => return Err("Expected `(auto-expose <input> ...)`".into()),
+            };
+            let mut float = |name, default| match kwargs.take(name) {
+                Some(val) => val.to_float().map(|f| f as f32),
+                None => Ok(default),
+            };
+            let speed = float("speed", 1.5)?;
+            let key = float("key", 0.18)?;
+            kwargs.finish()?;
+
+            let spec = NodeSpec::AutoExpose { input, speed, key };
+            Ok(Val::Node(plan.add(spec, None)))
+        },
+        "flow" => {
+            let (args, mut kwargs) = keyword_args(plan, env, "flow", iter)?;
+            let input = match args.as_slice() {
+                [input] => expr(plan, env, input)?.to_node()?,
+                _ => return Err("Expected `(flow <input> ...)`".to_string()),
+            };
+            let window = match kwargs.take("window") {
+                Some(window) => window.to_nat()?,
+                None => 3,
+            };
+            kwargs.finish()?;
+
+            let spec = NodeSpec::Flow {
+                input,
+                window: window as u32,
+            };
+            Ok(Val::Node(plan.add(spec, None)))
+        },
+        "reproject" => {
+            let (args, mut kwargs) =
+                keyword_args(plan, env, "reproject", iter)?;
+            let (input, velocity) = match args.as_slice() {
+                [input, velocity] => (
+                    expr(plan, env, input)?.to_node()?,
+                    expr(plan, env, velocity)?.to_node()?,
+                ),
+                _ => {
+                    return Err(
+                        "Expected `(reproject <input> <velocity> ...)`".into()
+                    )
+                },
+            };
+            let blend = match kwargs.take("blend") {
+                Some(blend) => blend.to_float()? as f32,
+                None => 0.9,
+            };
+            let clamp = match kwargs.take("clamp") {
+                Some(clamp) => clamp.to_bool()?,
+                None => false,
+            };
+            kwargs.finish()?;
+
+            let spec = NodeSpec::Reproject {
+                input,
+                velocity,
+                blend,
+                clamp,
+            };
+            Ok(Val::Node(plan.add(spec, None)))
+        },
+        "fluid" => {
+            let (args, mut kwargs) = keyword_args(plan, env, "fluid", iter)?;
+            let (width, height, inputs) = match args.as_slice() {
+                [width, height, inputs @ ..] => (
+                    expr(plan, env, width)?.to_nat()?,
+                    expr(plan, env, height)?.to_nat()?,
+                    inputs,
+                ),
+                _ => {
+                    return Err(
+                        "Expected `(fluid <width> <height> ...)`".to_string()
+                    )
+                },
+            };
+            let inputs = inputs
+                .iter()
+                .map(|input| expr(plan, env, input)?.to_node())
+                .collect::<Result<Vec<_>, _>>()?;
+            let iterations = match kwargs.take("iterations") {
+                Some(iterations) => iterations.to_nat()?,
+                None => 20,
+            };
+            let dissipation = match kwargs.take("dissipation") {
+                Some(dissipation) => dissipation.to_float()? as f32,
+                None => 0.99,
+            };
+            let warmup = kwargs.take("warmup").map(|w| w.to_nat());
+            let substeps = kwargs.take("substeps").map(|s| s.to_nat());
+            let (width, height) = (width as u32, height as u32);
+            let fallback = fallback(env, &mut kwargs, &inputs, width, height)?;
+            kwargs.finish()?;
+
+            let spec = NodeSpec::Fluid {
+                inputs,
+                width,
+                height,
+                iterations: iterations as u32,
+                dissipation,
+            };
+            let id = plan.add(spec, None);
+            if let Some(frames) = warmup.transpose()? {
+                plan.warm_up_node(id, frames as u32);
+            }
+            if let Some(substeps) = substeps.transpose()? {
+                plan.set_substeps(id, substeps as u32);
+            }
+            with_fallback(plan, id, fallback)
         },
         "extern" => {
-            let (name, inputs) = external(graph, env, iter)?;
-            let adder = env.external(&name)?;
-            let node_id = adder(graph, &inputs).map_err(|e| {
-                format!("While adding external function `{}`: {}", name, e)
-            })?;
-            Ok(Val::Node(node_id))
+            let (name, inputs) = external(plan, env, iter)?;
+            let spec = NodeSpec::Extern { name, inputs };
+            Ok(Val::Node(plan.add(spec, None)))
         },
         user_defined => {
             // evaluate the arguments (pass by value)
             let mut args = vec![];
             for arg in iter {
-                args.push(expr(graph, env, arg)?);
+                args.push(expr(plan, env, arg)?);
             }
 
             if let Some(val) = builtin(user_defined, &args) {
@@ -302,7 +2063,14 @@ fn node(
             }
 
             // get the function
-            let (params, body) = env.get_fn(user_defined)?.clone();
+            let (params, body) = match env.get_fn(user_defined) {
+                Ok(function) => function.clone(),
+                Err(_) => {
+                    let error = format!("Unknown function `{}`", user_defined);
+                    let known = FORMS.iter().copied().chain(env.fn_names());
+                    return Err(suggest(error, user_defined, known));
+                },
+            };
 
             // check things match up before calling
             if params.len() != args.len() {
@@ -315,8 +2083,7 @@ fn node(
             }
 
             // evaluate in new scope, declare arguments
-            // recursive definitions will bap the stack, so please don't
-            // write them
+            env.enter_call(user_defined)?;
             env.enter_scope();
             for (name, val) in params.iter().zip(args.iter()) {
                 env.set(name.to_string(), val.to_owned())
@@ -327,18 +2094,105 @@ fn node(
             let last = body.last().unwrap();
             let declarations = &body[..body.len() - 1];
             for declaration in declarations {
-                declare(graph, env, declaration)?;
+                declare(plan, env, declaration)?;
             }
 
             // TODO: multiple returns how?
             // last value must be an expression, return it
-            let ret = expr(graph, env, last)?.to_node()?;
+            let ret = expr(plan, env, last)?.to_node()?;
             env.exit_scope();
+            env.exit_call();
             Ok(Val::Node(ret))
         },
     }
 }
 
+/// Parses `(feed <url> :every <secs> :throttle <secs>
+/// <mappings...>)`, where each mapping is either
+/// `(field <name> <path>)` or `(row <path>)`.
+#[cfg(feature = "feed")]
+fn feed_node(
+    plan: &mut Plan,
+    env: &mut Env,
+    iter: lexpr::cons::ListIter<'_>,
+) -> Result<Val, String> {
+    use std::time::Duration;
+
+    use crate::feed::Mapping;
+
+    let (args, mut kwargs) = keyword_args(plan, env, "feed", iter)?;
+    let (url, forms) = match args.split_first() {
+        Some((url, forms)) => (expr(plan, env, url)?.to_string()?, forms),
+        None => return Err("Expected `(feed <url> ...)`".to_string()),
+    };
+    // `Duration::from_secs_f64` panics on anything else
+    let seconds = |key: &str, val: Val| {
+        let secs = val.to_float()?;
+        if secs.is_finite() && secs > 0.0 {
+            Ok(secs)
+        } else {
+            Err(format!(
+                "A feed's `:{}` must be a positive number of seconds, \
+                 found {}",
+                key, secs
+            ))
+        }
+    };
+    let every = match kwargs.take("every") {
+        Some(every) => seconds("every", every)?,
+        None => 1.0,
+    };
+    // none by default
+    let throttle = match kwargs.take("throttle") {
+        Some(throttle) => seconds("throttle", throttle)?,
+        None => 0.0,
+    };
+    kwargs.finish()?;
+
+    let mut mapping = Mapping::default();
+    for form in forms {
+        let mut iter = into_iter(form)?;
+        match next_symbol(&mut iter)? {
+            "field" => {
+                let name =
+                    expr(plan, env, next_item(&mut iter)?)?.to_string()?;
+                let path =
+                    expr(plan, env, next_item(&mut iter)?)?.to_string()?;
+                mapping.fields.push((name, path));
+            },
+            "row" => {
+                let path =
+                    expr(plan, env, next_item(&mut iter)?)?.to_string()?;
+                mapping.rows.push(path);
+            },
+            other => {
+                return Err(format!(
+                    "Expected `field` or `row` in feed, found `{}`",
+                    other
+                ))
+            },
+        }
+        iter_finish(iter)?;
+    }
+
+    let spec = NodeSpec::Feed {
+        url,
+        every: Duration::from_secs_f64(every),
+        throttle: Duration::from_secs_f64(throttle),
+        mapping,
+    };
+    Ok(Val::Node(plan.add(spec, None)))
+}
+
+#[cfg(not(feature = "feed"))]
+fn feed_node(
+    _plan: &mut Plan,
+    _env: &mut Env,
+    _iter: lexpr::cons::ListIter<'_>,
+) -> Result<Val, String> {
+    Err("shadergarden was built without the `feed` feature".to_string())
+}
+
 fn builtin(name: &str, args: &[Val]) -> Option<Result<Val, String>> {
     let result: fn(Vec<f64>) -> Val = match name {
         "+" => |n| Val::Number(n.into_iter().sum()),
@@ -372,7 +2226,7 @@ fn builtin(name: &str, args: &[Val]) -> Option<Result<Val, String>> {
 }
 
 fn subst(
-    graph: &mut ShaderGraph,
+    plan: &mut Plan,
     env: &mut Env,
     form: &Value,
     source: String,
@@ -383,17 +2237,17 @@ fn subst(
     let (name, subst) = match op {
         "define" => {
             let name =
-                expr(graph, env, next_item(&mut subst_iter)?)?.to_string()?;
+                expr(plan, env, next_item(&mut subst_iter)?)?.to_string()?;
             let val =
-                expr(graph, env, next_item(&mut subst_iter)?)?.to_string()?;
+                expr(plan, env, next_item(&mut subst_iter)?)?.to_string()?;
             let subst = format!("#define {} {}", name, val);
             (name, subst)
         },
         "ifdef" => {
             let name =
-                expr(graph, env, next_item(&mut subst_iter)?)?.to_string()?;
+                expr(plan, env, next_item(&mut subst_iter)?)?.to_string()?;
             let should_define =
-                expr(graph, env, next_item(&mut subst_iter)?)?.to_bool()?;
+                expr(plan, env, next_item(&mut subst_iter)?)?.to_bool()?;
             if should_define {
                 (name.clone(), format!("#define {} 1", name))
             } else {
@@ -406,5 +2260,206 @@ fn subst(
     };
 
     iter_finish(subst_iter)?;
-    return Ok(source.replace(&format!("<{}>", name), &subst));
+    Ok(source.replace(&format!("<{}>", name), &subst))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Plans a graph from lisp, with every shader it names
+    /// from `shaders`, by name and source.
+    pub(crate) fn plan(
+        lisp: &str,
+        shaders: &[(&str, &str)],
+    ) -> Result<Plan, String> {
+        plan_with_limits(lisp, shaders, &Limits::none())
+    }
+
+    pub(crate) fn plan_with_limits(
+        lisp: &str,
+        shaders: &[(&str, &str)],
+        limits: &Limits,
+    ) -> Result<Plan, String> {
+        let shaders = shaders
+            .iter()
+            .map(|(name, source)| (name.to_string(), source.to_string()))
+            .collect();
+        plan_from_sexp_with_limits(ShaderDir::new(lisp.into(), shaders), limits)
+    }
+
+    const BLEND: (&str, &str) = ("blend", "void main() {}");
+
+    /// Nodes by index, as ids can't be made out of the
+    /// graph module.
+    fn indices(ids: &[NodeId]) -> Vec<usize> {
+        ids.iter().map(|id| id.index()).collect()
+    }
+
+    #[test]
+    fn plans_inputs_outputs_and_names() {
+        let plan = plan(
+            "(input image)
+             (let edges (shader \"blend\" 64 32 image))
+             (let out (shader \"blend\" 64 32 edges image))
+             (output out)",
+            &[BLEND],
+        )
+        .unwrap();
+        let topology = plan.topology();
+        assert_eq!(topology.len(), 3);
+        assert_eq!(indices(topology.get_inputs()), [0]);
+        assert_eq!(indices(topology.get_outputs()), [2]);
+        let edges = topology.node_id("edges").unwrap();
+        let out = topology.node_id("out").unwrap();
+        assert_eq!(edges.index(), 1);
+        assert_eq!(indices(topology.node_inputs(out)), [1, 0]);
+        assert!(matches!(
+            plan.nodes().nth(1),
+            Some((_, NodeSpec::Shader {
+                width: 64,
+                height: 32,
+                recurrent: false,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn refuses_forms_removed_since_the_declared_version() {
+        let graph = "(let out (shader \"blend\" 8 8))\n(output out)\n";
+        let old = format!("(version 1)\n(uniform \"frame\" int)\n{}", graph);
+        let error = plan(&old, &[BLEND]).unwrap_err();
+        assert!(
+            error.starts_with(
+                "line 2: Declaring `frame` was removed in version 2, as \
+                 `u_frame` is built in as a int; remove the declaration"
+            ),
+            "{}",
+            error
+        );
+        assert!(error.contains("shadergarden migrate"));
+        assert!(plan(&format!("(version 1)\n{}", graph), &[BLEND]).is_ok());
+        // read as current, so refused as any declaration of
+        // a built-in uniform is
+        let error = plan(&old.replace("(version 1)\n", ""), &[BLEND]);
+        assert!(error.unwrap_err().contains("it is built in"));
+    }
+
+    #[test]
+    fn pastes_repeats_and_functions() {
+        let plan = plan(
+            "(define (sharpen image iter)
+                 (let out image)
+                 (repeat iter (let out (shader \"blend\" 8 8 out)))
+                 out)
+             (let seed (shader \"blend\" 8 8))
+             (let sharpened (sharpen seed 3))
+             (output sharpened)",
+            &[BLEND],
+        )
+        .unwrap();
+        let topology = plan.topology();
+        assert_eq!(topology.len(), 4);
+        assert_eq!(indices(topology.get_outputs()), [3]);
+        let inputs = plan
+            .nodes()
+            .map(|(id, _)| indices(topology.node_inputs(id)))
+            .collect::<Vec<_>>();
+        assert_eq!(inputs, [vec![], vec![0], vec![1], vec![2]]);
+    }
+
+    #[test]
+    fn orders_nodes() {
+        let plan = plan(
+            "(let trail (shader-rec \"blend\" 8 8))
+             (let sparks (shader \"blend\" 8 8))
+             (order sparks :before trail)
+             (output trail)",
+            &[BLEND],
+        )
+        .unwrap();
+        assert_eq!(indices(plan.topology().evaluation_order()), [1, 0]);
+    }
+
+    #[test]
+    fn refuses_invalid_graphs() {
+        let error = |lisp| plan(lisp, &[BLEND]).unwrap_err();
+        assert!(error("(let a (shader \"missing\" 8 8)) (output a)")
+            .contains("`missing`"));
+        assert!(error("(let a (shader \"blend\" 8 8 b)) (output a)")
+            .contains("`b` is not defined"));
+        assert!(error(
+            "(let a (shader \"blend\" 8 8))
+             (let b (shader \"blend\" 8 8 a))
+             (order b :before a)
+             (output b)"
+        )
+        .contains("cycle"));
+        assert!(error("(shader \"blend\" 8 8)").contains("name it"));
+    }
+
+    #[test]
+    fn stops_at_limits() {
+        let limits = Limits {
+            nodes: Some(2),
+            iterations: Some(2),
+            ..Limits::none()
+        };
+        let repeated = "(let a (shader \"blend\" 8 8))
+                        (repeat 3 (let a (shader \"blend\" 8 8 a)))
+                        (output a)";
+        let error = plan_with_limits(repeated, &[BLEND], &limits);
+        assert!(error.unwrap_err().contains("Repeating 3 times"));
+        let many = "(let a (shader \"blend\" 8 8))
+                    (let b (shader \"blend\" 8 8))
+                    (let c (shader \"blend\" 8 8))
+                    (output c)";
+        let error = plan_with_limits(many, &[BLEND], &limits);
+        assert!(error.unwrap_err().contains("more than 2 nodes"));
+    }
+
+    #[test]
+    fn counts_nested_repeats_in_all() {
+        let limits = Limits {
+            iterations: Some(4),
+            ..Limits::none()
+        };
+        let nested = |times| {
+            format!(
+                "(let a (shader \"blend\" 8 8))
+                 (repeat {} (repeat 2 (let a (shader \"blend\" 8 8 a))))
+                 (output a)",
+                times
+            )
+        };
+        // the outer repeat once, and the inner one twice
+        assert!(plan_with_limits(&nested(1), &[BLEND], &limits).is_ok());
+        let error = plan_with_limits(&nested(2), &[BLEND], &limits);
+        assert!(error.unwrap_err().contains("in all, 5 times"));
+    }
+
+    #[test]
+    fn stops_recursive_functions() {
+        let recursive = "(defnode blur (x) (blur (shader \"blend\" 8 8 x)))
+                         (let a (blur (shader \"blend\" 8 8)))
+                         (output a)";
+        let error = plan(recursive, &[BLEND]).unwrap_err();
+        assert!(error.contains("does it call itself?"), "{}", error);
+    }
+
+    #[cfg(feature = "feed")]
+    #[test]
+    fn refuses_feeds_that_would_never_wait() {
+        let feed = |keyword: &str| {
+            let lisp = format!(
+                "(let a (feed \"http://localhost\" {} (row \"a\")))",
+                keyword
+            );
+            plan(&lisp, &[]).unwrap_err()
+        };
+        assert!(feed(":every 0").contains("`:every` must be"));
+        assert!(feed(":every -1").contains("`:every` must be"));
+        assert!(feed(":throttle 0").contains("`:throttle` must be"));
+    }
 }
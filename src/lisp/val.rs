@@ -53,6 +53,9 @@ impl Val {
     pub fn to_bool(&self) -> Result<bool, String> {
         match self {
             Val::Bool(b) => Ok(*b),
+            // keywords take `true` and `false` as well
+            Val::String(s) if s == "true" => Ok(true),
+            Val::String(s) if s == "false" => Ok(false),
             other => Err(format!(
                 "Type mismatch: expected a Boolean, found `{:?}`",
                 other
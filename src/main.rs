@@ -1,33 +1,182 @@
 use std::{
-    collections::BTreeMap,
+    collections::{
+        BTreeMap,
+        BTreeSet,
+    },
     ffi::OsStr,
     fs,
-    path::PathBuf,
+    path::{
+        Path,
+        PathBuf,
+    },
+    rc::Rc,
+    sync::Mutex,
     time::{
         Duration,
         Instant,
+        SystemTime,
+        UNIX_EPOCH,
     },
 };
 
 use glium::{
-    backend::Facade,
+    backend::{
+        Context,
+        Facade,
+    },
     glutin::{
+        dpi::PhysicalPosition,
         event::{
+            ElementState,
             Event,
+            KeyboardInput,
+            ModifiersState,
+            MouseButton,
+            MouseScrollDelta,
+            VirtualKeyCode,
             WindowEvent,
         },
         event_loop::ControlFlow,
+        window::Fullscreen,
     },
+    uniforms::MagnifySamplerFilter,
     Surface,
 };
+#[cfg(target_os = "linux")]
+use shadergarden::dmabuf::DmabufExporter;
+#[cfg(feature = "ndi")]
+use shadergarden::ndi::NdiSender;
+#[cfg(target_os = "linux")]
+use shadergarden::pipewire::PipewireSource;
 use shadergarden::{
+    audio,
+    camera::{
+        self,
+        Camera,
+    },
+    canvas::{
+        Canvas,
+        Tile,
+    },
+    capabilities::{
+        Capabilities,
+        GlVersion,
+    },
+    checkpoint::{
+        Checkpoint,
+        Progress,
+    },
+    config::Config,
+    control::{
+        Control,
+        Requests,
+        Status,
+    },
+    devices,
+    diagnostic::{
+        self,
+        BuildError,
+        Diagnostic,
+        MessageFormat,
+    },
+    exr::{
+        self,
+        FloatImage,
+        Layer,
+    },
+    framelock::FrameLock,
+    graph::{
+        Baked,
+        Bundle,
+        Limits,
+        ShaderGraph,
+        Sources,
+    },
+    keyboard::{
+        key_code,
+        Keyboard,
+    },
+    keymap::{
+        Action,
+        Keymap,
+    },
+    latency::{
+        self,
+        Flashes,
+    },
+    lisp::{
+        migrate::migrate_graph,
+        plan_from_sexp_with_options,
+        BuildOptions,
+    },
+    map,
+    meta::Meta,
+    pacing::FramePacer,
     png,
-    reload,
+    present::{
+        Accumulator,
+        Compare,
+        Crossfade,
+        Dither,
+        DitherPattern,
+        History,
+        Interpolator,
+        Limiter,
+        Plots,
+        Presenter,
+        Reference,
+        Rotation,
+        Upscale,
+        View,
+    },
+    provenance::Provenance,
+    quality::QualityController,
+    record::{
+        RecordFormat,
+        Recorder,
+    },
+    reload::{
+        self,
+        watcher::ShaderGraphWatcher,
+        Archive,
+        PostChain,
+        Projects,
+        ShaderDir,
+    },
+    schedule::{
+        Period,
+        Schedule,
+        Sleep,
+        TimeOfDay,
+    },
+    shard::{
+        self,
+        Shard,
+    },
+    sink::{
+        NodeWindows,
+        OutputSink,
+        SinkSpec,
+        Sinks,
+    },
+    ssim::{
+        Image,
+        Ssim,
+    },
+    sync::{
+        SyncFollower,
+        SyncHost,
+        SyncMessage,
+    },
+    thumbnail::Thumbnail,
+    timing::TimingLog,
     util,
-    reload::watcher::ShaderGraphWatcher
 };
 use structopt::{
-    clap::AppSettings,
+    clap::{
+        AppSettings,
+        Shell,
+    },
     StructOpt,
 };
 
@@ -52,6 +201,468 @@ pub fn handle_event(event: Event<()>, control_flow: &mut ControlFlow) {
     }
 }
 
+/// Returns the key that was just pressed, if any.
+pub fn pressed_key(event: &Event<()>) -> Option<VirtualKeyCode> {
+    match event {
+        Event::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(key),
+                            ..
+                        },
+                    ..
+                },
+            ..
+        } => Some(*key),
+        _ => None,
+    }
+}
+
+/// Returns the key that was just pressed or released, and
+/// whether it's now down, if any.
+pub fn key_changed(event: &Event<()>) -> Option<(VirtualKeyCode, bool)> {
+    match event {
+        Event::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state,
+                            virtual_keycode: Some(key),
+                            ..
+                        },
+                    ..
+                },
+            ..
+        } => Some((*key, *state == ElementState::Pressed)),
+        _ => None,
+    }
+}
+
+/// Returns where the cursor moved to, if it moved.
+pub fn cursor_moved(event: &Event<()>) -> Option<PhysicalPosition<f64>> {
+    match event {
+        Event::WindowEvent {
+            event: WindowEvent::CursorMoved { position, .. },
+            ..
+        } => Some(*position),
+        _ => None,
+    }
+}
+
+/// Returns whether the left mouse button was just pressed.
+pub fn clicked(event: &Event<()>) -> bool {
+    matches!(event, Event::WindowEvent {
+        event: WindowEvent::MouseInput {
+            state: ElementState::Pressed,
+            button: MouseButton::Left,
+            ..
+        },
+        ..
+    })
+}
+
+/// Returns `true` when the right or middle mouse button,
+/// which drag the view, was just pressed, and `false` when
+/// it was just released.
+pub fn dragged(event: &Event<()>) -> Option<bool> {
+    match event {
+        Event::WindowEvent {
+            event:
+                WindowEvent::MouseInput {
+                    state,
+                    button: MouseButton::Right | MouseButton::Middle,
+                    ..
+                },
+            ..
+        } => Some(*state == ElementState::Pressed),
+        _ => None,
+    }
+}
+
+/// Returns `true` when the left mouse button, which drags
+/// the wipe over a reference image, was just pressed, and
+/// `false` when it was just released.
+pub fn wiped(event: &Event<()>) -> Option<bool> {
+    match event {
+        Event::WindowEvent {
+            event:
+                WindowEvent::MouseInput {
+                    state,
+                    button: MouseButton::Left,
+                    ..
+                },
+            ..
+        } => Some(*state == ElementState::Pressed),
+        _ => None,
+    }
+}
+
+/// Returns how far the mouse wheel was just scrolled up, in
+/// lines.
+pub fn scrolled(event: &Event<()>) -> Option<f64> {
+    match event {
+        Event::WindowEvent {
+            event: WindowEvent::MouseWheel { delta, .. },
+            ..
+        } => Some(match delta {
+            MouseScrollDelta::LineDelta(_, lines) => *lines as f64,
+            // roughly a line's worth of pixels on touchpads
+            MouseScrollDelta::PixelDelta(position) => position.y / 20.0,
+        }),
+        _ => None,
+    }
+}
+
+/// Writes the lockfile of a project from the files its
+/// graph was just built from, see `--lock`.
+fn write_lock(project: &Path, files: Vec<PathBuf>) {
+    match reload::lock::write(project, files) {
+        Ok(path) => eprintln!("[info] Locked `{}`", path.display()),
+        Err(e) => eprintln!("[warn] {}", e),
+    }
+}
+
+/// Renders a project's thumbnail from a graph built just
+/// for it, see `--thumbnail`.
+fn write_thumbnail<F: Facade>(
+    facade: &F,
+    project: &Path,
+    lisp_config: &Path,
+    options: &BuildOptions,
+) {
+    let options = BuildOptions {
+        error_screen: false,
+        background: false,
+        ..options.clone()
+    };
+    let written = ShaderGraphWatcher::build_initial(
+        facade.get_context(),
+        project,
+        lisp_config,
+        &options,
+    )
+    .and_then(|mut graph| Thumbnail::default().save(&mut graph, project));
+    match written {
+        Ok(path) => {
+            eprintln!("[info] Wrote a thumbnail to `{}`", path.display())
+        },
+        Err(e) => eprintln!("[warn] Could not write a thumbnail: {}", e),
+    }
+}
+
+/// Seconds since the unix epoch, for naming files.
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Waits for a recording to be written, and reports how it
+/// went.
+fn finish_recording(recorder: Recorder) {
+    let path = recorder.path().to_path_buf();
+    match recorder.finish() {
+        Ok(stats) => eprintln!(
+            "[info] Recorded {} frames to `{}`, {} dropped",
+            stats.frames,
+            path.display(),
+            stats.dropped,
+        ),
+        Err(e) => {
+            eprintln!("[warn] Could not record `{}`: {}", path.display(), e)
+        },
+    }
+}
+
+/// Describes the pixel of a texture under the cursor, where
+/// the texture is rotated, zoomed, and stretched to fill a
+/// window.
+fn pick(
+    texture: &glium::Texture2d,
+    window: (u32, u32),
+    rotation: Rotation,
+    view: View,
+    cursor: PhysicalPosition<f64>,
+) -> Option<String> {
+    let (width, height) = texture.dimensions();
+    let (u, v) = rotation
+        .unrotate(cursor.x / window.0 as f64, cursor.y / window.1 as f64);
+    let (u, v) = view.apply(u, v);
+    if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+        return None;
+    }
+
+    // the window counts down from the top, textures count up
+    let x = (u * width as f64) as u32;
+    let y = height - 1 - (v * height as f64) as u32;
+    let [r, g, b, a] = util::read_pixel(texture, x, y);
+    Some(format!(
+        "({}, {}) rgba {:.4} {:.4} {:.4} {:.4}",
+        x, y, r, g, b, a
+    ))
+}
+
+/// Returns whether a screensaver should exit: on any key or
+/// mouse button, or once the cursor has moved a little.
+fn screensaver_input(
+    event: &Event<()>,
+    cursor: &mut Option<PhysicalPosition<f64>>,
+) -> bool {
+    match event {
+        Event::WindowEvent {
+            event: WindowEvent::KeyboardInput { .. },
+            ..
+        }
+        | Event::WindowEvent {
+            event: WindowEvent::MouseInput { .. },
+            ..
+        } => true,
+        Event::WindowEvent {
+            event: WindowEvent::CursorMoved { position, .. },
+            ..
+        } => {
+            // the first position is wherever the cursor was
+            // when the screensaver started
+            let start = *cursor.get_or_insert(*position);
+            let (dx, dy) = (position.x - start.x, position.y - start.y);
+            dx * dx + dy * dy > 100.0
+        },
+        _ => false,
+    }
+}
+
+/// Windows runs screensavers as `<name>.scr /s` to show
+/// them, `/c` to configure them, and `/p <hwnd>` to preview
+/// them. Returns the arguments to show the screensaver
+/// with, if this was run as one, using the project next to
+/// the executable. Only `/s` is supported; the preview
+/// would need drawing into the settings' own window.
+fn screensaver_args() -> Option<Run> {
+    let exe = std::env::current_exe().ok()?;
+    if exe.extension()? != "scr" {
+        return None;
+    }
+    let project = exe.parent()?.to_path_buf();
+
+    let mode = std::env::args().nth(1).unwrap_or_default().to_lowercase();
+    if !mode.starts_with("/s") {
+        // there are no settings, and drawing into the
+        // preview window is not supported
+        std::process::exit(0);
+    }
+
+    Some(Run::from_iter(vec![
+        OsStr::new("shadergarden"),
+        OsStr::new("--screensaver"),
+        project.as_os_str(),
+    ]))
+}
+
+/// Returns the name of the nth named node, counting from 1,
+/// in the order nodes were added. Inputs are skipped, as
+/// they can't be bypassed.
+fn named_node(
+    graph: &shadergarden::graph::ShaderGraph,
+    index: usize,
+) -> Option<String> {
+    let mut names: Vec<_> = graph
+        .node_names()
+        .filter(|(_, id)| !graph.get_inputs().contains(id))
+        .collect();
+    names.sort_by_key(|(_, id)| *id);
+    names
+        .get(index.checked_sub(1)?)
+        .map(|(name, _)| name.to_string())
+}
+
+/// Restores the state of feedback nodes from a snapshot,
+/// logging how many were restored.
+fn load_graph_state(graph: &mut shadergarden::graph::ShaderGraph, path: &Path) {
+    match graph.load_state(path) {
+        Ok(loaded) => eprintln!(
+            "[info] Restored the state of {} node(s) from `{}`",
+            loaded,
+            path.display()
+        ),
+        Err(e) => eprintln!("[warn] {}", e),
+    }
+}
+
+/// How many times larger a graph must be rendered for its
+/// output to be at least as large as every sink asks for,
+/// at least 1, so it renders once at the largest size
+/// needed, and smaller sinks are scaled down from it.
+fn sink_scale<'a>(
+    output: (u32, u32),
+    sinks: impl IntoIterator<Item = &'a SinkSpec>,
+) -> f32 {
+    let (width, height) = (output.0.max(1) as f32, output.1.max(1) as f32);
+    sinks
+        .into_iter()
+        .filter_map(|sink| sink.size)
+        .fold(1.0, |scale: f32, (w, h)| {
+            scale.max(w as f32 / width).max(h as f32 / height)
+        })
+}
+
+/// Pins the graph's build seed if one was given, and logs
+/// it either way, so a variation can be seen again.
+fn seed_graph(graph: &mut shadergarden::graph::ShaderGraph, seed: Option<u32>) {
+    if let Some(seed) = seed {
+        graph.set_build_seed(seed);
+    }
+    eprintln!("[info] Build seed {}", graph.build_seed());
+}
+
+/// The shaders last built and the project they're from,
+/// written out if the process panics, see `dump_on_panic`.
+static CRASH_SOURCES: Mutex<Option<(PathBuf, Sources)>> = Mutex::new(None);
+
+/// Keeps a copy of the shaders a graph is running, to be
+/// written out if the process panics.
+fn keep_sources(project: &Path, graph: &ShaderGraph) {
+    if let Ok(mut kept) = CRASH_SOURCES.lock() {
+        *kept = Some((project.to_path_buf(), graph.sources()));
+    }
+}
+
+/// Writes the shaders last kept with `keep_sources` to
+/// `.shadergarden/crash-<unix time>/` in their project when
+/// the process panics, so exactly what was running can be
+/// recovered, even if the files have changed since.
+fn dump_on_panic() {
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        hook(info);
+        // try: the panic may have happened holding the lock
+        let kept = match CRASH_SOURCES.try_lock() {
+            Ok(kept) => kept,
+            Err(_) => return,
+        };
+        if let Some((project, sources)) = kept.as_ref() {
+            let dir = project
+                .join(".shadergarden")
+                .join(format!("crash-{}", unix_time()));
+            match sources.write(&dir) {
+                Ok(n) => eprintln!(
+                    "[info] Wrote the {} shaders that were running to `{}`",
+                    n,
+                    dir.display()
+                ),
+                Err(e) => eprintln!(
+                    "[warn] Could not write the shaders that were running: {}",
+                    e
+                ),
+            }
+        }
+    }));
+}
+
+/// How far the seek keys move the graph's clock.
+const SEEK_SECONDS: f32 = 1.0;
+
+/// How often `--profile` prints each node's GPU time.
+const PROFILE_EVERY: Duration = Duration::from_secs(5);
+
+/// Pauses or resumes the graph, without counting the time
+/// spent paused.
+fn set_paused(
+    graph: &mut shadergarden::graph::ShaderGraph,
+    paused: &mut bool,
+    pause: bool,
+) {
+    match (*paused, pause) {
+        (true, false) => {
+            graph.resume();
+            eprintln!("[info] Resumed");
+        },
+        (false, true) => {
+            graph.pause();
+            eprintln!("[info] Paused");
+        },
+        _ => (),
+    }
+    *paused = pause;
+}
+
+/// Toggles whether a named node is bypassed.
+fn toggle_bypass(
+    graph: &mut shadergarden::graph::ShaderGraph,
+    bypassed: &mut BTreeSet<String>,
+    index: usize,
+) {
+    let name = match named_node(graph, index) {
+        Some(name) => name,
+        None => return,
+    };
+
+    let enabled = bypassed.contains(&name);
+    match graph.set_node_enabled(&name, enabled) {
+        Ok(()) if enabled => {
+            bypassed.remove(&name);
+            eprintln!("[info] Enabled `{}`", name);
+        },
+        Ok(()) => {
+            bypassed.insert(name.clone());
+            eprintln!("[info] Bypassed `{}`", name);
+        },
+        Err(e) => eprintln!("[warn] {}", e),
+    }
+}
+
+/// Solos a named node, or stops soloing if it is already
+/// soloed.
+fn toggle_solo(
+    graph: &mut shadergarden::graph::ShaderGraph,
+    soloed: &mut Option<String>,
+    index: usize,
+) {
+    let name = match named_node(graph, index) {
+        Some(name) if soloed.as_ref() != Some(&name) => name,
+        Some(_) => return unsolo(graph, soloed),
+        None => return,
+    };
+
+    match graph.set_solo(Some(&name)) {
+        Ok(()) => {
+            eprintln!("[info] Soloing `{}`", name);
+            *soloed = Some(name);
+        },
+        Err(e) => eprintln!("[warn] {}", e),
+    }
+}
+
+fn unsolo(
+    graph: &mut shadergarden::graph::ShaderGraph,
+    soloed: &mut Option<String>,
+) {
+    if soloed.take().is_some() {
+        let _ = graph.set_solo(None);
+        eprintln!("[info] Stopped soloing");
+    }
+}
+
+/// Uses the keymap passed on the command line, or the
+/// project's `keymap.lisp` if it has one.
+fn keymap(path: Option<&PathBuf>, project: &Path) -> Keymap {
+    let default = project.join("keymap.lisp");
+    let path = match path {
+        Some(path) => path,
+        None if default.exists() => &default,
+        None => return Keymap::default(),
+    };
+
+    Keymap::load(path).unwrap_or_else(|e| {
+        eprintln!("[fatal] {}", e);
+        panic!();
+    })
+}
+
 pub fn dir(path: &OsStr) -> PathBuf {
     if path == "." {
         std::env::current_dir().expect("Can not determine package directory")
@@ -70,46 +681,1185 @@ pub fn package_dir(path: &OsStr) -> PathBuf {
 
 #[derive(StructOpt, Debug)]
 struct New {
+    /// Directory to create the project in, which must be
+    /// empty
     #[structopt(default_value = ".", parse(from_os_str = package_dir))]
-    project: PathBuf,
+    project:   PathBuf,
+    /// Imports a Shadertoy shader instead of the example:
+    /// JSON from the Shadertoy API, or a lone mainImage
+    #[structopt(long)]
+    shadertoy: Option<PathBuf>,
+    /// Width of the imported shader's passes
+    #[structopt(long, default_value = "512")]
+    width:     u32,
+    /// Height of the imported shader's passes
+    #[structopt(long, default_value = "512")]
+    height:    u32,
 }
 
 #[derive(StructOpt, Debug)]
-struct Run {
+struct Check {
+    /// Project directory, or the name of a project in one
+    /// of the favorite directories
+    #[structopt(default_value = ".", parse(from_os_str = package_dir))]
+    project:       PathBuf,
+    /// Graph to build instead of the project's
+    /// shader.graph
+    #[structopt(short, long)]
+    graph:         Option<PathBuf>,
+    /// Prints the result as JSON, for editors and build
+    /// tools
+    #[structopt(long)]
+    json:          bool,
+    /// Writes the graph to a GraphViz file, with each
+    /// node's size and the format of each edge
+    #[structopt(long, parse(from_os_str))]
+    dot:           Option<PathBuf>,
+    /// Exits with an error if the graph has any issues,
+    /// like nodes no output uses
+    #[structopt(long)]
+    strict:        bool,
+    /// Lets the graph read files outside the project
+    /// directory. Only for projects you trust
+    #[structopt(long)]
+    allow_outside: bool,
+    /// Builds the graph with one of the profiles it
+    /// declares
+    #[structopt(long)]
+    build_profile: Option<String>,
+    /// Compiles the shaders in software with OSMesa, which
+    /// is also used when there is no display to open a
+    /// window on, e.g. in CI
+    #[structopt(long)]
+    software:      bool,
+    /// Only evaluates the graph into a plan, finding its
+    /// shaders and checking its limits without compiling
+    /// them, which needs no GPU or OSMesa at all. Also used
+    /// when no context can be created
+    #[structopt(long)]
+    plan_only:     bool,
+}
+
+#[derive(StructOpt, Debug)]
+struct Freeze {
+    /// Project directory, or the name of a project in one
+    /// of the favorite directories
+    #[structopt(default_value = ".", parse(from_os_str = package_dir))]
+    project:       PathBuf,
+    /// Graph to build instead of the project's
+    /// shader.graph
+    #[structopt(short, long)]
+    graph:         Option<PathBuf>,
+    /// File to write the bundle to
+    #[structopt(short, long, default_value = "garden.sgb")]
+    output:        PathBuf,
+    /// Freezes the graph as built with one of the profiles
+    /// it declares, e.g. final
+    #[structopt(long)]
+    build_profile: Option<String>,
+    /// Compiles the shaders in software with OSMesa. Its
+    /// program binaries are only reused by OSMesa
+    #[structopt(long)]
+    software:      bool,
+}
+
+#[derive(StructOpt, Debug)]
+struct Bake {
+    /// Project directory, or the name of a project in one
+    /// of the favorite directories
+    #[structopt(default_value = ".", parse(from_os_str = package_dir))]
+    project:       PathBuf,
+    /// Graph to build instead of the project's
+    /// shader.graph
+    #[structopt(short, long)]
+    graph:         Option<PathBuf>,
+    /// Bakes the graph as built with one of the profiles
+    /// it declares, which it must then be run with
+    #[structopt(long)]
+    build_profile: Option<String>,
+    /// Scales the size of every node, as it will be run
+    #[structopt(long, default_value = "1.0")]
+    render_scale:  f32,
+    /// Renders in software with OSMesa
+    #[structopt(long)]
+    software:      bool,
+}
+
+#[derive(StructOpt, Debug)]
+struct Latency {
+    /// Times a camera, by index, pointed at the window,
+    /// which flashes
+    #[structopt(long)]
+    camera:        Option<usize>,
+    /// Times a camera given as an ffmpeg input, e.g.
+    /// v4l2:/dev/video2, in place of --camera
+    #[structopt(long)]
+    camera_device: Option<String>,
+    /// Times sound, playing clicks and capturing them
+    #[structopt(long)]
+    audio:         bool,
+    /// ffmpeg input to capture the clicks from, e.g.
+    /// pulse:default, in place of the default input
+    #[structopt(long)]
+    audio_device:  Option<String>,
+    /// ffmpeg output to play the clicks to, in place of the
+    /// default output
+    #[structopt(long)]
+    output_device: Option<String>,
+    /// How many flashes or clicks to time
+    #[structopt(long, default_value = "10")]
+    trials:        usize,
+}
+
+#[derive(StructOpt, Debug)]
+struct Devices {
+    /// Prints the devices as JSON, for editors and tools
+    #[structopt(long)]
+    json: bool,
+}
+
+#[derive(StructOpt, Debug)]
+struct Migrate {
+    /// Project directory, or the name of a project in one
+    /// of the favorite directories
     #[structopt(default_value = ".", parse(from_os_str = package_dir))]
     project: PathBuf,
+    /// Graph to migrate instead of the project's
+    /// shader.graph
     #[structopt(short, long)]
     graph:   Option<PathBuf>,
+    /// Only reports what would be rewritten, and exits with
+    /// an error if anything would be
+    #[structopt(long)]
+    check:   bool,
+}
+
+#[derive(StructOpt, Debug)]
+struct Examples {
+    #[structopt(subcommand)]
+    command: Option<ExamplesCommand>,
+}
+
+#[derive(StructOpt, Debug)]
+enum ExamplesCommand {
+    /// Writes one of the examples into a directory, to run
+    /// and change
+    Init {
+        /// Name of the example, as listed by `shadergarden
+        /// examples`
+        name:    String,
+        /// Directory to write it into, which must be empty.
+        /// Defaults to one named after the example
+        #[structopt(parse(from_os_str))]
+        project: Option<PathBuf>,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+struct Play {
+    /// Bundle written by `shadergarden freeze`
+    bundle:       PathBuf,
+    /// Width of the window
     #[structopt(short, long, default_value = "512")]
-    width:   u32,
+    width:        u32,
+    /// Height of the window
     #[structopt(short, long, default_value = "512")]
-    height:  u32,
+    height:       u32,
+    /// Covers the monitor the window opens on
+    #[structopt(long)]
+    fullscreen:   bool,
+    /// Waits for the display to refresh before presenting
+    /// each frame
+    #[structopt(long)]
+    vsync:        bool,
+    /// Maximum frames per second
+    #[structopt(long, default_value = "60")]
+    max_fps:      f64,
+    /// Scales the size of every node in the graph
+    #[structopt(long, default_value = "1.0")]
+    render_scale: f32,
+}
+
+#[derive(StructOpt, Debug)]
+struct Completions {
+    /// bash, zsh, fish, powershell, or elvish
+    #[structopt(possible_values = &Shell::variants())]
+    shell: Shell,
+}
+
+#[derive(StructOpt, Debug)]
+struct Run {
+    /// Project directory, the name of a project in one of
+    /// the favorite directories, or a link to a .tar.gz or
+    /// git repository to download and run
+    #[structopt(default_value = ".", parse(from_os_str = package_dir))]
+    project:          PathBuf,
+    /// Graph to build instead of the project's
+    /// shader.graph, or - to read it from stdin
+    #[structopt(short, long)]
+    graph:            Option<PathBuf>,
+    /// Width of the output. Defaults to 512
     #[structopt(short, long)]
-    inputs:  Vec<PathBuf>,
+    width:            Option<u32>,
+    /// Height of the output. Defaults to 512
+    #[structopt(short, long)]
+    height:           Option<u32>,
+    /// Images and videos passed to the graph's inputs, in
+    /// order
+    #[structopt(short, long)]
+    inputs:           Vec<PathBuf>,
+    /// Megabytes of input video uploaded per frame
+    #[structopt(long, default_value = "16")]
+    #[cfg_attr(not(feature = "ffmpeg"), allow(dead_code))]
+    upload_budget:    usize,
+    /// File to log late and dropped frames to
+    #[structopt(long)]
+    pacing_log:       Option<PathBuf>,
+    /// Scales the size of every node in the graph
+    #[structopt(long, default_value = "1.0")]
+    render_scale:     f32,
+    /// Builds the graph with one of the profiles it
+    /// declares, e.g. dev or final
+    #[structopt(long)]
+    build_profile:    Option<String>,
+    /// Runs every node, instead of fixing the nodes baked
+    /// with `shadergarden bake` to their images
+    #[structopt(long)]
+    unbaked:          bool,
+    /// Runs without cameras, audio, feeds, plugins, or
+    /// controls, which are black, silent, or left at their
+    /// defaults, e.g. to preview a graph on a machine
+    /// without its devices
+    #[structopt(long)]
+    safe:             bool,
+    /// How the output is scaled to fit the window:
+    /// nearest, bilinear, or fsr
+    #[structopt(long, default_value = "nearest")]
+    upscale:          Upscale,
+    /// Turns the output clockwise as it is drawn to the
+    /// window: 0, 90, 180, or 270 degrees
+    #[structopt(long, default_value = "0")]
+    rotate:           Rotation,
+    /// Lowers u_quality when frames take too long to
+    /// render at this frame rate, and raises it again
+    /// when there is room
+    #[structopt(long)]
+    target_fps:       Option<f64>,
+    /// File of key bindings, reloaded when changed
+    #[structopt(long)]
+    keymap:           Option<PathBuf>,
+    /// Window title
+    #[structopt(long)]
+    title:            Option<String>,
+    /// Hides the window's title bar and borders
+    #[structopt(long)]
+    no_decorations:   bool,
+    /// Shows the desktop through the window wherever the
+    /// output is transparent
+    #[structopt(long)]
+    transparent:      bool,
+    /// Keeps the window above all others
+    #[structopt(long)]
+    always_on_top:    bool,
+    /// Lets the mouse through to the windows below, e.g.
+    /// for overlay art (X11 only)
+    #[structopt(long)]
+    click_through:    bool,
+    /// Renders behind the desktop icons, covering the
+    /// primary monitor (X11 only)
+    #[structopt(long)]
+    wallpaper:        bool,
+    /// Runs fullscreen without a cursor, and exits on
+    /// any input
+    #[structopt(long)]
+    screensaver:      bool,
+    /// Caps the brightness of the output, from 0 to 1
+    #[structopt(long)]
+    max_luminance:    Option<f32>,
+    /// Limits how quickly the output can get brighter or
+    /// darker, in changes of brightness per second, to
+    /// keep it from flashing
+    #[structopt(long)]
+    flash_limit:      Option<f32>,
+    /// Quantizes the output for low bit depth displays,
+    /// dithering with an ordered or blue-noise pattern
+    #[structopt(long)]
+    dither:           Option<DitherPattern>,
+    /// Bits per channel of the display, when dithering
+    #[structopt(long, default_value = "8")]
+    dither_bits:      u32,
+    /// Raises the output to this power before it is
+    /// quantized, e.g. 2.2 for linearly driven LEDs
+    #[structopt(long)]
+    output_gamma:     Option<f32>,
+    /// Checks every node for NaN and infinite pixels,
+    /// logging where they first appear and highlighting
+    /// them in the output
+    #[structopt(long)]
+    validate:         bool,
+    /// Image to compare the output with, e.g. a target look
+    /// or a previous render
+    #[structopt(long)]
+    reference:        Option<PathBuf>,
+    /// How the output is compared with the reference:
+    /// wipe, or difference
+    #[structopt(long, default_value = "wipe")]
+    compare:          Compare,
+    /// Plots a float uniform over the last frames, e.g. a
+    /// control or stats_mean, over the top left of the
+    /// output. Repeat for several, Shift+F7 hides them
+    #[structopt(long, number_of_values = 1)]
+    plot:             Vec<String>,
+    /// Keeps the state of named feedback nodes when the
+    /// graph is rebuilt, so simulations carry on
+    #[structopt(long)]
+    keep_state:       bool,
+    /// Keeps u_time and u_frame running when the graph is
+    /// rebuilt, rather than starting them over
+    #[structopt(long)]
+    keep_clock:       bool,
+    /// Passes this as u_build_seed rather than a random
+    /// seed, to see a logged variation again
+    #[structopt(long)]
+    seed:             Option<u32>,
+    /// Shows build errors in the window in place of the
+    /// output, until they are fixed
+    #[structopt(long)]
+    error_screen:     bool,
+    /// Rebuilds the graph in the background when it
+    /// changes, compiling a few shaders each frame, so the
+    /// window keeps running until the new graph is ready
+    #[structopt(long)]
+    background_build: bool,
+    /// Lets the graph read files outside the project
+    /// directory, e.g. includes shared between projects.
+    /// Only for projects you trust
+    #[structopt(long)]
+    allow_outside:    bool,
+    /// Refuses to build graphs with more nodes than this,
+    /// e.g. on a kiosk running graphs from strangers
+    #[structopt(long)]
+    max_nodes:        Option<usize>,
+    /// Refuses to build graphs whose textures would take
+    /// more than this many megabytes
+    #[structopt(long)]
+    max_memory:       Option<u64>,
+    /// Refuses to build graphs with a node wider or taller
+    /// than this, in pixels
+    #[structopt(long)]
+    max_resolution:   Option<u32>,
+    /// Refuses to build graphs that repeat anything more
+    /// times than this: repeat loops, repeated shaders,
+    /// fluid solvers, substeps, and warm-up frames
+    #[structopt(long)]
+    max_iterations:   Option<u32>,
+    /// Compiles every shader, rather than linking programs
+    /// cached by earlier runs
+    #[structopt(long)]
+    no_program_cache: bool,
+    /// Restores feedback nodes from a snapshot saved with
+    /// the `save-state` command
+    #[structopt(long)]
+    load_state:       Option<PathBuf>,
+    /// Labels each node's draws and textures for GPU
+    /// debuggers like RenderDoc, apitrace, and Nsight
+    #[structopt(long)]
+    gl_debug:         bool,
+    /// Times each node on the GPU, and prints the slowest
+    /// every few seconds
+    #[structopt(long)]
+    profile:          bool,
+    /// Writes each node's GPU time every frame to a CSV
+    /// file, with a row per node
+    #[structopt(long)]
+    profile_csv:      Option<PathBuf>,
+    /// Stops the slowest node whenever a frame takes more
+    /// than this many milliseconds on the GPU, so a runaway
+    /// shader can't freeze the machine
+    #[structopt(long)]
+    gpu_timeout:      Option<f64>,
+    /// Also writes the output to a video file, a directory
+    /// of png frames, shared memory, or frame hashes while
+    /// running, as -o does for render. Repeat for
+    /// several, end with @<n> to keep every nth frame,
+    /// e.g. preview.mp4@4, and with @<width>x<height>
+    /// to write it at another size
+    #[structopt(long, number_of_values = 1)]
+    sink:             Vec<SinkSpec>,
+    /// Shows a named node in a window of its own, besides
+    /// the output. Repeat for several
+    #[structopt(long, number_of_values = 1)]
+    window:           Vec<String>,
+    /// Publishes the output over NDI, as a source with this
+    /// name, or a named node, written <source>=<node>.
+    /// Repeat for several
+    #[structopt(long, number_of_values = 1)]
+    ndi:              Vec<String>,
+    /// Shares the output as DMA-BUFs with clients of a Unix
+    /// socket at this path, or a named node, written
+    /// <socket>=<node>. Linux only, needs EGL. Repeat for
+    /// several
+    #[structopt(long, number_of_values = 1)]
+    dmabuf:           Vec<String>,
+    /// Publishes the output as a PipeWire video source with
+    /// this name, or a named node, written <source>=<node>.
+    /// Linux only. Repeat for several
+    #[structopt(long, number_of_values = 1)]
+    pipewire:         Vec<String>,
+    /// Plays a named node as sound: the bottom row is the
+    /// waveform of each frame, red left and green right.
+    /// Experimental
+    #[structopt(long)]
+    sonify:           Option<String>,
+    /// Device to play --sonify to, as an ffmpeg format and
+    /// device, e.g. pulse:default
+    #[structopt(long, requires = "sonify")]
+    sonify_device:    Option<String>,
+    /// File listing project directories to show in turn,
+    /// one per line, rebuilding each when it comes up
+    #[structopt(long, conflicts_with = "graph")]
+    playlist:         Option<PathBuf>,
+    /// Moves on to the next project this often, e.g. 120s
+    /// or 5m, with a playlist or a root of sub-projects
+    #[structopt(long)]
+    cycle:            Option<Period>,
+    /// How long to crossfade between projects, or 0 to cut
+    #[structopt(long, default_value = "1s")]
+    crossfade:        Period,
+    /// Writes shader.lock in the project, with a hash of
+    /// every file the graph is built from, whenever it
+    /// builds
+    #[structopt(long, conflicts_with = "verify")]
+    lock:             bool,
+    /// Refuses to run if any file the graph is built from
+    /// differs from shader.lock in the project, and stops
+    /// rebuilding the graph when files change
+    #[structopt(long, conflicts_with = "follow")]
+    verify:           bool,
+    /// SHA-1 that a downloaded archive must have, or the
+    /// full 40-character commit of a repository to run
+    #[structopt(long)]
+    checksum:         Option<String>,
+    /// Downloads the project again, rather than running
+    /// the copy downloaded before
+    #[structopt(long)]
+    refresh:          bool,
+    /// Renders a thumbnail of the output, one second in,
+    /// to .shadergarden/thumb.png in the project whenever
+    /// the graph builds
+    #[structopt(long, conflicts_with = "follow")]
+    thumbnail:        bool,
+    /// Time of day to start rendering, e.g. 09:00
+    #[structopt(long, requires = "off")]
+    on:               Option<TimeOfDay>,
+    /// Time of day to stop rendering and blank the
+    /// window, e.g. 22:00
+    #[structopt(long, requires = "on")]
+    off:              Option<TimeOfDay>,
+    /// Caps the frame rate, to limit CPU and GPU usage.
+    /// Defaults to 60, or 30 in wallpaper mode
+    #[structopt(long)]
+    max_fps:          Option<f64>,
+    /// Waits for the display to refresh before presenting
+    /// each frame: true or false
+    #[structopt(long)]
+    vsync:            Option<bool>,
+    /// Version of OpenGL to ask for, e.g. 4.3 or es3.0.
+    /// Falls back to the latest supported version
+    #[structopt(long)]
+    gl:               Option<GlVersion>,
+    /// Runs the graph once every this many frames, and
+    /// synthesizes the frames between from optical flow
+    #[structopt(long)]
+    interpolate:      Option<u32>,
+    /// Keeps this many past output frames on the GPU, to
+    /// freeze and step back through with F3 and the arrow
+    /// keys
+    #[structopt(long)]
+    history:          Option<usize>,
+    /// Skips the post chain in the user's config directory
+    #[structopt(long)]
+    no_post:          bool,
+    /// How build errors are printed: human, short for
+    /// `file:line:column: error: message`, or json
+    #[structopt(long, default_value = "human")]
+    message_format:   MessageFormat,
+    /// Named pipe to read commands from while running, one
+    /// per line, or - for stdin
+    #[structopt(long)]
+    control:          Option<PathBuf>,
+    /// Address to accept commands on over TCP, e.g.
+    /// 127.0.0.1:7777. Anyone who can reach it can control
+    /// this instance, and write screenshots and state
+    /// inside the project directory, so bind to
+    /// 127.0.0.1 unless the network is trusted
+    #[structopt(long)]
+    listen:           Option<String>,
+    /// UDP port to receive OSC on, for the graph's
+    /// controls, e.g. 9000
+    #[structopt(long)]
+    osc:              Option<u16>,
+    /// MIDI device to read the graph's controls from, e.g.
+    /// /dev/snd/midiC1D0
+    #[structopt(long)]
+    midi:             Option<PathBuf>,
+    /// Records the output losslessly from the start:
+    /// prores, ffv1, or pngseq. The record key starts
+    /// and stops recording, as pngseq if this is not
+    /// given
+    #[structopt(long)]
+    record:           Option<RecordFormat>,
+    /// Directory to write recordings to
+    #[structopt(long, default_value = "recordings")]
+    record_dir:       PathBuf,
+    /// Directory to keep a snapshot of the project in
+    /// every time it is rebuilt
+    #[structopt(long)]
+    archive:          Option<PathBuf>,
+    /// Address to send the project and uniforms to
+    /// followers from, e.g. 0.0.0.0:7778
+    #[structopt(long, conflicts_with = "follow")]
+    host:             Option<String>,
+    /// Address of a host to run the project of, instead
+    /// of the project on disk
+    #[structopt(long)]
+    follow:           Option<String>,
+    /// Address to lead a frame lock from, keeping the
+    /// machines that join it rendering in step
+    #[structopt(long, requires = "lock-peers", conflicts_with = "lock-join")]
+    lock_lead:        Option<String>,
+    /// Number of other machines to wait for before the
+    /// frame lock starts
+    #[structopt(long)]
+    lock_peers:       Option<usize>,
+    /// Address of a frame lock to join
+    #[structopt(long)]
+    lock_join:        Option<String>,
+    /// Size of a larger canvas that the output is one tile
+    /// of, e.g. 7680x2160
+    #[structopt(long)]
+    canvas:           Option<Canvas>,
+    /// Region of the canvas to render, e.g.
+    /// 1920x2160+3840+0. Sets the default output size
+    #[structopt(long, requires = "canvas")]
+    tile:             Option<Tile>,
+}
+
+impl Run {
+    fn window_options(&self, title: &str) -> util::WindowOptions {
+        util::WindowOptions {
+            title:         self
+                .title
+                .clone()
+                .unwrap_or_else(|| title.to_string()),
+            decorations:   !self.no_decorations,
+            transparent:   self.transparent,
+            always_on_top: self.always_on_top,
+            click_through: self.click_through,
+            fullscreen:    self.screensaver,
+            wallpaper:     self.wallpaper,
+            vsync:         self.vsync.unwrap_or(false),
+            visible:       true,
+            gl:            self.gl,
+        }
+    }
+
+    /// Fills in flags that weren't passed from the user's
+    /// config, and looks for the project in the favorite
+    /// directories if it isn't found, or downloads it if
+    /// it's a link.
+    fn with_config(mut self, config: &Config) -> Run {
+        self.width = self.width.or(config.width);
+        self.height = self.height.or(config.height);
+        self.vsync = self.vsync.or(config.vsync);
+        self.keymap = self.keymap.or_else(|| config.keymap.clone());
+        let url = self.project.to_string_lossy().to_string();
+        if !reload::is_url(&url) {
+            self.project = config.find_project(&self.project);
+            return self;
+        }
+
+        self.project =
+            reload::fetch(&url, self.checksum.as_deref(), self.refresh)
+                .unwrap_or_else(|e| {
+                    eprintln!("[fatal] Could not download `{}`: {}", url, e);
+                    std::process::exit(1);
+                });
+        eprintln!("[info] Running `{}` from `{}`", url, self.project.display());
+        // a project shared with its lockfile runs as locked
+        if !self.lock && self.project.join(reload::LOCK_FILE).is_file() {
+            self.verify = true;
+        }
+        self
+    }
+
+    /// Caches the programs compiled, and links programs
+    /// cached by earlier runs, unless told not to.
+    fn cache_programs(&self) {
+        if !self.no_program_cache {
+            util::binary::set_cache_dir(util::binary::default_cache_dir());
+        }
+    }
+
+    /// Reports the GPU time of each node, if asked to.
+    fn timing_log(&self) -> Option<TimingLog> {
+        if !self.profile && self.profile_csv.is_none() {
+            return None;
+        }
+        let every = Some(PROFILE_EVERY).filter(|_| self.profile);
+        let log = TimingLog::new(every);
+        match &self.profile_csv {
+            Some(path) => match log.csv_to(path) {
+                Ok(log) => Some(log),
+                Err(e) => {
+                    eprintln!("[warn] {}", e);
+                    None
+                },
+            },
+            None => Some(log),
+        }
+    }
+
+    fn width(&self) -> u32 {
+        self.width.or(self.tile.map(|t| t.width)).unwrap_or(512)
+    }
+
+    fn height(&self) -> u32 {
+        self.height.or(self.tile.map(|t| t.height)).unwrap_or(512)
+    }
+
+    /// The canvas and the tile of it to render, which is
+    /// the whole output unless `--canvas` is given.
+    fn canvas(&self) -> (Canvas, Tile) {
+        let canvas = self.canvas.unwrap_or(Canvas {
+            width:  self.width(),
+            height: self.height(),
+        });
+        let tile = self.tile.unwrap_or_else(|| Tile::whole(canvas));
+        if let Err(e) = tile.check(canvas) {
+            eprintln!("[fatal] {}", e);
+            panic!();
+        }
+        (canvas, tile)
+    }
+
+    /// Builds the presenter, with a limiter if any limits
+    /// are set.
+    fn presenter<F: Facade>(&self, facade: &F) -> Presenter {
+        let mut presenter = Presenter::new(facade, self.upscale).unwrap();
+        if self.max_luminance.is_some() || self.flash_limit.is_some() {
+            let limiter = Limiter::new(
+                facade.get_context(),
+                self.max_luminance.unwrap_or(1.0),
+                self.flash_limit.unwrap_or(f32::INFINITY),
+            );
+            presenter.limiter = Some(limiter.unwrap());
+        }
+        if self.dither.is_some() || self.output_gamma.is_some() {
+            let dither = Dither::new(
+                facade.get_context(),
+                self.dither,
+                self.dither_bits,
+                self.output_gamma.unwrap_or(1.0),
+            );
+            presenter.dither = Some(dither.unwrap());
+        }
+        if !self.plot.is_empty() {
+            let plots = Plots::new(facade.get_context(), &self.plot);
+            presenter.plots = Some(plots.unwrap());
+        }
+        presenter.highlight_invalid = self.validate;
+        presenter.rotation = self.rotate;
+        presenter.premultiply = self.transparent;
+        if let Some(path) = &self.reference {
+            match Reference::load(facade.get_context(), path, self.compare) {
+                Ok(reference) => presenter.reference = Some(reference),
+                Err(e) => {
+                    eprintln!("[fatal] {}", e);
+                    panic!();
+                },
+            }
+        }
+        presenter
+    }
+
+    /// The size of the window, which is turned on its side
+    /// if the output is.
+    fn window_size(&self) -> (f64, f64) {
+        let (width, height) = (self.width() as f64, self.height() as f64);
+        if self.rotate.is_sideways() {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// What graphs are allowed to ask for, see
+    /// [`Limits::check`].
+    fn limits(&self) -> Limits {
+        Limits {
+            nodes:      self.max_nodes,
+            memory:     self.max_memory.map(|mb| mb * 1024 * 1024),
+            resolution: self.max_resolution,
+            iterations: self.max_iterations,
+        }
+    }
+
+    /// Loads the user's post chain, unless it is disabled.
+    fn post_chain<F: Facade>(
+        &self,
+        facade: &F,
+        options: &BuildOptions,
+    ) -> Option<PostChain> {
+        if self.no_post {
+            return None;
+        }
+        let config = reload::config_dir()?.join("post.graph");
+
+        let size = (self.width(), self.height());
+        match PostChain::load(
+            facade.get_context(),
+            &config,
+            size,
+            options.clone(),
+        ) {
+            Ok(Some(post)) => {
+                eprintln!("[info] Applying post chain `{}`", config.display());
+                Some(post)
+            },
+            Ok(None) => None,
+            Err(e) => {
+                eprintln!("[fatal] Could not build post chain:");
+                eprintln!("{}", e);
+                panic!();
+            },
+        }
+    }
+
+    fn max_fps(&self) -> f64 {
+        let default = if self.wallpaper { 30.0 } else { 60.0 };
+        self.max_fps.unwrap_or(default)
+    }
+
+    fn build_options(&self) -> BuildOptions {
+        BuildOptions {
+            render_scale:  self.render_scale,
+            validate:      self.validate,
+            keep_state:    self.keep_state,
+            keep_clock:    self.keep_clock,
+            error_screen:  self.error_screen,
+            background:    self.background_build,
+            allow_outside: self.allow_outside,
+            limits:        self.limits(),
+            profile:       self.build_profile.clone(),
+            baked:         !self.unbaked,
+            safe:          self.safe,
+        }
+    }
+
+    /// Warns about flags that this build or platform can't
+    /// act on.
+    fn warn_unsupported(&self) {
+        if self.screensaver && std::env::var_os("XSCREENSAVER_WINDOW").is_some()
+        {
+            eprintln!("[warn] Drawing into the xscreensaver window is not supported, opening a fullscreen window instead");
+        }
+        #[cfg(not(feature = "knobs"))]
+        if !self.safe && (self.osc.is_some() || self.midi.is_some()) {
+            eprintln!(
+                "[warn] Built without the `knobs` feature, can not read \
+                 controls"
+            );
+        }
+        #[cfg(not(feature = "ndi"))]
+        if !self.ndi.is_empty() {
+            eprintln!(
+                "[warn] Built without the `ndi` feature, can not publish \
+                 over NDI"
+            );
+        }
+        #[cfg(not(target_os = "linux"))]
+        if !self.dmabuf.is_empty() {
+            eprintln!("[warn] DMA-BUFs can only be shared on Linux");
+        }
+        #[cfg(not(target_os = "linux"))]
+        if !self.pipewire.is_empty() {
+            eprintln!("[warn] PipeWire sources can only be published on Linux");
+        }
+    }
+
+    /// Paces frames, logging how late each was if asked to.
+    fn pacer(&self, frame: Duration) -> FramePacer {
+        let pacer = FramePacer::new(frame);
+        match &self.pacing_log {
+            Some(path) => pacer.log_to(path).unwrap_or_else(|e| {
+                eprintln!("[fatal] {}", e);
+                panic!();
+            }),
+            None => pacer,
+        }
+    }
+
+    /// Follows a host, if asked to, see `--follow`.
+    fn follower(&self) -> Option<SyncFollower> {
+        let address = self.follow.as_ref()?;
+        Some(SyncFollower::connect(address).unwrap_or_else(|e| {
+            eprintln!("[fatal] {}", e);
+            panic!();
+        }))
+    }
+
+    /// The projects of a playlist, or of a root listing
+    /// sub-projects, unless a single graph is run.
+    fn projects(
+        &self,
+        context: &Rc<Context>,
+        options: &BuildOptions,
+    ) -> Option<Projects> {
+        let loaded = match (&self.graph, &self.playlist) {
+            (None, Some(path)) => {
+                Projects::playlist(context, path, options.clone()).map(Some)
+            },
+            (None, None) => {
+                Projects::load(context, &self.project, options.clone())
+            },
+            (Some(_), _) => Ok(None),
+        };
+        loaded.unwrap_or_else(|e| {
+            eprintln!("[fatal] {}", e);
+            panic!();
+        })
+    }
+
+    /// Reads commands from `--control` and `--listen`, if
+    /// either is passed. Paths sent over TCP are relative
+    /// to `project`.
+    fn control(&self, project: &Path, lisp_config: &Path) -> Option<Control> {
+        if self.control.is_none() && self.listen.is_none() {
+            return None;
+        }
+        let control = Control::new();
+        if let Some(path) = &self.control {
+            if path == Path::new("-") && lisp_config == Path::new("-") {
+                eprintln!("[fatal] The graph and commands can't both be read from stdin");
+                panic!();
+            }
+            control.read_from(path).unwrap_or_else(|e| {
+                eprintln!("[fatal] {}", e);
+                panic!();
+            });
+        }
+        if let Some(address) = &self.listen {
+            control
+                .listen(address, project, self.allow_outside)
+                .unwrap_or_else(|e| {
+                    eprintln!("[fatal] {}", e);
+                    panic!();
+                });
+            eprintln!("[info] Listening for commands on {}", address);
+        }
+        Some(control)
+    }
+
+    /// Reads controls over OSC and MIDI, unless in safe
+    /// mode.
+    #[cfg(feature = "knobs")]
+    fn knobs(&self) -> Option<shadergarden::knobs::Knobs> {
+        if self.safe || (self.osc.is_none() && self.midi.is_none()) {
+            return None;
+        }
+        let knobs = shadergarden::knobs::Knobs::new();
+        if let Some(port) = self.osc {
+            knobs.listen_osc(port).unwrap_or_else(|e| {
+                eprintln!("[fatal] {}", e);
+                panic!();
+            });
+            eprintln!("[info] Listening for OSC on port {}", port);
+        }
+        if let Some(device) = &self.midi {
+            knobs.listen_midi(device).unwrap_or_else(|e| {
+                eprintln!("[fatal] {}", e);
+                panic!();
+            });
+            eprintln!("[info] Reading MIDI from `{}`", device.display());
+        }
+        Some(knobs)
+    }
+
+    /// Archives the project's sources as it is edited, see
+    /// `--archive`, starting with a snapshot of them now.
+    fn archive(&self) -> Option<Archive> {
+        let dir = self.archive.as_ref()?;
+        let mut archive = Archive::new(&self.project, dir, unix_time())
+            .unwrap_or_else(|e| {
+                eprintln!("[fatal] {}", e);
+                panic!();
+            });
+        archive.exclude(&self.record_dir);
+        eprintln!(
+            "[info] Archiving sources to `{}`",
+            archive.session().display()
+        );
+        if let Err(e) = archive.snapshot(unix_time()) {
+            eprintln!("[warn] {}", e);
+        }
+        Some(archive)
+    }
+
+    /// Hosts followers, if asked to, sending them the
+    /// project as it is now.
+    fn sync_host(
+        &self,
+        project: &Path,
+        lisp_config: &Path,
+    ) -> Option<SyncHost> {
+        let address = self.host.as_ref()?;
+        let host = SyncHost::listen(address).unwrap_or_else(|e| {
+            eprintln!("[fatal] {}", e);
+            panic!();
+        });
+        eprintln!("[info] Hosting followers on {}", address);
+        host.send_project(project, lisp_config, self.allow_outside);
+        Some(host)
+    }
+
+    /// Leads or joins a frame lock, waiting for every
+    /// machine to join when leading.
+    fn frame_lock(&self) -> Option<FrameLock> {
+        let lock = match (&self.lock_lead, &self.lock_join) {
+            (Some(address), _) => {
+                let peers = self.lock_peers.unwrap_or(0);
+                eprintln!(
+                    "[info] Waiting for {} machines to join the frame lock",
+                    peers
+                );
+                FrameLock::lead(address, peers)
+            },
+            (None, Some(address)) => FrameLock::follow(address),
+            (None, None) => return None,
+        };
+        Some(lock.unwrap_or_else(|e| {
+            eprintln!("[fatal] {}", e);
+            panic!();
+        }))
+    }
+
+    /// The hours to run in, see `--on` and `--off`.
+    fn schedule(&self) -> Option<Schedule> {
+        let (on, off) = self.on.zip(self.off)?;
+        Some(Schedule::new(on, off).unwrap_or_else(|e| {
+            eprintln!("[fatal] {}", e);
+            std::process::exit(1);
+        }))
+    }
+
+    #[cfg(feature = "ndi")]
+    fn ndi_senders(&self, max_fps: f64) -> Vec<(Option<String>, NdiSender)> {
+        senders(&self.ndi, |source| {
+            let sender = NdiSender::new(source, max_fps)?;
+            eprintln!("[info] Publishing NDI source `{}`", source);
+            Ok(sender)
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn dmabuf_exporters<F: Facade>(
+        &self,
+        facade: &F,
+    ) -> Vec<(Option<String>, DmabufExporter)> {
+        senders(&self.dmabuf, |socket| {
+            let exporter = DmabufExporter::new(facade, Path::new(socket))?;
+            eprintln!("[info] Sharing DMA-BUFs on `{}`", socket);
+            Ok(exporter)
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn pipewire_sources(
+        &self,
+        max_fps: f64,
+    ) -> Vec<(Option<String>, PipewireSource)> {
+        senders(&self.pipewire, |source| {
+            let pipewire = PipewireSource::new(source, max_fps)?;
+            eprintln!("[info] Publishing PipeWire source `{}`", source);
+            Ok(pipewire)
+        })
+    }
+
+    /// Plays a node as sound, see `--sonify`.
+    fn playback(&self) -> Option<audio::Playback> {
+        let node = self.sonify.as_ref()?;
+        match audio::Playback::start(self.sonify_device.as_deref()) {
+            Ok(playback) => {
+                eprintln!("[info] Playing `{}` as sound", node);
+                Some(playback)
+            },
+            Err(e) => {
+                eprintln!("[warn] {}", e);
+                None
+            },
+        }
+    }
 }
 
 #[derive(StructOpt, Debug)]
 struct Render {
     #[structopt(flatten)]
-    run:    Run,
-    #[structopt(short, long)]
-    output: PathBuf,
-    /// Starting frame
+    run:              Run,
+    /// Directory to write png frames to, or a video file
+    /// to encode with ffmpeg: .mp4, .webm, .gif, .mov, or
+    /// .mkv, shm:<name> for a ring of frames in shared
+    /// memory, gst:<pipeline> to feed a GStreamer
+    /// pipeline, or hash:<file> to write a SHA-1 of each
+    /// frame. Repeat to write several at once, end with
+    /// @<n> to keep every nth frame, e.g. preview.mp4@4,
+    /// and with @<width>x<height> to write it at
+    /// another size
+    #[structopt(short, long, required = true, number_of_values = 1)]
+    output:           Vec<SinkSpec>,
+    /// First frame to write. Earlier frames are still
+    /// rendered in graphs with feedback, so that
+    /// simulations get going
     #[structopt(short, long, default_value = "0")]
-    start:  u64,
-    /// Ending frame
+    start:            u64,
+    /// Frame to stop before
     #[structopt(short, long, default_value = "150")]
-    end:    u64,
+    end:              u64,
+    /// Number of frames to write, in place of --end
+    #[structopt(long, conflicts_with = "end")]
+    frames:           Option<u64>,
+    /// Frames per second of the rendered animation
     #[structopt(long, default_value = "30")]
-    fps:    f64,
+    fps:              f64,
+    /// Renders this many evenly spaced sub-frames per
+    /// frame and averages them, for motion blur
+    #[structopt(long, default_value = "1")]
+    temporal_samples: u32,
+    /// Renders in software with OSMesa, without a GPU or
+    /// display server
+    #[structopt(long)]
+    software:         bool,
+    /// Renders one share of the frames, e.g. 2/8 for the
+    /// second of eight, into outputs of its own, like
+    /// out-part-2-of-8.mp4, put together with `merge`
+    #[structopt(long)]
+    shard:            Option<Shard>,
+    /// Saves the state of feedback nodes after the last
+    /// frame, for the next shard to go on from with
+    /// --load-state
+    #[structopt(long)]
+    save_state:       Option<PathBuf>,
+    /// Directory to save checkpoints of the render in, to
+    /// resume from the last one when run again after being
+    /// interrupted
+    #[structopt(long)]
+    checkpoint:       Option<PathBuf>,
+    /// Frames rendered between checkpoints
+    #[structopt(long, default_value = "900")]
+    checkpoint_every: u64,
+    /// Directory to write a multi-layer EXR to for every
+    /// frame, with the output and every named node as
+    /// layers, for compositing
+    #[structopt(long)]
+    exr:              Option<PathBuf>,
+    /// Named node to write as a layer of --exr, in place of
+    /// every named node. Repeat to write several
+    #[structopt(long = "layer", number_of_values = 1, requires = "exr")]
+    layers:           Vec<String>,
+}
+
+#[derive(StructOpt, Debug)]
+struct Merge {
+    /// Output the shards were rendered to, e.g. out.mp4
+    /// for out-part-1-of-8.mp4 and the rest
+    #[structopt(parse(from_os_str))]
+    output:       PathBuf,
+    /// Deletes the parts once they're merged
+    #[structopt(long)]
+    remove_parts: bool,
+}
+
+#[derive(StructOpt, Debug)]
+struct Diff {
+    #[structopt(flatten)]
+    run:       Run,
+    /// Node to compare, in place of the output
+    #[structopt(long)]
+    node:      Option<String>,
+    /// Node to compare with, in place of --reference
+    #[structopt(long)]
+    against:   Option<String>,
+    /// Frame to compare, rendered at a fixed frame rate
+    #[structopt(long, default_value = "0")]
+    frame:     u64,
+    /// Frames per second to render at
+    #[structopt(long, default_value = "30")]
+    fps:       f64,
+    /// Writes a png that is black where the two match, and
+    /// brighter where they differ
+    #[structopt(long)]
+    heatmap:   Option<PathBuf>,
+    /// Exits with an error when the score is below this,
+    /// e.g. 0.99
+    #[structopt(long)]
+    threshold: Option<f32>,
+    /// Prints the result as JSON, for build tools
+    #[structopt(long)]
+    json:      bool,
+    /// Renders in software with OSMesa, without a GPU or
+    /// display server
+    #[structopt(long)]
+    software:  bool,
 }
 
 #[derive(StructOpt, Debug)]
 #[structopt(name = "Shader Garden", bin_name = "shadergarden", about, global_settings(&[AppSettings::ColoredHelp, AppSettings::DeriveDisplayOrder]))]
 enum Cli {
+    /// Creates a new project from the example garden, or
+    /// from a Shadertoy shader
     New(New),
+    /// Runs a project in a window, rebuilding it whenever
+    /// it changes
     Run(Run),
+    /// Renders a project offline, at a fixed frame rate,
+    /// to a sequence of png frames or a video
     Render(Render),
+    /// Puts the parts of a render split with --shard back
+    /// together
+    Merge(Merge),
+    /// Scores how similar a frame is to a reference image,
+    /// or to another node, with SSIM
+    Diff(Diff),
+    /// Builds a project without running it, and reports
+    /// any errors
+    Check(Check),
+    /// Builds a project once and writes it to a bundle,
+    /// with its shaders compiled, to play without the
+    /// project
+    Freeze(Freeze),
+    /// Renders the nodes of a project that never change
+    /// into images once, which later runs load instead of
+    /// running the nodes
+    Bake(Bake),
+    /// Measures how late a camera or sound arrives, to
+    /// line inputs up with `:delay`
+    Latency(Latency),
+    /// Lists the cameras, sound inputs, MIDI devices,
+    /// monitors, and GPUs found, as they are named to
+    /// shadergarden
+    Devices(Devices),
+    /// Rewrites a project's graph, and the files it
+    /// includes, from the syntax of older releases to the
+    /// current one, keeping comments
+    Migrate(Migrate),
+    /// Lists the example projects built in, or writes one
+    /// into a directory with `examples init <name>`
+    Examples(Examples),
+    /// Plays a bundle in a window, without reading or
+    /// watching a project
+    Play(Play),
+    /// Prints a shell completion script to stdout
+    Completions(Completions),
 }
 
 /// Main function
@@ -117,17 +1867,747 @@ enum Cli {
 /// window. If changes are detected, we rebuild the shader
 /// graph and swap it out.
 fn main() {
+    let config = Config::load().unwrap_or_else(|e| {
+        eprintln!("[warn] {}", e);
+        Config::default()
+    });
+
+    if let Some(run_args) = screensaver_args() {
+        return run(run_args.with_config(&config));
+    }
+
     // parse arguments and extract
     let args = Cli::from_args();
 
-    match args {
-        Cli::Run(r) => run(r),
-        Cli::Render(r) => render(r),
-        Cli::New(n) => new(n.project),
+    match args {
+        Cli::Run(r) => run(r.with_config(&config)),
+        Cli::Render(r) => render(Render {
+            run: r.run.with_config(&config),
+            ..r
+        }),
+        Cli::Merge(m) => merge(m),
+        Cli::Diff(d) => diff(Diff {
+            run: d.run.with_config(&config),
+            ..d
+        }),
+        Cli::New(n) => new(n),
+        Cli::Check(c) => check(c, &config),
+        Cli::Freeze(f) => freeze(f, &config),
+        Cli::Bake(b) => bake(b, &config),
+        Cli::Latency(l) => measure_latency(l),
+        Cli::Devices(d) => list_devices(d),
+        Cli::Migrate(m) => migrate(m, &config),
+        Cli::Examples(e) => examples(e),
+        Cli::Play(p) => play(p),
+        Cli::Completions(c) => completions(c.shell),
+    }
+}
+
+fn check(args: Check, config: &Config) {
+    let project = config.find_project(&args.project);
+    let lisp_config = args
+        .graph
+        .clone()
+        .unwrap_or_else(|| project.join("shader.graph"));
+
+    // shaders can only be compiled with a context, so build
+    // the graph in a window that is never shown, or in
+    // software where there is nowhere to open one, or only
+    // plan it where neither can be done
+    let mut _window = None;
+    let mut _software = None;
+    let context = if args.plan_only {
+        return check_plan(&args, &project, &lisp_config);
+    } else if args.software || !util::has_display() {
+        match util::create_software(1, 1) {
+            Ok(renderer) => _software.insert(renderer).get_context().clone(),
+            Err(e) => {
+                eprintln!("[warn] {}, so only checking the plan", e);
+                return check_plan(&args, &project, &lisp_config);
+            },
+        }
+    } else {
+        let mut window_options =
+            util::WindowOptions::new("Shader Garden".into());
+        window_options.visible = false;
+        let window = util::create_with(&window_options, 1.0, 1.0);
+        _window.insert(window).1.get_context().clone()
+    };
+
+    let started = Instant::now();
+    let result = ShaderGraphWatcher::build_initial(
+        &context,
+        &project,
+        &lisp_config,
+        &BuildOptions {
+            allow_outside: args.allow_outside,
+            profile: args.build_profile.clone(),
+            ..BuildOptions::default()
+        },
+    )
+    .map_err(|e| BuildError::new(e, &project, &lisp_config));
+    let build_ms = started.elapsed().as_secs_f64() * 1000.0;
+    let capabilities = Capabilities::detect(&context);
+    let issues = result.as_ref().map_or(vec![], |graph| graph.validate());
+
+    if let (Ok(graph), Some(dot)) = (&result, &args.dot) {
+        if let Err(e) = std::fs::write(dot, graph.to_dot()) {
+            eprintln!("[fatal] Could not write `{}`: {}", dot.display(), e);
+            std::process::exit(1);
+        }
+    }
+
+    if args.json {
+        let report = match &result {
+            Ok(graph) => serde_json::json!({
+                "ok": true,
+                "graph": lisp_config,
+                "build_ms": build_ms,
+                "inputs": graph.get_inputs().len(),
+                "outputs": graph.get_outputs().len(),
+                "nodes": graph.evaluation_order().len(),
+                "meta": graph.meta().to_json(),
+                "issues": issues
+                    .iter()
+                    .map(|issue| issue.to_json(graph.topology()))
+                    .collect::<Vec<_>>(),
+                "capabilities": capabilities.to_json(),
+            }),
+            Err(e) => serde_json::json!({
+                "ok": false,
+                "graph": lisp_config,
+                "build_ms": build_ms,
+                "node": e.node,
+                "errors": e
+                    .diagnostics
+                    .iter()
+                    .map(Diagnostic::to_json)
+                    .collect::<Vec<_>>(),
+                "capabilities": capabilities.to_json(),
+            }),
+        };
+        println!("{}", report);
+    } else {
+        match &result {
+            Ok(graph) => {
+                eprintln!(
+                    "[info] Built `{}` in {:.1}ms",
+                    lisp_config.display(),
+                    build_ms
+                );
+                for issue in issues.iter() {
+                    eprintln!("[warn] {}", issue.describe(graph.topology()));
+                }
+            },
+            Err(e) => {
+                eprintln!(
+                    "[fatal] Could not build `{}`:",
+                    lisp_config.display()
+                );
+                eprintln!("{}", e);
+            },
+        }
+    }
+
+    if result.is_err() || (args.strict && !issues.is_empty()) {
+        std::process::exit(1);
+    }
+}
+
+/// Checks a graph without a context, as far as its plan,
+/// see `ShaderGraphWatcher::plan_initial`. Shaders aren't
+/// compiled, so the checks that need them, like unused
+/// nodes or `--dot`, are skipped.
+fn check_plan(args: &Check, project: &Path, lisp_config: &Path) {
+    let options = BuildOptions {
+        allow_outside: args.allow_outside,
+        profile: args.build_profile.clone(),
+        ..BuildOptions::default()
+    };
+    let started = Instant::now();
+    let result =
+        ShaderGraphWatcher::plan_initial(project, lisp_config, &options)
+            .and_then(|(plan, _)| {
+                options.limits.check(&plan, options.render_scale)?;
+                Ok(plan)
+            })
+            .map_err(|e| BuildError::new(e, project, lisp_config));
+    let build_ms = started.elapsed().as_secs_f64() * 1000.0;
+    if args.dot.is_some() {
+        eprintln!("[warn] Not writing `--dot`, as the graph wasn't built");
+    }
+
+    if args.json {
+        let report = match &result {
+            Ok(plan) => serde_json::json!({
+                "ok": true,
+                "graph": lisp_config,
+                "build_ms": build_ms,
+                "plan_only": true,
+                "inputs": plan.topology().get_inputs().len(),
+                "outputs": plan.topology().get_outputs().len(),
+                "nodes": plan.topology().len(),
+                "meta": plan.meta().to_json(),
+            }),
+            Err(e) => serde_json::json!({
+                "ok": false,
+                "graph": lisp_config,
+                "build_ms": build_ms,
+                "plan_only": true,
+                "node": e.node,
+                "errors": e
+                    .diagnostics
+                    .iter()
+                    .map(Diagnostic::to_json)
+                    .collect::<Vec<_>>(),
+            }),
+        };
+        println!("{}", report);
+    } else {
+        match &result {
+            Ok(_) => eprintln!(
+                "[info] Planned `{}` in {:.1}ms, without compiling its shaders",
+                lisp_config.display(),
+                build_ms
+            ),
+            Err(e) => {
+                eprintln!(
+                    "[fatal] Could not plan `{}`:",
+                    lisp_config.display()
+                );
+                eprintln!("{}", e);
+            },
+        }
+    }
+
+    if result.is_err() {
+        std::process::exit(1);
+    }
+}
+
+fn freeze(args: Freeze, config: &Config) {
+    let project = config.find_project(&args.project);
+    let lisp_config =
+        args.graph.unwrap_or_else(|| project.join("shader.graph"));
+
+    // see `check`, binaries are only kept by the driver
+    // that made them, so this is best run where the bundle
+    // will be played
+    let mut _window = None;
+    let mut _software = None;
+    let context = if args.software || !util::has_display() {
+        let renderer = util::create_software(1, 1).unwrap_or_else(|e| {
+            eprintln!("[fatal] {}", e);
+            std::process::exit(1);
+        });
+        _software.insert(renderer).get_context().clone()
+    } else {
+        let mut window_options =
+            util::WindowOptions::new("Shader Garden".into());
+        window_options.visible = false;
+        let window = util::create_with(&window_options, 1.0, 1.0);
+        _window.insert(window).1.get_context().clone()
+    };
+
+    let options = BuildOptions {
+        profile: args.build_profile,
+        ..BuildOptions::default()
+    };
+    let frozen =
+        ShaderDir::new_from_dir(&project, options.allow_outside, || {
+            fs::read_to_string(&lisp_config).map_err(|e| {
+                format!("Could not read `{}`: {}", lisp_config.display(), e)
+            })
+        })
+        .and_then(|frozen| plan_from_sexp_with_options(frozen, &options))
+        .and_then(|plan| Bundle::freeze(&context, &plan, &map! {}))
+        .map_err(|e| BuildError::new(e, &project, &lisp_config));
+    let bundle = match frozen {
+        Ok((bundle, _)) => bundle,
+        Err(e) => {
+            eprintln!("[fatal] Could not build `{}`:", lisp_config.display());
+            eprintln!("{}", e);
+            std::process::exit(1);
+        },
+    };
+    if let Err(e) = bundle.write(&args.output) {
+        eprintln!("[fatal] {}", e);
+        std::process::exit(1);
+    }
+    eprintln!(
+        "[info] Froze `{}` into `{}`",
+        lisp_config.display(),
+        args.output.display()
+    );
+}
+
+fn bake(args: Bake, config: &Config) {
+    let project = config.find_project(&args.project);
+    let lisp_config =
+        args.graph.unwrap_or_else(|| project.join("shader.graph"));
+
+    // see `freeze`
+    let mut _window = None;
+    let mut _software = None;
+    let context = if args.software || !util::has_display() {
+        let renderer = util::create_software(1, 1).unwrap_or_else(|e| {
+            eprintln!("[fatal] {}", e);
+            std::process::exit(1);
+        });
+        _software.insert(renderer).get_context().clone()
+    } else {
+        let mut window_options =
+            util::WindowOptions::new("Shader Garden".into());
+        window_options.visible = false;
+        let window = util::create_with(&window_options, 1.0, 1.0);
+        _window.insert(window).1.get_context().clone()
+    };
+
+    // images baked before are not what's being baked now
+    let options = BuildOptions {
+        profile: args.build_profile,
+        baked: false,
+        ..BuildOptions::default()
+    };
+    let mut graph = ShaderGraph::new(&context);
+    graph.set_render_scale(args.render_scale);
+    let built =
+        ShaderDir::new_from_dir(&project, options.allow_outside, || {
+            fs::read_to_string(&lisp_config).map_err(|e| {
+                format!("Could not read `{}`: {}", lisp_config.display(), e)
+            })
+        })
+        .and_then(|dir| plan_from_sexp_with_options(dir, &options))
+        .and_then(|plan| plan.realize(&mut graph, &map! {}).map(|()| plan))
+        .map_err(|e| BuildError::new(e, &project, &lisp_config));
+    let plan = match built {
+        Ok(plan) => plan,
+        Err(e) => {
+            eprintln!("[fatal] Could not build `{}`:", lisp_config.display());
+            eprintln!("{}", e);
+            std::process::exit(1);
+        },
+    };
+    let bakeable = plan.bakeable();
+    if bakeable.is_empty() {
+        eprintln!("[info] Nothing in `{}` can be baked", lisp_config.display());
+        return;
+    }
+
+    // static nodes never read the inputs, so any will do
+    let blank = glium::Texture2d::empty(&context, 1, 1).unwrap_or_else(|e| {
+        eprintln!("[fatal] {}", e);
+        std::process::exit(1);
+    });
+    let inputs = graph.get_inputs().iter().map(|id| (*id, &blank)).collect();
+    graph.forward(inputs);
+
+    // without external functions, ids in the plan and the
+    // graph are the same
+    let dir = Baked::dir(&project);
+    if let Err(e) = fs::create_dir_all(&dir) {
+        eprintln!("[fatal] Could not create `{}`: {}", dir.display(), e);
+        std::process::exit(1);
+    }
+    let mut baked = vec![];
+    for id in bakeable {
+        let name = plan.topology().name_of(id).map(str::to_string);
+        let file = match &name {
+            Some(name) => format!("{}.png", name),
+            None => format!("node-{}.png", id.index()),
+        };
+        let image = dir.join(file);
+        let written = plan.cache_key(id).and_then(|key| {
+            let output =
+                graph.node_output(id).ok_or("The node has no output")?;
+            output.save_png(&image)?;
+            Ok(key)
+        });
+        match written {
+            Ok(key) => baked.push(Baked {
+                node: id,
+                name,
+                key,
+                image,
+            }),
+            Err(e) => eprintln!(
+                "[warn] Not baking {}: {}",
+                plan.topology().describe(id),
+                e
+            ),
+        }
+    }
+    if let Err(e) = Baked::write_all(&dir, &baked) {
+        eprintln!("[fatal] {}", e);
+        std::process::exit(1);
+    }
+    eprintln!(
+        "[info] Baked {} nodes of `{}` into `{}`",
+        baked.len(),
+        lisp_config.display(),
+        dir.display()
+    );
+}
+
+fn migrate(args: Migrate, config: &Config) {
+    let project = config.find_project(&args.project);
+    let lisp_config =
+        args.graph.unwrap_or_else(|| project.join("shader.graph"));
+
+    let files = migrate_graph(&project, &lisp_config).unwrap_or_else(|e| {
+        eprintln!("[fatal] {}", e);
+        std::process::exit(1);
+    });
+    let mut outdated = 0;
+    for (path, migrated) in files.iter() {
+        for warning in migrated.warnings.iter() {
+            eprintln!("[warn] In `{}`, {}", path.display(), warning);
+        }
+        for change in migrated.changes.iter() {
+            eprintln!("[info] In `{}`, {}", path.display(), change);
+        }
+        if migrated.changes.is_empty() {
+            continue;
+        }
+        outdated += 1;
+        if !args.check {
+            // keep the file as it was, next to it, in case
+            // the rewrite isn't wanted
+            let mut backup = path.clone().into_os_string();
+            backup.push(".bak");
+            let written = fs::copy(path, &backup)
+                .and_then(|_| fs::write(path, &migrated.source));
+            if let Err(e) = written {
+                eprintln!(
+                    "[fatal] Could not write `{}`: {}",
+                    path.display(),
+                    e
+                );
+                std::process::exit(1);
+            }
+            eprintln!(
+                "[info] Kept the original of `{}` as `{}`",
+                path.display(),
+                Path::new(&backup).display()
+            );
+        }
+    }
+
+    match (outdated, args.check) {
+        (0, _) => eprintln!("[info] `{}` is up to date", lisp_config.display()),
+        (_, true) => {
+            eprintln!("[fatal] {} of {} files need migrating, run without --check to rewrite them", outdated, files.len());
+            std::process::exit(1);
+        },
+        (_, false) => {
+            eprintln!("[info] Migrated {} of {} files", outdated, files.len())
+        },
+    }
+}
+
+fn examples(args: Examples) {
+    let (name, path) = match args.command {
+        Some(ExamplesCommand::Init { name, project }) => {
+            let path = project.unwrap_or_else(|| PathBuf::from(&name));
+            (name, path)
+        },
+        None => {
+            let width = reload::EXAMPLES
+                .iter()
+                .map(|e| e.name.len())
+                .max()
+                .unwrap_or(0);
+            for example in reload::EXAMPLES {
+                println!(
+                    "{:width$}  {}",
+                    example.name,
+                    example.description,
+                    width = width
+                );
+            }
+            eprintln!("[info] Run `shadergarden examples init <name>` to write one into a directory of its own");
+            return;
+        },
+    };
+
+    let example = reload::Example::find(&name).unwrap_or_else(|e| {
+        eprintln!("[fatal] {}", e);
+        std::process::exit(1);
+    });
+    fs::create_dir_all(&path).unwrap();
+    if let Ok(mut dir) = fs::read_dir(&path) {
+        if dir.next().is_some() {
+            eprintln!("[fatal] `{}` is not empty", path.display());
+            std::process::exit(1);
+        }
+    }
+    if let Err(e) = example.extract(&path) {
+        eprintln!("[fatal] {}", e);
+        std::process::exit(1);
+    }
+
+    eprintln!("[info] Wrote the `{}` example into `{}`, run it with `shadergarden run {}`", name, path.display(), path.display());
+}
+
+fn play(args: Play) {
+    let bundle = Bundle::read(&args.bundle).unwrap_or_else(|e| {
+        eprintln!("[fatal] {}", e);
+        std::process::exit(1);
+    });
+
+    let mut window_options = util::WindowOptions::new("Shader Garden".into());
+    window_options.fullscreen = args.fullscreen;
+    window_options.vsync = args.vsync;
+    let (event_loop, display) = util::create_with(
+        &window_options,
+        args.width as f64,
+        args.height as f64,
+    );
+
+    let mut graph = ShaderGraph::new(display.get_context());
+    graph.set_render_scale(args.render_scale);
+    if let Err(e) = bundle.realize(&mut graph, &map! {}) {
+        eprintln!("[fatal] Could not build `{}`: {}", args.bundle.display(), e);
+        std::process::exit(1);
+    }
+    drop(bundle);
+    if !graph.get_inputs().is_empty() {
+        eprintln!("[fatal] Bundles with inputs can only be played by embedding shadergarden");
+        std::process::exit(1);
+    }
+    let output = if let [output] = graph.get_outputs().as_slice() {
+        *output
+    } else {
+        eprintln!("[fatal] Graph has invalid output signature.");
+        std::process::exit(1);
+    };
+    if args.fullscreen {
+        display.gl_window().window().set_cursor_visible(false);
+    }
+
+    let frame_nanos = (1_000_000_000.0 / args.max_fps) as u64;
+    event_loop.run(move |event, _, control_flow| {
+        // waits until next frame, keep at top
+        *control_flow = wait_nanos(frame_nanos);
+        handle_event(event, control_flow);
+        if *control_flow == ControlFlow::Exit {
+            return;
+        }
+
+        let output_map = graph.forward(BTreeMap::new());
+        let target = display.draw();
+        output_map[&output]
+            .as_surface()
+            .fill(&target, MagnifySamplerFilter::Linear);
+        target.finish().unwrap();
+    });
+}
+
+/// Times sound first, as it needs no window, then the
+/// camera, with a window that flashes for it to see.
+fn measure_latency(args: Latency) {
+    let device = match (&args.camera_device, args.camera) {
+        (Some(device), _) => Some(device.clone()),
+        (None, Some(index)) => {
+            Some(camera::device(index).unwrap_or_else(|e| {
+                eprintln!("[fatal] {}", e);
+                std::process::exit(1);
+            }))
+        },
+        (None, None) => None,
+    };
+    if device.is_none() && !args.audio {
+        eprintln!("[fatal] Pass --camera or --audio to measure");
+        std::process::exit(1);
+    }
+    let ms = |time: Duration| time.as_secs_f64() * 1000.0;
+
+    let mut sound = None;
+    if args.audio {
+        eprintln!("[info] Playing {} clicks, keep the room quiet", args.trials);
+        let times = latency::clicks(
+            args.audio_device.as_deref(),
+            args.output_device.as_deref(),
+            args.trials,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("[fatal] {}", e);
+            std::process::exit(1);
+        });
+        sound = latency::median(&times);
+        match sound {
+            Some(median) => {
+                eprintln!("[info] Sound is captured {:.0}ms after it's played, the median of {} clicks", ms(median), times.len())
+            },
+            None => eprintln!("[warn] No click was heard, check the input can hear the output"),
+        }
+    }
+    let device = match device {
+        Some(device) => device,
+        None => return,
+    };
+
+    let (width, height) = (640, 480);
+    let (_camera, frames) =
+        Camera::start(&device, width, height, 30, Duration::ZERO)
+            .unwrap_or_else(|e| {
+                eprintln!("[fatal] {}", e);
+                std::process::exit(1);
+            });
+    let (event_loop, display) = util::create_with(
+        &util::WindowOptions::new("Shader Garden latency".into()),
+        width as f64,
+        height as f64,
+    );
+    eprintln!(
+        "[info] Point the camera at the window, {} flashes to go",
+        args.trials
+    );
+    let mut flashes = Flashes::new();
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = wait_nanos(1_000_000);
+        handle_event(event, control_flow);
+        let done = flashes.times.len() >= args.trials
+            // the camera can't see the window
+            || flashes.count() > 2 * args.trials + 1;
+        if *control_flow == ControlFlow::Exit || done {
+            let camera = latency::median(&flashes.times);
+            match camera {
+                Some(median) => {
+                    eprintln!("[info] The camera sees the screen {:.0}ms after it changes, the median of {} flashes", ms(median), flashes.times.len())
+                },
+                None => eprintln!("[warn] No flash was seen, check the camera can see the window"),
+            }
+            // what's caught sooner is held back to match
+            match (camera, sound) {
+                (Some(camera), Some(sound)) if camera > sound => {
+                    eprintln!("[info] To line them up, hold sound back with `(audio :delay {:.3})`", (camera - sound).as_secs_f64())
+                },
+                (Some(camera), Some(sound)) => {
+                    eprintln!("[info] To line them up, hold the camera back with `(camera :delay {:.3})`", (sound - camera).as_secs_f64())
+                },
+                _ => (),
+            }
+            std::process::exit(0);
+        }
+
+        let lit = flashes.lit(Instant::now());
+        let mut target = display.draw();
+        let level = if lit { 1.0 } else { 0.0 };
+        target.clear_color(level, level, level, 1.0);
+        target.finish().unwrap();
+        display.finish();
+        flashes.shown(lit, Instant::now());
+        while let Ok(frame) = frames.try_recv() {
+            flashes.frame(latency::brightness(&frame), Instant::now());
+        }
+    });
+}
+
+/// Lists every kind of device, with what names each.
+/// Monitors and the GPU rendering by default are only found
+/// with a display to open a hidden window on.
+fn list_devices(args: Devices) {
+    let mut sections = vec![
+        (
+            "cameras",
+            "Cameras, by index, for (camera <index>) and --camera",
+            devices::cameras(),
+        ),
+        (
+            "audio",
+            "Sound inputs, for :device of (audio) and --audio-device",
+            devices::audio_inputs(),
+        ),
+        ("midi", "MIDI devices, for --midi", devices::midi_ports()),
+    ];
+    let mut renderer = None;
+    if util::has_display() {
+        let options = util::WindowOptions {
+            visible: false,
+            ..util::WindowOptions::new("Shader Garden Devices".into())
+        };
+        let (event_loop, display) = util::create_with(&options, 1.0, 1.0);
+        sections.push((
+            "monitors",
+            "Monitors, by index, for :fullscreen of (window)",
+            devices::monitors(&event_loop),
+        ));
+        renderer = Some(Capabilities::detect(display.get_context()).renderer);
+    }
+    sections.push((
+        "gpus",
+        "GPUs, for DRI_PRIME=<id> with Mesa",
+        devices::gpus(),
+    ));
+
+    if args.json {
+        let mut json = serde_json::json!({ "renderer": renderer });
+        for (key, _, devices) in sections.iter() {
+            let devices = devices.iter().map(|device| serde_json::json!({ "id": device.id, "name": device.name }));
+            json[key] = devices.collect();
+        }
+        println!("{}", json);
+        return;
+    }
+    for (_, title, devices) in sections.iter() {
+        println!("{}:", title);
+        if devices.is_empty() {
+            println!("  none found");
+        }
+        let width = devices
+            .iter()
+            .map(|device| device.id.len())
+            .max()
+            .unwrap_or(0);
+        for device in devices.iter() {
+            println!("  {:width$}  {}", device.id, device.name, width = width);
+        }
+    }
+    match renderer {
+        Some(renderer) => println!("Rendering with {} by default", renderer),
+        None => println!("No display to find monitors on"),
+    }
+}
+
+fn completions(shell: Shell) {
+    let mut script = vec![];
+    Cli::clap().gen_completions_to("shadergarden", shell, &mut script);
+    let script = String::from_utf8_lossy(&script);
+    print!("{}", complete_graph_files(shell, &script));
+}
+
+/// clap completes every option that takes a value with any
+/// file, so narrow `--graph` down to `.graph` files.
+fn complete_graph_files(shell: Shell, script: &str) -> String {
+    let mut lines = vec![];
+    let mut graph_case = false;
+    for line in script.lines() {
+        let trimmed = line.trim();
+        let graph_option =
+            trimmed.starts_with("'--graph=[") || trimmed.starts_with("'-g+[");
+        let line = match shell {
+            Shell::Bash if graph_case && trimmed.starts_with("COMPREPLY") => {
+                let graph_files = "compgen -o plusdirs -f -X '!*.graph'";
+                line.replace("compgen -f", graph_files)
+            },
+            Shell::Zsh if graph_option => {
+                let graph_files = "]:graph:_files -g \"*.graph\"' \\";
+                line.replacen("]' \\", graph_files, 1)
+            },
+            Shell::Fish if trimmed.contains(" -l graph") => {
+                format!("{} -r -a \"(__fish_complete_suffix .graph)\"", line)
+            },
+            _ => line.to_string(),
+        };
+        graph_case = trimmed == "--graph)" || trimmed == "-g)";
+        lines.push(line);
     }
+    lines.join("\n") + "\n"
 }
 
-fn new(path: PathBuf) {
+fn new(args: New) {
+    let path = args.project;
     fs::create_dir_all(&path).unwrap();
     if let Ok(mut dir) = fs::read_dir(&path) {
         if dir.next().is_some() {
@@ -136,7 +2616,22 @@ fn new(path: PathBuf) {
         }
     }
 
-    if reload::BASE_PROJECT.extract(&path).is_err() {
+    if let Some(shadertoy) = &args.shadertoy {
+        let imported = reload::ShaderDir::new_from_shadertoy(
+            shadertoy,
+            args.width,
+            args.height,
+        )
+        .and_then(|shader_dir| write_project(&path, &shader_dir));
+        if let Err(e) = imported {
+            eprintln!(
+                "[fatal] Could not import `{}`: {}",
+                shadertoy.display(),
+                e
+            );
+            std::process::exit(1);
+        }
+    } else if reload::BASE_PROJECT.extract(&path).is_err() {
         eprintln!("[fatal] Could not create base project");
         panic!();
     }
@@ -147,155 +2642,1276 @@ fn new(path: PathBuf) {
     );
 }
 
+/// Writes a graph and its shaders into a project directory.
+fn write_project(
+    path: &Path,
+    shader_dir: &reload::ShaderDir,
+) -> Result<(), String> {
+    let write = |file: PathBuf, contents: &str| {
+        fs::write(&file, contents)
+            .map_err(|e| format!("Could not write `{}`: {}", file.display(), e))
+    };
+    write(path.join("shader.graph"), &shader_dir.lisp)?;
+    for (name, source) in shader_dir.shaders.iter() {
+        write(reload::shader_dir::shader_path(path, name), source)?;
+    }
+    Ok(())
+}
+
 // TODO: factor out common parts of render and run
 
 fn render(render: Render) {
-    let args = render.run;
+    let args = &render.run;
+    args.cache_programs();
+    if args.lock || args.verify {
+        eprintln!("[warn] Lockfiles are only written and checked by `run`");
+    }
     let lisp_config = args
         .graph
         .to_owned()
         .unwrap_or_else(|| args.project.join("shader.graph"));
-    let inputs = args.inputs;
+    let inputs = args.inputs.clone();
+    let (canvas, tile) = args.canvas();
 
-    // set up the main event loop
-    let (event_loop, display) = util::create(
-        "Shader Garden Renderer".to_string(),
-        args.width as f64,
-        args.height as f64,
-    );
+    // nothing is shown, so render in a window that never
+    // opens, or in software where there is nowhere to open one
+    let mut _window = None;
+    let mut _software = None;
+    let context = if render.software || !util::has_display() {
+        let renderer = util::create_software(1, 1).unwrap_or_else(|e| {
+            eprintln!("[fatal] {}", e);
+            std::process::exit(1);
+        });
+        _software.insert(renderer).get_context().clone()
+    } else {
+        let mut window_options = args.window_options("Shader Garden Renderer");
+        window_options.visible = false;
+        let window = util::create_with(&window_options, 1.0, 1.0);
+        _window.insert(window).1.get_context().clone()
+    };
+    if args.gl_debug {
+        if let Some((_, display)) = &_window {
+            util::debug::load_with(|symbol| {
+                display.gl_window().get_proc_address(symbol)
+            });
+        }
+    }
+    let mut timing = args.timing_log();
+    if timing.is_some() {
+        match &_window {
+            Some((_, display)) => {
+                util::timer::load_with(|symbol| {
+                    display.gl_window().get_proc_address(symbol)
+                });
+            },
+            None => {
+                eprintln!("[warn] Can't profile software renders");
+                timing = None;
+            },
+        }
+    }
 
-    // set up hot code reloading
-    let mut graph = ShaderGraphWatcher::build_initial(display.get_context(), &args.project, &lisp_config).unwrap();
+    let options = BuildOptions {
+        render_scale:  args.render_scale,
+        validate:      args.validate,
+        keep_state:    args.keep_state,
+        keep_clock:    args.keep_clock,
+        error_screen:  false,
+        background:    false,
+        allow_outside: args.allow_outside,
+        limits:        args.limits(),
+        profile:       args.build_profile.clone(),
+        baked:         !args.unbaked,
+        safe:          args.safe,
+    };
+    let build = |options: &BuildOptions| {
+        ShaderGraphWatcher::build_initial_with_files(
+            &context,
+            &args.project,
+            &lisp_config,
+            options,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("{}", BuildError::new(e, &args.project, &lisp_config));
+            std::process::exit(1);
+        })
+    };
+    let (mut graph, mut files) = build(&options);
+    // built again larger if an output asks for more pixels
+    // than the graph has, see `sink_scale`
+    let size = graph
+        .get_outputs()
+        .first()
+        .and_then(|id| graph.texture(*id))
+        .map(|texture| {
+            let (width, height) = texture.dimensions();
+            (width / graph.supersample(), height / graph.supersample())
+        });
+    let scale = size.map_or(1.0, |size| {
+        sink_scale(size, render.output.iter().chain(&render.run.sink))
+    });
+    let options = if scale > 1.0 {
+        let options = BuildOptions {
+            render_scale: options.render_scale * scale,
+            ..options
+        };
+        eprintln!(
+            "[info] Rendering at {}x the graph's size, for the largest output",
+            options.render_scale
+        );
+        let (built, built_files) = build(&options);
+        graph = built;
+        files = built_files;
+        options
+    } else {
+        options
+    };
+    let mut post = args.post_chain(&context, &options);
+    let mut accumulator = Accumulator::new(&context).unwrap();
+    // see `(profile ... :supersample <n>)`
+    accumulator.set_supersample(graph.supersample());
 
     eprintln!("[info] Built initial graph");
+    seed_graph(&mut graph, args.seed);
 
     // build a table of textures
     #[cfg(feature = "ffmpeg")]
-    let mut input_textures =
-        util::input_textures(&display, &inputs, args.width, args.height);
+    let mut input_textures = match &_window {
+        Some((_, display)) => util::input_textures(
+            display,
+            &inputs,
+            args.width(),
+            args.height(),
+            args.upload_budget << 20,
+        ),
+        None if inputs.is_empty() => vec![],
+        None => {
+            eprintln!("[fatal] Inputs can not be rendered in software");
+            std::process::exit(1);
+        },
+    };
 
     #[cfg(not(feature = "ffmpeg"))]
     assert!(inputs.is_empty(), "Inputs are not supported when running without ffmpeg");
 
-    eprintln!("[info] Starting Render...");
+    let input_nodes = graph.get_inputs().clone();
+    let output = if let [output] = graph.get_outputs().as_slice() {
+        *output
+    } else {
+        eprintln!("[fatal] Graph has invalid output signature.");
+        std::process::exit(1);
+    };
+
+    #[cfg(not(feature = "ffmpeg"))]
+    assert!(input_nodes.is_empty());
+
+    #[cfg(feature = "ffmpeg")]
+    assert_eq!(
+        input_nodes.len(),
+        input_textures.len(),
+        "The number of graph inputs and provided textures does not match up",
+    );
+
+    // every output is opened once the first frame's size is
+    // known, see `SinkSpec::open`, and live ones are kept
+    // open across checkpoints
+    let mut sinks: Option<Sinks> = None;
+    let mut live: Option<Sinks> = None;
 
-    let mut frame_number = 0;
-    let frames_output = render.output;
     let frame_start = render.start;
-    let frame_end = render.end;
-    let frame_nanos = (1000000000.0 / render.fps) as u64;
+    let frame_end = render
+        .frames
+        .map_or(render.end, |frames| frame_start + frames);
+    let samples = render.temporal_samples.max(1);
 
-    event_loop.run(move |event, _, mut control_flow| {
-        // waits until next frame, keep at top
-        *control_flow = wait_nanos(0);
-        handle_event(event, &mut control_flow);
+    // each shard writes its own parts, starting where
+    // outputs kept at a fraction of the frame rate would
+    let align = shard::align(&render.output);
+    let (outputs, (frame_start, frame_end)) = match render.shard {
+        Some(shard) => (
+            shard.outputs(&render.output),
+            shard.frames(frame_start, frame_end, align),
+        ),
+        None => (render.output.clone(), (frame_start, frame_end)),
+    };
 
-        // get the input and output handles
-        let input_nodes = graph.get_inputs();
-        let output = if let [output] = graph.get_outputs().as_slice() {
-            *output
-        } else {
-            eprintln!("[fatal] Graph has invalid output signature.");
-            panic!();
-        };
+    // frames before the first written are only rendered for
+    // the graph to get going, unless it starts from saved
+    // state, or every frame stands alone
+    if let Some(path) = &args.load_state {
+        load_graph_state(&mut graph, path);
+    }
+    let stands_alone = !graph.has_feedback() && input_nodes.is_empty();
+    let mut frame_first = if stands_alone || args.load_state.is_some() {
+        frame_start
+    } else {
+        0
+    };
 
-        #[cfg(not(feature = "ffmpeg"))]
-        assert!(input_nodes.is_empty());
+    // written next to every output, to render it again
+    let mut provenance = Provenance::new(&graph, &args.project, files)
+        .unwrap_or_else(|e| {
+            eprintln!("[fatal] {}", e);
+            std::process::exit(1);
+        });
+    provenance.profile = args.build_profile.clone();
+    provenance.render_scale = options.render_scale;
+    provenance.fps = render.fps;
+    provenance.frames = (frame_start, frame_end);
+    provenance.temporal_samples = samples;
+    // every shard renders the same variation
+    if let (Some(_), None) = (render.shard, args.seed) {
+        let seed =
+            u32::from_str_radix(&provenance.graph_hash()[..8], 16).unwrap();
+        graph.set_build_seed(seed);
+        provenance.build_seed = seed;
+        eprintln!("[info] Build seed {}, from the graph's files, shared by every shard", seed);
+    }
+    // a checkpointed render writes its outputs in segments,
+    // as parts merged once they're all done, so that an
+    // interrupted render can carry on after the last one
+    let checkpoint = render.checkpoint.as_deref().map(Checkpoint::new);
+    let every = checkpoint.as_ref().map(|_| render.checkpoint_every);
+    let frames = (frame_start, frame_end);
+    let mut progress = Progress::new(
+        provenance.graph_hash(),
+        graph.build_seed(),
+        frames,
+        every,
+        align,
+    );
+    if let (Some(checkpoint), Some(dir)) = (&checkpoint, &render.checkpoint) {
+        if let Err(e) = Checkpoint::check(&graph, &outputs) {
+            eprintln!("[fatal] {}", e);
+            std::process::exit(1);
+        }
+        match checkpoint.resume(&progress, args.seed.is_some()) {
+            Ok(Some(saved)) => {
+                progress = saved;
+                graph.set_build_seed(progress.seed);
+                provenance.build_seed = progress.seed;
+                if progress.done > 0 {
+                    load_graph_state(
+                        &mut graph,
+                        &checkpoint.state(progress.done),
+                    );
+                    frame_first = progress.next_frame(align);
+                }
+                eprintln!(
+                    "[info] Resuming from the checkpoint in `{}`, at frame {}",
+                    dir.display(),
+                    frame_first
+                );
+            },
+            Ok(None) => (),
+            Err(e) => {
+                eprintln!("[fatal] {}", e);
+                std::process::exit(1);
+            },
+        }
+    }
 
-        #[cfg(feature = "ffmpeg")]
-        assert_eq!(
-            input_nodes.len(),
-            input_textures.len(),
-            "The number of graph inputs and provided textures does not match up",
-        );
+    // every frame is a fixed step, however long it takes
+    let dt = 1.0 / (render.fps * samples as f64);
 
-        // render the shader graph, display the primary output
-        #[allow(unused_mut)]
-        let mut input_map = BTreeMap::new();
+    if timing.is_some() {
+        if let Err(e) = graph.set_profiling(true) {
+            eprintln!("[warn] {}", e);
+            timing = None;
+        }
+    }
 
-        #[cfg(feature = "ffmpeg")]
-        for (node_id, texture) in input_nodes.iter().zip(input_textures.iter_mut()) {
-            input_map.insert(*node_id, texture.next_frame());
+    eprintln!(
+        "[info] Rendering frames {} to {}...",
+        frame_start, frame_end
+    );
+    let started = Instant::now();
+
+    for frame_number in frame_first..frame_end {
+        // render the shader graph once per sub-frame, and
+        // average the primary output
+        accumulator.clear();
+        for sample in 0..samples {
+            #[allow(unused_mut)]
+            let mut input_map = BTreeMap::new();
+
+            // every sub-frame sees the same input frame, and
+            // every frame the next one, however long the
+            // decoder takes
+            #[cfg(feature = "ffmpeg")]
+            for (node_id, texture) in
+                input_nodes.iter().zip(input_textures.iter_mut())
+            {
+                let frame = if sample == 0 {
+                    texture.next_whole_frame()
+                } else {
+                    texture.current_frame()
+                };
+                input_map.insert(*node_id, frame);
+            }
+
+            let pass = frame_number * samples as u64 + sample as u64;
+            let time = pass as f64 * dt;
+            graph.set_canvas(canvas, tile);
+            let output_map = match pass {
+                // the first `u_dt` is a whole frame too
+                0 => {
+                    graph.set_fixed_time(time as f32, dt as f32);
+                    graph.forward(input_map)
+                },
+                _ => graph.render_at(time, pass, input_map),
+            };
+            let texture = match &mut post {
+                Some(post) => post.forward(output_map[&output]),
+                None => output_map[&output],
+            };
+            accumulator.add(texture, 1.0 / samples as f32).unwrap();
+            if let Some(timing) = &mut timing {
+                if let Err(e) = timing.record(&graph) {
+                    eprintln!("[warn] {}", e);
+                }
+            }
         }
 
-        // dumb hack to make the playback smooth(er)
-        graph.created = std::time::Instant::now()
-            - std::time::Duration::from_nanos(frame_nanos * frame_number);
-        let output_map = graph.forward(input_map);
+        if frame_number < frame_start {
+            continue;
+        }
+        let texture = accumulator.texture();
+        let open = |outputs: &mut dyn Iterator<Item = &SinkSpec>,
+                    provenance: &Provenance| {
+            let mut sinks = Sinks::new();
+            for output in outputs {
+                match output.open(
+                    &context,
+                    texture.dimensions(),
+                    render.fps,
+                    false,
+                    graph.meta(),
+                    Some(provenance),
+                ) {
+                    Ok(opened) => sinks.add(opened),
+                    Err(e) => {
+                        eprintln!("[fatal] {}", e);
+                        std::process::exit(1);
+                    },
+                }
+            }
+            sinks
+        };
+        let (_, segment_end) = progress.segment_frames(align);
+        let writing = sinks.get_or_insert_with(|| match &checkpoint {
+            Some(_) => {
+                let mut provenance = provenance.clone();
+                provenance.frames = progress.segment_frames(align);
+                open(
+                    &mut progress.segment().outputs(&outputs).iter(),
+                    &provenance,
+                )
+            },
+            None => open(&mut outputs.iter(), &provenance),
+        });
+        writing.push(texture);
+        live.get_or_insert_with(|| {
+            open(&mut render.run.sink.iter(), &provenance)
+        })
+        .push(texture);
+        // numbered from the start, so shards fill one directory
+        if let Some(dir) = &render.exr {
+            let path = dir.join(format!("frame-{:0>4}.exr", frame_number));
+            if let Err(e) = fs::create_dir_all(dir)
+                .map_err(|e| e.to_string())
+                .and_then(|_| write_exr(&graph, texture, &render.layers, &path))
+            {
+                eprintln!(
+                    "[fatal] Could not write `{}`: {}",
+                    path.display(),
+                    e
+                );
+                std::process::exit(1);
+            }
+        }
 
-        // set up the draw target and draw
-        let mut target = display.draw();
-        target.clear_color(0.0, 0.0, 0.0, 1.0);
-        let texture = output_map[&output];
-        util::texture(&display, &mut target, texture);
-        target.finish().unwrap();
+        // every segment is finished before it's checkpointed
+        if let (Some(checkpoint), true) =
+            (&checkpoint, frame_number + 1 == segment_end)
+        {
+            finish_sinks(sinks.take());
+            progress.done += 1;
+            if let Err(e) = checkpoint.save(&progress, &graph) {
+                eprintln!("[fatal] {}", e);
+                std::process::exit(1);
+            }
+            eprintln!("[info] Checkpointed at frame {}", segment_end);
+        }
+    }
 
-        if frame_number >= frame_start {
-            png::write_png(texture, &frames_output.join(
-                format!("frame-{:0>4}.png", frame_number - frame_start)
-            ));
+    finish_sinks(sinks);
+    finish_sinks(live);
+    if let Some(checkpoint) = &checkpoint {
+        if let Err(e) = checkpoint.finish(&outputs) {
+            eprintln!("[fatal] {}", e);
+            std::process::exit(1);
         }
-        if frame_number > frame_end {
-            panic!("Finished render, bailing pathetically");
+    }
+    if let Some(path) = &render.save_state {
+        match graph.save_state(path) {
+            Ok(saved) => eprintln!(
+                "[info] Saved the state of {} node(s) to `{}`",
+                saved,
+                path.display()
+            ),
+            Err(e) => eprintln!("[warn] {}", e),
         }
-        frame_number += 1
+    }
+    let outputs = outputs.iter().chain(&render.run.sink);
+    let outputs = outputs.map(|o| format!("`{}`", o.path.display()));
+    eprintln!(
+        "[info] Rendered {} frames to {} in {:.1}s",
+        frame_end.saturating_sub(frame_start),
+        outputs.collect::<Vec<_>>().join(", "),
+        started.elapsed().as_secs_f64(),
+    );
+}
+
+fn merge(args: Merge) {
+    let parts = shard::merge(&args.output).unwrap_or_else(|e| {
+        eprintln!("[fatal] {}", e);
+        std::process::exit(1);
     });
+    if args.remove_parts {
+        shard::remove_parts(&parts);
+    }
+    eprintln!(
+        "[info] Merged {} parts into `{}`",
+        parts.len(),
+        args.output.display()
+    );
 }
 
-fn run(args: Run) {
+/// Writes the output of a graph, and its named nodes or
+/// those in `names`, as the layers of an EXR, see `--exr`.
+/// Layers are scaled to the size of the output.
+fn write_exr(
+    graph: &ShaderGraph,
+    output: &glium::Texture2d,
+    names: &[String],
+    path: &Path,
+) -> Result<(), String> {
+    let (width, height) = output.dimensions();
+    // textures are read bottom row first
+    let pixels = util::read_texture(output);
+    let pixels = pixels
+        .chunks_exact(width.max(1) as usize)
+        .rev()
+        .flatten()
+        .flatten()
+        .copied()
+        .collect();
+    // unwrap: read as many pixels as the texture has
+    let mut layers = vec![Layer::new(
+        "",
+        FloatImage::from_raw(width, height, pixels).unwrap(),
+    )];
+    if names.is_empty() {
+        // nodes without a texture, like audio, are left out
+        for (name, _) in graph.node_names() {
+            if let Ok(image) = graph.read_output_f32(name) {
+                layers.push(Layer::new(name, image).fit((width, height)));
+            }
+        }
+    } else {
+        for name in names {
+            let image = graph.read_output_f32(name)?;
+            layers.push(Layer::new(name.as_str(), image).fit((width, height)));
+        }
+    }
+    exr::write(path, &layers)
+}
+
+/// Finishes writing to sinks, if any were opened.
+fn finish_sinks(sinks: Option<Sinks>) {
+    if let Some(sinks) = sinks {
+        if let Err(e) = sinks.finish() {
+            eprintln!("[fatal] {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn diff(diff: Diff) {
+    let args = &diff.run;
+    args.cache_programs();
     let lisp_config = args
         .graph
         .to_owned()
         .unwrap_or_else(|| args.project.join("shader.graph"));
-    let inputs = args.inputs;
+    let (canvas, tile) = args.canvas();
+    let fatal = |e: String| -> ! {
+        eprintln!("[fatal] {}", e);
+        std::process::exit(1);
+    };
+
+    // as in `render`, nothing is shown
+    let mut _window = None;
+    let mut _software = None;
+    let context = if diff.software || !util::has_display() {
+        let renderer = util::create_software(1, 1).unwrap_or_else(|e| fatal(e));
+        _software.insert(renderer).get_context().clone()
+    } else {
+        let mut window_options = args.window_options("Shader Garden Diff");
+        window_options.visible = false;
+        let window = util::create_with(&window_options, 1.0, 1.0);
+        _window.insert(window).1.get_context().clone()
+    };
+
+    let options = BuildOptions {
+        render_scale:  args.render_scale,
+        validate:      args.validate,
+        keep_state:    args.keep_state,
+        keep_clock:    args.keep_clock,
+        error_screen:  false,
+        background:    false,
+        allow_outside: args.allow_outside,
+        limits:        args.limits(),
+        profile:       args.build_profile.clone(),
+        baked:         !args.unbaked,
+        safe:          args.safe,
+    };
+    let mut graph = ShaderGraphWatcher::build_initial(
+        &context,
+        &args.project,
+        &lisp_config,
+        &options,
+    )
+    .unwrap_or_else(|e| {
+        eprintln!("{}", BuildError::new(e, &args.project, &lisp_config));
+        std::process::exit(1);
+    });
+    if !graph.get_inputs().is_empty() {
+        fatal("Graphs with inputs can not be compared".to_string());
+    }
+    seed_graph(&mut graph, args.seed);
+
+    let find = |name: &str| {
+        graph.node_id(name).unwrap_or_else(|| {
+            fatal(format!("There is no node named `{}`", name))
+        })
+    };
+    let node = match &diff.node {
+        Some(name) => find(name),
+        None => match graph.get_outputs().as_slice() {
+            [output] => *output,
+            _ => fatal("Graph has invalid output signature.".to_string()),
+        },
+    };
+    let against = diff.against.as_deref().map(find);
+    let reference = match (&against, &args.reference) {
+        (Some(_), _) => None,
+        (None, Some(path)) => {
+            Some(Image::load(path).unwrap_or_else(|e| fatal(e)))
+        },
+        (None, None) => fatal(
+            "Nothing to compare with, pass --reference or --against"
+                .to_string(),
+        ),
+    };
+
+    // every frame is a fixed step, as in `render`
+    let dt = 1.0 / diff.fps;
+    for frame_number in 0..=diff.frame {
+        graph.set_fixed_time((frame_number as f64 * dt) as f32, dt as f32);
+        graph.set_canvas(canvas, tile);
+        graph.forward(BTreeMap::new());
+    }
+
+    let read = |id| match graph.texture(id) {
+        Some(texture) => Image::read(texture),
+        None => fatal("Inputs can not be compared".to_string()),
+    };
+    let image = read(node);
+    let other = match against {
+        Some(id) => read(id),
+        None => reference.unwrap(),
+    };
+    let ssim = Ssim::compare(&image, &other).unwrap_or_else(|e| fatal(e));
+    if let Some(path) = &diff.heatmap {
+        ssim.write_heatmap(path).unwrap_or_else(|e| fatal(e));
+    }
+
+    let passed = diff
+        .threshold
+        .is_none_or(|threshold| ssim.score >= threshold);
+    if diff.json {
+        println!(
+            "{}",
+            serde_json::json!({
+                "ok": passed,
+                "graph": lisp_config,
+                "frame": diff.frame,
+                "ssim": ssim.score,
+                "threshold": diff.threshold,
+                "heatmap": diff.heatmap,
+            })
+        );
+    } else {
+        eprintln!("[info] SSIM of frame {}: {:.5}", diff.frame, ssim.score);
+        if !passed {
+            eprintln!(
+                "[fatal] The score is below the threshold of {}",
+                diff.threshold.unwrap()
+            );
+        }
+    }
+
+    if !passed {
+        std::process::exit(1);
+    }
+}
+
+/// Sends a frame to each sink given with `--ndi`,
+/// `--dmabuf`, or `--pipewire` that has one, dropping sinks
+/// that fail.
+#[cfg(any(feature = "ndi", target_os = "linux"))]
+/// Opens a sink for each of `specs`, `<name>=<node>` to
+/// publish a node, or just `<name>` for the output, leaving
+/// out those that can't be opened with a warning, see
+/// [`publish`].
+fn senders<S>(
+    specs: &[String],
+    mut open: impl FnMut(&str) -> Result<S, String>,
+) -> Vec<(Option<String>, S)> {
+    specs
+        .iter()
+        .filter_map(|spec| {
+            let (name, node) = match spec.split_once('=') {
+                Some((name, node)) => (name, Some(node.to_string())),
+                None => (spec.as_str(), None),
+            };
+            match open(name) {
+                Ok(sender) => Some((node, sender)),
+                Err(e) => {
+                    eprintln!("[warn] {}", e);
+                    None
+                },
+            }
+        })
+        .collect()
+}
+
+fn publish<'a, S, F>(senders: &mut Vec<(Option<String>, S)>, texture: F)
+where
+    S: OutputSink,
+    F: Fn(&Option<String>) -> Option<&'a glium::Texture2d>,
+{
+    senders.retain_mut(|(node, sender)| match texture(node) {
+        Some(texture) => match sender.push(texture) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!(
+                    "[warn] Stopped publishing `{}`: {}",
+                    sender.name(),
+                    e
+                );
+                false
+            },
+        },
+        None => true,
+    });
+}
+
+fn run(args: Run) {
+    args.cache_programs();
+    dump_on_panic();
+    let mut project = args.project.clone();
+    let mut lisp_config = args
+        .graph
+        .to_owned()
+        .unwrap_or_else(|| args.project.join("shader.graph"));
+    let window_options = args.window_options("Shader Garden Playground");
+    let max_fps = args.max_fps();
+    let (canvas, tile) = args.canvas();
+    let title = window_options.title.clone();
+    let screensaver = args.screensaver;
+    args.warn_unsupported();
+    let inputs = args.inputs.clone();
 
     // set up the main event loop
-    let (event_loop, display) = util::create(
-        "Shader Garden Playground".to_string(),
-        args.width as f64,
-        args.height as f64,
-    );
+    let (window_width, window_height) = args.window_size();
+    let (event_loop, display) =
+        util::create_with(&window_options, window_width, window_height);
+    if args.gl_debug {
+        util::debug::load_with(|symbol| {
+            display.gl_window().get_proc_address(symbol)
+        });
+    }
+    let mut timing = args.timing_log();
+    let mut gpu_timeout = args
+        .gpu_timeout
+        .map(|ms| Duration::from_secs_f64(ms / 1000.0));
+    if timing.is_some() || gpu_timeout.is_some() {
+        util::timer::load_with(|symbol| {
+            display.gl_window().get_proc_address(symbol)
+        });
+    }
+
+    let mut keymap = keymap(args.keymap.as_ref(), &args.project);
 
     // set up hot code reloading
-    let mut watcher = reload::ShaderGraphWatcher::new_watch_dir(
-        display.get_context(),
-        args.project,
-        lisp_config,
-    )
-    .map_err(|e| {
+    let options = args.build_options();
+    let mut post = args.post_chain(&display, &options);
+    let follower = args.follower();
+    let mut projects = match follower {
+        Some(_) => None,
+        None => args.projects(display.get_context(), &options),
+    };
+    // a playlist, or a root listing sub-projects, starts
+    // with the first that builds
+    let built = match (&follower, &mut projects) {
+        (Some(follower), _) => follower.watch(
+            display.get_context(),
+            &args.project,
+            options.clone(),
+        ),
+        (None, Some(projects)) => projects.start(),
+        (None, None) => reload::ShaderGraphWatcher::new_watch_dir(
+            display.get_context(),
+            args.project.clone(),
+            lisp_config.clone(),
+            options.clone(),
+        ),
+    };
+    let mut watcher = built.unwrap_or_else(|e| {
         eprintln!("[fatal] Could not build initial graph:");
-        eprintln!("{}", e);
+        let error = BuildError::new(e, &project, &lisp_config);
+        diagnostic::report(args.message_format, &error);
         panic!();
-    })
-    .unwrap();
+    });
     eprintln!("[info] Built initial graph");
+    if let Some(projects) = &projects {
+        // unwrap: the project shown always exists
+        let shown = projects.project(projects.active()).unwrap();
+        project = shown.dir.clone();
+        lisp_config = shown.graph();
+        eprintln!(
+            "[info] Showing project `{}`, {} of {}",
+            shown.name,
+            projects.active() + 1,
+            projects.len()
+        );
+    }
+    if args.verify {
+        if let Err(e) = reload::lock::verify(&project, watcher.files()) {
+            eprintln!("[fatal] {}", e);
+            std::process::exit(1);
+        }
+        eprintln!(
+            "[info] Verified `{}`",
+            project.join(reload::LOCK_FILE).display()
+        );
+    }
+    if args.lock {
+        write_lock(&project, watcher.files());
+    }
+    if args.thumbnail {
+        write_thumbnail(&display, &project, &lisp_config, &options);
+    }
+    // the lockfile is written the frame after each rebuild,
+    // once the graph is no longer borrowed
+    let mut relock = false;
+    // rebuilt larger if a sink asks for more pixels than the
+    // graph has, see `sink_scale`
+    let size = {
+        let graph = watcher.graph_no_reload();
+        graph
+            .get_outputs()
+            .first()
+            .and_then(|id| graph.texture(*id))
+            .map(|texture| texture.dimensions())
+    };
+    let scale = size.map_or(1.0, |size| sink_scale(size, &args.sink));
+    if scale > 1.0 {
+        let options = BuildOptions {
+            render_scale: options.render_scale * scale,
+            ..options.clone()
+        };
+        eprintln!(
+            "[info] Rendering at {}x the graph's size, for the largest sink",
+            options.render_scale
+        );
+        if let (_, reload::WatchResult::Err(e)) = watcher.set_options(options) {
+            eprintln!("[warn] Could not rebuild the graph larger: {}", e);
+        }
+    }
+    seed_graph(watcher.graph_no_reload(), args.seed);
+    keep_sources(&project, watcher.graph_no_reload());
+    for name in args.window.iter() {
+        if watcher.graph_no_reload().node_id(name).is_none() {
+            eprintln!("[warn] There is no node named `{}` to show yet", name);
+        }
+    }
+    if let Some(path) = &args.load_state {
+        load_graph_state(watcher.graph_no_reload(), path);
+    }
+    if screensaver {
+        display.gl_window().window().set_cursor_visible(false);
+    }
+    let mut presenter = args.presenter(&display);
+    if projects.is_some() && !args.crossfade.0.is_zero() {
+        match Crossfade::new(display.get_context(), args.crossfade.0) {
+            Ok(crossfade) => presenter.crossfade = Some(crossfade),
+            Err(e) => eprintln!("[warn] {}", e),
+        }
+    }
 
     // build a table of textures
     #[cfg(feature = "ffmpeg")]
-    let mut input_textures =
-        util::input_textures(&display, &inputs, args.width, args.height);
+    let mut input_textures = util::input_textures(
+        &display,
+        &inputs,
+        args.width(),
+        args.height(),
+        args.upload_budget << 20,
+    );
 
     #[cfg(not(feature = "ffmpeg"))]
     assert!(inputs.is_empty(), "Inputs are not supported when running without ffmpeg");
 
+    let frame_nanos = (1_000_000_000.0 / max_fps) as u64;
+    let mut pacer = args.pacer(Duration::from_nanos(frame_nanos));
+
+    let mut controller = args.target_fps.map(QualityController::new);
+    let interpolate = args.interpolate.unwrap_or(1).max(1);
+    let mut interpolator = if interpolate > 1 {
+        Some(Interpolator::new(display.get_context()).unwrap())
+    } else {
+        None
+    };
+    let control = args.control(&project, &lisp_config);
+    if args.safe {
+        eprintln!("[info] Safe mode: live inputs are black or silent, and controls keep their defaults");
+    }
+    #[cfg(feature = "knobs")]
+    let mut knobs = args.knobs();
+    let mut archive = args.archive();
+    let sync_host = args.sync_host(&project, &lisp_config);
+    let mut frame_lock = args.frame_lock();
+    let mut params: BTreeMap<(String, String), f32> = BTreeMap::new();
+    let mut recording = args.record.is_some();
+    let mut recorder: Option<Recorder> = None;
+    // opened once the first frame's size is known, as in
+    // `render`, see `SinkSpec::open`
+    let mut sinks: Option<Sinks> = None;
+    let mut node_windows = NodeWindows::default();
+    #[cfg(feature = "ndi")]
+    let mut ndi_senders = args.ndi_senders(max_fps);
+    #[cfg(target_os = "linux")]
+    let mut dmabuf_exporters = args.dmabuf_exporters(&display);
+    #[cfg(target_os = "linux")]
+    let mut pipewire_sources = args.pipewire_sources(max_fps);
+    let mut playback = args.playback();
+    let mut tick: u32 = 0;
+    let mut bypassed: BTreeSet<String> = BTreeSet::new();
+    let mut soloed: Option<String> = None;
+    // the palette picked while running, kept across reloads
+    let mut palette: Option<String> = None;
+    let mut modifiers = ModifiersState::empty();
+    let mut paused = false;
+    let mut history = args
+        .history
+        .map(|frames| History::new(display.get_context(), frames));
+    // how many frames back to show, while frozen
+    let mut scrub: Option<usize> = None;
+    let mut cursor = None;
+    let mut picker = false;
+    #[cfg(feature = "renderdoc")]
+    let renderdoc = shadergarden::renderdoc::RenderDoc::connect();
+    let mut pointer = None;
+    // where the view was last dragged from, while dragging
+    let mut panning = None;
+    let mut wiping = false;
+    // the left button, as Shadertoy's iMouse, see `set_mouse`
+    let mut mouse = [0.0; 4];
+    // as Shadertoy's keyboard texture, see `set_keyboard`
+    let mut keyboard = Keyboard::new();
+    let mut schedule = args.schedule();
+    let clear_alpha = if args.transparent { 0.0 } else { 1.0 };
+
     eprintln!("[info] Starting...");
 
-    event_loop.run(move |event, _, mut control_flow| {
+    event_loop.run(move |event, window_target, control_flow| {
         // waits until next frame, keep at top
-        *control_flow = wait_nanos(16_666_667);
-        handle_event(event, &mut control_flow);
+        *control_flow = wait_nanos(frame_nanos);
+
+        // closing a node's window closes only that window
+        if let Event::WindowEvent {
+            window_id,
+            event: WindowEvent::CloseRequested,
+        } = &event
+        {
+            if node_windows.close(*window_id) {
+                return;
+            }
+        }
+        let key = pressed_key(&event);
+        let click = clicked(&event);
+        pointer = cursor_moved(&event).or(pointer);
+        if let Some((key, down)) = key_changed(&event) {
+            if let Some(code) = key_code(key) {
+                keyboard.set_key(code, down);
+            }
+        }
+
+        // zoom with the wheel, and drag with the right or
+        // middle mouse button, to inspect the output up close
+        let window = display.get_framebuffer_dimensions();
+        let rotation = presenter.rotation;
+        let fraction = |position: PhysicalPosition<f64>| {
+            rotation.unrotate(position.x / window.0 as f64, position.y / window.1 as f64)
+        };
+        match dragged(&event) {
+            Some(true) => panning = pointer,
+            Some(false) => panning = None,
+            None => (),
+        }
+        if let (Some(to), Some(from)) = (cursor_moved(&event), panning) {
+            let ((x0, y0), (x1, y1)) = (fraction(from), fraction(to));
+            presenter.view.pan(x1 - x0, y1 - y0);
+            panning = Some(to);
+        }
+        if let (Some(lines), Some(pointer)) = (scrolled(&event), pointer) {
+            let (x, y) = fraction(pointer);
+            presenter.view.zoom_at(x, y, 1.25f64.powf(lines));
+        }
+
+        // drag the wipe over the reference with the left button
+        if let Some(pressed) = wiped(&event) {
+            wiping = pressed;
+        }
+        if let (true, Some(pointer)) = (wiping, pointer) {
+            let (x, y) = fraction(pointer);
+            let (u, v) = presenter.view.apply(x, y);
+            if let Some(reference) = &mut presenter.reference {
+                reference.split = u.clamp(0.0, 1.0);
+            }
+            // the window counts down from the top
+            let (u, v) = (u as f32, 1.0 - v as f32);
+            if mouse[2] <= 0.0 {
+                mouse = [u, v, u, v];
+            }
+            mouse[0] = u;
+            mouse[1] = v;
+        } else {
+            mouse[2] = -mouse[2].abs();
+        }
+
+        if let Event::WindowEvent {
+            event: WindowEvent::ModifiersChanged(state),
+            ..
+        } = event
+        {
+            modifiers = state;
+        }
+        if screensaver && screensaver_input(&event, &mut cursor) {
+            *control_flow = ControlFlow::Exit;
+        }
+        handle_event(event, control_flow);
+        if *control_flow == ControlFlow::Exit {
+            let stats = pacer.stats();
+            eprintln!(
+                "[info] Presented {} frames, {} late, {} dropped, worst {:.1}ms",
+                stats.frames,
+                stats.late,
+                stats.dropped,
+                stats.worst.as_secs_f64() * 1000.0,
+            );
+            if let Some(recorder) = recorder.take() {
+                finish_recording(recorder);
+            }
+            if let Some(sinks) = sinks.take() {
+                if let Err(e) = sinks.finish() {
+                    eprintln!("[warn] Could not finish {}", e);
+                }
+            }
+            return;
+        }
+
+        // blank the window outside of scheduled hours
+        if let Some(schedule) = &mut schedule {
+            match schedule.sleep(watcher.graph_no_reload().time()) {
+                Sleep::Awake => (),
+                Sleep::Woke(time) => watcher.graph_no_reload().seek(time),
+                Sleep::Asleep => {
+                    let mut target = display.draw();
+                    target.clear_color(0.0, 0.0, 0.0, clear_alpha);
+                    target.finish().unwrap();
+                    *control_flow = ControlFlow::WaitUntil(Instant::now() + Duration::from_secs(1));
+                    return;
+                },
+            }
+        }
+
+        keymap.reload();
+        // commands act like the matching key bindings
+        let mut requests = Requests::new(control.as_ref().and_then(Control::next));
+        let action = requests
+            .action
+            .or_else(|| key.and_then(|key| keymap.action(modifiers, key)));
+        let mut param = requests.set.take();
+        if let Some((node, uniform, value)) = &param {
+            params.insert((node.clone(), uniform.clone()), *value);
+        }
+
+        // switch projects before getting the graph, forgetting
+        // what was bypassed and set in the last, see `--cycle`
+        let switch = projects.as_ref().and_then(|projects| {
+            let cycle = args.cycle.map(|cycle| cycle.0);
+            projects.wanted(action, requests.project.as_deref(), cycle)
+        });
+        if let (Some(projects), Some(switch)) = (&mut projects, switch) {
+            // fade from the last frame shown
+            let last = watcher.graph_no_reload();
+            let last = last.get_outputs().first().and_then(|id| last.texture(*id));
+            if let (Some(crossfade), Some(last)) = (&mut presenter.crossfade, last) {
+                if let Err(e) = crossfade.start(last) {
+                    eprintln!("[warn] {}", e);
+                }
+            }
+            match projects.go(switch, &mut watcher, args.verify).cloned() {
+                Ok(shown) => {
+                    eprintln!("[info] Showing project `{}`, {} of {}", shown.name, projects.active() + 1, projects.len());
+                    project = shown.dir.clone();
+                    lisp_config = shown.graph();
+                    relock = args.lock;
+                    bypassed.clear();
+                    params.clear();
+                    soloed = None;
+                    palette = None;
+                    if let Some(host) = &sync_host {
+                        host.send_project(&project, &lisp_config, args.allow_outside);
+                    }
+                },
+                Err(e) => eprintln!("[warn] {}", e),
+            }
+        }
+        let switching = matches!(action, Some(Action::NextProject | Action::PreviousProject | Action::Project(_)));
+        if projects.is_none() && (switching || requests.project.is_some()) {
+            eprintln!("[warn] There are no projects to switch between, list them in `{}`", reload::PROJECTS_FILE);
+        }
+
+        if relock {
+            relock = false;
+            write_lock(&project, watcher.files());
+        }
+
+        // followers build whatever the host sends instead
+        let mut sources = None;
+        match follower.as_ref().and_then(SyncFollower::next) {
+            Some(SyncMessage::Sources { lisp, shaders }) => sources = Some((lisp, shaders)),
+            Some(SyncMessage::Set { node, uniform, value }) => {
+                params.insert((node.clone(), uniform.clone()), value);
+                param = Some((node, uniform, value));
+            },
+            None => (),
+        }
 
         // get the graph, notify if updated
-        let (graph, watch_result) = watcher.graph();
+        let frame_start = Instant::now();
+        let (graph, watch_result) = match sources {
+            Some((lisp, shaders)) => watcher.set_sources(lisp, shaders),
+            // a verified graph is never rebuilt from files that
+            // may have changed since
+            None if args.verify => {
+                if action == Some(Action::Reload) {
+                    eprintln!("[warn] Graphs aren't rebuilt with --verify");
+                }
+                (watcher.graph_no_reload(), reload::WatchResult::NoChange)
+            },
+            None if action == Some(Action::Reload) => watcher.graph_force_reload(),
+            None => watcher.graph(),
+        };
+        // a fresh graph has never run, so it runs once even
+        // while paused, to have outputs to show
+        let rebuilt = matches!(watch_result, reload::WatchResult::Rebuilt);
         match watch_result {
             reload::WatchResult::NoChange => (),
-            reload::WatchResult::Rebuilt => eprintln!("[info] Graph rebuilt"),
+            reload::WatchResult::Rebuilt => {
+                eprintln!("[info] Graph rebuilt");
+                relock = args.lock;
+                keep_sources(&project, graph);
+                if args.thumbnail {
+                    write_thumbnail(&display, &project, &lisp_config, &options);
+                }
+                // keep nodes bypassed and params set across
+                // reloads
+                for name in bypassed.iter() {
+                    let _ = graph.set_node_enabled(name, false);
+                }
+                for ((node, uniform), value) in params.iter() {
+                    let _ = graph.set_param(node, uniform, *value);
+                }
+                if let Some(archive) = &mut archive {
+                    if let Err(e) = archive.snapshot(unix_time()) {
+                        eprintln!("[warn] {}", e);
+                    }
+                }
+                if let Some(host) = &sync_host {
+                    host.send_project(&project, &lisp_config, args.allow_outside);
+                }
+                if graph.set_solo(soloed.as_deref()).is_err() {
+                    soloed = None;
+                }
+                if let Some(name) = &palette {
+                    if graph.set_palette(name).is_err() {
+                        palette = None;
+                    }
+                }
+                seed_graph(graph, args.seed);
+                if paused {
+                    graph.pause();
+                }
+            },
             reload::WatchResult::Err(e) => {
                 eprintln!("[warn] Could not rebuild graph:");
-                eprintln!("{}", e);
+                diagnostic::report(args.message_format, &e);
+            }
+        }
+
+        #[cfg(feature = "knobs")]
+        if let Some(knobs) = &mut knobs {
+            knobs.turn(graph);
+        }
+
+        if let Some((node, uniform, value)) = param {
+            if let Some(host) = &sync_host {
+                host.send(&SyncMessage::Set {
+                    node: node.clone(),
+                    uniform: uniform.clone(),
+                    value,
+                });
+            }
+            match graph.set_param(&node, &uniform, value) {
+                Ok(()) => eprintln!("[info] Set {}.{} to {}", node, uniform, value),
+                Err(e) => eprintln!("[warn] {}", e),
             }
         }
 
+        if let Some(path) = requests.load_state.take() {
+            load_graph_state(graph, &path);
+        }
+        requests.apply(graph, &mut palette);
+
+        // runs the graph once while paused
+        let mut stepped = false;
+        match action {
+            Some(Action::Pause) => {
+                // resuming also unfreezes
+                let pause = !paused;
+                scrub = None;
+                set_paused(graph, &mut paused, pause);
+            },
+            Some(Action::Freeze) => match (&history, scrub.take()) {
+                (None, _) => eprintln!("[warn] Pass --history to keep frames to step through"),
+                (Some(_), Some(_)) => set_paused(graph, &mut paused, false),
+                (Some(history), None) => {
+                    set_paused(graph, &mut paused, true);
+                    scrub = Some(0);
+                    eprintln!("[info] Frozen, {} frames kept", history.len());
+                },
+            },
+            Some(Action::StepBack) | Some(Action::StepForward) => {
+                if let (Some(history), Some(back)) = (&history, scrub) {
+                    let back = match action {
+                        Some(Action::StepBack) => (back + 1).min(history.len().saturating_sub(1)),
+                        _ => back.saturating_sub(1),
+                    };
+                    scrub = Some(back);
+                    eprintln!("[info] Showing the frame {} back", back);
+                }
+            },
+            Some(Action::StepFrame) => {
+                set_paused(graph, &mut paused, true);
+                scrub = None;
+                graph.step_frame(frame_nanos as f32 / 1e9);
+                stepped = true;
+            },
+            Some(Action::Slower) | Some(Action::Faster) | Some(Action::NormalSpeed) => {
+                let speed = match action {
+                    Some(Action::Slower) => graph.speed() / 2.0,
+                    Some(Action::Faster) => graph.speed() * 2.0,
+                    _ => 1.0,
+                };
+                graph.set_speed(speed);
+                eprintln!("[info] Running at {}x speed", speed);
+            },
+            Some(Action::SeekBack) | Some(Action::SeekForward) | Some(Action::Rewind) => {
+                let time = match action {
+                    Some(Action::SeekBack) => (graph.time() - SEEK_SECONDS).max(0.0),
+                    Some(Action::SeekForward) => graph.time() + SEEK_SECONDS,
+                    _ => 0.0,
+                };
+                graph.seek(time);
+                eprintln!("[info] Moved to {:.2}s", time);
+            },
+            Some(Action::ResetFeedback) => match graph.reset_feedback() {
+                Ok(reset) => {
+                    eprintln!("[info] Reset the state of {} node(s)", reset);
+                    seed_graph(graph, args.seed);
+                },
+                Err(e) => eprintln!("[warn] Could not reset feedback: {}", e),
+            },
+            Some(Action::Regenerate) => match graph.regenerate(None) {
+                Ok(made) => eprintln!("[info] Regenerated {} random texture(s)", made),
+                Err(e) => eprintln!("[warn] Could not regenerate: {}", e),
+            },
+            Some(Action::Fullscreen) => {
+                let gl_window = display.gl_window();
+                let window = gl_window.window();
+                match window.fullscreen() {
+                    Some(_) => window.set_fullscreen(None),
+                    None => window.set_fullscreen(Some(Fullscreen::Borderless(None))),
+                }
+            },
+            Some(Action::Bypass(index)) => toggle_bypass(graph, &mut bypassed, index),
+            Some(Action::Solo(index)) => toggle_solo(graph, &mut soloed, index),
+            Some(Action::Unsolo) => unsolo(graph, &mut soloed),
+            Some(Action::Palette) => match graph.next_palette() {
+                Some(name) => {
+                    eprintln!("[info] Switched to palette `{}`", name);
+                    palette = Some(name.to_string());
+                },
+                None => eprintln!("[warn] The graph has no palettes"),
+            },
+            Some(Action::Picker) => {
+                picker = !picker;
+                if !picker {
+                    display.gl_window().window().set_title(&title);
+                }
+            },
+            Some(Action::Capture) => {
+                #[cfg(feature = "renderdoc")]
+                match &renderdoc {
+                    Ok(renderdoc) => {
+                        renderdoc.trigger_capture();
+                        eprintln!("[info] Capturing the next frame");
+                    },
+                    Err(e) => eprintln!("[warn] {}", e),
+                }
+                #[cfg(not(feature = "renderdoc"))]
+                eprintln!("[warn] Built without the `renderdoc` feature, can not capture frames");
+            },
+            Some(
+                action @ (Action::ColorBlindness
+                | Action::ResetView
+                | Action::Compare
+                | Action::Scopes
+                | Action::Plots
+                | Action::Guides),
+            ) => presenter.act(action),
+            Some(Action::Record) => recording = !recording,
+            Some(Action::Screenshot) | Some(Action::Reload) | None => (),
+            Some(Action::NextProject) | Some(Action::PreviousProject) | Some(Action::Project(_)) => (),
+        }
+
         // get the input and output handles
         let input_nodes = graph.get_inputs();
         let output = if let [output] = graph.get_outputs().as_slice() {
@@ -319,17 +3935,224 @@ fn run(args: Run) {
         #[allow(unused_mut)]
         let mut input_map = BTreeMap::new();
 
+        // locked machines share a clock, counted in frames
+        if let Some(lock) = &mut frame_lock {
+            match lock.barrier() {
+                Ok(frame) => {
+                    let elapsed = Duration::from_nanos(frame_nanos * frame);
+                    graph.seek(elapsed.as_secs_f32());
+                },
+                Err(e) => {
+                    eprintln!("[warn] {}, running on alone", e);
+                    frame_lock = None;
+                },
+            }
+        }
+
+        // when interpolating, the graph only runs on some frames
+        let phase = tick % interpolate;
+        let running = (!paused && phase == 0) || stepped;
+        if !paused {
+            tick = tick.wrapping_add(1);
+        }
+
         #[cfg(feature = "ffmpeg")]
         for (node_id, texture) in input_nodes.iter().zip(input_textures.iter_mut()) {
-            input_map.insert(*node_id, texture.next_frame());
+            let frame = if running { texture.next_frame() } else { texture.current_frame() };
+            input_map.insert(*node_id, frame);
+        }
+
+        if let Some(controller) = &controller {
+            graph.set_quality(controller.quality());
+        }
+        graph.set_canvas(canvas, tile);
+        // graphs are profiled from the first pass after each
+        // rebuild, see `set_profiling`
+        if timing.is_some() {
+            if let Err(e) = graph.set_profiling(true) {
+                eprintln!("[warn] {}", e);
+                timing = None;
+            }
+        }
+        if gpu_timeout.is_some() {
+            if let Err(e) = graph.set_gpu_timeout(gpu_timeout) {
+                eprintln!("[warn] Can't time out slow frames: {}", e);
+                gpu_timeout = None;
+            }
+        }
+        graph.set_mouse(mouse);
+        // only the first frame the button is down
+        mouse[3] = -mouse[3].abs();
+        if let Err(e) = graph.set_keyboard(&keyboard) {
+            eprintln!("[warn] {}", e);
         }
+        keyboard.end_frame();
+        if let Some(plots) = presenter.plots.as_mut().filter(|_| running) {
+            plots.sample(|name| graph.float_uniform(name));
+        }
+        // read before the outputs borrow the graph
+        let meta = match sinks {
+            None if !args.sink.is_empty() => graph.meta().clone(),
+            _ => Meta::default(),
+        };
+        let output_map = if running || rebuilt {
+            graph.forward(input_map)
+        } else {
+            graph.pull_outputs(input_map)
+        };
+
+        if let Some(post) = &mut post {
+            match post.reload() {
+                reload::WatchResult::NoChange => (),
+                reload::WatchResult::Rebuilt => eprintln!("[info] Post chain rebuilt"),
+                reload::WatchResult::Err(e) => {
+                    eprintln!("[warn] Could not rebuild post chain:");
+                    diagnostic::report(args.message_format, &e);
+                },
+            }
+        }
+        let output_texture = match &mut post {
+            Some(post) if running => post.forward(output_map[&output]),
+            Some(post) => post.pull(output_map[&output]),
+            None => output_map[&output],
+        };
 
-        let output_map = graph.forward(input_map);
+        if let (true, Some(history)) = (running, &mut history) {
+            if let Err(e) = history.push(output_texture) {
+                eprintln!("[warn] {}", e);
+            }
+        }
+        let output_texture = match (scrub, &history) {
+            (Some(back), Some(history)) => history.get(back).unwrap_or(output_texture),
+            _ => output_texture,
+        };
+
+        let shown = match &mut interpolator {
+            Some(interpolator) if !paused => {
+                if running {
+                    if let Err(e) = interpolator.push(output_texture) {
+                        eprintln!("[warn] {}", e);
+                    }
+                }
+                interpolator.frame(phase as f32 / interpolate as f32)
+            },
+            _ => output_texture,
+        };
 
         // set up the draw target and draw
         let mut target = display.draw();
-        target.clear_color(0.0, 0.0, 0.0, 1.0);
-        util::texture(&display, &mut target, output_map[&output]);
+        target.clear_color(0.0, 0.0, 0.0, clear_alpha);
+        presenter.draw(&mut target, shown);
         target.finish().unwrap();
+        pacer.present();
+
+        // reading back a pixel stalls the pipeline, so only do it while picking
+        if let (true, Some(pointer)) = (picker, pointer) {
+            let window = display.get_framebuffer_dimensions();
+            if let Some(pixel) = pick(output_texture, window, presenter.rotation, presenter.view, pointer) {
+                display.gl_window().window().set_title(&format!("{} - {}", title, pixel));
+                if click {
+                    eprintln!("[info] Picked {}", pixel);
+                }
+            }
+        }
+
+        if action == Some(Action::Screenshot) {
+            let path = requests.screenshot.take().unwrap_or_else(|| {
+                PathBuf::from(format!("screenshot-{}.png", unix_time()))
+            });
+            png::write_png(output_texture, &path);
+        }
+
+        match (recording, recorder.take()) {
+            (true, Some(mut current)) => {
+                current.push(output_texture);
+                recorder = Some(current);
+            },
+            (true, None) => {
+                let format = args.record.unwrap_or(RecordFormat::PngSeq);
+                let size = (output_texture.width(), output_texture.height());
+                match Recorder::start(format, &args.record_dir, size, max_fps, unix_time()) {
+                    Ok(mut started) => {
+                        eprintln!("[info] Recording to `{}`", started.path().display());
+                        started.push(output_texture);
+                        recorder = Some(started);
+                    },
+                    Err(e) => {
+                        eprintln!("[warn] {}", e);
+                        recording = false;
+                    },
+                }
+            },
+            (false, Some(finished)) => finish_recording(finished),
+            (false, None) => (),
+        }
+
+        if running && !args.sink.is_empty() {
+            let sinks = sinks.get_or_insert_with(|| {
+                let size = (output_texture.width(), output_texture.height());
+                let mut sinks = Sinks::new();
+                for spec in args.sink.iter() {
+                    match spec.open(display.get_context(), size, max_fps, true, &meta, None) {
+                        Ok(opened) => {
+                            eprintln!("[info] Writing to `{}`", spec.path.display());
+                            sinks.add(opened);
+                        },
+                        Err(e) => eprintln!("[warn] {}", e),
+                    }
+                }
+                sinks
+            });
+            sinks.push(output_texture);
+        }
+        #[cfg(feature = "ndi")]
+        publish(&mut ndi_senders, |node| node.is_none().then_some(output_texture));
+        #[cfg(target_os = "linux")]
+        publish(&mut dmabuf_exporters, |node| node.is_none().then_some(output_texture));
+        #[cfg(target_os = "linux")]
+        publish(&mut pipewire_sources, |node| node.is_none().then_some(output_texture));
+
+        // see `ShaderGraph::add_window`, and `--window`
+        node_windows.show(window_target, &display, graph, &args.window);
+
+        #[cfg(feature = "ndi")]
+        publish(&mut ndi_senders, |node| {
+            graph.node_id(node.as_ref()?).and_then(|id| graph.texture(id))
+        });
+        #[cfg(target_os = "linux")]
+        publish(&mut dmabuf_exporters, |node| {
+            graph.node_id(node.as_ref()?).and_then(|id| graph.texture(id))
+        });
+        #[cfg(target_os = "linux")]
+        publish(&mut pipewire_sources, |node| {
+            graph.node_id(node.as_ref()?).and_then(|id| graph.texture(id))
+        });
+        if let (Some(playback), Some(node)) = (&mut playback, &args.sonify) {
+            // silence while the node is missing, e.g. mid-edit
+            let texture = graph.node_id(node).and_then(|id| graph.texture(id));
+            playback.play(&texture.map(audio::waveform).unwrap_or_default());
+        }
+
+        if let (true, Some(timing)) = (running, &mut timing) {
+            if let Err(e) = timing.record(graph) {
+                eprintln!("[warn] {}", e);
+            }
+        }
+
+        if let Some(controller) = &mut controller {
+            controller.update(frame_start.elapsed());
+        }
+
+        if let Some(control) = &control {
+            control.set_status(Status {
+                pacing:   pacer.stats(),
+                frame_ms: frame_start.elapsed().as_secs_f64() * 1000.0,
+                paused,
+                time:     graph.time(),
+                speed:    graph.speed(),
+                nodes:    graph.node_names().map(|(name, _)| name.to_string()).collect(),
+                soloed:   soloed.clone(),
+            });
+        }
     });
 }
@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+
+use serde_json::{
+    json,
+    Value,
+};
+
+/// Who made a piece, and what it's called, declared in the
+/// lisp with `(meta :title "..." :author "..." ...)`. It's
+/// carried into bundles, videos exported from the piece,
+/// and `check --json`, so that galleries can credit it
+/// without being told separately.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Meta {
+    pub title:   Option<String>,
+    pub author:  Option<String>,
+    pub license: Option<String>,
+    /// An image of the piece, e.g. for a gallery to show
+    /// before running it.
+    pub preview: Option<PathBuf>,
+}
+
+impl Meta {
+    pub fn is_empty(&self) -> bool { *self == Meta::default() }
+
+    /// Tags for a video, as `key=value` in the names that
+    /// `ffmpeg` and most players use.
+    pub fn video_tags(&self) -> Vec<String> {
+        [
+            ("title", &self.title),
+            ("artist", &self.author),
+            ("copyright", &self.license),
+        ]
+        .iter()
+        .filter_map(|(key, value)| {
+            value.as_ref().map(|value| format!("{}={}", key, value))
+        })
+        .collect()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "title": self.title,
+            "author": self.author,
+            "license": self.license,
+            "preview": self.preview.as_ref().map(|p| p.display().to_string()),
+        })
+    }
+
+    /// Reads what [`Meta::to_json`] wrote, missing fields
+    /// being unset.
+    pub fn from_json(json: &Value) -> Result<Meta, String> {
+        let field = |key: &str| match &json[key] {
+            Value::Null => Ok(None),
+            Value::String(value) => Ok(Some(value.clone())),
+            _ => Err(format!("Expected `{}` to be a string", key)),
+        };
+        Ok(Meta {
+            title:   field("title")?,
+            author:  field("author")?,
+            license: field("license")?,
+            preview: field("preview")?.map(PathBuf::from),
+        })
+    }
+}
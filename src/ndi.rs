@@ -0,0 +1,224 @@
+//! Publishes textures over NDI, so the output of a graph
+//! can be composited live in OBS, Resolume, TouchDesigner,
+//! or anything else that receives NDI, on this machine or
+//! across the network. The NDI runtime is loaded when the
+//! first source is created, from `NDI_RUNTIME_DIR_V6` or
+//! `NDI_RUNTIME_DIR_V5` if set, and else from the library
+//! path, so shadergarden builds without the NDI SDK.
+
+use std::{
+    ffi::CString,
+    os::raw::{
+        c_char,
+        c_int,
+        c_void,
+    },
+};
+
+use glium::Texture2d;
+
+use crate::sink::{
+    read_frame,
+    OutputSink,
+};
+
+/// `NDIlib_FourCC_video_type_RGBA`, 8 bits per channel,
+/// straight alpha.
+const FOURCC_RGBA: c_int = i32::from_le_bytes(*b"RGBA");
+
+/// `NDIlib_frame_format_type_progressive`.
+const PROGRESSIVE: c_int = 1;
+
+/// `NDIlib_send_timecode_synthesize`, to let NDI timestamp
+/// frames as they are sent.
+const SYNTHESIZE_TIMECODE: i64 = i64::MAX;
+
+/// `NDIlib_send_create_t`.
+#[repr(C)]
+struct SendCreate {
+    name:        *const c_char,
+    groups:      *const c_char,
+    clock_video: bool,
+    clock_audio: bool,
+}
+
+/// `NDIlib_video_frame_v2_t`.
+#[repr(C)]
+struct VideoFrame {
+    xres:                 c_int,
+    yres:                 c_int,
+    fourcc:               c_int,
+    frame_rate_n:         c_int,
+    frame_rate_d:         c_int,
+    picture_aspect_ratio: f32,
+    frame_format_type:    c_int,
+    timecode:             i64,
+    data:                 *const u8,
+    line_stride:          c_int,
+    metadata:             *const c_char,
+    timestamp:            i64,
+}
+
+type Initialize = unsafe extern "C" fn() -> bool;
+type SendCreateFn = unsafe extern "C" fn(*const SendCreate) -> *mut c_void;
+type SendDestroy = unsafe extern "C" fn(*mut c_void);
+type SendVideoAsync = unsafe extern "C" fn(*mut c_void, *const VideoFrame);
+
+/// The functions used from the NDI runtime.
+struct Library {
+    send_create:  SendCreateFn,
+    send_destroy: SendDestroy,
+    send_video:   SendVideoAsync,
+}
+
+impl Library {
+    #[cfg(unix)]
+    fn load() -> Result<Library, String> {
+        #[cfg(target_os = "macos")]
+        const NAMES: &[&str] = &["libndi.dylib", "/usr/local/lib/libndi.dylib"];
+        #[cfg(not(target_os = "macos"))]
+        const NAMES: &[&str] = &["libndi.so.6", "libndi.so.5", "libndi.so"];
+
+        let mut paths = vec![];
+        for var in ["NDI_RUNTIME_DIR_V6", "NDI_RUNTIME_DIR_V5"] {
+            if let Some(dir) = std::env::var_os(var) {
+                let dir = std::path::PathBuf::from(dir);
+                paths.extend(NAMES.iter().map(|name| dir.join(name)));
+            }
+        }
+        paths.extend(NAMES.iter().map(std::path::PathBuf::from));
+
+        // safety: the library is the NDI runtime, whose
+        // functions have the signatures from Processing.NDI.Lib.h
+        unsafe {
+            let library = paths
+                .iter()
+                .filter_map(|path| {
+                    CString::new(path.to_string_lossy().as_bytes()).ok()
+                })
+                .map(|path| libc::dlopen(path.as_ptr(), libc::RTLD_NOW))
+                .find(|library| !library.is_null())
+                .ok_or_else(|| {
+                    "Could not find the NDI runtime, install it from ndi.video \
+                     or set NDI_RUNTIME_DIR_V6"
+                        .to_string()
+                })?;
+
+            let symbol = |name: &[u8]| {
+                let symbol = libc::dlsym(library, name.as_ptr() as *const _);
+                if symbol.is_null() {
+                    Err("The NDI runtime is missing functions, update it"
+                        .to_string())
+                } else {
+                    Ok(symbol)
+                }
+            };
+            let initialize: Initialize =
+                std::mem::transmute(symbol(b"NDIlib_initialize\0")?);
+            let send_create: SendCreateFn =
+                std::mem::transmute(symbol(b"NDIlib_send_create\0")?);
+            let send_destroy: SendDestroy =
+                std::mem::transmute(symbol(b"NDIlib_send_destroy\0")?);
+            let send_video: SendVideoAsync = std::mem::transmute(symbol(
+                b"NDIlib_send_send_video_async_v2\0",
+            )?);
+            if !initialize() {
+                return Err("This CPU is not supported by NDI".to_string());
+            }
+            Ok(Library {
+                send_create,
+                send_destroy,
+                send_video,
+            })
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn load() -> Result<Library, String> {
+        Err("Publishing over NDI is only supported on Unix".to_string())
+    }
+}
+
+/// An NDI source, sent every frame pushed to it. NDI takes
+/// frames in memory, so each is read back from the GPU
+/// once, then compressed and sent by NDI in the background.
+pub struct NdiSender {
+    source:   String,
+    library:  Library,
+    instance: *mut c_void,
+    fps:      f64,
+    /// The frame being sent, which NDI reads from until the
+    /// next is sent.
+    sending:  Option<Vec<u8>>,
+}
+
+impl NdiSender {
+    /// Announces a source on the network, under a name,
+    /// for frames pushed at `fps`.
+    pub fn new(source: &str, fps: f64) -> Result<NdiSender, String> {
+        let library = Library::load()?;
+        let name = CString::new(source)
+            .map_err(|_| format!("`{}` is not a valid NDI name", source))?;
+        let create = SendCreate {
+            name:        name.as_ptr(),
+            groups:      std::ptr::null(),
+            // the runner already paces frames
+            clock_video: false,
+            clock_audio: false,
+        };
+
+        // safety: the settings are valid for the call, and NDI
+        // copies the name
+        let instance = unsafe { (library.send_create)(&create) };
+        if instance.is_null() {
+            return Err(format!("Could not create NDI source `{}`", source));
+        }
+        Ok(NdiSender {
+            source: source.to_string(),
+            library,
+            instance,
+            fps,
+            sending: None,
+        })
+    }
+}
+
+impl OutputSink for NdiSender {
+    fn name(&self) -> String { format!("NDI {}", self.source) }
+
+    fn push(&mut self, texture: &Texture2d) -> Result<(), String> {
+        let (width, height) = texture.dimensions();
+        let pixels = read_frame(texture).into_raw();
+        let frame = VideoFrame {
+            xres:                 width as c_int,
+            yres:                 height as c_int,
+            fourcc:               FOURCC_RGBA,
+            frame_rate_n:         (self.fps * 1000.0).round() as c_int,
+            frame_rate_d:         1000,
+            picture_aspect_ratio: 0.0,
+            frame_format_type:    PROGRESSIVE,
+            timecode:             SYNTHESIZE_TIMECODE,
+            data:                 pixels.as_ptr(),
+            line_stride:          width as c_int * 4,
+            metadata:             std::ptr::null(),
+            timestamp:            0,
+        };
+
+        // safety: the pixels are kept until the next frame is
+        // sent, which is when NDI is done with them
+        unsafe { (self.library.send_video)(self.instance, &frame) };
+        self.sending = Some(pixels);
+        Ok(())
+    }
+}
+
+impl Drop for NdiSender {
+    fn drop(&mut self) {
+        // safety: sending no frame waits for the last to be
+        // done with, before the source goes away
+        unsafe {
+            (self.library.send_video)(self.instance, std::ptr::null());
+            (self.library.send_destroy)(self.instance);
+        }
+    }
+}
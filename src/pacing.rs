@@ -0,0 +1,110 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::Path,
+    time::{
+        Duration,
+        Instant,
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+
+/// Counts of presented, late, and dropped frames since a
+/// [`FramePacer`] was created.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PacingStats {
+    pub frames:  u64,
+    /// Frames presented later than one and a half target
+    /// intervals after the previous one.
+    pub late:    u64,
+    /// Estimated number of frames that were never
+    /// presented, e.g. a frame presented two intervals
+    /// late means one frame was dropped.
+    pub dropped: u64,
+    pub worst:   Duration,
+    pub mean:    Duration,
+}
+
+/// Tracks when frames are presented, to verify playback
+/// stays smooth over long runs. Each late frame is logged
+/// with a timestamp, to a file if one is given.
+#[derive(Debug)]
+pub struct FramePacer {
+    target: Duration,
+    last:   Option<Instant>,
+    total:  Duration,
+    stats:  PacingStats,
+    log:    Option<File>,
+}
+
+impl FramePacer {
+    pub fn new(target: Duration) -> FramePacer {
+        FramePacer {
+            target,
+            last: None,
+            total: Duration::ZERO,
+            stats: PacingStats::default(),
+            log: None,
+        }
+    }
+
+    /// Appends late frames to a log file instead of writing
+    /// them to stderr.
+    pub fn log_to(mut self, path: &Path) -> Result<FramePacer, String> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| {
+                format!("Could not open pacing log `{}`: {}", path.display(), e)
+            })?;
+        self.log = Some(file);
+        Ok(self)
+    }
+
+    /// Records that a frame was just presented.
+    pub fn present(&mut self) {
+        let now = Instant::now();
+        let interval = match self.last.replace(now) {
+            Some(last) => now - last,
+            None => return,
+        };
+
+        self.stats.frames += 1;
+        self.total += interval;
+        self.stats.mean = Duration::from_secs_f64(
+            self.total.as_secs_f64() / self.stats.frames as f64,
+        );
+        self.stats.worst = self.stats.worst.max(interval);
+
+        if interval.as_secs_f64() < self.target.as_secs_f64() * 1.5 {
+            return;
+        }
+
+        let dropped =
+            (interval.as_secs_f64() / self.target.as_secs_f64()).round() as u64;
+        let dropped = dropped.saturating_sub(1);
+        self.stats.late += 1;
+        self.stats.dropped += dropped;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let line = format!(
+            "[warn] {:.3} late frame: {:.1}ms since the last, {} dropped",
+            timestamp,
+            interval.as_secs_f64() * 1000.0,
+            dropped,
+        );
+        match &mut self.log {
+            Some(file) => {
+                let _ = writeln!(file, "{}", line);
+            },
+            None => eprintln!("{}", line),
+        }
+    }
+
+    pub fn stats(&self) -> PacingStats { self.stats }
+}
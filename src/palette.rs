@@ -0,0 +1,59 @@
+/// A named set of colors, declared in the lisp with
+/// `(palette <name> <colors...>)`. The graph passes the
+/// active palette to every node, and the runner can switch
+/// between palettes while the graph runs, see
+/// [`crate::graph::ShaderGraph::set_palette`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    pub name:   String,
+    /// RGBA colors in `[0, 1]`.
+    pub colors: Vec<[f32; 4]>,
+}
+
+impl Palette {
+    /// Parses each color with [`parse_color`].
+    pub fn new(name: &str, colors: &[&str]) -> Result<Palette, String> {
+        if colors.is_empty() {
+            return Err(format!("Palette `{}` has no colors", name));
+        }
+        Ok(Palette {
+            name:   name.to_string(),
+            colors: colors
+                .iter()
+                .map(|color| parse_color(color))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Parses a color written in hex, as in CSS: `#rgb`,
+/// `#rgba`, `#rrggbb`, or `#rrggbbaa`. Colors without alpha
+/// are opaque.
+pub fn parse_color(color: &str) -> Result<[f32; 4], String> {
+    let invalid =
+        || format!("`{}` is not a color, expected hex like `#ff7e5f`", color);
+    let hex = color.strip_prefix('#').ok_or_else(invalid)?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+
+    // unwrap: only hex digits are left
+    let channel = |digits: &str| u8::from_str_radix(digits, 16).unwrap();
+    let channels = match hex.len() {
+        3 | 4 => hex
+            .char_indices()
+            .map(|(i, _)| channel(&hex[i..i + 1]) * 17)
+            .collect::<Vec<_>>(),
+        6 | 8 => (0..hex.len())
+            .step_by(2)
+            .map(|i| channel(&hex[i..i + 2]))
+            .collect(),
+        _ => return Err(invalid()),
+    };
+
+    let mut rgba = [1.0; 4];
+    for (out, channel) in rgba.iter_mut().zip(channels) {
+        *out = channel as f32 / 255.0;
+    }
+    Ok(rgba)
+}
@@ -0,0 +1,503 @@
+//! Publishes textures as PipeWire video sources, so that
+//! OBS, browsers, and video call apps on Linux can pick up
+//! the output of a graph like a camera. PipeWire is loaded
+//! when the first source is created, so shadergarden builds
+//! without it.
+
+use std::{
+    convert::TryInto,
+    ffi::CString,
+    os::raw::{
+        c_char,
+        c_int,
+        c_void,
+    },
+};
+
+use glium::Texture2d;
+
+use crate::sink::{
+    read_frame,
+    OutputSink,
+};
+
+const PW_DIRECTION_OUTPUT: c_int = 1;
+const PW_ID_ANY: u32 = u32::MAX;
+const PW_STREAM_FLAG_AUTOCONNECT: u32 = 1 << 0;
+const PW_STREAM_FLAG_MAP_BUFFERS: u32 = 1 << 2;
+const PW_STREAM_FLAG_DRIVER: u32 = 1 << 3;
+
+const SPA_TYPE_ID: u32 = 3;
+const SPA_TYPE_INT: u32 = 4;
+const SPA_TYPE_RECTANGLE: u32 = 10;
+const SPA_TYPE_FRACTION: u32 = 11;
+const SPA_TYPE_OBJECT: u32 = 15;
+const SPA_TYPE_OBJECT_FORMAT: u32 = 0x40003;
+const SPA_TYPE_OBJECT_PARAM_BUFFERS: u32 = 0x40004;
+const SPA_PARAM_ENUM_FORMAT: u32 = 3;
+const SPA_PARAM_FORMAT: u32 = 4;
+const SPA_PARAM_BUFFERS: u32 = 5;
+const SPA_FORMAT_MEDIA_TYPE: u32 = 1;
+const SPA_FORMAT_MEDIA_SUBTYPE: u32 = 2;
+const SPA_FORMAT_VIDEO_FORMAT: u32 = 0x20001;
+const SPA_FORMAT_VIDEO_SIZE: u32 = 0x20003;
+const SPA_FORMAT_VIDEO_FRAMERATE: u32 = 0x20004;
+const SPA_MEDIA_TYPE_VIDEO: u32 = 2;
+const SPA_MEDIA_SUBTYPE_RAW: u32 = 1;
+const SPA_VIDEO_FORMAT_RGBA: u32 = 11;
+const SPA_PARAM_BUFFERS_BUFFERS: u32 = 1;
+const SPA_PARAM_BUFFERS_BLOCKS: u32 = 2;
+const SPA_PARAM_BUFFERS_SIZE: u32 = 3;
+const SPA_PARAM_BUFFERS_STRIDE: u32 = 4;
+
+/// A callback passed an id and a pointer, like
+/// `param_changed`.
+type IdCallback = unsafe extern "C" fn(*mut c_void, u32, *const c_void);
+
+/// `struct pw_stream_events`, at version 0.
+#[repr(C)]
+struct StreamEvents {
+    version:       u32,
+    destroy:       Option<unsafe extern "C" fn(*mut c_void)>,
+    state_changed:
+        Option<unsafe extern "C" fn(*mut c_void, c_int, c_int, *const c_char)>,
+    control_info:  Option<IdCallback>,
+    io_changed:
+        Option<unsafe extern "C" fn(*mut c_void, u32, *mut c_void, u32)>,
+    param_changed: Option<IdCallback>,
+    add_buffer:    Option<unsafe extern "C" fn(*mut c_void, *mut c_void)>,
+    remove_buffer: Option<unsafe extern "C" fn(*mut c_void, *mut c_void)>,
+    process:       Option<unsafe extern "C" fn(*mut c_void)>,
+    drained:       Option<unsafe extern "C" fn(*mut c_void)>,
+}
+
+/// The start of `struct pw_buffer`.
+#[repr(C)]
+struct PwBuffer {
+    buffer: *mut SpaBuffer,
+}
+
+/// `struct spa_buffer`.
+#[repr(C)]
+struct SpaBuffer {
+    n_metas: u32,
+    n_datas: u32,
+    metas:   *mut c_void,
+    datas:   *mut SpaData,
+}
+
+/// `struct spa_data`.
+#[repr(C)]
+struct SpaData {
+    kind:      u32,
+    flags:     u32,
+    fd:        i64,
+    mapoffset: u32,
+    maxsize:   u32,
+    data:      *mut c_void,
+    chunk:     *mut SpaChunk,
+}
+
+/// `struct spa_chunk`.
+#[repr(C)]
+struct SpaChunk {
+    offset: u32,
+    size:   u32,
+    stride: i32,
+    flags:  i32,
+}
+
+type Init = unsafe extern "C" fn(*mut c_int, *mut *mut *mut c_char);
+type LoopNew =
+    unsafe extern "C" fn(*const c_char, *const c_void) -> *mut c_void;
+type LoopFn = unsafe extern "C" fn(*mut c_void);
+type LoopStart = unsafe extern "C" fn(*mut c_void) -> c_int;
+type LoopGet = unsafe extern "C" fn(*mut c_void) -> *mut c_void;
+type PropertiesNew = unsafe extern "C" fn(*const c_char, ...) -> *mut c_void;
+type StreamNew = unsafe extern "C" fn(
+    *mut c_void,
+    *const c_char,
+    *mut c_void,
+    *const StreamEvents,
+    *mut c_void,
+) -> *mut c_void;
+type StreamConnect = unsafe extern "C" fn(
+    *mut c_void,
+    c_int,
+    u32,
+    u32,
+    *mut *const c_void,
+    u32,
+) -> c_int;
+type UpdateParams =
+    unsafe extern "C" fn(*mut c_void, *mut *const c_void, u32) -> c_int;
+type Dequeue = unsafe extern "C" fn(*mut c_void) -> *mut PwBuffer;
+type Queue = unsafe extern "C" fn(*mut c_void, *mut PwBuffer) -> c_int;
+type StreamFn = unsafe extern "C" fn(*mut c_void) -> c_int;
+type StreamDestroy = unsafe extern "C" fn(*mut c_void);
+
+/// The functions used from PipeWire.
+struct Library {
+    loop_new:        LoopNew,
+    loop_get:        LoopGet,
+    loop_start:      LoopStart,
+    loop_stop:       LoopFn,
+    loop_destroy:    LoopFn,
+    loop_lock:       LoopFn,
+    loop_unlock:     LoopFn,
+    properties_new:  PropertiesNew,
+    stream_new:      StreamNew,
+    stream_connect:  StreamConnect,
+    update_params:   UpdateParams,
+    dequeue:         Dequeue,
+    queue:           Queue,
+    /// Only in PipeWire 0.3.34 and later.
+    trigger_process: Option<StreamFn>,
+    stream_destroy:  StreamDestroy,
+}
+
+impl Library {
+    fn load() -> Result<Library, String> {
+        // safety: the library is PipeWire, whose functions
+        // have the signatures from pipewire/*.h
+        unsafe {
+            let library = ["libpipewire-0.3.so.0", "libpipewire-0.3.so"]
+                .iter()
+                .filter_map(|name| CString::new(*name).ok())
+                .map(|name| libc::dlopen(name.as_ptr(), libc::RTLD_NOW))
+                .find(|library| !library.is_null())
+                .ok_or_else(|| {
+                    "Could not find PipeWire, install libpipewire-0.3"
+                        .to_string()
+                })?;
+
+            let symbol = |name: &[u8]| {
+                let symbol = libc::dlsym(library, name.as_ptr() as *const _);
+                if symbol.is_null() {
+                    Err("PipeWire is missing functions, update it".to_string())
+                } else {
+                    Ok(symbol)
+                }
+            };
+            let init: Init = std::mem::transmute(symbol(b"pw_init\0")?);
+            let loop_new: LoopNew =
+                std::mem::transmute(symbol(b"pw_thread_loop_new\0")?);
+            let loop_get: LoopGet =
+                std::mem::transmute(symbol(b"pw_thread_loop_get_loop\0")?);
+            let loop_start: LoopStart =
+                std::mem::transmute(symbol(b"pw_thread_loop_start\0")?);
+            let loop_stop: LoopFn =
+                std::mem::transmute(symbol(b"pw_thread_loop_stop\0")?);
+            let loop_destroy: LoopFn =
+                std::mem::transmute(symbol(b"pw_thread_loop_destroy\0")?);
+            let loop_lock: LoopFn =
+                std::mem::transmute(symbol(b"pw_thread_loop_lock\0")?);
+            let loop_unlock: LoopFn =
+                std::mem::transmute(symbol(b"pw_thread_loop_unlock\0")?);
+            let properties_new: PropertiesNew =
+                std::mem::transmute(symbol(b"pw_properties_new\0")?);
+            let stream_new: StreamNew =
+                std::mem::transmute(symbol(b"pw_stream_new_simple\0")?);
+            let stream_connect: StreamConnect =
+                std::mem::transmute(symbol(b"pw_stream_connect\0")?);
+            let update_params: UpdateParams =
+                std::mem::transmute(symbol(b"pw_stream_update_params\0")?);
+            let dequeue: Dequeue =
+                std::mem::transmute(symbol(b"pw_stream_dequeue_buffer\0")?);
+            let queue: Queue =
+                std::mem::transmute(symbol(b"pw_stream_queue_buffer\0")?);
+            let trigger_process = symbol(b"pw_stream_trigger_process\0")
+                .ok()
+                .map(|symbol| std::mem::transmute::<_, StreamFn>(symbol));
+            let stream_destroy: StreamDestroy =
+                std::mem::transmute(symbol(b"pw_stream_destroy\0")?);
+
+            init(std::ptr::null_mut(), std::ptr::null_mut());
+            Ok(Library {
+                loop_new,
+                loop_get,
+                loop_start,
+                loop_stop,
+                loop_destroy,
+                loop_lock,
+                loop_unlock,
+                properties_new,
+                stream_new,
+                stream_connect,
+                update_params,
+                dequeue,
+                queue,
+                trigger_process,
+                stream_destroy,
+            })
+        }
+    }
+}
+
+/// A SPA pod, a value in PipeWire's binary format, of a
+/// type, padded to 8 bytes.
+fn pod(kind: u32, body: &[u8]) -> Vec<u8> {
+    let mut pod = vec![];
+    pod.extend_from_slice(&(body.len() as u32).to_ne_bytes());
+    pod.extend_from_slice(&kind.to_ne_bytes());
+    pod.extend_from_slice(body);
+    pod.resize(pod.len().div_ceil(8) * 8, 0);
+    pod
+}
+
+/// A pod of two numbers, e.g. a rectangle or a fraction.
+fn pod_pair(kind: u32, a: u32, b: u32) -> Vec<u8> {
+    pod(kind, &[a.to_ne_bytes(), b.to_ne_bytes()].concat())
+}
+
+/// An object pod, of properties, each a key and a pod.
+fn pod_object(kind: u32, id: u32, properties: &[(u32, Vec<u8>)]) -> Vec<u64> {
+    let mut body = [kind.to_ne_bytes(), id.to_ne_bytes()].concat();
+    for (key, value) in properties {
+        body.extend_from_slice(&key.to_ne_bytes());
+        body.extend_from_slice(&0u32.to_ne_bytes());
+        body.extend_from_slice(value);
+    }
+    // PipeWire reads pods from memory aligned to 8 bytes
+    pod(SPA_TYPE_OBJECT, &body)
+        .chunks(8)
+        .map(|word| u64::from_ne_bytes(word.try_into().unwrap()))
+        .collect()
+}
+
+/// What the stream's callbacks are passed.
+struct Shared {
+    update_params: UpdateParams,
+    stream:        *mut c_void,
+    buffers:       Vec<u64>,
+}
+
+/// Once a format is agreed on, asks for buffers that fit a
+/// frame.
+unsafe extern "C" fn param_changed(
+    data: *mut c_void,
+    id: u32,
+    _: *const c_void,
+) {
+    let shared = &*(data as *const Shared);
+    if id == SPA_PARAM_FORMAT && !shared.stream.is_null() {
+        let mut params = [shared.buffers.as_ptr() as *const c_void];
+        (shared.update_params)(shared.stream, params.as_mut_ptr(), 1);
+    }
+}
+
+/// A PipeWire video source, sent every frame pushed to it,
+/// which PipeWire copies out of memory, so each is read
+/// back from the GPU once. The stream is connected with the
+/// size of the first frame, and can't change size after.
+pub struct PipewireSource {
+    source:  String,
+    library: Library,
+    fps:     f64,
+    thread:  *mut c_void,
+    stream:  *mut c_void,
+    size:    Option<(u32, u32)>,
+    shared:  Box<Shared>,
+    events:  Box<StreamEvents>,
+}
+
+impl PipewireSource {
+    /// Starts a PipeWire thread for a source under a name,
+    /// for frames pushed at `fps`.
+    pub fn new(source: &str, fps: f64) -> Result<PipewireSource, String> {
+        let library = Library::load()?;
+        let name = CString::new("shadergarden").unwrap();
+        // safety: the name is a valid C string, which PipeWire
+        // copies
+        let thread =
+            unsafe { (library.loop_new)(name.as_ptr(), std::ptr::null()) };
+        if thread.is_null() {
+            return Err("Could not start PipeWire".to_string());
+        }
+        let shared = Box::new(Shared {
+            update_params: library.update_params,
+            stream:        std::ptr::null_mut(),
+            buffers:       vec![],
+        });
+        let events = Box::new(StreamEvents {
+            version:       0,
+            destroy:       None,
+            state_changed: None,
+            control_info:  None,
+            io_changed:    None,
+            param_changed: Some(param_changed),
+            add_buffer:    None,
+            remove_buffer: None,
+            process:       None,
+            drained:       None,
+        });
+        Ok(PipewireSource {
+            source: source.to_string(),
+            library,
+            fps,
+            thread,
+            stream: std::ptr::null_mut(),
+            size: None,
+            shared,
+            events,
+        })
+    }
+
+    /// Creates the stream and connects it, offering frames
+    /// of a size.
+    fn connect(&mut self, size: (u32, u32)) -> Result<(), String> {
+        let stride = size.0 * 4;
+        let id = |value: u32| pod(SPA_TYPE_ID, &value.to_ne_bytes());
+        let int = |value: u32| pod(SPA_TYPE_INT, &value.to_ne_bytes());
+        let fps = (self.fps * 1000.0).round() as u32;
+        let format =
+            pod_object(SPA_TYPE_OBJECT_FORMAT, SPA_PARAM_ENUM_FORMAT, &[
+                (SPA_FORMAT_MEDIA_TYPE, id(SPA_MEDIA_TYPE_VIDEO)),
+                (SPA_FORMAT_MEDIA_SUBTYPE, id(SPA_MEDIA_SUBTYPE_RAW)),
+                (SPA_FORMAT_VIDEO_FORMAT, id(SPA_VIDEO_FORMAT_RGBA)),
+                (
+                    SPA_FORMAT_VIDEO_SIZE,
+                    pod_pair(SPA_TYPE_RECTANGLE, size.0, size.1),
+                ),
+                (
+                    SPA_FORMAT_VIDEO_FRAMERATE,
+                    pod_pair(SPA_TYPE_FRACTION, fps, 1000),
+                ),
+            ]);
+        self.shared.buffers =
+            pod_object(SPA_TYPE_OBJECT_PARAM_BUFFERS, SPA_PARAM_BUFFERS, &[
+                (SPA_PARAM_BUFFERS_BUFFERS, int(4)),
+                (SPA_PARAM_BUFFERS_BLOCKS, int(1)),
+                (SPA_PARAM_BUFFERS_SIZE, int(stride * size.1)),
+                (SPA_PARAM_BUFFERS_STRIDE, int(stride)),
+            ]);
+
+        let library = &self.library;
+        let name = CString::new(self.source.as_str()).map_err(|_| {
+            format!("`{}` is not a valid PipeWire name", self.source)
+        })?;
+        let text = |text: &str| CString::new(text).unwrap();
+        let class = text("media.class");
+        let video = text("Video/Source");
+        let node = text("node.name");
+        let description = text("node.description");
+
+        // safety: the strings outlive the calls, PipeWire
+        // copies them, and the events and what they're passed
+        // are boxed, so they outlive the stream
+        unsafe {
+            (library.loop_lock)(self.thread);
+            let properties = (library.properties_new)(
+                class.as_ptr(),
+                video.as_ptr(),
+                node.as_ptr(),
+                name.as_ptr(),
+                description.as_ptr(),
+                name.as_ptr(),
+                std::ptr::null::<c_char>(),
+            );
+            let stream = (library.stream_new)(
+                (library.loop_get)(self.thread),
+                name.as_ptr(),
+                properties,
+                &*self.events,
+                &mut *self.shared as *mut Shared as *mut c_void,
+            );
+            let connected = !stream.is_null() && {
+                self.shared.stream = stream;
+                let mut params = [format.as_ptr() as *const c_void];
+                (library.stream_connect)(
+                    stream,
+                    PW_DIRECTION_OUTPUT,
+                    PW_ID_ANY,
+                    PW_STREAM_FLAG_AUTOCONNECT
+                        | PW_STREAM_FLAG_MAP_BUFFERS
+                        | PW_STREAM_FLAG_DRIVER,
+                    params.as_mut_ptr(),
+                    1,
+                ) >= 0
+            };
+            (library.loop_unlock)(self.thread);
+            if !stream.is_null() {
+                self.stream = stream;
+            }
+            if !connected {
+                return Err(format!(
+                    "Could not create PipeWire source `{}`",
+                    self.source
+                ));
+            }
+            if (library.loop_start)(self.thread) < 0 {
+                return Err("Could not start PipeWire".to_string());
+            }
+        }
+        self.size = Some(size);
+        Ok(())
+    }
+}
+
+impl OutputSink for PipewireSource {
+    fn name(&self) -> String { format!("PipeWire {}", self.source) }
+
+    fn push(&mut self, texture: &Texture2d) -> Result<(), String> {
+        let size = texture.dimensions();
+        match self.size {
+            None => self.connect(size)?,
+            Some(connected) if connected != size => {
+                return Err(format!(
+                    "The output changed size, from {}x{} to {}x{}",
+                    connected.0, connected.1, size.0, size.1
+                ));
+            },
+            Some(_) => (),
+        }
+        let pixels = read_frame(texture).into_raw();
+        let library = &self.library;
+
+        // safety: the stream is connected, and buffers are
+        // only used with the loop locked, and written within
+        // the size PipeWire mapped for them
+        unsafe {
+            (library.loop_lock)(self.thread);
+            let buffer = (library.dequeue)(self.stream);
+            // no buffer is free until a consumer connects, or
+            // while it's behind, so the frame is dropped
+            if !buffer.is_null() && (*(*buffer).buffer).n_datas > 0 {
+                let data = &mut *(*(*buffer).buffer).datas;
+                if !data.data.is_null() {
+                    let length = pixels.len().min(data.maxsize as usize);
+                    std::ptr::copy_nonoverlapping(
+                        pixels.as_ptr(),
+                        data.data as *mut u8,
+                        length,
+                    );
+                    let chunk = &mut *data.chunk;
+                    chunk.offset = 0;
+                    chunk.size = length as u32;
+                    chunk.stride = size.0 as i32 * 4;
+                    chunk.flags = 0;
+                }
+                (library.queue)(self.stream, buffer);
+                if let Some(trigger) = library.trigger_process {
+                    trigger(self.stream);
+                }
+            }
+            (library.loop_unlock)(self.thread);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for PipewireSource {
+    fn drop(&mut self) {
+        // safety: the loop is stopped before the stream is
+        // destroyed, so no callback runs after
+        unsafe {
+            (self.library.loop_stop)(self.thread);
+            if !self.stream.is_null() {
+                (self.library.stream_destroy)(self.stream);
+            }
+            (self.library.loop_destroy)(self.thread);
+        }
+    }
+}
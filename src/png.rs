@@ -8,9 +8,12 @@ use glium::texture::{
     Texture2d,
 };
 use image::{
+    codecs::png::PngEncoder,
+    ColorType,
     ImageBuffer,
     ImageFormat,
     Rgba,
+    RgbaImage,
 };
 
 /// Pretty neat macro right here. Takes an image path, loads
@@ -19,7 +22,7 @@ use image::{
 macro_rules! include_png {
     ($path:literal) => {{
         let bytes = include_bytes!($path);
-        crate::png::image_from_bytes(bytes)
+        $crate::png::image_from_bytes(bytes)
     }};
 }
 
@@ -31,14 +34,13 @@ pub fn image_from_bytes<'a>(bytes: Vec<u8>) -> RawImage2d<'a, u8> {
     RawImage2d::from_raw_rgba_reversed(&image.into_raw(), image_dimensions)
 }
 
-pub fn load_png(path: &Path) -> RawImage2d<u8> {
+pub fn load_png(path: &Path) -> RawImage2d<'_, u8> {
     let bytes = std::fs::read(path).expect("Could not read input image");
     image_from_bytes(bytes)
 }
 
 pub fn write_png(texture: &Texture2d, path: &Path) {
-    let mut buffer =
-        ImageBuffer::new(texture.width() as u32, texture.height() as u32);
+    let mut buffer = ImageBuffer::new(texture.width(), texture.height());
 
     let sink: Vec<Vec<(u8, u8, u8, u8)>> = texture.read();
 
@@ -55,3 +57,42 @@ pub fn write_png(texture: &Texture2d, path: &Path) {
     buffer.save(&path).expect("Could not write frame");
     println!("Saved frame {}", path.display());
 }
+
+/// Encodes an image as a png, with text chunks for each
+/// `(keyword, text)`, which image viewers and tools like
+/// `exiftool` show. Keywords must be 1 to 79 letters.
+pub fn encode_with_text(
+    image: &RgbaImage,
+    text: &[(String, String)],
+) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![];
+    PngEncoder::new(&mut bytes)
+        .encode(image, image.width(), image.height(), ColorType::Rgba8)
+        .map_err(|e| e.to_string())?;
+
+    // the signature, then the header, which must come first
+    let header = 8 + 4 + 4 + 13 + 4;
+    let mut chunks = vec![];
+    for (keyword, text) in text {
+        if keyword.is_empty() || keyword.len() > 79 || !keyword.is_ascii() {
+            return Err(format!("`{}` is not a png text keyword", keyword));
+        }
+        // plain text is latin-1, so anything else is utf-8
+        // in an international text chunk, uncompressed and
+        // with no language
+        let (kind, data) = if text.is_ascii() {
+            (b"tEXt", format!("{}\0{}", keyword, text))
+        } else {
+            (b"iTXt", format!("{}\0\0\0\0\0{}", keyword, text))
+        };
+        chunks.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let mut crc = crc32fast::Hasher::new();
+        crc.update(kind);
+        crc.update(data.as_bytes());
+        chunks.extend_from_slice(kind);
+        chunks.extend_from_slice(data.as_bytes());
+        chunks.extend_from_slice(&crc.finalize().to_be_bytes());
+    }
+    bytes.splice(header..header, chunks);
+    Ok(bytes)
+}
@@ -0,0 +1,454 @@
+use std::{
+    convert::TryInto,
+    path::Path,
+};
+
+use glium::implement_vertex;
+
+/// A single point of a point cloud, as uploaded to the GPU.
+/// Positions are normalized to fit within `[-1, 1]` on
+/// load, colors are RGB in `[0, 1]`.
+#[derive(Debug, Copy, Clone)]
+pub struct Point {
+    pub position: [f32; 3],
+    pub color:    [f32; 3],
+}
+
+implement_vertex!(Point, position, color);
+
+/// Loads a point cloud from disk, picking a parser based on
+/// the file extension. Supports `.ply` (ascii and binary)
+/// and `.las` files.
+pub fn load_points(path: &Path) -> Result<Vec<Point>, String> {
+    let bytes = std::fs::read(path).map_err(|_| {
+        format!("Could not read point cloud `{}`", path.display())
+    })?;
+
+    let mut points = match path.extension().and_then(|e| e.to_str()) {
+        Some("ply") => parse_ply(&bytes)?,
+        Some("las") => parse_las(&bytes)?,
+        _ => {
+            return Err(format!(
+                "Unsupported point cloud format `{}`, expected `.ply` or \
+                 `.las`",
+                path.display()
+            ))
+        },
+    };
+
+    normalize(&mut points);
+    Ok(points)
+}
+
+/// Centers a point cloud on the origin and scales it
+/// uniformly so that it fits within `[-1, 1]` on every
+/// axis.
+pub fn normalize(points: &mut [Point]) {
+    let mut min = [f32::INFINITY; 3];
+    let mut max = [f32::NEG_INFINITY; 3];
+    for point in points.iter() {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(point.position[axis]);
+            max[axis] = max[axis].max(point.position[axis]);
+        }
+    }
+
+    let extent = (0..3).map(|a| max[a] - min[a]).fold(0.0, f32::max);
+    if extent <= 0.0 {
+        return;
+    }
+
+    for point in points.iter_mut() {
+        for axis in 0..3 {
+            let center = (min[axis] + max[axis]) / 2.0;
+            point.position[axis] =
+                (point.position[axis] - center) / extent * 2.0;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PlyFormat {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum PlyType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl PlyType {
+    fn parse(name: &str) -> Result<PlyType, String> {
+        let ty = match name {
+            "char" | "int8" => PlyType::I8,
+            "uchar" | "uint8" => PlyType::U8,
+            "short" | "int16" => PlyType::I16,
+            "ushort" | "uint16" => PlyType::U16,
+            "int" | "int32" => PlyType::I32,
+            "uint" | "uint32" => PlyType::U32,
+            "float" | "float32" => PlyType::F32,
+            "double" | "float64" => PlyType::F64,
+            other => {
+                return Err(format!("Unknown PLY property type `{}`", other))
+            },
+        };
+        Ok(ty)
+    }
+
+    fn size(self) -> usize {
+        match self {
+            PlyType::I8 | PlyType::U8 => 1,
+            PlyType::I16 | PlyType::U16 => 2,
+            PlyType::I32 | PlyType::U32 | PlyType::F32 => 4,
+            PlyType::F64 => 8,
+        }
+    }
+
+    /// Largest representable value, used to map integer
+    /// colors into `[0, 1]`.
+    fn range(self) -> f32 {
+        match self {
+            PlyType::U8 | PlyType::I8 => 255.0,
+            PlyType::U16 | PlyType::I16 => 65535.0,
+            _ => 1.0,
+        }
+    }
+
+    fn read(self, bytes: &[u8], format: PlyFormat) -> f32 {
+        macro_rules! num {
+            ($t:ty) => {{
+                let array =
+                    bytes[..std::mem::size_of::<$t>()].try_into().unwrap();
+                match format {
+                    PlyFormat::BigEndian => <$t>::from_be_bytes(array),
+                    _ => <$t>::from_le_bytes(array),
+                }
+            }};
+        }
+
+        match self {
+            PlyType::I8 => bytes[0] as i8 as f32,
+            PlyType::U8 => bytes[0] as f32,
+            PlyType::I16 => num!(i16) as f32,
+            PlyType::U16 => num!(u16) as f32,
+            PlyType::I32 => num!(i32) as f32,
+            PlyType::U32 => num!(u32) as f32,
+            PlyType::F32 => num!(f32),
+            PlyType::F64 => num!(f64) as f32,
+        }
+    }
+}
+
+/// Parses the vertex element of a PLY file.
+/// The vertex element must be the first element in the
+/// file; any elements after it (e.g. faces) are ignored.
+pub fn parse_ply(bytes: &[u8]) -> Result<Vec<Point>, String> {
+    const END: &[u8] = b"end_header";
+    let header_end = bytes
+        .windows(END.len())
+        .position(|w| w == END)
+        .ok_or("PLY file has no `end_header`")?;
+    let header = std::str::from_utf8(&bytes[..header_end])
+        .map_err(|_| "PLY header is not valid UTF8".to_string())?;
+
+    // skip past `end_header` and the newline following it
+    let mut body = header_end + END.len();
+    while body < bytes.len() && bytes[body] != b'\n' {
+        body += 1;
+    }
+    let body = &bytes[(body + 1).min(bytes.len())..];
+
+    let mut format = None;
+    let mut count = None;
+    let mut properties: Vec<(String, PlyType)> = vec![];
+    let mut in_vertex = false;
+
+    for line in header.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", "ascii", ..] => format = Some(PlyFormat::Ascii),
+            ["format", "binary_little_endian", ..] => {
+                format = Some(PlyFormat::LittleEndian)
+            },
+            ["format", "binary_big_endian", ..] => {
+                format = Some(PlyFormat::BigEndian)
+            },
+            ["element", "vertex", n] => {
+                if count.is_some() {
+                    return Err(
+                        "PLY file has multiple vertex elements".to_string()
+                    );
+                }
+                count = Some(n.parse::<usize>().map_err(|_| {
+                    format!("Invalid PLY vertex count `{}`", n)
+                })?);
+                in_vertex = true;
+            },
+            ["element", ..] => {
+                if count.is_none() {
+                    return Err(
+                        "PLY vertex element must come first".to_string()
+                    );
+                }
+                in_vertex = false;
+            },
+            ["property", "list", ..] if in_vertex => {
+                return Err("PLY vertex element can not have list properties"
+                    .to_string());
+            },
+            ["property", ty, name] if in_vertex => {
+                properties.push((name.to_string(), PlyType::parse(ty)?));
+            },
+            _ => (),
+        }
+    }
+
+    let format = format.ok_or("PLY file has no format declaration")?;
+    let count = count.ok_or("PLY file has no vertex element")?;
+    let index = |name: &str| properties.iter().position(|(n, _)| n == name);
+    let (x, y, z) = match (index("x"), index("y"), index("z")) {
+        (Some(x), Some(y), Some(z)) => (x, y, z),
+        _ => return Err("PLY vertices must have x, y, and z".to_string()),
+    };
+    let rgb = match (index("red"), index("green"), index("blue")) {
+        (Some(r), Some(g), Some(b)) => Some([r, g, b]),
+        _ => None,
+    };
+
+    // read every vertex into a row of floats, each taking at
+    // least a byte, however many the header claims
+    let mut rows = Vec::with_capacity(count.min(body.len()));
+    if format == PlyFormat::Ascii {
+        let text = std::str::from_utf8(body)
+            .map_err(|_| "PLY body is not valid UTF8".to_string())?;
+        for line in text.lines().filter(|l| !l.trim().is_empty()).take(count) {
+            let row = line
+                .split_whitespace()
+                .map(|w| w.parse::<f32>())
+                .collect::<Result<Vec<f32>, _>>()
+                .map_err(|_| format!("Invalid PLY vertex `{}`", line))?;
+            if row.len() < properties.len() {
+                return Err(format!("Invalid PLY vertex `{}`", line));
+            }
+            rows.push(row);
+        }
+    } else {
+        let stride: usize = properties.iter().map(|(_, t)| t.size()).sum();
+        for vertex in body.chunks_exact(stride).take(count) {
+            let mut offset = 0;
+            let mut row = Vec::with_capacity(properties.len());
+            for (_, ty) in properties.iter() {
+                row.push(ty.read(&vertex[offset..], format));
+                offset += ty.size();
+            }
+            rows.push(row);
+        }
+    }
+
+    if rows.len() < count {
+        return Err(format!(
+            "PLY file declares {} vertices, but only {} were found",
+            count,
+            rows.len()
+        ));
+    }
+
+    let points = rows
+        .iter()
+        .map(|row| Point {
+            position: [row[x], row[y], row[z]],
+            color:    match rgb {
+                Some(channels) => {
+                    let mut color = [0.0; 3];
+                    for (c, i) in color.iter_mut().zip(channels.iter()) {
+                        *c = row[*i] / properties[*i].1.range();
+                    }
+                    color
+                },
+                None => [1.0; 3],
+            },
+        })
+        .collect();
+
+    Ok(points)
+}
+
+/// Parses the point records of a LAS file (versions 1.0 to
+/// 1.4). Points without color are shaded by intensity.
+pub fn parse_las(bytes: &[u8]) -> Result<Vec<Point>, String> {
+    if bytes.len() < 227 || &bytes[0..4] != b"LASF" {
+        return Err("Not a valid LAS file".to_string());
+    }
+
+    let u16_at = |o: usize| u16::from_le_bytes([bytes[o], bytes[o + 1]]);
+    let u32_at =
+        |o: usize| u32::from_le_bytes(bytes[o..o + 4].try_into().unwrap());
+    let f64_at =
+        |o: usize| f64::from_le_bytes(bytes[o..o + 8].try_into().unwrap());
+
+    let minor_version = bytes[25];
+    let data_offset = u32_at(96) as usize;
+    let record_format = bytes[104] & 0x3f;
+    let record_length = u16_at(105) as usize;
+    let mut count = u32_at(107) as usize;
+    if count == 0 && minor_version >= 4 && bytes.len() >= 255 {
+        count =
+            u64::from_le_bytes(bytes[247..255].try_into().unwrap()) as usize;
+    }
+    let scale = [f64_at(131), f64_at(139), f64_at(147)];
+    let offset = [f64_at(155), f64_at(163), f64_at(171)];
+
+    let rgb_offset = match record_format {
+        2 => Some(20),
+        3 | 5 => Some(28),
+        7 | 8 | 10 => Some(30),
+        0 | 1 | 4 | 6 | 9 => None,
+        other => return Err(format!("Unsupported LAS point format {}", other)),
+    };
+
+    // the position, then the intensity or the color
+    let needed = rgb_offset.map_or(14, |o| o + 6);
+    if record_length < needed {
+        return Err(format!(
+            "LAS points of format {} take at least {} bytes, not {}",
+            record_format, needed, record_length
+        ));
+    }
+    let records = bytes
+        .get(data_offset..)
+        .ok_or("LAS point data offset is out of bounds")?;
+    let size = count.checked_mul(record_length);
+    if size.is_none_or(|size| records.len() < size) {
+        return Err(format!(
+            "LAS file declares {} points, but the file is too short",
+            count
+        ));
+    }
+
+    let points = records
+        .chunks_exact(record_length)
+        .take(count)
+        .map(|record| {
+            let i32_at = |o: usize| {
+                i32::from_le_bytes(record[o..o + 4].try_into().unwrap())
+            };
+            let u16_at = |o: usize| {
+                u16::from_le_bytes([record[o], record[o + 1]]) as f32 / 65535.0
+            };
+
+            let mut position = [0.0; 3];
+            for (axis, p) in position.iter_mut().enumerate() {
+                *p = (i32_at(axis * 4) as f64 * scale[axis] + offset[axis])
+                    as f32;
+            }
+
+            // LAS is z-up, swap so the cloud is y-up
+            position.swap(1, 2);
+
+            let color = match rgb_offset {
+                Some(o) => [u16_at(o), u16_at(o + 2), u16_at(o + 4)],
+                None => [u16_at(12); 3],
+            };
+
+            Point { position, color }
+        })
+        .collect();
+
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLY: &str = "ply\nformat ascii 1.0\nelement vertex 2\n\
+                       property float x\nproperty float y\n\
+                       property float z\nproperty uchar red\n\
+                       property uchar green\nproperty uchar blue\n\
+                       end_header\n";
+
+    #[test]
+    fn parses_ply() {
+        let ply = format!("{}0 1 2 255 0 0\n3 4 5 0 255 0\n", PLY);
+        let points = parse_ply(ply.as_bytes()).unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[1].position, [3.0, 4.0, 5.0]);
+        assert_eq!(points[1].color, [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn refuses_truncated_ply() {
+        let short = format!("{}0 1 2 255 0 0\n", PLY);
+        assert!(parse_ply(short.as_bytes()).unwrap_err().contains("only 1"));
+        let row = format!("{}0 1 2\n3 4 5\n", PLY);
+        assert!(parse_ply(row.as_bytes()).is_err());
+        assert!(parse_ply(&PLY.as_bytes()[..40]).is_err());
+
+        // a huge count isn't allocated up front
+        let huge = PLY.replace("vertex 2", &format!("vertex {}", usize::MAX));
+        assert!(parse_ply(huge.as_bytes()).is_err());
+        let binary = huge.replace("ascii", "binary_little_endian");
+        let binary = format!("{}{}", binary, "\0".repeat(15));
+        assert!(parse_ply(binary.as_bytes()).is_err());
+    }
+
+    /// A LAS 1.2 file of points in `format`, each `length`
+    /// bytes long.
+    fn las(format: u8, length: u16, count: u32, points: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = vec![0; 227];
+        bytes[0..4].copy_from_slice(b"LASF");
+        bytes[25] = 2;
+        bytes[96..100].copy_from_slice(&227u32.to_le_bytes());
+        bytes[104] = format;
+        bytes[105..107].copy_from_slice(&length.to_le_bytes());
+        bytes[107..111].copy_from_slice(&count.to_le_bytes());
+        for axis in 0..3 {
+            let scale = 131 + axis * 8;
+            bytes[scale..scale + 8].copy_from_slice(&1f64.to_le_bytes());
+        }
+        for point in points {
+            bytes.extend_from_slice(point);
+        }
+        bytes
+    }
+
+    #[test]
+    fn parses_las() {
+        let mut point = vec![0; 26];
+        point[0..4].copy_from_slice(&1i32.to_le_bytes());
+        point[8..12].copy_from_slice(&2i32.to_le_bytes());
+        point[20..22].copy_from_slice(&65535u16.to_le_bytes());
+        let points = parse_las(&las(2, 26, 1, &[&point])).unwrap();
+        // z-up, turned y-up
+        assert_eq!(points[0].position, [1.0, 2.0, 0.0]);
+        assert_eq!(points[0].color, [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn refuses_malformed_las() {
+        // too short for the color at bytes 28 to 34
+        let point = [0; 20];
+        let error = parse_las(&las(3, 20, 1, &[&point])).unwrap_err();
+        assert!(error.contains("at least 34 bytes"), "{}", error);
+
+        let point = [0; 20];
+        assert!(parse_las(&las(0, 20, 2, &[&point])).is_err());
+        assert!(parse_las(&las(0, 20, u32::MAX, &[&point])).is_err());
+        assert!(parse_las(&las(0, 20, 1, &[])[..100]).is_err());
+
+        // LAS 1.4 counts in 64 bits, which mustn't overflow
+        let mut wide = las(0, 20, 0, &[&[0; 28]]);
+        wide[25] = 4;
+        wide[247..255].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(parse_las(&wide).is_err());
+    }
+}
@@ -0,0 +1,25 @@
+#version 140
+
+// Adds a weighted copy of the input to the running total,
+// averaging each block of u_supersample by u_supersample
+// texels into one pixel.
+
+uniform sampler2D u_input;
+uniform float u_weight;
+uniform int u_supersample;
+
+in vec2 coords;
+out vec4 color;
+
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(u_input, 0));
+    vec2 corner = coords - 0.5 * float(u_supersample) * texel;
+    vec4 total = vec4(0.0);
+    for (int y = 0; y < u_supersample; y++) {
+        for (int x = 0; x < u_supersample; x++) {
+            total += texture(u_input, corner + (vec2(x, y) + 0.5) * texel);
+        }
+    }
+    float count = float(u_supersample * u_supersample);
+    color = total / count * u_weight;
+}
@@ -0,0 +1,107 @@
+use std::rc::Rc;
+
+use glium::{
+    backend::Context,
+    uniform,
+    Blend,
+    BlendingFunction,
+    DrawParameters,
+    LinearBlendingFactor,
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::util::{
+    compile_shader,
+    float_buffer,
+    RectStrip,
+};
+
+/// Sums weighted copies of textures at full precision, e.g.
+/// to average the sub-frames of a motion blurred frame.
+pub struct Accumulator {
+    context:     Rc<Context>,
+    rect_strip:  RectStrip,
+    program:     Program,
+    texture:     Texture2d,
+    /// See [`Accumulator::set_supersample`].
+    supersample: u32,
+}
+
+impl Accumulator {
+    pub fn new(context: &Rc<Context>) -> Result<Accumulator, String> {
+        let texture = float_buffer(context, 1, 1)?;
+        texture.as_surface().clear_color(0.0, 0.0, 0.0, 0.0);
+        Ok(Accumulator {
+            context: context.clone(),
+            rect_strip: RectStrip::new(context),
+            program: compile_shader(
+                context,
+                include_str!("./accumulate.frag"),
+            )?,
+            texture,
+            supersample: 1,
+        })
+    }
+
+    /// Averages each block of `factor` by `factor` pixels
+    /// of the textures added into one, for graphs
+    /// rendered larger than their size, see
+    /// [`crate::graph::ShaderGraph::supersample`].
+    pub fn set_supersample(&mut self, factor: u32) {
+        self.supersample = factor.max(1);
+    }
+
+    /// Resets the total to zero.
+    pub fn clear(&mut self) {
+        self.texture.as_surface().clear_color(0.0, 0.0, 0.0, 0.0);
+    }
+
+    /// Adds `input`, multiplied by `weight`, to the total.
+    /// The total is cleared if the input changes size.
+    pub fn add(
+        &mut self,
+        input: &Texture2d,
+        weight: f32,
+    ) -> Result<(), String> {
+        let (width, height) = input.dimensions();
+        let size = |size: u32| (size / self.supersample).max(1);
+        let (width, height) = (size(width), size(height));
+        if self.texture.dimensions() != (width, height) {
+            self.texture = float_buffer(&self.context, width, height)?;
+            self.clear();
+        }
+
+        let add = BlendingFunction::Addition {
+            source:      LinearBlendingFactor::One,
+            destination: LinearBlendingFactor::One,
+        };
+        let params = DrawParameters {
+            blend: Blend {
+                color: add,
+                alpha: add,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        self.texture
+            .as_surface()
+            .draw(
+                &self.rect_strip.buffer,
+                self.rect_strip.indices,
+                &self.program,
+                &uniform! {
+                    u_input: input,
+                    u_weight: weight,
+                    u_supersample: self.supersample as i32,
+                },
+                &params,
+            )
+            .map_err(|e| format!("Could not accumulate texture: {}", e))
+    }
+
+    /// The total so far.
+    pub fn texture(&self) -> &Texture2d { &self.texture }
+}
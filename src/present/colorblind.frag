@@ -0,0 +1,44 @@
+#version 140
+
+// Simulates how the output looks with color vision
+// deficiencies, using the matrices from Machado et al. 2009
+// at full severity.
+
+uniform sampler2D u_input;
+uniform int u_kind;
+
+in vec2 coords;
+out vec4 color;
+
+// rows, so colors are multiplied on the left
+const mat3 PROTANOPIA = mat3(
+    0.152286, 1.052583, -0.204868,
+    0.114503, 0.786281, 0.099216,
+    -0.003882, -0.048116, 1.051998
+);
+const mat3 DEUTERANOPIA = mat3(
+    0.367322, 0.860646, -0.227968,
+    0.280085, 0.672501, 0.047413,
+    -0.011820, 0.042940, 0.968881
+);
+const mat3 TRITANOPIA = mat3(
+    1.255528, -0.076749, -0.178779,
+    -0.078411, 0.930809, 0.147602,
+    0.004733, 0.691367, 0.303900
+);
+
+void main() {
+    vec4 current = texture(u_input, coords);
+
+    // the matrices apply to linear color
+    vec3 linear = pow(current.rgb, vec3(2.2));
+    if (u_kind == 0) {
+        linear = linear * PROTANOPIA;
+    } else if (u_kind == 1) {
+        linear = linear * DEUTERANOPIA;
+    } else {
+        linear = linear * TRITANOPIA;
+    }
+
+    color = vec4(pow(clamp(linear, 0., 1.), vec3(1. / 2.2)), current.a);
+}
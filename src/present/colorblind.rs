@@ -0,0 +1,123 @@
+use std::{
+    fmt,
+    rc::Rc,
+    str::FromStr,
+};
+
+use glium::{
+    backend::Context,
+    uniform,
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::util::{
+    compile_shader,
+    default_buffer,
+    RectStrip,
+};
+
+/// A color vision deficiency to simulate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorBlindness {
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+impl ColorBlindness {
+    /// Steps through each kind, then back to normal vision.
+    pub fn cycle(kind: Option<ColorBlindness>) -> Option<ColorBlindness> {
+        match kind {
+            None => Some(ColorBlindness::Protanopia),
+            Some(ColorBlindness::Protanopia) => {
+                Some(ColorBlindness::Deuteranopia)
+            },
+            Some(ColorBlindness::Deuteranopia) => {
+                Some(ColorBlindness::Tritanopia)
+            },
+            Some(ColorBlindness::Tritanopia) => None,
+        }
+    }
+}
+
+impl FromStr for ColorBlindness {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<ColorBlindness, String> {
+        match name {
+            "protanopia" => Ok(ColorBlindness::Protanopia),
+            "deuteranopia" => Ok(ColorBlindness::Deuteranopia),
+            "tritanopia" => Ok(ColorBlindness::Tritanopia),
+            other => Err(format!(
+                "Unknown color blindness `{}`, expected `protanopia`, \
+                 `deuteranopia`, or `tritanopia`",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ColorBlindness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorBlindness::Protanopia => "protanopia",
+            ColorBlindness::Deuteranopia => "deuteranopia",
+            ColorBlindness::Tritanopia => "tritanopia",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Redraws the output as it would be seen with a color
+/// vision deficiency, to check that a palette stays
+/// readable.
+pub struct Simulator {
+    context: Rc<Context>,
+    program: Program,
+    texture: Texture2d,
+}
+
+impl Simulator {
+    pub fn new(context: &Rc<Context>) -> Result<Simulator, String> {
+        Ok(Simulator {
+            context: context.clone(),
+            program: compile_shader(
+                context,
+                include_str!("./colorblind.frag"),
+            )?,
+            texture: default_buffer(context, 1, 1),
+        })
+    }
+
+    /// Simulates a deficiency, returning the simulated
+    /// copy of a texture.
+    pub fn apply(
+        &mut self,
+        rect_strip: &RectStrip,
+        input: &Texture2d,
+        kind: ColorBlindness,
+    ) -> &Texture2d {
+        if self.texture.dimensions() != input.dimensions() {
+            let (width, height) = input.dimensions();
+            self.texture = default_buffer(&self.context, width, height);
+        }
+
+        self.texture
+            .as_surface()
+            .draw(
+                &rect_strip.buffer,
+                rect_strip.indices,
+                &self.program,
+                &uniform! {
+                    u_input: input,
+                    u_kind: kind as i32,
+                },
+                &Default::default(),
+            )
+            .unwrap();
+
+        &self.texture
+    }
+}
@@ -0,0 +1,14 @@
+#version 140
+
+// Fades from a frozen frame to the output.
+
+uniform sampler2D u_input;
+uniform sampler2D u_from;
+uniform float u_mix;
+
+in vec2 coords;
+out vec4 color;
+
+void main() {
+    color = mix(texture(u_from, coords), texture(u_input, coords), u_mix);
+}
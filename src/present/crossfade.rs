@@ -0,0 +1,103 @@
+use std::{
+    rc::Rc,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use glium::{
+    backend::Context,
+    uniform,
+    uniforms::MagnifySamplerFilter,
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::util::{
+    compile_shader,
+    float_buffer,
+    RectStrip,
+};
+
+/// Fades from a frozen frame to the output, e.g. the last
+/// frame of one project to the first of the next.
+pub struct Crossfade {
+    context:      Rc<Context>,
+    program:      Program,
+    /// The frame faded from.
+    from:         Texture2d,
+    texture:      Texture2d,
+    pub duration: Duration,
+    started:      Option<Instant>,
+}
+
+impl Crossfade {
+    pub fn new(
+        context: &Rc<Context>,
+        duration: Duration,
+    ) -> Result<Crossfade, String> {
+        Ok(Crossfade {
+            context: context.clone(),
+            program: compile_shader(context, include_str!("./crossfade.frag"))?,
+            from: float_buffer(context, 1, 1)?,
+            texture: float_buffer(context, 1, 1)?,
+            duration,
+            started: None,
+        })
+    }
+
+    /// Starts fading from a copy of a frame, to whatever is
+    /// drawn next.
+    pub fn start(&mut self, from: &Texture2d) -> Result<(), String> {
+        if self.from.dimensions() != from.dimensions() {
+            let (width, height) = from.dimensions();
+            self.from = float_buffer(&self.context, width, height)?;
+        }
+        from.as_surface()
+            .fill(&self.from.as_surface(), MagnifySamplerFilter::Nearest);
+        self.started = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Returns the output faded in, or the output itself
+    /// once the fade is over.
+    pub fn apply<'a>(
+        &'a mut self,
+        rect_strip: &RectStrip,
+        input: &'a Texture2d,
+    ) -> Result<&'a Texture2d, String> {
+        let elapsed = match self.started {
+            Some(started) if started.elapsed() < self.duration => {
+                started.elapsed()
+            },
+            _ => {
+                self.started = None;
+                return Ok(input);
+            },
+        };
+        if self.texture.dimensions() != input.dimensions() {
+            let (width, height) = input.dimensions();
+            self.texture = float_buffer(&self.context, width, height)?;
+        }
+
+        let mix = elapsed.as_secs_f32() / self.duration.as_secs_f32();
+        self.texture
+            .as_surface()
+            .draw(
+                &rect_strip.buffer,
+                rect_strip.indices,
+                &self.program,
+                &uniform! {
+                    u_input: input,
+                    u_from: self.from.sampled(),
+                    u_mix: mix,
+                },
+                &Default::default(),
+            )
+            .unwrap();
+
+        Ok(&self.texture)
+    }
+}
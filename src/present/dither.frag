@@ -0,0 +1,43 @@
+#version 140
+
+// Applies a gamma curve, then quantizes each channel to a
+// few bits, offsetting each pixel by a threshold so that
+// gradients break up into fine noise instead of bands.
+
+uniform sampler2D u_input;
+uniform sampler2D u_noise;
+// 0 for none, 1 for ordered, 2 for blue noise
+uniform int u_pattern;
+uniform float u_levels;
+uniform float u_gamma;
+
+in vec2 coords;
+out vec4 color;
+
+// the 8x8 Bayer matrix, built up from bit interleaving
+float bayer(ivec2 p) {
+    int value = 0;
+    for (int bit = 0; bit < 3; bit++) {
+        int x = (p.x >> bit) & 1;
+        int y = (p.y >> bit) & 1;
+        value |= ((x ^ y) << (5 - 2 * bit)) | (y << (4 - 2 * bit));
+    }
+    return (float(value) + 0.5) / 64.;
+}
+
+void main() {
+    vec4 current = texture(u_input, coords);
+    vec3 curved = pow(max(current.rgb, 0.), vec3(u_gamma));
+
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+    float threshold = 0.5;
+    if (u_pattern == 1) {
+        threshold = bayer(pixel & 7);
+    } else if (u_pattern == 2) {
+        ivec2 size = textureSize(u_noise, 0);
+        threshold = texelFetch(u_noise, pixel % size, 0).r;
+    }
+
+    vec3 quantized = floor(curved * u_levels + threshold) / u_levels;
+    color = vec4(clamp(quantized, 0., 1.), current.a);
+}
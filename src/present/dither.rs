@@ -0,0 +1,191 @@
+use std::{
+    rc::Rc,
+    str::FromStr,
+};
+
+use glium::{
+    backend::Context,
+    uniform,
+    uniforms::{
+        MagnifySamplerFilter,
+        Sampler,
+    },
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::util::{
+    compile_shader,
+    default_buffer,
+    float_texture,
+    RectStrip,
+};
+
+/// The size of the blue noise tile, which is repeated
+/// across the output.
+const NOISE_SIZE: usize = 64;
+
+/// How pixels are offset before they are quantized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DitherPattern {
+    /// An 8x8 Bayer matrix, which leaves a fine crosshatch.
+    Ordered,
+    /// A tile of blue noise, which looks like film grain.
+    BlueNoise,
+}
+
+impl FromStr for DitherPattern {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<DitherPattern, String> {
+        match name {
+            "ordered" => Ok(DitherPattern::Ordered),
+            "blue-noise" => Ok(DitherPattern::BlueNoise),
+            other => Err(format!(
+                "Unknown dither `{}`, expected `ordered` or `blue-noise`",
+                other
+            )),
+        }
+    }
+}
+
+/// A final stage for driving low bit depth displays, like
+/// 6-bit panels and LED matrices. Applies a gamma curve,
+/// then quantizes each channel, dithering to hide the
+/// banding this would otherwise cause.
+pub struct Dither {
+    context:     Rc<Context>,
+    program:     Program,
+    noise:       Texture2d,
+    texture:     Texture2d,
+    /// The pattern to dither with, or `None` to round to
+    /// the nearest level.
+    pub pattern: Option<DitherPattern>,
+    /// Bits per channel of the display.
+    pub bits:    u32,
+    /// The output is raised to this power before it is
+    /// quantized, e.g. `2.2` for LEDs that are driven
+    /// linearly.
+    pub gamma:   f32,
+}
+
+impl Dither {
+    pub fn new(
+        context: &Rc<Context>,
+        pattern: Option<DitherPattern>,
+        bits: u32,
+        gamma: f32,
+    ) -> Result<Dither, String> {
+        let noise = float_texture(
+            context,
+            NOISE_SIZE as u32,
+            NOISE_SIZE as u32,
+            &blue_noise(NOISE_SIZE),
+        )?;
+
+        Ok(Dither {
+            context: context.clone(),
+            program: compile_shader(context, include_str!("./dither.frag"))?,
+            noise,
+            texture: default_buffer(context, 1, 1),
+            pattern,
+            bits: bits.clamp(1, 16),
+            gamma,
+        })
+    }
+
+    /// Dithers a texture, returning the quantized copy.
+    pub fn apply(
+        &mut self,
+        rect_strip: &RectStrip,
+        input: &Texture2d,
+    ) -> &Texture2d {
+        if self.texture.dimensions() != input.dimensions() {
+            let (width, height) = input.dimensions();
+            self.texture = default_buffer(&self.context, width, height);
+        }
+
+        let pattern = match self.pattern {
+            None => 0,
+            Some(DitherPattern::Ordered) => 1,
+            Some(DitherPattern::BlueNoise) => 2,
+        };
+        let levels = ((1u32 << self.bits) - 1) as f32;
+
+        self.texture
+            .as_surface()
+            .draw(
+                &rect_strip.buffer,
+                rect_strip.indices,
+                &self.program,
+                &uniform! {
+                    u_input: input,
+                    u_noise: Sampler::new(&self.noise)
+                        .magnify_filter(MagnifySamplerFilter::Nearest),
+                    u_pattern: pattern,
+                    u_levels: levels,
+                    u_gamma: self.gamma,
+                },
+                &Default::default(),
+            )
+            .unwrap();
+
+        &self.texture
+    }
+}
+
+/// Builds a tileable `size` by `size` threshold map of blue
+/// noise, with a simplified void-and-cluster: each cell is
+/// filled in turn, always picking the empty cell with the
+/// least energy from those already filled, and its
+/// threshold is the order it was filled in.
+fn blue_noise(size: usize) -> Vec<f32> {
+    const SIGMA: f32 = 1.5;
+    let cells = size * size;
+
+    // the energy a filled cell adds to each offset from it,
+    // wrapping around so the tile repeats seamlessly
+    let kernel: Vec<f32> = (0..cells)
+        .map(|offset| {
+            let (dx, dy) = (offset % size, offset / size);
+            let dx = dx.min(size - dx) as f32;
+            let dy = dy.min(size - dy) as f32;
+            (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+
+    // a little fixed noise breaks ties between cells, so the
+    // pattern doesn't fall into a grid
+    let mut seed = 0x2545_f491_u32;
+    let mut energy: Vec<f32> = (0..cells)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as f32 / u32::MAX as f32 * 1e-3
+        })
+        .collect();
+
+    let mut thresholds = vec![0.0; cells];
+    for rank in 0..cells {
+        let mut chosen = 0;
+        for cell in 1..cells {
+            if energy[cell] < energy[chosen] {
+                chosen = cell;
+            }
+        }
+        thresholds[chosen] = (rank as f32 + 0.5) / cells as f32;
+
+        let (x, y) = (chosen % size, chosen / size);
+        for (cell, energy) in energy.iter_mut().enumerate() {
+            let dx = (cell % size + size - x) % size;
+            let dy = (cell / size + size - y) % size;
+            *energy += kernel[dy * size + dx];
+        }
+        // never pick the same cell twice
+        energy[chosen] = f32::INFINITY;
+    }
+
+    thresholds
+}
@@ -0,0 +1,63 @@
+#version 140
+
+// A sharp upscale in the spirit of FSR1: a Catmull-Rom
+// bicubic reconstruction, followed by contrast adaptive
+// sharpening against the neighboring source texels.
+
+in vec2 coords;
+out vec4 color;
+
+uniform sampler2D tex;
+uniform float u_sharpness;
+
+vec4 catmull_rom(vec2 uv, vec2 size) {
+    vec2 position = uv * size - 0.5;
+    vec2 center = floor(position) + 0.5;
+    vec2 f = position - floor(position);
+
+    // weights for the four taps along each axis
+    vec2 w0 = f * (-0.5 + f * (1.0 - 0.5 * f));
+    vec2 w1 = 1.0 + f * f * (-2.5 + 1.5 * f);
+    vec2 w2 = f * (0.5 + f * (2.0 - 1.5 * f));
+    vec2 w3 = f * f * (-0.5 + 0.5 * f);
+
+    // fold the middle taps into one bilinear fetch
+    vec2 w12 = w1 + w2;
+    vec2 t0 = (center - 1.0) / size;
+    vec2 t12 = (center + w2 / w12) / size;
+    vec2 t3 = (center + 2.0) / size;
+
+    vec4 result = vec4(0.0);
+    result += texture(tex, vec2(t0.x, t0.y)) * w0.x * w0.y;
+    result += texture(tex, vec2(t12.x, t0.y)) * w12.x * w0.y;
+    result += texture(tex, vec2(t3.x, t0.y)) * w3.x * w0.y;
+    result += texture(tex, vec2(t0.x, t12.y)) * w0.x * w12.y;
+    result += texture(tex, vec2(t12.x, t12.y)) * w12.x * w12.y;
+    result += texture(tex, vec2(t3.x, t12.y)) * w3.x * w12.y;
+    result += texture(tex, vec2(t0.x, t3.y)) * w0.x * w3.y;
+    result += texture(tex, vec2(t12.x, t3.y)) * w12.x * w3.y;
+    result += texture(tex, vec2(t3.x, t3.y)) * w3.x * w3.y;
+    return result;
+}
+
+void main() {
+    vec2 size = vec2(textureSize(tex, 0));
+    vec2 texel = 1.0 / size;
+    vec4 center = catmull_rom(coords, size);
+
+    vec3 n = texture(tex, coords + vec2(0.0, texel.y)).rgb;
+    vec3 s = texture(tex, coords - vec2(0.0, texel.y)).rgb;
+    vec3 e = texture(tex, coords + vec2(texel.x, 0.0)).rgb;
+    vec3 w = texture(tex, coords - vec2(texel.x, 0.0)).rgb;
+
+    // sharpen less where the neighborhood is already contrasty,
+    // which avoids ringing around hard edges
+    vec3 lo = min(center.rgb, min(min(n, s), min(e, w)));
+    vec3 hi = max(center.rgb, max(max(n, s), max(e, w)));
+    vec3 amount = sqrt(clamp(min(lo, 1.0 - hi) / max(hi, 1e-4), 0.0, 1.0));
+    vec3 weight = -amount * mix(0.125, 0.2, u_sharpness);
+
+    vec3 sharpened = (center.rgb + weight * (n + s + e + w))
+        / (1.0 + 4.0 * weight);
+    color = vec4(clamp(sharpened, 0.0, 1.0), center.a);
+}
@@ -0,0 +1,17 @@
+#version 140
+
+// Outlines each pixel of the output, for inspecting it up
+// close. Lines are a screen pixel wide at any zoom.
+
+uniform vec2 u_size;
+
+in vec2 coords;
+out vec4 color;
+
+void main() {
+    vec2 pixel = coords * u_size;
+    // distance to the nearest pixel edge, in screen pixels
+    vec2 edge = abs(fract(pixel + 0.5) - 0.5) / fwidth(pixel);
+    float line = 1.0 - clamp(min(edge.x, edge.y), 0.0, 1.0);
+    color = vec4(vec3(0.5), 0.6 * line);
+}
@@ -0,0 +1,41 @@
+#version 140
+
+// Draws the outline of a centered box, dimming everything
+// outside of it if it is a crop, or a pair of nested boxes
+// for action and title safe areas.
+
+uniform vec2 u_extent;
+uniform vec2 u_resolution;
+uniform bool u_safe;
+
+in vec2 coords;
+out vec4 color;
+
+// how far a point is inside a centered box, in pixels
+float inside(vec2 extent) {
+    vec2 edge = (extent - abs(coords - 0.5)) * u_resolution;
+    return min(edge.x, edge.y);
+}
+
+bool outline(vec2 extent) {
+    float distance = inside(extent);
+    return distance >= 0. && distance < 1.5;
+}
+
+void main() {
+    vec4 line = vec4(1., 1., 1., 0.8);
+    vec4 clear = vec4(0.);
+
+    if (u_safe) {
+        bool action = outline(u_extent);
+        bool title = outline(u_extent * 0.8 / 0.9);
+        color = action || title ? line : clear;
+        return;
+    }
+
+    if (outline(u_extent)) {
+        color = line;
+    } else {
+        color = inside(u_extent) < 0. ? vec4(0., 0., 0., 0.6) : clear;
+    }
+}
@@ -0,0 +1,120 @@
+use std::{
+    fmt,
+    rc::Rc,
+};
+
+use glium::{
+    backend::Context,
+    uniform,
+    Blend,
+    DrawParameters,
+    Frame,
+    Program,
+    Surface,
+};
+
+use crate::util::{
+    compile_shader,
+    RectStrip,
+};
+
+/// A guide drawn over the output, for composing for a
+/// different screen than the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Guide {
+    /// A 16:9 crop, for widescreen video.
+    Wide,
+    /// A 9:16 crop, for phones and stories.
+    Tall,
+    /// A 4:3 crop, for projectors.
+    Standard,
+    /// The action safe and title safe areas, at 90% and 80%
+    /// of the window.
+    TitleSafe,
+}
+
+impl Guide {
+    /// Steps through each guide, then back to none.
+    pub fn cycle(guide: Option<Guide>) -> Option<Guide> {
+        match guide {
+            None => Some(Guide::Wide),
+            Some(Guide::Wide) => Some(Guide::Tall),
+            Some(Guide::Tall) => Some(Guide::Standard),
+            Some(Guide::Standard) => Some(Guide::TitleSafe),
+            Some(Guide::TitleSafe) => None,
+        }
+    }
+
+    /// The width over the height of the crop, if the guide
+    /// is a crop.
+    fn aspect(self) -> Option<f32> {
+        match self {
+            Guide::Wide => Some(16.0 / 9.0),
+            Guide::Tall => Some(9.0 / 16.0),
+            Guide::Standard => Some(4.0 / 3.0),
+            Guide::TitleSafe => None,
+        }
+    }
+}
+
+impl fmt::Display for Guide {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Guide::Wide => "16:9",
+            Guide::Tall => "9:16",
+            Guide::Standard => "4:3",
+            Guide::TitleSafe => "title safe",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Draws guides over the frame.
+pub struct Guides {
+    program: Program,
+}
+
+impl Guides {
+    pub fn new(context: &Rc<Context>) -> Result<Guides, String> {
+        Ok(Guides {
+            program: compile_shader(context, include_str!("./guides.frag"))?,
+        })
+    }
+
+    /// Draws a guide over the whole frame. Crops are as
+    /// large as will fit in the frame.
+    pub fn draw(
+        &self,
+        rect_strip: &RectStrip,
+        target: &mut Frame,
+        guide: Guide,
+    ) {
+        let (width, height) = target.get_dimensions();
+        let window = width as f32 / height.max(1) as f32;
+
+        // half the size of the box, as a fraction of the frame
+        let extent = match guide.aspect() {
+            Some(aspect) if aspect < window => [0.5 * aspect / window, 0.5],
+            Some(aspect) => [0.5, 0.5 * window / aspect],
+            None => [0.45, 0.45],
+        };
+
+        let params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            ..Default::default()
+        };
+        target
+            .draw(
+                &rect_strip.buffer,
+                rect_strip.indices,
+                &self.program,
+                &uniform! {
+                    u_extent: extent,
+                    u_resolution: [width as f32, height as f32],
+                    u_safe: guide.aspect().is_none(),
+                },
+                &params,
+            )
+            .unwrap();
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    collections::VecDeque,
+    rc::Rc,
+};
+
+use glium::{
+    backend::Context,
+    texture::{
+        MipmapsOption,
+        UncompressedFloatFormat,
+    },
+    uniforms::MagnifySamplerFilter,
+    Surface,
+    Texture2d,
+};
+
+/// Keeps the last few outputs of a graph on the GPU, so
+/// that transient glitches can be frozen and stepped
+/// through after the fact. Frames are kept as half floats,
+/// so each 1920x1080 frame takes about 16MB of VRAM.
+pub struct History {
+    context:  Rc<Context>,
+    capacity: usize,
+    /// Oldest first.
+    frames:   VecDeque<Texture2d>,
+}
+
+impl History {
+    pub fn new(context: &Rc<Context>, capacity: usize) -> History {
+        History {
+            context: context.clone(),
+            capacity,
+            frames: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// The number of frames kept so far.
+    pub fn len(&self) -> usize { self.frames.len() }
+
+    pub fn is_empty(&self) -> bool { self.frames.is_empty() }
+
+    /// Copies an output into the history, dropping the
+    /// oldest frame once full. The history starts over when
+    /// the output changes size.
+    pub fn push(&mut self, output: &Texture2d) -> Result<(), String> {
+        if self.capacity == 0 {
+            return Ok(());
+        }
+        let size = output.dimensions();
+        if self.frames.back().map(Texture2d::dimensions) != Some(size) {
+            self.frames.clear();
+        }
+
+        // reuse the oldest texture rather than allocating
+        let frame = if self.frames.len() == self.capacity {
+            // unwrap: the history is full, so not empty
+            self.frames.pop_front().unwrap()
+        } else {
+            Texture2d::empty_with_format(
+                &self.context,
+                UncompressedFloatFormat::F16F16F16F16,
+                MipmapsOption::NoMipmap,
+                size.0,
+                size.1,
+            )
+            .map_err(|e| format!("Could not keep frame history: {}", e))?
+        };
+        output
+            .as_surface()
+            .fill(&frame.as_surface(), MagnifySamplerFilter::Nearest);
+        self.frames.push_back(frame);
+        Ok(())
+    }
+
+    /// The frame `back` frames before the latest one, if it
+    /// is still kept.
+    pub fn get(&self, back: usize) -> Option<&Texture2d> {
+        let index = self.frames.len().checked_sub(back + 1)?;
+        self.frames.get(index)
+    }
+}
@@ -0,0 +1,22 @@
+#version 140
+
+// Synthesizes a frame part of the way between the previous
+// and current frames, by warping each along the optical
+// flow between them and blending the two.
+
+uniform sampler2D u_previous;
+uniform sampler2D u_current;
+uniform sampler2D u_flow;
+uniform float u_phase;
+
+in vec2 coords;
+out vec4 color;
+
+void main() {
+    vec2 size = vec2(textureSize(u_current, 0));
+    vec2 flow = texture(u_flow, coords).rg / size;
+
+    vec4 previous = texture(u_previous, coords - flow * u_phase);
+    vec4 current = texture(u_current, coords + flow * (1. - u_phase));
+    color = mix(previous, current, u_phase);
+}
@@ -0,0 +1,164 @@
+use std::rc::Rc;
+
+use glium::{
+    backend::Context,
+    uniform,
+    uniforms::{
+        Sampler,
+        SamplerWrapFunction,
+        Uniforms,
+    },
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::util::{
+    compile_shader,
+    float_buffer,
+    RectStrip,
+};
+
+/// Radius of the window the flow is estimated over.
+const FLOW_WINDOW: i32 = 3;
+
+/// Synthesizes frames between the outputs of a graph, for
+/// displays that refresh faster than the graph can run.
+/// Frames are warped along the optical flow between
+/// consecutive outputs, which is smooth for steady motion
+/// but can smear around edges and sudden changes. Shown
+/// frames lag one output behind the graph.
+pub struct Interpolator {
+    context:    Rc<Context>,
+    rect_strip: RectStrip,
+    copy:       Program,
+    luma:       Program,
+    flow:       Program,
+    warp:       Program,
+    previous:   Texture2d,
+    current:    Texture2d,
+    /// Luminance of the previous and current outputs.
+    lumas:      [Texture2d; 2],
+    motion:     Texture2d,
+    texture:    Texture2d,
+}
+
+impl Interpolator {
+    pub fn new(context: &Rc<Context>) -> Result<Interpolator, String> {
+        let buffer = || float_buffer(context, 1, 1);
+        Ok(Interpolator {
+            context:    context.clone(),
+            rect_strip: RectStrip::new(context),
+            copy:       compile_shader(
+                context,
+                include_str!("../util/texture.frag"),
+            )?,
+            luma:       compile_shader(
+                context,
+                include_str!("../graph/shaders/luma.frag"),
+            )?,
+            flow:       compile_shader(
+                context,
+                include_str!("../graph/shaders/flow.frag"),
+            )?,
+            warp:       compile_shader(
+                context,
+                include_str!("./interpolate.frag"),
+            )?,
+            previous:   buffer()?,
+            current:    buffer()?,
+            lumas:      [buffer()?, buffer()?],
+            motion:     buffer()?,
+            texture:    buffer()?,
+        })
+    }
+
+    /// Rebuilds all buffers when the output changes size.
+    /// Until the next output, there is no motion.
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        if self.texture.dimensions() == (width, height) {
+            return Ok(());
+        }
+
+        let context = self.context.clone();
+        let buffer = || float_buffer(&context, width, height);
+        self.previous = buffer()?;
+        self.current = buffer()?;
+        self.lumas = [buffer()?, buffer()?];
+        self.motion = buffer()?;
+        self.texture = buffer()?;
+        for texture in [&self.previous, &self.current, &self.motion] {
+            texture.as_surface().clear_color(0.0, 0.0, 0.0, 0.0);
+        }
+        Ok(())
+    }
+
+    /// Takes a new output of the graph, and estimates the
+    /// motion since the last one.
+    pub fn push(&mut self, output: &Texture2d) -> Result<(), String> {
+        let (width, height) = output.dimensions();
+        self.resize(width, height)?;
+
+        std::mem::swap(&mut self.previous, &mut self.current);
+        self.lumas.swap(0, 1);
+        let rect_strip = &self.rect_strip;
+        draw(
+            rect_strip,
+            &self.current,
+            &self.copy,
+            uniform! { tex: output },
+        )?;
+        draw(rect_strip, &self.lumas[1], &self.luma, uniform! {
+            u_input: output,
+        })?;
+        draw(rect_strip, &self.motion, &self.flow, uniform! {
+            u_current: &self.lumas[1],
+            u_previous: &self.lumas[0],
+            u_window: FLOW_WINDOW,
+        })
+    }
+
+    /// Returns a frame `phase` of the way from the previous
+    /// output to the current one, from `0` to `1`.
+    pub fn frame(&mut self, phase: f32) -> &Texture2d {
+        let clamp = |texture| {
+            Sampler::new(texture).wrap_function(SamplerWrapFunction::Clamp)
+        };
+
+        self.texture
+            .as_surface()
+            .draw(
+                &self.rect_strip.buffer,
+                self.rect_strip.indices,
+                &self.warp,
+                &uniform! {
+                    u_previous: clamp(&self.previous),
+                    u_current: clamp(&self.current),
+                    u_flow: &self.motion,
+                    u_phase: phase,
+                },
+                &Default::default(),
+            )
+            .unwrap();
+
+        &self.texture
+    }
+}
+
+fn draw<U: Uniforms>(
+    rect_strip: &RectStrip,
+    target: &Texture2d,
+    program: &Program,
+    uniforms: U,
+) -> Result<(), String> {
+    target
+        .as_surface()
+        .draw(
+            &rect_strip.buffer,
+            rect_strip.indices,
+            program,
+            &uniforms,
+            &Default::default(),
+        )
+        .map_err(|e| format!("Could not interpolate frame: {}", e))
+}
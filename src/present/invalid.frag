@@ -0,0 +1,17 @@
+#version 140
+
+// Flashes NaN and infinite pixels magenta, so they can be
+// found before they spread.
+
+uniform sampler2D u_input;
+uniform float u_time;
+
+in vec2 coords;
+out vec4 color;
+
+void main() {
+    vec4 current = texture(u_input, coords);
+    bool invalid = any(isnan(current)) || any(isinf(current));
+    float flash = step(0.5, fract(u_time * 2.));
+    color = invalid ? vec4(flash, 0., flash, 1.) : current;
+}
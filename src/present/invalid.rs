@@ -0,0 +1,68 @@
+use std::{
+    rc::Rc,
+    time::Instant,
+};
+
+use glium::{
+    backend::Context,
+    uniform,
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::util::{
+    compile_shader,
+    float_buffer,
+    RectStrip,
+};
+
+/// Highlights NaN and infinite pixels of the output, see
+/// [`crate::graph::Validator`] for finding the node they
+/// came from.
+pub struct InvalidHighlight {
+    context: Rc<Context>,
+    program: Program,
+    texture: Texture2d,
+    created: Instant,
+}
+
+impl InvalidHighlight {
+    pub fn new(context: &Rc<Context>) -> Result<InvalidHighlight, String> {
+        Ok(InvalidHighlight {
+            context: context.clone(),
+            program: compile_shader(context, include_str!("./invalid.frag"))?,
+            texture: float_buffer(context, 1, 1)?,
+            created: Instant::now(),
+        })
+    }
+
+    /// Returns a copy of a texture with invalid pixels
+    /// highlighted.
+    pub fn apply(
+        &mut self,
+        rect_strip: &RectStrip,
+        input: &Texture2d,
+    ) -> Result<&Texture2d, String> {
+        if self.texture.dimensions() != input.dimensions() {
+            let (width, height) = input.dimensions();
+            self.texture = float_buffer(&self.context, width, height)?;
+        }
+
+        self.texture
+            .as_surface()
+            .draw(
+                &rect_strip.buffer,
+                rect_strip.indices,
+                &self.program,
+                &uniform! {
+                    u_input: input,
+                    u_time: self.created.elapsed().as_secs_f32(),
+                },
+                &Default::default(),
+            )
+            .unwrap();
+
+        Ok(&self.texture)
+    }
+}
@@ -0,0 +1,37 @@
+#version 140
+
+// Caps the luminance of each pixel, and limits how quickly it
+// can change from one frame to the next, so that the output
+// can't flash.
+
+uniform sampler2D u_input;
+uniform sampler2D u_previous;
+uniform float u_max_luminance;
+uniform float u_max_change;
+uniform bool u_primed;
+
+in vec2 coords;
+out vec4 color;
+
+const vec3 LUMA = vec3(0.2126, 0.7152, 0.0722);
+
+// scales a color to a luminance, keeping its hue
+vec3 with_luminance(vec3 rgb, float luma, float target) {
+    if (luma < 1e-4) {
+        return vec3(target);
+    }
+    return rgb * (target / luma);
+}
+
+void main() {
+    vec4 current = texture(u_input, coords);
+    float luma = dot(current.rgb, LUMA);
+    float target = min(luma, u_max_luminance);
+
+    if (u_primed) {
+        float previous = dot(texture(u_previous, coords).rgb, LUMA);
+        target = clamp(target, previous - u_max_change, previous + u_max_change);
+    }
+
+    color = vec4(with_luminance(current.rgb, luma, target), current.a);
+}
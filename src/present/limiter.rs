@@ -0,0 +1,105 @@
+use std::{
+    rc::Rc,
+    time::Instant,
+};
+
+use glium::{
+    backend::Context,
+    uniform,
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::{
+    graph::Buffer,
+    util::{
+        compile_shader,
+        default_buffer,
+        RectStrip,
+    },
+};
+
+/// The longest a frame is taken to last, in seconds, so
+/// that a long frame can't let the output jump at once.
+const MAX_DT: f32 = 1.0 / 30.0;
+
+/// A final safety stage for public installations. Caps the
+/// brightness of the output, and limits how quickly each
+/// pixel can get brighter or darker, which keeps the
+/// output from flashing, e.g. for photosensitive viewers.
+pub struct Limiter {
+    context:           Rc<Context>,
+    program:           Program,
+    buffer:            Buffer,
+    /// Largest luminance, from `0` to `1`.
+    pub max_luminance: f32,
+    /// Largest change in luminance per second, e.g. `2`
+    /// means fading from black to white takes at least
+    /// half a second.
+    pub max_change:    f32,
+    last:              Option<Instant>,
+}
+
+impl Limiter {
+    pub fn new(
+        context: &Rc<Context>,
+        max_luminance: f32,
+        max_change: f32,
+    ) -> Result<Limiter, String> {
+        Ok(Limiter {
+            context: context.clone(),
+            program: compile_shader(context, include_str!("./limiter.frag"))?,
+            buffer: Buffer::new_double(|| default_buffer(context, 1, 1)),
+            max_luminance,
+            max_change,
+            last: None,
+        })
+    }
+
+    /// Limits a texture, returning the limited copy.
+    pub fn apply(
+        &mut self,
+        rect_strip: &RectStrip,
+        input: &Texture2d,
+    ) -> &Texture2d {
+        // history is dropped if the input changes size
+        if self.buffer.front().dimensions() != input.dimensions() {
+            let (width, height) = input.dimensions();
+            let context = &self.context;
+            self.buffer =
+                Buffer::new_double(|| default_buffer(context, width, height));
+            self.last = None;
+        }
+
+        // after a stall, e.g. a rebuild, a frame may only
+        // change as much as one at 30 fps would
+        let now = Instant::now();
+        let dt = self
+            .last
+            .replace(now)
+            .map(|last| (now - last).as_secs_f32().min(MAX_DT));
+
+        self.buffer.swap();
+        let front = self.buffer.front();
+        let previous = self.buffer.back().unwrap();
+        front
+            .as_surface()
+            .draw(
+                &rect_strip.buffer,
+                rect_strip.indices,
+                &self.program,
+                &uniform! {
+                    u_input: input,
+                    u_previous: previous,
+                    u_max_luminance: self.max_luminance,
+                    u_max_change: (self.max_change * dt.unwrap_or(0.0)).min(1.0),
+                    u_primed: dt.is_some(),
+                },
+                &Default::default(),
+            )
+            .unwrap();
+
+        front
+    }
+}
@@ -0,0 +1,465 @@
+use std::str::FromStr;
+
+use glium::{
+    backend::Facade,
+    uniform,
+    uniforms::{
+        MagnifySamplerFilter,
+        MinifySamplerFilter,
+        Sampler,
+        SamplerWrapFunction,
+    },
+    Blend,
+    BlendingFunction,
+    DrawParameters,
+    Frame,
+    LinearBlendingFactor,
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::{
+    keymap::Action,
+    util::RectStrip,
+};
+
+/// Screen pixels per output pixel from which the pixel grid
+/// is drawn over a zoomed in view.
+const GRID_FROM: f64 = 8.0;
+
+mod accumulator;
+mod colorblind;
+mod crossfade;
+mod dither;
+mod guides;
+mod history;
+mod interpolator;
+mod invalid;
+mod limiter;
+mod plots;
+mod reference;
+mod resizer;
+mod scopes;
+mod view;
+
+pub use accumulator::Accumulator;
+pub use colorblind::{
+    ColorBlindness,
+    Simulator,
+};
+pub use crossfade::Crossfade;
+pub use dither::{
+    Dither,
+    DitherPattern,
+};
+pub use guides::{
+    Guide,
+    Guides,
+};
+pub use history::History;
+pub use interpolator::Interpolator;
+pub use invalid::InvalidHighlight;
+pub use limiter::Limiter;
+pub use plots::Plots;
+pub use reference::{
+    Compare,
+    Reference,
+};
+pub use resizer::Resizer;
+pub use scopes::{
+    Scope,
+    Scopes,
+};
+pub use view::View;
+
+/// How the graph's output is scaled to fit the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upscale {
+    Nearest,
+    Bilinear,
+    /// A sharpened bicubic upscale, in the spirit of FSR1.
+    Fsr,
+}
+
+impl FromStr for Upscale {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Upscale, String> {
+        match name {
+            "nearest" => Ok(Upscale::Nearest),
+            "bilinear" => Ok(Upscale::Bilinear),
+            "fsr" => Ok(Upscale::Fsr),
+            other => Err(format!(
+                "Unknown upscale `{}`, expected `nearest`, `bilinear`, or \
+                 `fsr`",
+                other
+            )),
+        }
+    }
+}
+
+/// How far the output is turned, clockwise, as it is drawn
+/// to the window, for portrait screens and rotated
+/// projectors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    None,
+    Quarter,
+    Half,
+    ThreeQuarters,
+}
+
+impl Rotation {
+    /// Whether the output is turned on its side, so the
+    /// window is as wide as the output is tall.
+    pub fn is_sideways(self) -> bool {
+        matches!(self, Rotation::Quarter | Rotation::ThreeQuarters)
+    }
+
+    /// The rotation as a column-major matrix, in clip
+    /// space.
+    fn matrix(self) -> [[f32; 2]; 2] {
+        match self {
+            Rotation::None => [[1.0, 0.0], [0.0, 1.0]],
+            Rotation::Quarter => [[0.0, -1.0], [1.0, 0.0]],
+            Rotation::Half => [[-1.0, 0.0], [0.0, -1.0]],
+            Rotation::ThreeQuarters => [[0.0, 1.0], [-1.0, 0.0]],
+        }
+    }
+
+    /// Maps a point in the window back to where it is in
+    /// the output. Both are given as fractions of the width
+    /// and height, counting down from the top left.
+    pub fn unrotate(self, x: f64, y: f64) -> (f64, f64) {
+        let (x, y) = (2.0 * x - 1.0, 1.0 - 2.0 * y);
+        let [[a, b], [c, d]] = self.matrix();
+        // the inverse of a rotation is its transpose
+        let (x, y) = (a as f64 * x + b as f64 * y, c as f64 * x + d as f64 * y);
+        ((x + 1.0) / 2.0, (1.0 - y) / 2.0)
+    }
+}
+
+impl FromStr for Rotation {
+    type Err = String;
+
+    fn from_str(degrees: &str) -> Result<Rotation, String> {
+        match degrees {
+            "0" => Ok(Rotation::None),
+            "90" => Ok(Rotation::Quarter),
+            "180" => Ok(Rotation::Half),
+            "270" => Ok(Rotation::ThreeQuarters),
+            other => Err(format!(
+                "Unknown rotation `{}`, expected `0`, `90`, `180`, or `270`",
+                other
+            )),
+        }
+    }
+}
+
+/// Draws the output of a graph to the window. Unlike
+/// [`crate::util::texture`], the programs are only
+/// compiled once.
+pub struct Presenter {
+    rect_strip:            RectStrip,
+    blit:                  Program,
+    fsr:                   Program,
+    grid:                  Program,
+    pub upscale:           Upscale,
+    /// Turns the output as it is drawn.
+    pub rotation:          Rotation,
+    /// Zooms in on part of the output as it is drawn.
+    pub view:              View,
+    /// Fades in the output after switching projects.
+    pub crossfade:         Option<Crossfade>,
+    /// Applied to the output before it is drawn.
+    pub limiter:           Option<Limiter>,
+    /// Quantizes the output for low bit depth displays.
+    pub dither:            Option<Dither>,
+    /// Shows the output as it would be seen with a color
+    /// vision deficiency.
+    pub simulate:          Option<ColorBlindness>,
+    simulator:             Simulator,
+    /// A scope drawn over or in place of the output.
+    pub scope:             Option<Scope>,
+    scopes:                Scopes,
+    /// Highlights NaN and infinite pixels of the output.
+    pub highlight_invalid: bool,
+    invalid:               InvalidHighlight,
+    /// An image the output is compared with.
+    pub reference:         Option<Reference>,
+    /// A crop or safe area drawn over the output.
+    pub guide:             Option<Guide>,
+    guides:                Guides,
+    /// Uniforms plotted over the last frames, see
+    /// [`Plots`], drawn while `show_plots` is set.
+    pub plots:             Option<Plots>,
+    pub show_plots:        bool,
+    /// Multiplies the color of the output by its alpha as
+    /// it is drawn, as compositors expect of a transparent
+    /// window. The output itself is left as it is.
+    pub premultiply:       bool,
+}
+
+impl Presenter {
+    pub fn new<F: Facade>(
+        facade: &F,
+        upscale: Upscale,
+    ) -> Result<Presenter, String> {
+        let compile = |fragment| {
+            Program::from_source(
+                facade,
+                include_str!("./rotate.vert"),
+                fragment,
+                None,
+            )
+            .map_err(|e| format!("{}", e))
+        };
+
+        Ok(Presenter {
+            rect_strip: RectStrip::new(facade),
+            blit: compile(include_str!("../util/texture.frag"))?,
+            fsr: compile(include_str!("./fsr.frag"))?,
+            grid: compile(include_str!("./grid.frag"))?,
+            upscale,
+            rotation: Rotation::None,
+            view: View::default(),
+            crossfade: None,
+            limiter: None,
+            dither: None,
+            simulate: None,
+            simulator: Simulator::new(facade.get_context())?,
+            scope: None,
+            scopes: Scopes::new(facade.get_context())?,
+            highlight_invalid: false,
+            invalid: InvalidHighlight::new(facade.get_context())?,
+            reference: None,
+            guide: None,
+            guides: Guides::new(facade.get_context())?,
+            plots: None,
+            show_plots: true,
+            premultiply: false,
+        })
+    }
+
+    /// Writes a texture to the entire frame.
+    /// Carries out a key binding that changes how the
+    /// output is drawn, logging what it shows now: color
+    /// blindness, the view, the reference, scopes, plots,
+    /// and guides. Other actions are left alone.
+    pub fn act(&mut self, action: Action) {
+        match action {
+            Action::ColorBlindness => {
+                self.simulate = ColorBlindness::cycle(self.simulate);
+                match self.simulate {
+                    Some(kind) => eprintln!("[info] Simulating {}", kind),
+                    None => {
+                        eprintln!("[info] Stopped simulating color blindness")
+                    },
+                }
+            },
+            Action::ResetView => {
+                self.view = View::default();
+                eprintln!("[info] Reset the view");
+            },
+            Action::Compare => match &mut self.reference {
+                Some(reference) => {
+                    reference.compare = Compare::cycle(reference.compare);
+                    match reference.compare {
+                        Some(compare) => eprintln!(
+                            "[info] Comparing with the reference by {}",
+                            compare
+                        ),
+                        None => eprintln!("[info] Hid the reference"),
+                    }
+                },
+                None => eprintln!(
+                    "[warn] No reference to compare with, pass one with \
+                     --reference"
+                ),
+            },
+            Action::Scopes => {
+                self.scope = Scope::cycle(self.scope);
+                match self.scope {
+                    Some(scope) => eprintln!("[info] Showing {} scope", scope),
+                    None => eprintln!("[info] Hid scopes"),
+                }
+            },
+            Action::Plots => {
+                self.show_plots = !self.show_plots;
+                match (&self.plots, self.show_plots) {
+                    (None, _) => eprintln!("[warn] No uniforms to plot"),
+                    (Some(_), true) => eprintln!("[info] Showing plots"),
+                    (Some(_), false) => eprintln!("[info] Hid plots"),
+                }
+            },
+            Action::Guides => {
+                self.guide = Guide::cycle(self.guide);
+                match self.guide {
+                    Some(guide) => eprintln!("[info] Showing {} guide", guide),
+                    None => eprintln!("[info] Hid guides"),
+                }
+            },
+            _ => (),
+        }
+    }
+
+    pub fn draw(&mut self, target: &mut Frame, texture: &Texture2d) {
+        let texture = match &mut self.crossfade {
+            Some(crossfade) => {
+                match crossfade.apply(&self.rect_strip, texture) {
+                    Ok(faded) => faded,
+                    Err(e) => {
+                        eprintln!("[warn] {}", e);
+                        texture
+                    },
+                }
+            },
+            None => texture,
+        };
+        // before anything else can clamp them away
+        let texture = if self.highlight_invalid {
+            match self.invalid.apply(&self.rect_strip, texture) {
+                Ok(highlighted) => highlighted,
+                Err(e) => {
+                    eprintln!("[warn] {}", e);
+                    texture
+                },
+            }
+        } else {
+            texture
+        };
+        let texture = match &mut self.reference {
+            Some(reference) => {
+                match reference.apply(&self.rect_strip, texture) {
+                    Ok(compared) => compared,
+                    Err(e) => {
+                        eprintln!("[warn] {}", e);
+                        texture
+                    },
+                }
+            },
+            None => texture,
+        };
+        let texture = match &mut self.limiter {
+            Some(limiter) => limiter.apply(&self.rect_strip, texture),
+            None => texture,
+        };
+        let texture = match &mut self.dither {
+            Some(dither) => dither.apply(&self.rect_strip, texture),
+            None => texture,
+        };
+        let texture = match self.simulate {
+            Some(kind) => self.simulator.apply(&self.rect_strip, texture, kind),
+            None => texture,
+        };
+        let shown = match self.scope {
+            Some(scope @ Scope::FalseColor) | Some(scope @ Scope::Zebra) => {
+                self.scopes.image(&self.rect_strip, texture, scope)
+            },
+            _ => texture,
+        };
+
+        let sampler = Sampler::new(shown)
+            .wrap_function(SamplerWrapFunction::Clamp)
+            .minify_filter(MinifySamplerFilter::Linear);
+        let (program, sampler) = match self.upscale {
+            // zoomed in, pixels are shown as they are
+            _ if self.view.is_zoomed() => (
+                &self.blit,
+                sampler.magnify_filter(MagnifySamplerFilter::Nearest),
+            ),
+            Upscale::Nearest => (
+                &self.blit,
+                sampler.magnify_filter(MagnifySamplerFilter::Nearest),
+            ),
+            Upscale::Bilinear => (
+                &self.blit,
+                sampler.magnify_filter(MagnifySamplerFilter::Linear),
+            ),
+            Upscale::Fsr => (
+                &self.fsr,
+                sampler.magnify_filter(MagnifySamplerFilter::Linear),
+            ),
+        };
+
+        let (zoom, (x, y)) = (self.view.zoom as f32, self.view.center);
+        // textures count up from the bottom
+        let center = [x as f32, 1.0 - y as f32];
+        let params = if self.premultiply {
+            // the window is cleared, so blending only scales
+            // the color by the alpha, which is kept
+            let factor = |source| BlendingFunction::Addition {
+                source,
+                destination: LinearBlendingFactor::Zero,
+            };
+            DrawParameters {
+                blend: Blend {
+                    color: factor(LinearBlendingFactor::SourceAlpha),
+                    alpha: factor(LinearBlendingFactor::One),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+        } else {
+            Default::default()
+        };
+        target
+            .draw(
+                &self.rect_strip.buffer,
+                self.rect_strip.indices,
+                program,
+                &uniform! {
+                    tex: sampler,
+                    u_sharpness: 0.5f32,
+                    u_rotation: self.rotation.matrix(),
+                    u_zoom: zoom,
+                    u_center: center,
+                },
+                &params,
+            )
+            .unwrap();
+
+        let (width, height) = shown.dimensions();
+        let across = if self.rotation.is_sideways() {
+            height
+        } else {
+            width
+        };
+        let scale = target.get_dimensions().0 as f64 / across as f64;
+        if scale * self.view.zoom >= GRID_FROM {
+            let params = DrawParameters {
+                blend: Blend::alpha_blending(),
+                ..Default::default()
+            };
+            target
+                .draw(
+                    &self.rect_strip.buffer,
+                    self.rect_strip.indices,
+                    &self.grid,
+                    &uniform! {
+                        u_size: [width as f32, height as f32],
+                        u_rotation: self.rotation.matrix(),
+                        u_zoom: zoom,
+                        u_center: center,
+                    },
+                    &params,
+                )
+                .unwrap();
+        }
+
+        if let Some(guide) = self.guide {
+            self.guides.draw(&self.rect_strip, target, guide);
+        }
+        if let Some(scope) = self.scope {
+            self.scopes
+                .overlay(&self.rect_strip, target, texture, scope);
+        }
+        match &mut self.plots {
+            Some(plots) if self.show_plots => {
+                plots.draw(&self.rect_strip, target)
+            },
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,49 @@
+#version 140
+
+// Draws each row of values as a sparkline, the first row at
+// the top, over a dark background. Values are scaled to fit
+// their row already, and are NaN where there was none.
+
+uniform sampler2D u_values;
+uniform int u_rows;
+uniform vec2 u_resolution;
+
+in vec2 coords;
+out vec4 color;
+
+// room left above and below each line, as a fraction of
+// its row
+const float MARGIN = 0.1;
+
+bool missing(float value) {
+    return value != value;
+}
+
+void main() {
+    color = vec4(0., 0., 0., 0.6);
+
+    float rows = (1. - coords.y) * float(u_rows);
+    int row = min(int(rows), u_rows - 1);
+    // in row heights per pixel
+    float pixel = float(u_rows) / u_resolution.y;
+    if (row > 0 && fract(rows) < pixel) {
+        color = vec4(1., 1., 1., 0.25);
+        return;
+    }
+
+    // each column of pixels joins two values
+    int width = textureSize(u_values, 0).x;
+    float x = coords.x * float(width - 1);
+    int column = min(int(x), width - 2);
+    float a = texelFetch(u_values, ivec2(column, row), 0).r;
+    float b = texelFetch(u_values, ivec2(column + 1, row), 0).r;
+    if (missing(a) || missing(b)) {
+        return;
+    }
+
+    float y = (1. - fract(rows) - MARGIN) / (1. - 2. * MARGIN);
+    float thickness = pixel / (1. - 2. * MARGIN);
+    if (y > min(a, b) - thickness && y < max(a, b) + thickness) {
+        color = vec4(0.3, 1., 0.4, 1.);
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    rc::Rc,
+};
+
+use glium::{
+    backend::Context,
+    texture::{
+        ClientFormat,
+        RawImage2d,
+    },
+    uniform,
+    Blend,
+    DrawParameters,
+    Frame,
+    Program,
+    Rect,
+    Surface,
+    Texture2d,
+};
+
+use crate::util::{
+    compile_shader,
+    float_texture,
+    RectStrip,
+};
+
+/// How many values of each uniform are plotted, one per
+/// frame.
+const SAMPLES: usize = 256;
+/// The height of each plot, in pixels.
+const ROW_HEIGHT: u32 = 40;
+
+/// Plots float uniforms over the last frames as sparklines,
+/// e.g. to check the shape of a control's signal while
+/// tuning it. They're drawn over the top left corner of
+/// the frame, one row per uniform, in the order given.
+/// Each row is scaled to fit the values it shows.
+pub struct Plots {
+    program: Program,
+    names:   Vec<String>,
+    /// The values of each uniform, oldest first, and NaN
+    /// for frames it had none.
+    values:  Vec<VecDeque<f32>>,
+    texture: Texture2d,
+}
+
+impl Plots {
+    /// Plots uniforms by name, with or without `u_`.
+    pub fn new(
+        context: &Rc<Context>,
+        names: &[String],
+    ) -> Result<Plots, String> {
+        let names = names
+            .iter()
+            .map(|name| name.strip_prefix("u_").unwrap_or(name).to_string())
+            .collect::<Vec<_>>();
+        let gaps = vec![f32::NAN; SAMPLES * names.len().max(1)];
+        Ok(Plots {
+            program: compile_shader(context, include_str!("./plot.frag"))?,
+            values: vec![VecDeque::from(vec![f32::NAN; SAMPLES]); names.len()],
+            texture: float_texture(
+                context,
+                SAMPLES as u32,
+                names.len().max(1) as u32,
+                &gaps,
+            )?,
+            names,
+        })
+    }
+
+    /// The uniforms plotted, without `u_`.
+    pub fn names(&self) -> &[String] { &self.names }
+
+    /// Adds a frame's value of every uniform plotted,
+    /// looked up by name, or a gap for those without
+    /// one.
+    pub fn sample(&mut self, value: impl Fn(&str) -> Option<f32>) {
+        for (name, values) in self.names.iter().zip(self.values.iter_mut()) {
+            values.pop_front();
+            values.push_back(value(name).unwrap_or(f32::NAN));
+        }
+    }
+
+    /// Draws the plots over the top left corner of a frame.
+    pub fn draw(&mut self, rect_strip: &RectStrip, target: &mut Frame) {
+        if self.names.is_empty() {
+            return;
+        }
+        let mut scaled = Vec::with_capacity(SAMPLES * self.names.len());
+        for values in self.values.iter() {
+            let shown = values.iter().filter(|value| value.is_finite());
+            let (low, high) = shown
+                .fold((f32::MAX, f32::MIN), |(l, h), v| (l.min(*v), h.max(*v)));
+            // a flat line is drawn through the middle
+            let range = (high - low).max(f32::EPSILON);
+            scaled.extend(values.iter().map(|value| match value.is_finite() {
+                true if high > low => (value - low) / range,
+                true => 0.5,
+                false => f32::NAN,
+            }));
+        }
+        let rows = self.names.len() as u32;
+        self.texture.write(
+            Rect {
+                left:   0,
+                bottom: 0,
+                width:  SAMPLES as u32,
+                height: rows,
+            },
+            RawImage2d {
+                data:   Cow::Owned(scaled),
+                width:  SAMPLES as u32,
+                height: rows,
+                format: ClientFormat::F32,
+            },
+        );
+
+        let (width, height) = target.get_dimensions();
+        let (width, height) = (width / 3, (rows * ROW_HEIGHT).min(height / 2));
+        let params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            viewport: Some(Rect {
+                left: 0,
+                bottom: target.get_dimensions().1 - height,
+                width,
+                height,
+            }),
+            ..Default::default()
+        };
+        target
+            .draw(
+                &rect_strip.buffer,
+                rect_strip.indices,
+                &self.program,
+                &uniform! {
+                    u_values: &self.texture,
+                    u_rows: rows as i32,
+                    u_resolution: [width as f32, height as f32],
+                },
+                &params,
+            )
+            .unwrap();
+    }
+}
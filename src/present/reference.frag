@@ -0,0 +1,29 @@
+#version 140
+
+// Compares the output with a reference image: either the
+// reference left of a split and the output right of it, or
+// their difference.
+
+uniform sampler2D u_input;
+uniform sampler2D u_reference;
+uniform bool u_difference;
+uniform float u_split;
+
+in vec2 coords;
+out vec4 color;
+
+void main() {
+    vec4 current = texture(u_input, coords);
+    vec4 reference = texture(u_reference, coords);
+    if (u_difference) {
+        color = vec4(abs(current.rgb - reference.rgb), 1.);
+        return;
+    }
+
+    color = coords.x < u_split ? reference : current;
+    // a line a pixel wide along the split
+    float width = fwidth(coords.x);
+    if (abs(coords.x - u_split) < width) {
+        color = vec4(1.);
+    }
+}
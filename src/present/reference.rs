@@ -0,0 +1,143 @@
+use std::{
+    fmt,
+    path::Path,
+    rc::Rc,
+    str::FromStr,
+};
+
+use glium::{
+    backend::Context,
+    texture::RawImage2d,
+    uniform,
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::util::{
+    compile_shader,
+    float_buffer,
+    RectStrip,
+};
+
+/// How the output is compared with a reference image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compare {
+    /// Shows the reference left of a split that can be
+    /// dragged across the output.
+    Wipe,
+    /// Shows how far each pixel is from the reference.
+    Difference,
+}
+
+impl Compare {
+    /// Steps through each comparison, then back to none.
+    pub fn cycle(compare: Option<Compare>) -> Option<Compare> {
+        match compare {
+            None => Some(Compare::Wipe),
+            Some(Compare::Wipe) => Some(Compare::Difference),
+            Some(Compare::Difference) => None,
+        }
+    }
+}
+
+impl FromStr for Compare {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Compare, String> {
+        match name {
+            "wipe" => Ok(Compare::Wipe),
+            "difference" => Ok(Compare::Difference),
+            other => Err(format!(
+                "Unknown comparison `{}`, expected `wipe` or `difference`",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Compare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Compare::Wipe => "wipe",
+            Compare::Difference => "difference",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Compares the output with a reference image, for matching
+/// a target look or a previous render. The reference is
+/// stretched to the size of the output.
+pub struct Reference {
+    context:     Rc<Context>,
+    program:     Program,
+    image:       Texture2d,
+    texture:     Texture2d,
+    /// How the output is compared, or none to show the
+    /// output alone.
+    pub compare: Option<Compare>,
+    /// Where the wipe is, as a fraction of the width of the
+    /// output.
+    pub split:   f64,
+}
+
+impl Reference {
+    pub fn load(
+        context: &Rc<Context>,
+        path: &Path,
+        compare: Compare,
+    ) -> Result<Reference, String> {
+        let image = image::open(path)
+            .map_err(|e| format!("Could not load `{}`: {}", path.display(), e))?
+            .to_rgba8();
+        let size = image.dimensions();
+        let raw = RawImage2d::from_raw_rgba_reversed(&image.into_raw(), size);
+        let image = Texture2d::new(context, raw)
+            .map_err(|e| format!("Could not upload reference: {}", e))?;
+
+        Ok(Reference {
+            context: context.clone(),
+            program: compile_shader(context, include_str!("./reference.frag"))?,
+            image,
+            texture: float_buffer(context, 1, 1)?,
+            compare: Some(compare),
+            split: 0.5,
+        })
+    }
+
+    /// Returns the output compared with the reference, or
+    /// the output itself if there is no comparison.
+    pub fn apply<'a>(
+        &'a mut self,
+        rect_strip: &RectStrip,
+        input: &'a Texture2d,
+    ) -> Result<&'a Texture2d, String> {
+        let difference = match self.compare {
+            Some(compare) => compare == Compare::Difference,
+            None => return Ok(input),
+        };
+        if self.texture.dimensions() != input.dimensions() {
+            let (width, height) = input.dimensions();
+            self.texture = float_buffer(&self.context, width, height)?;
+        }
+
+        self.texture
+            .as_surface()
+            .draw(
+                &rect_strip.buffer,
+                rect_strip.indices,
+                &self.program,
+                &uniform! {
+                    u_input: input,
+                    u_reference: self.image.sampled(),
+                    u_difference: difference,
+                    u_split: self.split as f32,
+                },
+                &Default::default(),
+            )
+            .unwrap();
+
+        Ok(&self.texture)
+    }
+}
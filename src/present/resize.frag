@@ -0,0 +1,29 @@
+#version 140
+
+// Resizes the input to the target, averaging every texel it
+// covers when it shrinks, so that fine detail doesn't
+// shimmer, and bilinearly when it grows.
+
+uniform sampler2D u_input;
+
+in vec2 coords;
+out vec4 color;
+
+// more than this many taps a side is slower than it's worth
+const int MAX_TAPS = 8;
+
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(u_input, 0));
+    // input texels per output pixel
+    vec2 footprint = fwidth(coords) / texel;
+    ivec2 taps = clamp(ivec2(ceil(footprint)), 1, MAX_TAPS);
+    vec2 corner = coords - 0.5 * footprint * texel;
+    vec4 total = vec4(0.0);
+    for (int y = 0; y < taps.y; y++) {
+        for (int x = 0; x < taps.x; x++) {
+            vec2 offset = (vec2(x, y) + 0.5) / vec2(taps) * footprint;
+            total += texture(u_input, corner + offset * texel);
+        }
+    }
+    color = total / float(taps.x * taps.y);
+}
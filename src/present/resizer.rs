@@ -0,0 +1,59 @@
+use std::rc::Rc;
+
+use glium::{
+    backend::Context,
+    uniform,
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::util::{
+    compile_shader,
+    float_buffer,
+    RectStrip,
+};
+
+/// Resizes textures on the GPU, averaging the pixels each
+/// one covers when shrinking, e.g. to send a 4K render to
+/// a 720p preview. The result is kept at full precision.
+pub struct Resizer {
+    context:    Rc<Context>,
+    rect_strip: RectStrip,
+    program:    Program,
+    texture:    Texture2d,
+}
+
+impl Resizer {
+    pub fn new(context: &Rc<Context>) -> Result<Resizer, String> {
+        Ok(Resizer {
+            context:    context.clone(),
+            rect_strip: RectStrip::new(context),
+            program:    compile_shader(context, include_str!("./resize.frag"))?,
+            texture:    float_buffer(context, 1, 1)?,
+        })
+    }
+
+    /// Resizes `input`, returning the resized copy, which
+    /// is overwritten by the next call.
+    pub fn resize(
+        &mut self,
+        input: &Texture2d,
+        (width, height): (u32, u32),
+    ) -> Result<&Texture2d, String> {
+        if self.texture.dimensions() != (width, height) {
+            self.texture = float_buffer(&self.context, width, height)?;
+        }
+        self.texture
+            .as_surface()
+            .draw(
+                &self.rect_strip.buffer,
+                self.rect_strip.indices,
+                &self.program,
+                &uniform! { u_input: input },
+                &Default::default(),
+            )
+            .map_err(|e| format!("Could not resize texture: {}", e))?;
+        Ok(&self.texture)
+    }
+}
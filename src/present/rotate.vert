@@ -0,0 +1,18 @@
+#version 140
+
+// Like the default vertex shader, but turns the quad, so
+// the output can be rotated as it is drawn to the window,
+// and zooms in on part of the output, see `View`.
+
+uniform mat2 u_rotation;
+uniform float u_zoom;
+uniform vec2 u_center;
+
+in vec2 position;
+in vec2 tex_coords;
+out vec2 coords;
+
+void main() {
+    coords = (tex_coords - 0.5) / u_zoom + u_center;
+    gl_Position = vec4(u_rotation * position, 0.0, 1.0);
+}
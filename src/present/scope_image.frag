@@ -0,0 +1,39 @@
+#version 140
+
+// Recolors the output to judge exposure: false color maps
+// luminance to bands of color, and zebra stripes mark
+// clipped highlights.
+
+uniform sampler2D u_input;
+uniform int u_kind;
+uniform float u_time;
+
+in vec2 coords;
+out vec4 color;
+
+const vec3 LUMA = vec3(0.2126, 0.7152, 0.0722);
+
+vec3 false_color(float luma) {
+    if (luma < 0.02) return vec3(0.5, 0., 0.6); // crushed
+    if (luma < 0.1) return vec3(0., 0.2, 1.);   // shadows
+    if (luma < 0.38) return vec3(luma);
+    if (luma < 0.48) return vec3(0.2, 0.8, 0.2); // middle gray
+    if (luma < 0.8) return vec3(luma);
+    if (luma < 0.97) return vec3(1., 0.8, 0.);  // highlights
+    return vec3(1., 0., 0.);                    // clipped
+}
+
+void main() {
+    vec4 current = texture(u_input, coords);
+    float luma = dot(current.rgb, LUMA);
+
+    if (u_kind == 0) {
+        color = vec4(false_color(luma), current.a);
+        return;
+    }
+
+    // diagonal stripes that crawl, so they stand out from the image
+    float stripe = fract((gl_FragCoord.x + gl_FragCoord.y) / 16. - u_time);
+    bool zebra = luma > 0.95 && stripe < 0.5;
+    color = zebra ? vec4(0., 0., 0., current.a) : current;
+}
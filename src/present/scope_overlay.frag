@@ -0,0 +1,25 @@
+#version 140
+
+// Draws a waveform or histogram, scattered by the previous
+// passes, over a dark background.
+
+uniform sampler2D u_scope;
+uniform int u_kind;
+uniform float u_gain;
+
+in vec2 coords;
+out vec4 color;
+
+void main() {
+    vec4 background = vec4(0., 0., 0., 0.6);
+
+    if (u_kind == 0) {
+        float count = texture(u_scope, coords).r * u_gain;
+        float glow = 1. - exp(-count);
+        color = mix(background, vec4(0.3, 1., 0.4, 1.), glow);
+        return;
+    }
+
+    float height = texture(u_scope, vec2(coords.x, 0.5)).r * u_gain;
+    color = coords.y < height ? vec4(0.9, 0.9, 0.9, 1.) : background;
+}
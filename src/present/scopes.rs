@@ -0,0 +1,256 @@
+use std::{
+    fmt,
+    rc::Rc,
+    time::Instant,
+};
+
+use glium::{
+    backend::Context,
+    index::{
+        NoIndices,
+        PrimitiveType,
+    },
+    uniform,
+    vertex::EmptyVertexAttributes,
+    Blend,
+    DrawParameters,
+    Frame,
+    Program,
+    Rect,
+    Surface,
+    Texture2d,
+};
+
+use crate::util::{
+    compile_shader,
+    default_buffer,
+    float_buffer,
+    RectStrip,
+};
+
+const WAVEFORM_WIDTH: u32 = 256;
+const WAVEFORM_HEIGHT: u32 = 128;
+const HISTOGRAM_BINS: u32 = 256;
+const HISTOGRAM_GRID: u32 = 256;
+
+/// A video scope, for judging the exposure of the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// Colors the output by bands of luminance.
+    FalseColor,
+    /// Stripes highlights that are about to clip.
+    Zebra,
+    /// Plots the luminance of each column of the output.
+    Waveform,
+    Histogram,
+}
+
+impl Scope {
+    /// Steps through each scope, then back to none.
+    pub fn cycle(scope: Option<Scope>) -> Option<Scope> {
+        match scope {
+            None => Some(Scope::FalseColor),
+            Some(Scope::FalseColor) => Some(Scope::Zebra),
+            Some(Scope::Zebra) => Some(Scope::Waveform),
+            Some(Scope::Waveform) => Some(Scope::Histogram),
+            Some(Scope::Histogram) => None,
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Scope::FalseColor => "false color",
+            Scope::Zebra => "zebra",
+            Scope::Waveform => "waveform",
+            Scope::Histogram => "histogram",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Computes scopes of the output on the GPU. False color
+/// and zebra replace the output, while the waveform and
+/// histogram are drawn over its bottom left corner.
+pub struct Scopes {
+    context:   Rc<Context>,
+    image:     Program,
+    waveform:  Program,
+    histogram: Program,
+    overlay:   Program,
+    texture:   Texture2d,
+    /// Counts of samples, scattered by luminance.
+    counts:    Texture2d,
+    bins:      Texture2d,
+    created:   Instant,
+}
+
+impl Scopes {
+    pub fn new(context: &Rc<Context>) -> Result<Scopes, String> {
+        let scatter = |vertex| {
+            Program::from_source(
+                context,
+                vertex,
+                include_str!("../graph/shaders/histogram.frag"),
+                None,
+            )
+            .map_err(|e| format!("{}", e))
+        };
+
+        Ok(Scopes {
+            context:   context.clone(),
+            image:     compile_shader(
+                context,
+                include_str!("./scope_image.frag"),
+            )?,
+            waveform:  scatter(include_str!("./waveform.vert"))?,
+            histogram: scatter(include_str!(
+                "../graph/shaders/histogram.vert"
+            ))?,
+            overlay:   compile_shader(
+                context,
+                include_str!("./scope_overlay.frag"),
+            )?,
+            texture:   default_buffer(context, 1, 1),
+            counts:    float_buffer(context, WAVEFORM_WIDTH, WAVEFORM_HEIGHT)?,
+            bins:      float_buffer(context, HISTOGRAM_BINS, 1)?,
+            created:   Instant::now(),
+        })
+    }
+
+    /// Applies false color or zebra stripes to a texture,
+    /// returning the recolored copy. Other scopes leave the
+    /// texture as is.
+    pub fn image<'a>(
+        &'a mut self,
+        rect_strip: &RectStrip,
+        input: &'a Texture2d,
+        scope: Scope,
+    ) -> &'a Texture2d {
+        let kind = match scope {
+            Scope::FalseColor => 0,
+            Scope::Zebra => 1,
+            Scope::Waveform | Scope::Histogram => return input,
+        };
+
+        if self.texture.dimensions() != input.dimensions() {
+            let (width, height) = input.dimensions();
+            self.texture = default_buffer(&self.context, width, height);
+        }
+
+        self.texture
+            .as_surface()
+            .draw(
+                &rect_strip.buffer,
+                rect_strip.indices,
+                &self.image,
+                &uniform! {
+                    u_input: input,
+                    u_kind: kind,
+                    u_time: self.created.elapsed().as_secs_f32(),
+                },
+                &Default::default(),
+            )
+            .unwrap();
+
+        &self.texture
+    }
+
+    /// Draws the waveform or histogram of a texture over
+    /// the bottom left corner of the frame. Other scopes
+    /// draw nothing.
+    pub fn overlay(
+        &mut self,
+        rect_strip: &RectStrip,
+        target: &mut Frame,
+        input: &Texture2d,
+        scope: Scope,
+    ) {
+        let additive = DrawParameters {
+            blend: Blend {
+                color: glium::BlendingFunction::Addition {
+                    source:      glium::LinearBlendingFactor::One,
+                    destination: glium::LinearBlendingFactor::One,
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let (kind, scope, gain) = match scope {
+            Scope::Waveform => {
+                let mut surface = self.counts.as_surface();
+                surface.clear_color(0.0, 0.0, 0.0, 0.0);
+                surface
+                    .draw(
+                        EmptyVertexAttributes {
+                            len: (WAVEFORM_WIDTH * WAVEFORM_HEIGHT) as usize,
+                        },
+                        NoIndices(PrimitiveType::Points),
+                        &self.waveform,
+                        &uniform! {
+                            u_input: input,
+                            u_columns: WAVEFORM_WIDTH as i32,
+                            u_rows: WAVEFORM_HEIGHT as i32,
+                            u_weight: 1.0f32,
+                        },
+                        &additive,
+                    )
+                    .unwrap();
+                // a column spread evenly over every level glows
+                // at around two thirds of full brightness
+                (0, &self.counts, 1.0)
+            },
+            Scope::Histogram => {
+                let samples = HISTOGRAM_GRID * HISTOGRAM_GRID;
+                let mut surface = self.bins.as_surface();
+                surface.clear_color(0.0, 0.0, 0.0, 0.0);
+                surface
+                    .draw(
+                        EmptyVertexAttributes {
+                            len: samples as usize,
+                        },
+                        NoIndices(PrimitiveType::Points),
+                        &self.histogram,
+                        &uniform! {
+                            u_input: input,
+                            u_grid: HISTOGRAM_GRID as i32,
+                            u_bins: HISTOGRAM_BINS as i32,
+                            u_weight: 1.0 / samples as f32,
+                        },
+                        &additive,
+                    )
+                    .unwrap();
+                // an even histogram fills a quarter of the height
+                (1, &self.bins, HISTOGRAM_BINS as f32 / 4.0)
+            },
+            Scope::FalseColor | Scope::Zebra => return,
+        };
+
+        let (width, height) = target.get_dimensions();
+        let params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            viewport: Some(Rect {
+                left:   0,
+                bottom: 0,
+                width:  width / 3,
+                height: height / 4,
+            }),
+            ..Default::default()
+        };
+        target
+            .draw(
+                &rect_strip.buffer,
+                rect_strip.indices,
+                &self.overlay,
+                &uniform! {
+                    u_scope: scope,
+                    u_kind: kind,
+                    u_gain: gain,
+                },
+                &params,
+            )
+            .unwrap();
+    }
+}
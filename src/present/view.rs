@@ -0,0 +1,67 @@
+/// How many times larger than the window the output can be
+/// drawn.
+const MAX_ZOOM: f64 = 256.0;
+
+/// A zoom and pan applied to the output as it is drawn, for
+/// inspecting fine detail. Only what is shown changes: the
+/// graph still renders the whole output, at full size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct View {
+    /// How many times larger the output is drawn, from 1.
+    pub zoom:   f64,
+    /// The point of the output shown at the center of the
+    /// window, as fractions of its width and height,
+    /// counting down from the top left.
+    pub center: (f64, f64),
+}
+
+impl Default for View {
+    fn default() -> View {
+        View {
+            zoom:   1.0,
+            center: (0.5, 0.5),
+        }
+    }
+}
+
+impl View {
+    pub fn is_zoomed(&self) -> bool { self.zoom > 1.0 }
+
+    /// Maps a point of the window to the point of the
+    /// output shown there. Both are given as fractions,
+    /// counting down from the top left, before any
+    /// rotation.
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            (x - 0.5) / self.zoom + self.center.0,
+            (y - 0.5) / self.zoom + self.center.1,
+        )
+    }
+
+    /// Zooms in by `factor`, or out if it is less than 1,
+    /// keeping the point of the output at `(x, y)` in
+    /// place.
+    pub fn zoom_at(&mut self, x: f64, y: f64, factor: f64) {
+        let before = self.apply(x, y);
+        self.zoom = (self.zoom * factor).clamp(1.0, MAX_ZOOM);
+        let after = self.apply(x, y);
+        self.center.0 += before.0 - after.0;
+        self.center.1 += before.1 - after.1;
+        self.clamp();
+    }
+
+    /// Drags the output by a distance given as fractions of
+    /// the window.
+    pub fn pan(&mut self, dx: f64, dy: f64) {
+        self.center.0 -= dx / self.zoom;
+        self.center.1 -= dy / self.zoom;
+        self.clamp();
+    }
+
+    /// Keeps the output filling the window.
+    fn clamp(&mut self) {
+        let half = 0.5 / self.zoom;
+        self.center.0 = self.center.0.clamp(half, 1.0 - half);
+        self.center.1 = self.center.1.clamp(half, 1.0 - half);
+    }
+}
@@ -0,0 +1,18 @@
+#version 140
+
+// Scatters one point per sample of the input, at the column
+// it was taken from and the height of its luminance. There
+// is no vertex buffer, samples are placed on a grid using
+// `gl_VertexID`.
+
+uniform sampler2D u_input;
+uniform int u_columns;
+uniform int u_rows;
+
+void main() {
+    ivec2 cell = ivec2(gl_VertexID % u_columns, gl_VertexID / u_columns);
+    vec2 uv = (vec2(cell) + 0.5) / vec2(u_columns, u_rows);
+    vec3 rgb = texture(u_input, uv).rgb;
+    float luma = clamp(dot(rgb, vec3(0.2126, 0.7152, 0.0722)), 0., 1.);
+    gl_Position = vec4(uv.x * 2. - 1., luma * 2. - 1., 0., 1.);
+}
@@ -0,0 +1,258 @@
+//! A record of how a render was made, written next to it,
+//! so that any render can be made again exactly later: what
+//! the graph was built from, by hash, and with what seed,
+//! uniforms, frames, and size, see [`Provenance`].
+
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    process::Command,
+};
+
+use serde_json::{
+    json,
+    Value,
+};
+use sha1::{
+    Digest,
+    Sha1,
+};
+
+use crate::{
+    graph::{
+        ShaderGraph,
+        Uniform,
+    },
+    meta::Meta,
+    reload::Lockfile,
+};
+
+/// The file a render of pngs is described in, in their
+/// directory. Videos are described in `<video>.json`, see
+/// [`Provenance::path_for`].
+pub const PROVENANCE_FILE: &str = "render.json";
+
+/// How a render was made. Written as JSON next to it, and
+/// the key fields are embedded in each png, see
+/// [`Provenance::png_text`], and in videos as a comment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provenance {
+    /// The version of shadergarden that rendered it.
+    pub version:          String,
+    /// The files the graph was built from, by hash, see
+    /// [`Lockfile`].
+    pub files:            Lockfile,
+    /// The commit the project was at, if it's in a git
+    /// repository, ending `-dirty` if it had changes.
+    pub commit:           Option<String>,
+    pub build_seed:       u32,
+    pub profile:          Option<String>,
+    pub render_scale:     f32,
+    /// The global uniforms, as they were when the render
+    /// started.
+    pub uniforms:         Vec<(String, Uniform)>,
+    pub meta:             Meta,
+    /// The size of every frame, once known.
+    pub size:             (u32, u32),
+    pub fps:              f64,
+    /// The first frame rendered, and the one after the
+    /// last.
+    pub frames:           (u64, u64),
+    /// How many frames go by for each one kept, see
+    /// [`crate::sink::SinkSpec`].
+    pub every:            u32,
+    pub temporal_samples: u32,
+}
+
+impl Provenance {
+    /// Records the uniforms, seed, and metadata of a graph
+    /// about to be rendered, built from `files` in
+    /// `project`. The render itself is described after.
+    pub fn new(
+        graph: &ShaderGraph,
+        project: &Path,
+        files: Vec<PathBuf>,
+    ) -> Result<Provenance, String> {
+        let files = Lockfile::new(project, files)?;
+        Ok(Provenance {
+            version: files.version.clone(),
+            files,
+            commit: commit(project),
+            build_seed: graph.build_seed(),
+            profile: None,
+            render_scale: 1.0,
+            uniforms: graph
+                .uniforms()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+            meta: graph.meta().clone(),
+            size: (0, 0),
+            fps: 60.0,
+            frames: (0, 0),
+            every: 1,
+            temporal_samples: 1,
+        })
+    }
+
+    /// A single SHA-1 of every file the graph was built
+    /// from, in hex, to tell renders of the same graph.
+    pub fn graph_hash(&self) -> String {
+        let mut hasher = Sha1::new();
+        for (file, hash) in self.files.files.iter() {
+            hasher.update(format!("{} {}\n", file, hash).as_bytes());
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// How long the render is, in seconds.
+    pub fn duration(&self) -> f64 {
+        self.frames.1.saturating_sub(self.frames.0) as f64 / self.fps
+    }
+
+    pub fn to_json(&self) -> Value {
+        let uniforms = self
+            .uniforms
+            .iter()
+            .map(|(name, value)| (name.clone(), uniform_json(value)))
+            .collect::<serde_json::Map<_, _>>();
+        json!({
+            "shadergarden": self.version,
+            "graph": self.graph_hash(),
+            "files": self.files.files,
+            "commit": self.commit,
+            "seed": self.build_seed,
+            "profile": self.profile,
+            "render_scale": self.render_scale,
+            "uniforms": uniforms,
+            "meta": self.meta.to_json(),
+            "resolution": [self.size.0, self.size.1],
+            "fps": self.fps,
+            "start": self.frames.0,
+            "end": self.frames.1,
+            "every": self.every,
+            "duration": self.duration(),
+            "temporal_samples": self.temporal_samples,
+        })
+    }
+
+    /// Where a render to `output` is described: inside a
+    /// directory of pngs, or next to a video.
+    pub fn path_for(output: &Path) -> PathBuf {
+        match output.extension() {
+            Some(_) => {
+                let mut path = output.as_os_str().to_owned();
+                path.push(".json");
+                PathBuf::from(path)
+            },
+            None => output.join(PROVENANCE_FILE),
+        }
+    }
+
+    /// Writes the description of a render to `output`, see
+    /// [`Provenance::path_for`], returning where.
+    pub fn save(&self, output: &Path) -> Result<PathBuf, String> {
+        let path = Provenance::path_for(output);
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| {
+                format!("Could not create `{}`: {}", dir.display(), e)
+            })?;
+        }
+        let json = serde_json::to_string_pretty(&self.to_json()).unwrap();
+        fs::write(&path, json + "\n").map_err(|e| {
+            format!("Could not write `{}`: {}", path.display(), e)
+        })?;
+        Ok(path)
+    }
+
+    /// The key fields, as png text chunks: the credits of
+    /// the graph, under the standard keywords, and what it
+    /// takes to render it again, under `shadergarden:`
+    /// keywords.
+    pub fn png_text(&self) -> Vec<(String, String)> {
+        let mut text = vec![
+            (
+                "Software".to_string(),
+                format!("shadergarden {}", self.version),
+            ),
+            ("shadergarden:graph".to_string(), self.graph_hash()),
+            ("shadergarden:seed".to_string(), self.build_seed.to_string()),
+            ("shadergarden:fps".to_string(), self.fps.to_string()),
+        ];
+        let optional = [
+            ("Title", &self.meta.title),
+            ("Author", &self.meta.author),
+            ("Copyright", &self.meta.license),
+            ("shadergarden:commit", &self.commit),
+            ("shadergarden:profile", &self.profile),
+        ];
+        for (keyword, value) in optional.iter() {
+            if let Some(value) = value {
+                text.push((keyword.to_string(), value.clone()));
+            }
+        }
+        text
+    }
+
+    /// The key fields as one line, for the comment of a
+    /// video.
+    pub fn comment(&self) -> String {
+        let mut comment = format!(
+            "shadergarden {} graph {} seed {}",
+            self.version,
+            self.graph_hash(),
+            self.build_seed
+        );
+        if let Some(commit) = &self.commit {
+            comment += &format!(" commit {}", commit);
+        }
+        comment
+    }
+}
+
+fn uniform_json(uniform: &Uniform) -> Value {
+    match uniform {
+        Uniform::Float(x) => json!(x),
+        Uniform::Vec2(v) => json!(v),
+        Uniform::Vec3(v) => json!(v),
+        Uniform::Vec4(v) => json!(v),
+        Uniform::Int(n) => json!(n),
+        Uniform::Array(elements) => {
+            Value::Array(elements.iter().map(uniform_json).collect())
+        },
+        Uniform::Struct(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, value)| (name.clone(), uniform_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// The commit a project is at, if it's in a git repository
+/// and `git` is installed.
+fn commit(project: &Path) -> Option<String> {
+    let git = |args: &[&str]| {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(project)
+            .args(args)
+            .output();
+        match output {
+            Ok(output) if output.status.success() => {
+                Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+            },
+            _ => None,
+        }
+    };
+    let head = git(&["rev-parse", "HEAD"])?;
+    let dirty = git(&["status", "--porcelain", "--", "."])
+        .is_some_and(|status| !status.is_empty());
+    Some(if dirty { head + "-dirty" } else { head })
+}
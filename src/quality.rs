@@ -0,0 +1,83 @@
+use std::time::{
+    Duration,
+    Instant,
+};
+
+/// Adjusts a quality level between `0` and `1` to keep
+/// frames within a time budget. Shaders see the level as
+/// `u_quality`, and can use it to scale step counts, sample
+/// counts, and so on.
+///
+/// Frame times are smoothed, and quality only changes when
+/// they have been outside the band for a while, so that the
+/// level doesn't flicker back and forth.
+#[derive(Debug, Clone)]
+pub struct QualityController {
+    budget:   Duration,
+    quality:  f32,
+    smoothed: Option<f64>,
+    /// When frame times first left the band, if they have.
+    outside:  Option<Instant>,
+    changed:  Instant,
+}
+
+impl QualityController {
+    /// Frame time, in fractions of the budget, below which
+    /// quality is raised.
+    const LOW: f64 = 0.75;
+    /// Frame time, in fractions of the budget, above which
+    /// quality is lowered.
+    const HIGH: f64 = 1.05;
+    const STEP: f32 = 0.1;
+    const MIN: f32 = 0.1;
+
+    /// How long frame times must stay outside the band
+    /// before quality changes.
+    const PATIENCE: Duration = Duration::from_millis(500);
+    /// How long to wait after a change before another.
+    const COOLDOWN: Duration = Duration::from_secs(1);
+
+    pub fn new(target_fps: f64) -> QualityController {
+        QualityController {
+            budget:   Duration::from_secs_f64(1.0 / target_fps),
+            quality:  1.0,
+            smoothed: None,
+            outside:  None,
+            changed:  Instant::now(),
+        }
+    }
+
+    /// Records how long the last frame took to render,
+    /// returning the new quality level.
+    pub fn update(&mut self, frame_time: Duration) -> f32 {
+        let ratio = frame_time.as_secs_f64() / self.budget.as_secs_f64();
+        let smoothed = match self.smoothed {
+            Some(smoothed) => smoothed * 0.9 + ratio * 0.1,
+            None => ratio,
+        };
+        self.smoothed = Some(smoothed);
+
+        let step = if smoothed > Self::HIGH {
+            -Self::STEP
+        } else if smoothed < Self::LOW && self.quality < 1.0 {
+            Self::STEP
+        } else {
+            self.outside = None;
+            return self.quality;
+        };
+
+        let now = Instant::now();
+        let since = *self.outside.get_or_insert(now);
+        if now - since >= Self::PATIENCE && now - self.changed >= Self::COOLDOWN
+        {
+            self.quality = (self.quality + step).clamp(Self::MIN, 1.0);
+            self.changed = now;
+            self.outside = None;
+            eprintln!("[info] Quality set to {:.1}", self.quality);
+        }
+
+        self.quality
+    }
+
+    pub fn quality(&self) -> f32 { self.quality }
+}
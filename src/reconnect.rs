@@ -0,0 +1,147 @@
+//! Keeps live inputs going when they are unplugged, so a
+//! loose cable doesn't end a show. The process reading an
+//! input, e.g. `ffmpeg` capturing a camera, is started
+//! again every [`RETRY`] once it stops, until the input is
+//! back. Meanwhile the graph keeps what the input gave
+//! last.
+
+use std::{
+    io,
+    process::{
+        Child,
+        ChildStdout,
+        Command,
+        Stdio,
+    },
+    sync::{
+        atomic::{
+            AtomicBool,
+            Ordering,
+        },
+        Arc,
+        Mutex,
+    },
+    thread,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+/// How long to wait between tries to open an input again.
+pub const RETRY: Duration = Duration::from_secs(1);
+
+/// A process reading from an input, started again whenever
+/// it stops, until this is dropped. Its stdout is read by
+/// `read` a piece at a time, which returns whether to keep
+/// reading, e.g. `false` once nothing wants what it reads,
+/// and errors once the process stops writing.
+pub struct Reconnecting {
+    child:   Arc<Mutex<Option<Child>>>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Reconnecting {
+    /// Starts the process made by `command`. Errors if it
+    /// can't be started at all, e.g. because it isn't
+    /// installed. The input is called `name` in logs.
+    pub fn spawn(
+        name: String,
+        command: impl Fn() -> Command + Send + 'static,
+        mut read: impl FnMut(&mut ChildStdout) -> io::Result<bool> + Send + 'static,
+    ) -> Result<Reconnecting, String> {
+        let first = start(&command, false)?;
+        let child = Arc::new(Mutex::new(Some(first)));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let (shared, done) = (child.clone(), stopped.clone());
+        thread::spawn(move || {
+            let mut lost: Option<Instant> = None;
+            loop {
+                // unwrap: only taken here, and put back below
+                let mut stdout = {
+                    let mut child = shared.lock().unwrap();
+                    let child = child.as_mut().unwrap();
+                    child.stdout.take().unwrap()
+                };
+                loop {
+                    match read(&mut stdout) {
+                        Ok(true) => (),
+                        Ok(false) => {
+                            done.store(true, Ordering::SeqCst);
+                            break;
+                        },
+                        Err(_) => break,
+                    }
+                    if let Some(since) = lost.take() {
+                        eprintln!(
+                            "[info] {} is back, after {:.0}s",
+                            name,
+                            since.elapsed().as_secs_f64()
+                        );
+                    }
+                }
+
+                if let Some(child) = shared.lock().unwrap().as_mut() {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                }
+                if done.load(Ordering::SeqCst) {
+                    return;
+                }
+                if lost.is_none() {
+                    eprintln!(
+                        "[warn] {} stopped, keeping what it gave last until \
+                         it's back",
+                        name
+                    );
+                    lost = Some(Instant::now());
+                }
+
+                // try until a process starts at all, which it may
+                // not while the input is gone
+                loop {
+                    thread::sleep(RETRY);
+                    let mut child = shared.lock().unwrap();
+                    if done.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    if let Ok(spawned) = start(&command, true) {
+                        *child = Some(spawned);
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Reconnecting { child, stopped })
+    }
+}
+
+/// Starts a process with its stdout piped, and its stderr
+/// dropped if `quiet`, as tries while the input is gone
+/// fail every time.
+fn start(command: &dyn Fn() -> Command, quiet: bool) -> Result<Child, String> {
+    let mut command = command();
+    command.stdout(Stdio::piped());
+    if quiet {
+        command.stderr(Stdio::null());
+    }
+    command.spawn().map_err(|e| {
+        let program = command.get_program().to_string_lossy();
+        format!("Could not start {}: {}", program, e)
+    })
+}
+
+impl Drop for Reconnecting {
+    fn drop(&mut self) {
+        // checked with the lock held, so no process starts
+        // after this
+        let mut child = self.child.lock().unwrap();
+        self.stopped.store(true, Ordering::SeqCst);
+        if let Some(child) = child.as_mut() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
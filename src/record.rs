@@ -0,0 +1,382 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    io::Write,
+    path::{
+        Path,
+        PathBuf,
+    },
+    process::{
+        Command,
+        Stdio,
+    },
+    str::FromStr,
+    sync::mpsc::{
+        self,
+        Receiver,
+        SyncSender,
+    },
+    thread::{
+        self,
+        JoinHandle,
+    },
+};
+
+use glium::{
+    texture::pixel_buffer::PixelBuffer,
+    Texture2d,
+};
+use image::{
+    ImageBuffer,
+    Rgba,
+};
+
+/// Frames read back but not yet mapped. Mapping a pixel
+/// buffer waits for the GPU to finish copying into it, so
+/// buffers are left a few frames to be ready first.
+const IN_FLIGHT: usize = 3;
+
+/// Frames mapped but not yet written. When the writer falls
+/// this far behind, new frames are dropped instead of
+/// stalling the render loop.
+const QUEUE: usize = 32;
+
+type Pixels = Vec<(u8, u8, u8, u8)>;
+
+/// How a recording is stored. Every format is lossless.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// ProRes 4444 in a `.mov`, encoded by `ffmpeg`.
+    ProRes,
+    /// FFV1 in a `.mkv`, encoded by `ffmpeg`.
+    Ffv1,
+    /// A directory of numbered png frames.
+    PngSeq,
+}
+
+impl FromStr for RecordFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "prores" => Ok(RecordFormat::ProRes),
+            "ffv1" => Ok(RecordFormat::Ffv1),
+            "pngseq" => Ok(RecordFormat::PngSeq),
+            other => Err(format!(
+                "Unknown record format `{}`, expected `prores`, `ffv1`, \
+                 or `pngseq`",
+                other
+            )),
+        }
+    }
+}
+
+impl RecordFormat {
+    /// The `ffmpeg` arguments used to encode this format,
+    /// if it is encoded by `ffmpeg`.
+    fn codec(&self) -> Option<(&'static [&'static str], &'static str)> {
+        match self {
+            RecordFormat::ProRes => Some((
+                &[
+                    "-c:v",
+                    "prores_ks",
+                    "-profile:v",
+                    "4",
+                    "-pix_fmt",
+                    "yuva444p10le",
+                ],
+                "mov",
+            )),
+            RecordFormat::Ffv1 => Some((
+                &["-c:v", "ffv1", "-level", "3", "-pix_fmt", "bgra"],
+                "mkv",
+            )),
+            RecordFormat::PngSeq => None,
+        }
+    }
+}
+
+/// The `ffmpeg` arguments and filters used to export a
+/// video with the given extension. Textures are read bottom
+/// row first, so every filter starts by flipping them.
+fn export_codec(
+    extension: &str,
+) -> Option<(&'static [&'static str], &'static str)> {
+    match extension {
+        // x264 only takes even sizes in 4:2:0
+        "mp4" => Some((
+            &["-c:v", "libx264", "-crf", "18", "-pix_fmt", "yuv420p"],
+            "vflip,pad=ceil(iw/2)*2:ceil(ih/2)*2",
+        )),
+        // VP9 keeps alpha, but only if asked to
+        "webm" => Some((
+            &[
+                "-c:v",
+                "libvpx-vp9",
+                "-crf",
+                "24",
+                "-b:v",
+                "0",
+                "-pix_fmt",
+                "yuva420p",
+            ],
+            "vflip",
+        )),
+        "mov" => Some((RecordFormat::ProRes.codec()?.0, "vflip")),
+        "mkv" => Some((RecordFormat::Ffv1.codec()?.0, "vflip")),
+        "gif" => {
+            Some((&[], "vflip,split[a][b];[a]palettegen[p];[b][p]paletteuse"))
+        },
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RecordStats {
+    /// Frames passed on to be written.
+    pub frames:  u64,
+    /// Frames dropped because the writer fell behind, or
+    /// the output changed size.
+    pub dropped: u64,
+}
+
+/// Records the output to disk while running, without
+/// waiting on the GPU or the disk: frames are read back
+/// asynchronously, and written on a background thread.
+pub struct Recorder {
+    path:    PathBuf,
+    size:    (u32, u32),
+    pending: VecDeque<PixelBuffer<(u8, u8, u8, u8)>>,
+    tx:      Option<SyncSender<Pixels>>,
+    writer:  Option<JoinHandle<Result<(), String>>>,
+    stats:   RecordStats,
+    /// Whether to wait for the writer rather than drop
+    /// frames, see [`Recorder::export`].
+    wait:    bool,
+}
+
+impl Recorder {
+    /// Starts a new recording in `dir`, named after the
+    /// current time. `ffmpeg` must be on the path to record
+    /// video formats.
+    pub fn start(
+        format: RecordFormat,
+        dir: &Path,
+        size: (u32, u32),
+        fps: f64,
+        timestamp: u64,
+    ) -> Result<Recorder, String> {
+        fs::create_dir_all(dir).map_err(|e| {
+            format!("Could not create `{}`: {}", dir.display(), e)
+        })?;
+
+        let (tx, rx) = mpsc::sync_channel(QUEUE);
+        let (path, writer) = match format.codec() {
+            Some((codec, extension)) => {
+                let path =
+                    dir.join(format!("session-{}.{}", timestamp, extension));
+                // textures are read bottom row first
+                let writer = encode(&path, codec, "vflip", size, fps, &[], rx)?;
+                (path, writer)
+            },
+            None => {
+                let path = dir.join(format!("session-{}", timestamp));
+                fs::create_dir_all(&path).map_err(|e| {
+                    format!("Could not create `{}`: {}", path.display(), e)
+                })?;
+                let frames = path.clone();
+                let writer =
+                    thread::spawn(move || write_pngs(&frames, size, rx));
+                (path, writer)
+            },
+        };
+
+        Ok(Recorder {
+            path,
+            size,
+            pending: VecDeque::new(),
+            tx: Some(tx),
+            writer: Some(writer),
+            stats: RecordStats::default(),
+            wait: false,
+        })
+    }
+
+    /// Starts writing an offline render to a video, encoded
+    /// by `ffmpeg` according to its extension: `.mp4`,
+    /// `.webm`, `.gif`, or the lossless `.mov` and `.mkv`.
+    /// Unlike a recording, frames are never dropped:
+    /// pushing a frame waits for the writer to catch up
+    /// instead. The video is tagged with `tags`, each
+    /// `key=value`,
+    /// e.g. from [`crate::meta::Meta::video_tags`].
+    pub fn export(
+        path: &Path,
+        size: (u32, u32),
+        fps: f64,
+        tags: &[String],
+    ) -> Result<Recorder, String> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase)
+            .unwrap_or_default();
+        let (codec, filter) = export_codec(&extension).ok_or_else(|| {
+            format!(
+                "Can't export to `{}`, expected a `.mp4`, `.webm`, `.gif`, \
+                 `.mov`, or `.mkv` file",
+                path.display()
+            )
+        })?;
+
+        let (tx, rx) = mpsc::sync_channel(QUEUE);
+        let writer = encode(path, codec, filter, size, fps, tags, rx)?;
+        Ok(Recorder {
+            path: path.to_path_buf(),
+            size,
+            pending: VecDeque::new(),
+            tx: Some(tx),
+            writer: Some(writer),
+            stats: RecordStats::default(),
+            wait: true,
+        })
+    }
+
+    /// Drops frames when the writer falls behind, as a
+    /// recording does, rather than waiting for it, so that
+    /// an export can run alongside a live window.
+    pub fn dropping_frames(mut self) -> Recorder {
+        self.wait = false;
+        self
+    }
+
+    /// Where the recording is being written.
+    pub fn path(&self) -> &Path { &self.path }
+
+    pub fn stats(&self) -> RecordStats { self.stats }
+
+    /// Queues a frame to be recorded.
+    pub fn push(&mut self, texture: &Texture2d) {
+        let size = (texture.width(), texture.height());
+        if size != self.size {
+            self.stats.dropped += 1;
+            return;
+        }
+
+        self.pending.push_back(texture.read_to_pixel_buffer());
+        if self.pending.len() > IN_FLIGHT {
+            let buffer = self.pending.pop_front().unwrap();
+            self.send(buffer);
+        }
+    }
+
+    fn send(&mut self, buffer: PixelBuffer<(u8, u8, u8, u8)>) {
+        let pixels = match buffer.read() {
+            Ok(pixels) => pixels,
+            Err(_) => {
+                self.stats.dropped += 1;
+                return;
+            },
+        };
+
+        let sent = match &self.tx {
+            Some(tx) if self.wait => tx.send(pixels).is_ok(),
+            Some(tx) => tx.try_send(pixels).is_ok(),
+            None => false,
+        };
+        if sent {
+            self.stats.frames += 1;
+        } else {
+            self.stats.dropped += 1;
+        }
+    }
+
+    /// Writes the frames still in flight, and waits for the
+    /// recording to be finished.
+    pub fn finish(mut self) -> Result<RecordStats, String> {
+        while let Some(buffer) = self.pending.pop_front() {
+            self.send(buffer);
+        }
+        self.tx = None;
+
+        match self.writer.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result?,
+            Some(Err(_)) => return Err("Recording thread panicked".into()),
+            None => (),
+        }
+        Ok(self.stats)
+    }
+}
+
+/// Pipes raw frames to an `ffmpeg` process, tagging the
+/// video with `key=value` metadata.
+fn encode(
+    path: &Path,
+    codec: &[&str],
+    filter: &str,
+    (width, height): (u32, u32),
+    fps: f64,
+    metadata: &[String],
+    rx: Receiver<Pixels>,
+) -> Result<JoinHandle<Result<(), String>>, String> {
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-loglevel", "error", "-y"])
+        .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+        .args(["-s", &format!("{}x{}", width, height)])
+        .args(["-r", &fps.to_string(), "-i", "-"])
+        .args(["-vf", filter])
+        .args(codec);
+    for tag in metadata {
+        command.arg("-metadata").arg(tag);
+    }
+    let mut child = command
+        .arg(path)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Could not start ffmpeg: {}", e))?;
+    let mut stdin = child.stdin.take().unwrap();
+
+    Ok(thread::spawn(move || {
+        let mut bytes = Vec::with_capacity((width * height * 4) as usize);
+        for pixels in rx {
+            bytes.clear();
+            for (r, g, b, a) in pixels {
+                bytes.extend_from_slice(&[r, g, b, a]);
+            }
+            stdin
+                .write_all(&bytes)
+                .map_err(|e| format!("Could not write to ffmpeg: {}", e))?;
+        }
+
+        drop(stdin);
+        let status = child.wait().map_err(|e| format!("{}", e))?;
+        if !status.success() {
+            return Err(format!("ffmpeg exited with {}", status));
+        }
+        Ok(())
+    }))
+}
+
+/// Writes each frame to a numbered png.
+fn write_pngs(
+    dir: &Path,
+    (width, height): (u32, u32),
+    rx: Receiver<Pixels>,
+) -> Result<(), String> {
+    for (frame, pixels) in rx.iter().enumerate() {
+        let mut buffer = ImageBuffer::new(width, height);
+        for (index, (r, g, b, a)) in pixels.into_iter().enumerate() {
+            let x = index as u32 % width;
+            // textures are read bottom row first
+            let y = height - 1 - index as u32 / width;
+            buffer.put_pixel(x, y, Rgba([r, g, b, a]));
+        }
+
+        let path = dir.join(format!("{:06}.png", frame));
+        buffer.save(&path).map_err(|e| {
+            format!("Could not write `{}`: {}", path.display(), e)
+        })?;
+    }
+    Ok(())
+}
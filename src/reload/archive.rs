@@ -0,0 +1,170 @@
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+/// Keeps a copy of a project every time it is rebuilt, so
+/// that the exact sources behind any moment of a session
+/// can be recovered later. Each snapshot is a `.tar.gz` of
+/// the project directory, numbered in order, in a folder
+/// for the session.
+pub struct Archive {
+    project: PathBuf,
+    session: PathBuf,
+    exclude: Vec<PathBuf>,
+    count:   usize,
+}
+
+impl Archive {
+    /// Starts a session in a new folder in `dir`, named
+    /// after `timestamp`.
+    pub fn new(
+        project: &Path,
+        dir: &Path,
+        timestamp: u64,
+    ) -> Result<Archive, String> {
+        let session = dir.join(format!("session-{}", timestamp));
+        fs::create_dir_all(&session).map_err(|e| {
+            format!("Could not create `{}`: {}", session.display(), e)
+        })?;
+
+        let mut archive = Archive {
+            project: project.to_path_buf(),
+            session: session.clone(),
+            exclude: vec![],
+            count:   0,
+        };
+        // the archive may be kept inside the project
+        archive.exclude(dir);
+        Ok(archive)
+    }
+
+    /// Leaves a directory in the project out of snapshots,
+    /// e.g. one that recordings are written to.
+    pub fn exclude(&mut self, path: &Path) {
+        if let Ok(path) = path.canonicalize() {
+            self.exclude.push(path);
+        }
+    }
+
+    /// The folder snapshots are written to.
+    pub fn session(&self) -> &Path { &self.session }
+
+    /// Writes the next snapshot of the project, returning
+    /// its path.
+    pub fn snapshot(&mut self, timestamp: u64) -> Result<PathBuf, String> {
+        let mut tar = vec![];
+        self.add_dir(&mut tar, &self.project, Path::new(""), timestamp)?;
+        // a tar ends with two empty blocks
+        tar.resize(tar.len() + 1024, 0);
+
+        let path = self
+            .session
+            .join(format!("{:04}-{}.tar.gz", self.count, timestamp));
+        fs::write(&path, gzip(&tar, timestamp)).map_err(|e| {
+            format!("Could not write `{}`: {}", path.display(), e)
+        })?;
+        self.count += 1;
+        Ok(path)
+    }
+
+    fn add_dir(
+        &self,
+        tar: &mut Vec<u8>,
+        dir: &Path,
+        prefix: &Path,
+        timestamp: u64,
+    ) -> Result<(), String> {
+        let mut entries = fs::read_dir(dir)
+            .map_err(|e| format!("Could not read `{}`: {}", dir.display(), e))?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .collect::<Vec<_>>();
+        entries.sort();
+
+        for path in entries {
+            let name = match path.file_name().and_then(|n| n.to_str()) {
+                Some(name) if !name.starts_with('.') => name,
+                _ => continue,
+            };
+            let excluded = path
+                .canonicalize()
+                .map(|path| self.exclude.contains(&path))
+                .unwrap_or(true);
+            if excluded {
+                continue;
+            }
+
+            let relative = prefix.join(name);
+            if path.is_dir() {
+                self.add_dir(tar, &path, &relative, timestamp)?;
+            } else if path.is_file() {
+                let contents = fs::read(&path).map_err(|e| {
+                    format!("Could not read `{}`: {}", path.display(), e)
+                })?;
+                add_file(tar, &relative, &contents, timestamp)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Appends a file to a ustar archive.
+fn add_file(
+    tar: &mut Vec<u8>,
+    path: &Path,
+    contents: &[u8],
+    timestamp: u64,
+) -> Result<(), String> {
+    // tar paths always use `/`
+    let name = path
+        .iter()
+        .map(|part| part.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    if name.len() > 100 {
+        return Err(format!("`{}` is too long a path to archive", name));
+    }
+
+    let mut header = [0u8; 512];
+    let mut field = |offset: usize, value: &[u8]| {
+        header[offset..offset + value.len()].copy_from_slice(value);
+    };
+    field(0, name.as_bytes());
+    field(100, b"0000644\0");
+    field(108, b"0000000\0");
+    field(116, b"0000000\0");
+    field(124, format!("{:011o}\0", contents.len()).as_bytes());
+    field(136, format!("{:011o}\0", timestamp).as_bytes());
+    field(156, b"0");
+    field(257, b"ustar\0");
+    field(263, b"00");
+
+    // the checksum is summed with its own field as spaces
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|b| *b as u32).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+
+    tar.extend_from_slice(&header);
+    tar.extend_from_slice(contents);
+    let padding = (512 - contents.len() % 512) % 512;
+    tar.resize(tar.len() + padding, 0);
+    Ok(())
+}
+
+/// Wraps deflated bytes in a minimal gzip member.
+fn gzip(bytes: &[u8], timestamp: u64) -> Vec<u8> {
+    let mut gzip = vec![0x1f, 0x8b, 8, 0];
+    gzip.extend_from_slice(&(timestamp as u32).to_le_bytes());
+    // no extra flags, unknown operating system
+    gzip.extend_from_slice(&[0, 255]);
+    gzip.extend_from_slice(&deflate::deflate_bytes(bytes));
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(bytes);
+    gzip.extend_from_slice(&crc.finalize().to_le_bytes());
+    gzip.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    gzip
+}
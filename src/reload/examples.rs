@@ -0,0 +1,72 @@
+use std::path::Path;
+
+use include_dir::{
+    include_dir,
+    Dir,
+};
+
+use crate::diagnostic;
+
+/// A runnable project built into the binary, to start from
+/// or learn from, see [`EXAMPLES`].
+pub struct Example {
+    pub name:        &'static str,
+    pub description: &'static str,
+    pub dir:         Dir<'static>,
+}
+
+/// Every example, from the `gallery` directory of the repo.
+pub const EXAMPLES: &[Example] = &[
+    Example {
+        name:        "feedback",
+        description: "A trail left by a dot, zoomed and faded every \
+                      frame by a recurrent shader",
+        dir:         include_dir!("./gallery/feedback"),
+    },
+    Example {
+        name:        "audio-reactive",
+        description: "Bars following the spectrum of the default \
+                      audio input. Needs ffmpeg",
+        dir:         include_dir!("./gallery/audio_reactive"),
+    },
+    Example {
+        name:        "bloom",
+        description: "Lights that glow, blurred with a pyramid and \
+                      added back over the scene",
+        dir:         include_dir!("./gallery/bloom"),
+    },
+    Example {
+        name:        "game-of-life",
+        description: "Conway's game of life, colored by a second \
+                      recurrent shader",
+        dir:         include_dir!("./gallery/game_of_life"),
+    },
+];
+
+impl Example {
+    /// Looks an example up by name, suggesting the closest
+    /// one if there is none.
+    pub fn find(name: &str) -> Result<&'static Example, String> {
+        EXAMPLES
+            .iter()
+            .find(|example| example.name == name)
+            .ok_or_else(|| {
+                diagnostic::suggest(
+                    format!("There is no example `{}`", name),
+                    name,
+                    EXAMPLES.iter().map(|example| example.name),
+                )
+            })
+    }
+
+    /// Writes the example's files into a directory.
+    pub fn extract(&self, path: &Path) -> Result<(), String> {
+        self.dir.extract(path).map_err(|e| {
+            format!(
+                "Could not write the example into `{}`: {}",
+                path.display(),
+                e
+            )
+        })
+    }
+}
@@ -0,0 +1,333 @@
+//! Runs projects straight from a link, e.g. one shared by
+//! someone else. Projects are downloaded once into the
+//! user's [`cache_dir`], with `curl` and `tar` for archives
+//! and `git` for repositories, and are run from there
+//! after, even offline.
+
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    process::Command,
+};
+
+use sha1::{
+    Digest,
+    Sha1,
+};
+
+use crate::reload::{
+    cache_dir,
+    PROJECTS_FILE,
+};
+
+/// Whether a project is given as a link to fetch, rather
+/// than as a path.
+pub fn is_url(project: &str) -> bool {
+    ["http://", "https://", "git://", "ssh://", "git@"]
+        .iter()
+        .any(|scheme| project.starts_with(scheme))
+}
+
+/// How a project is downloaded.
+enum Source {
+    /// A `.tar.gz`, `.tgz`, or `.tar` archive.
+    Archive {
+        gzip: bool,
+    },
+    Git,
+}
+
+impl Source {
+    fn of(url: &str) -> Result<Source, String> {
+        let path = url.split(['?', '#']).next().unwrap_or(url);
+        if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+            Ok(Source::Archive { gzip: true })
+        } else if path.ends_with(".tar") {
+            Ok(Source::Archive { gzip: false })
+        } else if path.ends_with(".git")
+            || !url.starts_with("http://") && !url.starts_with("https://")
+        {
+            Ok(Source::Git)
+        } else {
+            Err(format!(
+                "Expected a .tar.gz, .tgz, or .tar archive, or a git \
+                 repository, found `{}`",
+                url
+            ))
+        }
+    }
+}
+
+/// Downloads a project, unless it already has been and
+/// not `refresh`, and returns its directory. `checksum` is
+/// the SHA-1 an archive must have, in hex, or the commit to
+/// check out of a repository, so that what runs is what
+/// was shared. Archives are checked every time they run.
+pub fn fetch(
+    url: &str,
+    checksum: Option<&str>,
+    refresh: bool,
+) -> Result<PathBuf, String> {
+    let cache = cache_dir()
+        .ok_or_else(|| "Could not find a cache directory".to_string())?
+        .join("projects");
+    fs::create_dir_all(&cache).map_err(|e| {
+        format!("Could not create `{}`: {}", cache.display(), e)
+    })?;
+    let name = key(url);
+    let dir = cache.join(&name);
+
+    match Source::of(url)? {
+        Source::Archive { gzip } => {
+            let archive = cache.join(format!("{}.tar", name));
+            let fresh = refresh || !archive.is_file();
+            if fresh {
+                eprintln!("[info] Downloading `{}`", url);
+                let part = cache.join(format!("{}.part", name));
+                let mut curl = Command::new("curl");
+                curl.args(["-fsSL", "-o"]).arg(&part).arg(url);
+                output(&mut curl, "curl")?;
+                rename(&part, &archive)?;
+            }
+            if let Some(checksum) = checksum {
+                verify(&archive, checksum)?;
+            }
+            if fresh || !dir.is_dir() {
+                unpack(&archive, &dir, gzip)?;
+            }
+        },
+        Source::Git => {
+            let commit = checksum.map(commit).transpose()?;
+            if !dir.is_dir() {
+                eprintln!("[info] Cloning `{}`", url);
+                let part = cache.join(format!("{}.part", name));
+                remove(&part)?;
+                let mut clone = git(None);
+                clone.args(["clone", "--depth", "1", url]).arg(&part);
+                output(&mut clone, "git")?;
+                rename(&part, &dir)?;
+            } else if refresh {
+                eprintln!("[info] Updating `{}`", url);
+                output(git(Some(&dir)).args(["pull", "--ff-only"]), "git")?;
+            }
+            if let Some(commit) = commit {
+                checkout(&dir, &commit)?;
+            }
+        },
+    }
+    find_project(&dir, url)
+}
+
+/// A directory name for a link, readable but unique.
+fn key(url: &str) -> String {
+    let hash = Sha1::digest(url.as_bytes())
+        .iter()
+        .take(6)
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    let last = url
+        .split(['?', '#'])
+        .next()
+        .unwrap_or(url)
+        .trim_end_matches('/')
+        .rsplit(['/', ':'])
+        .next()
+        .unwrap_or("project");
+    let stem = ["tar.gz", "tgz", "tar", "git"]
+        .iter()
+        .find_map(|ext| last.strip_suffix(&format!(".{}", ext)))
+        .unwrap_or(last);
+    let stem = stem
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect::<String>();
+    format!("{}-{}", stem, hash)
+}
+
+/// Checks the SHA-1 of a downloaded archive.
+fn verify(archive: &Path, checksum: &str) -> Result<(), String> {
+    let bytes = fs::read(archive).map_err(|e| {
+        format!("Could not read `{}`: {}", archive.display(), e)
+    })?;
+    let hash = Sha1::digest(&bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    if !hash.eq_ignore_ascii_case(checksum.trim()) {
+        return Err(format!(
+            "The download has SHA-1 {}, not {} as expected, refusing to \
+             run it. Pass --refresh to download it again",
+            hash, checksum
+        ));
+    }
+    Ok(())
+}
+
+/// Unpacks an archive into a new directory, replacing the
+/// old one only once it's unpacked.
+fn unpack(archive: &Path, dir: &Path, gzip: bool) -> Result<(), String> {
+    let part = dir.with_extension("part");
+    remove(&part)?;
+    fs::create_dir_all(&part)
+        .map_err(|e| format!("Could not create `{}`: {}", part.display(), e))?;
+    let mut tar = Command::new("tar");
+    tar.arg(if gzip { "-xzf" } else { "-xf" })
+        .arg(archive)
+        .arg("-C")
+        .arg(&part);
+    output(&mut tar, "tar")?;
+    remove(dir)?;
+    rename(&part, dir)
+}
+
+/// The commit a repository must be checked out at, as its
+/// full SHA-1 in lowercase hex. Abbreviated commits aren't
+/// enough to make sure what runs is what was shared, as
+/// another commit can be made to start the same way.
+fn commit(checksum: &str) -> Result<String, String> {
+    let commit = checksum.trim().to_ascii_lowercase();
+    if commit.len() != 40 || !commit.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(format!(
+            "Expected the full 40 character SHA-1 of a commit, found `{}`",
+            checksum
+        ));
+    }
+    Ok(commit)
+}
+
+/// Checks out a commit of a shallow clone, fetching it if
+/// it isn't there. `commit` is a full SHA-1, see
+/// [`commit`].
+fn checkout(dir: &Path, commit: &str) -> Result<(), String> {
+    let run = |args: &[&str]| output(git(Some(dir)).args(args), "git");
+    if run(&["rev-parse", "HEAD"])? != commit {
+        run(&["fetch", "--depth", "1", "origin", commit])?;
+        run(&["checkout", "--detach", "FETCH_HEAD"])?;
+    }
+    let head = run(&["rev-parse", "HEAD"])?;
+    if head != commit {
+        return Err(format!(
+            "The repository is at commit {}, not {} as expected",
+            head, commit
+        ));
+    }
+    Ok(())
+}
+
+/// The project in a download: its root, or the only
+/// directory in it, as archives often wrap everything in
+/// one.
+fn find_project(dir: &Path, url: &str) -> Result<PathBuf, String> {
+    let is_project = |dir: &Path| {
+        dir.join("shader.graph").is_file() || dir.join(PROJECTS_FILE).is_file()
+    };
+    if is_project(dir) {
+        return Ok(dir.to_path_buf());
+    }
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("Could not read `{}`: {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            // like .git, or the metadata of macOS archives
+            path.file_name()
+                .is_some_and(|name| !name.to_string_lossy().starts_with('.'))
+        })
+        .collect::<Vec<_>>();
+    match entries.as_slice() {
+        [only] if only.is_dir() && is_project(only) => Ok(only.clone()),
+        _ => Err(format!("`{}` has no shader.graph", url)),
+    }
+}
+
+/// A `git` command, run in a repository if given.
+fn git(dir: Option<&Path>) -> Command {
+    let mut command = Command::new("git");
+    if let Some(dir) = dir {
+        command.arg("-C").arg(dir);
+    }
+    command
+}
+
+/// Runs a command, returning what it printed.
+fn output(command: &mut Command, program: &str) -> Result<String, String> {
+    let output = command
+        .output()
+        .map_err(|e| format!("Could not start {}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn rename(from: &Path, to: &Path) -> Result<(), String> {
+    fs::rename(from, to)
+        .map_err(|e| format!("Could not move `{}`: {}", from.display(), e))
+}
+
+/// Removes a file or directory, if it's there.
+fn remove(path: &Path) -> Result<(), String> {
+    let removed = if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else if path.exists() {
+        fs::remove_file(path)
+    } else {
+        return Ok(());
+    };
+    removed.map_err(|e| format!("Could not remove `{}`: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_downloads_by_link() {
+        let name = key("https://example.com/gardens/life.tar.gz?v=2");
+        assert!(name.starts_with("life-"), "{}", name);
+        assert_eq!(name.len(), "life-".len() + 12);
+        assert_eq!(name, key("https://example.com/gardens/life.tar.gz?v=2"));
+        assert_ne!(name, key("https://example.com/gardens/life.tar.gz?v=3"));
+        assert!(key("git@github.com:someone/my garden.git")
+            .starts_with("mygarden-"));
+        assert!(key("https://example.com/").starts_with("examplecom-"));
+        // never a path, whatever the link
+        assert!(key("https://example.com/a/..").starts_with('-'));
+        assert!(!key("https://example.com/a\\..\\b").contains(['\\', '.']));
+    }
+
+    #[test]
+    fn tells_archives_from_repositories() {
+        let of = |url| {
+            Source::of(url).map(|source| match source {
+                Source::Archive { gzip } => Some(gzip),
+                Source::Git => None,
+            })
+        };
+        assert_eq!(of("https://example.com/a.tar.gz"), Ok(Some(true)));
+        assert_eq!(of("https://example.com/a.tgz#top"), Ok(Some(true)));
+        assert_eq!(of("https://example.com/a.tar?dl=1"), Ok(Some(false)));
+        assert_eq!(of("https://github.com/someone/a.git"), Ok(None));
+        assert_eq!(of("git@github.com:someone/a"), Ok(None));
+        assert_eq!(of("ssh://example.com/a"), Ok(None));
+        assert!(of("https://example.com/a.zip").is_err());
+        assert!(of("https://example.com/a").is_err());
+    }
+
+    #[test]
+    fn checks_out_only_full_commits() {
+        let full = "0123456789abcdef0123456789ABCDEF01234567";
+        assert_eq!(commit(full), Ok(full.to_ascii_lowercase()));
+        assert!(commit("").is_err());
+        assert!(commit("0123456").is_err());
+        assert!(commit(&full[..39]).is_err());
+        assert!(commit(&full.replace('a', "g")).is_err());
+    }
+}
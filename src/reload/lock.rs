@@ -0,0 +1,193 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use serde_json::{
+    json,
+    Value,
+};
+use sha1::{
+    Digest,
+    Sha1,
+};
+
+/// The lockfile of a project, in its directory.
+pub const LOCK_FILE: &str = "shader.lock";
+
+/// The version of shadergarden recorded in lockfiles.
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// A record of every file a graph was built from, by hash,
+/// and the version of shadergarden that built it, so that
+/// an installation can refuse to run anything but the
+/// content that was vetted, see [`Lockfile::differences`].
+/// Written as JSON:
+///
+/// ```json
+/// {
+///   "shadergarden": "0.2.0",
+///   "files": { "shader.graph": "<sha1>", "blur.frag": "<sha1>" }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lockfile {
+    pub version: String,
+    /// The SHA-1 of each file, by its path relative to the
+    /// project, or as given for files outside of it.
+    pub files:   BTreeMap<String, String>,
+}
+
+impl Lockfile {
+    /// Hashes files as they are now, with this version of
+    /// shadergarden.
+    pub fn new<I>(project: &Path, files: I) -> Result<Lockfile, String>
+    where
+        I: IntoIterator<Item = PathBuf>,
+    {
+        let mut hashes = BTreeMap::new();
+        for file in files {
+            let bytes = fs::read(&file).map_err(|e| {
+                format!("Could not read `{}`: {}", file.display(), e)
+            })?;
+            let hash = Sha1::digest(&bytes)
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>();
+            hashes.insert(relative(project, &file), hash);
+        }
+        Ok(Lockfile {
+            version: VERSION.to_string(),
+            files:   hashes,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Lockfile, String> {
+        let error = |e: String| format!("In `{}`: {}", path.display(), e);
+        let source = fs::read_to_string(path).map_err(|e| {
+            format!("Could not read `{}`: {}", path.display(), e)
+        })?;
+        let json: Value =
+            serde_json::from_str(&source).map_err(|e| error(e.to_string()))?;
+
+        let version = json["shadergarden"]
+            .as_str()
+            .ok_or_else(|| error("Expected a `shadergarden` version".into()))?;
+        let mut files = BTreeMap::new();
+        let listed = json["files"]
+            .as_object()
+            .ok_or_else(|| error("Expected an object of `files`".into()))?;
+        for (file, hash) in listed {
+            let hash = hash.as_str().ok_or_else(|| {
+                error(format!("Expected a hash for `{}`", file))
+            })?;
+            files.insert(file.clone(), hash.to_string());
+        }
+        Ok(Lockfile {
+            version: version.to_string(),
+            files,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = json!({
+            "shadergarden": self.version,
+            "files": self.files,
+        });
+        // unwrap: a map of strings always serializes
+        let source = serde_json::to_string_pretty(&json).unwrap() + "\n";
+        fs::write(path, source)
+            .map_err(|e| format!("Could not write `{}`: {}", path.display(), e))
+    }
+
+    /// What differs in a lockfile made since, e.g. of the
+    /// graph as it is built now: files added, removed, or
+    /// changed, and the version. Empty if nothing has.
+    pub fn differences(&self, now: &Lockfile) -> Vec<String> {
+        let mut differences = vec![];
+        if self.version != now.version {
+            differences.push(format!(
+                "locked with shadergarden {}, this is {}",
+                self.version, now.version
+            ));
+        }
+        for (file, hash) in self.files.iter() {
+            match now.files.get(file) {
+                Some(now) if now == hash => (),
+                Some(_) => differences.push(format!("`{}` changed", file)),
+                None => differences.push(format!("`{}` is unused", file)),
+            }
+        }
+        for file in now.files.keys() {
+            if !self.files.contains_key(file) {
+                differences.push(format!("`{}` is not locked", file));
+            }
+        }
+        differences
+    }
+}
+
+/// Locks a project to the files its graph was just built
+/// from, writing its lockfile, see `--lock`. Returns the
+/// path of the lockfile.
+pub fn write(project: &Path, files: Vec<PathBuf>) -> Result<PathBuf, String> {
+    let path = project.join(LOCK_FILE);
+    Lockfile::new(project, files)?.save(&path)?;
+    Ok(path)
+}
+
+/// Checks the files a project's graph was just built from
+/// against its lockfile, see `--verify`.
+pub fn verify(project: &Path, files: Vec<PathBuf>) -> Result<(), String> {
+    let path = project.join(LOCK_FILE);
+    let locked = Lockfile::load(&path)?;
+    let differences = locked.differences(&Lockfile::new(project, files)?);
+    if differences.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "The project does not match `{}`: {}",
+        path.display(),
+        differences.join(", ")
+    ))
+}
+
+/// A path relative to the project, with `/` between parts
+/// on every platform, or as given if it's outside.
+fn relative(project: &Path, file: &Path) -> String {
+    match file.strip_prefix(project) {
+        Ok(inside) => inside
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+        Err(_) => file.display().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifies_what_was_locked() {
+        let project = std::env::temp_dir()
+            .join(format!("shadergarden-lock-{}", std::process::id()));
+        fs::create_dir_all(&project).unwrap();
+        let graph = project.join("shader.graph");
+        fs::write(&graph, "(output (shader \"a\" 64 64))").unwrap();
+
+        let written = write(&project, vec![graph.clone()]);
+        let locked = verify(&project, vec![graph.clone()]);
+        fs::write(&graph, "(output (shader \"b\" 64 64))").unwrap();
+        let changed = verify(&project, vec![graph]);
+        fs::remove_dir_all(&project).unwrap();
+        assert_eq!(written, Ok(project.join(LOCK_FILE)));
+        assert_eq!(locked, Ok(()));
+        assert!(changed.unwrap_err().contains("`shader.graph` changed"));
+    }
+}
@@ -1,8 +1,40 @@
+pub mod archive;
+pub mod examples;
+pub mod fetch;
+pub mod lock;
+pub mod post;
+pub mod projects;
 pub mod shader_dir;
+pub mod shadertoy;
 pub mod watcher;
 
+pub use archive::Archive;
+pub use examples::{
+    Example,
+    EXAMPLES,
+};
+pub use fetch::{
+    fetch,
+    is_url,
+};
+pub use lock::{
+    Lockfile,
+    LOCK_FILE,
+};
+pub use post::{
+    cache_dir,
+    config_dir,
+    PostChain,
+};
+pub use projects::{
+    Projects,
+    SubProject,
+    Switch,
+    PROJECTS_FILE,
+};
 pub use shader_dir::{
     ShaderDir,
+    Stage,
     BASE_PROJECT,
 };
 pub use watcher::{
@@ -0,0 +1,215 @@
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    rc::Rc,
+    sync::{
+        atomic::{
+            AtomicBool,
+            Ordering,
+        },
+        Arc,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use glium::{
+    backend::Context,
+    Texture2d,
+};
+use notify::{
+    RecommendedWatcher,
+    RecursiveMode,
+    Watcher,
+};
+
+use crate::{
+    diagnostic::BuildError,
+    graph::{
+        NodeId,
+        ShaderGraph,
+    },
+    lisp::{
+        graph_from_sexp_with_options,
+        BuildOptions,
+    },
+    map,
+    reload::{
+        ShaderDir,
+        WatchResult,
+    },
+};
+
+/// The user's config directory: `$XDG_CONFIG_HOME`,
+/// `~/.config`, or `%APPDATA%` on Windows, followed by
+/// `shadergarden`.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            let home = std::env::var_os("HOME")?;
+            Some(Path::new(&home).join(".config"))
+        })
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(base.join("shadergarden"))
+}
+
+/// The user's cache directory: `$XDG_CACHE_HOME`,
+/// `~/.cache`, or `%LOCALAPPDATA%` on Windows, followed by
+/// `shadergarden`.
+pub fn cache_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            let home = std::env::var_os("HOME")?;
+            Some(Path::new(&home).join(".cache"))
+        })
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))?;
+    Some(base.join("shadergarden"))
+}
+
+/// A graph of passes applied after the output of every
+/// project, e.g. a personal grade or film grain, loaded
+/// from `post.graph` in the user's [`config_dir`]. The
+/// graph must have exactly one input and one output, and
+/// can use the shaders next to it. `width` and `height`
+/// are bound to the size of the output.
+pub struct PostChain {
+    context:     Rc<Context>,
+    config:      PathBuf,
+    options:     BuildOptions,
+    size:        (u32, u32),
+    changed:     Arc<AtomicBool>,
+    last_reload: Instant,
+    _watcher:    RecommendedWatcher,
+    graph:       ShaderGraph,
+}
+
+impl PostChain {
+    /// Loads the post chain at `config`, and watches the
+    /// directory it is in for changes. Returns `None` if
+    /// there is no post chain.
+    pub fn load(
+        context: &Rc<Context>,
+        config: &Path,
+        size: (u32, u32),
+        options: BuildOptions,
+    ) -> Result<Option<PostChain>, String> {
+        if !config.is_file() {
+            return Ok(None);
+        }
+
+        let changed = Arc::new(AtomicBool::new(false));
+        let mut watcher = RecommendedWatcher::new({
+            let changed = changed.clone();
+            move |res| match res {
+                Ok(_) => changed.store(true, Ordering::SeqCst),
+                Err(e) => println!("[warn] Watch error: `{:?}`.", e),
+            }
+        })
+        .map_err(|e| format!("Could not watch post chain: {}", e))?;
+        let dir = config.parent().unwrap_or_else(|| Path::new("."));
+        watcher
+            .watch(dir, RecursiveMode::Recursive)
+            .map_err(|e| format!("Could not watch post chain: {}", e))?;
+
+        let graph = PostChain::build(context, config, size, &options)?;
+        Ok(Some(PostChain {
+            context: context.clone(),
+            config: config.to_path_buf(),
+            options,
+            size,
+            changed,
+            last_reload: Instant::now(),
+            _watcher: watcher,
+            graph,
+        }))
+    }
+
+    fn build(
+        context: &Rc<Context>,
+        config: &Path,
+        (width, height): (u32, u32),
+        options: &BuildOptions,
+    ) -> Result<ShaderGraph, String> {
+        let dir = config.parent().unwrap_or_else(|| Path::new("."));
+        let read = || {
+            let lisp = fs::read_to_string(config).map_err(|e| {
+                format!("Could not read `{}`: {}", config.display(), e)
+            })?;
+            Ok(format!(
+                "(let width {}) (let height {})\n{}",
+                width, height, lisp
+            ))
+        };
+        let shader_dir =
+            ShaderDir::new_from_dir(dir, options.allow_outside, read)?;
+        let graph =
+            graph_from_sexp_with_options(context, shader_dir, map! {}, options)
+                .map_err(|e| format!("In post chain: {}", e))?;
+
+        if graph.get_inputs().len() != 1 || graph.get_outputs().len() != 1 {
+            return Err(
+                "The post chain must have exactly one input and one output"
+                    .to_string(),
+            );
+        }
+        Ok(graph)
+    }
+
+    /// Rebuilds the post chain if it has changed. If it
+    /// can't be rebuilt, the old one is kept.
+    pub fn reload(&mut self) -> WatchResult {
+        if self.last_reload.elapsed() < Duration::from_millis(300)
+            || !self.changed.swap(false, Ordering::SeqCst)
+        {
+            return WatchResult::NoChange;
+        }
+
+        self.last_reload = Instant::now();
+        match PostChain::build(
+            &self.context,
+            &self.config,
+            self.size,
+            &self.options,
+        ) {
+            Ok(graph) => {
+                self.graph = graph;
+                WatchResult::Rebuilt
+            },
+            Err(e) => {
+                let dir =
+                    self.config.parent().unwrap_or_else(|| Path::new("."));
+                // the size is bound on a line before the graph
+                let error = BuildError::new(e, dir, &self.config);
+                WatchResult::Err(error.with_prelude(1))
+            },
+        }
+    }
+
+    /// Runs the post chain over the output of a project.
+    pub fn forward<'a>(&'a mut self, output: &'a Texture2d) -> &'a Texture2d {
+        let (input, result) = self.ends();
+        self.graph.forward(map! { input => output })[&result]
+    }
+
+    /// Returns the result of the last run, without running
+    /// the post chain again, e.g. while paused.
+    pub fn pull<'a>(&'a self, output: &'a Texture2d) -> &'a Texture2d {
+        let (input, result) = self.ends();
+        self.graph.pull_outputs(map! { input => output })[&result]
+    }
+
+    /// The input and output of the post chain, which are
+    /// checked to exist when it is built.
+    fn ends(&self) -> (NodeId, NodeId) {
+        (self.graph.get_inputs()[0], self.graph.get_outputs()[0])
+    }
+}
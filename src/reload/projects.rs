@@ -0,0 +1,385 @@
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    rc::Rc,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use glium::backend::Context;
+use lexpr::Value;
+
+use crate::{
+    keymap::Action,
+    lisp::BuildOptions,
+    reload::{
+        lock,
+        shader_dir::resolve,
+        ShaderGraphWatcher,
+    },
+};
+
+/// The file at the root of a directory of sub-projects that
+/// lists them, see [`Projects`].
+pub const PROJECTS_FILE: &str = "projects.lisp";
+
+/// A project among several, in a directory of its own.
+#[derive(Debug, Clone)]
+pub struct SubProject {
+    pub name: String,
+    pub dir:  PathBuf,
+}
+
+impl SubProject {
+    /// The project's graph, `shader.graph` in its
+    /// directory.
+    pub fn graph(&self) -> PathBuf { self.dir.join("shader.graph") }
+}
+
+/// A change of the project shown, see [`Projects::go`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Switch {
+    /// To the project at an index, counting from 0.
+    To(usize),
+    /// To the next project that builds, or the previous if
+    /// `false`.
+    Step(bool),
+}
+
+/// Several projects under one root, each built and watched
+/// independently, one of which is shown at a time. The root
+/// lists them in [`PROJECTS_FILE`], e.g. for a workshop:
+///
+/// ```lisp
+/// (project "alice")
+/// (project "bob/sketch" :name "Bob")
+/// ```
+///
+/// A project is built the first time it's shown, and then
+/// kept watching in the background, so switching back to
+/// it is instant. Projects from a playlist, see
+/// [`Projects::playlist`], are rebuilt each time instead.
+pub struct Projects {
+    context:  Rc<Context>,
+    options:  BuildOptions,
+    projects: Vec<SubProject>,
+    /// The watcher of every project not shown, once built,
+    /// if they are kept.
+    parked:   Vec<Option<ShaderGraphWatcher>>,
+    keep:     bool,
+    active:   usize,
+    /// When the project shown was last switched to.
+    cycled:   Instant,
+}
+
+impl Projects {
+    /// Reads the projects listed in a root directory, if it
+    /// lists any. None of them is built until
+    /// [`Projects::start`]. They must be inside the root,
+    /// unless [`BuildOptions::allow_outside`].
+    pub fn load(
+        context: &Rc<Context>,
+        root: &Path,
+        options: BuildOptions,
+    ) -> Result<Option<Projects>, String> {
+        let path = root.join(PROJECTS_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let source = fs::read_to_string(&path).map_err(|e| {
+            format!("Could not read `{}`: {}", path.display(), e)
+        })?;
+        let projects = Projects::parse(root, &source, options.allow_outside)
+            .map_err(|e| format!("In `{}`: {}", path.display(), e))?;
+        if projects.is_empty() {
+            return Err(format!("`{}` lists no projects", path.display()));
+        }
+
+        Ok(Some(Projects::new(context, projects, options, true)))
+    }
+
+    /// Reads a playlist, a file listing project directories
+    /// one per line, relative to the file. Empty lines and
+    /// lines starting with `#` are skipped. Each project is
+    /// rebuilt from scratch whenever it's shown.
+    pub fn playlist(
+        context: &Rc<Context>,
+        path: &Path,
+        options: BuildOptions,
+    ) -> Result<Projects, String> {
+        let source = fs::read_to_string(path).map_err(|e| {
+            format!("Could not read `{}`: {}", path.display(), e)
+        })?;
+        let root = path.parent().unwrap_or_else(|| Path::new("."));
+        let projects = source
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| SubProject {
+                name: line.to_string(),
+                dir:  root.join(line),
+            })
+            .collect::<Vec<_>>();
+        if projects.is_empty() {
+            return Err(format!("`{}` lists no projects", path.display()));
+        }
+        Ok(Projects::new(context, projects, options, false))
+    }
+
+    fn new(
+        context: &Rc<Context>,
+        projects: Vec<SubProject>,
+        options: BuildOptions,
+        keep: bool,
+    ) -> Projects {
+        Projects {
+            context: context.clone(),
+            options,
+            parked: projects.iter().map(|_| None).collect(),
+            projects,
+            keep,
+            active: 0,
+            cycled: Instant::now(),
+        }
+    }
+
+    fn parse(
+        root: &Path,
+        source: &str,
+        allow_outside: bool,
+    ) -> Result<Vec<SubProject>, String> {
+        let options = lexpr::parse::Options::default()
+            .with_keyword_syntax(lexpr::parse::KeywordSyntax::ColonPrefix);
+        let forms = lexpr::from_str_custom(&format!("({})", source), options)
+            .map_err(|e| format!("{}", e))?;
+        let forms = forms
+            .list_iter()
+            .ok_or_else(|| "Expected a list of projects".to_string())?;
+
+        let mut projects: Vec<SubProject> = vec![];
+        for form in forms {
+            let parts: Vec<&Value> = form
+                .list_iter()
+                .ok_or_else(|| format!("Expected a project, found `{}`", form))?
+                .collect();
+
+            let (dir, name) = match parts.as_slice() {
+                [project, dir] if project.as_symbol() == Some("project") => {
+                    (dir, None)
+                },
+                [project, dir, key, name]
+                    if project.as_symbol() == Some("project")
+                        && key.as_keyword() == Some("name") =>
+                {
+                    (dir, Some(name))
+                },
+                _ => {
+                    return Err(format!(
+                        "Expected `(project \"<dir>\" [:name \"<name>\"])`, \
+                         found `{}`",
+                        form
+                    ))
+                },
+            };
+            let dir = dir.as_str().ok_or_else(|| {
+                format!("Expected a directory as a string, found `{}`", dir)
+            })?;
+            let name = match name {
+                Some(name) => {
+                    name.as_str().map(str::to_string).ok_or_else(|| {
+                        format!("Expected a name as a string, found `{}`", name)
+                    })?
+                },
+                None => dir.to_string(),
+            };
+            if projects.iter().any(|project| project.name == name) {
+                return Err(format!("Project `{}` is listed twice", name));
+            }
+            projects.push(SubProject {
+                name,
+                dir: resolve(root, dir, allow_outside)?,
+            });
+        }
+        Ok(projects)
+    }
+
+    pub fn len(&self) -> usize { self.projects.len() }
+
+    pub fn is_empty(&self) -> bool { self.projects.is_empty() }
+
+    /// The index of the project shown.
+    pub fn active(&self) -> usize { self.active }
+
+    pub fn project(&self, index: usize) -> Option<&SubProject> {
+        self.projects.get(index)
+    }
+
+    /// Looks up a project by name.
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.projects
+            .iter()
+            .position(|project| project.name == name)
+    }
+
+    /// Builds the watcher of the first project that builds,
+    /// for the first graph, skipping the others with a
+    /// warning.
+    pub fn start(&mut self) -> Result<ShaderGraphWatcher, String> {
+        for index in 0..self.len() {
+            match self.build(index) {
+                Ok(watcher) => {
+                    self.active = index;
+                    return Ok(watcher);
+                },
+                Err(e) => eprintln!("[warn] {}", e),
+            }
+        }
+        Err("None of the projects could be built".to_string())
+    }
+
+    fn build(&self, index: usize) -> Result<ShaderGraphWatcher, String> {
+        let project = &self.projects[index];
+        ShaderGraphWatcher::new_watch_dir(
+            &self.context,
+            project.dir.clone(),
+            project.graph(),
+            self.options.clone(),
+        )
+        .map_err(|e| {
+            format!("Could not build project `{}`: {}", project.name, e)
+        })
+    }
+
+    /// Shows another project, swapping `watcher`, the
+    /// watcher of the project shown, for its own, which
+    /// is built if it hasn't been. If it can't be
+    /// built, the project shown stays as it is.
+    pub fn switch(
+        &mut self,
+        index: usize,
+        watcher: &mut ShaderGraphWatcher,
+    ) -> Result<&SubProject, String> {
+        if index >= self.len() {
+            return Err(format!(
+                "There is no project {}, only {}",
+                index + 1,
+                self.len()
+            ));
+        }
+        if index != self.active {
+            let next = match self.parked[index].take() {
+                Some(next) => next,
+                None => self.build(index)?,
+            };
+            let last = std::mem::replace(watcher, next);
+            if self.keep {
+                self.parked[self.active] = Some(last);
+            }
+            self.active = index;
+        }
+        Ok(&self.projects[index])
+    }
+
+    /// Shows the next project that builds, or the previous
+    /// if not `forward`, skipping the others with a
+    /// warning. If none of them builds, the project
+    /// shown stays.
+    pub fn step(
+        &mut self,
+        forward: bool,
+        watcher: &mut ShaderGraphWatcher,
+    ) -> Result<&SubProject, String> {
+        let count = self.len();
+        for offset in 1..count {
+            let index = match forward {
+                true => (self.active + offset) % count,
+                false => (self.active + count - offset) % count,
+            };
+            match self.switch(index, watcher) {
+                Ok(_) => return Ok(&self.projects[index]),
+                Err(e) => eprintln!("[warn] {}", e),
+            }
+        }
+        Err("None of the other projects could be built".to_string())
+    }
+
+    /// The switch asked for in a frame, if any: by a key
+    /// binding, by a command naming a project, or to the
+    /// next project once the one shown has been shown for
+    /// `cycle`, see `--cycle`.
+    pub fn wanted(
+        &self,
+        action: Option<Action>,
+        name: Option<&str>,
+        cycle: Option<Duration>,
+    ) -> Option<Switch> {
+        let mut index = name.and_then(|name| {
+            let found = self.find(name);
+            if found.is_none() {
+                eprintln!("[warn] There is no project named `{}`", name);
+            }
+            found
+        });
+        if let Some(Action::Project(number)) = action {
+            index = Some(number - 1);
+        }
+        let due = cycle.is_some_and(|cycle| self.cycled.elapsed() >= cycle);
+        match (action, index) {
+            (Some(Action::NextProject), _) => Some(Switch::Step(true)),
+            (Some(Action::PreviousProject), _) => Some(Switch::Step(false)),
+            (_, Some(index)) if index != self.active => Some(Switch::To(index)),
+            (_, None) if due => Some(Switch::Step(true)),
+            _ => None,
+        }
+    }
+
+    /// Switches the project shown, see [`Projects::switch`]
+    /// and [`Projects::step`]. With `verify`, a project
+    /// that doesn't match its lockfile is never shown,
+    /// and the project shown stays as it is, see
+    /// `--verify`.
+    pub fn go(
+        &mut self,
+        switch: Switch,
+        watcher: &mut ShaderGraphWatcher,
+        verify: bool,
+    ) -> Result<&SubProject, String> {
+        self.cycled = Instant::now();
+        let previous = self.active;
+        match switch {
+            Switch::To(index) => self.switch(index, watcher)?,
+            Switch::Step(forward) => self.step(forward, watcher)?,
+        };
+        if verify {
+            let dir = &self.projects[self.active].dir;
+            if let Err(e) = lock::verify(dir, watcher.files()) {
+                let _ = self.switch(previous, watcher);
+                return Err(e);
+            }
+        }
+        Ok(&self.projects[self.active])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_projects_inside_the_root() {
+        let root = Path::new("root");
+        let parse = |source| Projects::parse(root, source, false);
+        let projects = parse("(project \"a\") (project \"b/c\" :name \"C\")");
+        let projects = projects.unwrap();
+        assert_eq!(projects[1].name, "C");
+        assert_eq!(projects[1].dir, root.join("b/c"));
+        assert!(parse("(project \"../elsewhere\")").is_err());
+        assert!(parse("(project \"/home\")").is_err());
+        assert!(Projects::parse(root, "(project \"/home\")", true).is_ok());
+    }
+}
@@ -1,8 +1,17 @@
 use std::{
-    collections::BTreeMap,
+    collections::{
+        BTreeMap,
+        BTreeSet,
+    },
     ffi::OsStr,
+    fmt,
     fs,
-    path::Path,
+    path::{
+        Component,
+        Path,
+        PathBuf,
+    },
+    thread,
 };
 
 use include_dir::{
@@ -10,13 +19,292 @@ use include_dir::{
     Dir,
 };
 
+use crate::reload::shadertoy;
+
 pub const BASE_PROJECT: Dir = include_dir!("./demos/base");
 
+/// What a shader file holds, from its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// `.frag`, the shaders most nodes run.
+    Fragment,
+    /// `.vert`, which no node runs yet.
+    Vertex,
+    /// `.comp`, run by `compute` nodes.
+    Compute,
+    /// `.glsl`, code shared with `#include` or `preamble`.
+    Library,
+    /// `.wgsl`, which OpenGL can't run.
+    Wgsl,
+    /// `.fs`, Interactive Shader Format shaders.
+    Isf,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 6] = [
+        Stage::Fragment,
+        Stage::Vertex,
+        Stage::Compute,
+        Stage::Library,
+        Stage::Wgsl,
+        Stage::Isf,
+    ];
+
+    /// The stage of a file extension, if it's a shader.
+    pub fn from_extension(extension: &str) -> Option<Stage> {
+        Stage::ALL
+            .iter()
+            .copied()
+            .find(|s| s.extension() == extension)
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Stage::Fragment => "frag",
+            Stage::Vertex => "vert",
+            Stage::Compute => "comp",
+            Stage::Library => "glsl",
+            Stage::Wgsl => "wgsl",
+            Stage::Isf => "fs",
+        }
+    }
+
+    /// Splits the name a shader is looked up by, see
+    /// [`shader_name`], into the name it was given and its
+    /// stage.
+    pub fn split(name: &str) -> (&str, Stage) {
+        let stage =
+            |(stem, extension)| Some((stem, Stage::from_extension(extension)?));
+        match name.rsplit_once('.').and_then(stage) {
+            Some((stem, stage)) if stage != Stage::Fragment => (stem, stage),
+            _ => (name, Stage::Fragment),
+        }
+    }
+
+    /// The name a shader of this stage is looked up by,
+    /// the inverse of [`Stage::split`].
+    pub fn name(self, stem: &str) -> String {
+        match self {
+            Stage::Fragment => stem.to_string(),
+            _ => format!("{}.{}", stem, self.extension()),
+        }
+    }
+
+    /// Whether a shader of another stage can be used where
+    /// one of this stage is needed: shared code can come
+    /// from a fragment shader, as it always could.
+    pub fn accepts(self, other: Stage) -> bool {
+        self == other || (self == Stage::Library && other == Stage::Fragment)
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Stage::Fragment => "a fragment shader",
+            Stage::Vertex => "a vertex shader",
+            Stage::Compute => "a compute shader",
+            Stage::Library => "shared GLSL code",
+            Stage::Wgsl => "a WGSL shader, for WebGPU",
+            Stage::Isf => "an ISF shader",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The name a shader file is looked up by: its name without
+/// the extension for `.frag` fragment shaders, and with it
+/// for every other [`Stage`], so they can share a name.
+/// Other files, like images or notes, are not shaders.
+pub fn shader_name(path: &Path) -> Result<Option<String>, String> {
+    let (name, extension) = (path.file_name(), path.extension());
+    let name = match extension.and_then(OsStr::to_str) {
+        Some("frag") => path.file_stem(),
+        Some(other) if Stage::from_extension(other).is_some() => name,
+        _ => return Ok(None),
+    };
+    let name = name
+        .ok_or("Could not infer shader name from file name")?
+        .to_str()
+        .ok_or("Could not convert file name to UTF8 string")?;
+    Ok(Some(name.to_string()))
+}
+
+/// The file in a directory a shader is read from, the
+/// inverse of [`shader_name`].
+pub fn shader_path(dir: &Path, name: &str) -> PathBuf {
+    match Stage::split(name) {
+        (_, Stage::Fragment) => dir.join(format!("{}.frag", name)),
+        _ => dir.join(name),
+    }
+}
+
+/// Every shader file in a directory, by name, see
+/// [`shader_name`], without reading any of them. Unless
+/// `allow_outside`, a symlink to a file outside the
+/// directory is an error, see [`resolve`].
+pub fn shader_files(
+    dir: &Path,
+    allow_outside: bool,
+) -> Result<BTreeMap<String, PathBuf>, String> {
+    let files = fs::read_dir(dir)
+        .map_err(|_| "Could not read shader directory".to_string())?;
+
+    let mut shaders = BTreeMap::new();
+    for p in files {
+        let path = p.map_err(|_| "Got a bad file path".to_string())?.path();
+        // by name first, as only shaders are worth a stat
+        let name = match shader_name(&path)? {
+            Some(name) if !path.is_dir() => name,
+            _ => continue,
+        };
+        // unwrap: shaders are named after their file
+        let file = path.file_name().and_then(OsStr::to_str).unwrap();
+        shaders.insert(name, resolve(dir, file, allow_outside)?);
+    }
+    Ok(shaders)
+}
+
+/// The fewest files each thread reads in [`read_shaders`],
+/// as shaders are small, and threads aren't free.
+const FILES_PER_THREAD: usize = 16;
+
+/// Reads shader files, found with [`shader_files`], a few
+/// threads at a time. Other paths aren't checked.
+pub fn read_shaders(
+    files: &BTreeMap<String, PathBuf>,
+) -> Result<BTreeMap<String, String>, String> {
+    let files = files.iter().collect::<Vec<_>>();
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = files.len().div_ceil(threads).max(FILES_PER_THREAD);
+
+    thread::scope(|scope| {
+        let readers = files
+            .chunks(chunk)
+            .map(|files| scope.spawn(move || read_each(files)))
+            .collect::<Vec<_>>();
+        let mut shaders = BTreeMap::new();
+        for reader in readers {
+            // unwrap: reading files doesn't panic
+            shaders.extend(reader.join().unwrap()?);
+        }
+        Ok(shaders)
+    })
+}
+
+fn read_each(
+    files: &[(&String, &PathBuf)],
+) -> Result<Vec<(String, String)>, String> {
+    files
+        .iter()
+        .map(|(name, path)| {
+            let contents = fs::read_to_string(path).map_err(|e| {
+                format!("Could not read `{}`: {}", path.display(), e)
+            })?;
+            Ok((name.to_string(), contents))
+        })
+        .collect()
+}
+
+/// Every word of a config that could name a shader: its
+/// symbols and strings, and the odd word of a comment.
+fn words(lisp: &str) -> BTreeSet<&str> {
+    lisp.split(|c: char| c.is_whitespace() || "()[]\"';".contains(c))
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Resolves a path named in a project, like an image or an
+/// include, relative to its `root`. Unless `allow_outside`,
+/// paths that lead out of the root are an error: absolute
+/// paths, `..` past the root, and symlinks to elsewhere, so
+/// that a downloaded project can't read arbitrary files.
+pub fn resolve(
+    root: &Path,
+    file: &str,
+    allow_outside: bool,
+) -> Result<PathBuf, String> {
+    let path = root.join(file);
+    if allow_outside {
+        return Ok(path);
+    }
+    let outside =
+        || Err(format!("`{}` is outside the project directory", file));
+
+    // by name first, for files that don't exist yet
+    let mut depth = 0;
+    for part in Path::new(file).components() {
+        match part {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => (),
+            Component::ParentDir if depth > 0 => depth -= 1,
+            _ => return outside(),
+        }
+    }
+    // then through symlinks, for files that do
+    if let (Ok(root), Ok(real)) = (root.canonicalize(), path.canonicalize()) {
+        if !real.starts_with(root) {
+            return outside();
+        }
+    }
+    Ok(path)
+}
+
+/// The `ffmpeg` input formats that capture from a device,
+/// rather than reading a file or a URL.
+const CAPTURE_FORMATS: &[&str] =
+    &["v4l2", "avfoundation", "dshow", "pulse", "alsa"];
+
+/// Checks a capture device named in a project, written
+/// `<format>:<device>`, as `ffmpeg` takes it. Unless
+/// `allow_outside`, only capture formats are allowed, and
+/// devices that look like paths or URLs aren't, other than
+/// `/dev/video<n>`, as `ffmpeg` demuxers can read any file
+/// or URL a downloaded project names, like [`resolve`]
+/// keeps it from doing.
+pub fn capture_device(device: &str, allow_outside: bool) -> Result<(), String> {
+    let (format, input) = match device.split_once(':') {
+        Some(split) if !allow_outside => split,
+        _ => return Ok(()),
+    };
+    if !CAPTURE_FORMATS.contains(&format) {
+        return Err(format!(
+            "`{}` isn't a capture device, expected one of the formats {}",
+            device,
+            CAPTURE_FORMATS.join(", ")
+        ));
+    }
+    let video = input.strip_prefix("/dev/video").is_some_and(|n| {
+        !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit())
+    });
+    let path = input.contains(['/', '\\']) || input.contains("..");
+    if (path || input.contains("://")) && !(format == "v4l2" && video) {
+        return Err(format!(
+            "`{}` is a file or URL, not a capture device",
+            device
+        ));
+    }
+    Ok(())
+}
+
 /// Represents a directory of shaders, and a shader graph
 /// lisp configuration file.
+#[derive(Debug, Clone)]
 pub struct ShaderDir {
-    pub lisp:    String,
-    pub shaders: BTreeMap<String, String>,
+    pub lisp:          String,
+    pub shaders:       BTreeMap<String, String>,
+    /// Shaders in the directory that the config doesn't
+    /// name, and so haven't been read. They're read if the
+    /// graph asks for one anyway, e.g. by a name it builds,
+    /// or in a file it includes, see
+    /// [`ShaderDir::read_all`].
+    pub unread:        BTreeMap<String, PathBuf>,
+    /// Directory that other files referenced by the
+    /// config, like point clouds, are resolved against.
+    pub root:          PathBuf,
+    /// Whether files outside `root` can be read, see
+    /// [`resolve`]. Off unless the project is trusted.
+    pub allow_outside: bool,
 }
 
 impl ShaderDir {
@@ -28,6 +316,9 @@ impl ShaderDir {
         ShaderDir {
             lisp: lisp_graph,
             shaders,
+            unread: BTreeMap::new(),
+            root: PathBuf::from("."),
+            allow_outside: false,
         }
     }
 
@@ -42,20 +333,11 @@ impl ShaderDir {
     ) -> Result<ShaderDir, String> {
         let mut shaders = BTreeMap::new();
         for file in dir.files() {
-            if file.path().is_dir()
-                || file.path().extension() != Some(OsStr::new("frag"))
-            {
-                continue;
-            }
-
             // get the key and value, insertomundo!
-            let name = file
-                .path()
-                .file_stem()
-                .ok_or("Could not infer shader name from file name")?
-                .to_str()
-                .ok_or("Could not convert file name to UTF8 string")?
-                .to_string();
+            let name = match shader_name(file.path())? {
+                Some(name) => name,
+                None => continue,
+            };
             let contents = String::from_utf8(file.contents().to_vec())
                 .map_err(|_| "Could not get shader contents")?;
             shaders.insert(name, contents);
@@ -64,42 +346,136 @@ impl ShaderDir {
         Ok(ShaderDir {
             lisp: lisp_graph,
             shaders,
+            unread: BTreeMap::new(),
+            root: PathBuf::from("."),
+            allow_outside: false,
         })
     }
 
-    /// Creates a new `ShaderDir` from a directory.
-    pub fn new_from_dir<T>(path: T, get_lisp : impl Fn() -> Result<String, String>) -> Result<ShaderDir, String>
+    /// Creates a `ShaderDir` from a Shadertoy shader, in
+    /// JSON as exported with the Shadertoy API, or a lone
+    /// `mainImage` shader. Every pass renders at `width` by
+    /// `height`. See [`crate::reload::shadertoy`].
+    pub fn new_from_shadertoy(
+        path: &Path,
+        width: u32,
+        height: u32,
+    ) -> Result<ShaderDir, String> {
+        shadertoy::load(path, width, height)
+    }
+
+    /// Creates a new `ShaderDir` from a directory. Only the
+    /// shaders the config names are read, so that projects
+    /// that keep lots of other files alongside their
+    /// shaders reload just as fast. The rest are left
+    /// in [`ShaderDir::unread`]. Unless
+    /// `allow_outside`, shaders can't be symlinks to
+    /// elsewhere.
+    pub fn new_from_dir<T>(
+        path: T,
+        allow_outside: bool,
+        get_lisp: impl Fn() -> Result<String, String>,
+    ) -> Result<ShaderDir, String>
     where
         T: AsRef<Path>,
     {
-
         let lisp = get_lisp()?;
+        let root = path.as_ref().to_path_buf();
 
-        let mut shaders = BTreeMap::new();
-        let files = fs::read_dir(path)
-            .map_err(|_| "Could not read shader directory".to_string())?;
+        let words = words(&lisp);
+        let named = |name: &String| {
+            let (stem, _) = Stage::split(name);
+            words.contains(name.as_str()) || words.contains(stem)
+        };
+        let (named, unread) = shader_files(&root, allow_outside)?
+            .into_iter()
+            .partition(|(n, _)| named(n));
+        let shaders = read_shaders(&named)?;
 
-        for p in files {
-            // some type stuff, you know the deal
-            let path = p.map_err(|_| "Got a bad file path".to_string())?.path();
+        Ok(ShaderDir {
+            lisp,
+            shaders,
+            unread,
+            root,
+            allow_outside,
+        })
+    }
 
-            // only include `.frag` files
-            if path.is_dir() || path.extension() != Some(OsStr::new("frag")) {
-                continue;
-            }
+    /// Reads every shader left in [`ShaderDir::unread`],
+    /// for when all of them are needed, e.g. to send
+    /// the project elsewhere.
+    pub fn read_all(&mut self) -> Result<(), String> {
+        let unread = std::mem::take(&mut self.unread);
+        self.shaders.extend(read_shaders(&unread)?);
+        Ok(())
+    }
+}
 
-            // get the key and value, insertomundo!
-            let name = path
-                .file_stem()
-                .ok_or("Could not infer shader name from file name")?
-                .to_str()
-                .ok_or("Could not convert file name to UTF8 string")?
-                .to_string();
-            let contents = fs::read_to_string(path)
-                .map_err(|_| "Could not get shader contents")?;
-            shaders.insert(name, contents);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_paths_in_the_project() {
+        let root = Path::new("project");
+        assert_eq!(
+            resolve(root, "images/a.png", false),
+            Ok(root.join("images/a.png"))
+        );
+        assert_eq!(
+            resolve(root, "./a/../b.png", false),
+            Ok(root.join("./a/../b.png"))
+        );
+        for outside in ["../a.png", "a/../../b.png", "/etc/passwd", ".."] {
+            assert!(resolve(root, outside, false).is_err(), "{}", outside);
+            assert_eq!(resolve(root, outside, true), Ok(root.join(outside)));
         }
+    }
 
-        Ok(ShaderDir { lisp, shaders })
+    #[cfg(unix)]
+    #[test]
+    fn refuses_symlinks_out_of_the_project() {
+        let dir = std::env::temp_dir()
+            .join(format!("shadergarden-resolve-{}", std::process::id()));
+        let root = dir.join("project");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(dir.join("secret.txt"), "").unwrap();
+        fs::write(root.join("a.png"), "").unwrap();
+        let link = root.join("link.txt");
+        let _ = fs::remove_file(&link);
+        std::os::unix::fs::symlink(dir.join("secret.txt"), &link).unwrap();
+
+        let inside = resolve(&root, "a.png", false);
+        let linked = resolve(&root, "link.txt", false);
+        let allowed = resolve(&root, "link.txt", true);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(inside, Ok(root.join("a.png")));
+        assert!(linked.is_err());
+        assert_eq!(allowed, Ok(link));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn only_captures_from_devices() {
+        for device in [
+            "v4l2:/dev/video0",
+            "avfoundation:0:1",
+            "dshow:video=Integrated Camera",
+            "pulse:default",
+            "alsa:hw:0",
+        ] {
+            assert_eq!(capture_device(device, false), Ok(()), "{}", device);
+        }
+        for device in [
+            "wav:/etc/passwd",
+            "image2:frames/%03d.png",
+            "lavfi:movie=/etc/passwd",
+            "pulse:http://example.com/stream",
+            "v4l2:/etc/passwd",
+            "v4l2:/dev/video0/../../etc/passwd",
+            "alsa:../sound.wav",
+        ] {
+            assert!(capture_device(device, false).is_err(), "{}", device);
+            assert_eq!(capture_device(device, true), Ok(()), "{}", device);
+        }
+    }
+}
@@ -0,0 +1,441 @@
+//! Turns Shadertoy shaders into shader graphs, so they run
+//! as they are. Each pass is wrapped in a prelude that
+//! defines Shadertoy's uniforms, like `iTime` and
+//! `iChannel0`, in terms of shadergarden's, and calls its
+//! `mainImage`. Buffers become nodes, run in order before
+//! the image.
+
+use std::{
+    collections::{
+        BTreeMap,
+        BTreeSet,
+    },
+    fmt::Write,
+    fs,
+    path::Path,
+};
+
+use serde_json::Value;
+
+use crate::reload::ShaderDir;
+
+/// What a pass reads through one of its channels.
+#[derive(Debug, Clone, PartialEq)]
+enum Source {
+    /// The output of a buffer, by its Shadertoy id.
+    Buffer(String),
+    /// An image or video, passed to the graph as an input,
+    /// by the path it had on Shadertoy.
+    File(String),
+    /// The microphone, or a music track, which is read from
+    /// the default audio input instead.
+    Audio,
+    Webcam,
+    /// The keys held, pressed, and toggled, as
+    /// `u_keyboard`.
+    Keyboard,
+}
+
+#[derive(Debug, Clone)]
+struct Channel {
+    index:  u32,
+    source: Source,
+    filter: &'static str,
+    wrap:   &'static str,
+}
+
+#[derive(Debug, Clone)]
+struct Pass {
+    /// The name of the pass's shader and node.
+    name:     String,
+    code:     String,
+    /// The id other passes read this one's output by.
+    output:   Option<String>,
+    channels: Vec<Channel>,
+}
+
+/// Reads a Shadertoy shader, either as exported with the
+/// Shadertoy API, in JSON, or a single `mainImage` on its
+/// own, and turns it into a graph that renders it at
+/// `width` by `height`.
+pub fn load(path: &Path, width: u32, height: u32) -> Result<ShaderDir, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Could not read `{}`: {}", path.display(), e))?;
+    let root = path.parent().unwrap_or_else(|| Path::new("."));
+
+    let (title, common, passes) = match path.extension() {
+        Some(extension) if extension == "json" => {
+            let json = serde_json::from_str(&text).map_err(|e| {
+                format!("`{}` is not valid JSON: {}", path.display(), e)
+            })?;
+            parse(&json)?
+        },
+        // a lone image pass, reading whichever channels it
+        // uses from the graph's inputs
+        _ => {
+            let channels = (0..4)
+                .filter(|i| text.contains(&format!("iChannel{}", i)))
+                .map(|index| Channel {
+                    index,
+                    source: Source::File(format!("iChannel{}", index)),
+                    filter: "linear",
+                    wrap: "clamp",
+                })
+                .collect();
+            let image = Pass {
+                name: "image".to_string(),
+                code: text,
+                output: None,
+                channels,
+            };
+            (None, String::new(), vec![image])
+        },
+    };
+
+    let mut shader_dir = convert(title, &common, &passes, width, height)?;
+    shader_dir.root = root.to_path_buf();
+    Ok(shader_dir)
+}
+
+/// Finds the passes of a shader in the JSON returned by the
+/// Shadertoy API, which may be wrapped in an object or an
+/// array, along with a line saying what it is.
+fn parse(json: &Value) -> Result<(Option<String>, String, Vec<Pass>), String> {
+    let shader = match json {
+        Value::Array(shaders) => shaders.first(),
+        Value::Object(object) => object.get("Shader").or(Some(json)),
+        _ => None,
+    };
+    let shader = shader
+        .filter(|shader| shader.get("renderpass").is_some())
+        .ok_or("Expected a Shadertoy shader, with a `renderpass` list")?;
+
+    let info = &shader["info"];
+    let title =
+        info["name"]
+            .as_str()
+            .map(|name| match info["username"].as_str() {
+                Some(user) => format!("\"{}\" by {}", name, user),
+                None => format!("\"{}\"", name),
+            });
+
+    let mut common = String::new();
+    let mut buffers = vec![];
+    let mut image = None;
+    let renderpass =
+        shader["renderpass"].as_array().ok_or("Expected a list")?;
+    for (index, pass) in renderpass.iter().enumerate() {
+        let code = pass["code"].as_str().unwrap_or_default().to_string();
+        let kind = pass["type"].as_str().unwrap_or_default();
+        let name = pass_name(pass["name"].as_str().unwrap_or_default())
+            .unwrap_or_else(|| format!("{}_{}", kind, index));
+        let pass = Pass {
+            name,
+            code,
+            output: pass["outputs"][0]["id"]
+                .as_str()
+                .map(String::from)
+                .or_else(|| {
+                    pass["outputs"][0]["id"].as_u64().map(|id| id.to_string())
+                }),
+            channels: channels(&pass["inputs"])?,
+        };
+        match kind {
+            "common" => common = pass.code,
+            "buffer" => buffers.push(pass),
+            "image" => image = Some(pass),
+            // only heard, so it can go
+            "sound" => eprintln!("[warn] Skipping the sound pass"),
+            other => {
+                return Err(format!("`{}` passes are not supported", other))
+            },
+        }
+    }
+
+    // buffers run in order, A to D
+    buffers.sort_by(|a, b| a.name.cmp(&b.name));
+    buffers.push(image.ok_or("The shader has no image pass")?);
+    Ok((title, common, buffers))
+}
+
+/// `Buffer A` becomes `buffer_a`.
+fn pass_name(name: &str) -> Option<String> {
+    let name = name
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    match name.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => Some(name),
+        _ => None,
+    }
+}
+
+/// The channels a pass reads, from its `inputs`.
+fn channels(inputs: &Value) -> Result<Vec<Channel>, String> {
+    let mut channels = vec![];
+    for input in inputs.as_array().into_iter().flatten() {
+        let index = input["channel"].as_u64().ok_or("Expected a channel")?;
+        // older exports name some fields differently
+        let kind = input["type"].as_str().or_else(|| input["ctype"].as_str());
+        let id = input["id"]
+            .as_str()
+            .map(String::from)
+            .or_else(|| input["id"].as_u64().map(|id| id.to_string()))
+            .unwrap_or_default();
+        let file = input["filepath"]
+            .as_str()
+            .or_else(|| input["src"].as_str())
+            .unwrap_or_default()
+            .to_string();
+
+        let source = match kind.unwrap_or_default() {
+            "buffer" => Source::Buffer(id),
+            "texture" | "video" => Source::File(file),
+            "mic" | "music" | "musicstream" => Source::Audio,
+            "webcam" => Source::Webcam,
+            "keyboard" => Source::Keyboard,
+            other => {
+                return Err(format!(
+                    "iChannel{} reads a `{}`, which is not supported",
+                    index, other
+                ))
+            },
+        };
+        let sampler = &input["sampler"];
+        channels.push(Channel {
+            index: index as u32,
+            source,
+            filter: match sampler["filter"].as_str() {
+                Some("nearest") => "nearest",
+                _ => "linear",
+            },
+            wrap: match sampler["wrap"].as_str() {
+                Some("repeat") => "repeat",
+                _ => "clamp",
+            },
+        });
+    }
+    Ok(channels)
+}
+
+/// Writes the graph and wraps the shaders of each pass.
+fn convert(
+    title: Option<String>,
+    common: &str,
+    passes: &[Pass],
+    width: u32,
+    height: u32,
+) -> Result<ShaderDir, String> {
+    let mut header = String::new();
+    let mut lisp = String::new();
+    if let Some(title) = title {
+        writeln!(header, "; Imported from Shadertoy: {}", title).unwrap();
+    }
+
+    // images and videos are passed in by the user, in the
+    // order they're first read
+    let mut files = vec![];
+    for channel in passes.iter().flat_map(|pass| pass.channels.iter()) {
+        if let Source::File(file) = &channel.source {
+            if !files.contains(file) {
+                files.push(file.clone());
+            }
+        }
+    }
+    if !files.is_empty() {
+        writeln!(header, "; Pass these channels' images or videos as inputs:")
+            .unwrap();
+    }
+    for (index, file) in files.iter().enumerate() {
+        writeln!(header, "; texture_{}: {}", index, file).unwrap();
+        writeln!(lisp, "(input texture_{})", index).unwrap();
+    }
+
+    writeln!(lisp, "(let w {})\n(let h {})", width, height).unwrap();
+    let sources = || passes.iter().flat_map(|pass| pass.channels.iter());
+    if sources().any(|channel| channel.source == Source::Audio) {
+        writeln!(lisp, "(let sound (audio))").unwrap();
+    }
+    if sources().any(|channel| channel.source == Source::Webcam) {
+        writeln!(lisp, "(let webcam (camera 0 :width w :height h))").unwrap();
+    }
+
+    let mut shaders = BTreeMap::new();
+    for (position, pass) in passes.iter().enumerate() {
+        let mut inputs: Vec<String> = vec![];
+        let mut samplers = vec![];
+        let mut recurrent = false;
+        for channel in pass.channels.iter() {
+            let input = match &channel.source {
+                Source::Buffer(id) => {
+                    let read = passes
+                        .iter()
+                        .position(|other| other.output.as_ref() == Some(id));
+                    match read {
+                        Some(read) if read == position => {
+                            recurrent = true;
+                            samplers.push((channel.index, "u_previous".into()));
+                            continue;
+                        },
+                        Some(read) if read < position => {
+                            passes[read].name.clone()
+                        },
+                        Some(read) => {
+                            return Err(format!(
+                                "`{}` reads `{}`, which runs after it, so \
+                                 would read its last frame; only earlier \
+                                 buffers, or its own last frame, can be read",
+                                pass.name, passes[read].name
+                            ))
+                        },
+                        None => {
+                            return Err(format!(
+                                "`{}` reads a buffer that isn't in the shader",
+                                pass.name
+                            ))
+                        },
+                    }
+                },
+                Source::File(file) => {
+                    // unwrap: every file was collected above
+                    let index = files.iter().position(|f| f == file).unwrap();
+                    format!("texture_{}", index)
+                },
+                Source::Audio => "sound".to_string(),
+                Source::Webcam => "webcam".to_string(),
+                Source::Keyboard => {
+                    samplers.push((channel.index, "u_keyboard".into()));
+                    continue;
+                },
+            };
+            let index = match inputs.iter().position(|i| *i == input) {
+                Some(index) => index,
+                None => {
+                    inputs.push(input);
+                    inputs.len() - 1
+                },
+            };
+            samplers.push((channel.index, format!("u_texture_{}", index)));
+        }
+
+        let image = position == passes.len() - 1;
+        let form = if recurrent { "shader-rec" } else { "shader" };
+        write!(lisp, "(let {} ({} \"{}\" w h", pass.name, form, pass.name)
+            .unwrap();
+        for input in inputs.iter() {
+            write!(lisp, " {}", input).unwrap();
+        }
+        if !image {
+            // buffers are stored as floats, and sampled as
+            // they're first read
+            let (filter, wrap) = reader(passes, pass)
+                .map(|channel| (channel.filter, channel.wrap))
+                .unwrap_or(("linear", "clamp"));
+            write!(lisp, " :format rgba32f :filter {} :wrap {}", filter, wrap)
+                .unwrap();
+        }
+        writeln!(lisp, "))").unwrap();
+
+        let source = wrap(common, &pass.code, &samplers, recurrent, image);
+        shaders.insert(pass.name.clone(), source);
+    }
+    // unwrap: there is always an image pass, last
+    writeln!(lisp, "(output {})", passes.last().unwrap().name).unwrap();
+
+    Ok(ShaderDir::new(header + &lisp, shaders))
+}
+
+/// The first channel that reads a pass, if any.
+fn reader<'a>(passes: &'a [Pass], pass: &Pass) -> Option<&'a Channel> {
+    let output = pass.output.as_ref()?;
+    passes
+        .iter()
+        .flat_map(|pass| pass.channels.iter())
+        .find(|channel| channel.source == Source::Buffer(output.clone()))
+}
+
+/// Wraps the code of a pass with a prelude, and a `main`
+/// that calls its `mainImage`. `samplers` are the uniforms
+/// each channel is bound to.
+fn wrap(
+    common: &str,
+    code: &str,
+    samplers: &[(u32, String)],
+    recurrent: bool,
+    image: bool,
+) -> String {
+    let mut source = String::from(
+        "#version 330
+
+in  vec2 coords;
+out vec4 color;
+
+uniform vec2  u_resolution;
+uniform float u_time;
+uniform float u_dt;
+uniform int   u_frame;
+uniform vec4  u_mouse;
+",
+    );
+    let inputs = samplers
+        .iter()
+        .filter(|(_, uniform)| uniform.starts_with("u_texture_"))
+        .map(|(_, uniform)| uniform)
+        .collect::<BTreeSet<_>>();
+    for uniform in inputs {
+        writeln!(source, "uniform sampler2D {};", uniform).unwrap();
+    }
+    if recurrent {
+        writeln!(source, "uniform sampler2D u_previous;").unwrap();
+    }
+    if samplers.iter().any(|(_, uniform)| uniform == "u_keyboard") {
+        writeln!(source, "uniform sampler2D u_keyboard;").unwrap();
+    }
+
+    source.push_str(
+        "
+// Shadertoy's uniforms, in terms of shadergarden's
+#define iResolution vec3(u_resolution, 1.)
+#define iTime u_time
+#define iTimeDelta u_dt
+#define iFrameRate (1. / max(u_dt, 1e-3))
+#define iFrame u_frame
+#define iMouse (u_mouse * u_resolution.xyxy)
+#define iDate vec4(0., 0., 0., u_time)
+#define iSampleRate 44100.
+#define iChannelTime float[4](u_time, u_time, u_time, u_time)
+vec3 iChannelResolution[4];
+",
+    );
+    for index in 0..4 {
+        match samplers.iter().find(|(channel, _)| *channel == index) {
+            Some((_, uniform)) => {
+                writeln!(source, "#define iChannel{} {}", index, uniform)
+            },
+            // nothing is bound, as on Shadertoy
+            None => writeln!(source, "uniform sampler2D iChannel{};", index),
+        }
+        .unwrap();
+    }
+
+    write!(source, "\n{}\n\n{}\n\nvoid main() {{\n", common, code).unwrap();
+    for (index, _) in samplers.iter() {
+        writeln!(
+            source,
+            "    iChannelResolution[{0}] =\n        \
+             vec3(textureSize(iChannel{0}, 0), 1.);",
+            index
+        )
+        .unwrap();
+    }
+    source.push_str("    color = vec4(0., 0., 0., 1.);\n");
+    source.push_str("    mainImage(color, gl_FragCoord.xy);\n");
+    if image {
+        // the image is shown opaque, whatever its alpha
+        source.push_str("    color.a = 1.;\n");
+    }
+    source.push_str("}\n");
+    source
+}
@@ -1,4 +1,11 @@
 use std::{
+    collections::{
+        BTreeMap,
+        BTreeSet,
+    },
+    fs,
+    io,
+    io::BufRead,
     path::{
         Path,
         PathBuf,
@@ -9,36 +16,56 @@ use std::{
             AtomicBool,
             Ordering,
         },
+        mpsc,
+        mpsc::{
+            Receiver,
+            Sender,
+            TryRecvError,
+        },
         Arc,
+        Mutex,
     },
+    thread,
     time::{
         Duration,
         Instant,
     },
-    thread,
-    sync::mpsc::{Sender, Receiver},
-    sync::mpsc,
-    io,
-    io::BufRead,
-    fs,
 };
 
 use glium::backend::Context;
-
 use notify::{
     RecommendedWatcher,
     RecursiveMode,
     Watcher,
 };
-
 #[cfg(target_family = "unix")]
-use signal_hook::{consts::SIGUSR1, iterator::Signals};
+use signal_hook::{
+    consts::SIGUSR1,
+    iterator::Signals,
+};
 
 use crate::{
-    graph::ShaderGraph,
-    lisp::graph_from_sexp,
+    diagnostic::BuildError,
+    graph::{
+        Plan,
+        Realization,
+        ShaderGraph,
+    },
+    lisp::{
+        graph_from_plan,
+        graph_from_sexp_with_options,
+        plan_from_sexp_with_options,
+        realization_from_plan,
+        BuildOptions,
+    },
     map,
-    reload::ShaderDir,
+    reload::{
+        shader_dir::{
+            shader_name,
+            shader_path,
+        },
+        ShaderDir,
+    },
 };
 
 /// A struct that watches a directory for changes,
@@ -50,9 +77,43 @@ pub struct ShaderGraphWatcher {
     path:         PathBuf,
     config:       PathBuf,
     changed:      Arc<AtomicBool>,
-    _watcher:     RecommendedWatcher,
+    /// Files changed since the last reload, to tell whether
+    /// only shaders need recompiling.
+    edited:       Arc<Mutex<BTreeSet<PathBuf>>>,
+    watcher:      Option<RecommendedWatcher>,
+    /// Files outside the directory that are also watched,
+    /// like lisp included from elsewhere.
+    watched:      BTreeSet<PathBuf>,
     shader_graph: ShaderGraph,
-    _stdin_rx:     Receiver<String>
+    /// Shown instead of the graph while it fails to build,
+    /// see [`BuildOptions::error_screen`].
+    error_graph:  Option<ShaderGraph>,
+    /// The lisp and plan the graph was last built from.
+    plan:         Option<(String, Plan)>,
+    options:      BuildOptions,
+    _stdin_rx:    Receiver<String>,
+    /// Sources to build from instead of the disk, see
+    /// [`ShaderGraphWatcher::set_sources`].
+    sources:      Option<(String, BTreeMap<String, String>)>,
+    /// A graph being rebuilt in the background, see
+    /// [`BuildOptions::background`].
+    pending:      Option<Pending>,
+}
+
+/// How long to spend creating the nodes of a graph rebuilt
+/// in the background each frame. Each node is compiled on
+/// the render thread, see [`BuildOptions::background`], so
+/// a frame can run over by however long one node takes.
+const BACKGROUND_BUDGET: Duration = Duration::from_millis(4);
+
+/// A graph being rebuilt in the background, along with the
+/// sources it is built from.
+enum Pending {
+    /// The lisp is being evaluated on another thread.
+    Planning(ShaderDir, Receiver<Result<Plan, String>>),
+    /// The nodes are being created, and their shaders
+    /// compiled, on the render thread between frames.
+    Realizing(ShaderDir, Box<Realization>),
 }
 
 pub enum WatchResult {
@@ -62,7 +123,7 @@ pub enum WatchResult {
     Rebuilt,
     /// Changes were made but the graph could not be
     /// rebuilt.
-    Err(String),
+    Err(BuildError),
 }
 
 impl ShaderGraphWatcher {
@@ -73,6 +134,7 @@ impl ShaderGraphWatcher {
         context: &Rc<Context>,
         path: T,
         config: T,
+        options: BuildOptions,
     ) -> Result<ShaderGraphWatcher, String>
     where
         T: AsRef<Path>,
@@ -81,12 +143,19 @@ impl ShaderGraphWatcher {
         let config = config.as_ref().to_path_buf();
 
         let changed = Arc::new(AtomicBool::new(false));
+        let edited = Arc::new(Mutex::new(BTreeSet::new()));
 
         // build the watcher
         let mut watcher = RecommendedWatcher::new({
             let changed = changed.clone();
-            move |res| match res {
-                Ok(_) => changed.store(true, Ordering::SeqCst),
+            let edited = edited.clone();
+            move |res: notify::Result<notify::Event>| match res {
+                Ok(event) => {
+                    if let Ok(mut files) = edited.lock() {
+                        files.extend(event.paths);
+                    }
+                    changed.store(true, Ordering::SeqCst)
+                },
                 Err(e) => println!("[warn] Watch error: `{:?}`.", e),
             }
         })
@@ -96,104 +165,215 @@ impl ShaderGraphWatcher {
         // SIGUSR handling thread
         #[cfg(target_family = "unix")]
         {
-            let signals = Signals::new(&[SIGUSR1]);
+            let signals = Signals::new([SIGUSR1]);
             match signals {
                 Ok(mut s) => {
-                        let changed = changed.clone();
-                        thread::spawn(move || {
-                            for sig in s.forever() {
-                                changed.store(true, Ordering::SeqCst);
-                                println!("[info] Received signal {:?}", sig);
-                            }
-                        });
-                    }
-                Err(e) => println!("[warn] Signal listen error: `{:?}`.", e)
+                    let changed = changed.clone();
+                    thread::spawn(move || {
+                        for sig in s.forever() {
+                            changed.store(true, Ordering::SeqCst);
+                            println!("[info] Received signal {:?}", sig);
+                        }
+                    });
+                },
+                Err(e) => println!("[warn] Signal listen error: `{:?}`.", e),
             };
         }
-        
-        //initial build
-        let shader_graph = ShaderGraphWatcher::build_initial(context, &path, &config)?;
+
+        // initial build
+        let shader_dir = ShaderGraphWatcher::read_initial(
+            &path,
+            &config,
+            options.allow_outside,
+        )?;
+        let lisp = shader_dir.lisp.clone();
+        let plan = plan_from_sexp_with_options(shader_dir, &options)?;
+        let shader_graph = graph_from_plan(context, &plan, map! {}, &options)?;
         let last_reload = Instant::now();
 
-        // STDIN reading thread
+        // STDIN reading thread, only needed when the graph is
+        // read from stdin, so that stdin is free otherwise
         let (tx, rx): (Sender<String>, Receiver<String>) = mpsc::channel();
-        {
+        if config == Path::new("-") {
             // let thread_tx = tx.clone();
             let changed = changed.clone();
-            thread::spawn(move || {
-                loop {
-                    println!("[info] Reading config from STDIN");
-                    let maybe_config = read_stdin_config();
-                    match maybe_config {
-                        Ok(c)  => {
-                            println!("[info] STDIN config received, sending to receiver");
-                            tx.send(c).unwrap();
-                            changed.store(true, Ordering::SeqCst);
-                        },
-                        Err(e) => println!("[warn] STDIN config read error: `{:?}`.", e),
-                    }
+            thread::spawn(move || loop {
+                println!("[info] Reading config from STDIN");
+                let maybe_config = read_stdin_config();
+                match maybe_config {
+                    Ok(c) => {
+                        println!(
+                            "[info] STDIN config received, sending to receiver"
+                        );
+                        tx.send(c).unwrap();
+                        changed.store(true, Ordering::SeqCst);
+                    },
+                    Err(e) => {
+                        println!("[warn] STDIN config read error: `{:?}`.", e)
+                    },
                 }
             });
         }
 
-        Ok(ShaderGraphWatcher {
+        let mut watcher = ShaderGraphWatcher {
             context: context.clone(),
             last_reload,
             path,
             config,
             changed,
-            _watcher: watcher,
+            edited,
+            watcher: Some(watcher),
+            watched: BTreeSet::new(),
+            shader_graph,
+            error_graph: None,
+            plan: None,
+            options,
+            _stdin_rx: rx,
+            sources: None,
+            pending: None,
+        };
+        watcher.watch_includes(&plan);
+        watcher.plan = Some((lisp, plan));
+        Ok(watcher)
+    }
+
+    /// Creates a watcher that builds from sources in
+    /// memory, e.g. received over the network, instead of
+    /// watching a directory. Files the graph refers to,
+    /// like point clouds, are still found in `path`.
+    pub fn from_sources(
+        context: &Rc<Context>,
+        path: &Path,
+        lisp: String,
+        shaders: BTreeMap<String, String>,
+        options: BuildOptions,
+    ) -> Result<ShaderGraphWatcher, String> {
+        let shader_dir = ShaderDir {
+            lisp:          lisp.clone(),
+            shaders:       shaders.clone(),
+            unread:        BTreeMap::new(),
+            root:          path.to_path_buf(),
+            allow_outside: options.allow_outside,
+        };
+        let plan = plan_from_sexp_with_options(shader_dir, &options)?;
+        let shader_graph = graph_from_plan(context, &plan, map! {}, &options)?;
+        let (_, rx) = mpsc::channel();
+
+        Ok(ShaderGraphWatcher {
+            context: context.clone(),
+            last_reload: Instant::now(),
+            path: path.to_path_buf(),
+            config: path.join("shader.graph"),
+            changed: Arc::new(AtomicBool::new(false)),
+            edited: Arc::new(Mutex::new(BTreeSet::new())),
+            watcher: None,
+            watched: BTreeSet::new(),
             shader_graph,
-            _stdin_rx: rx
+            error_graph: None,
+            plan: Some((lisp.clone(), plan)),
+            options,
+            _stdin_rx: rx,
+            sources: Some((lisp, shaders)),
+            pending: None,
         })
     }
 
-    // TO-DO: reduce some code duplication here (build => build_initial AND build_reload)
+    // TO-DO: reduce some code duplication here (build =>
+    // build_initial AND build_reload)
     pub fn build_initial(
         context: &Rc<Context>,
         path: &Path,
         config: &Path,
+        options: &BuildOptions,
     ) -> Result<ShaderGraph, String> {
-        let shader_dir = match config.to_str().unwrap() {
-            "-" => 
-                ShaderDir::new_from_dir(path, || {
-                    read_stdin_config().map_err(|s| {
-                        format!("Could not read config from stdin: {}", s)
-                    })
-                })?,
-            cfg => 
-                ShaderDir::new_from_dir(path, || {
-                    fs::read_to_string(&config).map_err(|_| {
-                        format!("Could not read `{}` in shader directory", cfg)
-                    })
-                })?,
-        };
-        let shader_graph = graph_from_sexp(context, shader_dir, map! {})?;
+        let shader_dir = ShaderGraphWatcher::read_initial(
+            path,
+            config,
+            options.allow_outside,
+        )?;
+        let shader_graph = graph_from_sexp_with_options(
+            context,
+            shader_dir,
+            map! {},
+            options,
+        )?;
         Ok(shader_graph)
     }
 
-    fn build(
+    /// Like [`ShaderGraphWatcher::build_initial`], but also
+    /// returns every file on disk the graph was built from,
+    /// see [`ShaderGraphWatcher::files`].
+    pub fn build_initial_with_files(
         context: &Rc<Context>,
         path: &Path,
         config: &Path,
+        options: &BuildOptions,
+    ) -> Result<(ShaderGraph, Vec<PathBuf>), String> {
+        let (plan, files) =
+            ShaderGraphWatcher::plan_initial(path, config, options)?;
+        let shader_graph = graph_from_plan(context, &plan, map! {}, options)?;
+        Ok((shader_graph, files))
+    }
+
+    /// Like [`ShaderGraphWatcher::build_initial_with_files`],
+    /// but only evaluates the lisp into a plan, without a
+    /// GL context, so that a project can be checked
+    /// where there is no GPU, e.g. in CI. Shaders are
+    /// found, but not compiled.
+    pub fn plan_initial(
+        path: &Path,
+        config: &Path,
+        options: &BuildOptions,
+    ) -> Result<(Plan, Vec<PathBuf>), String> {
+        let shader_dir = ShaderGraphWatcher::read_initial(
+            path,
+            config,
+            options.allow_outside,
+        )?;
+        let plan = plan_from_sexp_with_options(shader_dir, options)?;
+        let files = plan_files(path, config, &plan);
+        Ok((plan, files))
+    }
+
+    fn read_initial(
+        path: &Path,
+        config: &Path,
+        allow_outside: bool,
+    ) -> Result<ShaderDir, String> {
+        let shader_dir = match config.to_str().unwrap() {
+            "-" => ShaderDir::new_from_dir(path, allow_outside, || {
+                read_stdin_config().map_err(|s| {
+                    format!("Could not read config from stdin: {}", s)
+                })
+            })?,
+            cfg => ShaderDir::new_from_dir(path, allow_outside, || {
+                fs::read_to_string(config).map_err(|_| {
+                    format!("Could not read `{}` in shader directory", cfg)
+                })
+            })?,
+        };
+        Ok(shader_dir)
+    }
+
+    fn read(
+        path: &Path,
+        config: &Path,
+        allow_outside: bool,
         rx: &Receiver<String>,
-    ) -> Result<ShaderGraph, String> {
+    ) -> Result<ShaderDir, String> {
         let shader_dir = match config.to_str().unwrap() {
-            "-" => 
-                ShaderDir::new_from_dir(path, || {
-                    rx.recv().map_err(|s| {
-                        format!("Could not read config from stdin: {}", s)
-                    })
-                })?,
-            cfg => 
-                ShaderDir::new_from_dir(path, || {
-                    fs::read_to_string(&config).map_err(|_| {
-                        format!("Could not read `{}` in shader directory", cfg)
-                    })
-                })?,
+            "-" => ShaderDir::new_from_dir(path, allow_outside, || {
+                rx.recv().map_err(|s| {
+                    format!("Could not read config from stdin: {}", s)
+                })
+            })?,
+            cfg => ShaderDir::new_from_dir(path, allow_outside, || {
+                fs::read_to_string(config).map_err(|_| {
+                    format!("Could not read `{}` in shader directory", cfg)
+                })
+            })?,
         };
-        let shader_graph = graph_from_sexp(context, shader_dir, map! {})?;
-        Ok(shader_graph)
+        Ok(shader_dir)
     }
 
     /// Gets the shader graph without trying to reload
@@ -202,91 +382,381 @@ impl ShaderGraphWatcher {
     /// So only use this for fine-grained control over
     /// reloads.
     pub fn graph_no_reload(&mut self) -> &mut ShaderGraph {
-        &mut self.shader_graph
+        match &mut self.error_graph {
+            Some(error_graph) => error_graph,
+            None => &mut self.shader_graph,
+        }
     }
 
     /// Forces a rebuild of the graph. Do not call this in a
     /// loop! As with `graph_no_reload`, only use this
     /// for fine-grained control over reloads.
     pub fn graph_force_reload(&mut self) -> (&mut ShaderGraph, WatchResult) {
-        let watch_result = match ShaderGraphWatcher::build(
-            &self.context,
-            &self.path,
-            &self.config,
-            &self._stdin_rx,
-        ) {
-            Ok(graph) => {
-                self.shader_graph = graph;
+        self.reload(false)
+    }
+
+    /// Rebuilds the graph. If `incremental`, and only
+    /// shaders have changed since the last build, just the
+    /// nodes running them are recompiled, and the rest of
+    /// the graph is kept as is.
+    fn reload(&mut self, incremental: bool) -> (&mut ShaderGraph, WatchResult) {
+        let rebuilt = self.rebuild(incremental);
+        self.last_reload = Instant::now();
+        self.report(rebuilt)
+    }
+
+    /// Turns the outcome of a rebuild into a
+    /// [`WatchResult`]. A graph still being built in the
+    /// background has not changed yet.
+    fn report(
+        &mut self,
+        rebuilt: Result<bool, BuildError>,
+    ) -> (&mut ShaderGraph, WatchResult) {
+        let watch_result = match rebuilt {
+            Ok(true) => {
+                self.error_graph = None;
                 WatchResult::Rebuilt
             },
-            Err(error) => WatchResult::Err(error),
+            Ok(false) => WatchResult::NoChange,
+            Err(error) => {
+                if self.options.error_screen {
+                    self.show_error(&error);
+                }
+                WatchResult::Err(error)
+            },
         };
+        (self.graph_no_reload(), watch_result)
+    }
 
-        self.last_reload = Instant::now();
-        (&mut self.shader_graph, watch_result)
+    /// Swaps in a graph showing a build error, taking the
+    /// same inputs as the graph it stands in for. The last
+    /// good graph is kept, so it can still be patched.
+    fn show_error(&mut self, error: &BuildError) {
+        let inputs = self.shader_graph.get_inputs().len();
+        let text = format!("Could not rebuild the graph:\n\n{}", error);
+        match ShaderGraph::error_screen(&self.context, &text, inputs) {
+            Ok(graph) => self.error_graph = Some(graph),
+            Err(e) => eprintln!("[warn] Could not show the error: {}", e),
+        }
+    }
+
+    /// Rebuilds the graph, returning whether it was swapped
+    /// in. With [`BuildOptions::background`], a full
+    /// rebuild is only started here, and finished by
+    /// [`ShaderGraphWatcher::build_pending`].
+    fn rebuild(&mut self, incremental: bool) -> Result<bool, BuildError> {
+        // a new rebuild supersedes one in the background
+        self.pending = None;
+        let mut shader_dir = match &self.sources {
+            Some((lisp, shaders)) => {
+                ShaderDir::new(lisp.clone(), shaders.clone())
+            },
+            None => ShaderGraphWatcher::read(
+                &self.path,
+                &self.config,
+                self.options.allow_outside,
+                &self._stdin_rx,
+            )?,
+        };
+        shader_dir.root = self.path.clone();
+        shader_dir.allow_outside = self.options.allow_outside;
+        let same_lisp = match &self.plan {
+            Some((lisp, _)) => *lisp == shader_dir.lisp,
+            None => false,
+        };
+        let incremental = incremental && same_lisp;
+        if self.options.background && !incremental {
+            self.plan_in_background(shader_dir);
+            return Ok(false);
+        }
+
+        let located = self.located(&shader_dir);
+        let plan =
+            plan_from_sexp_with_options(shader_dir.clone(), &self.options)
+                .map_err(&located)?;
+        let patched = match &self.plan {
+            Some((_, old_plan)) if incremental => {
+                old_plan.patch(&mut self.shader_graph, &plan)
+            },
+            _ => None,
+        };
+        match patched {
+            Some(count) => {
+                let count = count.map_err(&located)?;
+                eprintln!("[info] Recompiled {} changed shader(s)", count);
+                self.shader_graph.regenerate_on_reload().map_err(&located)?;
+            },
+            None => {
+                let graph = graph_from_plan(
+                    &self.context,
+                    &plan,
+                    map! {},
+                    &self.options,
+                )
+                .map_err(&located)?;
+                self.swap_in(graph);
+            },
+        }
+        self.watch_includes(&plan);
+        self.plan = Some((shader_dir.lisp, plan));
+        Ok(true)
+    }
+
+    /// Points an error at the sources a graph was built
+    /// from, which may not be on disk.
+    fn located(&self, shader_dir: &ShaderDir) -> impl Fn(String) -> BuildError {
+        let (path, config) = (self.path.clone(), self.config.clone());
+        let shader_dir = shader_dir.clone();
+        move |e| BuildError::new(e, &path, &config).with_sources(&shader_dir)
+    }
+
+    /// Replaces the graph with a rebuilt one, carrying over
+    /// what the options ask to keep.
+    fn swap_in(&mut self, mut graph: ShaderGraph) {
+        // random textures only change when asked to
+        graph.carry_random(&self.shader_graph);
+        if self.options.keep_state {
+            let kept = graph.carry_state(&self.shader_graph);
+            if kept > 0 {
+                eprintln!("[info] Kept the state of {} node(s)", kept);
+            }
+        }
+        if self.options.keep_clock {
+            graph.carry_clock(&self.shader_graph);
+        }
+        self.shader_graph = graph;
+    }
+
+    /// Starts evaluating the lisp of a graph on another
+    /// thread, to be built by
+    /// [`ShaderGraphWatcher::build_pending`].
+    fn plan_in_background(&mut self, shader_dir: ShaderDir) {
+        let (tx, rx) = mpsc::channel();
+        let sources = shader_dir.clone();
+        let options = self.options.clone();
+        thread::spawn(move || {
+            // the rebuild may have been superseded meanwhile
+            let _ = tx.send(plan_from_sexp_with_options(sources, &options));
+        });
+        self.pending = Some(Pending::Planning(shader_dir, rx));
+    }
+
+    /// Moves a graph rebuilt in the background along,
+    /// creating its nodes for at most
+    /// [`BACKGROUND_BUDGET`], and swaps it in once it
+    /// is finished. Returns whether it was swapped in.
+    fn build_pending(&mut self) -> Result<bool, BuildError> {
+        let (shader_dir, mut realization) = match self.pending.take() {
+            None => return Ok(false),
+            Some(Pending::Realizing(shader_dir, realization)) => {
+                (shader_dir, *realization)
+            },
+            Some(Pending::Planning(shader_dir, rx)) => {
+                let planned = match rx.try_recv() {
+                    Ok(planned) => planned,
+                    Err(TryRecvError::Empty) => {
+                        self.pending = Some(Pending::Planning(shader_dir, rx));
+                        return Ok(false);
+                    },
+                    Err(TryRecvError::Disconnected) => {
+                        Err("The graph stopped building".to_string())
+                    },
+                };
+                let (context, options) = (&self.context, &self.options);
+                let realization = planned
+                    .and_then(|p| realization_from_plan(context, p, options))
+                    .map_err(self.located(&shader_dir))?;
+                (shader_dir, realization)
+            },
+        };
+
+        let finished = realization
+            .step(&map! {}, BACKGROUND_BUDGET)
+            .map_err(self.located(&shader_dir))?;
+        if !finished {
+            self.pending =
+                Some(Pending::Realizing(shader_dir, Box::new(realization)));
+            return Ok(false);
+        }
+        let (plan, graph) = realization.into_parts();
+        self.swap_in(graph);
+        self.watch_includes(&plan);
+        self.plan = Some((shader_dir.lisp, plan));
+        Ok(true)
+    }
+
+    /// Starts watching the files a plan or its shaders
+    /// include that are outside the watched directory.
+    fn watch_includes(&mut self, plan: &Plan) {
+        let watcher = match &mut self.watcher {
+            Some(watcher) => watcher,
+            None => return,
+        };
+        let root = self.path.canonicalize().unwrap_or(self.path.clone());
+        for file in plan.includes().iter().chain(plan.shader_includes()) {
+            let file = match file.canonicalize() {
+                Ok(file) if !file.starts_with(&root) => file,
+                _ => continue,
+            };
+            if self.watched.contains(&file) {
+                continue;
+            }
+            match watcher.watch(&file, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    self.watched.insert(file);
+                },
+                Err(e) => {
+                    eprintln!(
+                        "[warn] Could not watch `{}`: {}",
+                        file.display(),
+                        e
+                    )
+                },
+            }
+        }
+    }
+
+    /// Rebuilds the graph from sources in memory from now
+    /// on, rather than from the disk. As with a reload, the
+    /// old graph is kept if the new one fails to build.
+    pub fn set_sources(
+        &mut self,
+        lisp: String,
+        shaders: BTreeMap<String, String>,
+    ) -> (&mut ShaderGraph, WatchResult) {
+        self.sources = Some((lisp, shaders));
+        self.reload(true)
+    }
+
+    /// Rebuilds the graph with different options, e.g. at
+    /// another render scale, and keeps them for later
+    /// rebuilds. As with a reload, the old graph is kept if
+    /// the new one fails to build.
+    pub fn set_options(
+        &mut self,
+        options: BuildOptions,
+    ) -> (&mut ShaderGraph, WatchResult) {
+        self.options = options;
+        self.reload(false)
+    }
+
+    /// The options the graph is built with.
+    pub fn options(&self) -> &BuildOptions { &self.options }
+
+    /// Every file on disk the graph was last built from:
+    /// its lisp, unless read from stdin, the shaders it
+    /// uses, and the files its plan reads, see
+    /// [`Plan::files`].
+    pub fn files(&self) -> Vec<PathBuf> {
+        match &self.plan {
+            Some((_, plan)) => plan_files(&self.path, &self.config, plan),
+            None => vec![],
+        }
     }
 
     /// Reloads a shader graph if there have been changes,
     /// And the graph hasn't been rebuilt recently.
     /// Note that if compilation fails, the old graph will
     /// remain in use. Returns a borrowed `ShaderGraph`,
-    /// and whether the graph was rebuilt.
+    /// and whether the graph was rebuilt. With
+    /// [`BuildOptions::background`], the old graph is
+    /// returned until the new one is finished.
     pub fn graph(&mut self) -> (&mut ShaderGraph, WatchResult) {
         if self.last_reload.elapsed() > Duration::from_millis(300)
             && self.changed.swap(false, Ordering::SeqCst)
         {
-            self.graph_force_reload()
+            let files = match self.edited.lock() {
+                Ok(mut files) => std::mem::take(&mut *files),
+                Err(_) => BTreeSet::new(),
+            };
+            let incremental = match &self.plan {
+                Some((_, plan)) => only_shaders(&files, plan),
+                None => false,
+            };
+            self.reload(incremental)
         } else {
-            (self.graph_no_reload(), WatchResult::NoChange)
+            let rebuilt = self.build_pending();
+            self.report(rebuilt)
         }
     }
 }
 
+/// Whether the files changed are all shaders, or files
+/// included by one, so that only the shaders need
+/// recompiling, see [`Plan::patches`]. Anything else
+/// changing, or a reload without a file changing, rebuilds
+/// everything.
+fn only_shaders(files: &BTreeSet<PathBuf>, plan: &Plan) -> bool {
+    let included = plan.shader_includes();
+    let shader = |file: &PathBuf| {
+        matches!(shader_name(file), Ok(Some(_)))
+            || file.canonicalize().is_ok_and(|f| included.contains(&f))
+    };
+    !files.is_empty() && files.iter().all(shader)
+}
+
+/// Every file on disk a plan was built from, see
+/// [`ShaderGraphWatcher::files`].
+fn plan_files(path: &Path, config: &Path, plan: &Plan) -> Vec<PathBuf> {
+    let mut files = vec![];
+    if config != Path::new("-") {
+        files.push(config.to_path_buf());
+    }
+    for name in plan.shaders() {
+        files.push(shader_path(path, name));
+    }
+    files.extend(plan.files());
+    files
+}
+
 fn read_stdin_config() -> Result<String, String> {
     let mut byte_vec: Vec<u8> = Vec::new();
     let stdin = io::stdin(); // We get `Stdin` here.
-    
+
     {
         let mut handle = stdin.lock();
 
-        let mut count = 0; //the number of open parentheses with no corresponding closing
+        let mut count = 0; // the number of open parentheses with no corresponding
+                           // closing
         let mut start = 0;
 
-        //consume ONE s-expression
+        // consume ONE s-expression
         loop {
             let bytes_read = handle.read_until(b')', &mut byte_vec);
             match bytes_read {
                 Ok(read) => {
-                    //count the number of opening parenthesis in read bytes
+                    // count the number of opening parenthesis in read bytes
                     let len = byte_vec.len();
-                    for i in len-read..len {
-                        if b'(' == byte_vec[i] {
+                    for byte in &byte_vec[len - read..] {
+                        if b'(' == *byte {
                             count += 1;
                         }
-                    };                 
+                    }
+                },
+                Err(err) => {
+                    return Err(format!("Reading STDIN error: {}", err))
                 },
-                Err(err) => return Err(format!("Reading STDIN error: {}", err)),
             };
 
-            //subtract 1 since we reached a closing parenthesis
+            // subtract 1 since we reached a closing parenthesis
             count -= 1;
 
             if count < 0 {
                 return Err("Unbound s-expression error".to_string());
             } else if count > 0 {
-                continue; //s-expression not finished
+                continue; // s-expression not finished
             } else {
-                //check if s-expression is an output one
+                // check if s-expression is an output one
                 if did_read_output_sexpr(&byte_vec, start) {
                     break;
                 } else {
                     start = byte_vec.len() - 1;
                 }
             }
-        } 
+        }
     }
 
-    //All s-expressions read, convert to string
+    // All s-expressions read, convert to string
     let is_valid_utf8 = std::str::from_utf8(&byte_vec);
     match is_valid_utf8 {
         Ok(the_str) => Ok(the_str.to_string()),
@@ -294,31 +764,163 @@ fn read_stdin_config() -> Result<String, String> {
     }
 }
 
-fn did_read_output_sexpr(bytes : &Vec<u8>, start : usize) -> bool {
+fn did_read_output_sexpr(bytes: &[u8], start: usize) -> bool {
     let mut i = start;
 
-    //skip everything before initial '('
+    // skip everything before initial '('
     while bytes[i] != b'(' {
         i += 1;
     }
 
-    //skip '('
+    // skip '('
     i += 1;
 
-    //find index past initial '(' and past all whitespace
+    // find index past initial '(' and past all whitespace
     while (bytes[i] as char).is_whitespace() {
         i += 1;
     }
 
-    //check if the rest of the bytes Vec has enough space for 'output', if not, false
+    // check if the rest of the bytes Vec has enough space for
+    // 'output', if not, false
     if bytes.len() - i < 6 {
         return false;
     }
-    
-    //convert slice to str and use equality comparison
-    let maybe_str = std::str::from_utf8(&bytes[i..i+6]);
+
+    // convert slice to str and use equality comparison
+    let maybe_str = std::str::from_utf8(&bytes[i..i + 6]);
     match maybe_str {
-        Ok(s)  => s == "output",
-        Err(_) => false, 
+        Ok(s) => s == "output",
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A project on disk, removed once dropped.
+    struct Project(PathBuf);
+
+    impl Project {
+        fn new(name: &str, files: &[(&str, &str)]) -> Project {
+            let dir = std::env::temp_dir().join(format!(
+                "shadergarden-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let project = Project(dir);
+            for (file, text) in files {
+                project.write(file, text);
+            }
+            project
+        }
+
+        fn write(&self, file: &str, text: &str) {
+            let path = self.0.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+
+        fn plan(&self) -> Result<(Plan, Vec<PathBuf>), String> {
+            let config = self.0.join("shader.graph");
+            let options = BuildOptions::default();
+            ShaderGraphWatcher::plan_initial(&self.0, &config, &options)
+        }
+    }
+
+    impl Drop for Project {
+        fn drop(&mut self) { let _ = fs::remove_dir_all(&self.0); }
+    }
+
+    const GRAPH: &str = "(let a (shader \"a\" 8 8))
+                         (let b (shader \"b\" 8 8 a))
+                         (output b)";
+
+    #[test]
+    fn plans_a_project_without_a_context() {
+        let project = Project::new("plan", &[
+            ("shader.graph", GRAPH),
+            ("a.frag", "void main() {}"),
+            ("b.frag", "void main() {}"),
+            ("notes.txt", "not a shader"),
+        ]);
+        let (plan, files) = project.plan().unwrap();
+        assert_eq!(plan.topology().len(), 2);
+        let names = files
+            .iter()
+            .map(|file| file.strip_prefix(&project.0).unwrap())
+            .map(|file| file.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["shader.graph", "a.frag", "b.frag"]);
+    }
+
+    #[test]
+    fn reports_missing_files() {
+        let project = Project::new("missing", &[("a.frag", "")]);
+        assert!(project.plan().unwrap_err().contains("shader.graph"));
+        project.write("shader.graph", GRAPH);
+        assert!(project.plan().unwrap_err().contains("`b`"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn patches_shaders_edited_on_disk() {
+        let project = Project::new("patch", &[
+            ("shader.graph", GRAPH),
+            ("a.frag", "void main() {}"),
+            ("b.frag", "void main() {}"),
+        ]);
+        let (old, _) = project.plan().unwrap();
+        project.write("b.frag", "void main() { }");
+        let edited = [project.0.join("b.frag")].iter().cloned().collect();
+        assert!(only_shaders(&edited, &old));
+        let (new, _) = project.plan().unwrap();
+        let patches = old.patches(&new).unwrap();
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches[0].0.index(), 1);
+    }
+
+    #[test]
+    fn rebuilds_when_the_lisp_changes() {
+        let project = Project::new("rebuild", &[
+            ("shader.graph", GRAPH),
+            ("a.frag", ""),
+            ("b.frag", ""),
+        ]);
+        let (old, _) = project.plan().unwrap();
+        let edited = ["shader.graph", "a.frag"]
+            .iter()
+            .map(|file| project.0.join(file))
+            .collect();
+        assert!(!only_shaders(&edited, &old));
+        assert!(!only_shaders(&BTreeSet::new(), &old));
+
+        let resized = GRAPH.replace("\"b\" 8 8", "\"b\" 4 4");
+        project.write("shader.graph", &resized);
+        let (new, _) = project.plan().unwrap();
+        assert_eq!(old.patches(&new), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuses_shaders_linked_from_outside() {
+        let secret = Project::new("secret", &[("b.frag", "secret")]);
+        let project = Project::new("linked", &[
+            ("shader.graph", GRAPH),
+            ("a.frag", "void main() {}"),
+        ]);
+        let b = project.0.join("b.frag");
+        std::os::unix::fs::symlink(secret.0.join("b.frag"), b).unwrap();
+
+        let error = project.plan().unwrap_err();
+        assert!(error.contains("outside the project"), "{}", error);
+        let options = BuildOptions {
+            allow_outside: true,
+            ..BuildOptions::default()
+        };
+        let config = project.0.join("shader.graph");
+        assert!(ShaderGraphWatcher::plan_initial(
+            &project.0, &config, &options
+        )
+        .is_ok());
+    }
+}
@@ -0,0 +1,80 @@
+use std::os::raw::{
+    c_int,
+    c_void,
+};
+
+/// `eRENDERDOC_API_Version_1_1_2`, the oldest version with
+/// everything used here.
+const API_VERSION: c_int = 10102;
+
+/// Index of `TriggerCapture` in the table of functions
+/// returned by `RENDERDOC_GetAPI`, see `renderdoc_app.h`.
+const TRIGGER_CAPTURE: usize = 15;
+
+type GetApi = unsafe extern "C" fn(c_int, *mut *mut c_void) -> c_int;
+
+/// A connection to RenderDoc's in-application API. This
+/// only works when shadergarden was launched from
+/// RenderDoc, which loads its library into the process.
+pub struct RenderDoc {
+    api: *const Option<unsafe extern "C" fn()>,
+}
+
+impl RenderDoc {
+    /// Connects to RenderDoc, if it has been loaded into
+    /// this process.
+    #[cfg(unix)]
+    pub fn connect() -> Result<RenderDoc, String> {
+        let not_loaded = || {
+            "RenderDoc is not loaded, launch shadergarden from RenderDoc \
+             to capture frames"
+                .to_string()
+        };
+
+        // safety: the library is only looked up, never loaded,
+        // and the function has the signature from renderdoc_app.h
+        unsafe {
+            let library = libc::dlopen(
+                b"librenderdoc.so\0".as_ptr() as *const _,
+                libc::RTLD_NOW | libc::RTLD_NOLOAD,
+            );
+            if library.is_null() {
+                return Err(not_loaded());
+            }
+
+            let get_api = libc::dlsym(
+                library,
+                b"RENDERDOC_GetAPI\0".as_ptr() as *const _,
+            );
+            if get_api.is_null() {
+                return Err(not_loaded());
+            }
+            let get_api: GetApi = std::mem::transmute(get_api);
+
+            let mut api = std::ptr::null_mut();
+            if get_api(API_VERSION, &mut api) != 1 || api.is_null() {
+                return Err("RenderDoc does not support API 1.1.2".to_string());
+            }
+
+            Ok(RenderDoc {
+                api: api as *const _,
+            })
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn connect() -> Result<RenderDoc, String> {
+        Err("Capturing with RenderDoc is only supported on Unix".to_string())
+    }
+
+    /// Captures the next frame that is presented.
+    pub fn trigger_capture(&self) {
+        // safety: the table stays valid for the life of the
+        // process, and has at least as many entries as 1.1.2
+        unsafe {
+            if let Some(trigger) = *self.api.add(TRIGGER_CAPTURE) {
+                trigger();
+            }
+        }
+    }
+}
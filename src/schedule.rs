@@ -0,0 +1,258 @@
+use std::{
+    fmt,
+    str::FromStr,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+/// A time of day, in minutes after midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimeOfDay(u32);
+
+impl TimeOfDay {
+    /// The current local time. Falls back to UTC on
+    /// platforms where the local timezone is not known.
+    pub fn now() -> TimeOfDay {
+        #[cfg(unix)]
+        {
+            // safety: localtime_r only writes to `tm`
+            unsafe {
+                let now = libc::time(std::ptr::null_mut());
+                let mut tm: libc::tm = std::mem::zeroed();
+                if !libc::localtime_r(&now, &mut tm).is_null() {
+                    return TimeOfDay((tm.tm_hour * 60 + tm.tm_min) as u32);
+                }
+            }
+        }
+
+        let seconds = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        TimeOfDay(((seconds / 60) % (24 * 60)) as u32)
+    }
+}
+
+impl FromStr for TimeOfDay {
+    type Err = String;
+
+    fn from_str(time: &str) -> Result<TimeOfDay, String> {
+        let invalid =
+            || format!("Expected a time like `09:30`, found `{}`", time);
+        let (hours, minutes) = time.split_once(':').ok_or_else(invalid)?;
+        let hours: u32 = hours.parse().map_err(|_| invalid())?;
+        let minutes: u32 = minutes.parse().map_err(|_| invalid())?;
+        if hours >= 24 || minutes >= 60 {
+            return Err(invalid());
+        }
+        Ok(TimeOfDay(hours * 60 + minutes))
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.0 / 60, self.0 % 60)
+    }
+}
+
+/// A length of time, written in seconds, minutes, or hours,
+/// e.g. `90s`, `2m`, or `1.5h`. Plain numbers are seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Period(pub Duration);
+
+impl FromStr for Period {
+    type Err = String;
+
+    fn from_str(period: &str) -> Result<Period, String> {
+        let invalid = || {
+            format!("Expected a time like `90s` or `2m`, found `{}`", period)
+        };
+        let (number, unit) = match period.find(|c: char| c.is_alphabetic()) {
+            Some(split) => period.split_at(split),
+            None => (period, "s"),
+        };
+        let scale = match unit {
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            _ => return Err(invalid()),
+        };
+        let number: f64 = number.trim().parse().map_err(|_| invalid())?;
+        if !number.is_finite() || number < 0.0 {
+            return Err(invalid());
+        }
+        Ok(Period(Duration::from_secs_f64(number * scale)))
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}s", self.0.as_secs_f64())
+    }
+}
+
+/// Daily hours during which a graph should be rendered,
+/// e.g. the opening hours of an exhibition. If `off` is
+/// before `on`, the hours run past midnight. `on` and `off`
+/// can't be the same time, see [`Schedule::new`].
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    pub on:  TimeOfDay,
+    pub off: TimeOfDay,
+    /// Whether it was on when last checked, and when.
+    checked: Option<(bool, Instant)>,
+    /// The time on the graph's clock when it fell asleep.
+    asleep:  Option<f32>,
+}
+
+/// Whether a scheduled graph runs in a frame, see
+/// [`Schedule::sleep`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sleep {
+    Awake,
+    /// Woke up for the scheduled hours, and the graph's
+    /// clock should be moved back to the time it fell
+    /// asleep at, so that the time asleep isn't counted.
+    Woke(f32),
+    /// Outside of the scheduled hours, the window is
+    /// blanked.
+    Asleep,
+}
+
+impl Schedule {
+    /// How often the local time is checked.
+    const POLL: Duration = Duration::from_secs(1);
+
+    /// Errors if `on` and `off` are the same time, which
+    /// could mean either always or never.
+    pub fn new(on: TimeOfDay, off: TimeOfDay) -> Result<Schedule, String> {
+        if on == off {
+            return Err(format!(
+                "The schedule starts and stops at {}, leave out `--on` \
+                 and `--off` to run all day",
+                on
+            ));
+        }
+        Ok(Schedule {
+            on,
+            off,
+            checked: None,
+            asleep: None,
+        })
+    }
+
+    /// Whether a time is within the scheduled hours, from
+    /// `on` up to but not including `off`.
+    pub fn is_on_at(&self, time: TimeOfDay) -> bool {
+        if self.on < self.off {
+            self.on <= time && time < self.off
+        } else {
+            time >= self.on || time < self.off
+        }
+    }
+
+    /// Whether it is currently within the scheduled hours.
+    pub fn is_on(&mut self) -> bool {
+        match self.checked {
+            Some((on, at)) if at.elapsed() < Self::POLL => on,
+            _ => {
+                let on = self.is_on_at(TimeOfDay::now());
+                self.checked = Some((on, Instant::now()));
+                on
+            },
+        }
+    }
+
+    /// Whether the graph sleeps this frame, given the time
+    /// on its clock, logging when it falls asleep or wakes
+    /// up.
+    pub fn sleep(&mut self, clock: f32) -> Sleep {
+        let on = self.is_on();
+        self.sleep_if(!on, clock)
+    }
+
+    fn sleep_if(&mut self, off: bool, clock: f32) -> Sleep {
+        match (self.asleep, off) {
+            (Some(time), false) => {
+                self.asleep = None;
+                eprintln!("[info] Resuming for scheduled hours");
+                Sleep::Woke(time)
+            },
+            (None, true) => {
+                self.asleep = Some(clock);
+                eprintln!(
+                    "[info] Outside of scheduled hours, sleeping until {}",
+                    self.on
+                );
+                Sleep::Asleep
+            },
+            (Some(_), true) => Sleep::Asleep,
+            (None, false) => Sleep::Awake,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(on: &str, off: &str) -> Result<Schedule, String> {
+        Schedule::new(on.parse()?, off.parse()?)
+    }
+
+    fn is_on_at(schedule: &Schedule, time: &str) -> bool {
+        schedule.is_on_at(time.parse().unwrap())
+    }
+
+    #[test]
+    fn keeps_the_clock_while_asleep() {
+        let mut day = schedule("09:00", "22:00").unwrap();
+        assert_eq!(day.sleep_if(false, 1.0), Sleep::Awake);
+        assert_eq!(day.sleep_if(true, 2.0), Sleep::Asleep);
+        assert_eq!(day.sleep_if(true, 3.0), Sleep::Asleep);
+        assert_eq!(day.sleep_if(false, 4.0), Sleep::Woke(2.0));
+        assert_eq!(day.sleep_if(false, 5.0), Sleep::Awake);
+    }
+
+    #[test]
+    fn runs_during_the_day() {
+        let day = schedule("09:00", "22:00").unwrap();
+        assert!(!is_on_at(&day, "08:59"));
+        assert!(is_on_at(&day, "09:00"));
+        assert!(is_on_at(&day, "21:59"));
+        assert!(!is_on_at(&day, "22:00"));
+        assert!(!is_on_at(&day, "00:00"));
+    }
+
+    #[test]
+    fn runs_past_midnight() {
+        let night = schedule("22:00", "02:30").unwrap();
+        assert!(!is_on_at(&night, "21:59"));
+        assert!(is_on_at(&night, "22:00"));
+        assert!(is_on_at(&night, "23:59"));
+        assert!(is_on_at(&night, "00:00"));
+        assert!(is_on_at(&night, "02:29"));
+        assert!(!is_on_at(&night, "02:30"));
+        assert!(!is_on_at(&night, "12:00"));
+
+        let to_midnight = schedule("18:00", "00:00").unwrap();
+        assert!(is_on_at(&to_midnight, "23:59"));
+        assert!(!is_on_at(&to_midnight, "00:00"));
+    }
+
+    #[test]
+    fn refuses_empty_schedules() {
+        assert!(schedule("09:00", "09:00").is_err());
+        assert!(schedule("00:00", "00:00").is_err());
+    }
+
+    #[test]
+    fn parses_times() {
+        assert_eq!("09:30".parse(), Ok(TimeOfDay(570)));
+        assert!("24:00".parse::<TimeOfDay>().is_err());
+        assert!("9".parse::<TimeOfDay>().is_err());
+        assert_eq!(TimeOfDay(570).to_string(), "09:30");
+    }
+}
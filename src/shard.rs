@@ -0,0 +1,437 @@
+//! Splitting an offline render between processes, or
+//! machines, each rendering a range of the frames into
+//! outputs of its own, see [`Shard`], which [`merge`] puts
+//! back together afterwards.
+
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    process::Command,
+    str::FromStr,
+};
+
+use serde_json::{
+    json,
+    Value,
+};
+
+use crate::{
+    provenance::Provenance,
+    sink::SinkSpec,
+};
+
+/// One of the processes a render is split between, written
+/// `<index>/<count>` on the command line, e.g. `2/8` for
+/// the second of eight, counting from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    pub index: u32,
+    pub count: u32,
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid shard `{}`, expected e.g. `2/8`", s);
+        let (index, count) = s.split_once('/').ok_or_else(invalid)?;
+        let index = index.trim().parse::<u32>().map_err(|_| invalid())?;
+        let count = count.trim().parse::<u32>().map_err(|_| invalid())?;
+        if index == 0 || index > count {
+            return Err(format!(
+                "There is no shard {} of {}, they count from 1",
+                index, count
+            ));
+        }
+        Ok(Shard { index, count })
+    }
+}
+
+impl Shard {
+    /// The frames this shard renders, of those from `start`
+    /// up to `end`, as evenly split as they can be with
+    /// every range starting a multiple of `align` frames
+    /// after `start`, so that outputs keeping every `n`th
+    /// frame keep the frames they would in one render.
+    pub fn frames(&self, start: u64, end: u64, align: u64) -> (u64, u64) {
+        let total = end.saturating_sub(start);
+        let align = align.max(1);
+        let boundary = |index: u32| {
+            if index >= self.count {
+                total
+            } else {
+                total * index as u64 / self.count as u64 / align * align
+            }
+        };
+        (
+            start + boundary(self.index - 1),
+            start + boundary(self.index),
+        )
+    }
+
+    /// Where this shard writes an output, e.g.
+    /// `out-part-2-of-8.mp4` for `out.mp4`, or
+    /// `frames-part-2-of-8` for a directory of pngs.
+    pub fn part(&self, output: &Path) -> PathBuf {
+        let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+        let tag = format!("part-{}-of-{}", self.index, self.count);
+        let name = match output.extension() {
+            Some(extension) => {
+                format!("{}-{}.{}", stem, tag, extension.to_string_lossy())
+            },
+            None => format!("{}-{}", stem, tag),
+        };
+        output.with_file_name(name)
+    }
+
+    /// The outputs this shard writes in place of `outputs`,
+    /// the part of each file, and every stream as it is,
+    /// for readers to find it again.
+    pub fn outputs(&self, outputs: &[SinkSpec]) -> Vec<SinkSpec> {
+        let part = |output: &SinkSpec| match output.is_stream() {
+            true => output.clone(),
+            false => SinkSpec {
+                path: self.part(&output.path),
+                ..output.clone()
+            },
+        };
+        outputs.iter().map(part).collect()
+    }
+
+    /// Reads which shard wrote a part of `output`, from its
+    /// name, the inverse of [`Shard::part`].
+    fn of_part(output: &Path, part: &Path) -> Option<Shard> {
+        let stem = output.file_stem()?.to_str()?;
+        let name = part.file_name()?.to_str()?;
+        let name = match output.extension() {
+            Some(extension) => {
+                name.strip_suffix(extension.to_str()?)?.strip_suffix('.')?
+            },
+            None => name,
+        };
+        let tag = name.strip_prefix(stem)?.strip_prefix("-part-")?;
+        let (index, count) = tag.split_once("-of-")?;
+        Some(Shard {
+            index: index.parse().ok()?,
+            count: count.parse().ok()?,
+        })
+    }
+}
+
+/// How many frames apart the shards of a render writing
+/// `outputs` start, so that outputs keeping every `n`th
+/// frame keep the frames they would in one render, see
+/// [`Shard::frames`].
+pub fn align(outputs: &[SinkSpec]) -> u64 {
+    let gcd = |mut a: u64, mut b: u64| {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    };
+    let lcm = |a: u64, b: u64| a / gcd(a, b) * b;
+    outputs
+        .iter()
+        .fold(1, |align, output| lcm(align, output.every as u64))
+}
+
+/// The parts the shards of a render wrote for `output`, in
+/// order, checking that none is missing.
+pub fn parts(output: &Path) -> Result<Vec<PathBuf>, String> {
+    let dir = match output.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("Could not read `{}`: {}", dir.display(), e))?;
+
+    let mut found = vec![];
+    for entry in entries.flatten() {
+        let part = output.with_file_name(entry.file_name());
+        if let Some(shard) = Shard::of_part(output, &part) {
+            found.push((shard, part));
+        }
+    }
+    found.sort_by_key(|(shard, _)| shard.index);
+
+    let count = match found.first() {
+        Some((shard, _)) => shard.count,
+        None => {
+            return Err(format!(
+                "There are no parts of `{}` to merge, render them with \
+                 `--shard`",
+                output.display()
+            ))
+        },
+    };
+    if let Some((shard, part)) = found.iter().find(|(s, _)| s.count != count) {
+        return Err(format!(
+            "`{}` is a part of {}, but others are parts of {}",
+            part.display(),
+            shard.count,
+            count
+        ));
+    }
+    for index in 1..=count {
+        if !found.iter().any(|(shard, _)| shard.index == index) {
+            let missing = Shard { index, count }.part(output);
+            return Err(format!("`{}` is missing", missing.display()));
+        }
+    }
+    Ok(found.into_iter().map(|(_, part)| part).collect())
+}
+
+/// Puts the parts of a sharded render back together into
+/// `output`, as if it had been rendered in one go, and
+/// returns them. Videos are joined by `ffmpeg` without
+/// being encoded again, except gifs, and pngs are copied
+/// and numbered on from the part before. The descriptions
+/// of the parts, see [`Provenance`], must agree on how they
+/// were rendered, and their frames follow on from each
+/// other, so that parts of different renders aren't mixed.
+/// The frame hashes of a `hash:<file>` output, which aren't
+/// described, are joined one after another, see
+/// [`crate::sink::FrameHashes`].
+pub fn merge(output: &Path) -> Result<Vec<PathBuf>, String> {
+    if let Some(file) = output.to_str().and_then(|o| o.strip_prefix("hash:")) {
+        let output = Path::new(file);
+        let parts = parts(output)?;
+        join_hashes(output, &parts)?;
+        return Ok(parts);
+    }
+
+    let parts = parts(output)?;
+    let described = merged_provenance(&parts)?;
+
+    match output.extension() {
+        Some(_) => join_videos(output, &parts)?,
+        None => copy_pngs(output, &parts)?,
+    }
+
+    let path = Provenance::path_for(output);
+    let json = serde_json::to_string_pretty(&described).unwrap();
+    fs::write(&path, json + "\n")
+        .map_err(|e| format!("Could not write `{}`: {}", path.display(), e))?;
+    Ok(parts)
+}
+
+/// Removes the parts of a render once they're merged.
+pub fn remove_parts(parts: &[PathBuf]) {
+    for part in parts.iter() {
+        // videos are described next to them, pngs inside,
+        // and frame hashes not at all
+        let described = Provenance::path_for(part);
+        let removed = match part.extension() {
+            Some(_) if !described.exists() => fs::remove_file(part),
+            Some(_) => {
+                fs::remove_file(part).and_then(|_| fs::remove_file(described))
+            },
+            None => fs::remove_dir_all(part),
+        };
+        if let Err(e) = removed {
+            eprintln!("[warn] Could not remove `{}`: {}", part.display(), e);
+        }
+    }
+}
+
+/// The description of the whole render, from those of its
+/// parts.
+fn merged_provenance(parts: &[PathBuf]) -> Result<Value, String> {
+    let mut merged: Option<Value> = None;
+    for part in parts {
+        let path = Provenance::path_for(part);
+        let error =
+            |e: String| format!("Could not read `{}`: {}", path.display(), e);
+        let mut json: Value = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+            .map_err(error)?;
+        let start = json["start"].as_u64().unwrap_or_default();
+        let end = json["end"].as_u64().unwrap_or_default();
+
+        let whole = match &mut merged {
+            Some(whole) => whole,
+            None => {
+                merged = Some(json);
+                continue;
+            },
+        };
+        if whole["end"].as_u64() != Some(start) {
+            return Err(format!(
+                "`{}` starts at frame {}, not where the part before ends",
+                part.display(),
+                start
+            ));
+        }
+        // everything else must match
+        for key in ["start", "end", "duration"] {
+            json[key] = whole[key].clone();
+        }
+        if json != *whole {
+            return Err(format!(
+                "`{}` was rendered differently from the parts before it, \
+                 compare `{}` with theirs",
+                part.display(),
+                path.display()
+            ));
+        }
+        whole["end"] = json!(end);
+    }
+
+    let mut merged = merged.unwrap_or_default();
+    let frames = merged["end"].as_u64().unwrap_or_default()
+        - merged["start"].as_u64().unwrap_or_default();
+    let fps = merged["fps"].as_f64().unwrap_or(1.0);
+    merged["duration"] = json!(frames as f64 / fps);
+    Ok(merged)
+}
+
+/// Joins videos with `ffmpeg`'s concat demuxer, which reads
+/// them from a list, written next to the output.
+fn join_videos(output: &Path, parts: &[PathBuf]) -> Result<(), String> {
+    let mut list = String::new();
+    for part in parts {
+        // relative to the list, which is next to the parts
+        let name = part.file_name().unwrap().to_string_lossy();
+        list += &format!("file '{}'\n", name.replace('\'', "'\\''"));
+    }
+    let mut path = output.as_os_str().to_owned();
+    path.push(".parts.txt");
+    let path = PathBuf::from(path);
+    fs::write(&path, list)
+        .map_err(|e| format!("Could not write `{}`: {}", path.display(), e))?;
+
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-loglevel", "error", "-y"])
+        .args(["-f", "concat", "-safe", "0", "-i"])
+        .arg(&path);
+    // gifs can't be joined as they are, as each part has a
+    // palette of its own
+    let gif = output.extension().is_some_and(|e| e == "gif");
+    if gif {
+        command.args(["-vf", "split[a][b];[a]palettegen[p];[b][p]paletteuse"]);
+    } else {
+        command.args(["-c", "copy"]);
+    }
+    let status = command.arg(output).status();
+    let _ = fs::remove_file(&path);
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("ffmpeg exited with {}", status)),
+        Err(e) => Err(format!("Could not start ffmpeg: {}", e)),
+    }
+}
+
+/// Joins the lines of frame hashes of each part, which are
+/// numbered by frame already, checking that they follow on
+/// from each other.
+fn join_hashes(output: &Path, parts: &[PathBuf]) -> Result<(), String> {
+    let mut joined = String::new();
+    let mut last: Option<u64> = None;
+    for part in parts {
+        let hashes = fs::read_to_string(part).map_err(|e| {
+            format!("Could not read `{}`: {}", part.display(), e)
+        })?;
+        for line in hashes.lines() {
+            let frame = line
+                .split_once(' ')
+                .and_then(|(frame, _)| frame.parse::<u64>().ok())
+                .ok_or_else(|| format!("`{}` isn't a frame hash", line))?;
+            if last.is_some_and(|last| frame <= last) {
+                return Err(format!(
+                    "`{}` has frame {}, which the part before has passed",
+                    part.display(),
+                    frame
+                ));
+            }
+            last = Some(frame);
+            joined += line;
+            joined += "\n";
+        }
+    }
+    fs::write(output, joined)
+        .map_err(|e| format!("Could not write `{}`: {}", output.display(), e))
+}
+
+/// Copies the pngs of each part into one directory, as
+/// `frame-0000.png` and on, as `PngSequence` names them.
+fn copy_pngs(output: &Path, parts: &[PathBuf]) -> Result<(), String> {
+    fs::create_dir_all(output).map_err(|e| {
+        format!("Could not create `{}`: {}", output.display(), e)
+    })?;
+    let mut frame = 0;
+    for part in parts {
+        let mut pngs = fs::read_dir(part)
+            .map_err(|e| format!("Could not read `{}`: {}", part.display(), e))?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|e| e == "png"))
+            .collect::<Vec<_>>();
+        // by number, as `frame-10000` sorts before `frame-9999`
+        pngs.sort_by_key(|path| {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let digits = stem.trim_start_matches(|c: char| !c.is_ascii_digit());
+            (digits.parse::<u64>().unwrap_or(u64::MAX), path.clone())
+        });
+        for png in pngs {
+            let to = output.join(format!("frame-{:0>4}.png", frame));
+            fs::copy(&png, &to).map_err(|e| {
+                format!("Could not copy `{}`: {}", png.display(), e)
+            })?;
+            frame += 1;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_parts_of_files_only() {
+        let outputs: Vec<SinkSpec> = ["out.mp4@2", "frames@3", "shm:live"]
+            .iter()
+            .map(|output| output.parse().unwrap())
+            .collect();
+        assert_eq!(align(&outputs), 6);
+
+        let parts = Shard { index: 2, count: 8 }.outputs(&outputs);
+        let paths: Vec<_> = parts.iter().map(|o| o.path.clone()).collect();
+        assert_eq!(paths, [
+            PathBuf::from("out-part-2-of-8.mp4"),
+            PathBuf::from("frames-part-2-of-8"),
+            PathBuf::from("shm:live"),
+        ]);
+        assert_eq!(parts[0].every, 2);
+    }
+
+    #[test]
+    fn joins_frame_hashes_in_order() {
+        let dir = std::env::temp_dir()
+            .join(format!("shadergarden-hashes-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("out.txt");
+        let write = |index, hashes: &str| {
+            let part = Shard { index, count: 2 }.part(&output);
+            fs::write(part, hashes).unwrap();
+        };
+        write(2, "4 cc\n6 dd\n");
+        write(1, "0 aa\n2 bb\n");
+
+        let hash = PathBuf::from(format!("hash:{}", output.display()));
+        let merged = merge(&hash).map(|parts| parts.len());
+        let joined = fs::read_to_string(&output);
+        write(2, "0 aa\n");
+        let overlapping = merge(&hash);
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(merged, Ok(2));
+        assert_eq!(joined.unwrap(), "0 aa\n2 bb\n4 cc\n6 dd\n");
+        assert!(overlapping.unwrap_err().contains("frame 0"));
+    }
+}
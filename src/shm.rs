@@ -0,0 +1,265 @@
+//! Writes frames into a ring buffer in shared memory, so
+//! that another process, like an encoder, can read them as
+//! they're rendered, straight from the memory they were
+//! written to. See [`ShmRing`] for the layout.
+
+use std::sync::atomic::{
+    AtomicU64,
+    Ordering,
+};
+
+use glium::Texture2d;
+
+use crate::sink::{
+    read_frame,
+    OutputSink,
+};
+
+/// Written at the start of the memory, with a version.
+pub const MAGIC: &[u8; 8] = b"SGRING01";
+
+/// Frames kept in the ring by default, see
+/// [`ShmRing::new`].
+pub const SLOTS: u32 = 4;
+
+/// Bytes before the first slot.
+const HEADER: usize = 64;
+
+/// Bytes at the start of each slot, before its pixels.
+const SLOT_HEADER: usize = 16;
+
+/// The frame number of a slot being written.
+const WRITING: u64 = u64::MAX;
+
+/// A ring of frames in a POSIX shared memory object, e.g.
+/// `/dev/shm/garden` on Linux for the name `garden`. Every
+/// number is little-endian, and the layout is:
+///
+/// ```text
+/// offset  size  header
+///      0     8  magic, "SGRING01"
+///      8     4  u32, bytes in the header, 64
+///     12     4  u32, slots in the ring
+///     16     4  u32, width of each frame in pixels
+///     20     4  u32, height of each frame in pixels
+///     24     4  u32, bytes in each row of pixels
+///     28     4  pixel format, "RGBA", 8 bits per channel
+///     32     8  u64, bytes in each slot, header included
+///     40     8  f64, frames per second
+///     48     8  u64, frames written so far
+///     56     8  u64, 1 once the writer has finished
+///
+/// offset  size  slot n, at 64 + n * slot size
+///      0     8  u64, the frame in the slot, or all ones
+///               while it's being written
+///      8     8  reserved
+///     16     *  pixels, top row first
+/// ```
+///
+/// Frame `f` is written to slot `f % slots`: first its
+/// frame number is set to all ones, then its pixels are
+/// written, then its frame number is set, and then the
+/// count of frames written. A reader takes the count, and
+/// reads the latest frame, `count - 1`, in place. Frames
+/// are never held back for readers, so a reader should
+/// check that the frame number of the slot is still the one
+/// it expects once it's done with the pixels, and if not,
+/// skip to the latest. The memory is removed when the sink
+/// is dropped, but readers that have it mapped keep it.
+pub struct ShmRing {
+    name:      String,
+    memory:    *mut u8,
+    length:    usize,
+    slots:     u32,
+    size:      (u32, u32),
+    slot_size: usize,
+    written:   u64,
+}
+
+impl ShmRing {
+    /// Creates the shared memory object `name`, replacing
+    /// any left over, for `slots` frames of `size` pushed
+    /// at `fps`.
+    pub fn new(
+        name: &str,
+        size: (u32, u32),
+        fps: f64,
+        slots: u32,
+    ) -> Result<ShmRing, String> {
+        let name = match name.strip_prefix('/') {
+            Some(_) => name.to_string(),
+            None => format!("/{}", name),
+        };
+        let slots = slots.max(1);
+        let stride = size.0 as usize * 4;
+        let pixels = stride * size.1 as usize;
+        // every slot starts on a cache line
+        let slot_size = (SLOT_HEADER + pixels).div_ceil(64) * 64;
+        let length = HEADER + slot_size * slots as usize;
+        let memory = map(&name, length)?;
+
+        let ring = ShmRing {
+            name,
+            memory,
+            length,
+            slots,
+            size,
+            slot_size,
+            written: 0,
+        };
+        // safety: the header is within the memory, which is
+        // zeroed, and no reader trusts it before the magic
+        unsafe {
+            ring.write(8, &(HEADER as u32).to_le_bytes());
+            ring.write(12, &slots.to_le_bytes());
+            ring.write(16, &size.0.to_le_bytes());
+            ring.write(20, &size.1.to_le_bytes());
+            ring.write(24, &(stride as u32).to_le_bytes());
+            ring.write(28, b"RGBA");
+            ring.write(32, &(slot_size as u64).to_le_bytes());
+            ring.write(40, &fps.to_le_bytes());
+            for slot in 0..slots as usize {
+                ring.counter(HEADER + slot * slot_size)
+                    .store(WRITING, Ordering::Relaxed);
+            }
+            ring.write(0, MAGIC);
+        }
+        Ok(ring)
+    }
+
+    /// Copies bytes into the memory at an offset.
+    ///
+    /// # Safety
+    ///
+    /// The bytes must fit within the memory.
+    unsafe fn write(&self, offset: usize, bytes: &[u8]) {
+        std::ptr::copy_nonoverlapping(
+            bytes.as_ptr(),
+            self.memory.add(offset),
+            bytes.len(),
+        );
+    }
+
+    /// The number at an offset, shared with readers.
+    ///
+    /// # Safety
+    ///
+    /// The offset must be a multiple of 8 within the
+    /// memory.
+    unsafe fn counter(&self, offset: usize) -> &AtomicU64 {
+        &*(self.memory.add(offset) as *const AtomicU64)
+    }
+}
+
+impl OutputSink for ShmRing {
+    fn name(&self) -> String { format!("shm:{}", self.name) }
+
+    fn push(&mut self, texture: &Texture2d) -> Result<(), String> {
+        if texture.dimensions() != self.size {
+            return Err(format!(
+                "The output changed size, from {}x{} to {}x{}",
+                self.size.0,
+                self.size.1,
+                texture.width(),
+                texture.height()
+            ));
+        }
+        let pixels = read_frame(texture).into_raw();
+        let frame = self.written;
+        let index = (frame % self.slots as u64) as usize;
+        let slot = HEADER + index * self.slot_size;
+
+        // safety: the slot, and the pixels, which are the
+        // size it was made for, are within the memory
+        unsafe {
+            self.counter(slot).store(WRITING, Ordering::Release);
+            self.write(slot + SLOT_HEADER, &pixels);
+            self.counter(slot).store(frame, Ordering::Release);
+            self.counter(48).store(frame + 1, Ordering::Release);
+        }
+        self.written += 1;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), String> {
+        // safety: the flag is within the header
+        unsafe { self.counter(56).store(1, Ordering::Release) };
+        Ok(())
+    }
+}
+
+impl Drop for ShmRing {
+    fn drop(&mut self) { unmap(&self.name, self.memory, self.length); }
+}
+
+#[cfg(unix)]
+fn map(name: &str, length: usize) -> Result<*mut u8, String> {
+    use std::ffi::CString;
+
+    let path = CString::new(name)
+        .map_err(|_| format!("`{}` is not a valid shared memory name", name))?;
+    let error = |what: &str| {
+        format!(
+            "Could not {} shared memory `{}`: {}",
+            what,
+            name,
+            std::io::Error::last_os_error()
+        )
+    };
+
+    // safety: the name is a valid C string, and the memory
+    // is mapped at the length the object was sized to
+    unsafe {
+        // a ring left by a writer that crashed may be
+        // another size, so it's made again
+        libc::shm_unlink(path.as_ptr());
+        let fd = libc::shm_open(
+            path.as_ptr(),
+            libc::O_CREAT | libc::O_EXCL | libc::O_RDWR,
+            0o600,
+        );
+        if fd < 0 {
+            return Err(error("create"));
+        }
+        if libc::ftruncate(fd, length as libc::off_t) != 0 {
+            let e = error("size");
+            libc::close(fd);
+            libc::shm_unlink(path.as_ptr());
+            return Err(e);
+        }
+        let memory = libc::mmap(
+            std::ptr::null_mut(),
+            length,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED,
+            fd,
+            0,
+        );
+        libc::close(fd);
+        if memory == libc::MAP_FAILED {
+            let e = error("map");
+            libc::shm_unlink(path.as_ptr());
+            return Err(e);
+        }
+        Ok(memory as *mut u8)
+    }
+}
+
+#[cfg(unix)]
+fn unmap(name: &str, memory: *mut u8, length: usize) {
+    // safety: the memory was mapped by `map` at this length
+    unsafe {
+        libc::munmap(memory as *mut libc::c_void, length);
+        if let Ok(path) = std::ffi::CString::new(name) {
+            libc::shm_unlink(path.as_ptr());
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn map(_name: &str, _length: usize) -> Result<*mut u8, String> {
+    Err("Shared memory rings are only supported on Unix".to_string())
+}
+
+#[cfg(not(unix))]
+fn unmap(_name: &str, _memory: *mut u8, _length: usize) {}
@@ -0,0 +1,630 @@
+//! Where the output of a graph goes once it's rendered: a
+//! window, a video, a sequence of images, or anything else
+//! implementing [`OutputSink`]. Several sinks can be fed
+//! the same frames at once, see [`Sinks`], each at its own
+//! frame rate, see [`Decimated`].
+
+use std::{
+    fs,
+    io::Write,
+    path::{
+        Path,
+        PathBuf,
+    },
+    rc::Rc,
+    str::FromStr,
+};
+
+use glium::{
+    backend::Context,
+    glutin::{
+        event_loop::EventLoopWindowTarget,
+        window::WindowId,
+    },
+    Display,
+    Surface,
+    Texture2d,
+};
+use image::{
+    ImageBuffer,
+    Rgba,
+};
+use sha1::{
+    Digest,
+    Sha1,
+};
+
+use crate::{
+    graph::{
+        OutputWindow,
+        ShaderGraph,
+    },
+    gstreamer::GstreamerSink,
+    meta::Meta,
+    png::encode_with_text,
+    present::{
+        Presenter,
+        Resizer,
+        Upscale,
+    },
+    provenance::Provenance,
+    record::Recorder,
+    shm::{
+        ShmRing,
+        SLOTS,
+    },
+    util,
+};
+
+/// Receives every frame of output, as a texture. Sinks that
+/// want the frame on the CPU read it back themselves, e.g.
+/// with [`read_frame`], or wrap a closure in a
+/// [`FrameSink`].
+pub trait OutputSink {
+    /// Describes the sink in messages, e.g. by its path.
+    fn name(&self) -> String;
+
+    /// Takes the next frame of output.
+    fn push(&mut self, texture: &Texture2d) -> Result<(), String>;
+
+    /// Writes out anything still pending, once the last
+    /// frame has been pushed.
+    fn finish(self: Box<Self>) -> Result<(), String> { Ok(()) }
+}
+
+/// Reads a frame back to the CPU, top row first.
+pub fn read_frame(texture: &Texture2d) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let rows: Vec<Vec<(u8, u8, u8, u8)>> = texture.read();
+    let mut image = ImageBuffer::new(texture.width(), texture.height());
+    for (y, row) in rows.iter().rev().enumerate() {
+        for (x, &(r, g, b, a)) in row.iter().enumerate() {
+            image.put_pixel(x as u32, y as u32, Rgba([r, g, b, a]));
+        }
+    }
+    image
+}
+
+/// Several sinks fed the same frames. A sink that fails is
+/// dropped, with a warning, and the others carry on.
+#[derive(Default)]
+pub struct Sinks {
+    sinks: Vec<Box<dyn OutputSink>>,
+}
+
+impl Sinks {
+    pub fn new() -> Sinks { Sinks::default() }
+
+    pub fn add(&mut self, sink: Box<dyn OutputSink>) { self.sinks.push(sink); }
+
+    pub fn len(&self) -> usize { self.sinks.len() }
+
+    pub fn is_empty(&self) -> bool { self.sinks.is_empty() }
+
+    /// Passes a frame to every sink.
+    pub fn push(&mut self, texture: &Texture2d) {
+        self.sinks.retain_mut(|sink| match sink.push(texture) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("[warn] Dropped `{}`: {}", sink.name(), e);
+                false
+            },
+        });
+    }
+
+    /// Finishes every sink, returning the first error.
+    pub fn finish(self) -> Result<(), String> {
+        let mut result = Ok(());
+        for sink in self.sinks {
+            let name = sink.name();
+            if let Err(e) = sink.finish() {
+                if result.is_ok() {
+                    result = Err(format!("`{}`: {}", name, e));
+                }
+            }
+        }
+        result
+    }
+}
+
+impl OutputSink for Recorder {
+    fn name(&self) -> String { self.path().display().to_string() }
+
+    fn push(&mut self, texture: &Texture2d) -> Result<(), String> {
+        Recorder::push(self, texture);
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), String> {
+        Recorder::finish(*self).map(|_| ())
+    }
+}
+
+/// Writes each frame to a directory as a numbered png,
+/// `frame-0000.png` onwards.
+pub struct PngSequence {
+    dir:   PathBuf,
+    frame: u64,
+    text:  Vec<(String, String)>,
+}
+
+impl PngSequence {
+    pub fn new(dir: &Path) -> Result<PngSequence, String> {
+        fs::create_dir_all(dir).map_err(|e| {
+            format!("Could not create `{}`: {}", dir.display(), e)
+        })?;
+        Ok(PngSequence {
+            dir:   dir.to_path_buf(),
+            frame: 0,
+            text:  vec![],
+        })
+    }
+
+    /// Embeds text in every png, see [`encode_with_text`].
+    pub fn with_text(mut self, text: Vec<(String, String)>) -> PngSequence {
+        self.text = text;
+        self
+    }
+}
+
+impl OutputSink for PngSequence {
+    fn name(&self) -> String { self.dir.display().to_string() }
+
+    fn push(&mut self, texture: &Texture2d) -> Result<(), String> {
+        let path = self.dir.join(format!("frame-{:0>4}.png", self.frame));
+        let error = |e| format!("Could not write `{}`: {}", path.display(), e);
+        let image = read_frame(texture);
+        if self.text.is_empty() {
+            image.save(&path).map_err(|e| error(e.to_string()))?;
+        } else {
+            let bytes = encode_with_text(&image, &self.text).map_err(error)?;
+            fs::write(&path, bytes).map_err(|e| error(e.to_string()))?;
+        }
+        self.frame += 1;
+        Ok(())
+    }
+}
+
+/// Writes a SHA-1 of each frame to a text file, as lines
+/// of `<frame> <hash>`, so that renders can be checked to
+/// match bit for bit, e.g. across machines, drivers, or
+/// versions, by comparing the files. Frames are hashed as
+/// they would be exported, read back as 8-bit RGBA, top row
+/// first. Frames are numbered as in the render, from
+/// `first`, and `every` apart when only every `every`th
+/// frame is kept, so that the parts of a sharded render
+/// can be put back together, see [`crate::shard::merge`].
+pub struct FrameHashes {
+    path:  PathBuf,
+    file:  fs::File,
+    frame: u64,
+    every: u32,
+}
+
+impl FrameHashes {
+    pub fn new(
+        path: &Path,
+        first: u64,
+        every: u32,
+    ) -> Result<FrameHashes, String> {
+        let file = fs::File::create(path).map_err(|e| {
+            format!("Could not create `{}`: {}", path.display(), e)
+        })?;
+        Ok(FrameHashes {
+            path: path.to_path_buf(),
+            file,
+            frame: first,
+            every: every.max(1),
+        })
+    }
+}
+
+impl OutputSink for FrameHashes {
+    fn name(&self) -> String { self.path.display().to_string() }
+
+    fn push(&mut self, texture: &Texture2d) -> Result<(), String> {
+        let image = read_frame(texture);
+        let hash = Sha1::digest(image.as_raw())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        // written as it goes, so a render that fails part of
+        // the way still shows where it went wrong
+        writeln!(self.file, "{} {}", self.frame, hash).map_err(|e| {
+            format!("Could not write `{}`: {}", self.path.display(), e)
+        })?;
+        self.frame += self.every as u64;
+        Ok(())
+    }
+}
+
+/// A sink given on the command line: a video encoded by
+/// `ffmpeg` for a path with an extension, see
+/// [`Recorder::export`], a ring of frames in shared memory
+/// for `shm:<name>`, see [`ShmRing`], a GStreamer pipeline
+/// for `gst:<pipeline>`, see [`GstreamerSink`], the hash of
+/// every frame for `hash:<path>`, see [`FrameHashes`], or
+/// else a directory of pngs. It keeps every `every`th
+/// frame, written `<path>@<every>`, e.g. `preview.mp4@4`
+/// for a quarter of the frame rate, and may be given a
+/// size of its own, written `<path>@<width>x<height>`, e.g.
+/// `preview.mp4@1280x720`, see [`Resized`]. Both can be
+/// given, in either order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SinkSpec {
+    pub path:  PathBuf,
+    pub every: u32,
+    pub size:  Option<(u32, u32)>,
+}
+
+impl FromStr for SinkSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let size = |suffix: &str| -> Option<(u32, u32)> {
+            let (width, height) = suffix.split_once('x')?;
+            Some((width.parse().ok()?, height.parse().ok()?))
+        };
+        // an `@` not followed by a number or size is part of
+        // the path
+        let (mut path, mut every, mut resized) = (s, None, None);
+        while let Some((rest, suffix)) = path.rsplit_once('@') {
+            match (suffix.parse::<u32>(), size(suffix)) {
+                (Ok(n), _) if every.is_none() => every = Some(n),
+                (_, Some(size)) if resized.is_none() => resized = Some(size),
+                _ => break,
+            }
+            path = rest;
+        }
+        let every = every.unwrap_or(1);
+        if every == 0 {
+            return Err(format!("Can't keep every 0th frame of `{}`", path));
+        }
+        if let Some((0, _) | (_, 0)) = resized {
+            return Err(format!("Can't write `{}` at a size of 0", path));
+        }
+        Ok(SinkSpec {
+            path: PathBuf::from(path),
+            every,
+            size: resized,
+        })
+    }
+}
+
+impl SinkSpec {
+    /// The name of the shared memory for a `shm:<name>`
+    /// sink.
+    pub fn shm_name(&self) -> Option<&str> {
+        self.path.to_str()?.strip_prefix("shm:")
+    }
+
+    /// The pipeline of a `gst:<pipeline>` sink.
+    pub fn gst_pipeline(&self) -> Option<&str> {
+        self.path.to_str()?.strip_prefix("gst:")
+    }
+
+    /// The file of a `hash:<path>` sink.
+    pub fn hash_path(&self) -> Option<&Path> {
+        self.path.to_str()?.strip_prefix("hash:").map(Path::new)
+    }
+
+    /// Whether the sink hands frames to another process,
+    /// rather than writing files, which can't be split into
+    /// parts, see [`crate::shard::Shard::part`].
+    pub fn is_stream(&self) -> bool {
+        self.shm_name().is_some() || self.gst_pipeline().is_some()
+    }
+
+    /// Opens the sink, for frames of `size` rendered at
+    /// `fps`, resized to the sink's own size if it has one.
+    /// Live sinks drop frames when they fall behind, rather
+    /// than holding up the render. Videos are tagged with
+    /// `meta`. Offline renders are described by their
+    /// `provenance`, written next to them, see
+    /// [`Provenance::save`], and embedded in the pngs or as
+    /// the comment of the video.
+    pub fn open(
+        &self,
+        context: &Rc<Context>,
+        size: (u32, u32),
+        fps: f64,
+        live: bool,
+        meta: &Meta,
+        provenance: Option<&Provenance>,
+    ) -> Result<Box<dyn OutputSink>, String> {
+        let sink = self.open_sized(
+            self.size.unwrap_or(size),
+            fps,
+            live,
+            meta,
+            provenance,
+        )?;
+        let sink: Box<dyn OutputSink> = match self.size {
+            Some(resized) if resized != size => {
+                Box::new(Resized::new(context, sink, resized)?)
+            },
+            _ => sink,
+        };
+        Ok(match self.every {
+            1 => sink,
+            every => Box::new(Decimated::new(sink, every)),
+        })
+    }
+
+    /// Opens the sink for frames of its own size, at its
+    /// own frame rate, without resizing or dropping
+    /// any.
+    fn open_sized(
+        &self,
+        size: (u32, u32),
+        fps: f64,
+        live: bool,
+        meta: &Meta,
+        provenance: Option<&Provenance>,
+    ) -> Result<Box<dyn OutputSink>, String> {
+        let fps = fps / self.every as f64;
+        // read by another process, with nothing to describe
+        let stream: Option<Box<dyn OutputSink>> =
+            match (self.shm_name(), self.gst_pipeline()) {
+                (Some(name), _) => {
+                    Some(Box::new(ShmRing::new(name, size, fps, SLOTS)?))
+                },
+                (_, Some(pipeline)) => Some(Box::new(GstreamerSink::new(
+                    pipeline, size, fps, live,
+                )?)),
+                _ => None,
+            };
+        if let Some(stream) = stream {
+            return Ok(stream);
+        }
+        // checks the render, rather than being part of it
+        if let Some(path) = self.hash_path() {
+            let first = provenance.map_or(0, |p| p.frames.0);
+            return Ok(Box::new(FrameHashes::new(path, first, self.every)?));
+        }
+        let mut tags = meta.video_tags();
+        if let Some(provenance) = provenance {
+            tags.push(format!("comment={}", provenance.comment()));
+        }
+        let export = || Recorder::export(&self.path, size, fps, &tags);
+        let sink: Box<dyn OutputSink> = match self.path.extension() {
+            Some(_) if live => Box::new(export()?.dropping_frames()),
+            Some(_) => Box::new(export()?),
+            None => {
+                let pngs = PngSequence::new(&self.path)?;
+                match provenance {
+                    Some(provenance) => {
+                        Box::new(pngs.with_text(provenance.png_text()))
+                    },
+                    None => Box::new(pngs),
+                }
+            },
+        };
+        if let Some(provenance) = provenance {
+            let mut provenance = provenance.clone();
+            provenance.size = size;
+            provenance.every = self.every;
+            provenance.save(&self.path)?;
+        }
+        Ok(sink)
+    }
+}
+
+/// Passes every `every`th frame on to another sink, from
+/// the first, so sinks can run at a fraction of the frame
+/// rate, e.g. a preview alongside a full recording.
+pub struct Decimated {
+    sink:  Box<dyn OutputSink>,
+    every: u32,
+    count: u32,
+}
+
+impl Decimated {
+    pub fn new(sink: Box<dyn OutputSink>, every: u32) -> Decimated {
+        Decimated {
+            sink,
+            every: every.max(1),
+            count: 0,
+        }
+    }
+}
+
+impl OutputSink for Decimated {
+    fn name(&self) -> String { self.sink.name() }
+
+    fn push(&mut self, texture: &Texture2d) -> Result<(), String> {
+        let keep = self.count == 0;
+        self.count = (self.count + 1) % self.every;
+        if keep {
+            self.sink.push(texture)?;
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), String> { self.sink.finish() }
+}
+
+/// Resizes every frame before passing it on to another
+/// sink, so sinks can be written at sizes other than the
+/// output's, e.g. a 720p preview of a 4K render. See
+/// [`Resizer`].
+pub struct Resized {
+    sink:    Box<dyn OutputSink>,
+    size:    (u32, u32),
+    resizer: Resizer,
+}
+
+impl Resized {
+    pub fn new(
+        context: &Rc<Context>,
+        sink: Box<dyn OutputSink>,
+        size: (u32, u32),
+    ) -> Result<Resized, String> {
+        Ok(Resized {
+            sink,
+            size,
+            resizer: Resizer::new(context)?,
+        })
+    }
+}
+
+impl OutputSink for Resized {
+    fn name(&self) -> String { self.sink.name() }
+
+    fn push(&mut self, texture: &Texture2d) -> Result<(), String> {
+        if texture.dimensions() == self.size {
+            return self.sink.push(texture);
+        }
+        self.sink.push(self.resizer.resize(texture, self.size)?)
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), String> { self.sink.finish() }
+}
+
+/// Calls a closure with each frame, read back to the CPU,
+/// e.g. to send it over the network.
+pub struct FrameSink<F> {
+    name:   String,
+    handle: F,
+}
+
+impl<F> FrameSink<F>
+where
+    F: FnMut(ImageBuffer<Rgba<u8>, Vec<u8>>) -> Result<(), String>,
+{
+    pub fn new(name: &str, handle: F) -> FrameSink<F> {
+        FrameSink {
+            name: name.to_string(),
+            handle,
+        }
+    }
+}
+
+impl<F> OutputSink for FrameSink<F>
+where
+    F: FnMut(ImageBuffer<Rgba<u8>, Vec<u8>>) -> Result<(), String>,
+{
+    fn name(&self) -> String { self.name.clone() }
+
+    fn push(&mut self, texture: &Texture2d) -> Result<(), String> {
+        (self.handle)(read_frame(texture))
+    }
+}
+
+/// Shows each frame in a window, drawn by a [`Presenter`],
+/// e.g. to mirror the output on a second display.
+pub struct WindowSink {
+    name:          String,
+    pub display:   Display,
+    pub presenter: Presenter,
+}
+
+impl WindowSink {
+    pub fn new(name: &str, display: Display) -> Result<WindowSink, String> {
+        let presenter = Presenter::new(&display, Upscale::Bilinear)?;
+        Ok(WindowSink {
+            name: name.to_string(),
+            display,
+            presenter,
+        })
+    }
+}
+
+impl OutputSink for WindowSink {
+    fn name(&self) -> String { self.name.clone() }
+
+    fn push(&mut self, texture: &Texture2d) -> Result<(), String> {
+        let mut target = self.display.draw();
+        target.clear_color(0.0, 0.0, 0.0, 1.0);
+        self.presenter.draw(&mut target, texture);
+        target
+            .finish()
+            .map_err(|e| format!("Could not present: {}", e))
+    }
+}
+
+/// The windows nodes are shown in, see
+/// [`ShaderGraph::add_window`], and the windows the user
+/// closed, which stay closed.
+#[derive(Default)]
+pub struct NodeWindows {
+    open:      Vec<(OutputWindow, WindowSink)>,
+    dismissed: Vec<OutputWindow>,
+}
+
+impl NodeWindows {
+    /// Closes the window with an id, if it's one of these.
+    /// Returns whether it was.
+    pub fn close(&mut self, id: WindowId) -> bool {
+        let index = self
+            .open
+            .iter()
+            .position(|(_, sink)| sink.display.gl_window().window().id() == id);
+        match index {
+            Some(index) => {
+                let (closed, _) = self.open.remove(index);
+                self.dismissed.push(closed);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Opens a window for each window `graph` asks for, and
+    /// for each of the `named` nodes, closes those no
+    /// longer asked for, and shows each node in its window.
+    /// Windows share textures with `display`.
+    pub fn show<T>(
+        &mut self,
+        target: &EventLoopWindowTarget<T>,
+        display: &Display,
+        graph: &ShaderGraph,
+        named: &[String],
+    ) {
+        let mut wanted = graph.windows().to_vec();
+        for name in named.iter() {
+            if let Some(node) = graph.node_id(name) {
+                let title = graph.topology().describe(node);
+                wanted.push(OutputWindow {
+                    node,
+                    title,
+                    size: None,
+                    fullscreen: None,
+                });
+            }
+        }
+        wanted.retain(|window| !self.dismissed.contains(window));
+        self.open.retain(|(window, _)| wanted.contains(window));
+        for window in wanted {
+            if self.open.iter().any(|(open, _)| *open == window) {
+                continue;
+            }
+            let size = match (window.size, graph.texture(window.node)) {
+                (Some(size), _) => size,
+                (None, Some(texture)) => texture.dimensions(),
+                (None, None) => continue,
+            };
+            let opened = util::create_shared(
+                target,
+                display,
+                &window.title,
+                size,
+                window.fullscreen,
+            )
+            .and_then(|shared| WindowSink::new(&window.title, shared));
+            match opened {
+                Ok(sink) => self.open.push((window, sink)),
+                Err(e) => {
+                    eprintln!("[warn] {}", e);
+                    self.dismissed.push(window);
+                },
+            }
+        }
+        for (window, sink) in self.open.iter_mut() {
+            if let Some(texture) = graph.texture(window.node) {
+                if let Err(e) = sink.push(texture) {
+                    eprintln!("[warn] {}", e);
+                }
+            }
+        }
+    }
+}
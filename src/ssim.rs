@@ -0,0 +1,184 @@
+//! The structural similarity index (SSIM) between two
+//! images, for checking in CI that a change to a graph
+//! didn't change how it looks. Scores are from -1 to 1, and
+//! 1 only for identical images. Images are compared in
+//! luminance, over a Gaussian window, as in Wang et al.
+
+use std::path::Path;
+
+use glium::Texture2d;
+use image::{
+    ImageBuffer,
+    Rgb,
+};
+
+use crate::util::read_texture;
+
+/// The standard deviation of the window, in pixels, and how
+/// far it reaches on each side.
+const SIGMA: f32 = 1.5;
+const RADIUS: usize = 5;
+
+/// Keep the score stable where the images are flat, for
+/// values from 0 to 1.
+const C1: f32 = 0.01 * 0.01;
+const C2: f32 = 0.03 * 0.03;
+
+/// An image as floats, row by row, bottom to top, like a
+/// texture read back with [`Image::read`].
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub width:  u32,
+    pub height: u32,
+    pub pixels: Vec<[f32; 4]>,
+}
+
+impl Image {
+    /// Reads a texture back from the GPU.
+    pub fn read(texture: &Texture2d) -> Image {
+        Image {
+            width:  texture.width(),
+            height: texture.height(),
+            pixels: read_texture(texture),
+        }
+    }
+
+    /// Loads an image file, like a reference render.
+    pub fn load(path: &Path) -> Result<Image, String> {
+        let image = image::open(path)
+            .map_err(|e| format!("Could not load `{}`: {}", path.display(), e))?
+            .to_rgba8();
+        let (width, height) = image.dimensions();
+        // files are stored top to bottom
+        let pixels = image
+            .rows()
+            .rev()
+            .flatten()
+            .map(|pixel| pixel.0.map(|channel| channel as f32 / 255.0))
+            .collect();
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// The luminance of each pixel, clamped to `0..1`, as
+    /// the output would be when saved.
+    fn luma(&self) -> Vec<f32> {
+        self.pixels
+            .iter()
+            .map(|[r, g, b, _]| 0.2126 * r + 0.7152 * g + 0.0722 * b)
+            .map(|luma| luma.clamp(0.0, 1.0))
+            .collect()
+    }
+}
+
+/// How similar two images are, overall and at each pixel.
+#[derive(Debug, Clone)]
+pub struct Ssim {
+    /// The mean similarity over the whole image.
+    pub score:  f32,
+    pub width:  u32,
+    pub height: u32,
+    /// The similarity around each pixel, row by row, bottom
+    /// to top.
+    pub map:    Vec<f32>,
+}
+
+impl Ssim {
+    /// Compares two images of the same size.
+    pub fn compare(a: &Image, b: &Image) -> Result<Ssim, String> {
+        if (a.width, a.height) != (b.width, b.height) {
+            return Err(format!(
+                "Can't compare a {}x{} image with a {}x{} one",
+                a.width, a.height, b.width, b.height
+            ));
+        }
+
+        let (x, y) = (a.luma(), b.luma());
+        let product = |p: &[f32], q: &[f32]| {
+            p.iter().zip(q).map(|(p, q)| p * q).collect::<Vec<_>>()
+        };
+        let blur = |values: &[f32]| blur(values, a.width, a.height);
+        let (mean_x, mean_y) = (blur(&x), blur(&y));
+        let (xx, yy, xy) = (
+            blur(&product(&x, &x)),
+            blur(&product(&y, &y)),
+            blur(&product(&x, &y)),
+        );
+
+        let map = (0..x.len())
+            .map(|i| {
+                let (mx, my) = (mean_x[i], mean_y[i]);
+                let var_x = xx[i] - mx * mx;
+                let var_y = yy[i] - my * my;
+                let cov = xy[i] - mx * my;
+                ((2.0 * mx * my + C1) * (2.0 * cov + C2))
+                    / ((mx * mx + my * my + C1) * (var_x + var_y + C2))
+            })
+            .collect::<Vec<_>>();
+        let score = map.iter().sum::<f32>() / map.len().max(1) as f32;
+
+        Ok(Ssim {
+            score,
+            width: a.width,
+            height: a.height,
+            map,
+        })
+    }
+
+    /// Writes the map as a png, black where the images
+    /// match and brighter where they differ, through
+    /// red and yellow to white.
+    pub fn write_heatmap(&self, path: &Path) -> Result<(), String> {
+        let mut buffer = ImageBuffer::new(self.width, self.height);
+        for (index, similarity) in self.map.iter().enumerate() {
+            let x = index as u32 % self.width;
+            // the map counts up from the bottom
+            let y = self.height - 1 - index as u32 / self.width;
+            let heat = (1.0 - similarity).clamp(0.0, 1.0);
+            let channel =
+                |from: f32| ((heat * 3.0 - from).clamp(0.0, 1.0) * 255.0) as u8;
+            let color = [channel(0.0), channel(1.0), channel(2.0)];
+            buffer.put_pixel(x, y, Rgb(color));
+        }
+        buffer
+            .save(path)
+            .map_err(|e| format!("Could not write `{}`: {}", path.display(), e))
+    }
+}
+
+/// Blurs values with the Gaussian window, one axis at a
+/// time. Edges are handled by renormalizing the window
+/// over the pixels inside the image.
+fn blur(values: &[f32], width: u32, height: u32) -> Vec<f32> {
+    let weights = (0..=2 * RADIUS)
+        .map(|i| {
+            let d = i as f32 - RADIUS as f32;
+            (-d * d / (2.0 * SIGMA * SIGMA)).exp()
+        })
+        .collect::<Vec<_>>();
+    let (width, height) = (width as usize, height as usize);
+
+    let pass = |values: &[f32], step: usize, length: usize| {
+        let mut out = vec![0.0; values.len()];
+        for (index, out) in out.iter_mut().enumerate() {
+            let at = index / step % length;
+            let (mut sum, mut total) = (0.0, 0.0);
+            for (i, weight) in weights.iter().enumerate() {
+                let offset = at as isize + i as isize - RADIUS as isize;
+                if offset < 0 || offset >= length as isize {
+                    continue;
+                }
+                let other = index - at * step + offset as usize * step;
+                sum += weight * values[other];
+                total += weight;
+            }
+            *out = sum / total;
+        }
+        out
+    };
+    let rows = pass(values, 1, width);
+    pass(&rows, width, height)
+}
@@ -0,0 +1,360 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{
+        BufRead,
+        BufReader,
+        Write,
+    },
+    net::{
+        TcpListener,
+        TcpStream,
+    },
+    path::Path,
+    rc::Rc,
+    sync::{
+        mpsc::{
+            self,
+            Receiver,
+            SyncSender,
+            TrySendError,
+        },
+        Arc,
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use glium::backend::Context;
+use serde_json::Value;
+
+use crate::{
+    lisp::BuildOptions,
+    reload::{
+        ShaderDir,
+        ShaderGraphWatcher,
+    },
+};
+
+/// What a host sends to its followers, one JSON object per
+/// line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncMessage {
+    /// The whole project, sent every time the host's graph
+    /// is rebuilt.
+    Sources {
+        lisp:    String,
+        shaders: BTreeMap<String, String>,
+    },
+    /// A uniform set on the host, see
+    /// [`crate::graph::ShaderGraph::set_param`].
+    Set {
+        node:    String,
+        uniform: String,
+        value:   f32,
+    },
+}
+
+impl SyncMessage {
+    pub fn to_json(&self) -> Value {
+        match self {
+            SyncMessage::Sources { lisp, shaders } => serde_json::json!({
+                "sources": { "graph": lisp, "shaders": shaders },
+            }),
+            SyncMessage::Set {
+                node,
+                uniform,
+                value,
+            } => serde_json::json!({
+                "set": { "node": node, "uniform": uniform, "value": value },
+            }),
+        }
+    }
+
+    pub fn from_json(json: &Value) -> Result<SyncMessage, String> {
+        let string = |value: &Value, key: &str| {
+            value[key]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| format!("Expected a string `{}`", key))
+        };
+
+        if let Some(sources) = json.get("sources") {
+            let shaders = sources["shaders"]
+                .as_object()
+                .ok_or("Expected an object `shaders`")?
+                .keys()
+                .map(|name| {
+                    string(&sources["shaders"], name)
+                        .map(|source| (name.clone(), source))
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(SyncMessage::Sources {
+                lisp: string(sources, "graph")?,
+                shaders,
+            })
+        } else if let Some(set) = json.get("set") {
+            Ok(SyncMessage::Set {
+                node:    string(set, "node")?,
+                uniform: string(set, "uniform")?,
+                value:   set["value"]
+                    .as_f64()
+                    .ok_or("Expected a number `value`")?
+                    as f32,
+            })
+        } else {
+            Err(format!("Unknown sync message `{}`", json))
+        }
+    }
+}
+
+/// How many messages can wait to be written to a follower
+/// before it is dropped, as too slow to keep up.
+const QUEUE: usize = 64;
+
+/// How long writing a message to a follower can take
+/// before it is dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Everything a follower that joins late needs to catch
+/// up, along with the followers to send new messages to.
+#[derive(Default)]
+struct HostState {
+    followers: Vec<SyncSender<Arc<str>>>,
+    sources:   Option<Arc<str>>,
+    params:    BTreeMap<(String, String), Arc<str>>,
+}
+
+/// Broadcasts a project to followers over TCP, so that
+/// several instances run the same piece as it is edited on
+/// the host. Followers that connect late are sent the
+/// latest sources and uniforms first. Each follower is
+/// written to by a thread of its own, so one that stalls
+/// never holds up the host, or the others.
+pub struct SyncHost {
+    state: Arc<Mutex<HostState>>,
+}
+
+impl SyncHost {
+    /// Accepts followers on `address`, e.g. `0.0.0.0:7778`.
+    pub fn listen(address: &str) -> Result<SyncHost, String> {
+        let listener = TcpListener::bind(address)
+            .map_err(|e| format!("Could not listen on `{}`: {}", address, e))?;
+
+        let state = Arc::new(Mutex::new(HostState::default()));
+        thread::spawn({
+            let state = state.clone();
+            move || {
+                for stream in listener.incoming().flatten() {
+                    // queued along with the follower, so nothing
+                    // sent in between is missed
+                    let (tx, rx) = mpsc::sync_channel(QUEUE);
+                    let catch_up = match state.lock() {
+                        Ok(mut state) => {
+                            state.followers.push(tx);
+                            let params = state.params.values().cloned();
+                            state
+                                .sources
+                                .iter()
+                                .cloned()
+                                .chain(params)
+                                .collect()
+                        },
+                        Err(_) => return,
+                    };
+                    thread::spawn(move || follow(stream, catch_up, rx));
+                }
+            }
+        });
+        Ok(SyncHost { state })
+    }
+
+    /// Sends a message to every follower, forgetting those
+    /// that have disconnected, or fallen too far behind.
+    pub fn send(&self, message: &SyncMessage) {
+        let line = Arc::<str>::from(message.to_json().to_string());
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+
+        match message {
+            SyncMessage::Sources { .. } => state.sources = Some(line.clone()),
+            SyncMessage::Set { node, uniform, .. } => {
+                let key = (node.clone(), uniform.clone());
+                state.params.insert(key, line.clone());
+            },
+        }
+        state
+            .followers
+            .retain(|tx| match tx.try_send(line.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    eprintln!("[warn] Dropped a follower that fell behind");
+                    false
+                },
+                Err(TrySendError::Disconnected(_)) => false,
+            });
+    }
+
+    /// Sends followers the project as it is on disk: its
+    /// graph, and every shader in its directory, as
+    /// followers may ask for shaders the graph doesn't
+    /// name.
+    pub fn send_project(
+        &self,
+        project: &Path,
+        graph: &Path,
+        allow_outside: bool,
+    ) {
+        let shader_dir =
+            ShaderDir::new_from_dir(project, allow_outside, || {
+                fs::read_to_string(graph).map_err(|e| {
+                    format!("Could not read `{}`: {}", graph.display(), e)
+                })
+            });
+        let shader_dir =
+            shader_dir.and_then(|mut dir| dir.read_all().map(|()| dir));
+        match shader_dir {
+            Ok(ShaderDir { lisp, shaders, .. }) => {
+                self.send(&SyncMessage::Sources { lisp, shaders })
+            },
+            Err(e) => {
+                eprintln!("[warn] Could not send project to followers: {}", e)
+            },
+        }
+    }
+}
+
+/// Writes to a follower what it needs to catch up, and then
+/// every message queued for it, until it disconnects, or a
+/// write takes longer than [`WRITE_TIMEOUT`].
+fn follow(
+    mut stream: TcpStream,
+    catch_up: Vec<Arc<str>>,
+    rx: Receiver<Arc<str>>,
+) {
+    let _ = stream.set_write_timeout(Some(WRITE_TIMEOUT));
+    let peer = stream.peer_addr().map(|peer| peer.to_string());
+    let peer = peer.unwrap_or_else(|_| "?".to_string());
+    let mut write = |line: &str| writeln!(stream, "{}", line).is_ok();
+    if !catch_up.iter().all(|line| write(line)) {
+        return;
+    }
+    eprintln!("[info] Follower {} joined", peer);
+    for line in rx.iter() {
+        if !write(&line) {
+            eprintln!("[warn] Follower {} left", peer);
+            return;
+        }
+    }
+}
+
+/// Receives a project from a [`SyncHost`].
+pub struct SyncFollower {
+    rx: Receiver<SyncMessage>,
+}
+
+impl SyncFollower {
+    pub fn connect(address: &str) -> Result<SyncFollower, String> {
+        let stream = TcpStream::connect(address).map_err(|e| {
+            format!("Could not connect to `{}`: {}", address, e)
+        })?;
+
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stream).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                let message = serde_json::from_str(&line)
+                    .map_err(|e| format!("{}", e))
+                    .and_then(|json| SyncMessage::from_json(&json));
+                match message {
+                    Ok(message) => {
+                        if tx.send(message).is_err() {
+                            return;
+                        }
+                    },
+                    Err(e) => eprintln!("[warn] Bad sync message: {}", e),
+                }
+            }
+            eprintln!("[warn] Lost connection to the sync host");
+        });
+        Ok(SyncFollower { rx })
+    }
+
+    /// Takes the next message that has been received, if
+    /// any.
+    pub fn next(&self) -> Option<SyncMessage> { self.rx.try_recv().ok() }
+
+    /// Waits for the host to send its sources, skipping
+    /// anything sent before them. Returns `None` if the
+    /// host disconnects first.
+    pub fn wait_for_sources(&self) -> Option<SyncMessage> {
+        self.rx
+            .iter()
+            .find(|message| matches!(message, SyncMessage::Sources { .. }))
+    }
+
+    /// Builds the first graph from the sources the host
+    /// sends, once it does, see
+    /// [`SyncFollower::wait_for_sources`].
+    pub fn watch(
+        &self,
+        context: &Rc<Context>,
+        project: &Path,
+        options: BuildOptions,
+    ) -> Result<ShaderGraphWatcher, String> {
+        eprintln!("[info] Waiting for the project from the host");
+        match self.wait_for_sources() {
+            Some(SyncMessage::Sources { lisp, shaders }) => {
+                ShaderGraphWatcher::from_sources(
+                    context, project, lisp, shaders, options,
+                )
+            },
+            _ => Err("The host disconnected".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn stalled_followers_dont_hold_up_the_host() {
+        let address = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap()
+            .to_string();
+        let host = SyncHost::listen(&address).unwrap();
+        // connects, but never reads
+        let _stalled = TcpStream::connect(&address).unwrap();
+        let follower = SyncFollower::connect(&address).unwrap();
+        while host.state.lock().unwrap().followers.len() < 2 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let sources = |lisp: String| SyncMessage::Sources {
+            lisp,
+            shaders: BTreeMap::new(),
+        };
+        let started = Instant::now();
+        for _ in 0..QUEUE * 2 {
+            host.send(&sources("x".repeat(1 << 16)));
+        }
+        host.send(&sources("last".to_string()));
+        assert!(started.elapsed() < WRITE_TIMEOUT);
+
+        let last = follower.rx.iter().find(|message| match message {
+            SyncMessage::Sources { lisp, .. } => lisp == "last",
+            _ => false,
+        });
+        assert!(last.is_some());
+    }
+}
@@ -0,0 +1,109 @@
+//! Small previews of what a project looks like, for project
+//! browsers, or for surveying a folder of sketches at a
+//! glance. A thumbnail is rendered from a graph of its own,
+//! run from the start up to a fixed time, so that it shows
+//! the same frame however long the project has been
+//! running, see [`Thumbnail`].
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use image::{
+    imageops,
+    RgbaImage,
+};
+
+use crate::{
+    graph::ShaderGraph,
+    sink::read_frame,
+};
+
+/// Where a project's thumbnail is kept, in its directory.
+pub const THUMBNAIL_FILE: &str = ".shadergarden/thumb.png";
+
+/// How a thumbnail is rendered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thumbnail {
+    /// The time shown, in seconds.
+    pub time: f32,
+    /// The frame rate the graph runs at up to then, which
+    /// matters to simulations.
+    pub fps:  f32,
+    /// The width or height, whichever is larger, of the
+    /// thumbnail. Smaller outputs aren't scaled up.
+    pub size: u32,
+}
+
+impl Default for Thumbnail {
+    fn default() -> Thumbnail {
+        Thumbnail {
+            time: 1.0,
+            fps:  30.0,
+            size: 256,
+        }
+    }
+}
+
+impl Thumbnail {
+    /// Runs a freshly built graph from the start up to the
+    /// time shown, at a fixed step, and reads back its
+    /// output, scaled down. Graphs with inputs can't be
+    /// rendered, as nothing feeds them.
+    pub fn render(&self, graph: &mut ShaderGraph) -> Result<RgbaImage, String> {
+        if !graph.get_inputs().is_empty() {
+            return Err(
+                "Can't render a thumbnail of a graph with inputs".to_string()
+            );
+        }
+        let output = match graph.get_outputs().as_slice() {
+            [output] => *output,
+            _ => {
+                return Err(
+                    "Can't render a thumbnail of a graph without exactly \
+                     one output"
+                        .to_string(),
+                )
+            },
+        };
+
+        let dt = 1.0 / self.fps;
+        let frames = (self.time * self.fps).round().max(0.0) as u32;
+        for frame in 0..frames {
+            graph.set_fixed_time(frame as f32 * dt, dt);
+            graph.forward(BTreeMap::new());
+        }
+        graph.set_fixed_time(frames as f32 * dt, dt);
+        let image = read_frame(graph.forward(BTreeMap::new())[&output]);
+
+        let (width, height) = image.dimensions();
+        let scale = (self.size as f32 / width.max(height) as f32).min(1.0);
+        let scaled = |size: u32| ((size as f32 * scale).round() as u32).max(1);
+        Ok(imageops::thumbnail(&image, scaled(width), scaled(height)))
+    }
+
+    /// Renders a thumbnail, see [`Thumbnail::render`], into
+    /// [`THUMBNAIL_FILE`] in a project, returning where.
+    pub fn save(
+        &self,
+        graph: &mut ShaderGraph,
+        project: &Path,
+    ) -> Result<PathBuf, String> {
+        let image = self.render(graph)?;
+        let path = project.join(THUMBNAIL_FILE);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| {
+                format!("Could not create `{}`: {}", dir.display(), e)
+            })?;
+        }
+        image.save(&path).map_err(|e| {
+            format!("Could not write `{}`: {}", path.display(), e)
+        })?;
+        Ok(path)
+    }
+}
@@ -0,0 +1,143 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{
+        BufWriter,
+        Write,
+    },
+    path::Path,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use crate::graph::{
+    NodeId,
+    ShaderGraph,
+};
+
+/// Reports the GPU time of each node, measured with
+/// [`ShaderGraph::set_profiling`]: as a table of averages
+/// printed every so often, and as a CSV with a row per node
+/// per frame, `frame,node,name,gpu_ms`.
+#[derive(Debug)]
+pub struct TimingLog {
+    /// How often the table is printed, if at all.
+    every:  Option<Duration>,
+    since:  Instant,
+    /// The total time of each node since the table was last
+    /// printed.
+    totals: BTreeMap<NodeId, Duration>,
+    frames: u32,
+    /// The last pass reported, as passes are measured a few
+    /// frames late and may be seen more than once.
+    last:   Option<i32>,
+    csv:    Option<BufWriter<File>>,
+}
+
+impl TimingLog {
+    pub fn new(every: Option<Duration>) -> TimingLog {
+        TimingLog {
+            every,
+            since: Instant::now(),
+            totals: BTreeMap::new(),
+            frames: 0,
+            last: None,
+            csv: None,
+        }
+    }
+
+    /// Also writes every pass to a CSV file.
+    pub fn csv_to(mut self, path: &Path) -> Result<TimingLog, String> {
+        let error = |e: std::io::Error| {
+            format!("Could not write timings to `{}`: {}", path.display(), e)
+        };
+        let mut csv = BufWriter::new(File::create(path).map_err(error)?);
+        writeln!(csv, "frame,node,name,gpu_ms").map_err(error)?;
+        self.csv = Some(csv);
+        Ok(self)
+    }
+
+    /// Reports the latest pass measured, if it's new.
+    pub fn record(&mut self, graph: &ShaderGraph) -> Result<(), String> {
+        let timings = match graph.timings() {
+            Some(timings) if self.last != Some(timings.frame) => timings,
+            _ => return Ok(()),
+        };
+        self.last = Some(timings.frame);
+
+        if let Some(csv) = &mut self.csv {
+            for (id, time) in timings.nodes.iter() {
+                writeln!(
+                    csv,
+                    "{},{},{},{:.4}",
+                    timings.frame,
+                    id.index(),
+                    name_of(graph, *id),
+                    time.as_secs_f64() * 1000.0,
+                )
+                .map_err(|e| format!("Could not write timings: {}", e))?;
+            }
+        }
+
+        let every = match self.every {
+            Some(every) => every,
+            None => return Ok(()),
+        };
+        for (id, time) in timings.nodes.iter() {
+            *self.totals.entry(*id).or_default() += *time;
+        }
+        self.frames += 1;
+        if self.since.elapsed() >= every {
+            self.print(graph);
+            self.totals.clear();
+            self.frames = 0;
+            self.since = Instant::now();
+        }
+        Ok(())
+    }
+
+    /// Prints the average time of each node per frame, the
+    /// slowest first.
+    fn print(&self, graph: &ShaderGraph) {
+        let mut rows = self
+            .totals
+            .iter()
+            .map(|(id, total)| (*id, *total / self.frames.max(1)))
+            .collect::<Vec<_>>();
+        rows.sort_by_key(|(_, time)| std::cmp::Reverse(*time));
+        let frame = rows.iter().map(|(_, time)| *time).sum::<Duration>();
+
+        eprintln!(
+            "[info] GPU time over {} frames: {:.2} ms per frame",
+            self.frames,
+            frame.as_secs_f64() * 1000.0,
+        );
+        for (id, time) in rows {
+            let share = time.as_secs_f64() / frame.as_secs_f64().max(1e-9);
+            eprintln!(
+                "  {:>8.3} ms  {:>5.1}%  {}",
+                time.as_secs_f64() * 1000.0,
+                share * 100.0,
+                graph.topology().describe(id),
+            );
+        }
+    }
+}
+
+impl Drop for TimingLog {
+    fn drop(&mut self) {
+        if let Some(csv) = &mut self.csv {
+            let _ = csv.flush();
+        }
+    }
+}
+
+/// A node's name, or its index if it has none.
+fn name_of(graph: &ShaderGraph, id: NodeId) -> String {
+    match graph.node_names().find(|(_, named)| *named == id) {
+        Some((name, _)) => name.to_string(),
+        None => format!("node {}", id.index()),
+    }
+}
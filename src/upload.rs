@@ -0,0 +1,307 @@
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    io::{
+        self,
+        Read,
+    },
+    process::{
+        Child,
+        Command,
+        Stdio,
+    },
+    sync::mpsc::{
+        self,
+        Receiver,
+        RecvTimeoutError,
+        TrySendError,
+    },
+    thread,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use glium::{
+    backend::Facade,
+    texture::{
+        pixel_buffer::PixelBuffer,
+        ClientFormat,
+        MipmapsOption,
+        RawImage2d,
+        UncompressedFloatFormat,
+    },
+    Texture2d,
+};
+
+/// A single RGB frame, stored row by row starting at the
+/// bottom of the image, as OpenGL expects.
+pub type Frame = Vec<(u8, u8, u8)>;
+
+/// A texture updated with frames produced on another
+/// thread, e.g. by a video decoder. Frames are copied into
+/// a pixel buffer and uploaded a few rows at a time, so
+/// that no more than `budget` bytes are transferred each
+/// frame. Large frames are spread over several frames
+/// instead of stalling the render thread, and the
+/// previous frame is shown until the next one is complete.
+pub struct StreamingTexture {
+    width:   u32,
+    height:  u32,
+    front:   Texture2d,
+    back:    Texture2d,
+    staging: PixelBuffer<(u8, u8, u8)>,
+    frames:  Receiver<Frame>,
+    /// The frame being uploaded, and its next row, if an
+    /// upload is in progress.
+    pending: Option<(Frame, u32)>,
+    budget:  usize,
+}
+
+impl StreamingTexture {
+    pub fn new<F: Facade>(
+        facade: &F,
+        width: u32,
+        height: u32,
+        frames: Receiver<Frame>,
+        budget: usize,
+    ) -> Result<StreamingTexture, String> {
+        // glium sets GL_UNPACK_ALIGNMENT to 1 on direct uploads
+        // but not on uploads from a pixel buffer, where rows of
+        // an odd width aren't 4-byte aligned, so start black
+        let texture = || {
+            let black = RawImage2d {
+                data: Cow::Owned(vec![0; (width * height * 3) as usize]),
+                width,
+                height,
+                format: ClientFormat::U8U8U8,
+            };
+            Texture2d::with_format(
+                facade,
+                black,
+                UncompressedFloatFormat::U8U8U8U8,
+                MipmapsOption::NoMipmap,
+            )
+            .map_err(|e| format!("Could not create texture: {}", e))
+        };
+
+        Ok(StreamingTexture {
+            width,
+            height,
+            front: texture()?,
+            back: texture()?,
+            staging: PixelBuffer::new_empty(facade, (width * height) as usize),
+            frames,
+            pending: None,
+            budget,
+        })
+    }
+
+    /// Continues the upload in progress, or starts
+    /// uploading the newest frame if there is none. Returns
+    /// the most recent complete frame.
+    pub fn update(&mut self) -> &Texture2d {
+        if self.pending.is_none() {
+            // skip frames that arrived while uploading
+            if let Some(frame) = self.frames.try_iter().last() {
+                self.start(frame);
+            }
+        }
+        self.upload(self.budget);
+        &self.front
+    }
+
+    /// Uploads the next frame whole, waiting for it if it
+    /// hasn't arrived, so that every frame is shown exactly
+    /// once and in order, e.g. for offline renders. A frame
+    /// partly uploaded by [`StreamingTexture::update`] is
+    /// the next one. Keeps the last frame once the frames
+    /// stop.
+    pub fn next_whole(&mut self) -> &Texture2d {
+        if self.pending.is_none() {
+            if let Ok(frame) = self.frames.recv() {
+                self.start(frame);
+            }
+        }
+        self.upload(usize::MAX);
+        &self.front
+    }
+
+    /// The most recent complete frame.
+    pub fn texture(&self) -> &Texture2d { &self.front }
+
+    fn start(&mut self, frame: Frame) {
+        if frame.len() == self.staging.len() {
+            self.pending = Some((frame, 0));
+        } else {
+            eprintln!("[warn] Dropped frame of the wrong size");
+        }
+    }
+
+    /// Copies up to `budget` bytes of the pending frame
+    /// into the pixel buffer, a whole number of rows,
+    /// and on into the back texture, which is shown
+    /// once it's complete.
+    fn upload(&mut self, budget: usize) {
+        let (frame, start) = match &self.pending {
+            Some((frame, start)) => (frame, *start),
+            None => return,
+        };
+        let end = next_rows(start, self.width, self.height, budget);
+        let range = (start * self.width) as usize..(end * self.width) as usize;
+        // unwrap: frames are checked to fill the buffer
+        let slice = self.staging.slice(range.clone()).unwrap();
+        slice.write(&frame[range]);
+        self.back.main_level().raw_upload_from_pixel_buffer(
+            slice,
+            0..self.width,
+            start..end,
+            0..1,
+        );
+
+        if end == self.height {
+            std::mem::swap(&mut self.front, &mut self.back);
+            self.pending = None;
+        } else if let Some((_, next)) = &mut self.pending {
+            *next = end;
+        }
+    }
+}
+
+/// The end of the rows of an RGB frame of a given size to
+/// upload next, from `start`, within `budget` bytes, and at
+/// least one row so that uploads always finish.
+fn next_rows(start: u32, width: u32, height: u32, budget: usize) -> u32 {
+    let row_bytes = width as usize * 3;
+    let rows = (budget / row_bytes).clamp(1, height as usize);
+    (start + rows as u32).min(height)
+}
+
+/// Runs an `ffmpeg` command that writes raw `rgb24` frames
+/// of the given size to stdout, bottom row first, and reads
+/// them on a background thread. If `interval` is given,
+/// frames are sent no faster than that, e.g. to play a
+/// video at its own speed. Only the newest frame is kept
+/// when the receiver falls behind, to keep latency low.
+/// Kill the child to stop.
+pub fn spawn_frames(
+    command: &mut Command,
+    width: u32,
+    height: u32,
+    interval: Option<Duration>,
+) -> Result<(Child, Receiver<Frame>), String> {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Could not start ffmpeg: {}", e))?;
+    let mut stdout = child.stdout.take().unwrap();
+
+    let (sender, receiver) = mpsc::sync_channel(1);
+    thread::spawn(move || {
+        let mut bytes = vec![0; (width * height * 3) as usize];
+        let mut next = Instant::now();
+        // ffmpeg logs why it stopped, if it wasn't killed
+        while let Ok(frame) = read_frame(&mut stdout, &mut bytes) {
+            if let Some(interval) = interval {
+                next += interval;
+                thread::sleep(next.saturating_duration_since(Instant::now()));
+            }
+            if let Err(TrySendError::Disconnected(_)) = sender.try_send(frame) {
+                return;
+            }
+        }
+    });
+
+    Ok((child, receiver))
+}
+
+/// Reads a raw `rgb24` frame of as many bytes as `bytes`
+/// holds, which is read into.
+pub fn read_frame(
+    reader: &mut impl Read,
+    bytes: &mut [u8],
+) -> io::Result<Frame> {
+    reader.read_exact(bytes)?;
+    Ok(bytes.chunks_exact(3).map(|p| (p[0], p[1], p[2])).collect())
+}
+
+/// Holds every frame back by `delay` before passing it on,
+/// e.g. to line a camera up with slower inputs. As with
+/// [`spawn_frames`], frames are dropped rather than queued
+/// when the receiver falls behind. Stops once the frames
+/// do.
+pub fn delay_frames(
+    frames: Receiver<Frame>,
+    delay: Duration,
+) -> Receiver<Frame> {
+    let (sender, receiver) = mpsc::sync_channel(1);
+    thread::spawn(move || {
+        let mut held: VecDeque<(Instant, Frame)> = VecDeque::new();
+        loop {
+            let wait = match held.front() {
+                Some((due, _)) => due.saturating_duration_since(Instant::now()),
+                None => delay,
+            };
+            match frames.recv_timeout(wait) {
+                Ok(frame) => held.push_back((Instant::now() + delay, frame)),
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+            let now = Instant::now();
+            while held.front().is_some_and(|(due, _)| *due <= now) {
+                // unwrap: there is a frame at the front
+                let (_, frame) = held.pop_front().unwrap();
+                if let Err(TrySendError::Disconnected(_)) =
+                    sender.try_send(frame)
+                {
+                    return;
+                }
+            }
+        }
+    });
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The row ranges uploaded for a whole frame.
+    fn slices(width: u32, height: u32, budget: usize) -> Vec<(u32, u32)> {
+        let mut slices = vec![];
+        let mut start = 0;
+        while start < height {
+            let end = next_rows(start, width, height, budget);
+            slices.push((start, end));
+            start = end;
+        }
+        slices
+    }
+
+    #[test]
+    fn uploads_whole_rows_within_budget() {
+        // 1919 pixels of 3 bytes, 5757 to a row
+        assert_eq!(slices(1919, 5, 3 * 5757 + 1), vec![(0, 3), (3, 5)]);
+    }
+
+    #[test]
+    fn uploads_a_row_at_least() {
+        assert_eq!(slices(1919, 3, 1), vec![(0, 1), (1, 2), (2, 3)]);
+        assert_eq!(slices(1919, 3, 0).len(), 3);
+    }
+
+    #[test]
+    fn uploads_everything_at_once_without_a_budget() {
+        assert_eq!(slices(1080, 1919, usize::MAX), vec![(0, 1919)]);
+    }
+
+    #[test]
+    fn reads_odd_widths_without_padding() {
+        let bytes = (0..3 * 3 * 2).collect::<Vec<u8>>();
+        let mut buffer = vec![0; bytes.len()];
+        let frame = read_frame(&mut &bytes[..], &mut buffer).unwrap();
+        assert_eq!(frame.len(), 6);
+        assert_eq!(frame[3], (9, 10, 11));
+    }
+}
@@ -0,0 +1,208 @@
+//! Program binaries, which let a graph that was compiled
+//! once be linked again without compiling its shaders, see
+//! [`crate::graph::Bundle`]. Binaries only work with the
+//! driver that made them, so programs are compiled from
+//! source whenever one is turned down. They can also be
+//! kept on disk between runs, see [`set_cache_dir`].
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    convert::TryInto,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    rc::Rc,
+};
+
+use glium::{
+    backend::Context,
+    program::Binary,
+    Program,
+};
+use sha1::{
+    Digest,
+    Sha1,
+};
+
+/// Identifies a program for a driver, see [`key`].
+pub type Key = [u8; 20];
+
+/// A linked program as the driver stores it.
+#[derive(Debug, Clone)]
+pub struct ProgramBinary {
+    /// Identifies the shaders linked, see [`key`].
+    pub key:     Key,
+    pub format:  u32,
+    pub content: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Cache {
+    /// Whether to keep the binaries of programs compiled.
+    recording: bool,
+    binaries:  HashMap<Key, ProgramBinary>,
+    /// Where binaries are kept between runs, see
+    /// [`set_cache_dir`].
+    dir:       Option<PathBuf>,
+}
+
+thread_local! {
+    static CACHE: RefCell<Cache> = RefCell::new(Cache::default());
+}
+
+/// Identifies a pair of shaders linked by the driver of a
+/// context, which is told by its vendor, renderer, and
+/// version, with a SHA-1 of them all, which stays the same
+/// from build to build, unlike the standard library's
+/// hashers.
+fn key(context: &Context, vertex: &str, fragment: &str) -> Key {
+    let mut hasher = Sha1::new();
+    for part in [
+        vertex,
+        fragment,
+        context.get_opengl_vendor_string(),
+        context.get_opengl_renderer_string(),
+        context.get_opengl_version_string(),
+    ] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.finalize().into()
+}
+
+/// Starts or stops keeping the binaries of programs
+/// compiled on this thread, to be taken with
+/// [`take_binaries`].
+pub fn record_binaries(recording: bool) {
+    CACHE.with(|cache| cache.borrow_mut().recording = recording);
+}
+
+/// Links programs from these binaries instead of compiling
+/// them, until they are taken with [`take_binaries`].
+pub fn load_binaries(binaries: Vec<ProgramBinary>) {
+    CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        for binary in binaries {
+            cache.binaries.insert(binary.key, binary);
+        }
+    });
+}
+
+/// Keeps the binaries of programs compiled on this thread
+/// in a directory, e.g. [`default_cache_dir`], and links
+/// programs from them in later runs instead of compiling
+/// them, which makes starting and rebuilding large graphs
+/// much faster. Binaries are kept apart for each driver,
+/// and are compiled again when a driver turns them down.
+/// Pass `None` to stop.
+pub fn set_cache_dir(dir: Option<PathBuf>) {
+    CACHE.with(|cache| cache.borrow_mut().dir = dir);
+}
+
+/// `program-cache` in the user's cache directory, see
+/// [`crate::reload::cache_dir`].
+pub fn default_cache_dir() -> Option<PathBuf> {
+    Some(crate::reload::cache_dir()?.join("program-cache"))
+}
+
+/// Where the binary of a program is cached.
+fn cache_path(dir: &Path, key: &Key) -> PathBuf {
+    let name: String = key.iter().map(|byte| format!("{:02x}", byte)).collect();
+    dir.join(format!("{}.bin", name))
+}
+
+/// Reads a binary written by [`write_cached`], if any.
+fn read_cached(path: &Path, key: Key) -> Option<ProgramBinary> {
+    let bytes = fs::read(path).ok()?;
+    let format = u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?);
+    Some(ProgramBinary {
+        key,
+        format,
+        content: bytes[4..].to_vec(),
+    })
+}
+
+/// Writes a binary as its format, as a little-endian `u32`,
+/// then its content. The file is written next to where it
+/// goes and then moved there, so that runs at the same time
+/// never read half a binary.
+fn write_cached(path: &Path, binary: &ProgramBinary) -> Result<(), String> {
+    let error = |e: std::io::Error| {
+        format!("Could not cache `{}`: {}", path.display(), e)
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(error)?;
+    }
+    let mut bytes = binary.format.to_le_bytes().to_vec();
+    bytes.extend_from_slice(&binary.content);
+    let partial = path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(&partial, bytes).map_err(error)?;
+    fs::rename(&partial, path).map_err(error)
+}
+
+/// Takes every binary recorded or loaded, ordered by key.
+pub fn take_binaries() -> Vec<ProgramBinary> {
+    let binaries =
+        CACHE.with(|cache| std::mem::take(&mut cache.borrow_mut().binaries));
+    let mut binaries = binaries.into_values().collect::<Vec<_>>();
+    binaries.sort_by_key(|binary| binary.key);
+    binaries
+}
+
+/// Links a program from a loaded or cached binary, if there
+/// is one the driver accepts, or compiles it.
+pub fn program(
+    context: &Rc<Context>,
+    vertex: &str,
+    fragment: &str,
+) -> Result<Program, String> {
+    let key = key(context, vertex, fragment);
+    let (recording, binary, dir) = CACHE.with(|cache| {
+        let cache = cache.borrow();
+        let binary = cache.binaries.get(&key).cloned();
+        (cache.recording, binary, cache.dir.clone())
+    });
+    let cached = dir.map(|dir| cache_path(&dir, &key));
+    let binary = binary.or_else(|| read_cached(cached.as_ref()?, key));
+    if let Some(binary) = binary {
+        let linked = Program::new(context, Binary {
+            format:  binary.format,
+            content: binary.content.clone(),
+        });
+        if let Ok(program) = linked {
+            // a bundle also needs binaries found on disk
+            if recording {
+                CACHE.with(|cache| {
+                    cache.borrow_mut().binaries.insert(key, binary)
+                });
+            }
+            return Ok(program);
+        }
+    }
+
+    let program = Program::from_source(context, vertex, fragment, None)
+        .map_err(|e| format!("{}", e))?;
+    if !recording && cached.is_none() {
+        return Ok(program);
+    }
+    let binary = match program.get_binary() {
+        Ok(binary) => ProgramBinary {
+            key,
+            format: binary.format,
+            content: binary.content,
+        },
+        Err(_) => return Ok(program),
+    };
+    if let Some(path) = cached {
+        if let Err(e) = write_cached(&path, &binary) {
+            eprintln!("[warn] {}", e);
+        }
+    }
+    if recording {
+        CACHE.with(|cache| cache.borrow_mut().binaries.insert(key, binary));
+    }
+    Ok(program)
+}
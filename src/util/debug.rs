@@ -0,0 +1,129 @@
+//! Debug groups and object labels, so that tools like
+//! RenderDoc, apitrace, and Nsight show which node each
+//! draw call and texture belongs to. glium doesn't expose
+//! these functions, so they are loaded separately with
+//! [`load_with`]. Until then, or if the driver doesn't
+//! support `KHR_debug`, every function here does nothing.
+
+use std::{
+    ffi::c_void,
+    os::raw::{
+        c_char,
+        c_int,
+        c_uint,
+    },
+    sync::atomic::{
+        AtomicPtr,
+        Ordering,
+    },
+};
+
+use glium::{
+    backend::Context,
+    GlObject,
+    Handle,
+    Program,
+    Texture2d,
+};
+
+const DEBUG_SOURCE_APPLICATION: c_uint = 0x824A;
+const TEXTURE: c_uint = 0x1702;
+const PROGRAM: c_uint = 0x82E2;
+
+type PushDebugGroup =
+    unsafe extern "system" fn(c_uint, c_uint, c_int, *const c_char);
+type PopDebugGroup = unsafe extern "system" fn();
+type ObjectLabel =
+    unsafe extern "system" fn(c_uint, c_uint, c_int, *const c_char);
+
+static PUSH_DEBUG_GROUP: AtomicPtr<c_void> =
+    AtomicPtr::new(std::ptr::null_mut());
+static POP_DEBUG_GROUP: AtomicPtr<c_void> =
+    AtomicPtr::new(std::ptr::null_mut());
+static OBJECT_LABEL: AtomicPtr<c_void> = AtomicPtr::new(std::ptr::null_mut());
+
+/// Loads the debug functions, e.g. with
+/// `display.gl_window().get_proc_address`.
+pub fn load_with<F: FnMut(&str) -> *const c_void>(mut load: F) {
+    let mut store = |pointer: &AtomicPtr<c_void>, name| {
+        pointer.store(load(name) as *mut c_void, Ordering::SeqCst)
+    };
+    store(&PUSH_DEBUG_GROUP, "glPushDebugGroup");
+    store(&POP_DEBUG_GROUP, "glPopDebugGroup");
+    store(&OBJECT_LABEL, "glObjectLabel");
+}
+
+/// Whether the debug functions have been loaded.
+pub fn is_loaded() -> bool {
+    !PUSH_DEBUG_GROUP.load(Ordering::SeqCst).is_null()
+        && !POP_DEBUG_GROUP.load(Ordering::SeqCst).is_null()
+        && !OBJECT_LABEL.load(Ordering::SeqCst).is_null()
+}
+
+/// Starts a group of commands, up to the matching
+/// [`pop_group`].
+pub fn push_group(context: &Context, message: &str) {
+    if !is_loaded() {
+        return;
+    }
+
+    // safety: the pointer was loaded for this function, and the
+    // message length is passed explicitly
+    unsafe {
+        let push: PushDebugGroup =
+            std::mem::transmute(PUSH_DEBUG_GROUP.load(Ordering::SeqCst));
+        context.exec_in_context(|| {
+            push(
+                DEBUG_SOURCE_APPLICATION,
+                0,
+                message.len() as c_int,
+                message.as_ptr() as *const c_char,
+            )
+        });
+    }
+}
+
+pub fn pop_group(context: &Context) {
+    if !is_loaded() {
+        return;
+    }
+
+    // safety: the pointer was loaded for this function
+    unsafe {
+        let pop: PopDebugGroup =
+            std::mem::transmute(POP_DEBUG_GROUP.load(Ordering::SeqCst));
+        context.exec_in_context(|| pop());
+    }
+}
+
+fn label(context: &Context, kind: c_uint, id: c_uint, name: &str) {
+    if !is_loaded() {
+        return;
+    }
+
+    // safety: the pointer was loaded for this function, and the
+    // name length is passed explicitly
+    unsafe {
+        let label: ObjectLabel =
+            std::mem::transmute(OBJECT_LABEL.load(Ordering::SeqCst));
+        context.exec_in_context(|| {
+            label(
+                kind,
+                id,
+                name.len() as c_int,
+                name.as_ptr() as *const c_char,
+            )
+        });
+    }
+}
+
+pub fn label_texture(context: &Context, texture: &Texture2d, name: &str) {
+    label(context, TEXTURE, texture.get_id(), name);
+}
+
+pub fn label_program(context: &Context, program: &Program, name: &str) {
+    // programs made with ARB extensions can't be labeled
+    if let Handle::Id(id) = program.get_id() {
+        label(context, PROGRAM, id, name);
+    }
+}
@@ -8,15 +8,29 @@ use glium::{
         Facade,
     },
     glutin::{
-        dpi::LogicalSize,
-        event_loop::EventLoop,
-        window::WindowBuilder,
+        dpi::{
+            LogicalSize,
+            PhysicalSize,
+        },
+        event_loop::{
+            EventLoop,
+            EventLoopWindowTarget,
+        },
+        window::{
+            Fullscreen,
+            WindowBuilder,
+        },
+        Api,
         ContextBuilder,
+        GlProfile,
+        GlRequest,
     },
     implement_vertex,
     index::NoIndices,
     texture::{
+        ClientFormat,
         MipmapsOption,
+        RawImage2d,
         UncompressedFloatFormat,
     },
     uniform,
@@ -26,15 +40,26 @@ use glium::{
     },
     Display,
     Frame,
+    HeadlessRenderer,
     Program,
+    Rect,
     Surface,
     Texture2d,
     VertexBuffer,
 };
 
+use crate::capabilities::{
+    Capabilities,
+    GlVersion,
+};
 #[cfg(feature = "ffmpeg")]
 use crate::input::FrameStream;
 
+pub mod binary;
+pub mod debug;
+pub mod font;
+pub mod timer;
+
 #[derive(Copy, Clone)]
 pub struct Vertex {
     position:   [f32; 2],
@@ -69,14 +94,137 @@ impl RectStrip {
     }
 }
 
+/// How the window is titled, decorated, and shown.
+#[derive(Debug, Clone)]
+pub struct WindowOptions {
+    pub title:         String,
+    pub decorations:   bool,
+    /// Lets the desktop show through wherever the alpha of
+    /// what is drawn is below `1`.
+    pub transparent:   bool,
+    pub always_on_top: bool,
+    /// Lets the mouse through to whatever is below the
+    /// window. Only supported on X11.
+    pub click_through: bool,
+    /// Covers the monitor the window opens on.
+    pub fullscreen:    bool,
+    /// Covers the primary monitor, behind the desktop
+    /// icons. Only supported on X11.
+    pub wallpaper:     bool,
+    /// Waits for the display to refresh before presenting
+    /// each frame.
+    pub vsync:         bool,
+    /// Hidden windows can still be rendered to, e.g. to
+    /// build a graph without showing anything.
+    pub visible:       bool,
+    /// The version of GL to ask for. If it is unavailable,
+    /// the latest supported version is used instead.
+    pub gl:            Option<GlVersion>,
+}
+
+impl WindowOptions {
+    /// A regular window with the given title.
+    pub fn new(title: String) -> WindowOptions {
+        WindowOptions {
+            title,
+            decorations: true,
+            transparent: false,
+            always_on_top: false,
+            click_through: false,
+            fullscreen: false,
+            wallpaper: false,
+            vsync: false,
+            visible: true,
+            gl: None,
+        }
+    }
+}
+
 /// Makes a window on which to display something.
 /// Note that width and height are specified according to
 /// [`LogicalSize`]. Fore more fine-grained control, use the
 /// individual components.
 pub fn create_window(title: String, width: f64, height: f64) -> WindowBuilder {
-    WindowBuilder::new()
+    create_window_with(&WindowOptions::new(title), width, height)
+}
+
+/// Like [`create_window`], but with non-default
+/// [`WindowOptions`].
+pub fn create_window_with(
+    options: &WindowOptions,
+    width: f64,
+    height: f64,
+) -> WindowBuilder {
+    let wb = WindowBuilder::new()
         .with_inner_size(LogicalSize::new(width, height))
+        .with_title(options.title.clone())
+        .with_decorations(options.decorations && !options.wallpaper)
+        .with_transparent(options.transparent)
+        .with_always_on_top(options.always_on_top && !options.wallpaper)
+        .with_visible(options.visible);
+    let wb = if options.fullscreen && !options.wallpaper {
+        wb.with_fullscreen(Some(Fullscreen::Borderless(None)))
+    } else {
+        wb
+    };
+
+    if !options.wallpaper {
+        return wb;
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        use glium::glutin::platform::unix::{
+            WindowBuilderExtUnix,
+            XWindowType,
+        };
+        // window managers keep desktop windows below everything
+        wb.with_x11_window_type(vec![XWindowType::Desktop])
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        eprintln!(
+            "[warn] Wallpaper mode is only supported on X11, opening a \
+             borderless window instead"
+        );
+        wb
+    }
+}
+
+/// Opens another window on the same event loop as
+/// `display`, sharing its textures, so that it can show any
+/// of them, e.g. a node of a graph built in `display`. The
+/// window covers the monitor with the given index, if any.
+/// It never waits for vsync, so as not to hold up the main
+/// window.
+pub fn create_shared<T>(
+    target: &EventLoopWindowTarget<T>,
+    display: &Display,
+    title: &str,
+    (width, height): (u32, u32),
+    fullscreen: Option<usize>,
+) -> Result<Display, String> {
+    let mut wb = WindowBuilder::new()
         .with_title(title)
+        .with_inner_size(PhysicalSize::new(width, height));
+    if let Some(index) = fullscreen {
+        let monitor =
+            target.available_monitors().nth(index).ok_or_else(|| {
+                format!("There is no monitor {} to show `{}` on", index, title)
+            })?;
+        wb = wb.with_fullscreen(Some(Fullscreen::Borderless(Some(monitor))));
+    }
+
+    let gl_window = display.gl_window();
+    let shared = ContextBuilder::new()
+        .with_shared_lists(gl_window.context())
+        .with_vsync(false)
+        .build_windowed(wb, target)
+        .map_err(|e| format!("Could not open `{}`: {}", title, e))?;
+    drop(gl_window);
+    Display::from_gl_window(shared)
+        .map_err(|e| format!("Could not open `{}`: {}", title, e))
 }
 
 /// Sets up a window on which to draw.
@@ -86,15 +234,275 @@ pub fn create(
     title: String,
     width: f64,
     height: f64,
+) -> (EventLoop<()>, Display) {
+    create_with(&WindowOptions::new(title), width, height)
+}
+
+/// Like [`create`], but with non-default [`WindowOptions`].
+pub fn create_with(
+    options: &WindowOptions,
+    width: f64,
+    height: f64,
 ) -> (EventLoop<()>, Display) {
     let event_loop = EventLoop::new();
-    let wb = create_window(title, width, height);
-    let cb = ContextBuilder::new();
-    let display =
-        Display::new(wb, cb, &event_loop).expect("Unable to create display");
+    let mut wb = create_window_with(options, width, height);
+    if options.wallpaper {
+        if let Some(monitor) = event_loop.primary_monitor() {
+            wb = wb
+                .with_inner_size(monitor.size())
+                .with_position(monitor.position());
+        }
+    }
+    let cb = || ContextBuilder::new().with_vsync(options.vsync);
+    let display = match options.gl {
+        Some(version) => {
+            let api = if version.es {
+                Api::OpenGlEs
+            } else {
+                Api::OpenGl
+            };
+            let number = (version.major, version.minor);
+            let mut requested = cb().with_gl(GlRequest::Specific(api, number));
+            // profiles only exist from OpenGL 3.2
+            if !version.es && number >= (3, 2) {
+                requested = requested.with_gl_profile(GlProfile::Core);
+            }
+            Display::new(wb.clone(), requested, &event_loop).or_else(|e| {
+                eprintln!(
+                    "[warn] {} is unavailable ({}), falling back to the \
+                     latest supported version",
+                    version, e
+                );
+                Display::new(wb, cb(), &event_loop)
+            })
+        },
+        None => Display::new(wb, cb(), &event_loop),
+    }
+    .expect("Unable to create display");
+
+    report(display.get_context());
+    if options.click_through {
+        if let Err(e) = set_click_through(&display) {
+            eprintln!("[warn] {}", e);
+        }
+    }
     (event_loop, display)
 }
 
+/// Lets the mouse through a window, by giving it an empty
+/// input shape with XFixes, which is loaded when needed,
+/// like the rest of X11.
+#[cfg(target_os = "linux")]
+fn set_click_through(display: &Display) -> Result<(), String> {
+    use std::os::raw::{
+        c_char,
+        c_int,
+        c_ulong,
+        c_void,
+    };
+
+    use glium::glutin::platform::unix::WindowExtUnix;
+
+    type CreateRegion =
+        unsafe extern "C" fn(*mut c_void, *mut c_void, c_int) -> c_ulong;
+    type SetWindowShapeRegion = unsafe extern "C" fn(
+        *mut c_void,
+        c_ulong,
+        c_int,
+        c_int,
+        c_int,
+        c_ulong,
+    );
+    type DestroyRegion = unsafe extern "C" fn(*mut c_void, c_ulong);
+    type Flush = unsafe extern "C" fn(*mut c_void) -> c_int;
+    // from X11/extensions/shape.h
+    const SHAPE_INPUT: c_int = 2;
+
+    let gl_window = display.gl_window();
+    let window = gl_window.window();
+    let xlib = (window.xlib_display(), window.xlib_window());
+    let (xdisplay, xwindow) = match xlib {
+        (Some(xdisplay), Some(xwindow)) => (xdisplay, xwindow),
+        _ => return Err("Click-through windows need X11".to_string()),
+    };
+
+    // names end in a nul, as C expects
+    let symbol = |library: &str, name: &str| {
+        // never closed, as with the libraries winit loads
+        let found = unsafe {
+            let library = library.as_ptr() as *const c_char;
+            let handle = libc::dlopen(library, libc::RTLD_LAZY);
+            if handle.is_null() {
+                std::ptr::null_mut()
+            } else {
+                libc::dlsym(handle, name.as_ptr() as *const c_char)
+            }
+        };
+        if found.is_null() {
+            Err(format!(
+                "Could not find `{}` in `{}`, for a click-through window",
+                name.trim_end_matches('\0'),
+                library.trim_end_matches('\0')
+            ))
+        } else {
+            Ok(found)
+        }
+    };
+    let xfixes = "libXfixes.so.3\0";
+    let create = symbol(xfixes, "XFixesCreateRegion\0")?;
+    let set = symbol(xfixes, "XFixesSetWindowShapeRegion\0")?;
+    let destroy = symbol(xfixes, "XFixesDestroyRegion\0")?;
+    let flush = symbol("libX11.so.6\0", "XFlush\0")?;
+
+    // safety: the signatures are those of Xfixes.h and
+    // Xlib.h, and the display and window outlive the calls
+    unsafe {
+        let create = std::mem::transmute::<*mut c_void, CreateRegion>(create);
+        let set = std::mem::transmute::<*mut c_void, SetWindowShapeRegion>(set);
+        let destroy =
+            std::mem::transmute::<*mut c_void, DestroyRegion>(destroy);
+        let flush = std::mem::transmute::<*mut c_void, Flush>(flush);
+
+        let empty = create(xdisplay, std::ptr::null_mut(), 0);
+        set(xdisplay, xwindow, SHAPE_INPUT, 0, 0, empty);
+        destroy(xdisplay, empty);
+        flush(xdisplay);
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_click_through(_display: &Display) -> Result<(), String> {
+    Err("Click-through windows are only supported on X11".to_string())
+}
+
+/// Creates an offscreen context rendered in software by
+/// Mesa's OSMesa, which needs neither a GPU nor a display
+/// server, e.g. for checking graphs in CI. Errors if
+/// OSMesa is not installed.
+#[cfg(target_os = "linux")]
+pub fn create_software(
+    width: u32,
+    height: u32,
+) -> Result<HeadlessRenderer, String> {
+    use glium::glutin::{
+        dpi::PhysicalSize,
+        platform::unix::HeadlessContextExt,
+    };
+    let context = ContextBuilder::new()
+        .build_osmesa(PhysicalSize::new(width, height))
+        .map_err(|e| format!("Could not create a software context: {}", e))?;
+    let renderer = HeadlessRenderer::new(context)
+        .map_err(|e| format!("Could not create a software context: {}", e))?;
+    report(renderer.get_context());
+    Ok(renderer)
+}
+
+/// Software contexts are only supported through OSMesa.
+#[cfg(not(target_os = "linux"))]
+pub fn create_software(
+    _width: u32,
+    _height: u32,
+) -> Result<HeadlessRenderer, String> {
+    Err("Software rendering is only supported on Linux".to_string())
+}
+
+/// An offscreen context, see [`create_headless`], and the
+/// event loop a hardware one was created on, kept open for
+/// as long as it is.
+pub struct Headless {
+    pub renderer: HeadlessRenderer,
+    _events:      Option<EventLoop<()>>,
+}
+
+/// Creates an offscreen context on the GPU, without a
+/// window, where there is a display server to create it
+/// through, or else in software, see [`create_software`].
+/// Errors if neither can be created.
+pub fn create_headless(width: u32, height: u32) -> Result<Headless, String> {
+    let hardware = match has_display() {
+        true => create_hardware(width, height),
+        false => Err("There is no display server".to_string()),
+    };
+    match hardware {
+        Ok(headless) => Ok(headless),
+        Err(e) => {
+            eprintln!("[warn] {}, so rendering in software", e);
+            Ok(Headless {
+                renderer: create_software(width, height)?,
+                _events:  None,
+            })
+        },
+    }
+}
+
+/// Creates an offscreen context on the GPU, as a pbuffer,
+/// or, where those aren't supported, a surfaceless one.
+fn create_hardware(width: u32, height: u32) -> Result<Headless, String> {
+    let error = |e: &dyn std::fmt::Display| {
+        format!("Could not create a hardware context: {}", e)
+    };
+    // winit panics, rather than erroring, when it can't
+    // connect to the display server
+    let events = std::panic::catch_unwind(new_event_loop)
+        .map_err(|_| error(&"the display server refused"))?;
+    let size = PhysicalSize::new(width, height);
+    let context = ContextBuilder::new().build_headless(&events, size);
+    #[cfg(target_os = "linux")]
+    let context = context.or_else(|_| {
+        use glium::glutin::platform::unix::HeadlessContextExt;
+        ContextBuilder::new().build_surfaceless(&events)
+    });
+    let context = context.map_err(|e| error(&e))?;
+    let renderer = HeadlessRenderer::new(context).map_err(|e| error(&e))?;
+    report(renderer.get_context());
+    Ok(Headless {
+        renderer,
+        _events: Some(events),
+    })
+}
+
+/// An event loop, on whichever thread the caller is on,
+/// where the platform allows it.
+fn new_event_loop() -> EventLoop<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use glium::glutin::platform::unix::EventLoopExtUnix;
+        EventLoop::new_any_thread()
+    }
+    #[cfg(target_os = "windows")]
+    {
+        use glium::glutin::platform::windows::EventLoopExtWindows;
+        EventLoop::new_any_thread()
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    EventLoop::new()
+}
+
+/// Whether a window can be opened at all, i.e. whether
+/// there is a display server to open it on.
+pub fn has_display() -> bool {
+    if !cfg!(target_os = "linux") {
+        return true;
+    }
+    ["DISPLAY", "WAYLAND_DISPLAY"]
+        .iter()
+        .any(|var| std::env::var_os(var).is_some())
+}
+
+/// Logs what a new context supports.
+fn report(context: &Rc<Context>) {
+    let caps = Capabilities::detect(context);
+    eprintln!("[info] Using {} ({})", caps.version, caps.renderer);
+    if caps.is_software() {
+        eprintln!("[warn] Rendering in software, expect graphs to run slowly");
+    }
+    let missing = caps.missing();
+    if !missing.is_empty() {
+        eprintln!("[warn] Unavailable: {}", missing.join(", "));
+    }
+}
+
 /// Writes an output texture to the entire window.
 pub fn texture(display: &Display, target: &mut Frame, texture: &Texture2d) {
     let rect_strip = RectStrip::new(display);
@@ -112,7 +520,7 @@ pub fn texture(display: &Display, target: &mut Frame, texture: &Texture2d) {
     target
         .draw(
             &rect_strip.buffer,
-            &rect_strip.indices,
+            rect_strip.indices,
             &program,
             &uniform! {
                 tex: Sampler::new(texture)
@@ -139,12 +547,106 @@ pub fn default_buffer(
     .unwrap()
 }
 
+/// Uploads a single-channel float texture, e.g. for data
+/// tables. `values` is read row by row, bottom to top.
+pub fn float_texture(
+    context: &Rc<Context>,
+    width: u32,
+    height: u32,
+    values: &[f32],
+) -> Result<Texture2d, String> {
+    let raw = RawImage2d {
+        data: std::borrow::Cow::Borrowed(values),
+        width,
+        height,
+        format: ClientFormat::F32,
+    };
+
+    Texture2d::with_format(
+        context,
+        raw,
+        UncompressedFloatFormat::F32,
+        MipmapsOption::NoMipmap,
+    )
+    .map_err(|e| format!("Could not upload float texture: {}", e))
+}
+
+/// Creates an empty four-channel float texture, for
+/// intermediate results that need full precision.
+pub fn float_buffer(
+    context: &Rc<Context>,
+    width: u32,
+    height: u32,
+) -> Result<Texture2d, String> {
+    Texture2d::empty_with_format(
+        context,
+        UncompressedFloatFormat::F32F32F32F32,
+        MipmapsOption::NoMipmap,
+        width,
+        height,
+    )
+    .map_err(|e| format!("Could not create float texture: {}", e))
+}
+
+/// Reads a texture back to the CPU as floats, regardless of
+/// its internal format. Pixels are returned row by row,
+/// bottom to top.
+pub fn read_texture(texture: &Texture2d) -> Vec<[f32; 4]> {
+    let rect = Rect {
+        left:   0,
+        bottom: 0,
+        width:  texture.width(),
+        height: texture.height(),
+    };
+
+    let rows: Vec<Vec<(f32, f32, f32, f32)>> = texture
+        .main_level()
+        .first_layer()
+        .into_image(None)
+        .unwrap()
+        .raw_read(&rect);
+
+    rows.into_iter()
+        .flatten()
+        .map(|(r, g, b, a)| [r, g, b, a])
+        .collect()
+}
+
+/// Reads a single pixel of a texture back to the CPU as
+/// floats, counting from the bottom left corner.
+pub fn read_pixel(texture: &Texture2d, x: u32, y: u32) -> [f32; 4] {
+    let rect = Rect {
+        left:   x,
+        bottom: y,
+        width:  1,
+        height: 1,
+    };
+
+    let rows: Vec<Vec<(f32, f32, f32, f32)>> = texture
+        .main_level()
+        .first_layer()
+        .into_image(None)
+        .unwrap()
+        .raw_read(&rect);
+
+    let (r, g, b, a) = rows[0][0];
+    [r, g, b, a]
+}
+
 pub fn compile_shader(
     context: &Rc<Context>,
     source: &str,
 ) -> Result<Program, String> {
-    Program::from_source(context, include_str!("./texture.vert"), source, None)
-        .map_err(|e| format!("{}", e))
+    binary::program(context, include_str!("./texture.vert"), source)
+}
+
+/// Compiles a fragment shader against the built-in point
+/// cloud vertex shader, see [`crate::points`].
+pub fn compile_point_shader(
+    context: &Rc<Context>,
+    source: &str,
+) -> Result<Program, String> {
+    binary::program(context, include_str!("./points.vert"), source)
 }
 
 #[cfg(feature = "ffmpeg")]
@@ -153,6 +655,7 @@ pub fn input_textures(
     inputs: &[PathBuf],
     width: u32,
     height: u32,
+    budget: usize,
 ) -> Vec<FrameStream> {
     // build a table of textures
     let mut input_textures = vec![];
@@ -162,7 +665,7 @@ pub fn input_textures(
             texture_path.to_string_lossy()
         );
         input_textures.push(
-            FrameStream::new(&texture_path, width, height, display)
+            FrameStream::new(&texture_path, width, height, budget, display)
                 .expect("Couldn't open frame source"),
         );
     }
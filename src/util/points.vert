@@ -0,0 +1,31 @@
+#version 140
+
+in vec3 position;
+in vec3 color;
+out vec3 point_color;
+out vec3 point_position;
+
+uniform float u_time;
+uniform vec2 u_resolution;
+
+void main() {
+    // slowly orbit the cloud around the y axis
+    float angle = u_time * 0.25;
+    mat3 orbit = mat3(
+        cos(angle), 0., -sin(angle),
+        0.,         1., 0.,
+        sin(angle), 0., cos(angle));
+    vec3 view = orbit * position;
+
+    // simple perspective camera looking down -z
+    float aspect = u_resolution.x / u_resolution.y;
+    float depth = 3. - view.z;
+    gl_Position = vec4(
+        view.x * 2. / aspect,
+        view.y * 2.,
+        depth / 10.,
+        depth);
+
+    point_color = color;
+    point_position = position;
+}
@@ -0,0 +1,152 @@
+//! GPU timer queries, to measure how long the GPU spends on
+//! each node, see
+//! [`crate::graph::ShaderGraph::set_profiling`]. glium only
+//! exposes these through draw parameters, which every node
+//! would need to be passed, so they are loaded separately
+//! with [`load_with`], as in [`super::debug`]. Until then,
+//! or if the driver doesn't support `ARB_timer_query`, no
+//! query can be started.
+
+use std::{
+    ffi::c_void,
+    os::raw::{
+        c_int,
+        c_uint,
+    },
+    sync::atomic::{
+        AtomicPtr,
+        Ordering,
+    },
+    time::Duration,
+};
+
+use glium::backend::Context;
+
+const TIME_ELAPSED: c_uint = 0x88BF;
+const QUERY_RESULT: c_uint = 0x8866;
+const QUERY_RESULT_AVAILABLE: c_uint = 0x8867;
+
+type GenQueries = unsafe extern "system" fn(c_int, *mut c_uint);
+type DeleteQueries = unsafe extern "system" fn(c_int, *const c_uint);
+type BeginQuery = unsafe extern "system" fn(c_uint, c_uint);
+type EndQuery = unsafe extern "system" fn(c_uint);
+type GetQueryObjectuiv = unsafe extern "system" fn(c_uint, c_uint, *mut c_uint);
+type GetQueryObjectui64v = unsafe extern "system" fn(c_uint, c_uint, *mut u64);
+
+static GEN_QUERIES: AtomicPtr<c_void> = AtomicPtr::new(std::ptr::null_mut());
+static DELETE_QUERIES: AtomicPtr<c_void> = AtomicPtr::new(std::ptr::null_mut());
+static BEGIN_QUERY: AtomicPtr<c_void> = AtomicPtr::new(std::ptr::null_mut());
+static END_QUERY: AtomicPtr<c_void> = AtomicPtr::new(std::ptr::null_mut());
+static GET_QUERY_OBJECTUIV: AtomicPtr<c_void> =
+    AtomicPtr::new(std::ptr::null_mut());
+static GET_QUERY_OBJECTUI64V: AtomicPtr<c_void> =
+    AtomicPtr::new(std::ptr::null_mut());
+
+/// Loads the timer query functions, e.g. with
+/// `display.gl_window().get_proc_address`.
+pub fn load_with<F: FnMut(&str) -> *const c_void>(mut load: F) {
+    let mut store = |pointer: &AtomicPtr<c_void>, name| {
+        pointer.store(load(name) as *mut c_void, Ordering::SeqCst)
+    };
+    store(&GEN_QUERIES, "glGenQueries");
+    store(&DELETE_QUERIES, "glDeleteQueries");
+    store(&BEGIN_QUERY, "glBeginQuery");
+    store(&END_QUERY, "glEndQuery");
+    store(&GET_QUERY_OBJECTUIV, "glGetQueryObjectuiv");
+    store(&GET_QUERY_OBJECTUI64V, "glGetQueryObjectui64v");
+}
+
+/// Whether the timer query functions have been loaded.
+pub fn is_loaded() -> bool {
+    [
+        &GEN_QUERIES,
+        &DELETE_QUERIES,
+        &BEGIN_QUERY,
+        &END_QUERY,
+        &GET_QUERY_OBJECTUIV,
+        &GET_QUERY_OBJECTUI64V,
+    ]
+    .iter()
+    .all(|pointer| !pointer.load(Ordering::SeqCst).is_null())
+}
+
+/// A query measuring the GPU time of the commands between
+/// [`Query::begin`] and [`Query::end`]. Only one query can
+/// be running at a time.
+#[derive(Debug)]
+pub struct Query(c_uint);
+
+impl Query {
+    /// Creates a query, if the functions are loaded.
+    pub fn new(context: &Context) -> Option<Query> {
+        if !is_loaded() {
+            return None;
+        }
+
+        let mut id = 0;
+        // safety: the pointer was loaded for this function, and
+        // one id is written
+        unsafe {
+            let gen: GenQueries =
+                std::mem::transmute(GEN_QUERIES.load(Ordering::SeqCst));
+            context.exec_in_context(|| gen(1, &mut id));
+        }
+        Some(Query(id))
+    }
+
+    pub fn begin(&self, context: &Context) {
+        // safety: queries are only made once the functions
+        // are loaded
+        unsafe {
+            let begin: BeginQuery =
+                std::mem::transmute(BEGIN_QUERY.load(Ordering::SeqCst));
+            context.exec_in_context(|| begin(TIME_ELAPSED, self.0));
+        }
+    }
+
+    pub fn end(&self, context: &Context) {
+        // safety: as in `begin`
+        unsafe {
+            let end: EndQuery =
+                std::mem::transmute(END_QUERY.load(Ordering::SeqCst));
+            context.exec_in_context(|| end(TIME_ELAPSED));
+        }
+    }
+
+    /// The time measured, once the GPU has finished the
+    /// commands, without waiting for it unless `wait`.
+    pub fn result(&self, context: &Context, wait: bool) -> Option<Duration> {
+        // safety: as in `begin`, and one value is written
+        unsafe {
+            if !wait {
+                let available: GetQueryObjectuiv = std::mem::transmute(
+                    GET_QUERY_OBJECTUIV.load(Ordering::SeqCst),
+                );
+                let mut ready = 0;
+                context.exec_in_context(|| {
+                    available(self.0, QUERY_RESULT_AVAILABLE, &mut ready)
+                });
+                if ready == 0 {
+                    return None;
+                }
+            }
+
+            let result: GetQueryObjectui64v = std::mem::transmute(
+                GET_QUERY_OBJECTUI64V.load(Ordering::SeqCst),
+            );
+            let mut nanos = 0;
+            context
+                .exec_in_context(|| result(self.0, QUERY_RESULT, &mut nanos));
+            Some(Duration::from_nanos(nanos))
+        }
+    }
+
+    pub fn delete(self, context: &Context) {
+        // safety: as in `begin`, and one id is read
+        unsafe {
+            let delete: DeleteQueries =
+                std::mem::transmute(DELETE_QUERIES.load(Ordering::SeqCst));
+            context.exec_in_context(|| delete(1, &self.0));
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    path::Path,
+    process::{
+        Child,
+        Command,
+    },
+    sync::mpsc::Receiver,
+    time::Duration,
+};
+
+use crate::upload::{
+    spawn_frames,
+    Frame,
+};
+
+/// How a video file is played, see [`Video::open`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Playback {
+    /// Whether to start over at the end, rather than hold
+    /// the last frame.
+    pub looping: bool,
+    /// How fast to play, e.g. `0.5` for half speed.
+    pub rate:    f64,
+    /// Where to start, in seconds.
+    pub start:   f64,
+    /// Frames shown per second, whatever the video's own
+    /// frame rate.
+    pub fps:     u32,
+}
+
+impl Default for Playback {
+    fn default() -> Playback {
+        Playback {
+            looping: true,
+            rate:    1.0,
+            start:   0.0,
+            fps:     30,
+        }
+    }
+}
+
+/// A video file decoded by an `ffmpeg` process, which must
+/// be on the path, and played in real time. Playback stops
+/// once this is dropped.
+pub struct Video {
+    child: Child,
+}
+
+impl Video {
+    /// Starts playing a video, scaled to `width` by
+    /// `height`. Returns the video and its frames.
+    pub fn open(
+        path: &Path,
+        width: u32,
+        height: u32,
+        playback: &Playback,
+    ) -> Result<(Video, Receiver<Frame>), String> {
+        if !path.is_file() {
+            return Err(format!("Could not find video `{}`", path.display()));
+        }
+        if playback.rate <= 0.0 || playback.fps == 0 {
+            return Err("Videos must play at a positive rate".to_string());
+        }
+
+        let mut command = Command::new("ffmpeg");
+        command.args(["-loglevel", "error", "-nostdin"]);
+        if playback.looping {
+            command.args(["-stream_loop", "-1"]);
+        }
+        let filters = format!(
+            "setpts=PTS/{},fps={},scale={}:{},vflip",
+            playback.rate, playback.fps, width, height
+        );
+        command
+            .args(["-ss", &playback.start.to_string(), "-i"])
+            .arg(path)
+            .args(["-an", "-vf", &filters])
+            .args(["-pix_fmt", "rgb24", "-f", "rawvideo", "-"]);
+
+        let interval = Duration::from_secs_f64(1.0 / playback.fps as f64);
+        let (child, frames) =
+            spawn_frames(&mut command, width, height, Some(interval))?;
+        Ok((Video { child }, frames))
+    }
+
+    /// Reads the size of a video with `ffprobe`.
+    pub fn probe(path: &Path) -> Result<(u32, u32), String> {
+        let output = Command::new("ffprobe")
+            .args(["-v", "error", "-select_streams", "v:0"])
+            .args(["-show_entries", "stream=width,height", "-of", "csv=p=0"])
+            .arg(path)
+            .output()
+            .map_err(|e| format!("Could not start ffprobe: {}", e))?;
+        let size = String::from_utf8_lossy(&output.stdout);
+        let invalid =
+            || format!("Could not read the size of `{}`", path.display());
+        let (width, height) =
+            size.trim().split_once(',').ok_or_else(invalid)?;
+        Ok((
+            width.parse().map_err(|_| invalid())?,
+            height.parse().map_err(|_| invalid())?,
+        ))
+    }
+}
+
+impl Drop for Video {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}